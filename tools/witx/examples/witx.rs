@@ -1,10 +1,20 @@
 use anyhow::{anyhow, bail, Result};
-use std::fs::File;
+use std::alloc::{GlobalAlloc, Layout as AllocLayout, System};
+use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
 use structopt::{clap::AppSettings, StructOpt};
-use witx::{load, phases, Document, Documentation};
+use witx::codegen::{self, Config, Registry};
+use witx::codes::{self, ErrorCode};
+use witx::diagnostic::Style;
+use witx::timings::Timer;
+use witx::{
+    load, phases, DocIndex, DocTemplate, Document, Documentation, Layout, LoadOptions, Target,
+    Type, WasmType, WitxError,
+};
 
 /// Validate and process witx files
 #[derive(StructOpt, Debug)]
@@ -20,6 +30,29 @@ struct Args {
     #[structopt(short = "v", long = "verbose")]
     verbose: bool,
 
+    /// Directory to look for the files of `use` directives in, when they
+    /// aren't relative to the root file. May be given more than once, to
+    /// search in order
+    #[structopt(
+        short = "I",
+        long = "include",
+        number_of_values = 1,
+        value_name = "DIR",
+        parse(from_os_str),
+        global = true
+    )]
+    include: Vec<PathBuf>,
+
+    /// Flag to enable for `(@witx cfg ...)` blocks. May be given more than
+    /// once
+    #[structopt(long = "cfg", number_of_values = 1, value_name = "FLAG", global = true)]
+    cfg: Vec<String>,
+
+    /// Don't color diagnostics, even on a terminal. Colors are also left out
+    /// when the NO_COLOR environment variable is set
+    #[structopt(long = "no-color", global = true)]
+    no_color: bool,
+
     #[structopt(subcommand)]
     cmd: Command,
 }
@@ -28,10 +61,13 @@ struct Args {
 enum Command {
     /// Output documentation
     Docs {
-        /// Path to root of witx document
+        /// Path to root of witx document, or a directory of witx documents
+        /// to document each on its own page, with an `index.md` linking to
+        /// them
         #[structopt(number_of_values = 1, value_name = "INPUT", parse(from_os_str))]
         input: Vec<PathBuf>,
-        /// Path to generated documentation in Markdown format
+        /// Path to generated documentation in Markdown format, or the
+        /// directory to generate it in if INPUT is a directory
         #[structopt(
             short = "o",
             long = "output",
@@ -39,14 +75,38 @@ enum Command {
             parse(from_os_str)
         )]
         output: Option<PathBuf>,
+        /// Template for text before the documentation
+        #[structopt(long = "header", value_name = "TEMPLATE", parse(from_os_str))]
+        header: Option<PathBuf>,
+        /// Template for text after the documentation
+        #[structopt(long = "footer", value_name = "TEMPLATE", parse(from_os_str))]
+        footer: Option<PathBuf>,
+        /// Template replacing the documentation of each type
+        #[structopt(long = "type-template", value_name = "TEMPLATE", parse(from_os_str))]
+        type_template: Option<PathBuf>,
+        /// Template replacing the documentation of each function
+        #[structopt(long = "func-template", value_name = "TEMPLATE", parse(from_os_str))]
+        func_template: Option<PathBuf>,
+        /// Documentation format: Markdown, JSON, or a man page per module
+        /// written to the OUTPUT directory
+        #[structopt(
+            long = "format",
+            default_value = "md",
+            possible_values = &["md", "json", "man"]
+        )]
+        format: String,
     },
+    /// Output a man page for this tool
+    Man,
     /// Update documentation in WASI repository to reflect witx specs
     RepoDocs,
+    /// List the phases of the WASI repository, with their directories
+    Phases,
     /// Examine differences between interfaces
     Polyfill {
         /// Path to root of witx document
         #[structopt(
-            required = true,
+            required_unless = "phases",
             number_of_values = 1,
             value_name = "INPUT",
             parse(from_os_str)
@@ -54,81 +114,910 @@ enum Command {
         input: Vec<PathBuf>,
         /// Path to root of witx document describing interface to polyfill
         #[structopt(
-            required = true,
+            required_unless = "phases",
             number_of_values = 1,
             value_name = "OLDER_INTERFACE",
             parse(from_os_str)
         )]
         older_interface: Vec<PathBuf>,
+        /// Phases of the WASI repository to compare instead of INPUT and
+        /// OLDER_INTERFACE, such as `snapshot..ephemeral`
+        #[structopt(long = "phases", value_name = "OLD..NEW")]
+        phases: Option<String>,
         /// Module to examine (use newname=oldname syntax if name is different
-        /// between new and old interfaces)
+        /// between new and old interfaces). When both interfaces are
+        /// directories of snapshot files, modules are paired by file name and
+        /// only exceptions need mapping. Otherwise, modules are paired by
+        /// their names and functions when no mapping is given
         #[structopt(
             short = "m",
             long = "module_mapping",
-            required = true,
             number_of_values = 1,
             value_name = "NEWNAME=OLDNAME",
             parse(try_from_str = parse_module_mapping)
         )]
         module_mapping: Vec<(String, String)>,
+        /// Handle type renamed between the old and new interfaces
+        #[structopt(
+            long = "handle_alias",
+            number_of_values = 1,
+            value_name = "NEWNAME=OLDNAME",
+            parse(try_from_str = parse_module_mapping)
+        )]
+        handle_alias: Vec<(String, String)>,
+        /// Compare types by their definitions alone, treating types renamed
+        /// between the interfaces as the same
+        #[structopt(long = "ignore_type_names")]
+        ignore_type_names: bool,
+        /// Target the older interface is laid out for. When the targets
+        /// differ, pointer-sized types are compared by their size on each
+        #[structopt(long = "old_target")]
+        old_target: Option<Target>,
+        /// Target the newer interface is laid out for
+        #[structopt(long = "new_target")]
+        new_target: Option<Target>,
+        /// Output a migration guide instead of a summary
+        #[structopt(long = "guide")]
+        guide: bool,
+        /// Output an HTML page instead of a summary
+        #[structopt(long = "html")]
+        html: bool,
+        /// Fail if the interfaces are incompatible, other than by the
+        /// findings accepted in the --baseline file
+        #[structopt(long = "strict")]
+        strict: bool,
+        /// File of accepted incompatibilities, for --strict
+        #[structopt(long = "baseline", value_name = "FILE", parse(from_os_str))]
+        baseline: Option<PathBuf>,
+        /// Write every incompatibility found to FILE, to accept them all
+        #[structopt(long = "write_baseline", value_name = "FILE", parse(from_os_str))]
+        write_baseline: Option<PathBuf>,
+    },
+    /// Output a changelog of the differences between interfaces
+    #[structopt(alias = "diff")]
+    Changelog {
+        /// Path to root of witx document
+        #[structopt(
+            required_unless = "phases",
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// Path to root of witx document describing the previous interface
+        #[structopt(
+            required_unless = "phases",
+            number_of_values = 1,
+            value_name = "OLDER_INTERFACE",
+            parse(from_os_str)
+        )]
+        older_interface: Vec<PathBuf>,
+        /// Phases of the WASI repository to compare instead of INPUT and
+        /// OLDER_INTERFACE, such as `snapshot..ephemeral`
+        #[structopt(long = "phases", value_name = "OLD..NEW")]
+        phases: Option<String>,
+        /// Modules to compare under different names (use newname=oldname
+        /// syntax)
+        #[structopt(
+            short = "m",
+            long = "module_mapping",
+            number_of_values = 1,
+            value_name = "NEWNAME=OLDNAME",
+            parse(try_from_str = parse_module_mapping)
+        )]
+        module_mapping: Vec<(String, String)>,
+        /// Compare types by their definitions alone, treating types renamed
+        /// between the interfaces as the same
+        #[structopt(long = "ignore_type_names")]
+        ignore_type_names: bool,
+        /// Only output whether the changes are a major, minor or patch
+        /// version increment
+        #[structopt(long = "semver")]
+        semver: bool,
+        /// Output an HTML page with the old and new definitions side by side
+        #[structopt(long = "html")]
+        html: bool,
+    },
+    /// Print the core wasm signature of every function and the memory layout
+    /// of every type
+    Abi {
+        /// Path to root of witx document
+        #[structopt(
+            required = true,
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// Target to compute signatures and layouts for (wasm32 or wasm64)
+        #[structopt(long = "target", default_value = "wasm32")]
+        target: Target,
+    },
+    /// Output stable hashes of a document, its types, modules and functions
+    Fingerprint {
+        /// Path to root of witx document
+        #[structopt(
+            required = true,
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// Only output the hash of the whole document
+        #[structopt(long = "document")]
+        document: bool,
+    },
+    /// Write a lockfile of the fingerprints of every type, module and
+    /// function, for verify-lock
+    Lock {
+        /// Path to root of witx document
+        #[structopt(
+            required = true,
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// Path to write the lockfile to, instead of stdout
+        #[structopt(
+            short = "o",
+            long = "output",
+            value_name = "OUTPUT",
+            parse(from_os_str)
+        )]
+        output: Option<PathBuf>,
+    },
+    /// Fail if any type, module or function has changed since the lockfile
+    /// was written
+    VerifyLock {
+        /// Path to root of witx document
+        #[structopt(
+            required = true,
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// Lockfile written by the lock subcommand
+        #[structopt(long = "lockfile", value_name = "FILE", parse(from_os_str))]
+        lockfile: PathBuf,
+    },
+    /// Convert a document to a WIT package, with a note on each thing WIT
+    /// can't say as witx does
+    ToWit {
+        /// Path to root of witx document
+        #[structopt(
+            required = true,
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// Name of the package, like `wasi:nn`
+        #[structopt(long = "package", value_name = "NAME")]
+        package: String,
+        /// Output only the modules of a world of this name, and the types
+        /// they use
+        #[structopt(long = "world", value_name = "NAME")]
+        world: Option<String>,
+        /// Module the world imports. With neither imports nor exports, the
+        /// world imports every module
+        #[structopt(long = "import", value_name = "MODULE", requires = "world")]
+        imports: Vec<String>,
+        /// Module the world exports
+        #[structopt(long = "export", value_name = "MODULE", requires = "world")]
+        exports: Vec<String>,
+    },
+    /// Convert the interfaces of a WIT package to a witx document
+    FromWit {
+        /// Path to a WIT file
+        #[structopt(value_name = "INPUT", parse(from_os_str))]
+        input: PathBuf,
+    },
+    /// Look up a type, module or function in each of several documents,
+    /// with its fingerprint in each
+    Query {
+        /// Name of a type or module, or MODULE::FUNC for a function
+        #[structopt(value_name = "ITEM")]
+        item: String,
+        /// A document to search and the root of it, which may be a
+        /// directory of witx files
+        #[structopt(
+            short = "r",
+            long = "root",
+            required = true,
+            number_of_values = 1,
+            value_name = "NAME=PATH",
+            parse(try_from_str = parse_root)
+        )]
+        roots: Vec<(String, PathBuf)>,
+    },
+    /// Output the dependency graph between types and functions
+    Graph {
+        /// Path to root of witx document
+        #[structopt(
+            required = true,
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// Graph language to output
+        #[structopt(
+            long = "format",
+            default_value = "dot",
+            possible_values = &["dot", "mermaid"]
+        )]
+        format: String,
+        /// List the named types which no function uses instead
+        #[structopt(long = "unused")]
+        unused: bool,
+    },
+    /// Output a matrix of the optional features each implementation supports
+    Matrix {
+        /// Path to root of witx document
+        #[structopt(
+            required = true,
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// An implementation and its supported capabilities (use
+        /// name=capability,... syntax)
+        #[structopt(
+            short = "i",
+            long = "implementation",
+            number_of_values = 1,
+            value_name = "NAME=CAPABILITIES"
+        )]
+        implementations: Vec<witx::capabilities::Implementation>,
+    },
+    /// Explain an error code, or list every code with a summary
+    Explain {
+        /// Code from an error report, such as `WITX0003`
+        #[structopt(value_name = "CODE")]
+        code: Option<ErrorCode>,
     },
+    /// Generate language bindings
+    Bindgen {
+        /// Path to root of witx document
+        #[structopt(
+            required = true,
+            number_of_values = 1,
+            value_name = "INPUT",
+            parse(from_os_str)
+        )]
+        input: Vec<PathBuf>,
+        /// Backend to generate bindings with: a language (rust-guest,
+        /// wasmtime-host, wasmer-host, c, cpp, assemblyscript, swift,
+        /// conformance, webnn, kotlin, grpc, protobuf, flatbuffers,
+        /// openvino-stub, onnx-stub or benchmark) or another registered
+        /// backend, as listed by `backends`
+        #[structopt(short = "l", long = "language")]
+        language: String,
+        /// Value of an option of the backend, as NAME=VALUE
+        #[structopt(
+            long = "backend-option",
+            number_of_values = 1,
+            parse(try_from_str = parse_backend_option)
+        )]
+        backend_options: Vec<(String, String)>,
+        /// Directory to write generated files to
+        #[structopt(
+            short = "o",
+            long = "out-dir",
+            value_name = "OUT_DIR",
+            parse(from_os_str)
+        )]
+        out_dir: PathBuf,
+        /// Name of the generated crate (rust-guest, wasmtime-host, wasmer-host,
+        /// conformance, grpc, openvino-stub, onnx-stub, benchmark)
+        #[structopt(long = "crate-name")]
+        crate_name: Option<String>,
+        /// Include guard of the generated C header (c, cpp)
+        #[structopt(long = "header-guard")]
+        header_guard: Option<String>,
+        /// Namespace of generated declarations (c uses it as a prefix, cpp as
+        /// the namespace and C prefix, kotlin as the package, grpc and
+        /// protobuf as the protobuf package, flatbuffers as the namespace)
+        #[structopt(long = "namespace")]
+        namespace: Option<String>,
+        /// Also generate mock implementations of each module (wasmtime-host,
+        /// wasmer-host)
+        #[structopt(long = "mocks")]
+        mocks: bool,
+        /// Also generate tracing wrappers of each module (wasmtime-host,
+        /// wasmer-host)
+        #[structopt(long = "tracing")]
+        tracing: bool,
+        /// Also generate wrappers enforcing resource limits (wasmtime-host,
+        /// wasmer-host)
+        #[structopt(long = "limits")]
+        limits: bool,
+        /// Generate `#![no_std]` bindings (rust-guest)
+        #[structopt(long = "no-std")]
+        no_std: bool,
+        /// Only generate the raw ABI layer, without safe wrappers (rust-guest)
+        #[structopt(long = "raw-only")]
+        raw_only: bool,
+        /// Extra trait to derive on every generated type (rust-guest,
+        /// wasmtime-host, wasmer-host)
+        #[structopt(long = "derive", number_of_values = 1)]
+        derives: Vec<String>,
+        /// Extra outer attribute, without `#[...]`, on every generated type
+        /// (rust-guest, wasmtime-host, wasmer-host)
+        #[structopt(long = "attribute", number_of_values = 1)]
+        attributes: Vec<String>,
+        /// Implement serde's `Serialize` and `Deserialize` for generated
+        /// types (rust-guest, wasmtime-host, wasmer-host)
+        #[structopt(long = "serde")]
+        serde: bool,
+        /// Implement proptest's `Arbitrary` for generated types, behind a
+        /// `proptest` feature (wasmtime-host, wasmer-host)
+        #[structopt(long = "proptest")]
+        proptest: bool,
+        /// How C++ wrappers report errors: exceptions or expected (cpp)
+        #[structopt(long = "cpp-errors", default_value = "exceptions")]
+        cpp_errors: codegen::CppErrors,
+        /// Report the time and memory each phase of loading the input and
+        /// generating the bindings takes, and what it processed, on stderr
+        #[structopt(long = "timings")]
+        timings: bool,
+    },
+    /// List the backends bindgen generates bindings with, and their options
+    Backends,
+}
+
+/// The backends of `bindgen`. A driver with backends of its own registers
+/// them here.
+fn registry() -> Registry {
+    Registry::builtin()
+}
+
+/// The allocator of the CLI, counting the bytes it allocates for
+/// `--timings`.
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: AllocLayout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: AllocLayout) {
+        System.dealloc(ptr, layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: AllocLayout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size.saturating_sub(layout.size()), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// How every subcommand renders diagnostics, set once the arguments are
+/// parsed.
+static STYLE: OnceLock<Style> = OnceLock::new();
+
+fn style() -> Style {
+    STYLE.get().copied().unwrap_or_default()
+}
+
+/// Report an error and exit.
+fn fail(message: &dyn std::fmt::Display) -> ! {
+    eprintln!("{}", style().error(message));
+    process::exit(1)
 }
 
 pub fn main() {
     let args = Args::from_args();
     pretty_env_logger::init();
+    STYLE.get_or_init(|| Style::detect(args.no_color));
     let verbose = args.verbose;
+    let options = args
+        .include
+        .iter()
+        .fold(LoadOptions::new(), |o, d| o.include(d));
+    let options = args.cfg.iter().fold(options, |o, f| o.cfg(f));
 
     match args.cmd {
-        Command::Docs { input, output } => {
-            let doc = load_witx(&input, "input", verbose);
+        Command::Docs {
+            input,
+            output,
+            header,
+            footer,
+            type_template,
+            func_template,
+            format,
+        } => {
+            if format == "man" {
+                write_man_pages(&input, output.as_deref(), &options, verbose)
+                    .unwrap_or_else(|e| fail(&format!("failed to write man pages: {}", e)));
+                return;
+            }
+            let read = |path: Option<PathBuf>| {
+                path.map(|p| {
+                    fs::read_to_string(&p).unwrap_or_else(|e| {
+                        fail(&format!("failed to read template {:?}: {}", p, e))
+                    })
+                })
+            };
+            let template = DocTemplate {
+                header: read(header),
+                footer: read(footer),
+                typename: read(type_template),
+                func: read(func_template),
+            };
+            if let [dir] = &input[..] {
+                if dir.is_dir() {
+                    let output = output
+                        .unwrap_or_else(|| fail(&"documenting a directory requires --output"));
+                    write_docs_dir(dir, &output, &template, &options, verbose)
+                        .unwrap_or_else(|e| fail(&format!("failed to write docs: {}", e)));
+                    return;
+                }
+            }
+            let doc = load_witx(&input, "input", &options, verbose);
+            let md = match format.as_str() {
+                "json" => doc.to_json(),
+                _ => template.render(&doc),
+            };
             if let Some(output) = output {
-                write_docs(&doc, output)
+                fs::write(&output, md).expect("write output file");
             } else {
-                println!("{}", doc.to_md())
+                println!("{}", md)
             }
         }
+        Command::Man => print!("{}", cli_man_page()),
         Command::RepoDocs => {
             for phase in &[
                 phases::snapshot().unwrap(),
                 phases::ephemeral().unwrap(),
                 phases::old::snapshot_0().unwrap(),
             ] {
-                let doc = load(&phase).expect("parse phase");
-                write_docs(&doc, phases::docs_path(&phase));
+                let doc = load(phase).expect("parse phase");
+                write_docs(&doc, phases::docs_path(phase));
             }
         }
+        Command::Phases => match phases::available() {
+            Ok(available) => {
+                for phase in available {
+                    println!("{} {}", phase.name, phase.path.display());
+                }
+            }
+            Err(e) => fail(&e),
+        },
         Command::Polyfill {
             input,
             older_interface,
+            phases,
             module_mapping,
+            handle_alias,
+            ignore_type_names,
+            old_target,
+            new_target,
+            guide,
+            html,
+            strict,
+            baseline,
+            write_baseline,
         } => {
             use std::{collections::HashMap, iter::FromIterator};
-            use witx::polyfill::Polyfill;
+            use witx::baseline::Baseline;
+            use witx::polyfill::{infer_module_mapping, Polyfill};
+            use witx::{Id, RepContext};
 
-            let doc = load_witx(&input, "input", verbose);
-            let older_doc = load_witx(&older_interface, "older_interface", verbose);
-            let module_mapping = HashMap::from_iter(module_mapping.into_iter());
-            let polyfill = match Polyfill::new(&doc, &older_doc, &module_mapping) {
+            let module_mapping = HashMap::from_iter(module_mapping);
+            let mut cx = handle_alias
+                .iter()
+                .fold(RepContext::new(), |cx, (new, old)| {
+                    cx.handle_alias(Id::new(old), Id::new(new))
+                });
+            if ignore_type_names {
+                cx = cx.ignore_type_names();
+            }
+            if old_target.is_some() || new_target.is_some() {
+                cx = cx.targets(
+                    old_target.unwrap_or_default(),
+                    new_target.unwrap_or_default(),
+                );
+            }
+            // Phases are compared as directories of snapshot files.
+            let (input, older_interface) = match phases {
+                Some(range) => {
+                    let (old, new) = phase_range(&range);
+                    (vec![new.path], vec![old.path])
+                }
+                None => (input, older_interface),
+            };
+            let polyfill = match (&input[..], &older_interface[..]) {
+                ([new], [old]) if new.is_dir() && old.is_dir() => {
+                    Polyfill::from_snapshots_with(old, new, &options, &module_mapping, &cx)
+                }
+                _ => {
+                    let doc = load_witx(&input, "input", &options, verbose);
+                    let older_doc =
+                        load_witx(&older_interface, "older_interface", &options, verbose);
+                    let mut module_mapping = module_mapping;
+                    if module_mapping.is_empty() {
+                        let inferred = infer_module_mapping(&doc, &older_doc);
+                        if inferred.is_empty() {
+                            fail(
+                                &"couldn't infer a module mapping, so --module_mapping is required",
+                            );
+                        }
+                        for pair in inferred {
+                            eprintln!(
+                                "{}",
+                                style().note(&format!(
+                                    "inferred module mapping {}={} (confidence {:.2})",
+                                    pair.new.as_str(),
+                                    pair.old.as_str(),
+                                    pair.confidence
+                                ))
+                            );
+                            module_mapping.insert(
+                                pair.new.as_str().to_string(),
+                                pair.old.as_str().to_string(),
+                            );
+                        }
+                    }
+                    Polyfill::with_context(&doc, &older_doc, &module_mapping, &cx)
+                }
+            };
+            let polyfill = match polyfill {
                 Ok(polyfill) => polyfill,
                 Err(e) => {
-                    eprintln!("couldn't calculate polyfill");
                     if verbose {
                         println!("{:?}", e);
                     }
-                    process::exit(1);
+                    fail(&format!("couldn't calculate polyfill: {}", e));
                 }
             };
-            println!("{}", polyfill.to_md());
+            if let Some(path) = write_baseline {
+                let baseline = Baseline::from_findings(&polyfill.findings());
+                fs::write(&path, baseline.to_string()).expect("write baseline file");
+                return;
+            }
+            if html {
+                print!("{}", polyfill.to_html());
+            } else if guide {
+                println!("{}", polyfill.migration_guide());
+            } else {
+                println!("{}", polyfill.to_md());
+            }
             if verbose {
                 println!("{:?}", polyfill);
             }
+            if strict {
+                let baseline = match baseline {
+                    Some(path) => {
+                        let source = fs::read_to_string(&path).expect("read baseline file");
+                        Baseline::parse(&source)
+                            .unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e)))
+                    }
+                    None => Baseline::new(),
+                };
+                for entry in baseline.stale(&polyfill.findings()) {
+                    eprintln!(
+                        "{}",
+                        style().warning(&format!("stale baseline entry: {}", entry))
+                    );
+                }
+                if let Err(findings) = polyfill.check_strict(&baseline) {
+                    for finding in findings {
+                        eprintln!("{}", style().error(&format!("incompatible: {}", finding)));
+                    }
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Changelog {
+            input,
+            older_interface,
+            phases,
+            module_mapping,
+            ignore_type_names,
+            semver,
+            html,
+        } => {
+            use std::{collections::HashMap, iter::FromIterator};
+            use witx::{diff::DocumentDiff, RepContext};
+
+            let (input, older_interface) = match phases {
+                Some(range) => {
+                    let (old, new) = phase_range(&range);
+                    let files = |phase: phases::Phase| phase.files().unwrap_or_else(|e| fail(&e));
+                    (files(new), files(old))
+                }
+                None => (input, older_interface),
+            };
+
+            let doc = load_witx(&input, "input", &options, verbose);
+            let older_doc = load_witx(&older_interface, "older_interface", &options, verbose);
+            let module_mapping = HashMap::from_iter(module_mapping);
+            let mut cx = RepContext::new();
+            if ignore_type_names {
+                cx = cx.ignore_type_names();
+            }
+            let diff = DocumentDiff::with_context(&older_doc, &doc, &module_mapping, &cx);
+            if semver {
+                println!("{}", diff.semver());
+                return;
+            }
+            if html {
+                print!("{}", diff.to_html(&older_doc, &doc));
+                return;
+            }
+            print!("{}", diff.to_md());
+            if verbose {
+                println!("{:?}", diff);
+            }
+        }
+        Command::Abi { input, target } => {
+            let doc = load_witx(&input, "input", &options, verbose);
+            print!("{}", abi_dump(&doc, target));
+        }
+        Command::Fingerprint { input, document } => {
+            let doc = load_witx(&input, "input", &options, verbose);
+            if document {
+                println!("{}", doc.fingerprint());
+            } else {
+                for (item, fingerprint) in doc.fingerprints() {
+                    println!("{} {}", fingerprint, item);
+                }
+            }
+        }
+        Command::Lock { input, output } => {
+            let doc = load_witx(&input, "input", &options, verbose);
+            let lockfile = format!(
+                "# Fingerprints of the interface, checked by `witx verify-lock`.\n\
+                 # Regenerate with `witx lock` after changing it.\n{}",
+                witx::lockfile::Lockfile::new(&doc)
+            );
+            match output {
+                Some(path) => fs::write(&path, lockfile)
+                    .unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e))),
+                None => print!("{}", lockfile),
+            }
+        }
+        Command::VerifyLock { input, lockfile } => {
+            let doc = load_witx(&input, "input", &options, verbose);
+            let lock = fs::read_to_string(&lockfile)
+                .map_err(|e| e.to_string())
+                .and_then(|s| witx::lockfile::Lockfile::parse(&s).map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| fail(&format!("{}: {}", lockfile.display(), e)));
+            if let Err(changes) = lock.verify(&doc) {
+                for change in changes {
+                    eprintln!("{}", style().error(&format!("not in lockfile: {}", change)));
+                }
+                eprintln!(
+                    "{}",
+                    style().note(&"update the lockfile with `witx lock` if the change is intended")
+                );
+                process::exit(1);
+            }
+        }
+        Command::ToWit {
+            input,
+            package,
+            world,
+            imports,
+            exports,
+        } => {
+            use witx::Id;
+
+            let doc = load_witx(&input, "input", &options, verbose);
+            let wit = match world {
+                Some(name) => {
+                    let mut world = witx::wit::World {
+                        name,
+                        imports: imports.iter().map(Id::new).collect(),
+                        exports: exports.iter().map(Id::new).collect(),
+                    };
+                    if world.imports.is_empty() && world.exports.is_empty() {
+                        world.imports = doc.modules().map(|m| m.name.clone()).collect();
+                    }
+                    witx::wit::to_wit_world(&doc, &package, &world)
+                        .unwrap_or_else(|e| fail(&e.to_string()))
+                }
+                None => witx::wit::to_wit(&doc, &package),
+            };
+            for note in wit.notes.iter() {
+                eprintln!("{}", style().note(note));
+            }
+            print!("{}", wit.source);
+        }
+        Command::FromWit { input } => {
+            let import = fs::read_to_string(&input)
+                .map_err(|e| e.to_string())
+                .and_then(|s| witx::wit::from_wit(&s).map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| fail(&format!("{}: {}", input.display(), e)));
+            for note in import.notes.iter() {
+                eprintln!("{}", style().note(note));
+            }
+            if let Err(e) = import.document() {
+                fail(&format!(
+                    "{}: converted document is invalid: {}",
+                    input.display(),
+                    e
+                ));
+            }
+            print!("{}", import.source);
+        }
+        Command::Query { item, roots } => {
+            use witx::{workspace::Workspace, Id};
+
+            let mut ws = Workspace::new();
+            for (name, path) in roots {
+                if let Err(e) = ws.load(&name, &[path]) {
+                    match &e {
+                        witx::workspace::WorkspaceError::Load { error, .. } => {
+                            eprintln!("{}: {}", e, style().witx_error(error, &witx::Filesystem));
+                            process::exit(1);
+                        }
+                        _ => fail(&e),
+                    }
+                }
+            }
+            let mut found = Vec::new();
+            match item.split_once("::") {
+                Some((module, func)) => {
+                    for (doc, f) in ws.func(&Id::new(module), &Id::new(func)) {
+                        found.push((doc, format!("{} func {}", f.fingerprint(), item)));
+                    }
+                }
+                None => {
+                    let id = Id::new(&item);
+                    for (doc, t) in ws.typename(&id) {
+                        found.push((doc, format!("{} type {}", t.fingerprint(), item)));
+                    }
+                    for (doc, m) in ws.module(&id) {
+                        found.push((doc, format!("{} module {}", m.fingerprint(), item)));
+                    }
+                }
+            }
+            for doc in ws.names() {
+                let mut any = false;
+                for (_, line) in found.iter().filter(|(d, _)| *d == doc) {
+                    println!("{}: {}", doc, line);
+                    any = true;
+                }
+                if !any {
+                    println!("{}: not defined", doc);
+                }
+            }
+        }
+        Command::Graph {
+            input,
+            format,
+            unused,
+        } => {
+            let doc = load_witx(&input, "input", &options, verbose);
+            if unused {
+                for t in witx::graph::Graph::new(&doc).unused_types() {
+                    println!("{}", t.name.as_str());
+                }
+                return;
+            }
+            match format.as_str() {
+                "mermaid" => print!("{}", witx::graph::to_mermaid(&doc)),
+                _ => print!("{}", witx::graph::to_dot(&doc)),
+            }
+        }
+        Command::Matrix {
+            input,
+            implementations,
+        } => {
+            let doc = load_witx(&input, "input", &options, verbose);
+            match witx::capabilities::SupportMatrix::new(&doc, implementations) {
+                Ok(matrix) => print!("{}", matrix.to_md()),
+                Err(e) => fail(&e),
+            }
+        }
+        Command::Explain { code: Some(code) } => match code.explanation() {
+            Some(explanation) => println!("{}", explanation),
+            None => fail(&format!("no error has the code {}", code)),
+        },
+        Command::Explain { code: None } => {
+            for explanation in codes::REGISTRY {
+                println!("{} {}", explanation.code, explanation.title);
+            }
+        }
+        Command::Backends => {
+            for backend in registry().backends() {
+                println!("{}", backend.name());
+                for option in backend.options() {
+                    match option.default {
+                        Some(default) => {
+                            println!("  {}: {} (default {:?})", option.name, option.docs, default)
+                        }
+                        None => println!("  {}: {}", option.name, option.docs),
+                    }
+                }
+            }
+        }
+        Command::Bindgen {
+            input,
+            language,
+            backend_options,
+            out_dir,
+            crate_name,
+            header_guard,
+            namespace,
+            mocks,
+            tracing,
+            limits,
+            no_std,
+            raw_only,
+            derives,
+            attributes,
+            serde,
+            proptest,
+            cpp_errors,
+            timings,
+        } => {
+            let allocated = || ALLOCATED.load(Ordering::Relaxed);
+            let timer = Timer::new().allocations(&allocated);
+            let doc = loaded(timer.load(&options, &input), "input", verbose);
+            let codegen_options = codegen::Options {
+                crate_name,
+                header_guard,
+                namespace,
+                mocks,
+                tracing,
+                limits,
+                no_std,
+                raw_only,
+                type_attributes: codegen::TypeAttributes {
+                    derives,
+                    attributes,
+                },
+                serde,
+                proptest,
+                cpp_errors,
+                ..codegen::Options::default()
+            };
+            let config = Config {
+                options: codegen_options,
+                values: backend_options.into_iter().collect(),
+            };
+            let registry = registry();
+            let files = timer
+                .time(&format!("codegen {}", language), || {
+                    registry.generate(&language, &doc, &config)
+                })
+                .unwrap_or_else(|e| fail(&e));
+            for file in files {
+                let path = out_dir.join(&file.path);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).expect("create output directory");
+                }
+                fs::write(&path, file.contents).expect("write output file");
+            }
+            if timings {
+                eprintln!("{}", timer.timings());
+            }
         }
     }
 }
 
-fn load_witx(input: &[PathBuf], field_name: &str, verbose: bool) -> Document {
-    match load(input) {
+fn load_witx(
+    input: &[PathBuf],
+    field_name: &str,
+    options: &LoadOptions,
+    verbose: bool,
+) -> Document {
+    loaded(options.load(input), field_name, verbose)
+}
+
+/// The document `result` loaded as `field_name`, exiting with its error
+/// otherwise.
+fn loaded(result: Result<Document, WitxError>, field_name: &str, verbose: bool) -> Document {
+    match result {
         Ok(doc) => {
             if verbose {
                 println!("{}: {:?}", field_name, doc);
@@ -136,7 +1025,7 @@ fn load_witx(input: &[PathBuf], field_name: &str, verbose: bool) -> Document {
             doc
         }
         Err(e) => {
-            eprintln!("{}", e.report());
+            eprintln!("{}", style().witx_error(&e, &witx::Filesystem));
             if verbose {
                 println!("{:?}", e);
             }
@@ -145,24 +1034,212 @@ fn load_witx(input: &[PathBuf], field_name: &str, verbose: bool) -> Document {
     }
 }
 
+/// Document each witx file in `dir` on its own page in `output`, with an
+/// index of their modules.
+fn write_docs_dir(
+    dir: &Path,
+    output: &Path,
+    template: &DocTemplate,
+    options: &LoadOptions,
+    verbose: bool,
+) -> Result<()> {
+    let mut files = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|p| p.extension().map(|e| e == "witx").unwrap_or(false));
+    files.sort();
+    fs::create_dir_all(output)?;
+    let mut index = DocIndex::new();
+    for file in files {
+        let doc = load_witx(std::slice::from_ref(&file), "input", options, verbose);
+        let page = file.with_extension("md");
+        let page = page.file_name().unwrap().to_str().unwrap();
+        fs::write(output.join(page), template.render(&doc))?;
+        index.page(page, doc);
+    }
+    fs::write(output.join("index.md"), index.to_md())?;
+    Ok(())
+}
+
+/// Write a man page for each module of the document at `input`, or of each
+/// witx file if `input` is a directory, into `output`. Without an `output`
+/// the pages are printed.
+fn write_man_pages(
+    input: &[PathBuf],
+    output: Option<&Path>,
+    options: &LoadOptions,
+    verbose: bool,
+) -> Result<()> {
+    let docs = match input {
+        [dir] if dir.is_dir() => {
+            let mut files = fs::read_dir(dir)?
+                .map(|e| e.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            files.retain(|p| p.extension().map(|e| e == "witx").unwrap_or(false));
+            files.sort();
+            files
+                .iter()
+                .map(|file| load_witx(std::slice::from_ref(file), "input", options, verbose))
+                .collect()
+        }
+        _ => vec![load_witx(input, "input", options, verbose)],
+    };
+    if let Some(output) = output {
+        fs::create_dir_all(output)?;
+    }
+    for (name, page) in docs.iter().flat_map(|doc| doc.man_pages()) {
+        match output {
+            Some(output) => fs::write(output.join(name), page)?,
+            None => print!("{}", page),
+        }
+    }
+    Ok(())
+}
+
+/// A man page for the CLI, from the help of each of its subcommands.
+fn cli_man_page() -> String {
+    fn help(args: &[&str]) -> String {
+        let app = Args::clap().global_setting(AppSettings::ColorNever);
+        match app.get_matches_from_safe(args) {
+            Err(e) => e.message,
+            Ok(_) => String::new(),
+        }
+    }
+    fn roff(text: &str) -> String {
+        text.lines()
+            .map(|l| {
+                let l = l.replace('\\', "\\e").replace('-', "\\-");
+                if l.starts_with('.') || l.starts_with('\'') {
+                    format!("\\&{}\n", l)
+                } else {
+                    format!("{}\n", l)
+                }
+            })
+            .collect()
+    }
+    let main = help(&["witx", "--help"]);
+    let mut man = format!(
+        ".TH WITX 1 \"\" \"witx {}\"\n.SH NAME\nwitx \\- {}\n.SH DESCRIPTION\n.nf\n{}.fi\n",
+        env!("CARGO_PKG_VERSION"),
+        main.lines().nth(1).unwrap_or_default(),
+        roff(&main)
+    );
+    let subcommands = main
+        .lines()
+        .skip_while(|l| !l.starts_with("SUBCOMMANDS:"))
+        .skip(1)
+        .filter_map(|l| l.split_whitespace().next())
+        .filter(|name| *name != "help");
+    man.push_str(".SH COMMANDS\n");
+    for name in subcommands {
+        man.push_str(&format!(
+            ".SS {}\n.nf\n{}.fi\n",
+            name,
+            roff(&help(&["witx", name, "--help"]))
+        ));
+    }
+    man
+}
+
 fn write_docs<P: AsRef<Path>>(document: &Document, path: P) {
     let mut file = File::create(path.as_ref()).expect("create output file");
     file.write_all(document.to_md().as_bytes())
         .expect("write output file");
 }
 
+fn abi_dump(doc: &Document, target: Target) -> String {
+    fn wasm_types(kind: &str, tys: &[WasmType]) -> String {
+        if tys.is_empty() {
+            return String::new();
+        }
+        let tys = tys.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        format!(" ({} {})", kind, tys.join(" "))
+    }
+
+    let mut out = String::new();
+    out.push_str(&format!("# Functions ({})\n", target));
+    for m in doc.modules() {
+        for f in m.funcs() {
+            let (params, results) = f.wasm_signature_for(target);
+            out.push_str(&format!(
+                "{}::{}:{}{}\n",
+                m.name.as_str(),
+                f.name.as_str(),
+                wasm_types("param", &params),
+                wasm_types("result", &results),
+            ));
+        }
+    }
+
+    out.push_str(&format!("\n# Types ({})\n", target));
+    for t in doc.typenames() {
+        let sa = t.mem_size_align_for(target);
+        out.push_str(&format!(
+            "{}: size {}, align {}\n",
+            t.name.as_str(),
+            sa.size,
+            sa.align
+        ));
+        match &**t.type_() {
+            Type::Record(r) if r.bitflags_repr().is_none() => {
+                for m in r.member_layout_for(target) {
+                    let sa = m.member.tref.mem_size_align_for(target);
+                    out.push_str(&format!(
+                        "    {}: offset {}, size {}, align {}\n",
+                        m.member.name.as_str(),
+                        m.offset,
+                        sa.size,
+                        sa.align
+                    ));
+                }
+            }
+            Type::Variant(v) if !v.is_enum() => {
+                out.push_str(&format!(
+                    "    tag: offset 0, size {}\n    payload: offset {}\n",
+                    v.tag_repr.mem_size(),
+                    v.payload_offset_for(target)
+                ));
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// The old and new phases of the range `OLD..NEW`, exiting if there are no
+/// such phases.
+fn phase_range(range: &str) -> (phases::Phase, phases::Phase) {
+    phases::range(range).unwrap_or_else(|e| fail(&e))
+}
+
+fn parse_backend_option(o: &str) -> Result<(String, String)> {
+    match o.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => bail!("invalid backend option: '{}', expected NAME=VALUE", o),
+    }
+}
+
+fn parse_root(r: &str) -> Result<(String, PathBuf)> {
+    match r.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_string(), PathBuf::from(path)))
+        }
+        _ => bail!("invalid root: '{}', expected NAME=PATH", r),
+    }
+}
+
 fn parse_module_mapping(m: &str) -> Result<(String, String)> {
     let s: Vec<_> = m.split('=').collect();
     let (n, o) = match s.len() {
         1 => {
             let mname = s
-                .get(0)
+                .first()
                 .ok_or(anyhow!("module name cannot be an empty string"))?;
             (mname, mname)
         }
         2 => {
             let newname = s
-                .get(0)
+                .first()
                 .ok_or(anyhow!("new module name cannot be an empty string"))?;
             let oldname = s
                 .get(1)
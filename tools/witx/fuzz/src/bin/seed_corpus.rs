@@ -0,0 +1,21 @@
+//! Seeds the corpus of every fuzz target with the witx files of each phase:
+//!
+//!     cargo run --bin seed_corpus
+
+use std::fs;
+use std::path::PathBuf;
+
+const TARGETS: &[&str] = &["lex", "parse", "validate"];
+
+fn main() {
+    let seeds = witx::fuzz::seed_corpus().expect("read the witx files of each phase");
+    let corpus = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    for target in TARGETS {
+        let dir = corpus.join(target);
+        fs::create_dir_all(&dir).expect("create corpus directory");
+        for (name, contents) in seeds.iter() {
+            fs::write(dir.join(name), contents).expect("write seed");
+        }
+    }
+    println!("seeded {} files into {}", seeds.len(), corpus.display());
+}
@@ -0,0 +1,117 @@
+//! Rust guest bindings for a witx document, generated at compile time.
+//!
+//! ```ignore
+//! mod nn {
+//!     witx_macro::witx!(path = "witx/wasi_ephemeral_nn.witx");
+//! }
+//! ```
+//!
+//! The macro accepts either witx text, as a string literal, or a `path` to a
+//! document relative to the crate's manifest directory. It expands to the
+//! same items the `rust-guest` backend writes to `src/lib.rs`, so it is
+//! usually invoked inside a module. Files read through `use` declarations
+//! are tracked, so the bindings are regenerated when any of them change.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, LitStr, Token};
+use witx::codegen::{self, Language, Options};
+use witx::{Document, Filesystem, WitxError, WitxIo};
+
+mod kw {
+    syn::custom_keyword!(path);
+}
+
+enum Input {
+    Text(LitStr),
+    Path(LitStr),
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(kw::path) {
+            input.parse::<kw::path>()?;
+            input.parse::<Token![=]>()?;
+            Ok(Input::Path(input.parse()?))
+        } else {
+            Ok(Input::Text(input.parse()?))
+        }
+    }
+}
+
+/// Records the path of each file read through it.
+struct Tracker {
+    paths: RefCell<Vec<PathBuf>>,
+}
+
+impl WitxIo for Tracker {
+    fn fgets(&self, path: &Path) -> Result<String, WitxError> {
+        let source = Filesystem.fgets(path)?;
+        self.paths.borrow_mut().push(path.to_path_buf());
+        Ok(source)
+    }
+    fn fget_line(&self, path: &Path, line_num: usize) -> Result<String, WitxError> {
+        Filesystem.fget_line(path, line_num)
+    }
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, WitxError> {
+        Filesystem.canonicalize(path)
+    }
+    fn size(&self, path: &Path) -> Option<u64> {
+        Filesystem.size(path)
+    }
+}
+
+/// The document described by `input`, and the files it was read from.
+fn load(input: &Input) -> Result<(Document, Vec<PathBuf>), String> {
+    match input {
+        Input::Text(text) => witx::parse(&text.value())
+            .map(|doc| (doc, Vec::new()))
+            .map_err(|e| e.report()),
+        Input::Path(path) => {
+            let root = std::env::var_os("CARGO_MANIFEST_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_default();
+            let path = root.join(path.value());
+            let tracker = Tracker {
+                paths: RefCell::new(Vec::new()),
+            };
+            let doc = witx::load_with(&[&path], &tracker).map_err(|e| e.report_with(&tracker))?;
+            Ok((doc, tracker.paths.into_inner()))
+        }
+    }
+}
+
+fn expand(input: &Input) -> Result<TokenStream, String> {
+    let (doc, paths) = load(input)?;
+    let lib = codegen::generate(&doc, Language::RustGuest, &Options::default())
+        .into_iter()
+        .find(|f| f.path == Path::new("src/lib.rs"))
+        .expect("the rust-guest backend generates src/lib.rs");
+    let bindings: TokenStream = lib
+        .contents
+        .parse()
+        .map_err(|e| format!("generated bindings don't parse: {}", e))?;
+    // `include_bytes!` makes cargo rebuild the crate when a file changes.
+    let paths = paths.iter().map(|p| p.to_string_lossy().into_owned());
+    Ok(quote! {
+        #( const _: &[u8] = include_bytes!(#paths); )*
+        #bindings
+    })
+}
+
+/// Expand to the Rust guest bindings of a witx document, given as text or
+/// as `path = "..."` relative to `CARGO_MANIFEST_DIR`.
+#[proc_macro]
+pub fn witx(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as Input);
+    let span = match &input {
+        Input::Text(lit) | Input::Path(lit) => lit.span(),
+    };
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(msg) => syn::Error::new(span, msg).to_compile_error().into(),
+    }
+}
@@ -0,0 +1,29 @@
+mod inline {
+    witx_macro::witx!(
+        ";;; A tensor's dimensions.
+         (typename $dims (list u32))
+         (typename $errno (enum (@witx tag u16) $success $busy))
+         (typename $rank u32)
+         (module $nn
+            (@interface func (export \"rank\")
+                (param $dims $dims)
+                (result $error (expected $rank (error $errno)))))"
+    );
+}
+
+mod nn {
+    witx_macro::witx!(path = "../../../phases/ephemeral/witx/wasi_ephemeral_nn.witx");
+}
+
+#[test]
+fn inline() {
+    assert_eq!(inline::ERRNO_BUSY.raw(), 1);
+    let _: fn(inline::Dims<'_>) -> Result<inline::Rank, inline::Errno> = inline::nn::rank;
+}
+
+#[test]
+fn path() {
+    assert_eq!(nn::NN_ERRNO_SUCCESS.raw(), 0);
+    assert_eq!(std::mem::size_of::<nn::Graph>(), 4);
+    assert_eq!(std::mem::size_of::<nn::raw::Graph>(), 4);
+}
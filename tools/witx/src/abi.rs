@@ -21,8 +21,9 @@
 //! generators will need to implement the various instructions to support APIs.
 
 use crate::{
-    BuiltinType, Id, IntRepr, InterfaceFunc, InterfaceFuncParam, NamedType, Type, TypeRef,
+    BuiltinType, Id, IntRepr, InterfaceFunc, InterfaceFuncParam, NamedType, Target, Type, TypeRef,
 };
+use std::fmt;
 
 /// Enumerates wasm types used by interface types when lowering/lifting.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -35,6 +36,17 @@ pub enum WasmType {
     // e.g. externref, so we don't need to define them here.
 }
 
+impl fmt::Display for WasmType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WasmType::I32 => f.write_str("i32"),
+            WasmType::I64 => f.write_str("i64"),
+            WasmType::F32 => f.write_str("f32"),
+            WasmType::F64 => f.write_str("f64"),
+        }
+    }
+}
+
 impl From<IntRepr> for WasmType {
     fn from(i: IntRepr) -> WasmType {
         match i {
@@ -50,6 +62,8 @@ impl From<IntRepr> for WasmType {
 /// types functions do not have ABIs, they have APIs. For the meantime, however,
 /// we mandate ABIs to ensure we can all talk to each other.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Abi {
     /// Only stable ABI currently, and is the historical WASI ABI since it was
     /// first created.
@@ -450,11 +464,25 @@ impl InterfaceFunc {
     /// The first entry returned is the list of parameters and the second entry
     /// is the list of results for the wasm function signature.
     pub fn wasm_signature(&self) -> (Vec<WasmType>, Vec<WasmType>) {
+        self.wasm_signature_for(Target::Wasm32)
+    }
+
+    /// Same as [`InterfaceFunc::wasm_signature`], except that pointer-sized
+    /// values (pointers, list pointers and lengths, and `usize`) are lowered
+    /// to the pointer type of `target`.
+    pub fn wasm_signature_for(&self, target: Target) -> (Vec<WasmType>, Vec<WasmType>) {
         assert_eq!(self.abi, Abi::Preview1);
+        let ptr = target.pointer_type();
         let mut params = Vec::new();
         let mut results = Vec::new();
         for param in self.params.iter() {
             match &**param.tref.type_() {
+                Type::Builtin(BuiltinType::U32 {
+                    lang_ptr_size: true,
+                })
+                | Type::Pointer(_)
+                | Type::ConstPointer(_) => params.push(ptr),
+
                 Type::Builtin(BuiltinType::S8)
                 | Type::Builtin(BuiltinType::U8 { .. })
                 | Type::Builtin(BuiltinType::S16)
@@ -462,14 +490,12 @@ impl InterfaceFunc {
                 | Type::Builtin(BuiltinType::S32)
                 | Type::Builtin(BuiltinType::U32 { .. })
                 | Type::Builtin(BuiltinType::Char)
-                | Type::Pointer(_)
-                | Type::ConstPointer(_)
                 | Type::Handle(_)
                 | Type::Variant(_) => params.push(WasmType::I32),
 
                 Type::Record(r) => match r.bitflags_repr() {
                     Some(repr) => params.push(WasmType::from(repr)),
-                    None => params.push(WasmType::I32),
+                    None => params.push(ptr),
                 },
 
                 Type::Builtin(BuiltinType::S64) | Type::Builtin(BuiltinType::U64) => {
@@ -480,14 +506,20 @@ impl InterfaceFunc {
                 Type::Builtin(BuiltinType::F64) => params.push(WasmType::F64),
 
                 Type::List(_) => {
-                    params.push(WasmType::I32);
-                    params.push(WasmType::I32);
+                    params.push(ptr);
+                    params.push(ptr);
                 }
             }
         }
 
         for param in self.results.iter() {
             match &**param.tref.type_() {
+                Type::Builtin(BuiltinType::U32 {
+                    lang_ptr_size: true,
+                })
+                | Type::Pointer(_)
+                | Type::ConstPointer(_) => results.push(ptr),
+
                 Type::Builtin(BuiltinType::S8)
                 | Type::Builtin(BuiltinType::U8 { .. })
                 | Type::Builtin(BuiltinType::S16)
@@ -495,8 +527,6 @@ impl InterfaceFunc {
                 | Type::Builtin(BuiltinType::S32)
                 | Type::Builtin(BuiltinType::U32 { .. })
                 | Type::Builtin(BuiltinType::Char)
-                | Type::Pointer(_)
-                | Type::ConstPointer(_)
                 | Type::Handle(_) => results.push(WasmType::I32),
 
                 Type::Builtin(BuiltinType::S64) | Type::Builtin(BuiltinType::U64) => {
@@ -525,10 +555,10 @@ impl InterfaceFunc {
                         match &**ty.type_() {
                             Type::Record(r) if r.is_tuple() => {
                                 for _ in 0..r.members.len() {
-                                    params.push(WasmType::I32);
+                                    params.push(ptr);
                                 }
                             }
-                            _ => params.push(WasmType::I32),
+                            _ => params.push(ptr),
                         }
                     }
                 }
@@ -597,8 +627,8 @@ impl<B: Bindgen> Generator<'_, B> {
         // If necessary for our ABI, insert return pointers for any returned
         // values through a result.
         assert!(func.results.len() < 2);
-        if let Some(result) = func.results.get(0) {
-            self.prep_return_pointer(&result.tref.type_());
+        if let Some(result) = func.results.first() {
+            self.prep_return_pointer(result.tref.type_());
         }
 
         let (params, results) = func.wasm_signature();
@@ -610,7 +640,7 @@ impl<B: Bindgen> Generator<'_, B> {
         });
 
         // Lift the return value if one is present.
-        if let Some(result) = func.results.get(0) {
+        if let Some(result) = func.results.first() {
             self.lift(&result.tref, true);
         }
 
@@ -644,7 +674,7 @@ impl<B: Bindgen> Generator<'_, B> {
 
         // Like above the current ABI only has at most one result, so lower it
         // here if necessary.
-        if let Some(result) = func.results.get(0) {
+        if let Some(result) = func.results.first() {
             self.lower(&result.tref, Some(&mut nth));
         }
 
@@ -677,7 +707,7 @@ impl<B: Bindgen> Generator<'_, B> {
             inst.results_len(),
             self.results.len()
         );
-        self.stack.extend(self.results.drain(..));
+        self.stack.append(&mut self.results);
     }
 
     fn lower(&mut self, ty: &TypeRef, retptr: Option<&mut usize>) {
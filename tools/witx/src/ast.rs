@@ -1,8 +1,11 @@
-use crate::Abi;
+use crate::{Abi, Location, WitxVersion};
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::rc::{Rc, Weak};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Id(String);
 
 impl Id {
@@ -42,15 +45,52 @@ impl From<&str> for Id {
 pub struct Document {
     definitions: Vec<Definition>,
     entries: HashMap<Id, Entry>,
+    version: WitxVersion,
+    provenance: HashMap<Id, Provenance>,
+    func_provenance: HashMap<(Id, Id), Provenance>,
 }
 
 impl Document {
-    pub(crate) fn new(definitions: Vec<Definition>, entries: HashMap<Id, Entry>) -> Self {
+    pub(crate) fn new(
+        definitions: Vec<Definition>,
+        entries: HashMap<Id, Entry>,
+        version: WitxVersion,
+    ) -> Self {
         Document {
             definitions,
             entries,
+            version,
+            provenance: HashMap::new(),
+            func_provenance: HashMap::new(),
         }
     }
+    pub(crate) fn with_provenance(
+        mut self,
+        provenance: HashMap<Id, Provenance>,
+        func_provenance: HashMap<(Id, Id), Provenance>,
+    ) -> Self {
+        self.provenance = provenance;
+        self.func_provenance = func_provenance;
+        self
+    }
+    /// The version of the witx language the document's root file declares
+    /// it is written in.
+    pub fn version(&self) -> WitxVersion {
+        self.version
+    }
+    #[cfg(feature = "serde")]
+    pub(crate) fn definitions(&self) -> &[Definition] {
+        &self.definitions
+    }
+    /// Where the type or module `name` was defined. Only documents parsed
+    /// from source record this: deserialized and merged ones don't.
+    pub fn provenance(&self, name: &Id) -> Option<&Provenance> {
+        self.provenance.get(name)
+    }
+    /// Where the function `func` of the module `module` was defined.
+    pub fn func_provenance(&self, module: &Id, func: &Id) -> Option<&Provenance> {
+        self.func_provenance.get(&(module.clone(), func.clone()))
+    }
     pub fn typename(&self, name: &Id) -> Option<Rc<NamedType>> {
         self.entries.get(name).and_then(|e| match e {
             Entry::Typename(nt) => Some(nt.upgrade().expect("always possible to upgrade entry")),
@@ -64,33 +104,27 @@ impl Document {
         })
     }
     /// All of the (unique) types used as "err" variant of results returned from
-    /// functions.
+    /// functions, in the order they're first used.
     pub fn error_types<'a>(&'a self) -> impl Iterator<Item = TypeRef> + 'a {
-        let errors: HashSet<TypeRef> = self
-            .modules()
-            .flat_map(|m| {
-                m.funcs()
-                    .filter_map(|f| {
-                        if f.results.len() == 1 {
-                            Some(f.results[0].tref.type_().clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .filter_map(|t| match &*t {
-                        Type::Variant(v) => {
-                            let (_ok, err) = v.as_expected()?;
-                            Some(err?.clone())
+        let mut seen = HashSet::new();
+        let mut errors = Vec::new();
+        for m in self.modules() {
+            for f in m.funcs().filter(|f| f.results.len() == 1) {
+                if let Type::Variant(v) = &**f.results[0].tref.type_() {
+                    if let Some((_ok, Some(err))) = v.as_expected() {
+                        if seen.insert(err.clone()) {
+                            errors.push(err.clone());
                         }
-                        _ => None,
-                    })
-                    .collect::<HashSet<TypeRef>>()
-            })
-            .collect();
+                    }
+                }
+            }
+        }
         errors.into_iter()
     }
+    /// The module `name`, looked up in the index of the document's names
+    /// built as it was validated.
     pub fn module(&self, name: &Id) -> Option<Rc<Module>> {
-        self.entries.get(&name).and_then(|e| match e {
+        self.entries.get(name).and_then(|e| match e {
             Entry::Module(m) => Some(m.upgrade().expect("always possible to upgrade entry")),
             _ => None,
         })
@@ -125,7 +159,61 @@ impl std::hash::Hash for Document {
     }
 }
 
+/// Where a definition came from: the file defining it, and the chain of
+/// `(use ...)` directives that brought that file into the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// The definition's name in the file defining it.
+    pub location: Location,
+    /// The files whose `use` led to the defining file, starting at the root
+    /// file of the document. Empty for definitions in a root file.
+    pub uses: Vec<PathBuf>,
+}
+
+impl Provenance {
+    pub fn path(&self) -> &Path {
+        &self.location.path
+    }
+
+    /// Whether the definition was brought in by a `use`, rather than being
+    /// in a root file.
+    pub fn is_used(&self) -> bool {
+        !self.uses.is_empty()
+    }
+
+    /// Every file from the root to the defining one.
+    pub fn chain(&self) -> impl Iterator<Item = &Path> {
+        self.uses
+            .iter()
+            .map(|p| p.as_path())
+            .chain(std::iter::once(self.path()))
+    }
+}
+
+impl std::fmt::Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.path().display(),
+            self.location.line,
+            self.location.column
+        )?;
+        if self.is_used() {
+            let uses = self
+                .uses
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            write!(f, " (used from {})", uses.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Definition {
     Typename(Rc<NamedType>),
     Module(Rc<Module>),
@@ -192,10 +280,23 @@ impl TypeRef {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NamedType {
     pub name: Id,
     pub tref: TypeRef,
     pub docs: String,
+    pub meta: Vec<Meta>,
+}
+
+/// Metadata about a module, function or type given by a
+/// `(@witx meta key "value")` annotation, such as its owner or stability.
+/// Metadata is for tools consuming the document, and doesn't change what the
+/// item means.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Meta {
+    pub key: String,
+    pub value: String,
 }
 
 impl NamedType {
@@ -210,6 +311,8 @@ impl NamedType {
 /// Currently this is relatively close to that with just a few `*.witx`
 /// extensions for now.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Type {
     /// A structure with named field.
     Record(RecordDatatype),
@@ -250,6 +353,8 @@ impl Type {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum BuiltinType {
     /// This is a 32-bit unicode scalar value, not a code point.
     ///
@@ -298,6 +403,8 @@ pub enum BuiltinType {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum IntRepr {
     U8,
     U16,
@@ -316,6 +423,15 @@ impl IntRepr {
             IntRepr::U64 => BuiltinType::U64,
         }
     }
+
+    pub fn bits(&self) -> u32 {
+        match self {
+            IntRepr::U8 => 8,
+            IntRepr::U16 => 16,
+            IntRepr::U32 => 32,
+            IntRepr::U64 => 64,
+        }
+    }
 }
 
 /// A struct-like value with named fields.
@@ -324,6 +440,7 @@ impl IntRepr {
 /// number of named fields that all have their own particular type. Field order
 /// dictates layout in memory.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordDatatype {
     /// A hint as to what this record might be.
     ///
@@ -338,6 +455,8 @@ pub struct RecordDatatype {
 
 /// Different kinds of records used for hinting various language-specific types.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum RecordKind {
     /// A tuple where the name of all fields are consecutive integers starting
     /// at "0".
@@ -350,18 +469,43 @@ pub enum RecordKind {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordMember {
     pub name: Id,
     pub tref: TypeRef,
+    /// The length of a list field, in terms of the other fields.
+    pub size: Option<SizeExpr>,
     pub docs: String,
 }
 
+/// The length of a list, in terms of the other fields of its record or the
+/// other parameters of its function. Declared with `(@witx size expr)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SizeExpr {
+    Const(u64),
+    /// The value of an integer.
+    Value(Id),
+    /// The length of a list, `(len $x)`.
+    Len(Id),
+    /// The product of the elements of a list of integers, `(product $x)`.
+    Product(Id),
+    /// The bytes of data in a tensor with a list of `u32` dimensions and a
+    /// `tensor_type`, `(tensor_size $dimensions $type)`.
+    TensorSize {
+        dimensions: Id,
+        ty: Id,
+    },
+    /// `(mul expr...)`
+    Mul(Vec<SizeExpr>),
+    /// `(add expr...)`
+    Add(Vec<SizeExpr>),
+}
+
 impl RecordDatatype {
     pub fn is_tuple(&self) -> bool {
-        match self.kind {
-            RecordKind::Tuple => true,
-            _ => false,
-        }
+        matches!(self.kind, RecordKind::Tuple)
     }
 
     pub fn bitflags_repr(&self) -> Option<IntRepr> {
@@ -384,12 +528,16 @@ impl RecordDatatype {
 /// simple `bool`. Variants are primarily used heavily with various kinds of
 /// shorthands in the `*.witx` format to represent idioms in languages.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Variant {
     /// The bit representation of the width of this variant's tag when the
     /// variant is stored in memory.
     pub tag_repr: IntRepr,
     /// The possible cases that values of this variant type can take.
     pub cases: Vec<Case>,
+    /// For enums declared with `(@witx feature $name)`, the feature whose
+    /// optional capabilities are the cases of the enum.
+    pub feature: Option<Id>,
 }
 
 impl Variant {
@@ -441,6 +589,7 @@ impl Variant {
 
 /// One of a number of possible types that a `Variant` can take.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Case {
     /// The name of this case and how to identify it.
     pub name: Id,
@@ -452,6 +601,7 @@ pub struct Case {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HandleDatatype {}
 
 #[derive(Debug, Clone)]
@@ -460,6 +610,7 @@ pub struct Module {
     definitions: Vec<ModuleDefinition>,
     entries: HashMap<Id, ModuleEntry>,
     pub docs: String,
+    pub meta: Vec<Meta>,
 }
 
 impl Module {
@@ -468,14 +619,20 @@ impl Module {
         definitions: Vec<ModuleDefinition>,
         entries: HashMap<Id, ModuleEntry>,
         docs: String,
+        meta: Vec<Meta>,
     ) -> Self {
         Module {
             name,
             definitions,
             entries,
             docs,
+            meta,
         }
     }
+    #[cfg(feature = "serde")]
+    pub(crate) fn definitions(&self) -> &[ModuleDefinition] {
+        &self.definitions
+    }
     pub fn import(&self, name: &Id) -> Option<Rc<ModuleImport>> {
         self.entries.get(name).and_then(|e| match e {
             ModuleEntry::Import(d) => Some(d.upgrade().expect("always possible to upgrade entry")),
@@ -488,12 +645,26 @@ impl Module {
             _ => None,
         })
     }
+    /// The function `name`, looked up in the index of the module's names
+    /// built as it was validated.
     pub fn func(&self, name: &Id) -> Option<Rc<InterfaceFunc>> {
         self.entries.get(name).and_then(|e| match e {
             ModuleEntry::Func(d) => Some(d.upgrade().expect("always possible to upgrade entry")),
             _ => None,
         })
     }
+    /// Every function of the module, by name, for callers looking up many.
+    pub fn funcs_by_name(&self) -> HashMap<&Id, Rc<InterfaceFunc>> {
+        self.entries
+            .iter()
+            .filter_map(|(name, e)| match e {
+                ModuleEntry::Func(d) => {
+                    Some((name, d.upgrade().expect("always possible to upgrade entry")))
+                }
+                _ => None,
+            })
+            .collect()
+    }
     pub fn funcs<'a>(&'a self) -> impl Iterator<Item = Rc<InterfaceFunc>> + 'a {
         self.definitions.iter().filter_map(|d| match d {
             ModuleDefinition::Func(d) => Some(d.clone()),
@@ -506,7 +677,10 @@ impl PartialEq for Module {
     fn eq(&self, rhs: &Module) -> bool {
         // For equality, we don't care about the ordering of definitions,
         // so we only need to check that the entries map is equal
-        self.name == rhs.name && self.entries == rhs.entries && self.docs == rhs.docs
+        self.name == rhs.name
+            && self.entries == rhs.entries
+            && self.docs == rhs.docs
+            && self.meta == rhs.meta
     }
 }
 impl Eq for Module {}
@@ -516,10 +690,13 @@ impl std::hash::Hash for Module {
         std::hash::Hash::hash(&self.name, state);
         std::hash::Hash::hash(&self.definitions, state);
         std::hash::Hash::hash(&self.docs, state);
+        std::hash::Hash::hash(&self.meta, state);
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ModuleDefinition {
     Import(Rc<ModuleImport>),
     Func(Rc<InterfaceFunc>),
@@ -554,6 +731,7 @@ impl PartialEq for ModuleEntry {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleImport {
     pub name: Id,
     pub variant: ModuleImportVariant,
@@ -561,31 +739,67 @@ pub struct ModuleImport {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ModuleImportVariant {
     Memory,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceFunc {
     pub abi: Abi,
     pub name: Id,
     pub params: Vec<InterfaceFuncParam>,
     pub results: Vec<InterfaceFuncParam>,
     pub noreturn: bool,
+    /// The optional feature this function belongs to, declared with
+    /// `(@witx feature $name)`. Implementations which don't support the
+    /// feature may leave the function out.
+    pub feature: Option<Id>,
     pub docs: String,
+    pub meta: Vec<Meta>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceFuncParam {
     pub name: Id,
     pub tref: TypeRef,
+    /// The length of a list parameter, in terms of the other parameters.
+    pub size: Option<SizeExpr>,
     pub docs: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Constant {
     pub ty: Id,
     pub name: Id,
     pub value: u64,
     pub docs: String,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn funcs_by_name() {
+        let doc = crate::parse(
+            "(module $m
+               (import \"memory\" (memory))
+               (@interface func (export \"f\"))
+               (@interface func (export \"g\") (param $x u8)))",
+        )
+        .unwrap();
+        let module = doc.module(&Id::new("m")).unwrap();
+        let funcs = module.funcs_by_name();
+        assert_eq!(funcs.len(), 2);
+        for f in module.funcs() {
+            assert!(Rc::ptr_eq(&funcs[&f.name], &f));
+            assert!(Rc::ptr_eq(&module.func(&f.name).unwrap(), &f));
+        }
+        assert!(module.func(&Id::new("memory")).is_none());
+    }
+}
@@ -0,0 +1,293 @@
+//! Incompatibilities between interfaces that have been accepted.
+//!
+//! A strict check of a polyfill fails on every incompatibility it finds. A
+//! baseline is a checked-in list of the findings already known and accepted,
+//! so that the check only fails on new ones. Each line of a baseline file is
+//! a finding, as displayed:
+//!
+//! ```text
+//! # Comments and blank lines are ignored.
+//! 3f0c8e2b9d4a6f71 wasi_ephemeral_nn::load param builder
+//! 9a7d1c0e5b3f2846 wasi_ephemeral_nn::compute result error
+//! ```
+//!
+//! A finding's fingerprint combines those of the old and new functions, so
+//! an accepted finding comes back when either function changes again.
+
+use crate::fingerprint::Fingerprint;
+use crate::polyfill::{FuncPolyfill, Polyfill};
+use crate::{Id, RepEquality};
+use std::collections::HashSet;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BaselineError {
+    #[error("Invalid baseline entry on line {line}: {text:?}")]
+    InvalidEntry { line: usize, text: String },
+}
+
+/// Whether a finding is about a param or a result of its function.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FindingKind {
+    Param,
+    Result,
+}
+
+impl FindingKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FindingKind::Param => "param",
+            FindingKind::Result => "result",
+        }
+    }
+}
+
+/// A param or result of a function that the old interface can't be
+/// polyfilled with, because its type is incompatible or it has no
+/// counterpart in the other interface.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Finding {
+    /// The module of the old interface.
+    pub module: Id,
+    /// The function, by its name in the old interface.
+    pub func: Id,
+    pub kind: FindingKind,
+    /// The param or result, by its name in whichever interface has it.
+    pub param: Id,
+    pub fingerprint: Fingerprint,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}::{} {} {}",
+            self.fingerprint,
+            self.module.as_str(),
+            self.func.as_str(),
+            self.kind.as_str(),
+            self.param.as_str()
+        )
+    }
+}
+
+impl Polyfill {
+    /// Every incompatibility in this polyfill, by module, function and param.
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for module in self.modules.iter() {
+            for func in module.funcs.iter() {
+                func_findings(&module.old.name, func, &mut findings);
+            }
+        }
+        findings
+    }
+
+    /// Checks that this polyfill has no incompatibilities other than those
+    /// accepted by `baseline`, returning the new ones otherwise.
+    pub fn check_strict(&self, baseline: &Baseline) -> Result<(), Vec<Finding>> {
+        let new = self
+            .findings()
+            .into_iter()
+            .filter(|f| !baseline.accepts(f))
+            .collect::<Vec<_>>();
+        if new.is_empty() {
+            Ok(())
+        } else {
+            Err(new)
+        }
+    }
+}
+
+fn func_findings(module: &Id, func: &FuncPolyfill, findings: &mut Vec<Finding>) {
+    let fingerprint = Fingerprint::of_source(&format!(
+        "{} {}",
+        func.old.fingerprint(),
+        func.new.fingerprint()
+    ));
+    let mut push = |kind, param: &Id| {
+        findings.push(Finding {
+            module: module.clone(),
+            func: func.old.name.clone(),
+            kind,
+            param: param.clone(),
+            fingerprint,
+        })
+    };
+    for (kind, mapped, unknown) in [
+        (
+            FindingKind::Param,
+            &func.mapped_params,
+            &func.unknown_params,
+        ),
+        (
+            FindingKind::Result,
+            &func.mapped_results,
+            &func.unknown_results,
+        ),
+    ] {
+        for p in mapped.iter().filter(|p| p.repeq() == RepEquality::NotEq) {
+            push(kind, &p.old.name);
+        }
+        for p in unknown.iter() {
+            push(kind, &p.param().name);
+        }
+    }
+}
+
+/// The findings accepted by a baseline file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baseline {
+    entries: Vec<String>,
+}
+
+impl Baseline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A baseline accepting each of `findings`.
+    pub fn from_findings(findings: &[Finding]) -> Self {
+        Baseline {
+            entries: findings.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    /// Parses the contents of a baseline file.
+    pub fn parse(source: &str) -> Result<Self, BaselineError> {
+        let mut entries = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match &words[..] {
+                [fp, _, kind, _]
+                    if fp.len() == 16
+                        && u64::from_str_radix(fp, 16).is_ok()
+                        && (*kind == "param" || *kind == "result") =>
+                {
+                    entries.push(words.join(" "))
+                }
+                _ => {
+                    return Err(BaselineError::InvalidEntry {
+                        line: i + 1,
+                        text: line.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(Baseline { entries })
+    }
+
+    pub fn accepts(&self, finding: &Finding) -> bool {
+        self.entries.contains(&finding.to_string())
+    }
+
+    /// The entries that accept none of `findings`, and could be removed.
+    pub fn stale<'a>(&'a self, findings: &[Finding]) -> Vec<&'a str> {
+        let findings = findings
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<HashSet<_>>();
+        self.entries
+            .iter()
+            .filter(|e| !findings.contains(*e))
+            .map(|e| e.as_str())
+            .collect()
+    }
+}
+
+impl fmt::Display for Baseline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn compare(new: &str, old: &str) -> Polyfill {
+        let new = crate::parse(new).unwrap();
+        let old = crate::parse(old).unwrap();
+        let mut mapping = HashMap::new();
+        mapping.insert("m".to_string(), "m".to_string());
+        Polyfill::new(&new, &old, &mapping).unwrap()
+    }
+
+    #[test]
+    fn findings() {
+        let polyfill = compare(
+            "(module $m (@interface func (export \"f\")
+                (param $a u8) (param $b u64) (param $d u8) (result $r u32)))",
+            "(module $m (@interface func (export \"f\")
+                (param $a u16) (param $b u32) (param $c u8) (result $r u32)))",
+        );
+        let findings = polyfill.findings();
+        assert_eq!(
+            findings
+                .iter()
+                .map(|f| format!(
+                    "{}::{} {} {}",
+                    f.module.as_str(),
+                    f.func.as_str(),
+                    f.kind.as_str(),
+                    f.param.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec!["m::f param a", "m::f param c", "m::f param d"]
+        );
+        assert!(findings
+            .iter()
+            .all(|f| f.fingerprint == findings[0].fingerprint));
+    }
+
+    #[test]
+    fn check_strict() {
+        let old = "(module $m (@interface func (export \"f\") (param $a u16)))";
+        let polyfill = compare(
+            "(module $m (@interface func (export \"f\") (param $a u8)))",
+            old,
+        );
+        let findings = polyfill.findings();
+        assert_eq!(
+            polyfill.check_strict(&Baseline::new()),
+            Err(findings.clone())
+        );
+
+        let baseline = Baseline::parse(&format!(
+            "# Known breaks\n\n{}",
+            Baseline::from_findings(&findings)
+        ))
+        .unwrap();
+        assert_eq!(polyfill.check_strict(&baseline), Ok(()));
+        assert!(baseline.stale(&findings).is_empty());
+
+        // Changing the function again invalidates the accepted finding.
+        let changed = compare(
+            "(module $m (@interface func (export \"f\") (param $a s8)))",
+            old,
+        );
+        assert_eq!(changed.check_strict(&baseline), Err(changed.findings()));
+        assert_eq!(baseline.stale(&changed.findings()).len(), 1);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Baseline::parse("0123456789abcdef m::f param a\nm::f param a\n"),
+            Err(BaselineError::InvalidEntry {
+                line: 2,
+                text: "m::f param a".to_string()
+            })
+        );
+        assert!(Baseline::parse("0123456789abcdef m::f arg a").is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Generate bindings from a build script.
+//!
+//! ```no_run
+//! // build.rs
+//! use witx::{build::WitxBuild, codegen::Language};
+//!
+//! fn main() {
+//!     WitxBuild::new()
+//!         .file("witx/wasi_ephemeral_nn.witx")
+//!         .language(Language::WasmtimeHost)
+//!         .generate()
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! Files are written to `$OUT_DIR` unless another directory is given, at the
+//! paths the backend chooses, so the crate can pull them in with
+//! `include!(concat!(env!("OUT_DIR"), "/src/lib.rs"))`. Every file the
+//! document is read from, including those it `use`s, is reported to cargo
+//! with `cargo:rerun-if-changed`, so the bindings are regenerated whenever
+//! the document changes.
+
+use crate::codegen::{self, Language, Options};
+use crate::{Document, Filesystem, WitxError, WitxIo};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("no witx files to generate bindings from")]
+    NoFiles,
+    #[error("no output directory: `OUT_DIR` isn't set, so it must be given with `out_dir`")]
+    NoOutDir,
+    #[error("failed to load witx document: {}", .0.report())]
+    Load(#[from] WitxError),
+    #[error("failed to write {0:?}")]
+    Write(PathBuf, #[source] std::io::Error),
+}
+
+/// Generates bindings for a witx document, configured with a builder.
+#[derive(Debug, Clone)]
+pub struct WitxBuild {
+    files: Vec<PathBuf>,
+    language: Language,
+    options: Options,
+    out_dir: Option<PathBuf>,
+    cargo_metadata: bool,
+}
+
+impl Default for WitxBuild {
+    fn default() -> Self {
+        WitxBuild::new()
+    }
+}
+
+impl WitxBuild {
+    /// A build generating Rust guest bindings into `$OUT_DIR`.
+    pub fn new() -> Self {
+        WitxBuild {
+            files: Vec::new(),
+            language: Language::RustGuest,
+            options: Options::default(),
+            out_dir: None,
+            cargo_metadata: true,
+        }
+    }
+
+    /// Add a root file of the document.
+    pub fn file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.files.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Add several root files of the document.
+    pub fn files<P: AsRef<Path>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.files
+            .extend(paths.into_iter().map(|p| p.as_ref().to_path_buf()));
+        self
+    }
+
+    /// The language to generate bindings for.
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Settings for the backend.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Write generated files to `dir` rather than `$OUT_DIR`.
+    pub fn out_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.out_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Whether to print `cargo:rerun-if-changed` lines. On by default.
+    pub fn cargo_metadata(mut self, cargo_metadata: bool) -> Self {
+        self.cargo_metadata = cargo_metadata;
+        self
+    }
+
+    /// Generate the bindings, returning the paths of the files written.
+    pub fn generate(&self) -> Result<Vec<PathBuf>, BuildError> {
+        if self.files.is_empty() {
+            return Err(BuildError::NoFiles);
+        }
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or(BuildError::NoOutDir)?,
+        };
+
+        let (doc, sources) = self.load();
+        if self.cargo_metadata {
+            // Report the files read so far even if the document is invalid,
+            // so fixing it triggers a rebuild.
+            for path in sources {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+        let doc = doc?;
+
+        let mut written = Vec::new();
+        for file in codegen::generate(&doc, self.language, &self.options) {
+            let path = out_dir.join(&file.path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| BuildError::Write(parent.to_path_buf(), e))?;
+            }
+            std::fs::write(&path, file.contents).map_err(|e| BuildError::Write(path.clone(), e))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /// The document, and the files it depends on: its roots, and every file
+    /// read while loading it.
+    fn load(&self) -> (Result<Document, WitxError>, Vec<PathBuf>) {
+        let tracker = Tracker {
+            sources: RefCell::new(Vec::new()),
+        };
+        let doc = crate::load_with(&self.files, &tracker);
+        let mut sources = self.files.clone();
+        for path in tracker.sources.into_inner() {
+            if !sources.contains(&path) {
+                sources.push(path);
+            }
+        }
+        (doc, sources)
+    }
+}
+
+/// Records the path of each file read through it.
+struct Tracker {
+    sources: RefCell<Vec<PathBuf>>,
+}
+
+impl WitxIo for Tracker {
+    fn fgets(&self, path: &Path) -> Result<String, WitxError> {
+        let source = Filesystem.fgets(path)?;
+        self.sources.borrow_mut().push(path.to_path_buf());
+        Ok(source)
+    }
+    fn fget_line(&self, path: &Path, line_num: usize) -> Result<String, WitxError> {
+        Filesystem.fget_line(path, line_num)
+    }
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, WitxError> {
+        Filesystem.canonicalize(path)
+    }
+    fn size(&self, path: &Path) -> Option<u64> {
+        Filesystem.size(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generate() {
+        let root = std::env::temp_dir().join(format!("witx-build-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("types.witx"), "(typename $size u32)").unwrap();
+        std::fs::write(
+            root.join("nn.witx"),
+            "(use \"types.witx\")
+             (module $nn (@interface func (export \"f\") (param $s $size)))",
+        )
+        .unwrap();
+
+        let written = WitxBuild::new()
+            .file(root.join("nn.witx"))
+            .language(Language::C)
+            .out_dir(root.join("out"))
+            .cargo_metadata(false)
+            .generate()
+            .unwrap();
+        assert_eq!(written.len(), 1);
+        assert!(written[0].starts_with(root.join("out")));
+        let header = std::fs::read_to_string(&written[0]).unwrap();
+        assert!(header.contains("size"), "{}", header);
+
+        let build = WitxBuild::new().file(root.join("nn.witx"));
+        let (doc, sources) = build.load();
+        assert!(doc.is_ok());
+        assert!(sources.contains(&root.join("types.witx")), "{:?}", sources);
+
+        assert!(matches!(
+            WitxBuild::new().out_dir(&root).generate(),
+            Err(BuildError::NoFiles)
+        ));
+        assert!(matches!(
+            WitxBuild::new()
+                .file(root.join("missing.witx"))
+                .out_dir(&root)
+                .cargo_metadata(false)
+                .generate(),
+            Err(BuildError::Load(_))
+        ));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
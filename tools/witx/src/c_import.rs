@@ -0,0 +1,1223 @@
+//! Draft witx modules from C headers, to bootstrap specifying an existing
+//! native API.
+//!
+//! [`import_header`] reads the structs, enums, typedefs and function
+//! prototypes of a header with a limited C parser: there is no preprocessor,
+//! so directives are skipped and macros aren't expanded. What it can't
+//! translate it skips, with a [`Note`] saying so. C doesn't say which
+//! pointers are lists, which params are outputs or which integers are
+//! errors, so the draft is a starting point to edit:
+//!
+//! * structs are records, enums are enums with `u32` tags, with the prefix
+//!   their cases share removed, and typedefs are aliases,
+//! * structs which are only declared are handles, and so are pointers to
+//!   them,
+//! * `const char *` is a string, and a pointer followed by a param naming
+//!   its length, like `data` and `data_len`, is a list,
+//! * other pointers are witx pointers, and arrays are lists,
+//! * types the header uses without defining are handles.
+//!
+//! ```
+//! let import = witx::c_import::import_header(
+//!     "typedef struct graph graph;
+//!      int load(const unsigned char *model, size_t model_len, graph **out);",
+//!     "nn",
+//! )
+//! .unwrap();
+//! assert!(import.source.contains("(param $model (list u8))"));
+//! import.document().unwrap();
+//! ```
+
+use crate::{Document, WitxError};
+use heck::SnakeCase;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CImportError {
+    #[error("Unterminated comment starting on line {line}")]
+    UnterminatedComment { line: usize },
+    #[error("Unbalanced braces or parentheses on line {line}")]
+    Unbalanced { line: usize },
+}
+
+/// Something in the header the draft leaves out or may get wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A draft witx document imported from a header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// The witx source of the draft.
+    pub source: String,
+    pub notes: Vec<Note>,
+}
+
+impl Import {
+    /// The draft, validated.
+    pub fn document(&self) -> Result<Document, WitxError> {
+        crate::parse(&self.source)
+    }
+}
+
+/// Convert the declarations of the C header `header` into a draft witx
+/// document, with its functions in the module `module`.
+pub fn import_header(header: &str, module: &str) -> Result<Import, CImportError> {
+    let tokens = lex(header)?;
+    let decls = declarations(&tokens)?;
+    let mut importer = Importer::default();
+    for decl in decls.iter() {
+        importer.declaration(decl);
+    }
+    Ok(importer.finish(module))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tok {
+    Ident(String),
+    Number(String),
+    Str,
+    Punct(char),
+    Comment(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    line: usize,
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, CImportError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let (mut i, mut line) = (0, 1);
+    let mut line_start = true;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = line;
+        if c == '\n' {
+            line += 1;
+            line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '#' && line_start {
+            // A directive, up to the end of its last continued line.
+            while i < chars.len() && chars[i] != '\n' {
+                if chars[i] == '\\' && chars.get(i + 1) == Some(&'\n') {
+                    line += 1;
+                    i += 1;
+                }
+                i += 1;
+            }
+            continue;
+        }
+        line_start = false;
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            let end = chars[i..]
+                .iter()
+                .position(|c| *c == '\n')
+                .map_or(chars.len(), |n| i + n);
+            let text = chars[i + 2..end].iter().collect::<String>();
+            push_comment(&mut tokens, comment_text(&text), start);
+            i = end;
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            let end = (i + 2..chars.len().saturating_sub(1))
+                .find(|j| chars[*j] == '*' && chars[j + 1] == '/')
+                .ok_or(CImportError::UnterminatedComment { line })?;
+            let text = chars[i + 2..end].iter().collect::<String>();
+            line += text.matches('\n').count();
+            let text = text
+                .lines()
+                .map(comment_text)
+                .collect::<Vec<_>>()
+                .join("\n");
+            push_comment(&mut tokens, text.trim().to_string(), start);
+            i = end + 2;
+        } else if c.is_alphabetic() || c == '_' {
+            let end = (i..chars.len())
+                .find(|j| !(chars[*j].is_alphanumeric() || chars[*j] == '_'))
+                .unwrap_or(chars.len());
+            let ident = chars[i..end].iter().collect();
+            tokens.push(Token {
+                tok: Tok::Ident(ident),
+                line,
+            });
+            i = end;
+        } else if c.is_ascii_digit() {
+            let end = (i..chars.len())
+                .find(|j| !(chars[*j].is_alphanumeric() || chars[*j] == '.'))
+                .unwrap_or(chars.len());
+            let number = chars[i..end].iter().collect();
+            tokens.push(Token {
+                tok: Tok::Number(number),
+                line,
+            });
+            i = end;
+        } else if c == '"' || c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            tokens.push(Token {
+                tok: Tok::Str,
+                line,
+            });
+            i += 1;
+        } else {
+            tokens.push(Token {
+                tok: Tok::Punct(c),
+                line,
+            });
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/// A line of a comment without its decoration: the `*`s and `/`s of doc
+/// comments like `/** ... */` and `/// ...`.
+fn comment_text(line: &str) -> String {
+    line.trim()
+        .trim_start_matches(['*', '/', '!', '<'])
+        .trim()
+        .to_string()
+}
+
+/// Adds a comment, joined with the comment right before it: consecutive
+/// `//` lines are one comment.
+fn push_comment(tokens: &mut Vec<Token>, text: String, line: usize) {
+    if let Some(Token {
+        tok: Tok::Comment(prev),
+        line: prev_line,
+    }) = tokens.last_mut()
+    {
+        if *prev_line + prev.matches('\n').count() + 1 == line {
+            prev.push('\n');
+            prev.push_str(&text);
+            return;
+        }
+    }
+    tokens.push(Token {
+        tok: Tok::Comment(text),
+        line,
+    });
+}
+
+/// A top-level declaration: its tokens without comments, the comment right
+/// before it, and the line it starts on. Comments inside braces are kept,
+/// as the docs of members.
+#[derive(Debug)]
+struct Decl {
+    docs: String,
+    line: usize,
+    tokens: Vec<Token>,
+}
+
+impl Decl {
+    fn tok(&self, i: usize) -> Option<&Tok> {
+        self.tokens.get(i).map(|t| &t.tok)
+    }
+}
+
+fn declarations(tokens: &[Token]) -> Result<Vec<Decl>, CImportError> {
+    let mut decls = Vec::new();
+    let mut docs = String::new();
+    let mut current: Vec<Token> = Vec::new();
+    // The depth of braces and parens, and of `extern "C" {` blocks, which are
+    // skipped through.
+    let (mut depth, mut externs) = (0usize, 0usize);
+    let mut i = 0;
+    while i < tokens.len() {
+        let t = &tokens[i];
+        i += 1;
+        match &t.tok {
+            Tok::Comment(text) if current.is_empty() => {
+                docs = text.clone();
+                continue;
+            }
+            Tok::Comment(_) if depth == 0 => continue,
+            Tok::Ident(kw) if kw == "extern" && current.is_empty() => {
+                if let (Some(Tok::Str), Some(Tok::Punct('{'))) = (
+                    tokens.get(i).map(|t| &t.tok),
+                    tokens.get(i + 1).map(|t| &t.tok),
+                ) {
+                    externs += 1;
+                    i += 2;
+                    docs.clear();
+                    continue;
+                }
+            }
+            Tok::Punct('}') if depth == 0 && current.is_empty() && externs > 0 => {
+                externs -= 1;
+                continue;
+            }
+            _ => {}
+        }
+        match t.tok {
+            Tok::Punct('{') | Tok::Punct('(') | Tok::Punct('[') => depth += 1,
+            Tok::Punct('}') | Tok::Punct(')') | Tok::Punct(']') => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or(CImportError::Unbalanced { line: t.line })?;
+            }
+            _ => {}
+        }
+        current.push(t.clone());
+        // A declaration ends at a `;`, or after the body of a function
+        // definition.
+        let is_body = matches!(t.tok, Tok::Punct('}'))
+            && depth == 0
+            && current
+                .iter()
+                .rev()
+                .skip(1)
+                .any(|t| t.tok == Tok::Punct('{'))
+            && body_follows_params(&current);
+        if (depth == 0 && t.tok == Tok::Punct(';')) || is_body {
+            decls.push(Decl {
+                docs: std::mem::take(&mut docs),
+                line: current[0].line,
+                tokens: std::mem::take(&mut current),
+            });
+        }
+    }
+    if depth != 0 {
+        let line = tokens.last().map_or(1, |t| t.line);
+        return Err(CImportError::Unbalanced { line });
+    }
+    Ok(decls)
+}
+
+/// Whether the outermost braces of `tokens` follow a `)`, as the body of a
+/// function definition does.
+fn body_follows_params(tokens: &[Token]) -> bool {
+    let mut depth = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        match t.tok {
+            Tok::Punct('(') => depth += 1,
+            Tok::Punct(')') => depth -= 1,
+            Tok::Punct('{') if depth == 0 => {
+                return i > 0 && tokens[i - 1].tok == Tok::Punct(')');
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// A C type, as far as the draft needs it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CType {
+    Void,
+    /// A builtin, by its witx syntax.
+    Builtin(&'static str),
+    /// A typedef or struct, enum or union tag.
+    Named(String),
+    Pointer {
+        to: Box<CType>,
+        is_const: bool,
+    },
+    Array(Box<CType>),
+}
+
+/// A declarator: a type and the name it declares.
+#[derive(Debug)]
+struct Declarator {
+    ty: CType,
+    name: String,
+    docs: String,
+    line: usize,
+}
+
+/// The witx syntax of the builtin named by the C type specifiers `words`.
+fn builtin(words: &[&str]) -> Option<&'static str> {
+    let unsigned = words.contains(&"unsigned");
+    let longs = words.iter().filter(|w| **w == "long").count();
+    let ty = match words
+        .iter()
+        .find(|w| !["unsigned", "signed", "int"].contains(*w))
+    {
+        None => {
+            if unsigned {
+                "u32"
+            } else {
+                "s32"
+            }
+        }
+        Some(&"char") if unsigned => "u8",
+        Some(&"char") if words.contains(&"signed") => "s8",
+        Some(&"char") => "(@witx char8)",
+        Some(&"short") if unsigned => "u16",
+        Some(&"short") => "s16",
+        Some(&"long") if longs == 1 && unsigned => "(@witx usize)",
+        Some(&"long") if longs == 1 => "s64",
+        Some(&"long") if unsigned => "u64",
+        Some(&"long") => "s64",
+        Some(&"float") => "f32",
+        Some(&"double") => "f64",
+        Some(&"bool") | Some(&"_Bool") => "bool",
+        Some(&"uint8_t") => "u8",
+        Some(&"int8_t") => "s8",
+        Some(&"uint16_t") => "u16",
+        Some(&"int16_t") => "s16",
+        Some(&"uint32_t") => "u32",
+        Some(&"int32_t") => "s32",
+        Some(&"uint64_t") => "u64",
+        Some(&"int64_t") => "s64",
+        Some(&"size_t") | Some(&"uintptr_t") => "(@witx usize)",
+        Some(_) => return None,
+    };
+    Some(ty)
+}
+
+/// Words which qualify a declaration without changing its type.
+const QUALIFIERS: &[&str] = &[
+    "const",
+    "volatile",
+    "static",
+    "extern",
+    "inline",
+    "register",
+    "restrict",
+    "__restrict",
+];
+
+/// Words which are type specifiers by themselves.
+const SPECIFIERS: &[&str] = &[
+    "unsigned", "signed", "int", "char", "short", "long", "float", "double", "bool", "_Bool",
+    "void",
+];
+
+/// Parses the type specifiers at the start of `tokens`, with the number of
+/// tokens they take and whether they're `const`.
+fn specifiers(tokens: &[Token]) -> Option<(CType, usize, bool)> {
+    let mut words = Vec::new();
+    let mut is_const = false;
+    let mut i = 0;
+    while let Some(Tok::Ident(word)) = tokens.get(i).map(|t| &t.tok) {
+        if word == "const" {
+            is_const = true;
+        } else if QUALIFIERS.contains(&word.as_str()) {
+            // Storage and qualifiers other than `const` don't matter here.
+        } else if ["struct", "enum", "union"].contains(&word.as_str()) && words.is_empty() {
+            match tokens.get(i + 1).map(|t| &t.tok) {
+                Some(Tok::Ident(tag)) => {
+                    i += 2;
+                    while let Some(Tok::Ident(word)) = tokens.get(i).map(|t| &t.tok) {
+                        if !QUALIFIERS.contains(&word.as_str()) {
+                            break;
+                        }
+                        is_const |= word == "const";
+                        i += 1;
+                    }
+                    return Some((CType::Named(tag.clone()), i, is_const));
+                }
+                _ => return None,
+            }
+        } else if SPECIFIERS.contains(&word.as_str()) {
+            words.push(word.as_str());
+        } else if words.is_empty() {
+            // A typedef name, unless it's the name being declared.
+            let next = tokens.get(i + 1).map(|t| &t.tok);
+            if matches!(next, Some(Tok::Ident(_)) | Some(Tok::Punct('*'))) {
+                words.push(word.as_str());
+            } else {
+                break;
+            }
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    let ty = match words[..] {
+        [] => return None,
+        ["void"] => CType::Void,
+        _ => match builtin(&words) {
+            Some(b) => CType::Builtin(b),
+            None if words.len() == 1 => CType::Named(words[0].to_string()),
+            None => return None,
+        },
+    };
+    Some((ty, i, is_const))
+}
+
+/// Parses a declarator of type `base` from `tokens`: pointers, a name and
+/// any array suffix.
+fn declarator(base: &CType, base_const: bool, tokens: &[Token]) -> Option<(CType, String)> {
+    let mut ty = base.clone();
+    let mut is_const = base_const;
+    let mut i = 0;
+    loop {
+        match tokens.get(i).map(|t| &t.tok) {
+            Some(Tok::Punct('*')) => {
+                ty = CType::Pointer {
+                    to: Box::new(ty),
+                    is_const,
+                };
+                is_const = false;
+            }
+            Some(Tok::Ident(q)) if QUALIFIERS.contains(&q.as_str()) => is_const |= q == "const",
+            _ => break,
+        }
+        i += 1;
+    }
+    let name = match tokens.get(i).map(|t| &t.tok) {
+        Some(Tok::Ident(name)) => name.clone(),
+        _ => return None,
+    };
+    i += 1;
+    while let Some(Tok::Punct('[')) = tokens.get(i).map(|t| &t.tok) {
+        let close = tokens[i..].iter().position(|t| t.tok == Tok::Punct(']'))?;
+        ty = CType::Array(Box::new(ty));
+        i += close + 1;
+    }
+    if i != tokens.len() {
+        return None;
+    }
+    Some((ty, name))
+}
+
+/// Splits `tokens` at the commas outside of any brackets.
+fn split_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, t) in tokens.iter().enumerate() {
+        match t.tok {
+            Tok::Punct('(') | Tok::Punct('[') | Tok::Punct('{') => depth += 1,
+            Tok::Punct(')') | Tok::Punct(']') | Tok::Punct('}') => depth = depth.saturating_sub(1),
+            Tok::Punct(',') if depth == 0 => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+/// The witx name of the C name `name`, in snake case without a `_t` suffix.
+fn witx_name(name: &str) -> String {
+    let name = name.strip_suffix("_t").unwrap_or(name);
+    name.to_snake_case()
+}
+
+/// Docs as witx doc comments, indented by `indent`.
+fn doc_lines(docs: &str, indent: &str) -> String {
+    let mut out = String::new();
+    for line in docs.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            writeln!(out, "{};;;", indent).unwrap();
+        } else {
+            writeln!(out, "{};;; {}", indent, line).unwrap();
+        }
+    }
+    out
+}
+
+/// Names params with a length, as `data` has `data_len` or `len`.
+fn is_length_of(len: &str, ptr: &str) -> bool {
+    let len = len.to_lowercase();
+    let ptr = ptr.to_lowercase();
+    ["len", "length", "size", "count", "num", "n"]
+        .iter()
+        .any(|suffix| {
+            len == *suffix
+                || len == format!("{}_{}", ptr, suffix)
+                || len == format!("{}{}", ptr, suffix)
+                || len == format!("{}_{}", suffix, ptr)
+        })
+}
+
+#[derive(Default)]
+struct Importer {
+    /// Definitions, in the order of the header.
+    types: Vec<String>,
+    funcs: Vec<String>,
+    notes: Vec<Note>,
+    /// The witx names of the C names defined so far, by typedef or tag.
+    defined: HashMap<String, String>,
+    /// C names which are handles: structs which are only declared, and
+    /// typedefs of them or of pointers to them.
+    handles: HashSet<String>,
+    /// C names used without being defined, with their witx names.
+    undefined: Vec<(String, String)>,
+}
+
+impl Importer {
+    fn note(&mut self, line: usize, message: String) {
+        self.notes.push(Note { line, message });
+    }
+
+    fn declaration(&mut self, decl: &Decl) {
+        let line = decl.line;
+        match decl.tok(0) {
+            Some(Tok::Ident(kw)) if kw == "typedef" => self.typedef(decl),
+            Some(Tok::Ident(kw)) if ["struct", "enum", "union"].contains(&kw.as_str()) => {
+                match (decl.tok(1), decl.tok(2)) {
+                    (Some(Tok::Ident(tag)), Some(Tok::Punct('{'))) => {
+                        let tag = tag.clone();
+                        if let Some(body) = self.body(decl, 2) {
+                            self.aggregate(kw, &tag, &tag, body, &decl.docs, line);
+                        }
+                    }
+                    (Some(Tok::Ident(tag)), Some(Tok::Punct(';'))) if kw == "struct" => {
+                        self.handles.insert(tag.clone());
+                    }
+                    _ if decl.tokens.iter().any(|t| t.tok == Tok::Punct('(')) => {
+                        self.function(decl)
+                    }
+                    _ => self.note(line, "skipped an unsupported declaration".to_string()),
+                }
+            }
+            _ if decl.tokens.iter().any(|t| t.tok == Tok::Punct('(')) => self.function(decl),
+            _ => self.note(
+                line,
+                "skipped a declaration which isn't a type or function".into(),
+            ),
+        }
+    }
+
+    /// The tokens inside the braces opening at `open`, up to the matching
+    /// close.
+    fn body<'a>(&mut self, decl: &'a Decl, open: usize) -> Option<&'a [Token]> {
+        let mut depth = 0;
+        for (i, t) in decl.tokens.iter().enumerate().skip(open) {
+            match t.tok {
+                Tok::Punct('{') => depth += 1,
+                Tok::Punct('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(&decl.tokens[open + 1..i]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn typedef(&mut self, decl: &Decl) {
+        let line = decl.line;
+        let rest = &decl.tokens[1..decl.tokens.len() - 1];
+        // `typedef struct [tag] { ... } name;`
+        if let Some(Tok::Ident(kw)) = rest.first().map(|t| &t.tok) {
+            if ["struct", "enum", "union"].contains(&kw.as_str()) {
+                let (tag, open) = match rest.get(1).map(|t| &t.tok) {
+                    Some(Tok::Ident(tag)) => (Some(tag.clone()), 2),
+                    _ => (None, 1),
+                };
+                if let Some(Tok::Punct('{')) = rest.get(open).map(|t| &t.tok) {
+                    let close = open + 1 + self.body(decl, open + 1).map_or(0, |b| b.len());
+                    let names = split_commas(&rest[close + 1..]);
+                    let name = match names.first().and_then(|n| n.first()).map(|t| &t.tok) {
+                        Some(Tok::Ident(name)) if names[0].len() == 1 => name.clone(),
+                        _ => {
+                            self.note(line, "skipped a typedef of an unnamed type".into());
+                            return;
+                        }
+                    };
+                    let body = &rest[open + 1..close];
+                    let tag = tag.as_deref().unwrap_or(&name);
+                    self.aggregate(kw, tag, &name, body, &decl.docs, line);
+                    return;
+                }
+            }
+        }
+        if rest.iter().any(|t| t.tok == Tok::Punct('(')) {
+            self.note(line, "skipped a typedef of a function type".into());
+            return;
+        }
+        let (base, n, is_const) = match specifiers(rest) {
+            Some(s) => s,
+            None => {
+                self.note(line, "skipped a typedef of an unsupported type".into());
+                return;
+            }
+        };
+        for part in split_commas(&rest[n..]) {
+            let (ty, name) = match declarator(&base, is_const, part) {
+                Some(d) => d,
+                None => {
+                    self.note(line, "skipped a typedef of an unsupported type".into());
+                    continue;
+                }
+            };
+            let witx = witx_name(&name);
+            // `typedef struct tag name;` and `typedef struct tag *name;` of a
+            // struct which isn't defined make `name` a handle.
+            let opaque = |ty: &CType| match ty {
+                CType::Named(tag) => !self.defined.contains_key(tag),
+                _ => false,
+            };
+            let handle = match &ty {
+                CType::Named(tag) if opaque(&ty) || *tag == name => Some(tag.clone()),
+                CType::Pointer { to, .. } if opaque(to) => match &**to {
+                    CType::Named(tag) => Some(tag.clone()),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let definition = match handle {
+                Some(tag) => {
+                    self.handles.insert(tag.clone());
+                    self.handles.insert(name.clone());
+                    self.defined.insert(tag, witx.clone());
+                    "(handle)".to_string()
+                }
+                None => self.witx_type(&ty, line),
+            };
+            self.defined.insert(name.clone(), witx.clone());
+            let mut out = doc_lines(&decl.docs, "");
+            writeln!(out, "(typename ${} {})", witx, definition).unwrap();
+            self.types.push(out);
+        }
+    }
+
+    /// Defines a struct, enum or union `kind` named `name`, with the members
+    /// `body`.
+    fn aggregate(
+        &mut self,
+        kind: &str,
+        tag: &str,
+        name: &str,
+        body: &[Token],
+        docs: &str,
+        line: usize,
+    ) {
+        let witx = witx_name(name);
+        let definition = match kind {
+            "struct" => self.record(body),
+            "enum" => self.enumeration(body, line),
+            _ => {
+                self.note(
+                    line,
+                    format!(
+                        "skipped union `{}`: C unions have no tag to say which case is set",
+                        name
+                    ),
+                );
+                None
+            }
+        };
+        if let Some(definition) = definition {
+            self.defined.insert(tag.to_string(), witx.clone());
+            self.defined.insert(name.to_string(), witx.clone());
+            let mut out = doc_lines(docs, "");
+            write!(out, "(typename ${}\n  {})\n", witx, definition).unwrap();
+            self.types.push(out);
+        }
+    }
+
+    /// The members of a struct or enum body, with the docs before each.
+    fn members(body: &[Token]) -> Vec<(String, Vec<Token>)> {
+        let mut members = Vec::new();
+        let mut docs = String::new();
+        let mut current = Vec::new();
+        for t in body {
+            match &t.tok {
+                Tok::Comment(text) if current.is_empty() => docs = text.clone(),
+                Tok::Comment(_) => {}
+                Tok::Punct(';') | Tok::Punct(',') => {
+                    members.push((std::mem::take(&mut docs), std::mem::take(&mut current)));
+                }
+                _ => current.push(t.clone()),
+            }
+        }
+        if !current.is_empty() {
+            members.push((docs, current));
+        }
+        members
+    }
+
+    fn record(&mut self, body: &[Token]) -> Option<String> {
+        let mut fields = Vec::new();
+        let mut docs = String::new();
+        let mut current: Vec<Token> = Vec::new();
+        for t in body {
+            match &t.tok {
+                Tok::Comment(text) if current.is_empty() => docs = text.clone(),
+                Tok::Comment(_) => {}
+                Tok::Punct(';') => {
+                    let (base, n, is_const) = match specifiers(&current) {
+                        Some(s) if !current.iter().any(|t| t.tok == Tok::Punct('{')) => s,
+                        _ => {
+                            self.note(t.line, "skipped a struct with an unsupported field".into());
+                            return None;
+                        }
+                    };
+                    for part in split_commas(&current[n..]) {
+                        let (part, width) = match part.iter().position(|t| t.tok == Tok::Punct(':'))
+                        {
+                            Some(colon) => (&part[..colon], true),
+                            None => (part, false),
+                        };
+                        if width {
+                            self.note(t.line, "bit field widths aren't kept".into());
+                        }
+                        match declarator(&base, is_const, part) {
+                            Some((ty, name)) => fields.push(Declarator {
+                                ty,
+                                name,
+                                docs: docs.clone(),
+                                line: t.line,
+                            }),
+                            None => {
+                                self.note(
+                                    t.line,
+                                    "skipped a struct with an unsupported field".into(),
+                                );
+                                return None;
+                            }
+                        }
+                    }
+                    docs.clear();
+                    current.clear();
+                }
+                _ => current.push(t.clone()),
+            }
+        }
+        let mut out = "(record".to_string();
+        for field in fields {
+            if let CType::Array(_) = field.ty {
+                self.note(
+                    field.line,
+                    format!("array field `{}` is a list, without its length", field.name),
+                );
+            }
+            let ty = self.witx_type(&field.ty, field.line);
+            out.push('\n');
+            out.push_str(&doc_lines(&field.docs, "    "));
+            write!(out, "    (field ${} {})", field.name.to_snake_case(), ty).unwrap();
+        }
+        out.push(')');
+        Some(out)
+    }
+
+    fn enumeration(&mut self, body: &[Token], line: usize) -> Option<String> {
+        let members = Self::members(body);
+        let mut cases = Vec::new();
+        for (docs, tokens) in members.iter() {
+            match tokens.first().map(|t| &t.tok) {
+                Some(Tok::Ident(name)) => {
+                    let value = match &tokens[1..] {
+                        [] => Some(None),
+                        [eq, value] if eq.tok == Tok::Punct('=') => match &value.tok {
+                            Tok::Number(n) => parse_int(n).map(Some),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    match value {
+                        Some(Some(v)) if v != cases.len() as u64 => {
+                            self.note(
+                                tokens[0].line,
+                                format!(
+                                    "enum case `{}` is numbered {} in C, and {} in witx",
+                                    name,
+                                    v,
+                                    cases.len()
+                                ),
+                            );
+                        }
+                        None => self.note(
+                            tokens[0].line,
+                            format!("the value of enum case `{}` isn't kept", name),
+                        ),
+                        _ => {}
+                    }
+                    cases.push((docs.clone(), name.clone()));
+                }
+                _ => {
+                    self.note(line, "skipped an enum with an unsupported case".into());
+                    return None;
+                }
+            }
+        }
+        if cases.is_empty() {
+            self.note(line, "skipped an enum without cases".into());
+            return None;
+        }
+        let names = cases.iter().map(|(_, n)| n.as_str()).collect::<Vec<_>>();
+        let prefix = common_prefix(&names);
+        let mut out = "(enum (@witx tag u32)".to_string();
+        for (docs, name) in cases.iter() {
+            out.push('\n');
+            out.push_str(&doc_lines(docs, "    "));
+            write!(out, "    ${}", name[prefix..].to_snake_case()).unwrap();
+        }
+        out.push(')');
+        Some(out)
+    }
+
+    fn function(&mut self, decl: &Decl) {
+        let line = decl.line;
+        let tokens = decl
+            .tokens
+            .iter()
+            .filter(|t| !matches!(t.tok, Tok::Comment(_)))
+            .cloned()
+            .collect::<Vec<_>>();
+        let open = tokens
+            .iter()
+            .position(|t| t.tok == Tok::Punct('('))
+            .unwrap();
+        let mut depth = 0;
+        let close = tokens[open..]
+            .iter()
+            .position(|t| {
+                match t.tok {
+                    Tok::Punct('(') => depth += 1,
+                    Tok::Punct(')') => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|n| open + n);
+        let parsed = specifiers(&tokens[..open]).and_then(|(base, n, is_const)| {
+            let (ret, name) = declarator(&base, is_const, &tokens[n..open])?;
+            Some((ret, name, close?))
+        });
+        let (ret, name, close) = match parsed {
+            Some(p) => p,
+            None => {
+                self.note(line, "skipped an unsupported function declaration".into());
+                return;
+            }
+        };
+        let mut params = Vec::new();
+        let inner = &tokens[open + 1..close];
+        let is_void = matches!(inner, [t] if t.tok == Tok::Ident("void".into()));
+        if !inner.is_empty() && !is_void {
+            for (i, part) in split_commas(inner).into_iter().enumerate() {
+                let param = specifiers(part).and_then(|(base, n, is_const)| {
+                    match declarator(&base, is_const, &part[n..]) {
+                        Some(d) => Some(d),
+                        // An unnamed param.
+                        None if n == part.len() => Some((base, format!("arg{}", i))),
+                        None => {
+                            let mut named = part[n..].to_vec();
+                            named.push(Token {
+                                tok: Tok::Ident(format!("arg{}", i)),
+                                line,
+                            });
+                            declarator(&base, is_const, &named)
+                        }
+                    }
+                });
+                match param {
+                    Some((ty, name)) => params.push((ty, name)),
+                    None => {
+                        self.note(
+                            line,
+                            format!("skipped function `{}`: unsupported param", name),
+                        );
+                        return;
+                    }
+                }
+            }
+        }
+
+        let mut out = String::new();
+        out.push_str(&doc_lines(&decl.docs, "  "));
+        write!(out, "  (@interface func (export \"{}\")", name).unwrap();
+        let mut i = 0;
+        while i < params.len() {
+            let (ty, pname) = &params[i];
+            let length = params.get(i + 1).filter(|(lty, lname)| {
+                matches!(lty, CType::Builtin(b) if !b.starts_with('f') && *b != "bool")
+                    && is_length_of(lname, pname)
+            });
+            let ty = match (ty, length) {
+                (CType::Pointer { to, .. }, Some(_)) if **to != CType::Void => {
+                    i += 1;
+                    let elem = match &**to {
+                        CType::Builtin("(@witx char8)") => "u8".to_string(),
+                        to => self.witx_type(to, line),
+                    };
+                    format!("(list {})", elem)
+                }
+                _ => self.witx_type(ty, line),
+            };
+            write!(out, "\n    (param ${} {})", pname.to_snake_case(), ty).unwrap();
+            i += 1;
+        }
+        if ret != CType::Void {
+            let ty = self.witx_type(&ret, line);
+            write!(out, "\n    (result $result {})", ty).unwrap();
+        }
+        out.push_str(")\n");
+        self.funcs.push(out);
+    }
+
+    /// The witx syntax of `ty`.
+    fn witx_type(&mut self, ty: &CType, line: usize) -> String {
+        match ty {
+            CType::Void => {
+                self.note(line, "`void` has no witx type, and is a `u8`".into());
+                "u8".to_string()
+            }
+            CType::Builtin(b) => b.to_string(),
+            CType::Named(name) => {
+                if let Some(witx) = self.defined.get(name) {
+                    return format!("${}", witx);
+                }
+                let witx = witx_name(name);
+                if !self.undefined.iter().any(|(n, _)| n == name) {
+                    if !self.handles.contains(name) {
+                        self.note(
+                            line,
+                            format!("`{}` isn't defined in the header, and is a handle", name),
+                        );
+                    }
+                    self.handles.insert(name.clone());
+                    self.undefined.push((name.clone(), witx.clone()));
+                }
+                format!("${}", witx)
+            }
+            CType::Pointer { to, is_const } => match &**to {
+                CType::Builtin("(@witx char8)") if *is_const => "string".to_string(),
+                // A pointer to an opaque struct is its handle.
+                CType::Named(name)
+                    if self.handles.contains(name) || !self.defined.contains_key(name) =>
+                {
+                    self.witx_type(to, line)
+                }
+                CType::Void => format!("(@witx {} u8)", pointer(*is_const)),
+                to => format!(
+                    "(@witx {} {})",
+                    pointer(*is_const),
+                    self.witx_type(to, line)
+                ),
+            },
+            CType::Array(elem) => format!("(list {})", self.witx_type(elem, line)),
+        }
+    }
+
+    fn finish(self, module: &str) -> Import {
+        let mut source = String::new();
+        source.push_str(
+            ";; A draft imported from a C header. Review every definition: C doesn't\n\
+             ;; say which pointers are lists, which params are outputs, or which\n\
+             ;; integers are errors.\n",
+        );
+        for (_, witx) in self.undefined.iter() {
+            if !self.defined.values().any(|d| d == witx) {
+                write!(source, "\n(typename ${} (handle))\n", witx).unwrap();
+            }
+        }
+        for ty in self.types.iter() {
+            source.push('\n');
+            source.push_str(ty);
+        }
+        write!(
+            source,
+            "\n(module ${}\n  (import \"memory\" (memory))\n",
+            witx_name(module)
+        )
+        .unwrap();
+        for func in self.funcs.iter() {
+            source.push('\n');
+            source.push_str(func);
+        }
+        source.push_str(")\n");
+        Import {
+            source,
+            notes: self.notes,
+        }
+    }
+}
+
+fn pointer(is_const: bool) -> &'static str {
+    if is_const {
+        "const_pointer"
+    } else {
+        "pointer"
+    }
+}
+
+fn parse_int(n: &str) -> Option<u64> {
+    let n = n.trim_end_matches(['u', 'U', 'l', 'L']);
+    match n.strip_prefix("0x").or_else(|| n.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => n.parse().ok(),
+    }
+}
+
+/// The length of the prefix up to an `_` that all of `names` share, without
+/// leaving any of them empty or starting with a digit.
+fn common_prefix(names: &[&str]) -> usize {
+    if names.len() < 2 {
+        return 0;
+    }
+    let first = names[0];
+    let mut prefix = 0;
+    for (i, _) in first.match_indices('_') {
+        let candidate = &first[..=i];
+        let fits = names.iter().all(|n| {
+            n.starts_with(candidate)
+                && n.len() > candidate.len()
+                && !n[candidate.len()..].starts_with(|c: char| c.is_ascii_digit())
+        });
+        if !fits {
+            break;
+        }
+        prefix = i + 1;
+    }
+    prefix
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Id;
+
+    const HEADER: &str = r#"
+#ifndef NN_H
+#define NN_H \
+    1
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/** The type of the elements of a tensor. */
+typedef enum {
+    NN_TENSOR_F16,
+    NN_TENSOR_F32,
+    /* Unsigned bytes. */
+    NN_TENSOR_U8 = 2,
+} nn_tensor_type_t;
+
+// A tensor.
+// Its data is laid out in row-major order.
+typedef struct nn_tensor {
+    /// The size of each dimension.
+    const uint32_t *dims;
+    size_t rank;
+    nn_tensor_type_t type;
+    unsigned char data[16];
+} nn_tensor_t;
+
+typedef struct nn_graph nn_graph_t;
+struct nn_context;
+typedef int nn_status;
+
+union nn_value { int i; float f; };
+
+/// Loads a graph from the bytes of a model.
+nn_status nn_load(const unsigned char *model, size_t model_len, nn_graph_t **graph);
+nn_status nn_init(nn_graph_t *graph, struct nn_context **context);
+nn_status nn_set_input(struct nn_context *context, uint32_t index, const nn_tensor_t *tensor);
+int nn_version(void);
+void nn_log(const char *message, nn_device_t device);
+static inline int nn_twice(int x) { return x * 2; }
+
+#ifdef __cplusplus
+}
+#endif
+#endif
+"#;
+
+    #[test]
+    fn header() {
+        let import = import_header(HEADER, "nn").unwrap();
+        let doc = import
+            .document()
+            .unwrap_or_else(|e| panic!("{}\n{}", e, import.source));
+        assert!(import.source.contains(
+            ";;; The type of the elements of a tensor.\n\
+             (typename $nn_tensor_type\n  (enum (@witx tag u32)\n    $f16\n    $f32\n    \
+             ;;; Unsigned bytes.\n    $u8))"
+        ));
+        assert!(import.source.contains(
+            ";;; A tensor.\n;;; Its data is laid out in row-major order.\n(typename $nn_tensor\n"
+        ));
+        assert!(import.source.contains(
+            "    ;;; The size of each dimension.\n    (field $dims (@witx const_pointer u32))\n"
+        ));
+        assert!(import.source.contains("    (field $data (list u8)))"));
+        assert!(import.source.contains("(typename $nn_graph (handle))"));
+        assert!(import.source.contains("(typename $nn_status s32)"));
+        assert!(import.source.contains(
+            "  ;;; Loads a graph from the bytes of a model.\n  (@interface func (export \"nn_load\")\n    \
+             (param $model (list u8))\n    (param $graph (@witx pointer $nn_graph))\n    \
+             (result $result $nn_status))"
+        ));
+        assert!(import.source.contains(
+            "    (param $graph $nn_graph)\n    (param $context (@witx pointer $nn_context))"
+        ));
+        assert!(import
+            .source
+            .contains("(@interface func (export \"nn_version\")\n    (result $result s32))"));
+        assert!(import
+            .source
+            .contains("    (param $message string)\n    (param $device $nn_device)"));
+
+        let module = doc.module(&Id::new("nn")).unwrap();
+        assert_eq!(module.funcs().count(), 6);
+        assert!(doc.typename(&Id::new("nn_context")).is_some());
+        assert!(doc.typename(&Id::new("nn_value")).is_none());
+
+        let notes = import
+            .notes
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            notes,
+            vec![
+                "line 26: array field `data` is a list, without its length",
+                "line 33: skipped union `nn_value`: C unions have no tag to say which case is set",
+                "line 40: `nn_device_t` isn't defined in the header, and is a handle",
+            ]
+        );
+    }
+
+    #[test]
+    fn enum_values() {
+        let import = import_header("enum color { RED = 1, GREEN, BLUE = 1 << 2 };", "m").unwrap();
+        assert!(import.source.contains("$red\n    $green\n    $blue"));
+        assert_eq!(
+            import
+                .notes
+                .iter()
+                .map(|n| n.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "enum case `RED` is numbered 1 in C, and 0 in witx",
+                "the value of enum case `BLUE` isn't kept",
+            ]
+        );
+        import.document().unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            import_header("int f(void);\n/* open", "m"),
+            Err(CImportError::UnterminatedComment { line: 2 })
+        );
+        assert_eq!(
+            import_header("struct s {\n int x;", "m"),
+            Err(CImportError::Unbalanced { line: 2 })
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! A compact binary format for validated documents, enabled with the `cache`
+//! feature.
+//!
+//! Build scripts which generate bindings from witx can use [`load`] in place
+//! of [`crate::load`] to skip parsing and validation when none of the source
+//! files have changed. A cache records the content hash of every file read
+//! while loading the document, including those reached through `use`, and is
+//! only used while all of them still match.
+//!
+//! Encoded documents start with a header naming the version of this crate that
+//! wrote them, and are rejected by any other version.
+
+use crate::fingerprint::Fingerprint;
+use crate::{Document, Filesystem, WitxError, WitxIo};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+const MAGIC: &[u8] = b"witxast\0";
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("not an encoded witx document")]
+    Format,
+    #[error("encoded by witx {0}")]
+    Version(String),
+    #[error("malformed encoded document")]
+    Decode(#[from] postcard::Error),
+}
+
+fn encode_with_header<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    let bytes_of = |r: postcard::Result<Vec<u8>>| r.expect("in-memory encoding can't fail");
+    bytes.extend(bytes_of(postcard::to_stdvec(env!("CARGO_PKG_VERSION"))));
+    bytes.extend(bytes_of(postcard::to_stdvec(value)));
+    bytes
+}
+
+fn decode_with_header<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, CacheError> {
+    let bytes = bytes.strip_prefix(MAGIC).ok_or(CacheError::Format)?;
+    let (version, bytes) = postcard::take_from_bytes::<String>(bytes)?;
+    if version != env!("CARGO_PKG_VERSION") {
+        return Err(CacheError::Version(version));
+    }
+    Ok(postcard::from_bytes(bytes)?)
+}
+
+/// Encode a document in the binary format.
+pub fn encode(doc: &Document) -> Vec<u8> {
+    encode_with_header(doc)
+}
+
+/// Decode a document encoded with [`encode`].
+pub fn decode(bytes: &[u8]) -> Result<Document, CacheError> {
+    decode_with_header(bytes)
+}
+
+/// A document, and the hashes of the sources it was loaded from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedDocument {
+    roots: Vec<PathBuf>,
+    sources: Vec<(PathBuf, Fingerprint)>,
+    document: Document,
+}
+
+/// Records the hash of each file read through it.
+struct Recorder<'a> {
+    io: &'a dyn WitxIo,
+    sources: RefCell<Vec<(PathBuf, Fingerprint)>>,
+}
+
+impl WitxIo for Recorder<'_> {
+    fn fgets(&self, path: &Path) -> Result<String, WitxError> {
+        let source = self.io.fgets(path)?;
+        self.sources
+            .borrow_mut()
+            .push((path.to_path_buf(), Fingerprint::of_source(&source)));
+        Ok(source)
+    }
+    fn fget_line(&self, path: &Path, line_num: usize) -> Result<String, WitxError> {
+        self.io.fget_line(path, line_num)
+    }
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, WitxError> {
+        self.io.canonicalize(path)
+    }
+    fn size(&self, path: &Path) -> Option<u64> {
+        self.io.size(path)
+    }
+}
+
+impl CachedDocument {
+    /// Load the document at `paths`, recording the sources it is made of.
+    pub fn load_with<P: AsRef<Path>>(paths: &[P], io: &dyn WitxIo) -> Result<Self, WitxError> {
+        let recorder = Recorder {
+            io,
+            sources: RefCell::new(Vec::new()),
+        };
+        let document = crate::toplevel::parse_witx_with(paths, &recorder)?;
+        Ok(CachedDocument {
+            roots: paths.iter().map(|p| p.as_ref().to_path_buf()).collect(),
+            sources: recorder.sources.into_inner(),
+            document,
+        })
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    pub fn into_document(self) -> Document {
+        self.document
+    }
+
+    /// The files the document was loaded from, and the hashes of their
+    /// contents at the time.
+    pub fn sources(&self) -> impl Iterator<Item = (&Path, Fingerprint)> {
+        self.sources.iter().map(|(p, fp)| (p.as_path(), *fp))
+    }
+
+    /// Whether loading `paths` now would give the same document: the paths
+    /// are the same ones, and every source is unchanged.
+    pub fn is_fresh<P: AsRef<Path>>(&self, paths: &[P], io: &dyn WitxIo) -> bool {
+        self.roots.len() == paths.len()
+            && self.roots.iter().zip(paths).all(|(r, p)| r == p.as_ref())
+            && self.sources.iter().all(|(path, fp)| match io.fgets(path) {
+                Ok(source) => Fingerprint::of_source(&source) == *fp,
+                Err(_) => false,
+            })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_with_header(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CacheError> {
+        decode_with_header(bytes)
+    }
+}
+
+/// Load a witx document from the filesystem, using the cache at `cache` if
+/// it is fresh, and otherwise replacing it.
+pub fn load<P: AsRef<Path>>(paths: &[P], cache: &Path) -> Result<Document, WitxError> {
+    load_with(paths, cache, &Filesystem)
+}
+
+/// Like [`load`], reading the witx sources through `io`.
+///
+/// A cache which can't be read or is stale is a miss, and failing to write
+/// the new cache only logs a warning.
+pub fn load_with<P: AsRef<Path>>(
+    paths: &[P],
+    cache: &Path,
+    io: &dyn WitxIo,
+) -> Result<Document, WitxError> {
+    match std::fs::read(cache) {
+        Ok(bytes) => match CachedDocument::from_bytes(&bytes) {
+            Ok(cached) if cached.is_fresh(paths, io) => return Ok(cached.into_document()),
+            Ok(_) => log::debug!("cache {:?} is stale", cache),
+            Err(e) => log::debug!("cache {:?} is unusable: {}", cache, e),
+        },
+        Err(e) => log::debug!("no cache at {:?}: {}", cache, e),
+    }
+    let cached = CachedDocument::load_with(paths, io)?;
+    if let Err(e) = std::fs::write(cache, cached.to_bytes()) {
+        log::warn!("failed to write cache {:?}: {}", cache, e);
+    }
+    Ok(cached.into_document())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockFs;
+
+    const A: &str = "(use \"b\")
+        (typename $r (record (field $x $e) (field $y (list u8))))
+        (module $m
+            (import \"memory\" (memory))
+            (@interface func (export \"f\")
+                (param $r $r)
+                (result $error (expected $e (error $e)))))";
+    const B: &str = "(typename $e (enum $p $q))";
+
+    #[test]
+    fn roundtrip() {
+        let doc = crate::toplevel::parse_witx_with(&["/a"], MockFs::new(&[("/a", A), ("/b", B)]))
+            .unwrap();
+        let decoded = decode(&encode(&doc)).unwrap();
+        assert_eq!(doc, decoded);
+        assert_eq!(doc.fingerprint(), decoded.fingerprint());
+
+        let mut bytes = encode(&doc);
+        bytes[0] = b'x';
+        assert!(matches!(decode(&bytes), Err(CacheError::Format)));
+    }
+
+    #[test]
+    fn freshness() {
+        let io = MockFs::new(&[("/a", A), ("/b", B)]);
+        let cached = CachedDocument::load_with(&["/a"], &io).unwrap();
+        let cached = CachedDocument::from_bytes(&cached.to_bytes()).unwrap();
+        assert_eq!(cached.sources().count(), 2);
+        assert!(cached.is_fresh(&["/a"], &io));
+        assert!(!cached.is_fresh(&["/b"], &io));
+
+        let changed = MockFs::new(&[("/a", A), ("/b", "(typename $e (enum $p $q $r))")]);
+        assert!(!cached.is_fresh(&["/a"], &changed));
+    }
+}
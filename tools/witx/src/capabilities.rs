@@ -0,0 +1,226 @@
+//! Optional features of a document, and which implementations support them.
+//!
+//! Features are declared with `(@witx feature $name)` annotations. On an enum
+//! every case is a capability of its own, named `feature.case`, such as the
+//! execution targets or graph encodings a backend supports. On a function the
+//! function belongs to the feature, such as an asynchronous variant of
+//! `compute`, and the feature's name is the capability.
+
+use crate::{Document, Id, Type};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// An optional capability an implementation may support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    /// The feature the capability belongs to.
+    pub feature: Id,
+    pub kind: CapabilityKind,
+    pub docs: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityKind {
+    /// A case of the enum `ty`.
+    Case { ty: Id, case: Id },
+    /// Functions gated on the feature, as module and function names.
+    Funcs(Vec<(Id, Id)>),
+}
+
+impl Capability {
+    /// The name implementations use to declare support for the capability.
+    pub fn name(&self) -> String {
+        match &self.kind {
+            CapabilityKind::Case { case, .. } => {
+                format!("{}.{}", self.feature.as_str(), case.as_str())
+            }
+            CapabilityKind::Funcs(_) => self.feature.as_str().to_string(),
+        }
+    }
+}
+
+impl Document {
+    /// The capabilities declared by feature annotations, in the order they
+    /// are declared.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = Vec::new();
+        for nt in self.typenames() {
+            if let Type::Variant(v) = &**nt.type_() {
+                if let Some(feature) = &v.feature {
+                    for case in v.cases.iter() {
+                        capabilities.push(Capability {
+                            feature: feature.clone(),
+                            kind: CapabilityKind::Case {
+                                ty: nt.name.clone(),
+                                case: case.name.clone(),
+                            },
+                            docs: case.docs.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        for m in self.modules() {
+            for f in m.funcs() {
+                let feature = match &f.feature {
+                    Some(feature) => feature,
+                    None => continue,
+                };
+                let func = (m.name.clone(), f.name.clone());
+                let existing = capabilities
+                    .iter_mut()
+                    .find(|c| c.feature == *feature && matches!(c.kind, CapabilityKind::Funcs(_)));
+                match existing {
+                    Some(Capability {
+                        kind: CapabilityKind::Funcs(funcs),
+                        ..
+                    }) => funcs.push(func),
+                    _ => capabilities.push(Capability {
+                        feature: feature.clone(),
+                        kind: CapabilityKind::Funcs(vec![func]),
+                        docs: String::new(),
+                    }),
+                }
+            }
+        }
+        capabilities
+    }
+}
+
+/// The capabilities an implementation supports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Implementation {
+    pub name: String,
+    /// Names of supported capabilities, as given by [`Capability::name`].
+    pub supports: Vec<String>,
+}
+
+impl FromStr for Implementation {
+    type Err = String;
+    /// Parse `name=capability,capability,...`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, supports) = match s.find('=') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => return Err(format!("expected `name=capability,...`, found `{}`", s)),
+        };
+        Ok(Implementation {
+            name: name.to_string(),
+            supports: supports
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CapabilityError {
+    #[error("implementation `{implementation}` supports unknown capability `{capability}`")]
+    Unknown {
+        implementation: String,
+        capability: String,
+    },
+}
+
+/// Which capabilities of a document each implementation supports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupportMatrix {
+    pub capabilities: Vec<Capability>,
+    pub implementations: Vec<Implementation>,
+}
+
+impl SupportMatrix {
+    /// Check that `implementations` only claim capabilities declared by `doc`.
+    pub fn new(
+        doc: &Document,
+        implementations: Vec<Implementation>,
+    ) -> Result<Self, CapabilityError> {
+        let capabilities = doc.capabilities();
+        for i in implementations.iter() {
+            for s in i.supports.iter() {
+                if !capabilities.iter().any(|c| c.name() == *s) {
+                    return Err(CapabilityError::Unknown {
+                        implementation: i.name.clone(),
+                        capability: s.clone(),
+                    });
+                }
+            }
+        }
+        Ok(SupportMatrix {
+            capabilities,
+            implementations,
+        })
+    }
+
+    pub fn supports(&self, implementation: &Implementation, capability: &Capability) -> bool {
+        implementation.supports.contains(&capability.name())
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn doc() -> Document {
+        crate::parse(
+            "(typename $target (enum (@witx tag u8) (@witx feature $target) $cpu $gpu))
+             (typename $errno (enum $success $busy))
+             (module $nn
+                (@interface func (export \"compute\"))
+                (@interface func (export \"compute_async\") (@witx feature $async))
+                (@interface func (export \"wait\") (@witx feature $async)))",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn capabilities() {
+        let names = doc()
+            .capabilities()
+            .iter()
+            .map(|c| c.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["target.cpu", "target.gpu", "async"]);
+        match &doc().capabilities()[2].kind {
+            CapabilityKind::Funcs(funcs) => assert_eq!(funcs.len(), 2),
+            k => panic!("unexpected {:?}", k),
+        }
+    }
+
+    #[test]
+    fn matrix() {
+        let cpu = "openvino=target.cpu".parse::<Implementation>().unwrap();
+        let matrix = SupportMatrix::new(&doc(), vec![cpu]).unwrap();
+        let i = &matrix.implementations[0];
+        assert!(matrix.supports(i, &matrix.capabilities[0]));
+        assert!(!matrix.supports(i, &matrix.capabilities[1]));
+
+        let tpu = "x=target.tpu".parse::<Implementation>().unwrap();
+        assert_eq!(
+            SupportMatrix::new(&doc(), vec![tpu]),
+            Err(CapabilityError::Unknown {
+                implementation: "x".to_string(),
+                capability: "target.tpu".to_string(),
+            })
+        );
+        assert!("no-equals".parse::<Implementation>().is_err());
+    }
+
+    #[test]
+    fn feature_round_trips() {
+        let doc = doc();
+        let rendered = doc.to_string();
+        assert!(rendered.contains("(@witx feature $target)"));
+        assert!(rendered.contains("(@witx feature $async)"));
+        assert_eq!(crate::parse(&rendered).unwrap(), doc);
+    }
+}
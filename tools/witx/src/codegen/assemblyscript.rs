@@ -0,0 +1,298 @@
+//! AssemblyScript declarations of the types and imports of a document.
+//!
+//! The declarations follow the conventions of AssemblyScript's own WASI
+//! bindings: types are aliases of their core wasm representation, enum cases
+//! and flags are constants in a namespace merged with their type, and imports
+//! are `@external` functions. Lists have no type of their own and are passed
+//! as a pointer and a length wherever they are used. Records which can't be
+//! described by an `@unmanaged` class, because they contain other records or
+//! unions, are described by their size and field offsets instead.
+
+use super::{doc_comment, file_stem, Options, OutputFile};
+use crate::{
+    BuiltinType, Document, IntRepr, InterfaceFunc, Layout, Module, NamedType, Type, TypeRef,
+};
+use heck::ShoutySnakeCase;
+use std::fmt::Write;
+
+pub(super) fn generate(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let mut body = String::new();
+    for nt in doc.typenames() {
+        body.push('\n');
+        define_type(&mut body, &nt);
+    }
+    for c in doc.constants() {
+        body.push('\n');
+        comment(&mut body, &c.docs, "");
+        writeln!(
+            body,
+            "export const {}_{}: {} = {};",
+            c.ty.as_str().to_shouty_snake_case(),
+            c.name.as_str().to_shouty_snake_case(),
+            c.ty.as_str(),
+            c.value
+        )
+        .unwrap();
+    }
+    for m in doc.modules() {
+        body.push('\n');
+        declare_module(&mut body, &m);
+    }
+
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n");
+    match &options.namespace {
+        Some(ns) => {
+            writeln!(out, "\nexport namespace {} {{", ns).unwrap();
+            for line in body.trim_start_matches('\n').lines() {
+                if line.is_empty() {
+                    out.push('\n');
+                } else {
+                    writeln!(out, "  {}", line).unwrap();
+                }
+            }
+            out.push_str("}\n");
+        }
+        None => out.push_str(&body),
+    }
+    vec![OutputFile::new(format!("{}.ts", file_stem(doc)), out)]
+}
+
+fn comment(out: &mut String, docs: &str, indent: &str) {
+    if docs.trim().is_empty() {
+        return;
+    }
+    writeln!(out, "{}/**", indent).unwrap();
+    out.push_str(&doc_comment(docs, " *", indent));
+    writeln!(out, "{} */", indent).unwrap();
+}
+
+fn ident(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return format!("f{}", name);
+    }
+    match name {
+        "break" | "case" | "catch" | "class" | "const" | "continue" | "debugger" | "default"
+        | "delete" | "do" | "else" | "enum" | "export" | "extends" | "false" | "finally"
+        | "for" | "function" | "if" | "import" | "in" | "instanceof" | "new" | "null"
+        | "return" | "super" | "switch" | "this" | "throw" | "true" | "try" | "typeof" | "var"
+        | "void" | "while" | "with" | "let" | "static" | "yield" | "implements" | "interface"
+        | "package" | "private" | "protected" | "public" => format!("{}_", name),
+        _ => name.to_string(),
+    }
+}
+
+fn builtin(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::Char => "u32",
+        BuiltinType::U8 { .. } => "u8",
+        BuiltinType::U16 => "u16",
+        BuiltinType::U32 {
+            lang_ptr_size: false,
+        } => "u32",
+        BuiltinType::U32 {
+            lang_ptr_size: true,
+        } => "usize",
+        BuiltinType::U64 => "u64",
+        BuiltinType::S8 => "i8",
+        BuiltinType::S16 => "i16",
+        BuiltinType::S32 => "i32",
+        BuiltinType::S64 => "i64",
+        BuiltinType::F32 => "f32",
+        BuiltinType::F64 => "f64",
+    }
+}
+
+fn int_repr(repr: IntRepr) -> &'static str {
+    builtin(repr.to_builtin())
+}
+
+/// The AssemblyScript type of values of `tref`, if they are a single core
+/// wasm value.
+fn value_ty(tref: &TypeRef) -> Option<String> {
+    match tref {
+        TypeRef::Name(nt) => value_ty(&nt.tref).map(|_| nt.name.as_str().to_string()),
+        TypeRef::Value(ty) => match &**ty {
+            Type::Builtin(b) => Some(builtin(*b).to_string()),
+            Type::Pointer(_) | Type::ConstPointer(_) => Some("usize".to_string()),
+            Type::Handle(_) => Some("u32".to_string()),
+            Type::Variant(v) if v.is_enum() => Some(int_repr(v.tag_repr).to_string()),
+            Type::Record(r) => r.bitflags_repr().map(|repr| int_repr(repr).to_string()),
+            Type::Variant(_) | Type::List(_) => None,
+        },
+    }
+}
+
+fn define_type(out: &mut String, nt: &NamedType) {
+    let name = nt.name.as_str();
+    if let Some(ty) = value_ty(&nt.tref) {
+        comment(out, &nt.docs, "");
+        let ty = match &nt.tref {
+            TypeRef::Name(other) => other.name.as_str().to_string(),
+            TypeRef::Value(_) => ty,
+        };
+        writeln!(out, "export type {} = {};", name, ty).unwrap();
+        let consts = match &nt.tref {
+            TypeRef::Value(ty) => match &**ty {
+                Type::Variant(v) => v
+                    .cases
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| (c.name.as_str(), &c.docs, i.to_string()))
+                    .collect(),
+                Type::Record(r) => r
+                    .members
+                    .iter()
+                    .enumerate()
+                    .map(|(i, m)| (m.name.as_str(), &m.docs, format!("1 << {}", i)))
+                    .collect(),
+                _ => Vec::new(),
+            },
+            TypeRef::Name(_) => Vec::new(),
+        };
+        if !consts.is_empty() {
+            writeln!(out, "export namespace {} {{", name).unwrap();
+            for (case, docs, value) in consts {
+                comment(out, docs, "  ");
+                let mut case = case.to_shouty_snake_case();
+                if case.starts_with(|c: char| c.is_ascii_digit()) {
+                    case.insert(0, '_');
+                }
+                writeln!(out, "  export const {}: {} = {};", case, name, value).unwrap();
+            }
+            out.push_str("}\n");
+        }
+        return;
+    }
+
+    match &**nt.type_() {
+        Type::Record(r) if r.members.iter().all(|m| is_flat(&m.tref)) => {
+            comment(out, &nt.docs, "");
+            out.push_str("@unmanaged\n");
+            writeln!(out, "export class {} {{", name).unwrap();
+            for m in r.members.iter() {
+                comment(out, &m.docs, "  ");
+                let field = ident(m.name.as_str());
+                match value_ty(&m.tref) {
+                    Some(ty) => writeln!(out, "  {}: {};", field, ty).unwrap(),
+                    None => {
+                        writeln!(out, "  {}: usize;", field).unwrap();
+                        writeln!(out, "  {}_len: usize;", m.name.as_str()).unwrap();
+                    }
+                }
+            }
+            out.push_str("}\n");
+        }
+        Type::Record(r) => {
+            comment(out, &nt.docs, "");
+            writeln!(out, "export namespace {} {{", name).unwrap();
+            size_align(out, nt);
+            for m in r.member_layout() {
+                writeln!(
+                    out,
+                    "  export const {}_OFFSET: usize = {};",
+                    m.member.name.as_str().to_shouty_snake_case(),
+                    m.offset
+                )
+                .unwrap();
+            }
+            out.push_str("}\n");
+        }
+        Type::Variant(v) => {
+            comment(out, &nt.docs, "");
+            writeln!(out, "export namespace {} {{", name).unwrap();
+            size_align(out, nt);
+            writeln!(out, "  export type TAG = {};", int_repr(v.tag_repr)).unwrap();
+            writeln!(
+                out,
+                "  export const PAYLOAD_OFFSET: usize = {};",
+                v.payload_offset()
+            )
+            .unwrap();
+            out.push_str("}\n");
+        }
+        // Lists are expanded to a pointer and a length where they're used.
+        _ => {}
+    }
+}
+
+/// Whether `tref` can be a field of an `@unmanaged` class.
+fn is_flat(tref: &TypeRef) -> bool {
+    value_ty(tref).is_some() || matches!(&**tref.type_(), Type::List(_))
+}
+
+fn size_align(out: &mut String, nt: &NamedType) {
+    let sa = nt.mem_size_align();
+    writeln!(out, "  export const SIZE: usize = {};", sa.size).unwrap();
+    writeln!(out, "  export const ALIGN: usize = {};", sa.align).unwrap();
+}
+
+fn declare_module(out: &mut String, module: &Module) {
+    comment(out, &module.docs, "");
+    writeln!(out, "export namespace {} {{", module.name.as_str()).unwrap();
+    for (i, f) in module.funcs().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        declare_func(out, module, &f);
+    }
+    out.push_str("}\n");
+}
+
+fn declare_func(out: &mut String, module: &Module, func: &InterfaceFunc) {
+    comment(out, &func.docs, "  ");
+    let mut params = Vec::new();
+    for p in func.params.iter() {
+        let name = ident(p.name.as_str());
+        match value_ty(&p.tref) {
+            Some(ty) => params.push(format!("{}: {}", name, ty)),
+            None => {
+                params.push(format!("{}: usize", name));
+                if let Type::List(_) = &**p.tref.type_() {
+                    params.push(format!("{}_len: usize", p.name.as_str()));
+                }
+            }
+        }
+    }
+    let mut ret = "void".to_string();
+    if let Some(result) = func.results.first() {
+        match &**result.tref.type_() {
+            Type::Variant(v) if !v.is_enum() => {
+                let (ok, err) = v.as_expected().expect("results are `expected` variants");
+                ret = match err.and_then(value_ty) {
+                    Some(ty) => ty,
+                    None => int_repr(v.tag_repr).to_string(),
+                };
+                if let Some(ok) = ok {
+                    let n = match &**ok.type_() {
+                        Type::Record(r) if r.is_tuple() => r.members.len(),
+                        _ => 1,
+                    };
+                    for i in 0..n {
+                        params.push(format!("retptr{}: usize", i));
+                    }
+                }
+            }
+            _ => {
+                if let Some(ty) = value_ty(&result.tref) {
+                    ret = ty;
+                }
+            }
+        }
+    }
+    writeln!(
+        out,
+        "  @external(\"{}\", \"{}\")",
+        module.name.as_str(),
+        func.name.as_str()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  export declare function {}({}): {};",
+        ident(func.name.as_str()),
+        params.join(", "),
+        ret
+    )
+    .unwrap();
+}
@@ -0,0 +1,293 @@
+//! Code generators selected by name, including ones from other crates.
+//!
+//! Every [`Language`] of this crate is a [`CodegenBackend`], and a
+//! [`Registry`] holds backends by the name they're selected with. A crate
+//! shipping its own backend implements the trait, declaring the options it
+//! takes beyond the common [`Options`], and registers it with the built-in
+//! ones in its own driver, without changes to this crate:
+//!
+//! ```
+//! use witx::codegen::{BackendOption, CodegenBackend, Config, OutputFile, Registry};
+//! use witx::Document;
+//!
+//! struct Names;
+//!
+//! impl CodegenBackend for Names {
+//!     fn name(&self) -> &str {
+//!         "names"
+//!     }
+//!     fn options(&self) -> &[BackendOption] {
+//!         &[BackendOption {
+//!             name: "separator",
+//!             docs: "Text between the names",
+//!             default: Some("\n"),
+//!         }]
+//!     }
+//!     fn generate(&self, doc: &Document, config: &Config) -> Vec<OutputFile> {
+//!         let names = doc.typenames().map(|t| t.name.as_str().to_string());
+//!         let separator = config.value("separator").unwrap_or("\n");
+//!         let contents = names.collect::<Vec<_>>().join(separator);
+//!         vec![OutputFile::new("names.txt", contents)]
+//!     }
+//! }
+//!
+//! let mut registry = Registry::builtin();
+//! registry.register(Names);
+//! let doc = witx::parse("(typename $a u8) (typename $b u8)").unwrap();
+//! let config = Config::new().value_of("separator", ",");
+//! let files = registry.generate("names", &doc, &config).unwrap();
+//! assert_eq!(files[0].contents, "a,b");
+//! ```
+
+use super::{generate, Language, Options, OutputFile};
+use crate::Document;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// A code generator for a document.
+pub trait CodegenBackend {
+    /// The name selecting the backend, e.g. on the command line.
+    fn name(&self) -> &str;
+
+    /// The options the backend takes besides the common [`Options`], all
+    /// taking string values.
+    fn options(&self) -> &[BackendOption] {
+        &[]
+    }
+
+    /// Generate code for `doc`. Values are given for every option of the
+    /// backend with a default, and only for its options.
+    fn generate(&self, doc: &Document, config: &Config) -> Vec<OutputFile>;
+}
+
+/// An option of a backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendOption {
+    pub name: &'static str,
+    /// One line describing the option.
+    pub docs: &'static str,
+    /// The value of the option when none is given. Options without one
+    /// must be given.
+    pub default: Option<&'static str>,
+}
+
+/// The settings a backend generates code with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// The settings common to backends.
+    pub options: Options,
+    /// The values of the backend's own options, by name.
+    pub values: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A config with the common settings `options`.
+    pub fn with_options(options: Options) -> Self {
+        Config {
+            options,
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// Set the backend option `name` to `value`.
+    pub fn value_of(mut self, name: &str, value: &str) -> Self {
+        self.values.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// The value of the backend option `name`.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|v| v.as_str())
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BackendError {
+    #[error("unknown backend `{name}`: expected one of {}", .known.join(", "))]
+    UnknownBackend { name: String, known: Vec<String> },
+    #[error("backend `{backend}` has no option `{option}`")]
+    UnknownOption { backend: String, option: String },
+    #[error("backend `{backend}` needs a value for its option `{option}`")]
+    MissingOption { backend: String, option: String },
+}
+
+impl CodegenBackend for Language {
+    fn name(&self) -> &str {
+        Language::name(self)
+    }
+
+    fn generate(&self, doc: &Document, config: &Config) -> Vec<OutputFile> {
+        generate(doc, *self, &config.options)
+    }
+}
+
+/// Backends by name, in the order they were registered.
+#[derive(Default)]
+pub struct Registry {
+    backends: Vec<Box<dyn CodegenBackend>>,
+}
+
+impl Registry {
+    /// A registry without any backends.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry of every [`Language`] of this crate.
+    pub fn builtin() -> Self {
+        let mut registry = Registry::new();
+        for language in Language::all() {
+            registry.register(*language);
+        }
+        registry
+    }
+
+    /// Add `backend`, in place of any backend of the same name.
+    pub fn register(&mut self, backend: impl CodegenBackend + 'static) {
+        let backend: Box<dyn CodegenBackend> = Box::new(backend);
+        match self
+            .backends
+            .iter()
+            .position(|b| b.name() == backend.name())
+        {
+            Some(i) => self.backends[i] = backend,
+            None => self.backends.push(backend),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn CodegenBackend> {
+        self.backends
+            .iter()
+            .find(|b| b.name() == name)
+            .map(|b| &**b)
+    }
+
+    pub fn backends(&self) -> impl Iterator<Item = &dyn CodegenBackend> {
+        self.backends.iter().map(|b| &**b)
+    }
+
+    /// Generate code for `doc` with the backend `name`, after checking
+    /// `config` against its options and filling in their defaults.
+    pub fn generate(
+        &self,
+        name: &str,
+        doc: &Document,
+        config: &Config,
+    ) -> Result<Vec<OutputFile>, BackendError> {
+        let backend = self.get(name).ok_or_else(|| BackendError::UnknownBackend {
+            name: name.to_string(),
+            known: self.backends().map(|b| b.name().to_string()).collect(),
+        })?;
+        let options = backend.options();
+        if let Some(option) = config
+            .values
+            .keys()
+            .find(|v| !options.iter().any(|o| o.name == v.as_str()))
+        {
+            return Err(BackendError::UnknownOption {
+                backend: name.to_string(),
+                option: option.clone(),
+            });
+        }
+        let mut config = config.clone();
+        for option in options {
+            if config.values.contains_key(option.name) {
+                continue;
+            }
+            match option.default {
+                Some(default) => {
+                    config
+                        .values
+                        .insert(option.name.to_string(), default.to_string());
+                }
+                None => {
+                    return Err(BackendError::MissingOption {
+                        backend: name.to_string(),
+                        option: option.name.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(backend.generate(doc, &config))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Fixed;
+
+    impl CodegenBackend for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+        fn options(&self) -> &[BackendOption] {
+            &[
+                BackendOption {
+                    name: "path",
+                    docs: "The file to write",
+                    default: None,
+                },
+                BackendOption {
+                    name: "contents",
+                    docs: "What to write in it",
+                    default: Some("fixed"),
+                },
+            ]
+        }
+        fn generate(&self, _doc: &Document, config: &Config) -> Vec<OutputFile> {
+            let path = config.value("path").unwrap();
+            let contents = config.value("contents").unwrap();
+            vec![OutputFile::new(path, contents.to_string())]
+        }
+    }
+
+    #[test]
+    fn registry() {
+        let doc = crate::parse("(typename $t u8)").unwrap();
+        let mut registry = Registry::builtin();
+        let names = registry.backends().map(|b| b.name()).collect::<Vec<_>>();
+        let languages = Language::all().iter().map(|l| l.name()).collect::<Vec<_>>();
+        assert_eq!(names, languages);
+        assert_eq!(
+            registry.generate("c", &doc, &Config::new()),
+            Ok(generate(&doc, Language::C, &Options::default()))
+        );
+
+        registry.register(Fixed);
+        assert_eq!(registry.backends().last().unwrap().name(), "fixed");
+        assert_eq!(
+            registry.generate("fixed", &doc, &Config::new().value_of("path", "a")),
+            Ok(vec![OutputFile::new("a", "fixed".to_string())])
+        );
+        assert_eq!(
+            registry.generate("fixed", &doc, &Config::new()),
+            Err(BackendError::MissingOption {
+                backend: "fixed".to_string(),
+                option: "path".to_string(),
+            })
+        );
+        assert_eq!(
+            registry.generate("c", &doc, &Config::new().value_of("path", "a")),
+            Err(BackendError::UnknownOption {
+                backend: "c".to_string(),
+                option: "path".to_string(),
+            })
+        );
+        match registry.generate("cobol", &doc, &Config::new()) {
+            Err(e @ BackendError::UnknownBackend { .. }) => {
+                assert!(e.to_string().ends_with(", benchmark, fixed"), "{}", e)
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // Registering a backend of the same name replaces it, in place.
+        registry.register(Language::C);
+        assert_eq!(registry.backends().count(), languages.len() + 1);
+    }
+}
@@ -0,0 +1,604 @@
+//! Skeleton wasi-nn backends for inference runtimes.
+//!
+//! The generated crate has the wasmtime host bindings of the document in
+//! `src/bindings.rs`, and an implementation of each module's trait in
+//! `src/lib.rs` for backend authors to fill in. Functions with wasi-nn's
+//! signatures keep track of graphs, execution contexts and tensors, leaving
+//! the calls into the runtime as `TODO` comments and `todo!()`s. Other
+//! functions are left entirely to the author.
+//!
+//! The runtime's devices and element types are mapped from the cases of the
+//! `execution_target` and `tensor_type` enums, so cases the runtime can't
+//! handle are rejected with `invalid_argument`, and element types are also
+//! mapped back to `tensor_type` for the runtime's outputs.
+
+use super::rust::{ident, type_name, variant_name};
+use super::rust_host::{
+    self, error_case, method_sig, trait_name, tref_ty, Engine, WASMTIME_VERSION,
+};
+use super::{Options, OutputFile};
+use crate::tensor::{ElementType, TENSOR_TYPE};
+use crate::{Document, Id, InterfaceFunc, Module, Type};
+use std::fmt::Write;
+
+/// An inference runtime to generate a backend for.
+pub(super) struct Runtime {
+    /// The name of the runtime in docs.
+    pub title: &'static str,
+    /// The name of the type implementing the backend.
+    pub backend: &'static str,
+    /// Tag of the `TODO` comments for the author.
+    pub tag: &'static str,
+    /// The case of `graph_encoding` the runtime loads.
+    pub encoding: &'static str,
+    /// The graph builders `load` expects, in order, as a field name and
+    /// documentation.
+    pub builders: &'static [(&'static str, &'static str)],
+    /// The runtime's device for each case of `execution_target` it supports.
+    pub devices: &'static [(&'static str, &'static str)],
+    /// The Rust type of the runtime's element types.
+    pub element_type: &'static str,
+    /// Constants defined for the runtime's element types, with their values.
+    pub element_consts: &'static [(&'static str, i32)],
+    /// The runtime's element type for each element type it supports, as an
+    /// expression of type `element_type`.
+    pub element_types: &'static [(ElementType, &'static str)],
+    /// What's left to hold in a graph and an execution context.
+    pub todo_graph: &'static str,
+    pub todo_context: &'static str,
+    /// What's left to do in `load`, `init_execution_context` and `compute`.
+    pub todo_load: &'static str,
+    pub todo_init: &'static str,
+    pub todo_compute: &'static str,
+}
+
+pub(super) const OPENVINO: Runtime = Runtime {
+    title: "OpenVINO",
+    backend: "OpenVino",
+    tag: "openvino",
+    encoding: "openvino",
+    builders: &[
+        ("xml", "The model's IR, in OpenVINO's XML format."),
+        ("weights", "The model's weights, as the IR's binary data."),
+    ],
+    devices: &[("cpu", "CPU"), ("gpu", "GPU"), ("tpu", "MYRIAD")],
+    element_type: "&'static str",
+    element_consts: &[],
+    element_types: &[
+        (ElementType::F16, "\"f16\""),
+        (ElementType::F32, "\"f32\""),
+        (ElementType::U8, "\"u8\""),
+        (ElementType::I32, "\"i32\""),
+        (ElementType::Quantized { bits: 4 }, "\"u4\""),
+        (ElementType::Quantized { bits: 1 }, "\"u1\""),
+    ],
+    todo_graph: "the compiled model.",
+    todo_context: "the infer request.",
+    todo_load: "read the model with `ov_core_read_model_from_memory_buffer`\n\
+                and compile it for `device` with `ov_core_compile_model`.",
+    todo_init: "create an infer request for the compiled model with\n\
+                `ov_compiled_model_create_infer_request`.",
+    todo_compute: "set each input as an `ov_tensor_t` of its element type\n\
+                   with `ov_infer_request_set_input_tensor_by_index`, run\n\
+                   `ov_infer_request_infer`, and copy the data of each output\n\
+                   tensor to `context.outputs`.",
+};
+
+pub(super) const ONNX_RUNTIME: Runtime = Runtime {
+    title: "ONNX Runtime",
+    backend: "OnnxRuntime",
+    tag: "onnx",
+    encoding: "onnx",
+    builders: &[("model", "The model, in the ONNX format.")],
+    devices: &[
+        ("cpu", "CPUExecutionProvider"),
+        ("gpu", "CUDAExecutionProvider"),
+    ],
+    element_type: "ONNXTensorElementDataType",
+    element_consts: &[
+        ("ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT", 1),
+        ("ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT8", 2),
+        ("ONNX_TENSOR_ELEMENT_DATA_TYPE_INT32", 6),
+        ("ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT16", 10),
+        ("ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT4", 21),
+    ],
+    element_types: &[
+        (ElementType::F32, "ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT"),
+        (ElementType::U8, "ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT8"),
+        (ElementType::I32, "ONNX_TENSOR_ELEMENT_DATA_TYPE_INT32"),
+        (ElementType::F16, "ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT16"),
+        (
+            ElementType::Quantized { bits: 4 },
+            "ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT4",
+        ),
+    ],
+    todo_graph: "the `OrtSession`, released with `ReleaseSession` when dropped.",
+    todo_context: "the `OrtRunOptions`, released with `ReleaseRunOptions` when\n\
+                   dropped.",
+    todo_load: "create `OrtSessionOptions` with `CreateSessionOptions`, append\n\
+                the execution provider named `device` to them, and create an\n\
+                `OrtSession` from the model with `CreateSessionFromArray`.",
+    todo_init: "create `OrtRunOptions` for running the session with\n\
+                `CreateRunOptions`.",
+    todo_compute: "create an `OrtValue` for each input with\n\
+                   `CreateTensorWithDataAsOrtValue` and its element type, run the\n\
+                   session with `Run`, and copy the data of each output, from\n\
+                   `GetTensorMutableData`, to `context.outputs`.",
+};
+
+pub(super) fn generate(doc: &Document, options: &Options, runtime: &Runtime) -> Vec<OutputFile> {
+    let crate_name = options.crate_name.as_deref().unwrap_or("backend");
+    let manifest = format!(
+        "[package]\n\
+         name = \"{}\"\n\
+         version = \"0.1.0\"\n\
+         edition = \"2018\"\n\
+         \n\
+         [dependencies]\n\
+         wasmtime = {{ version = \"{}\", default-features = false, features = [\"runtime\"] }}\n",
+        crate_name, WASMTIME_VERSION
+    );
+    vec![
+        OutputFile::new("Cargo.toml", manifest),
+        OutputFile::new(
+            "src/bindings.rs",
+            rust_host::render(doc, &Options::default(), Engine::Wasmtime),
+        ),
+        OutputFile::new("src/lib.rs", render(doc, runtime)),
+    ]
+}
+
+fn render(doc: &Document, runtime: &Runtime) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "//! An {} backend, generated by witx as a starting point.",
+        runtime.title
+    )
+    .unwrap();
+    out.push_str("//!\n");
+    out.push_str("//! `bindings.rs` is generated from the spec and shouldn't be edited. Search\n");
+    writeln!(
+        out,
+        "//! this file for `TODO({})` for what's left to implement.\n",
+        runtime.tag
+    )
+    .unwrap();
+    out.push_str("#![allow(unused_variables)]\n\n");
+    out.push_str("mod bindings;\n\n");
+    out.push_str("pub use bindings::*;\n");
+    for m in doc.modules() {
+        writeln!(
+            out,
+            "use bindings::{}::{};",
+            ident(m.name.as_str()),
+            trait_name(&m)
+        )
+        .unwrap();
+    }
+
+    let wasi_nn = wasi_nn_types(doc);
+    if wasi_nn {
+        define_state(&mut out, runtime);
+        define_mappings(&mut out, doc, runtime);
+    } else {
+        out.push('\n');
+        writeln!(
+            out,
+            "#[derive(Debug, Default)]\npub struct {} {{",
+            runtime.backend
+        )
+        .unwrap();
+        writeln!(out, "    // TODO({}): the backend's state.", runtime.tag).unwrap();
+        out.push_str("}\n");
+    }
+    for m in doc.modules() {
+        out.push('\n');
+        define_impl(&mut out, &m, runtime, wasi_nn);
+    }
+    out
+}
+
+/// Whether `doc` has the types the backend's state is made of, with a
+/// `tensor_type` of element types.
+fn wasi_nn_types(doc: &Document) -> bool {
+    let is_enum = |name: &str| {
+        doc.typename(&Id::new(name))
+            .is_some_and(|nt| matches!(&**nt.type_(), Type::Variant(v) if v.is_enum()))
+    };
+    let tensor = doc.typename(&Id::new("tensor")).is_some_and(|nt| {
+        matches!(&**nt.type_(), Type::Record(r) if ["dimensions", "type", "data"]
+            .iter()
+            .all(|f| r.members.iter().any(|m| m.name.as_str() == *f)))
+    });
+    let handle = |name: &str| {
+        doc.typename(&Id::new(name))
+            .is_some_and(|nt| matches!(&**nt.type_(), Type::Handle(_)))
+    };
+    let element_types = doc
+        .typename(&Id::new(TENSOR_TYPE))
+        .is_some_and(|nt| ElementType::cases_of(&nt).is_some());
+    tensor
+        && element_types
+        && is_enum("execution_target")
+        && handle("graph")
+        && handle("graph_execution_context")
+}
+
+fn define_state(out: &mut String, runtime: &Runtime) {
+    writeln!(out, "\n/// A graph loaded by [`{}`].", runtime.backend).unwrap();
+    out.push_str("#[derive(Debug)]\n");
+    out.push_str("pub struct LoadedGraph {\n");
+    for (field, docs) in runtime.builders {
+        writeln!(out, "    /// {}", docs).unwrap();
+        writeln!(out, "    pub {}: Vec<u8>,", field).unwrap();
+    }
+    writeln!(
+        out,
+        "    /// The {} device to run the graph on.",
+        runtime.title
+    )
+    .unwrap();
+    out.push_str("    pub device: &'static str,\n");
+    out.push_str(&todo(runtime, runtime.todo_graph, "    "));
+    out.push_str("}\n\n");
+
+    writeln!(
+        out,
+        "/// An execution context created by [`{}`].",
+        runtime.backend
+    )
+    .unwrap();
+    out.push_str("#[derive(Debug)]\n");
+    out.push_str("pub struct ExecutionContext {\n");
+    out.push_str("    pub graph: Graph,\n");
+    out.push_str("    /// Inputs set so far, by index.\n");
+    out.push_str("    pub inputs: Vec<(u32, Tensor)>,\n");
+    out.push_str("    /// The data of each output of the last computation.\n");
+    out.push_str("    pub outputs: Vec<Vec<u8>>,\n");
+    out.push_str(&todo(runtime, runtime.todo_context, "    "));
+    out.push_str("}\n\n");
+
+    writeln!(out, "/// Runs graphs with {}.", runtime.title).unwrap();
+    out.push_str("#[derive(Debug, Default)]\n");
+    writeln!(out, "pub struct {} {{", runtime.backend).unwrap();
+    out.push_str("    pub graphs: Vec<LoadedGraph>,\n");
+    out.push_str("    pub contexts: Vec<ExecutionContext>,\n");
+    out.push_str("}\n");
+}
+
+/// Functions mapping the cases of `execution_target` and `tensor_type` to
+/// the runtime's names.
+fn define_mappings(out: &mut String, doc: &Document, runtime: &Runtime) {
+    let targets = doc.typename(&Id::new("execution_target")).unwrap();
+    writeln!(
+        out,
+        "\n/// The {} device for `target`, if it has one.",
+        runtime.title
+    )
+    .unwrap();
+    out.push_str("pub fn device(target: ExecutionTarget) -> Option<&'static str> {\n");
+    out.push_str("    match target {\n");
+    if let Type::Variant(v) = &**targets.type_() {
+        for c in v.cases.iter() {
+            let device = runtime
+                .devices
+                .iter()
+                .find(|(case, _)| *case == c.name.as_str())
+                .map(|(_, device)| format!("Some(\"{}\")", device))
+                .unwrap_or_else(|| "None".to_string());
+            writeln!(
+                out,
+                "        ExecutionTarget::{} => {},",
+                variant_name(&c.name),
+                device
+            )
+            .unwrap();
+        }
+    }
+    out.push_str("    }\n");
+    out.push_str("}\n");
+
+    if !runtime.element_consts.is_empty() {
+        writeln!(
+            out,
+            "\n/// The element types of {}'s tensors.\npub type {} = i32;",
+            runtime.title, runtime.element_type
+        )
+        .unwrap();
+        for (name, value) in runtime.element_consts {
+            writeln!(
+                out,
+                "pub const {}: {} = {};",
+                name, runtime.element_type, value
+            )
+            .unwrap();
+        }
+    }
+
+    // The runtime's element type for each case of `tensor_type`.
+    let tensor_type = doc.typename(&Id::new(TENSOR_TYPE)).unwrap();
+    let ty = type_name(&tensor_type.name);
+    let cases = ElementType::cases_of(&tensor_type)
+        .unwrap()
+        .into_iter()
+        .zip(match &**tensor_type.type_() {
+            Type::Variant(v) => v.cases.iter().map(|c| variant_name(&c.name)),
+            _ => unreachable!(),
+        })
+        .map(|(e, case)| {
+            let element = runtime.element_types.iter().find(|(t, _)| *t == e);
+            (case, element.map(|(_, expr)| *expr))
+        })
+        .collect::<Vec<_>>();
+    writeln!(
+        out,
+        "\n/// The {} element type of `ty`, if it has one.",
+        runtime.title
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub fn element_type(ty: {}) -> Option<{}> {{",
+        ty, runtime.element_type
+    )
+    .unwrap();
+    out.push_str("    match ty {\n");
+    for (case, element) in cases.iter() {
+        let element = element
+            .map(|e| format!("Some({})", e))
+            .unwrap_or_else(|| "None".to_string());
+        writeln!(out, "        {}::{} => {},", ty, case, element).unwrap();
+    }
+    out.push_str("    }\n");
+    out.push_str("}\n");
+
+    writeln!(
+        out,
+        "\n/// The `{}` of the {} element type `element_type`, if it has one.",
+        ty, runtime.title
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub fn tensor_type(element_type: {}) -> Option<{}> {{",
+        runtime.element_type.replace("&'static ", "&"),
+        ty
+    )
+    .unwrap();
+    out.push_str("    match element_type {\n");
+    for (case, element) in cases.iter() {
+        if let Some(element) = element {
+            writeln!(out, "        {} => Some({}::{}),", element, ty, case).unwrap();
+        }
+    }
+    out.push_str("        _ => None,\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+}
+
+/// The names of `func`'s parameters, if it takes parameters of the Rust
+/// types `params` and returns `ret`.
+fn signature(func: &InterfaceFunc, params: &[&str], ret: &str) -> Option<Vec<String>> {
+    let types = func
+        .params
+        .iter()
+        .map(|p| tref_ty(&p.tref))
+        .collect::<Vec<_>>();
+    let result = func.results.first().map(|r| tref_ty(&r.tref));
+    if types != params || result.as_deref().is_none_or(|r| !r.starts_with(ret)) {
+        return None;
+    }
+    Some(func.params.iter().map(|p| ident(p.name.as_str())).collect())
+}
+
+/// A `TODO` comment for `text`, indented by `indent`.
+fn todo(runtime: &Runtime, text: &str, indent: &str) -> String {
+    let mut out = String::new();
+    for (i, line) in text.lines().enumerate() {
+        if i == 0 {
+            writeln!(out, "{}// TODO({}): {}", indent, runtime.tag, line).unwrap();
+        } else {
+            writeln!(out, "{}// {}", indent, line).unwrap();
+        }
+    }
+    out
+}
+
+/// The body of `func`, if it's one of wasi-nn's functions.
+fn body(func: &InterfaceFunc, runtime: &Runtime) -> Option<String> {
+    let invalid = error_case(func, "invalid_argument")?;
+    let mut out = String::new();
+    match func.name.as_str() {
+        "load" => {
+            let p = signature(
+                func,
+                &["GraphBuilderArray", "GraphEncoding", "ExecutionTarget"],
+                "Result<Graph,",
+            )?;
+            let encodings = match &**func.params[1].tref.type_() {
+                Type::Variant(v) => v.cases.iter().map(|c| c.name.as_str()).collect(),
+                _ => Vec::new(),
+            };
+            if !encodings.contains(&runtime.encoding) {
+                return None;
+            }
+            writeln!(
+                out,
+                "        if {} != GraphEncoding::{} {{",
+                p[1],
+                variant_name(&Id::new(runtime.encoding))
+            )
+            .unwrap();
+            writeln!(out, "            return Err({});", invalid).unwrap();
+            out.push_str("        }\n");
+            let fields = runtime.builders.iter().map(|(f, _)| *f).collect::<Vec<_>>();
+            // A tuple of the graph builders, or the only one.
+            let (pattern, value) = match fields.as_slice() {
+                [field] => (field.to_string(), format!("{}.clone()", field)),
+                _ => (
+                    format!("({})", fields.join(", ")),
+                    format!(
+                        "({})",
+                        fields
+                            .iter()
+                            .map(|f| format!("{}.clone()", f))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                ),
+            };
+            writeln!(
+                out,
+                "        let {} = match {}.as_slice() {{",
+                pattern, p[0]
+            )
+            .unwrap();
+            writeln!(out, "            [{}] => {},", fields.join(", "), value).unwrap();
+            writeln!(out, "            _ => return Err({}),", invalid).unwrap();
+            out.push_str("        };\n");
+            writeln!(
+                out,
+                "        let device = device({}).ok_or({})?;",
+                p[2], invalid
+            )
+            .unwrap();
+            out.push_str(&todo(runtime, runtime.todo_load, "        "));
+            writeln!(
+                out,
+                "        self.graphs.push(LoadedGraph {{ {}, device }});",
+                fields.join(", ")
+            )
+            .unwrap();
+            out.push_str("        Ok((self.graphs.len() - 1) as Graph)\n");
+        }
+        "init_execution_context" => {
+            let p = signature(func, &["Graph"], "Result<GraphExecutionContext,")?;
+            writeln!(
+                out,
+                "        let loaded = self.graphs.get({} as usize).ok_or({})?;",
+                p[0], invalid
+            )
+            .unwrap();
+            out.push_str(&todo(runtime, runtime.todo_init, "        "));
+            out.push_str("        self.contexts.push(ExecutionContext {\n");
+            if p[0] == "graph" {
+                out.push_str("            graph,\n");
+            } else {
+                writeln!(out, "            graph: {},", p[0]).unwrap();
+            }
+            out.push_str("            inputs: Vec::new(),\n");
+            out.push_str("            outputs: Vec::new(),\n");
+            out.push_str("        });\n");
+            out.push_str("        Ok((self.contexts.len() - 1) as GraphExecutionContext)\n");
+        }
+        "set_input" => {
+            let p = signature(
+                func,
+                &["GraphExecutionContext", "u32", "Tensor"],
+                "Result<(),",
+            )?;
+            writeln!(
+                out,
+                "        let context = self.contexts.get_mut({} as usize).ok_or({})?;",
+                p[0], invalid
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "        let valid = {t}.r#type.is_valid_buffer(&{t}.dimensions, {t}.data.len());",
+                t = p[2]
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "        if !valid || element_type({}.r#type).is_none() {{",
+                p[2]
+            )
+            .unwrap();
+            writeln!(out, "            return Err({});", invalid).unwrap();
+            out.push_str("        }\n");
+            writeln!(
+                out,
+                "        context.inputs.retain(|(i, _)| *i != {});",
+                p[1]
+            )
+            .unwrap();
+            writeln!(out, "        context.inputs.push(({}, {}));", p[1], p[2]).unwrap();
+            out.push_str("        Ok(())\n");
+        }
+        "compute" => {
+            let p = signature(func, &["GraphExecutionContext"], "Result<(),")?;
+            writeln!(
+                out,
+                "        let context = self.contexts.get_mut({} as usize).ok_or({})?;",
+                p[0], invalid
+            )
+            .unwrap();
+            out.push_str("        let loaded = &self.graphs[context.graph as usize];\n");
+            out.push_str(&todo(runtime, runtime.todo_compute, "        "));
+            writeln!(out, "        todo!(\"compute with {}\")", runtime.title).unwrap();
+        }
+        "get_output" => {
+            let p = signature(
+                func,
+                &["GraphExecutionContext", "u32", "GuestPtr<u8>", "BufferSize"],
+                "Result<BufferSize,",
+            )?;
+            writeln!(
+                out,
+                "        let context = self.contexts.get({} as usize).ok_or({})?;",
+                p[0], invalid
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "        let data = context.outputs.get({} as usize).ok_or({})?;",
+                p[1], invalid
+            )
+            .unwrap();
+            writeln!(out, "        if data.len() > {} as usize {{", p[3]).unwrap();
+            writeln!(out, "            return Err({});", invalid).unwrap();
+            out.push_str("        }\n");
+            writeln!(
+                out,
+                "        mem.bytes_mut({}.offset(), data.len() as u32)\n            \
+                 .map_err(|_| {})?\n            \
+                 .copy_from_slice(data);",
+                p[2], invalid
+            )
+            .unwrap();
+            out.push_str("        Ok(data.len() as BufferSize)\n");
+        }
+        _ => return None,
+    }
+    Some(out)
+}
+
+fn define_impl(out: &mut String, module: &Module, runtime: &Runtime, wasi_nn: bool) {
+    writeln!(
+        out,
+        "impl {} for {} {{",
+        trait_name(module),
+        runtime.backend
+    )
+    .unwrap();
+    for (i, f) in module.funcs().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        writeln!(out, "    {} {{", method_sig(&f, Engine::Wasmtime)).unwrap();
+        match body(&f, runtime).filter(|_| wasi_nn) {
+            Some(body) => out.push_str(&body),
+            None => writeln!(
+                out,
+                "        todo!(\"implement `{}` with {}\")",
+                f.name.as_str(),
+                runtime.title
+            )
+            .unwrap(),
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+}
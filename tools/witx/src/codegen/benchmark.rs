@@ -0,0 +1,685 @@
+//! A criterion benchmark crate for hosts implementing wasi-nn.
+//!
+//! The generated guest program forwards calls to each of wasi-nn's
+//! functions, and the driver in `src/lib.rs` instantiates it against a
+//! `wasmtime::Linker` provided by the implementation. Graphs and tensors are
+//! written to guest memory with the layout and enum values of the document,
+//! once, so the benchmarks of `load`, `init_execution_context`, `set_input`,
+//! `compute` and `get_output` only time the calls, as does a whole inference.
+//! Inputs are tensors of zeroes, which only need a type and dimensions, so
+//! backends can be compared on the same graph at the same sizes.
+//!
+//! `benches/inference.rs` runs the configs described by environment
+//! variables, and is left for the implementation's author to add their host
+//! to. Nothing is generated for documents without wasi-nn's functions.
+
+use super::{Options, OutputFile};
+use crate::tensor::ElementType;
+use crate::{Document, Id, InterfaceFunc, Layout, Module, Type, TypeRef, WasmType};
+use std::fmt::Write;
+
+/// Wasmtime and criterion releases the generated crate is written against.
+const WASMTIME_VERSION: &str = "48";
+const CRITERION_VERSION: &str = "0.7";
+
+/// The functions benchmarked, with the number of `i32`s each is lowered to.
+const FUNCS: &[(&str, usize)] = &[
+    ("load", 5),
+    ("init_execution_context", 2),
+    ("set_input", 3),
+    ("compute", 1),
+    ("get_output", 5),
+];
+
+pub(super) fn generate(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let (module, spec) = match doc.modules().find_map(|m| Some((m.clone(), Spec::of(&m)?))) {
+        Some(found) => found,
+        None => return Vec::new(),
+    };
+    let crate_name = options.crate_name.as_deref().unwrap_or("benchmark");
+    let manifest = format!(
+        "[package]\n\
+         name = \"{}\"\n\
+         version = \"0.1.0\"\n\
+         edition = \"2018\"\n\
+         \n\
+         [dependencies]\n\
+         criterion = \"{}\"\n\
+         wasmtime = {{ version = \"{}\", default-features = false, features = [\"runtime\", \"cranelift\", \"wat\"] }}\n\
+         \n\
+         [[bench]]\n\
+         name = \"inference\"\n\
+         harness = false\n",
+        crate_name, CRITERION_VERSION, WASMTIME_VERSION
+    );
+    let guest_path = format!("guests/{}.wat", module.name.as_str());
+    let lib = DRIVER.replace("// CONSTANTS\n", &constants(&module, &spec, &guest_path));
+    let bench = BENCH.replace("CRATE", &crate_name.replace('-', "_"));
+    vec![
+        OutputFile::new("Cargo.toml", manifest),
+        OutputFile::new(guest_path, guest(&module)),
+        OutputFile::new("src/lib.rs", lib),
+        OutputFile::new("benches/inference.rs", bench),
+    ]
+}
+
+/// What the driver needs to know about a module with wasi-nn's functions.
+struct Spec {
+    /// The cases of the encoding and target `load` takes, and of the error
+    /// enum of its result.
+    encodings: Vec<String>,
+    targets: Vec<String>,
+    errors: Vec<String>,
+    /// The cases of the tensor type enum.
+    tensor_types: Vec<ElementType>,
+    /// The size, alignment and field offsets of the tensor record
+    /// `set_input` takes, and the size of its type enum.
+    tensor_size: usize,
+    tensor_align: usize,
+    dimensions: usize,
+    ty: usize,
+    ty_size: usize,
+    data: usize,
+}
+
+impl Spec {
+    /// The spec of `module`, if it has each of [`FUNCS`], lowered as in
+    /// wasi-nn.
+    fn of(module: &Module) -> Option<Spec> {
+        let mut funcs = Vec::new();
+        for (name, nparams) in FUNCS {
+            let func = module.func(&Id::new(name))?;
+            let (params, results) = func.wasm_signature();
+            if params != vec![WasmType::I32; *nparams] || results != [WasmType::I32] {
+                return None;
+            }
+            funcs.push(func);
+        }
+        let (load, set_input) = (&funcs[0], &funcs[2]);
+        let tensor = match &**set_input.params.get(2)?.tref.type_() {
+            Type::Record(r) => r.clone(),
+            _ => return None,
+        };
+        let offset = |name: &str| {
+            tensor
+                .member_layout()
+                .iter()
+                .find(|m| m.member.name.as_str() == name)
+                .map(|m| m.offset)
+        };
+        let ty = tensor.members.iter().find(|m| m.name.as_str() == "type")?;
+        let tensor_types = match &ty.tref {
+            TypeRef::Name(nt) => ElementType::cases_of(nt)?,
+            TypeRef::Value(_) => return None,
+        };
+        let sa = tensor.mem_size_align();
+        Some(Spec {
+            encodings: cases(&load.params.get(1)?.tref)?,
+            targets: cases(&load.params.get(2)?.tref)?,
+            errors: error_cases(load)?,
+            tensor_types,
+            tensor_size: sa.size,
+            tensor_align: sa.align,
+            dimensions: offset("dimensions")?,
+            ty: offset("type")?,
+            ty_size: ty.tref.mem_size(),
+            data: offset("data")?,
+        })
+    }
+}
+
+/// The names of the cases of `tref`, if it's an enum.
+fn cases(tref: &TypeRef) -> Option<Vec<String>> {
+    match &**tref.type_() {
+        Type::Variant(v) if v.is_enum() => Some(
+            v.cases
+                .iter()
+                .map(|c| c.name.as_str().to_string())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// The cases of the error enum `func` returns.
+fn error_cases(func: &InterfaceFunc) -> Option<Vec<String>> {
+    let result = func.results.first()?;
+    match &**result.tref.type_() {
+        Type::Variant(v) => cases(v.as_expected()?.1?),
+        _ => None,
+    }
+}
+
+/// A list of string literals.
+fn strings(names: &[String]) -> String {
+    let names = names
+        .iter()
+        .map(|n| format!("\"{}\"", n))
+        .collect::<Vec<_>>();
+    format!("&[{}]", names.join(", "))
+}
+
+fn constants(module: &Module, spec: &Spec, guest_path: &str) -> String {
+    let enum_name = |tref: &TypeRef| match tref {
+        TypeRef::Name(nt) => nt.name.as_str().to_string(),
+        TypeRef::Value(_) => "enum".to_string(),
+    };
+    let load = module.func(&Id::new("load")).unwrap();
+    let tensor_types = spec
+        .tensor_types
+        .iter()
+        .map(|t| format!("(\"{}\", {})", t, t.bits()))
+        .collect::<Vec<_>>();
+    let mut out = String::new();
+    writeln!(out, "/// The module the host implements.").unwrap();
+    writeln!(
+        out,
+        "pub const MODULE: &str = \"{}\";",
+        module.name.as_str()
+    )
+    .unwrap();
+    writeln!(out, "/// A guest forwarding calls to the host's functions.").unwrap();
+    writeln!(
+        out,
+        "const GUEST: &str = include_str!(\"../{}\");",
+        guest_path
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "/// The cases of `{}`, by value.",
+        enum_name(&load.params[1].tref)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub const ENCODINGS: &[&str] = {};",
+        strings(&spec.encodings)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "/// The cases of `{}`, by value.",
+        enum_name(&load.params[2].tref)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub const TARGETS: &[&str] = {};",
+        strings(&spec.targets)
+    )
+    .unwrap();
+    writeln!(out, "/// The errors functions return, by value.").unwrap();
+    writeln!(
+        out,
+        "pub const ERRORS: &[&str] = {};",
+        strings(&spec.errors)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "/// The cases of `tensor_type`, by value, with the bits of their elements."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub const TENSOR_TYPES: &[(&str, usize)] = &[{}];",
+        tensor_types.join(", ")
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "// The layout of a tensor in guest memory.").unwrap();
+    for (name, value) in [
+        ("TENSOR_SIZE", spec.tensor_size),
+        ("TENSOR_ALIGN", spec.tensor_align),
+        ("TENSOR_DIMENSIONS", spec.dimensions),
+        ("TENSOR_TYPE", spec.ty),
+        ("TENSOR_TYPE_SIZE", spec.ty_size),
+        ("TENSOR_DATA", spec.data),
+    ] {
+        writeln!(out, "const {}: usize = {};", name, value).unwrap();
+    }
+    out
+}
+
+/// A guest program exporting a function for each of [`FUNCS`], forwarding
+/// its arguments to the host's.
+fn guest(module: &Module) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        ";; Forwards calls to `{}`, for the benchmark driver.",
+        module.name.as_str()
+    )
+    .unwrap();
+    out.push_str("(module\n");
+    let sig = |n: usize| format!("(param{}) (result i32)", " i32".repeat(n));
+    for (name, nparams) in FUNCS {
+        writeln!(
+            out,
+            "  (import \"{}\" \"{}\" (func ${} {}))",
+            module.name.as_str(),
+            name,
+            name,
+            sig(*nparams)
+        )
+        .unwrap();
+    }
+    out.push_str("  (memory (export \"memory\") 1)\n");
+    for (name, nparams) in FUNCS {
+        let args = (0..*nparams)
+            .map(|i| format!(" (local.get {})", i))
+            .collect::<String>();
+        writeln!(
+            out,
+            "  (func (export \"{}\") {}\n    (call ${}{}))",
+            name,
+            sig(*nparams),
+            name,
+            args
+        )
+        .unwrap();
+    }
+    out.push_str(")\n");
+    out
+}
+
+/// The driver, with the constants of the document in place of
+/// `// CONSTANTS`.
+const DRIVER: &str = r#"// This file is automatically generated by witx, DO NOT EDIT!
+
+use criterion::{Criterion, Throughput};
+use std::fmt;
+use wasmtime::{Instance, Linker, Memory, Module, Store, TypedFunc, WasmParams, WasmResults};
+
+// CONSTANTS
+
+/// Where functions return their results in guest memory.
+const RET: usize = 8;
+/// Where the data written by the driver starts.
+const START: usize = 16;
+
+/// The type and dimensions of a tensor of zeroes, written as
+/// `<type>:<dimensions>`, such as `f32:1x3x224x224`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tensor {
+    pub ty: &'static str,
+    pub dimensions: Vec<u32>,
+}
+
+impl Tensor {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (ty, dimensions) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected `<type>:<dimensions>`, got `{}`", s))?;
+        let ty = TENSOR_TYPES
+            .iter()
+            .map(|(name, _)| *name)
+            .find(|name| *name == ty)
+            .ok_or_else(|| format!("unknown tensor type `{}`", ty))?;
+        let dimensions = dimensions
+            .split('x')
+            .map(|d| d.parse().map_err(|_| format!("invalid dimension `{}`", d)))
+            .collect::<Result<_, _>>()?;
+        Ok(Tensor { ty, dimensions })
+    }
+
+    /// The value of the tensor's type.
+    pub fn type_value(&self) -> usize {
+        TENSOR_TYPES.iter().position(|(name, _)| *name == self.ty).unwrap()
+    }
+
+    /// The size of the tensor's data in bytes.
+    pub fn data_size(&self) -> usize {
+        let bits = TENSOR_TYPES[self.type_value()].1;
+        let elements = self.dimensions.iter().map(|d| *d as usize).product::<usize>();
+        (elements * bits + 7) / 8
+    }
+}
+
+impl fmt::Display for Tensor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dimensions = self.dimensions.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        write!(f, "{}:{}", self.ty, dimensions.join("x"))
+    }
+}
+
+/// What to benchmark: a graph, and the inputs to compute it with.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Name of the benchmark group.
+    pub name: String,
+    /// The graph builders passed to `load`.
+    pub model: Vec<Vec<u8>>,
+    pub encoding: &'static str,
+    pub target: &'static str,
+    /// The inputs, by index.
+    pub inputs: Vec<Tensor>,
+    /// The number of outputs to retrieve, and the space for each in bytes.
+    pub outputs: u32,
+    pub output_size: usize,
+}
+
+impl Config {
+    /// The configs described by environment variables, one for each set of
+    /// inputs:
+    ///
+    /// * `WASI_NN_BENCH_MODEL`: the files of the graph builders, separated
+    ///   as in `PATH`.
+    /// * `WASI_NN_BENCH_ENCODING` and `WASI_NN_BENCH_TARGET`: cases of the
+    ///   encoding and target, the first ones by default.
+    /// * `WASI_NN_BENCH_INPUTS`: sets of inputs separated by spaces, each a
+    ///   list of tensors separated by commas, such as `f32:1x3x224x224`.
+    /// * `WASI_NN_BENCH_OUTPUTS` and `WASI_NN_BENCH_OUTPUT_SIZE`: the number
+    ///   of outputs, 1 by default, and the space for each, 1 MiB by default.
+    pub fn from_env() -> Result<Vec<Config>, String> {
+        let var = |name: &str| std::env::var(name).ok();
+        let required = |name: &str| var(name).ok_or_else(|| format!("`{}` isn't set", name));
+        let model = std::env::split_paths(&required("WASI_NN_BENCH_MODEL")?)
+            .map(|path| {
+                std::fs::read(&path).map_err(|e| format!("failed to read {:?}: {}", path, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let case = |name: &str, cases: &'static [&'static str]| match var(name) {
+            Some(value) => cases
+                .iter()
+                .find(|c| **c == value)
+                .copied()
+                .ok_or_else(|| format!("`{}` isn't one of {}", value, cases.join(", "))),
+            None => Ok(cases[0]),
+        };
+        let encoding = case("WASI_NN_BENCH_ENCODING", ENCODINGS)?;
+        let target = case("WASI_NN_BENCH_TARGET", TARGETS)?;
+        let number = |name: &str, default: usize| match var(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("`{}` isn't a number: {}", name, value)),
+            None => Ok(default),
+        };
+        let outputs = number("WASI_NN_BENCH_OUTPUTS", 1)? as u32;
+        let output_size = number("WASI_NN_BENCH_OUTPUT_SIZE", 1 << 20)?;
+        required("WASI_NN_BENCH_INPUTS")?
+            .split_whitespace()
+            .map(|inputs| {
+                Ok(Config {
+                    name: format!("{}/{}", target, inputs),
+                    model: model.clone(),
+                    encoding,
+                    target,
+                    inputs: inputs.split(',').map(Tensor::parse).collect::<Result<_, _>>()?,
+                    outputs,
+                    output_size,
+                })
+            })
+            .collect()
+    }
+}
+
+/// An instance of the guest, calling the host's functions with values it
+/// has written to guest memory.
+pub struct Session<T: 'static> {
+    store: Store<T>,
+    memory: Memory,
+    /// The end of the data written so far.
+    top: usize,
+    load: TypedFunc<(i32, i32, i32, i32, i32), i32>,
+    init_execution_context: TypedFunc<(i32, i32), i32>,
+    set_input: TypedFunc<(i32, i32, i32), i32>,
+    compute: TypedFunc<i32, i32>,
+    get_output: TypedFunc<(i32, i32, i32, i32, i32), i32>,
+}
+
+fn typed<T, P: WasmParams, R: WasmResults>(
+    instance: &Instance,
+    store: &mut Store<T>,
+    name: &str,
+) -> Result<TypedFunc<P, R>, String> {
+    instance
+        .get_typed_func(store, name)
+        .map_err(|e| format!("`{}`: {}", name, e))
+}
+
+fn trapped(e: wasmtime::Error) -> String {
+    format!("trapped: {}", e)
+}
+
+/// The index of `name` in `cases`.
+fn case(cases: &[&str], name: &str) -> Result<i32, String> {
+    cases
+        .iter()
+        .position(|c| *c == name)
+        .map(|i| i as i32)
+        .ok_or_else(|| format!("unknown case `{}`", name))
+}
+
+impl<T: 'static> Session<T> {
+    pub fn new(linker: &Linker<T>, mut store: Store<T>) -> Result<Self, String> {
+        let module = Module::new(linker.engine(), GUEST).map_err(|e| e.to_string())?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| format!("instantiation failed: {}", e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .expect("the guest exports `memory`");
+        Ok(Session {
+            load: typed(&instance, &mut store, "load")?,
+            init_execution_context: typed(&instance, &mut store, "init_execution_context")?,
+            set_input: typed(&instance, &mut store, "set_input")?,
+            compute: typed(&instance, &mut store, "compute")?,
+            get_output: typed(&instance, &mut store, "get_output")?,
+            store,
+            memory,
+            top: START,
+        })
+    }
+
+    /// Copies `bytes` to guest memory aligned to `align`, growing it if need
+    /// be, and returns their address.
+    fn write(&mut self, bytes: &[u8], align: usize) -> Result<i32, String> {
+        let at = (self.top + align - 1) / align * align;
+        let end = at + bytes.len();
+        let size = self.memory.data_size(&self.store);
+        if end > size {
+            let pages = (end - size + 0xffff) / 0x10000;
+            self.memory
+                .grow(&mut self.store, pages as u64)
+                .map_err(|e| format!("failed to grow memory: {}", e))?;
+        }
+        self.memory.data_mut(&mut self.store)[at..end].copy_from_slice(bytes);
+        self.top = end;
+        Ok(at as i32)
+    }
+
+    /// The `u32` returned at `RET` by the last call.
+    fn ret(&self) -> u32 {
+        let bytes = &self.memory.data(&self.store)[RET..RET + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn check(func: &str, code: i32) -> Result<(), String> {
+        match code {
+            0 => Ok(()),
+            code => Err(format!(
+                "`{}` failed with `{}`",
+                func,
+                ERRORS.get(code as usize).copied().unwrap_or("an unknown error")
+            )),
+        }
+    }
+
+    /// Copies graph builders to guest memory, returning the list of them to
+    /// pass to `load`.
+    pub fn write_model(&mut self, model: &[Vec<u8>]) -> Result<(i32, i32), String> {
+        let mut list = Vec::new();
+        for builder in model {
+            let ptr = self.write(builder, 1)?;
+            list.extend_from_slice(&ptr.to_le_bytes());
+            list.extend_from_slice(&(builder.len() as u32).to_le_bytes());
+        }
+        Ok((self.write(&list, 4)?, model.len() as i32))
+    }
+
+    /// Copies a tensor of zeroes to guest memory, returning its address.
+    pub fn write_tensor(&mut self, tensor: &Tensor) -> Result<i32, String> {
+        let dimensions = tensor
+            .dimensions
+            .iter()
+            .flat_map(|d| d.to_le_bytes())
+            .collect::<Vec<_>>();
+        let dimensions = self.write(&dimensions, 4)?;
+        let data = self.write(&vec![0; tensor.data_size()], 8)?;
+        let mut record = vec![0; TENSOR_SIZE];
+        let mut field = |offset: usize, bytes: &[u8]| {
+            record[offset..offset + bytes.len()].copy_from_slice(bytes)
+        };
+        field(TENSOR_DIMENSIONS, &dimensions.to_le_bytes());
+        field(TENSOR_DIMENSIONS + 4, &(tensor.dimensions.len() as u32).to_le_bytes());
+        field(TENSOR_TYPE, &tensor.type_value().to_le_bytes()[..TENSOR_TYPE_SIZE]);
+        field(TENSOR_DATA, &data.to_le_bytes());
+        field(TENSOR_DATA + 4, &(tensor.data_size() as u32).to_le_bytes());
+        self.write(&record, TENSOR_ALIGN)
+    }
+
+    /// Reserves `len` bytes of guest memory for an output, returning their
+    /// address.
+    pub fn alloc(&mut self, len: usize) -> Result<i32, String> {
+        self.write(&vec![0; len], 8)
+    }
+
+    pub fn load(&mut self, model: (i32, i32), encoding: &str, target: &str) -> Result<i32, String> {
+        let args = (model.0, model.1, case(ENCODINGS, encoding)?, case(TARGETS, target)?, RET as i32);
+        let code = self.load.call(&mut self.store, args).map_err(trapped)?;
+        Self::check("load", code)?;
+        Ok(self.ret() as i32)
+    }
+
+    pub fn init_execution_context(&mut self, graph: i32) -> Result<i32, String> {
+        let code = self
+            .init_execution_context
+            .call(&mut self.store, (graph, RET as i32))
+            .map_err(trapped)?;
+        Self::check("init_execution_context", code)?;
+        Ok(self.ret() as i32)
+    }
+
+    /// Sets the input `index` to the tensor written at `tensor`.
+    pub fn set_input(&mut self, context: i32, index: u32, tensor: i32) -> Result<(), String> {
+        let code = self
+            .set_input
+            .call(&mut self.store, (context, index as i32, tensor))
+            .map_err(trapped)?;
+        Self::check("set_input", code)
+    }
+
+    pub fn compute(&mut self, context: i32) -> Result<(), String> {
+        let code = self.compute.call(&mut self.store, context).map_err(trapped)?;
+        Self::check("compute", code)
+    }
+
+    /// Copies the output `index` to the `len` bytes at `buffer`, returning
+    /// the size of the output.
+    pub fn get_output(&mut self, context: i32, index: u32, buffer: i32, len: usize) -> Result<usize, String> {
+        let args = (context, index as i32, buffer, len as i32, RET as i32);
+        let code = self.get_output.call(&mut self.store, args).map_err(trapped)?;
+        Self::check("get_output", code)?;
+        Ok(self.ret() as usize)
+    }
+}
+
+/// Benchmarks each function of the host in `linker`, and a whole inference,
+/// with `config`. Each benchmark reuses the graph and execution context
+/// created before it, so `load` and `init_execution_context` create as many
+/// as they're called.
+pub fn bench<T: 'static>(
+    c: &mut Criterion,
+    linker: &Linker<T>,
+    store: Store<T>,
+    config: &Config,
+) -> Result<(), String> {
+    let mut s = Session::new(linker, store)?;
+    let model = s.write_model(&config.model)?;
+    let inputs = config
+        .inputs
+        .iter()
+        .map(|t| s.write_tensor(t))
+        .collect::<Result<Vec<_>, _>>()?;
+    let output = s.alloc(config.output_size)?;
+    let input_bytes = config.inputs.iter().map(|t| t.data_size() as u64).sum();
+
+    let graph = s.load(model, config.encoding, config.target)?;
+    let context = s.init_execution_context(graph)?;
+    for (i, tensor) in inputs.iter().enumerate() {
+        s.set_input(context, i as u32, *tensor)?;
+    }
+    s.compute(context)?;
+    for i in 0..config.outputs {
+        s.get_output(context, i, output, config.output_size)?;
+    }
+
+    let mut group = c.benchmark_group(config.name.clone());
+    group.bench_function("load", |b| {
+        b.iter(|| s.load(model, config.encoding, config.target).unwrap())
+    });
+    group.bench_function("init_execution_context", |b| {
+        b.iter(|| s.init_execution_context(graph).unwrap())
+    });
+    group.bench_function("compute", |b| b.iter(|| s.compute(context).unwrap()));
+    group.bench_function("get_output", |b| {
+        b.iter(|| {
+            for i in 0..config.outputs {
+                s.get_output(context, i, output, config.output_size).unwrap();
+            }
+        })
+    });
+    group.throughput(Throughput::Bytes(input_bytes));
+    group.bench_function("set_input", |b| {
+        b.iter(|| {
+            for (i, tensor) in inputs.iter().enumerate() {
+                s.set_input(context, i as u32, *tensor).unwrap();
+            }
+        })
+    });
+    group.bench_function("inference", |b| {
+        b.iter(|| {
+            for (i, tensor) in inputs.iter().enumerate() {
+                s.set_input(context, i as u32, *tensor).unwrap();
+            }
+            s.compute(context).unwrap();
+            for i in 0..config.outputs {
+                s.get_output(context, i, output, config.output_size).unwrap();
+            }
+        })
+    });
+    group.finish();
+    Ok(())
+}
+"#;
+
+/// The benchmarks, with the name of the crate in place of `CRATE`.
+const BENCH: &str = r#"//! Benchmarks of a host implementation, with the configs described by
+//! `Config::from_env`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::{Linker, Store};
+
+/// The data of the host implementation's stores.
+type Host = ();
+
+/// A linker with the host implementation's functions, and a store for it.
+fn host() -> (Linker<Host>, Store<Host>) {
+    // TODO: add the implementation of `CRATE::MODULE` to a linker, such as
+    // with the `add_to_linker` function of its wasmtime-host bindings.
+    todo!()
+}
+
+fn inference(c: &mut Criterion) {
+    for config in CRATE::Config::from_env().unwrap() {
+        let (linker, store) = host();
+        CRATE::bench(c, &linker, store, &config).unwrap();
+    }
+}
+
+criterion_group!(benches, inference);
+criterion_main!(benches);
+"#;
@@ -0,0 +1,528 @@
+//! A C header declaring the types and imports of a document.
+//!
+//! The header follows the conventions of wasi-libc's `api.h`: every type is a
+//! `typedef` with a `_t` suffix, enum cases and flags are `#define`d
+//! constants, and functions are declared with the `import_module` and
+//! `import_name` attributes understood by clang. When compiled for wasm32,
+//! the size and alignment of every type are checked with static assertions.
+
+use super::{doc_comment, file_stem, Options, OutputFile};
+use crate::tensor::ElementType;
+use crate::{
+    BuiltinType, Document, Id, IntRepr, InterfaceFunc, Layout, Module, NamedType, Type, TypeRef,
+};
+use std::fmt::Write;
+
+pub(super) fn generate(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let stem = file_stem(doc);
+    let guard = match &options.header_guard {
+        Some(guard) => guard.clone(),
+        None => format!("{}_H", macro_case(&stem)),
+    };
+    let names = Names::new(doc, options);
+    let header = render(doc, &names, &guard);
+    vec![OutputFile::new(format!("{}.h", stem), header)]
+}
+
+pub(super) fn macro_case(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The C names of the items of a document.
+pub(super) struct Names {
+    pub(super) prefix: String,
+    /// Whether function names include their module, which is needed when
+    /// several modules may export functions of the same name.
+    qualify_funcs: bool,
+}
+
+impl Names {
+    pub(super) fn new(doc: &Document, options: &Options) -> Names {
+        // Without a namespace the module name is used as a prefix, since type
+        // names like `size` would otherwise clash with the C standard library.
+        Names {
+            prefix: match &options.namespace {
+                Some(ns) => ns.clone(),
+                None => format!("{}_", file_stem(doc)),
+            },
+            qualify_funcs: doc.modules().count() > 1,
+        }
+    }
+
+    pub(super) fn type_(&self, name: &Id) -> String {
+        format!("{}{}_t", self.prefix, name.as_str())
+    }
+
+    pub(super) fn constant(&self, ty: &Id, name: &Id) -> String {
+        format!(
+            "{}{}_{}",
+            macro_case(&self.prefix),
+            macro_case(ty.as_str()),
+            macro_case(name.as_str())
+        )
+    }
+
+    pub(super) fn func(&self, module: &Module, func: &InterfaceFunc) -> String {
+        if self.qualify_funcs {
+            format!(
+                "{}{}_{}",
+                self.prefix,
+                module.name.as_str(),
+                func.name.as_str()
+            )
+        } else {
+            format!("{}{}", self.prefix, func.name.as_str())
+        }
+    }
+
+    pub(super) fn tref(&self, tref: &TypeRef) -> String {
+        match tref {
+            TypeRef::Name(nt) => self.type_(&nt.name),
+            TypeRef::Value(ty) => self.anonymous(ty),
+        }
+    }
+
+    pub(super) fn anonymous(&self, ty: &Type) -> String {
+        match ty {
+            Type::Builtin(b) => builtin(*b).to_string(),
+            Type::Pointer(t) => format!("{} *", self.tref(t)),
+            Type::ConstPointer(t) => format!("const {} *", self.tref(t)),
+            Type::Handle(_) => "int32_t".to_string(),
+            Type::List(t) => format!("struct {{ {}; size_t buf_len; }}", self.list_buf(t)),
+            Type::Variant(v) if v.is_bool() => "bool".to_string(),
+            Type::Variant(v) if v.is_enum() => int_repr(v.tag_repr).to_string(),
+            Type::Record(r) if r.bitflags_repr().is_some() => {
+                int_repr(r.bitflags_repr().unwrap()).to_string()
+            }
+            Type::Record(_) | Type::Variant(_) => {
+                unreachable!("anonymous {} types have no C representation", ty.kind())
+            }
+        }
+    }
+
+    /// The declaration of the pointer to the elements of a list of `elem`.
+    fn list_buf(&self, elem: &TypeRef) -> String {
+        match &**elem.type_() {
+            Type::Builtin(BuiltinType::Char) => "const char *buf".to_string(),
+            _ => decl(&format!("{} *", self.tref(elem)), "buf"),
+        }
+    }
+}
+
+/// Declare `name` with type `ty`.
+pub(super) fn decl(ty: &str, name: &str) -> String {
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+pub(super) fn ident(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return format!("f{}", name);
+    }
+    match name {
+        "auto" | "break" | "case" | "char" | "const" | "continue" | "default" | "do" | "double"
+        | "else" | "enum" | "extern" | "float" | "for" | "goto" | "if" | "inline" | "int"
+        | "long" | "register" | "restrict" | "return" | "short" | "signed" | "sizeof"
+        | "static" | "struct" | "switch" | "typedef" | "union" | "unsigned" | "void"
+        | "volatile" | "while" | "bool" => format!("{}_", name),
+        _ => name.to_string(),
+    }
+}
+
+fn builtin(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::Char => "uint32_t",
+        BuiltinType::U8 { lang_c_char: true } => "char",
+        BuiltinType::U8 { lang_c_char: false } => "uint8_t",
+        BuiltinType::U16 => "uint16_t",
+        BuiltinType::U32 {
+            lang_ptr_size: false,
+        } => "uint32_t",
+        BuiltinType::U32 {
+            lang_ptr_size: true,
+        } => "size_t",
+        BuiltinType::U64 => "uint64_t",
+        BuiltinType::S8 => "int8_t",
+        BuiltinType::S16 => "int16_t",
+        BuiltinType::S32 => "int32_t",
+        BuiltinType::S64 => "int64_t",
+        BuiltinType::F32 => "float",
+        BuiltinType::F64 => "double",
+    }
+}
+
+pub(super) fn int_repr(repr: IntRepr) -> &'static str {
+    builtin(repr.to_builtin())
+}
+
+pub(super) fn comment(out: &mut String, docs: &str, indent: &str) {
+    if docs.trim().is_empty() {
+        return;
+    }
+    writeln!(out, "{}/**", indent).unwrap();
+    out.push_str(&doc_comment(docs, " *", indent));
+    writeln!(out, "{} */", indent).unwrap();
+}
+
+fn render(doc: &Document, names: &Names, guard: &str) -> String {
+    let mut out = String::new();
+    out.push_str("/**\n * This file is automatically generated by witx, DO NOT EDIT!\n */\n\n");
+    writeln!(out, "#ifndef {}", guard).unwrap();
+    writeln!(out, "#define {}", guard).unwrap();
+    out.push_str(
+        "\n#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n#include <string.h>\n",
+    );
+    out.push_str("\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n");
+    let types = doc
+        .types_topo_order()
+        .expect("validated documents have no cycles");
+    for nt in types {
+        out.push('\n');
+        define_type(&mut out, names, &nt);
+    }
+    layout_assertions(&mut out, doc, names);
+    for c in doc.constants() {
+        out.push('\n');
+        comment(&mut out, &c.docs, "");
+        writeln!(
+            out,
+            "#define {} (({}){})",
+            names.constant(&c.ty, &c.name),
+            names.type_(&c.ty),
+            c.value
+        )
+        .unwrap();
+    }
+    for m in doc.modules() {
+        for f in m.funcs() {
+            out.push('\n');
+            declare_func(&mut out, names, &m, &f);
+        }
+    }
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n");
+    writeln!(out, "\n#endif /* {} */", guard).unwrap();
+    out
+}
+
+/// Check the layout witx calculates for each type against the compiler's,
+/// so the header fails to compile if they ever disagree. Layouts are of
+/// wasm32 memory, so they're only checked when compiling for it.
+fn layout_assertions(out: &mut String, doc: &Document, names: &Names) {
+    let check = format!("{}ASSERT_LAYOUT", macro_case(&names.prefix));
+    out.push_str("\n#if defined(__wasm32__)\n#ifdef __cplusplus\n");
+    writeln!(
+        out,
+        "#define {}(t, size, align) static_assert(sizeof(t) == size && alignof(t) == align, \"layout of \" #t)",
+        check
+    )
+    .unwrap();
+    out.push_str("#else\n");
+    writeln!(
+        out,
+        "#define {}(t, size, align) _Static_assert(sizeof(t) == size && _Alignof(t) == align, \"layout of \" #t)",
+        check
+    )
+    .unwrap();
+    out.push_str("#endif\n");
+    for nt in doc.typenames() {
+        let layout = nt.mem_size_align();
+        writeln!(
+            out,
+            "{}({}, {}, {});",
+            check,
+            names.type_(&nt.name),
+            layout.size,
+            layout.align
+        )
+        .unwrap();
+    }
+    writeln!(out, "#undef {}\n#endif", check).unwrap();
+}
+
+fn define_type(out: &mut String, names: &Names, nt: &NamedType) {
+    let name = names.type_(&nt.name);
+    comment(out, &nt.docs, "");
+    let ty = match &nt.tref {
+        TypeRef::Name(other) => {
+            writeln!(out, "typedef {} {};", names.type_(&other.name), name).unwrap();
+            return;
+        }
+        TypeRef::Value(ty) => ty,
+    };
+    match &**ty {
+        Type::Record(r) => {
+            if let Some(repr) = r.bitflags_repr() {
+                writeln!(out, "typedef {} {};", int_repr(repr), name).unwrap();
+                for (i, m) in r.members.iter().enumerate() {
+                    comment(out, &m.docs, "");
+                    writeln!(
+                        out,
+                        "#define {} ((({})1) << {})",
+                        names.constant(&nt.name, &m.name),
+                        name,
+                        i
+                    )
+                    .unwrap();
+                }
+                let flags = r.members.iter().map(|m| &m.name).collect::<Vec<_>>();
+                name_funcs(out, names, nt, &flags, true);
+            } else {
+                writeln!(out, "typedef struct {} {{", name).unwrap();
+                for (i, m) in r.members.iter().enumerate() {
+                    comment(out, &m.docs, "    ");
+                    let field = if r.is_tuple() {
+                        format!("f{}", i)
+                    } else {
+                        ident(m.name.as_str())
+                    };
+                    writeln!(out, "    {};", decl(&names.tref(&m.tref), &field)).unwrap();
+                }
+                writeln!(out, "}} {};", name).unwrap();
+            }
+        }
+        Type::Variant(v) if v.is_enum() => {
+            writeln!(out, "typedef {} {};", int_repr(v.tag_repr), name).unwrap();
+            for (i, c) in v.cases.iter().enumerate() {
+                comment(out, &c.docs, "");
+                writeln!(
+                    out,
+                    "#define {} (({}){})",
+                    names.constant(&nt.name, &c.name),
+                    name,
+                    i
+                )
+                .unwrap();
+            }
+            let cases = v.cases.iter().map(|c| &c.name).collect::<Vec<_>>();
+            name_funcs(out, names, nt, &cases, false);
+            tensor_type_funcs(out, names, nt);
+        }
+        Type::Variant(v) => {
+            let union = names.type_(&Id::new(format!("{}_u", nt.name.as_str())));
+            writeln!(out, "typedef union {} {{", union).unwrap();
+            for c in v.cases.iter() {
+                if let Some(tref) = &c.tref {
+                    comment(out, &c.docs, "    ");
+                    writeln!(
+                        out,
+                        "    {};",
+                        decl(&names.tref(tref), &ident(c.name.as_str()))
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(out, "}} {};", union).unwrap();
+            writeln!(out, "typedef struct {} {{", name).unwrap();
+            writeln!(out, "    {} tag;", int_repr(v.tag_repr)).unwrap();
+            writeln!(out, "    {} u;", union).unwrap();
+            writeln!(out, "}} {};", name).unwrap();
+        }
+        Type::List(elem) => {
+            writeln!(out, "typedef struct {} {{", name).unwrap();
+            writeln!(out, "    {};", names.list_buf(elem)).unwrap();
+            out.push_str("    size_t buf_len;\n");
+            writeln!(out, "}} {};", name).unwrap();
+        }
+        Type::Pointer(_) | Type::ConstPointer(_) | Type::Handle(_) | Type::Builtin(_) => {
+            writeln!(out, "typedef {};", decl(&names.anonymous(ty), &name)).unwrap();
+        }
+    }
+}
+
+/// Functions between the values of the enum or flags type `nt` and their witx
+/// names, and the number of values to iterate over. The values of enums are
+/// numbered from 0, and flag `i` is `1 << i`.
+fn name_funcs(out: &mut String, names: &Names, nt: &NamedType, values: &[&Id], flags: bool) {
+    let name = names.type_(&nt.name);
+    let func = format!("{}{}", names.prefix, nt.name.as_str());
+    let (what, value) = if flags {
+        ("flags", format!("({})1 << i", name))
+    } else {
+        ("values", format!("({})i", name))
+    };
+    writeln!(
+        out,
+        "\n/**\n * The number of {} of `{}`.\n */\n#define {}_COUNT {}",
+        what,
+        name,
+        macro_case(&func),
+        values.len()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "\n/**\n * The witx name of `value`, or NULL if it isn't {}.\n */",
+        if flags { "a single flag" } else { "known" }
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "static inline const char *{}_name({} value) {{",
+        func, name
+    )
+    .unwrap();
+    out.push_str("    switch (value) {\n");
+    for v in values.iter() {
+        writeln!(
+            out,
+            "    case {}: return \"{}\";",
+            names.constant(&nt.name, v),
+            v.as_str()
+        )
+        .unwrap();
+    }
+    out.push_str("    default: return NULL;\n    }\n}\n");
+    out.push_str(
+        "\n/**\n * Set `*value` to the value named `name` in witx, returning whether\n \
+         * there is one.\n */\n",
+    );
+    writeln!(
+        out,
+        "static inline bool {}_from_name(const char *name, {} *value) {{",
+        func, name
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    for (size_t i = 0; i < {}_COUNT; i++) {{",
+        macro_case(&func)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "        if (strcmp(name, {}_name({})) == 0) {{",
+        func, value
+    )
+    .unwrap();
+    writeln!(out, "            *value = {};", value).unwrap();
+    out.push_str("            return true;\n        }\n    }\n    return false;\n}\n");
+}
+
+/// Functions giving the size of tensor data, if `nt` is the enum of tensor
+/// element types.
+fn tensor_type_funcs(out: &mut String, names: &Names, nt: &NamedType) {
+    let (types, ty) = match (ElementType::cases_of(nt), &**nt.type_()) {
+        (Some(types), Type::Variant(v)) => (types, v.clone()),
+        _ => return,
+    };
+    let name = names.type_(&nt.name);
+    let func = format!("{}{}", names.prefix, nt.name.as_str());
+    out.push_str(
+        "\n/**\n * Size of an element of `ty` in bits, or 0 if the type isn't known.\n */\n",
+    );
+    writeln!(out, "static inline uint32_t {}_bits({} ty) {{", func, name).unwrap();
+    out.push_str("    switch (ty) {\n");
+    for (c, element) in ty.cases.iter().zip(types) {
+        writeln!(
+            out,
+            "    case {}: return {};",
+            names.constant(&nt.name, &c.name),
+            element.bits()
+        )
+        .unwrap();
+    }
+    out.push_str("    default: return 0;\n    }\n}\n");
+    out.push_str(
+        "\n/**\n * Bytes of data in a tensor of type `ty` with `dimensions`, or 0 if the\n \
+         * type isn't known or the size overflows. Packed elements are padded to a\n \
+         * whole byte.\n */\n",
+    );
+    writeln!(
+        out,
+        "static inline size_t {}_buffer_size({} ty, const uint32_t *dimensions, size_t dimensions_len) {{",
+        func, name
+    )
+    .unwrap();
+    writeln!(out, "    size_t bits = {}_bits(ty);", func).unwrap();
+    out.push_str("    for (size_t i = 0; i < dimensions_len; i++) {\n");
+    out.push_str("        if (dimensions[i] != 0 && bits > SIZE_MAX / dimensions[i]) {\n");
+    out.push_str("            return 0;\n        }\n");
+    out.push_str("        bits *= dimensions[i];\n    }\n");
+    out.push_str("    return bits / 8 + (bits % 8 != 0);\n}\n");
+}
+
+fn declare_func(out: &mut String, names: &Names, module: &Module, func: &InterfaceFunc) {
+    comment(out, &func.docs, "");
+    let mut params = Vec::new();
+    for p in func.params.iter() {
+        let name = ident(p.name.as_str());
+        match &**p.tref.type_() {
+            Type::List(elem) => {
+                let buf = match &**elem.type_() {
+                    Type::Builtin(BuiltinType::Char) => "const char *".to_string(),
+                    _ => format!("const {} *", names.tref(elem)),
+                };
+                params.push(decl(&buf, &name));
+                params.push(format!("size_t {}_len", name));
+            }
+            Type::Record(r) if r.bitflags_repr().is_none() => {
+                params.push(decl(&format!("const {} *", names.tref(&p.tref)), &name))
+            }
+            Type::Variant(v) if !v.is_enum() => {
+                params.push(decl(&format!("const {} *", names.tref(&p.tref)), &name))
+            }
+            _ => params.push(decl(&names.tref(&p.tref), &name)),
+        }
+    }
+
+    let mut ret = "void".to_string();
+    if let Some(result) = func.results.first() {
+        match &**result.tref.type_() {
+            Type::Variant(v) if !v.is_enum() => {
+                let (ok, err) = v.as_expected().expect("results are `expected` variants");
+                ret = match err {
+                    Some(err) => names.tref(err),
+                    None => int_repr(v.tag_repr).to_string(),
+                };
+                if let Some(ok) = ok {
+                    match &**ok.type_() {
+                        Type::Record(r) if r.is_tuple() => {
+                            for (i, m) in r.members.iter().enumerate() {
+                                let ty = format!("{} *", names.tref(&m.tref));
+                                params.push(decl(&ty, &format!("retptr{}", i)));
+                            }
+                        }
+                        _ => params.push(decl(&format!("{} *", names.tref(ok)), "retptr0")),
+                    }
+                }
+            }
+            _ => ret = names.tref(&result.tref),
+        }
+    }
+    if params.is_empty() {
+        params.push("void".to_string());
+    }
+    // Unlike `_Noreturn`, the attribute is also understood by C++ compilers.
+    let noreturn = if func.noreturn {
+        "__attribute__((__noreturn__)) "
+    } else {
+        ""
+    };
+    writeln!(
+        out,
+        "{}{}{}(",
+        noreturn,
+        decl(&ret, ""),
+        names.func(module, func)
+    )
+    .unwrap();
+    writeln!(out, "    {}", params.join(",\n    ")).unwrap();
+    writeln!(
+        out,
+        ") __attribute__((__import_module__(\"{}\"), __import_name__(\"{}\")));",
+        module.name.as_str(),
+        func.name.as_str()
+    )
+    .unwrap();
+}
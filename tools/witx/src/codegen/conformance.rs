@@ -0,0 +1,391 @@
+//! A conformance suite for hosts implementing a document's modules.
+//!
+//! Every function is called from a small guest program, written in the wasm
+//! text format, with arguments chosen from its interface signature: all
+//! zeroes (empty lists, and records in zeroed memory, so tensors with no
+//! dimensions or data), a handle that was never returned by the host, and an
+//! enum value past the last case. The generated driver instantiates each
+//! program against a `wasmtime::Linker` provided by the implementation and
+//! checks that zeroed arguments don't trap, invalid handles are reported as
+//! errors, and unknown enum values are rejected. Zeroed arguments which don't
+//! have their declared sizes, such as a tensor with no data for its single
+//! element, must be rejected instead.
+
+use super::{Options, OutputFile};
+use crate::tensor::ElementType;
+use crate::{Document, Id, InterfaceFunc, Module, SizeExpr, Type, TypeRef, WasmType};
+use std::fmt::Write;
+
+/// Wasmtime release the generated driver is written against.
+const WASMTIME_VERSION: &str = "48";
+
+/// Zeroed guest memory that inputs passed by pointer point to.
+const INPUT_AREA: u32 = 1024;
+/// Guest memory that return pointers point to.
+const OUTPUT_AREA: u32 = 32768;
+/// Space reserved for each return pointer.
+const OUTPUT_SLOT: u32 = 64;
+
+/// What a host must do for a test to pass.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Expect {
+    /// Return, with any result.
+    NoTrap,
+    /// Return a non-zero error code.
+    Error,
+    /// Return a non-zero error code, or trap.
+    Rejected,
+}
+
+impl Expect {
+    fn name(&self) -> &'static str {
+        match self {
+            Expect::NoTrap => "NoTrap",
+            Expect::Error => "Error",
+            Expect::Rejected => "Rejected",
+        }
+    }
+}
+
+/// A single call of a function.
+struct Case {
+    name: String,
+    expect: Expect,
+    /// Value of each core wasm argument.
+    args: Vec<i64>,
+}
+
+pub(super) fn generate(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let crate_name = options.crate_name.as_deref().unwrap_or("conformance");
+    let manifest = format!(
+        "[package]\n\
+         name = \"{}\"\n\
+         version = \"0.1.0\"\n\
+         edition = \"2018\"\n\
+         \n\
+         [dependencies]\n\
+         wasmtime = {{ version = \"{}\", default-features = false, features = [\"runtime\", \"cranelift\", \"wat\"] }}\n",
+        crate_name, WASMTIME_VERSION
+    );
+    let mut files = vec![OutputFile::new("Cargo.toml", manifest)];
+
+    let mut tests = String::new();
+    for m in doc.modules() {
+        for f in m.funcs() {
+            // Calling a function which doesn't return would end the test.
+            if f.noreturn {
+                continue;
+            }
+            for case in cases(&f) {
+                let path = format!(
+                    "guests/{}/{}-{}.wat",
+                    m.name.as_str(),
+                    f.name.as_str(),
+                    case.name
+                );
+                writeln!(
+                    tests,
+                    "    Test {{\n        \
+                     module: \"{}\",\n        \
+                     func: \"{}\",\n        \
+                     case: \"{}\",\n        \
+                     expect: Expect::{},\n        \
+                     wat: include_str!(\"../{}\"),\n    \
+                     }},",
+                    m.name.as_str(),
+                    f.name.as_str(),
+                    case.name,
+                    case.expect.name(),
+                    path
+                )
+                .unwrap();
+                files.push(OutputFile::new(path, guest(&m, &f, &case)));
+            }
+        }
+    }
+    let lib = DRIVER.replace("    // TESTS\n", &tests);
+    files.push(OutputFile::new("src/lib.rs", lib));
+    files
+}
+
+/// The cases exercising `func`.
+fn cases(func: &InterfaceFunc) -> Vec<Case> {
+    let (params, _) = func.wasm_signature();
+    let returns_error = func.results.iter().any(|r| match &**r.tref.type_() {
+        Type::Variant(v) => v.as_expected().is_some(),
+        _ => false,
+    });
+
+    let mut zeroed = Vec::new();
+    // Index of the core wasm argument of each handle and enum parameter.
+    let mut handles = Vec::new();
+    let mut enums = Vec::new();
+    for p in func.params.iter() {
+        match &**p.tref.type_() {
+            Type::Handle(_) => {
+                handles.push((p.name.as_str(), zeroed.len()));
+                zeroed.push(0);
+            }
+            Type::Variant(v) if v.is_enum() => {
+                enums.push((p.name.as_str(), zeroed.len(), v.cases.len() as i64));
+                zeroed.push(0);
+            }
+            Type::List(_) => {
+                zeroed.push(i64::from(INPUT_AREA));
+                zeroed.push(0);
+            }
+            Type::Pointer(_) | Type::ConstPointer(_) => zeroed.push(i64::from(INPUT_AREA)),
+            Type::Record(r) if r.bitflags_repr().is_none() => zeroed.push(i64::from(INPUT_AREA)),
+            _ => zeroed.push(0),
+        }
+    }
+    for i in 0..params.len() - zeroed.len() {
+        zeroed.push(i64::from(OUTPUT_AREA + OUTPUT_SLOT * i as u32));
+    }
+
+    let params = func
+        .params
+        .iter()
+        .map(|p| (p.name.clone(), p.tref.clone()))
+        .collect::<Vec<_>>();
+    let valid = func.params.iter().all(|p| {
+        p.size
+            .as_ref()
+            .is_none_or(|s| zeroed_size(s, &params) == Some(0))
+            && zeroed_valid(&p.tref)
+    });
+    let mut cases = vec![Case {
+        name: "zeroed".to_string(),
+        expect: if valid {
+            Expect::NoTrap
+        } else {
+            Expect::Rejected
+        },
+        args: zeroed.clone(),
+    }];
+    if returns_error {
+        for (name, i) in handles {
+            let mut args = zeroed.clone();
+            args[i] = -1;
+            cases.push(Case {
+                name: format!("invalid-{}", name),
+                expect: if valid {
+                    Expect::Error
+                } else {
+                    Expect::Rejected
+                },
+                args,
+            });
+        }
+    }
+    for (name, i, ncases) in enums {
+        let mut args = zeroed.clone();
+        args[i] = ncases;
+        cases.push(Case {
+            name: format!("unsupported-{}", name),
+            expect: Expect::Rejected,
+            args,
+        });
+    }
+    cases
+}
+
+/// Whether the lists in a zeroed `tref`, which are empty, have their declared
+/// sizes.
+fn zeroed_valid(tref: &TypeRef) -> bool {
+    match &**tref.type_() {
+        Type::Record(r) => {
+            let fields = r
+                .members
+                .iter()
+                .map(|m| (m.name.clone(), m.tref.clone()))
+                .collect::<Vec<_>>();
+            r.members.iter().all(|m| {
+                m.size
+                    .as_ref()
+                    .is_none_or(|s| zeroed_size(s, &fields) == Some(0))
+                    && zeroed_valid(&m.tref)
+            })
+        }
+        _ => true,
+    }
+}
+
+/// The value of `size` when the fields or parameters in `scope` are zeroed.
+fn zeroed_size(size: &SizeExpr, scope: &[(Id, TypeRef)]) -> Option<u64> {
+    match size {
+        SizeExpr::Const(n) => Some(*n),
+        SizeExpr::Value(_) | SizeExpr::Len(_) => Some(0),
+        SizeExpr::Product(_) => Some(1),
+        SizeExpr::TensorSize { ty, .. } => {
+            let tref = &scope.iter().find(|(name, _)| name == ty)?.1;
+            let first = match tref {
+                TypeRef::Name(nt) => *ElementType::cases_of(nt)?.first()?,
+                TypeRef::Value(_) => return None,
+            };
+            first.buffer_size(&[]).map(|n| n as u64)
+        }
+        SizeExpr::Mul(exprs) => exprs
+            .iter()
+            .try_fold(1u64, |n, e| n.checked_mul(zeroed_size(e, scope)?)),
+        SizeExpr::Add(exprs) => exprs
+            .iter()
+            .try_fold(0u64, |n, e| n.checked_add(zeroed_size(e, scope)?)),
+    }
+}
+
+/// A guest program exporting a `run` function which calls `func` once.
+fn guest(module: &Module, func: &InterfaceFunc, case: &Case) -> String {
+    let (params, results) = func.wasm_signature();
+    let sig = |kind: &str, tys: &[WasmType]| {
+        if tys.is_empty() {
+            String::new()
+        } else {
+            let tys = tys.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+            format!(" ({} {})", kind, tys.join(" "))
+        }
+    };
+    let args = params
+        .iter()
+        .zip(case.args.iter())
+        .map(|(ty, v)| format!(" ({}.const {})", ty, v))
+        .collect::<String>();
+    let mut out = String::new();
+    writeln!(
+        out,
+        ";; {}::{}: {}",
+        module.name.as_str(),
+        func.name.as_str(),
+        case.name
+    )
+    .unwrap();
+    out.push_str("(module\n");
+    writeln!(
+        out,
+        "  (import \"{}\" \"{}\" (func $f{}{}))",
+        module.name.as_str(),
+        func.name.as_str(),
+        sig("param", &params),
+        sig("result", &results)
+    )
+    .unwrap();
+    out.push_str("  (memory (export \"memory\") 1)\n");
+    writeln!(
+        out,
+        "  (func (export \"run\"){}\n    (call $f{})))",
+        sig("result", &results),
+        args
+    )
+    .unwrap();
+    out
+}
+
+/// The driver, with the generated tests in place of `// TESTS`.
+const DRIVER: &str = r#"// This file is automatically generated by witx, DO NOT EDIT!
+
+use std::fmt::{self, Write};
+use wasmtime::{Linker, Module, Store, Val};
+
+/// What a host must do for a test to pass.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Expect {
+    /// Return, with any result.
+    NoTrap,
+    /// Return a non-zero error code.
+    Error,
+    /// Return a non-zero error code, or trap.
+    Rejected,
+}
+
+/// A guest program calling `module::func` once.
+#[derive(Debug)]
+pub struct Test {
+    pub module: &'static str,
+    pub func: &'static str,
+    pub case: &'static str,
+    pub expect: Expect,
+    pub wat: &'static str,
+}
+
+pub const TESTS: &[Test] = &[
+    // TESTS
+];
+
+/// The result of running a test, with a description of the failure.
+#[derive(Debug)]
+pub struct Outcome {
+    pub test: &'static Test,
+    pub result: Result<(), String>,
+}
+
+/// Run every test against the functions in `linker`, with a new store from
+/// `new_store` for each test.
+pub fn run<T: 'static>(linker: &Linker<T>, mut new_store: impl FnMut() -> Store<T>) -> Vec<Outcome> {
+    TESTS
+        .iter()
+        .map(|test| Outcome {
+            test,
+            result: run_one(linker, new_store(), test),
+        })
+        .collect()
+}
+
+fn run_one<T: 'static>(linker: &Linker<T>, mut store: Store<T>, test: &Test) -> Result<(), String> {
+    let module = Module::new(linker.engine(), test.wat).map_err(|e| e.to_string())?;
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .map_err(|e| format!("instantiation failed: {}", e))?;
+    let run = instance
+        .get_func(&mut store, "run")
+        .expect("tests export `run`");
+    let mut results = vec![Val::I32(0); run.ty(&store).results().len()];
+    let code = match run.call(&mut store, &[], &mut results) {
+        Ok(()) => results.first().and_then(|v| v.i32()).unwrap_or(0),
+        Err(trap) => {
+            return match test.expect {
+                Expect::Rejected => Ok(()),
+                _ => Err(format!("trapped: {}", trap)),
+            }
+        }
+    };
+    match test.expect {
+        Expect::Error | Expect::Rejected if code == 0 => Err("returned success".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Pass or fail of each function, with the failing cases.
+pub fn report(outcomes: &[Outcome]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < outcomes.len() {
+        let test = outcomes[i].test;
+        let n = outcomes[i..]
+            .iter()
+            .take_while(|o| o.test.module == test.module && o.test.func == test.func)
+            .count();
+        let failures = outcomes[i..i + n]
+            .iter()
+            .filter_map(|o| o.result.as_ref().err().map(|e| (o.test.case, e)))
+            .collect::<Vec<_>>();
+        let status = if failures.is_empty() { "PASS" } else { "FAIL" };
+        writeln!(out, "{} {}::{}", status, test.module, test.func).unwrap();
+        for (case, error) in failures {
+            writeln!(out, "    {}: {}", case, error).unwrap();
+        }
+        i += n;
+    }
+    out
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = if self.result.is_ok() { "PASS" } else { "FAIL" };
+        write!(
+            f,
+            "{} {}::{} {}",
+            status, self.test.module, self.test.func, self.test.case
+        )
+    }
+}
+"#;
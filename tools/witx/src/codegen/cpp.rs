@@ -0,0 +1,539 @@
+//! A C++17 header wrapping the C header of a document.
+//!
+//! The C header is generated alongside, and every declaration of the C++
+//! header is built on it, in a namespace named after the module. Types are
+//! aliases of their C counterparts, and each handle is given a move-only
+//! class owning it, which releases it with the module's `drop_<handle>`
+//! function if there is one. Functions take lists as `span`s and strings as
+//! `std::string_view`s, handles by reference to their class, and report
+//! errors either by throwing or by returning an `expected`, as chosen with
+//! [`CppErrors`]. Before C++20, `span` is a minimal stand-in for `std::span`.
+
+use super::c::{self, comment, decl, int_repr, macro_case, Names};
+use super::{
+    drops_handle, error_enums, error_message, file_stem, is_owned_handle, CppErrors, Options,
+    OutputFile,
+};
+use crate::{Document, Id, InterfaceFunc, Module, NamedType, Type, TypeRef};
+use std::fmt::Write;
+
+pub(super) fn generate(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let mut files = c::generate(doc, options);
+    let header = files[0].path.to_string_lossy().into_owned();
+    let stem = file_stem(doc);
+    // The namespace option is a prefix for C, like `nn_`.
+    let namespace = match &options.namespace {
+        Some(ns) => ns.trim_end_matches('_').to_string(),
+        None => stem.clone(),
+    };
+    let cpp = Cpp {
+        names: Names::new(doc, options),
+        errors: options.cpp_errors,
+        error_enums: error_enums(doc).iter().map(|nt| nt.name.clone()).collect(),
+        handles: doc
+            .typenames()
+            .filter(|nt| is_owned_handle(nt))
+            .map(|nt| ident(nt.name.as_str()))
+            .collect(),
+    };
+    let guard = format!("{}_HPP", macro_case(&stem));
+    let contents = cpp.render(doc, &header, &namespace, &guard);
+    files.push(OutputFile::new(format!("{}.hpp", stem), contents));
+    files
+}
+
+/// A C++ identifier for a witx name.
+fn ident(name: &str) -> String {
+    match name {
+        "alignas" | "alignof" | "and" | "asm" | "catch" | "class" | "constexpr" | "delete"
+        | "explicit" | "export" | "false" | "friend" | "mutable" | "namespace" | "new"
+        | "noexcept" | "nullptr" | "operator" | "or" | "private" | "protected" | "public"
+        | "template" | "this" | "throw" | "true" | "try" | "typeid" | "typename" | "using"
+        | "virtual" => format!("{}_", name),
+        _ => c::ident(name),
+    }
+}
+
+/// A C string literal of `s`.
+fn string_literal(s: &str) -> String {
+    let mut lit = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => lit.push_str("\\\""),
+            '\\' => lit.push_str("\\\\"),
+            '\n' => lit.push_str("\\n"),
+            c => lit.push(c),
+        }
+    }
+    lit.push('"');
+    lit
+}
+
+struct Cpp {
+    names: Names,
+    errors: CppErrors,
+    /// Names of the enums with an exception class.
+    error_enums: Vec<Id>,
+    /// Names of the classes owning handles.
+    handles: Vec<String>,
+}
+
+/// What a wrapper returns, and how to compute it once the C function has
+/// been called.
+struct Ret {
+    ty: String,
+    value: Option<String>,
+}
+
+impl Cpp {
+    fn render(&self, doc: &Document, header: &str, namespace: &str, guard: &str) -> String {
+        let mut out = String::new();
+        out.push_str("/**\n * This file is automatically generated by witx, DO NOT EDIT!\n */\n\n");
+        writeln!(out, "#ifndef {}", guard).unwrap();
+        writeln!(out, "#define {}", guard).unwrap();
+        writeln!(out, "\n#include \"{}\"\n", header).unwrap();
+        out.push_str("#include <cstddef>\n#include <cstdint>\n");
+        match self.errors {
+            CppErrors::Exceptions => out.push_str("#include <exception>\n"),
+            CppErrors::Expected => out.push_str("#include <optional>\n"),
+        }
+        out.push_str("#include <stdexcept>\n#include <string_view>\n#include <tuple>\n");
+        out.push_str("#include <type_traits>\n#include <utility>\n");
+        out.push_str("#if __cplusplus >= 202002L\n#include <span>\n#endif\n");
+        writeln!(out, "\nnamespace {} {{", namespace).unwrap();
+        out.push_str(SPAN);
+        if self.errors == CppErrors::Expected {
+            out.push_str(EXPECTED);
+        }
+
+        out.push('\n');
+        for nt in doc.typenames() {
+            writeln!(
+                out,
+                "using {}_t = ::{};",
+                ident(nt.name.as_str()),
+                self.names.type_(&nt.name)
+            )
+            .unwrap();
+        }
+        if self.errors == CppErrors::Exceptions {
+            for name in self.error_enums.iter() {
+                let nt = doc.typename(name).expect("error enums are defined");
+                self.define_error(&mut out, &nt);
+            }
+        }
+        for nt in doc.typenames().filter(|nt| is_owned_handle(nt)) {
+            self.define_handle(&mut out, doc, &nt);
+        }
+
+        let nested = doc.modules().count() > 1;
+        for m in doc.modules() {
+            if nested {
+                writeln!(out, "\nnamespace {} {{", ident(m.name.as_str())).unwrap();
+            }
+            for f in m.funcs().filter(|f| drops_handle(f).is_none()) {
+                out.push('\n');
+                self.define_func(&mut out, &m, &f);
+            }
+            if nested {
+                writeln!(out, "\n}} // namespace {}", ident(m.name.as_str())).unwrap();
+            }
+        }
+        writeln!(out, "\n}} // namespace {}", namespace).unwrap();
+        writeln!(out, "\n#endif /* {} */", guard).unwrap();
+        out
+    }
+
+    /// The exception thrown for the error enum `nt`, described by the docs of
+    /// each case.
+    fn define_error(&self, out: &mut String, nt: &NamedType) {
+        let cases = match &**nt.type_() {
+            Type::Variant(v) => &v.cases,
+            _ => unreachable!("error enums are variants"),
+        };
+        let name = ident(nt.name.as_str());
+        writeln!(
+            out,
+            "\n/**\n * An error returned as a `{}`.\n */",
+            nt.name.as_str()
+        )
+        .unwrap();
+        writeln!(out, "class {}_error : public std::exception {{", name).unwrap();
+        out.push_str("public:\n");
+        writeln!(
+            out,
+            "    explicit {}_error({}_t code) noexcept : code_(code) {{}}",
+            name, name
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    {}_t code() const noexcept {{ return code_; }}",
+            name
+        )
+        .unwrap();
+        out.push_str("    const char *what() const noexcept override {\n");
+        out.push_str("        switch (code_) {\n");
+        for c in cases.iter() {
+            writeln!(
+                out,
+                "        case {}: return {};",
+                self.names.constant(&nt.name, &c.name),
+                string_literal(&error_message(c))
+            )
+            .unwrap();
+        }
+        out.push_str("        default: return \"unknown error\";\n");
+        out.push_str("        }\n    }\n\nprivate:\n");
+        writeln!(out, "    {}_t code_;", name).unwrap();
+        out.push_str("};\n");
+    }
+
+    /// A move-only class owning a handle of `nt`.
+    fn define_handle(&self, out: &mut String, doc: &Document, nt: &NamedType) {
+        let name = ident(nt.name.as_str());
+        let drop = doc.modules().find_map(|m| {
+            m.funcs()
+                .find(|f| drops_handle(f).as_ref() == Some(&nt.name))
+                .map(|f| self.names.func(&m, &f))
+        });
+        out.push('\n');
+        comment(out, &nt.docs, "");
+        writeln!(out, "class {} {{", name).unwrap();
+        out.push_str("public:\n");
+        out.push_str("    /** Take ownership of a raw handle. */\n");
+        match &drop {
+            Some(_) => writeln!(
+                out,
+                "    explicit {}({}_t raw) noexcept : raw_(raw), owned_(true) {{}}",
+                name, name
+            ),
+            None => writeln!(
+                out,
+                "    explicit {}({}_t raw) noexcept : raw_(raw) {{}}",
+                name, name
+            ),
+        }
+        .unwrap();
+        writeln!(out, "    {}(const {} &) = delete;", name, name).unwrap();
+        writeln!(out, "    {} &operator=(const {} &) = delete;", name, name).unwrap();
+        match &drop {
+            Some(drop) => {
+                writeln!(
+                    out,
+                    "    {}({} &&other) noexcept\n        : raw_(other.raw_), owned_(std::exchange(other.owned_, false)) {{}}",
+                    name, name
+                )
+                .unwrap();
+                writeln!(out, "    {} &operator=({} &&other) noexcept {{", name, name).unwrap();
+                out.push_str("        if (this != &other) {\n");
+                out.push_str("            reset();\n");
+                out.push_str("            raw_ = other.raw_;\n");
+                out.push_str("            owned_ = std::exchange(other.owned_, false);\n");
+                out.push_str("        }\n        return *this;\n    }\n");
+                writeln!(out, "    ~{}() {{ reset(); }}", name).unwrap();
+                out.push_str("\n    /** The raw handle, which stays owned by this object. */\n");
+                writeln!(
+                    out,
+                    "    {}_t get() const noexcept {{ return raw_; }}",
+                    name
+                )
+                .unwrap();
+                out.push_str("    /** Give up ownership of the handle without releasing it. */\n");
+                writeln!(out, "    {}_t release() noexcept {{", name).unwrap();
+                out.push_str("        owned_ = false;\n        return raw_;\n    }\n");
+                out.push_str("\nprivate:\n");
+                out.push_str("    void reset() noexcept {\n");
+                out.push_str("        if (owned_) {\n");
+                out.push_str("            owned_ = false;\n");
+                writeln!(out, "            (void)::{}(raw_);", drop).unwrap();
+                out.push_str("        }\n    }\n\n");
+                writeln!(out, "    {}_t raw_;", name).unwrap();
+                out.push_str("    bool owned_;\n");
+            }
+            None => {
+                writeln!(out, "    {}({} &&) noexcept = default;", name, name).unwrap();
+                writeln!(
+                    out,
+                    "    {} &operator=({} &&) noexcept = default;",
+                    name, name
+                )
+                .unwrap();
+                out.push_str("\n    /** The raw handle, which stays owned by this object. */\n");
+                writeln!(
+                    out,
+                    "    {}_t get() const noexcept {{ return raw_; }}",
+                    name
+                )
+                .unwrap();
+                out.push_str("    /** Give up ownership of the handle. */\n");
+                writeln!(out, "    {}_t release() noexcept {{ return raw_; }}", name).unwrap();
+                out.push_str("\nprivate:\n");
+                writeln!(out, "    {}_t raw_;", name).unwrap();
+            }
+        }
+        out.push_str("};\n");
+    }
+
+    /// The C++ spelling of the C type of `tref`.
+    fn tref(&self, tref: &TypeRef) -> String {
+        match tref {
+            TypeRef::Name(nt) => format!("{}_t", ident(nt.name.as_str())),
+            TypeRef::Value(ty) => self.names.anonymous(ty),
+        }
+    }
+
+    /// The C++ type of a value of `tref` returned by a wrapper, and its
+    /// value given the C value `raw`.
+    fn lift(&self, tref: &TypeRef, raw: &str) -> (String, String) {
+        match tref {
+            TypeRef::Name(nt) if is_owned_handle(nt) => {
+                let class = ident(nt.name.as_str());
+                let value = format!("{}({})", class, raw);
+                (class, value)
+            }
+            _ => (self.tref(tref), raw.to_string()),
+        }
+    }
+
+    fn define_func(&self, out: &mut String, module: &Module, func: &InterfaceFunc) {
+        let mut params = Vec::new();
+        let mut args = Vec::new();
+        for p in func.params.iter() {
+            // Params can't shadow the classes their type may refer to.
+            let mut name = ident(p.name.as_str());
+            if self.handles.contains(&name) {
+                name.push('_');
+            }
+            match &**p.tref.type_() {
+                Type::List(elem) => {
+                    match &**elem.type_() {
+                        Type::Builtin(crate::BuiltinType::Char) => {
+                            params.push(format!("std::string_view {}", name))
+                        }
+                        _ => params.push(format!("span<const {}> {}", self.tref(elem), name)),
+                    }
+                    args.push(format!("{}.data()", name));
+                    args.push(format!("{}.size()", name));
+                }
+                Type::Record(r) if r.bitflags_repr().is_none() => {
+                    params.push(format!("const {} &{}", self.tref(&p.tref), name));
+                    args.push(format!("&{}", name));
+                }
+                Type::Variant(v) if !v.is_enum() => {
+                    params.push(format!("const {} &{}", self.tref(&p.tref), name));
+                    args.push(format!("&{}", name));
+                }
+                _ => match &p.tref {
+                    TypeRef::Name(nt) if is_owned_handle(nt) => {
+                        params.push(format!("const {} &{}", ident(nt.name.as_str()), name));
+                        args.push(format!("{}.get()", name));
+                    }
+                    _ => {
+                        params.push(decl(&self.tref(&p.tref), &name));
+                        args.push(name);
+                    }
+                },
+            }
+        }
+
+        let mut body = String::new();
+        let call =
+            |args: &[String]| format!("::{}({})", self.names.func(module, func), args.join(", "));
+        let ret = match func.results.first() {
+            None => {
+                writeln!(body, "    {};", call(&args)).unwrap();
+                Ret {
+                    ty: "void".to_string(),
+                    value: None,
+                }
+            }
+            Some(result) => match &**result.tref.type_() {
+                Type::Variant(v) if !v.is_enum() => {
+                    let (ok, err) = v.as_expected().expect("results are `expected` variants");
+                    let mut oks = Vec::new();
+                    if let Some(ok) = ok {
+                        match &**ok.type_() {
+                            Type::Record(r) if r.is_tuple() => {
+                                oks.extend(r.members.iter().map(|m| m.tref.clone()))
+                            }
+                            _ => oks.push(ok.clone()),
+                        }
+                    }
+                    for (i, ok) in oks.iter().enumerate() {
+                        writeln!(body, "    {} retptr{};", self.tref(ok), i).unwrap();
+                        args.push(format!("&retptr{}", i));
+                    }
+                    writeln!(body, "    auto ret = {};", call(&args)).unwrap();
+                    let err_ty = match err {
+                        Some(err) => self.tref(err),
+                        None => int_repr(v.tag_repr).to_string(),
+                    };
+                    let ret = match oks.len() {
+                        0 => Ret {
+                            ty: "void".to_string(),
+                            value: None,
+                        },
+                        1 => {
+                            let (ty, value) = self.lift(&oks[0], "retptr0");
+                            Ret {
+                                ty,
+                                value: Some(value),
+                            }
+                        }
+                        _ => {
+                            let (tys, values): (Vec<_>, Vec<_>) = oks
+                                .iter()
+                                .enumerate()
+                                .map(|(i, ok)| self.lift(ok, &format!("retptr{}", i)))
+                                .unzip();
+                            let ty = format!("std::tuple<{}>", tys.join(", "));
+                            let value = format!("{}({})", ty, values.join(", "));
+                            Ret {
+                                ty,
+                                value: Some(value),
+                            }
+                        }
+                    };
+                    body.push_str("    if (ret != 0) {\n");
+                    match self.errors {
+                        CppErrors::Exceptions => {
+                            let error = match err {
+                                Some(TypeRef::Name(nt)) if self.error_enums.contains(&nt.name) => {
+                                    format!("{}_error(ret)", ident(nt.name.as_str()))
+                                }
+                                _ => format!(
+                                    "std::runtime_error({})",
+                                    string_literal(&format!("{} failed", func.name.as_str()))
+                                ),
+                            };
+                            writeln!(body, "        throw {};", error).unwrap();
+                        }
+                        CppErrors::Expected => {
+                            writeln!(
+                                body,
+                                "        return expected<{}, {}>::failure(ret);",
+                                ret.ty, err_ty
+                            )
+                            .unwrap();
+                        }
+                    }
+                    body.push_str("    }\n");
+                    match self.errors {
+                        CppErrors::Exceptions => ret,
+                        CppErrors::Expected => Ret {
+                            ty: format!("expected<{}, {}>", ret.ty, err_ty),
+                            value: Some(ret.value.unwrap_or_else(|| "{}".to_string())),
+                        },
+                    }
+                }
+                _ => {
+                    let (ty, value) = self.lift(&result.tref, &call(&args));
+                    Ret {
+                        ty,
+                        value: Some(value),
+                    }
+                }
+            },
+        };
+        if let Some(value) = &ret.value {
+            writeln!(body, "    return {};", value).unwrap();
+        }
+
+        comment(out, &func.docs, "");
+        let noreturn = if func.noreturn { "[[noreturn]] " } else { "" };
+        writeln!(
+            out,
+            "{}inline {} {}({}) {{",
+            noreturn,
+            ret.ty,
+            ident(func.name.as_str()),
+            params.join(", ")
+        )
+        .unwrap();
+        out.push_str(&body);
+        out.push_str("}\n");
+    }
+}
+
+const SPAN: &str = "
+#if __cplusplus >= 202002L
+template <typename T>
+using span = std::span<T>;
+#else
+/**
+ * A view of contiguous values, standing in for `std::span` before C++20.
+ */
+template <typename T>
+class span {
+public:
+    constexpr span() noexcept = default;
+    constexpr span(T *data, std::size_t size) noexcept : data_(data), size_(size) {}
+    template <std::size_t N>
+    constexpr span(T (&array)[N]) noexcept : data_(array), size_(N) {}
+    template <typename C,
+              typename = std::enable_if_t<!std::is_same_v<std::decay_t<C>, span>>,
+              typename = decltype(std::declval<C &>().data())>
+    constexpr span(C &&container) noexcept
+        : data_(container.data()), size_(container.size()) {}
+
+    constexpr T *data() const noexcept { return data_; }
+    constexpr std::size_t size() const noexcept { return size_; }
+    constexpr bool empty() const noexcept { return size_ == 0; }
+    constexpr T *begin() const noexcept { return data_; }
+    constexpr T *end() const noexcept { return data_ + size_; }
+
+private:
+    T *data_ = nullptr;
+    std::size_t size_ = 0;
+};
+#endif
+";
+
+const EXPECTED: &str = "
+/**
+ * The value of a successful call, or the error it failed with, standing in
+ * for C++23's `std::expected`.
+ */
+template <typename T, typename E>
+class [[nodiscard]] expected {
+public:
+    expected(T value) : value_(std::move(value)) {}
+    static expected failure(E error) { return expected(std::nullopt, error); }
+
+    bool has_value() const noexcept { return value_.has_value(); }
+    explicit operator bool() const noexcept { return has_value(); }
+    T &value() & { return *value_; }
+    const T &value() const & { return *value_; }
+    T &&value() && { return std::move(*value_); }
+    T &operator*() & { return *value_; }
+    T *operator->() { return &*value_; }
+    E error() const noexcept { return error_; }
+
+private:
+    expected(std::nullopt_t, E error) : error_(error) {}
+
+    std::optional<T> value_;
+    E error_{};
+};
+
+template <typename E>
+class [[nodiscard]] expected<void, E> {
+public:
+    expected() noexcept = default;
+    static expected failure(E error) {
+        expected e;
+        e.failed_ = true;
+        e.error_ = error;
+        return e;
+    }
+
+    bool has_value() const noexcept { return !failed_; }
+    explicit operator bool() const noexcept { return has_value(); }
+    E error() const noexcept { return error_; }
+
+private:
+    bool failed_ = false;
+    E error_{};
+};
+";
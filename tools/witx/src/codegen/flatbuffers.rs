@@ -0,0 +1,196 @@
+//! A FlatBuffers schema of a document's types, for services marshalling
+//! them off-box.
+//!
+//! Named records are tables, enums are enums of their tag's type, flags are
+//! `bit_flags` enums, and variants are tables holding a union of a table per
+//! case. Named aliases are inlined. FlatBuffers can't say everything witx
+//! can, so some types lose information:
+//!
+//! - Handles and pointers are their `uint` values, only meaningful to the
+//!   host they come from.
+//! - `char` is a `uint` code point.
+//! - Lists of lists are vectors of tables wrapping the inner vectors.
+
+use super::{doc_comment, file_stem, Options, OutputFile};
+use crate::{BuiltinType, Document, IntRepr, Type, TypeRef, Variant};
+use heck::CamelCase;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::mem;
+
+pub(super) fn generate(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let namespace = options.namespace.clone().unwrap_or_else(|| file_stem(doc));
+    vec![OutputFile::new(
+        format!("{}.fbs", file_stem(doc)),
+        render(doc, &namespace),
+    )]
+}
+
+fn render(doc: &Document, namespace: &str) -> String {
+    let mut schema = Schema::default();
+    for nt in doc.typenames() {
+        if defines_table(&nt.tref) {
+            schema.define(&table_name(nt.name.as_str()), nt.type_(), &nt.docs);
+        }
+    }
+    for (name, ty) in mem::take(&mut schema.wrappers) {
+        write!(
+            schema.out,
+            "\ntable {} {{\n    items: [{}];\n}}\n",
+            name, ty
+        )
+        .unwrap();
+    }
+
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n\n");
+    out.push_str(
+        "// Handles and pointers are their `uint` values, only meaningful to the\n\
+         // host they come from.\n\n",
+    );
+    writeln!(out, "namespace {};", namespace).unwrap();
+    out.push_str(&schema.out);
+    out
+}
+
+fn table_name(name: &str) -> String {
+    name.to_camel_case()
+}
+
+/// Whether `tref` is a table or enum of its own, rather than inlined.
+fn defines_table(tref: &TypeRef) -> bool {
+    match &**tref.type_() {
+        Type::Record(_) => true,
+        Type::Variant(v) => !v.is_bool(),
+        _ => false,
+    }
+}
+
+fn builtin(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::U8 { .. } => "ubyte",
+        BuiltinType::U16 => "ushort",
+        BuiltinType::U32 { .. } | BuiltinType::Char => "uint",
+        BuiltinType::U64 => "ulong",
+        BuiltinType::S8 => "byte",
+        BuiltinType::S16 => "short",
+        BuiltinType::S32 => "int",
+        BuiltinType::S64 => "long",
+        BuiltinType::F32 => "float",
+        BuiltinType::F64 => "double",
+    }
+}
+
+fn repr(repr: IntRepr) -> &'static str {
+    builtin(repr.to_builtin())
+}
+
+#[derive(Default)]
+struct Schema {
+    out: String,
+    /// Tables wrapping a vector, which can't be nested in another vector.
+    wrappers: BTreeSet<(String, String)>,
+}
+
+impl Schema {
+    /// The type of a field of type `tref`. Anonymous tables are defined
+    /// named `name`.
+    fn field(&mut self, tref: &TypeRef, name: &str) -> String {
+        match tref {
+            TypeRef::Name(nt) if defines_table(tref) => table_name(nt.name.as_str()),
+            TypeRef::Name(nt) => self.field(&nt.tref, name),
+            TypeRef::Value(ty) => match &**ty {
+                Type::Builtin(b) => builtin(*b).to_string(),
+                Type::Pointer(_) | Type::ConstPointer(_) | Type::Handle(_) => "uint".to_string(),
+                Type::List(elem) => match &**elem.type_() {
+                    Type::Builtin(BuiltinType::Char) => "string".to_string(),
+                    _ => {
+                        let elem = self.field(elem, name);
+                        format!("[{}]", self.unvectored(elem))
+                    }
+                },
+                Type::Variant(v) if v.is_bool() => "bool".to_string(),
+                Type::Record(_) | Type::Variant(_) => {
+                    let table = table_name(name);
+                    self.define(&table, ty, "");
+                    table
+                }
+            },
+        }
+    }
+
+    /// `ty`, wrapped in a table if it's a vector.
+    fn unvectored(&mut self, ty: String) -> String {
+        match ty.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            Some(elem) => {
+                let wrapper = format!("Repeated{}", elem.to_camel_case());
+                self.wrappers.insert((wrapper.clone(), elem.to_string()));
+                wrapper
+            }
+            None => ty,
+        }
+    }
+
+    /// Define the table or enum `name` for the record or variant `ty`,
+    /// documented by `docs`, after the anonymous tables it uses.
+    fn define(&mut self, name: &str, ty: &Type, docs: &str) {
+        let mut out = String::new();
+        match ty {
+            Type::Variant(v) if v.is_enum() => {
+                writeln!(out, "enum {} : {} {{", name, repr(v.tag_repr)).unwrap();
+                for c in v.cases.iter() {
+                    out.push_str(&doc_comment(&c.docs, "///", "    "));
+                    writeln!(out, "    {},", c.name.as_str().to_camel_case()).unwrap();
+                }
+            }
+            Type::Variant(v) => self.union(name, v, &mut out),
+            Type::Record(r) => match r.bitflags_repr() {
+                Some(flags) => {
+                    writeln!(out, "enum {} : {} (bit_flags) {{", name, repr(flags)).unwrap();
+                    for m in r.members.iter() {
+                        out.push_str(&doc_comment(&m.docs, "///", "    "));
+                        writeln!(out, "    {},", m.name.as_str().to_camel_case()).unwrap();
+                    }
+                }
+                None => {
+                    writeln!(out, "table {} {{", name).unwrap();
+                    for (i, m) in r.members.iter().enumerate() {
+                        let field = if r.is_tuple() {
+                            format!("f{}", i)
+                        } else {
+                            m.name.as_str().to_string()
+                        };
+                        let ty = self.field(&m.tref, &format!("{}_{}", name, field));
+                        out.push_str(&doc_comment(&m.docs, "///", "    "));
+                        writeln!(out, "    {}: {};", field, ty).unwrap();
+                    }
+                }
+            },
+            _ => unreachable!("only records and variants are tables"),
+        }
+        out.push_str("}\n");
+        self.out.push('\n');
+        self.out.push_str(&doc_comment(docs, "///", ""));
+        self.out.push_str(&out);
+    }
+
+    /// The union of a table per case of `v`, and the table `name` holding it.
+    fn union(&mut self, name: &str, v: &Variant, out: &mut String) {
+        let mut cases = String::new();
+        for c in v.cases.iter() {
+            let case = format!("{}{}", name, c.name.as_str().to_camel_case());
+            let mut table = format!("\ntable {} {{\n", case);
+            if let Some(tref) = &c.tref {
+                let ty = self.field(tref, &case);
+                writeln!(table, "    value: {};", ty).unwrap();
+            }
+            table.push_str("}\n");
+            self.out.push_str(&table);
+            cases.push_str(&doc_comment(&c.docs, "///", "    "));
+            writeln!(cases, "    {},", case).unwrap();
+        }
+        write!(self.out, "\nunion {}Value {{\n{}}}\n", name, cases).unwrap();
+        writeln!(out, "table {} {{", name).unwrap();
+        writeln!(out, "    value: {}Value;", name).unwrap();
+    }
+}
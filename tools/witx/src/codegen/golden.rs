@@ -0,0 +1,160 @@
+//! Checked-in copies of the bindings every backend generates for a document.
+//!
+//! Golden files live under a directory with a subdirectory per language,
+//! named as on the command line, holding the language's output files. A
+//! test compares freshly generated bindings against them with
+//! [`compare_golden`], so a change to a backend or to the layout logic shows
+//! up in review as changes to the golden files, which [`bless_golden`]
+//! rewrites.
+
+use super::{generate, Language, Options, OutputFile};
+use crate::Document;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GoldenError {
+    #[error("IO error with file {0:?}")]
+    Io(PathBuf, #[source] io::Error),
+}
+
+/// A difference between generated bindings and their golden files. Paths
+/// are relative to the golden directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoldenDiff {
+    /// A generated file with no golden copy.
+    Added { path: PathBuf, actual: String },
+    /// A generated file different from its golden copy.
+    Changed {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    /// A golden copy of a file that is no longer generated.
+    Removed { path: PathBuf, expected: String },
+}
+
+impl GoldenDiff {
+    pub fn path(&self) -> &Path {
+        match self {
+            GoldenDiff::Added { path, .. }
+            | GoldenDiff::Changed { path, .. }
+            | GoldenDiff::Removed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for GoldenDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let change = match self {
+            GoldenDiff::Added { .. } => "added",
+            GoldenDiff::Changed { .. } => "changed",
+            GoldenDiff::Removed { .. } => "removed",
+        };
+        write!(f, "{}: {}", self.path().display(), change)
+    }
+}
+
+/// The bindings of `doc` in every language, each under a directory named
+/// after its language.
+pub fn golden_files(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    Language::all()
+        .iter()
+        .flat_map(|language| {
+            generate(doc, *language, options)
+                .into_iter()
+                .map(move |file| {
+                    OutputFile::new(Path::new(language.name()).join(file.path), file.contents)
+                })
+        })
+        .collect()
+}
+
+/// The differences between the bindings of `doc` and the golden files in
+/// `dir`, in path order.
+pub fn compare_golden(
+    doc: &Document,
+    options: &Options,
+    dir: &Path,
+) -> Result<Vec<GoldenDiff>, GoldenError> {
+    let files = golden_files(doc, options);
+    let mut diffs = Vec::new();
+    for file in files.iter() {
+        let full = dir.join(&file.path);
+        match fs::read_to_string(&full) {
+            // Git may check out the golden files with dos line endings.
+            Ok(expected) if expected.replace('\r', "") == file.contents => {}
+            Ok(expected) => diffs.push(GoldenDiff::Changed {
+                path: file.path.clone(),
+                expected,
+                actual: file.contents.clone(),
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => diffs.push(GoldenDiff::Added {
+                path: file.path.clone(),
+                actual: file.contents.clone(),
+            }),
+            Err(e) => return Err(GoldenError::Io(full, e)),
+        }
+    }
+    for path in stale_files(dir, &files)? {
+        let full = dir.join(&path);
+        let expected = fs::read_to_string(&full).map_err(|e| GoldenError::Io(full, e))?;
+        diffs.push(GoldenDiff::Removed { path, expected });
+    }
+    diffs.sort_by(|a, b| a.path().cmp(b.path()));
+    Ok(diffs)
+}
+
+/// Rewrites the golden files in `dir` with the bindings of `doc`, removing
+/// those of files no longer generated.
+pub fn bless_golden(doc: &Document, options: &Options, dir: &Path) -> Result<(), GoldenError> {
+    let files = golden_files(doc, options);
+    for path in stale_files(dir, &files)? {
+        let full = dir.join(path);
+        fs::remove_file(&full).map_err(|e| GoldenError::Io(full, e))?;
+    }
+    for file in files.iter() {
+        let full = dir.join(&file.path);
+        if let Some(parent) = full.parent() {
+            fs::create_dir_all(parent).map_err(|e| GoldenError::Io(parent.to_path_buf(), e))?;
+        }
+        fs::write(&full, &file.contents).map_err(|e| GoldenError::Io(full, e))?;
+    }
+    Ok(())
+}
+
+/// The files in the language directories of `dir` that aren't in `files`.
+fn stale_files(dir: &Path, files: &[OutputFile]) -> Result<Vec<PathBuf>, GoldenError> {
+    fn walk(dir: &Path, relative: &Path, found: &mut Vec<PathBuf>) -> Result<(), GoldenError> {
+        let io = |e| GoldenError::Io(dir.to_path_buf(), e);
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(io(e)),
+        };
+        for entry in entries {
+            let entry = entry.map_err(io)?;
+            let relative = relative.join(entry.file_name());
+            if entry.file_type().map_err(io)?.is_dir() {
+                walk(&entry.path(), &relative, found)?;
+            } else {
+                found.push(relative);
+            }
+        }
+        Ok(())
+    }
+    let mut found = Vec::new();
+    for language in Language::all() {
+        walk(
+            &dir.join(language.name()),
+            Path::new(language.name()),
+            &mut found,
+        )?;
+    }
+    found.retain(|path| !files.iter().any(|f| &f.path == path));
+    found.sort();
+    Ok(found)
+}
@@ -0,0 +1,405 @@
+//! A gRPC mirror of a document's modules, for hosts proxying calls to a
+//! remote implementation.
+//!
+//! The document is translated to a protobuf service per module, with an rpc
+//! per function taking its parameters and returning its results, and a Rust
+//! crate builds the service with `tonic`. Named records and variants are
+//! messages, enums are protobuf enums, and other named types are inlined.
+//! Handles and pointers are passed as their `u32` values, so they're only
+//! meaningful to the remote host.
+//!
+//! The protobuf backend outputs the messages and enums of the types alone,
+//! without services, as wire types for anything marshalling them.
+
+use super::{doc_comment, file_stem, Options, OutputFile};
+use crate::{BuiltinType, Document, InterfaceFunc, Module, Type, TypeRef, Variant};
+use heck::{CamelCase, ShoutySnakeCase, SnakeCase};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::mem;
+
+const TONIC_VERSION: &str = "0.12";
+const PROST_VERSION: &str = "0.13";
+
+pub(super) fn generate(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let crate_name = options.crate_name.as_deref().unwrap_or("bindings");
+    let package = options.namespace.clone().unwrap_or_else(|| file_stem(doc));
+    let proto_path = format!("proto/{}.proto", file_stem(doc));
+    let manifest = format!(
+        "[package]\n\
+         name = \"{}\"\n\
+         version = \"0.1.0\"\n\
+         edition = \"2018\"\n\
+         \n\
+         [dependencies]\n\
+         prost = \"{}\"\n\
+         tonic = \"{}\"\n\
+         \n\
+         [build-dependencies]\n\
+         tonic-build = \"{}\"\n",
+        crate_name, PROST_VERSION, TONIC_VERSION, TONIC_VERSION
+    );
+    let build = format!(
+        "// This file is automatically generated by witx, DO NOT EDIT!\n\n\
+         fn main() -> Result<(), Box<dyn std::error::Error>> {{\n    \
+         tonic_build::compile_protos(\"{}\")?;\n    \
+         Ok(())\n\
+         }}\n",
+        proto_path
+    );
+    vec![
+        OutputFile::new("Cargo.toml", manifest),
+        OutputFile::new("build.rs", build),
+        OutputFile::new(proto_path.clone(), render_proto(doc, &package, true)),
+        OutputFile::new("src/lib.rs", render_lib(doc, &package, &proto_path)),
+    ]
+}
+
+/// The protobuf schema of the types of `doc`, without its modules.
+pub(super) fn generate_schema(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let package = options.namespace.clone().unwrap_or_else(|| file_stem(doc));
+    vec![OutputFile::new(
+        format!("{}.proto", file_stem(doc)),
+        render_proto(doc, &package, false),
+    )]
+}
+
+/// The protobuf type of a field, and whether it's repeated.
+struct Field {
+    ty: String,
+    repeated: bool,
+}
+
+impl Field {
+    fn scalar(ty: &str) -> Field {
+        Field {
+            ty: ty.to_string(),
+            repeated: false,
+        }
+    }
+
+    fn declare(&self, name: &str, number: usize) -> String {
+        format!(
+            "{}{} {} = {};",
+            if self.repeated { "repeated " } else { "" },
+            self.ty,
+            name,
+            number
+        )
+    }
+}
+
+#[derive(Default)]
+struct Proto {
+    /// Messages wrapping a repeated field, which can't be nested in another
+    /// repeated field or a oneof.
+    wrappers: BTreeSet<(String, String)>,
+    /// Whether `google.protobuf.Empty` is used, for variant cases without a
+    /// payload.
+    empty: bool,
+}
+
+/// The protobuf file of the types of `doc`, and a service for each of its
+/// modules if `services`.
+fn render_proto(doc: &Document, package: &str, services: bool) -> String {
+    let mut proto = Proto::default();
+    let mut body = String::new();
+    for nt in doc.typenames() {
+        if defines_message(&nt.tref) {
+            body.push('\n');
+            body.push_str(&doc_comment(&nt.docs, "//", ""));
+            body.push_str(&proto.define(&message_name(nt.name.as_str()), nt.type_(), ""));
+        }
+    }
+    if services {
+        let multiple = doc.modules().count() > 1;
+        for m in doc.modules() {
+            body.push_str(&proto.service(&m, multiple));
+        }
+    }
+    for (name, ty) in mem::take(&mut proto.wrappers) {
+        write!(
+            body,
+            "\nmessage {} {{\n    repeated {} items = 1;\n}}\n",
+            name, ty
+        )
+        .unwrap();
+    }
+
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n\n");
+    if !services {
+        out.push_str(
+            "// Handles and pointers are their `uint32` values, only meaningful to the\n\
+             // host they come from.\n\n",
+        );
+    }
+    out.push_str("syntax = \"proto3\";\n\n");
+    writeln!(out, "package {};", package).unwrap();
+    if proto.empty {
+        out.push_str("\nimport \"google/protobuf/empty.proto\";\n");
+    }
+    out.push_str(&body);
+    out
+}
+
+fn message_name(name: &str) -> String {
+    name.to_camel_case()
+}
+
+/// Whether `tref` is a message or enum of its own, rather than inlined.
+fn defines_message(tref: &TypeRef) -> bool {
+    match &**tref.type_() {
+        Type::Record(r) => r.bitflags_repr().is_none(),
+        Type::Variant(v) => !v.is_bool(),
+        _ => false,
+    }
+}
+
+fn builtin(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::U8 { .. } | BuiltinType::U16 | BuiltinType::U32 { .. } | BuiltinType::Char => {
+            "uint32"
+        }
+        BuiltinType::S8 | BuiltinType::S16 | BuiltinType::S32 => "sint32",
+        BuiltinType::U64 => "uint64",
+        BuiltinType::S64 => "sint64",
+        BuiltinType::F32 => "float",
+        BuiltinType::F64 => "double",
+    }
+}
+
+impl Proto {
+    /// The type of a field `name` of type `tref`. Anonymous messages are
+    /// defined in `nested`, named after the field.
+    fn field(&mut self, tref: &TypeRef, name: &str, nested: &mut String, indent: &str) -> Field {
+        match tref {
+            TypeRef::Name(nt) if defines_message(tref) => {
+                Field::scalar(&message_name(nt.name.as_str()))
+            }
+            TypeRef::Name(nt) => self.field(&nt.tref, name, nested, indent),
+            TypeRef::Value(ty) => match &**ty {
+                Type::Builtin(b) => Field::scalar(builtin(*b)),
+                Type::Pointer(_) | Type::ConstPointer(_) | Type::Handle(_) => {
+                    Field::scalar("uint32")
+                }
+                Type::List(elem) => match &**elem.type_() {
+                    Type::Builtin(BuiltinType::U8 { .. }) => Field::scalar("bytes"),
+                    Type::Builtin(BuiltinType::Char) => Field::scalar("string"),
+                    _ => {
+                        let elem = self.field(elem, name, nested, indent);
+                        Field {
+                            ty: self.unrepeated(elem),
+                            repeated: true,
+                        }
+                    }
+                },
+                Type::Record(r) if r.bitflags_repr().is_some() => {
+                    Field::scalar(builtin(r.bitflags_repr().unwrap().to_builtin()))
+                }
+                Type::Variant(v) if v.is_bool() => Field::scalar("bool"),
+                Type::Record(_) | Type::Variant(_) => {
+                    let message = message_name(name);
+                    nested.push_str(&self.define(&message, ty, indent));
+                    Field::scalar(&message)
+                }
+            },
+        }
+    }
+
+    /// The type of `field`, wrapped in a message if it's repeated.
+    fn unrepeated(&mut self, field: Field) -> String {
+        if !field.repeated {
+            return field.ty;
+        }
+        let wrapper = format!("Repeated{}", field.ty.to_camel_case());
+        self.wrappers.insert((wrapper.clone(), field.ty));
+        wrapper
+    }
+
+    /// The definition of a message or enum `name` for the record or variant
+    /// `ty`.
+    fn define(&mut self, name: &str, ty: &Type, indent: &str) -> String {
+        let mut out = String::new();
+        match ty {
+            Type::Variant(v) if v.is_enum() => {
+                writeln!(out, "{}enum {} {{", indent, name).unwrap();
+                for (i, c) in v.cases.iter().enumerate() {
+                    out.push_str(&doc_comment(&c.docs, "//", &format!("{}    ", indent)));
+                    writeln!(
+                        out,
+                        "{}    {}_{} = {};",
+                        indent,
+                        name.to_shouty_snake_case(),
+                        c.name.as_str().to_shouty_snake_case(),
+                        i
+                    )
+                    .unwrap();
+                }
+            }
+            Type::Variant(v) => {
+                writeln!(out, "{}message {} {{", indent, name).unwrap();
+                let inner = format!("{}    ", indent);
+                let (nested, oneof) = self.oneof(v, "value", &inner);
+                out.push_str(&nested);
+                out.push_str(&oneof);
+            }
+            Type::Record(r) => {
+                writeln!(out, "{}message {} {{", indent, name).unwrap();
+                let inner = format!("{}    ", indent);
+                let mut nested = String::new();
+                let mut fields = String::new();
+                for (i, m) in r.members.iter().enumerate() {
+                    let field_name = if r.is_tuple() {
+                        format!("f{}", i)
+                    } else {
+                        m.name.as_str().to_string()
+                    };
+                    let field = self.field(&m.tref, &field_name, &mut nested, &inner);
+                    fields.push_str(&doc_comment(&m.docs, "//", &inner));
+                    writeln!(fields, "{}{}", inner, field.declare(&field_name, i + 1)).unwrap();
+                }
+                out.push_str(&nested);
+                out.push_str(&fields);
+            }
+            _ => unreachable!("only records and variants are messages"),
+        }
+        writeln!(out, "{}}}", indent).unwrap();
+        out
+    }
+
+    /// The nested messages and `oneof` declaration for the cases of `v`.
+    fn oneof(&mut self, v: &Variant, name: &str, indent: &str) -> (String, String) {
+        let mut nested = String::new();
+        let mut oneof = String::new();
+        writeln!(oneof, "{}oneof {} {{", indent, name).unwrap();
+        for (i, c) in v.cases.iter().enumerate() {
+            let case = c.name.as_str();
+            let ty = match &c.tref {
+                Some(tref) => {
+                    let field = self.field(tref, case, &mut nested, indent);
+                    self.unrepeated(field)
+                }
+                None => {
+                    self.empty = true;
+                    "google.protobuf.Empty".to_string()
+                }
+            };
+            oneof.push_str(&doc_comment(&c.docs, "//", &format!("{}    ", indent)));
+            writeln!(oneof, "{}    {} {} = {};", indent, ty, case, i + 1).unwrap();
+        }
+        writeln!(oneof, "{}}}", indent).unwrap();
+        (nested, oneof)
+    }
+
+    fn service(&mut self, module: &Module, prefixed: bool) -> String {
+        let service = message_name(module.name.as_str());
+        let prefix = if prefixed { service.as_str() } else { "" };
+        let mut messages = String::new();
+        let mut rpcs = String::new();
+        for f in module.funcs() {
+            let name = format!("{}{}", prefix, message_name(f.name.as_str()));
+            messages.push_str(&self.request(&f, &name));
+            messages.push_str(&self.response(&f, &name));
+            rpcs.push_str(&doc_comment(&f.docs, "//", "    "));
+            writeln!(
+                rpcs,
+                "    rpc {}({}Request) returns ({}Response);",
+                message_name(f.name.as_str()),
+                name,
+                name
+            )
+            .unwrap();
+        }
+        let mut out = messages;
+        out.push('\n');
+        out.push_str(&doc_comment(&module.docs, "//", ""));
+        writeln!(out, "service {} {{", service).unwrap();
+        out.push_str(&rpcs);
+        out.push_str("}\n");
+        out
+    }
+
+    fn request(&mut self, func: &InterfaceFunc, name: &str) -> String {
+        let mut nested = String::new();
+        let mut fields = String::new();
+        for (i, p) in func.params.iter().enumerate() {
+            let field = self.field(&p.tref, p.name.as_str(), &mut nested, "    ");
+            fields.push_str(&doc_comment(&p.docs, "//", "    "));
+            writeln!(fields, "    {}", field.declare(p.name.as_str(), i + 1)).unwrap();
+        }
+        format!("\nmessage {}Request {{\n{}{}}}\n", name, nested, fields)
+    }
+
+    fn response(&mut self, func: &InterfaceFunc, name: &str) -> String {
+        let mut nested = String::new();
+        let mut fields = String::new();
+        for (i, r) in func.results.iter().enumerate() {
+            // An anonymous `expected` is the response itself, rather than a
+            // message holding it.
+            if let TypeRef::Value(ty) = &r.tref {
+                if let Type::Variant(v) = &**ty {
+                    if func.results.len() == 1 && v.as_expected().is_some() {
+                        let (n, oneof) = self.oneof(v, r.name.as_str(), "    ");
+                        nested.push_str(&n);
+                        fields.push_str(&oneof);
+                        continue;
+                    }
+                }
+            }
+            let field = self.field(&r.tref, r.name.as_str(), &mut nested, "    ");
+            fields.push_str(&doc_comment(&r.docs, "//", "    "));
+            writeln!(fields, "    {}", field.declare(r.name.as_str(), i + 1)).unwrap();
+        }
+        format!("\nmessage {}Response {{\n{}{}}}\n", name, nested, fields)
+    }
+}
+
+fn render_lib(doc: &Document, package: &str, proto_path: &str) -> String {
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n\n");
+    writeln!(out, "//! gRPC services built from `{}`.", proto_path).unwrap();
+    out.push_str(
+        "//!\n\
+         //! Implement a module's service trait to serve it, or use its client to\n\
+         //! forward calls to a remote host.\n\n",
+    );
+    out.push_str("pub mod proto {\n");
+    writeln!(out, "    tonic::include_proto!(\"{}\");", package).unwrap();
+    out.push_str("}\n\n");
+
+    let mut params = Vec::new();
+    let mut services = Vec::new();
+    for m in doc.modules() {
+        let service = message_name(m.name.as_str());
+        let module = m.name.as_str().to_snake_case();
+        writeln!(
+            out,
+            "pub use proto::{module}_client::{service}Client;\n\
+             pub use proto::{module}_server::{{{service}, {service}Server}};",
+            module = module,
+            service = service
+        )
+        .unwrap();
+        params.push(format!("{}: impl {}", module, service));
+        services.push(format!(
+            "        .add_service({}Server::new({}))\n",
+            service, module
+        ));
+    }
+    if !services.is_empty() {
+        out.push_str("\n/// Serve every module at `addr`, until an error occurs.\n");
+        writeln!(
+            out,
+            "pub async fn serve(\n    addr: std::net::SocketAddr,\n    {},\n) -> Result<(), tonic::transport::Error> {{",
+            params.join(",\n    ")
+        )
+        .unwrap();
+        out.push_str("    tonic::transport::Server::builder()\n");
+        for s in services {
+            out.push_str(&s);
+        }
+        out.push_str("        .serve(addr)\n        .await\n}\n");
+    }
+    out
+}
@@ -0,0 +1,934 @@
+//! Kotlin host bindings, implementing a document's modules for guests run by
+//! the chicory JVM runtime.
+//!
+//! Every named type gets a Kotlin definition: records are data classes,
+//! enums are enum classes carrying their value and witx name, flags and
+//! handles are value classes, and other variants are sealed classes. Each
+//! module gets an interface whose methods take the guest memory followed by
+//! the lifted parameters, and a function returning chicory `HostFunction`s
+//! calling an implementation of it, as described by
+//! [`InterfaceFunc::call_interface`]. Errors of functions returning an
+//! `expected` are thrown as a `WitxException`.
+//!
+//! Integers keep their signedness, with Kotlin's unsigned types, and `char`s
+//! are `Int` code points. Lists are `List`s, except for `ByteArray`s of `u8`
+//! and `String`s of `char`, and pointers are `UInt` addresses.
+
+use super::{file_stem, OutputFile};
+use crate::{
+    Bindgen, BuiltinType, Document, Instruction, IntRepr, InterfaceFunc, Layout, Module, NamedType,
+    Type, TypeRef, WasmType,
+};
+use heck::{CamelCase, MixedCase, ShoutySnakeCase};
+use std::fmt::Write;
+use std::mem;
+
+pub(super) fn generate(doc: &Document, options: &super::Options) -> Vec<OutputFile> {
+    let stem = file_stem(doc);
+    let package = options.namespace.clone().unwrap_or_else(|| stem.clone());
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n");
+    out.push_str("@file:Suppress(\"NAME_SHADOWING\", \"UNREACHABLE_CODE\", \"unused\")\n\n");
+    writeln!(out, "package {}\n", package).unwrap();
+    out.push_str(PRELUDE);
+    for nt in doc.typenames() {
+        out.push('\n');
+        define_type(&mut out, &nt);
+    }
+    for m in doc.modules() {
+        out.push('\n');
+        define_module(&mut out, &m);
+    }
+    vec![OutputFile::new(format!("{}.kt", stem), out)]
+}
+
+/// Guest memory access and errors shared by every generated file.
+const PRELUDE: &str = r#"import com.dylibso.chicory.runtime.HostFunction
+import com.dylibso.chicory.runtime.Memory
+import com.dylibso.chicory.wasm.types.FunctionType
+import com.dylibso.chicory.wasm.types.ValType
+import java.nio.ByteBuffer
+import java.nio.ByteOrder
+import java.nio.charset.CodingErrorAction
+
+/** An error returned by a function, such as a case of an errno enum. */
+class WitxException(val error: Any) : Exception("witx error: $error")
+
+/** The linear memory of a guest, in which values are little-endian. */
+class GuestMemory(val memory: Memory) {
+    /** A copy of `len` bytes at `offset`. */
+    fun readBytes(offset: Int, len: Int): ByteArray = memory.readBytes(offset, len)
+
+    fun writeBytes(offset: Int, bytes: ByteArray) = memory.write(offset, bytes)
+
+    private fun buffer(offset: Int, len: Int): ByteBuffer =
+        ByteBuffer.wrap(readBytes(offset, len)).order(ByteOrder.LITTLE_ENDIAN)
+
+    private fun put(offset: Int, len: Int, f: (ByteBuffer) -> Unit) {
+        val buffer = ByteBuffer.allocate(len).order(ByteOrder.LITTLE_ENDIAN)
+        f(buffer)
+        writeBytes(offset, buffer.array())
+    }
+
+    fun getS8(offset: Int): Byte = readBytes(offset, 1)[0]
+    fun getU8(offset: Int): UByte = getS8(offset).toUByte()
+    fun getS16(offset: Int): Short = buffer(offset, 2).short
+    fun getU16(offset: Int): UShort = getS16(offset).toUShort()
+    fun getS32(offset: Int): Int = buffer(offset, 4).int
+    fun getU32(offset: Int): UInt = getS32(offset).toUInt()
+    fun getS64(offset: Int): Long = buffer(offset, 8).long
+    fun getU64(offset: Int): ULong = getS64(offset).toULong()
+    fun getF32(offset: Int): Float = buffer(offset, 4).float
+    fun getF64(offset: Int): Double = buffer(offset, 8).double
+
+    fun setS8(offset: Int, value: Byte) = writeBytes(offset, byteArrayOf(value))
+    fun setU8(offset: Int, value: UByte) = setS8(offset, value.toByte())
+    fun setS16(offset: Int, value: Short) = put(offset, 2) { it.putShort(value) }
+    fun setU16(offset: Int, value: UShort) = setS16(offset, value.toShort())
+    fun setS32(offset: Int, value: Int) = put(offset, 4) { it.putInt(value) }
+    fun setU32(offset: Int, value: UInt) = setS32(offset, value.toInt())
+    fun setS64(offset: Int, value: Long) = put(offset, 8) { it.putLong(value) }
+    fun setU64(offset: Int, value: ULong) = setS64(offset, value.toLong())
+    fun setF32(offset: Int, value: Float) = put(offset, 4) { it.putFloat(value) }
+    fun setF64(offset: Int, value: Double) = put(offset, 8) { it.putDouble(value) }
+
+    fun readString(offset: Int, len: Int): String =
+        Charsets.UTF_8.newDecoder()
+            .onMalformedInput(CodingErrorAction.REPORT)
+            .decode(ByteBuffer.wrap(readBytes(offset, len)))
+            .toString()
+
+    /** `len` elements of `size` bytes at `offset`, each read by `read`. */
+    fun <T> readList(size: Int, offset: Int, len: Int, read: (Int) -> T): List<T> =
+        List(len) { read(offset + it * size) }
+}
+
+private fun invalid(value: Any, ty: String): Nothing =
+    throw IllegalArgumentException("$value is not a valid `$ty`")
+
+private fun listWrite(): Nothing =
+    throw IllegalArgumentException("lists can't be written to guest memory")
+"#;
+
+/// The name of the Kotlin type of a witx type.
+fn type_name(name: &str) -> String {
+    name.to_camel_case()
+}
+
+/// A Kotlin identifier for a witx field, param or function name.
+fn ident(name: &str) -> String {
+    let name = name.to_mixed_case();
+    match name.as_str() {
+        "as" | "break" | "class" | "continue" | "do" | "else" | "false" | "for" | "fun" | "if"
+        | "in" | "interface" | "is" | "null" | "object" | "package" | "return" | "super"
+        | "this" | "throw" | "true" | "try" | "typealias" | "typeof" | "val" | "var" | "when"
+        | "while" => format!("`{}`", name),
+        _ => name,
+    }
+}
+
+fn reader(nt: &NamedType) -> String {
+    format!("read{}", type_name(nt.name.as_str()))
+}
+
+fn writer(nt: &NamedType) -> String {
+    format!("write{}", type_name(nt.name.as_str()))
+}
+
+fn builtin(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::U8 { .. } => "UByte",
+        BuiltinType::S8 => "Byte",
+        BuiltinType::U16 => "UShort",
+        BuiltinType::S16 => "Short",
+        BuiltinType::U32 { .. } => "UInt",
+        BuiltinType::S32 | BuiltinType::Char => "Int",
+        BuiltinType::U64 => "ULong",
+        BuiltinType::S64 => "Long",
+        BuiltinType::F32 => "Float",
+        BuiltinType::F64 => "Double",
+    }
+}
+
+/// The `GuestMemory` accessor suffix of `b`.
+fn accessor(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::U8 { .. } => "U8",
+        BuiltinType::S8 => "S8",
+        BuiltinType::U16 => "U16",
+        BuiltinType::S16 => "S16",
+        BuiltinType::U32 { .. } => "U32",
+        BuiltinType::S32 | BuiltinType::Char => "S32",
+        BuiltinType::U64 => "U64",
+        BuiltinType::S64 => "S64",
+        BuiltinType::F32 => "F32",
+        BuiltinType::F64 => "F64",
+    }
+}
+
+/// The conversion from a `Long` to the Kotlin type of `repr`.
+fn from_long(repr: IntRepr) -> &'static str {
+    match repr {
+        IntRepr::U8 => "toUByte",
+        IntRepr::U16 => "toUShort",
+        IntRepr::U32 => "toUInt",
+        IntRepr::U64 => "toULong",
+    }
+}
+
+fn tref_ty(tref: &TypeRef) -> String {
+    match tref {
+        TypeRef::Name(nt) => type_name(nt.name.as_str()),
+        TypeRef::Value(ty) => anonymous_ty(ty),
+    }
+}
+
+fn anonymous_ty(ty: &Type) -> String {
+    match ty {
+        Type::Builtin(b) => builtin(*b).to_string(),
+        Type::List(elem) => match &**elem.type_() {
+            Type::Builtin(BuiltinType::U8 { .. }) => "ByteArray".to_string(),
+            Type::Builtin(BuiltinType::Char) => "String".to_string(),
+            _ => format!("List<{}>", tref_ty(elem)),
+        },
+        Type::Pointer(_) | Type::ConstPointer(_) | Type::Handle(_) => "UInt".to_string(),
+        Type::Variant(v) if v.is_bool() => "Boolean".to_string(),
+        Type::Variant(v) if v.is_enum() => builtin(v.tag_repr.to_builtin()).to_string(),
+        Type::Record(r) if r.bitflags_repr().is_some() => {
+            builtin(r.bitflags_repr().unwrap().to_builtin()).to_string()
+        }
+        Type::Record(r) if r.is_tuple() && (2..=3).contains(&r.members.len()) => {
+            let members = r
+                .members
+                .iter()
+                .map(|m| tref_ty(&m.tref))
+                .collect::<Vec<_>>();
+            let class = if members.len() == 2 {
+                "kotlin.Pair"
+            } else {
+                "kotlin.Triple"
+            };
+            format!("{}<{}>", class, members.join(", "))
+        }
+        Type::Record(_) | Type::Variant(_) => {
+            unreachable!(
+                "anonymous {} types have no Kotlin representation",
+                ty.kind()
+            )
+        }
+    }
+}
+
+/// `offset + n`, folding `n` into a constant already added to `offset`.
+fn offset_plus(offset: &str, n: usize) -> String {
+    if n == 0 {
+        return offset.to_string();
+    }
+    if let Some((base, m)) = offset.rsplit_once(" + ") {
+        if let Ok(m) = m.parse::<usize>() {
+            return format!("{} + {}", base, m + n);
+        }
+    }
+    format!("{} + {}", offset, n)
+}
+
+/// An expression reading a `tref` at `offset` from `mem`.
+fn read(tref: &TypeRef, offset: &str) -> String {
+    match tref {
+        TypeRef::Name(nt) => format!("{}(mem, {})", reader(nt), offset),
+        TypeRef::Value(ty) => read_type(ty, None, offset),
+    }
+}
+
+/// An expression reading the list of `elem` with pointer `ptr` and length
+/// `len`, both `Int`s.
+fn read_list(elem: &TypeRef, ptr: &str, len: &str) -> String {
+    match &**elem.type_() {
+        Type::Builtin(BuiltinType::U8 { .. }) => format!("mem.readBytes({}, {})", ptr, len),
+        Type::Builtin(BuiltinType::Char) => format!("mem.readString({}, {})", ptr, len),
+        _ => format!(
+            "mem.readList({}, {}, {}) {{ offset -> {} }}",
+            elem.mem_size_align().size,
+            ptr,
+            len,
+            read(elem, "offset")
+        ),
+    }
+}
+
+fn get(b: BuiltinType, offset: &str) -> String {
+    format!("mem.get{}({})", accessor(b), offset)
+}
+
+fn set(b: BuiltinType, offset: &str, value: &str) -> String {
+    format!("mem.set{}({}, {})", accessor(b), offset, value)
+}
+
+/// An expression reading `ty` at `offset`, which is the definition of the
+/// named type `name` if given.
+fn read_type(ty: &Type, name: Option<&str>, offset: &str) -> String {
+    let u32 = BuiltinType::U32 {
+        lang_ptr_size: false,
+    };
+    match ty {
+        Type::Builtin(b) => get(*b, offset),
+        Type::Pointer(_) | Type::ConstPointer(_) => get(u32, offset),
+        Type::Handle(_) => match name {
+            Some(name) => format!("{}({})", type_name(name), get(u32, offset)),
+            None => get(u32, offset),
+        },
+        Type::List(elem) => read_list(
+            elem,
+            &format!("{}.toInt()", get(u32, offset)),
+            &format!("{}.toInt()", get(u32, &offset_plus(offset, 4))),
+        ),
+        Type::Record(r) => {
+            if let Some(repr) = r.bitflags_repr() {
+                let bits = get(repr.to_builtin(), offset);
+                return match name {
+                    Some(name) => format!("{}({})", type_name(name), bits),
+                    None => bits,
+                };
+            }
+            let fields = r
+                .member_layout()
+                .iter()
+                .map(|m| read(&m.member.tref, &offset_plus(offset, m.offset)))
+                .collect::<Vec<_>>();
+            let class = match name {
+                Some(name) => type_name(name),
+                None if fields.len() == 2 => "kotlin.Pair".to_string(),
+                None => "kotlin.Triple".to_string(),
+            };
+            format!("{}({})", class, fields.join(", "))
+        }
+        Type::Variant(v) => {
+            let tag = format!("{}.toInt()", get(v.tag_repr.to_builtin(), offset));
+            if v.is_bool() {
+                return format!("{} != 0", tag);
+            }
+            let name = match name {
+                Some(name) => name,
+                None if v.is_enum() => return get(v.tag_repr.to_builtin(), offset),
+                None => unreachable!("anonymous variants have no Kotlin representation"),
+            };
+            if v.is_enum() {
+                return format!("{}.fromValue({})", type_name(name), tag);
+            }
+            let payload = v.payload_offset();
+            let mut expr = format!("when (val tag = {}) {{\n", tag);
+            for (i, c) in v.cases.iter().enumerate() {
+                let case = format!("{}.{}", type_name(name), type_name(c.name.as_str()));
+                match &c.tref {
+                    Some(tref) => writeln!(
+                        expr,
+                        "    {} -> {}({})",
+                        i,
+                        case,
+                        read(tref, &offset_plus(offset, payload))
+                    ),
+                    None => writeln!(expr, "    {} -> {}", i, case),
+                }
+                .unwrap();
+            }
+            writeln!(expr, "    else -> invalid(tag, \"{}\")", name).unwrap();
+            expr.push('}');
+            expr
+        }
+    }
+}
+
+/// A statement writing `value`, a `tref`, at `offset` to `mem`.
+fn write(out: &mut String, tref: &TypeRef, offset: &str, value: &str, indent: &str) {
+    match tref {
+        TypeRef::Name(nt) => {
+            writeln!(out, "{}{}(mem, {}, {})", indent, writer(nt), offset, value).unwrap()
+        }
+        TypeRef::Value(ty) => write_type(out, ty, None, offset, value, indent),
+    }
+}
+
+fn write_type(
+    out: &mut String,
+    ty: &Type,
+    name: Option<&str>,
+    offset: &str,
+    value: &str,
+    indent: &str,
+) {
+    let u32 = BuiltinType::U32 {
+        lang_ptr_size: false,
+    };
+    match ty {
+        Type::Builtin(b) => writeln!(out, "{}{}", indent, set(*b, offset, value)).unwrap(),
+        Type::Pointer(_) | Type::ConstPointer(_) => {
+            writeln!(out, "{}{}", indent, set(u32, offset, value)).unwrap()
+        }
+        Type::Handle(_) => {
+            let value = match name {
+                Some(_) => format!("{}.handle", value),
+                None => value.to_string(),
+            };
+            writeln!(out, "{}{}", indent, set(u32, offset, &value)).unwrap()
+        }
+        Type::List(_) => writeln!(out, "{}listWrite()", indent).unwrap(),
+        Type::Record(r) => {
+            if let Some(repr) = r.bitflags_repr() {
+                let value = match name {
+                    Some(_) => format!("{}.bits", value),
+                    None => value.to_string(),
+                };
+                writeln!(out, "{}{}", indent, set(repr.to_builtin(), offset, &value)).unwrap();
+                return;
+            }
+            for (i, m) in r.member_layout().iter().enumerate() {
+                let field = match name {
+                    Some(_) if !r.is_tuple() => {
+                        format!("{}.{}", value, ident(m.member.name.as_str()))
+                    }
+                    _ => format!("{}.component{}()", value, i + 1),
+                };
+                write(
+                    out,
+                    &m.member.tref,
+                    &offset_plus(offset, m.offset),
+                    &field,
+                    indent,
+                );
+            }
+        }
+        Type::Variant(v) => {
+            let tag = v.tag_repr.to_builtin();
+            let tag_value = |i: &str| format!("{}.{}()", i, from_long(v.tag_repr));
+            if v.is_bool() {
+                let value = format!("if ({}) 1u else 0u", value);
+                let value = match v.tag_repr {
+                    IntRepr::U32 => value,
+                    _ => format!("({}).{}()", value, from_long(v.tag_repr)),
+                };
+                writeln!(out, "{}{}", indent, set(tag, offset, &value)).unwrap();
+                return;
+            }
+            let name = match name {
+                Some(name) => name,
+                None if v.is_enum() => {
+                    writeln!(out, "{}{}", indent, set(tag, offset, value)).unwrap();
+                    return;
+                }
+                None => unreachable!("anonymous variants have no Kotlin representation"),
+            };
+            if v.is_enum() {
+                let raw = tag_value(&format!("{}.value.toLong()", value));
+                writeln!(out, "{}{}", indent, set(tag, offset, &raw)).unwrap();
+                return;
+            }
+            writeln!(out, "{}when ({}) {{", indent, value).unwrap();
+            for (i, c) in v.cases.iter().enumerate() {
+                let case = format!("{}.{}", type_name(name), type_name(c.name.as_str()));
+                let inner = format!("{}        ", indent);
+                let raw = match v.tag_repr {
+                    IntRepr::U32 => format!("{}u", i),
+                    IntRepr::U64 => format!("{}uL", i),
+                    repr => format!("{}u.{}()", i, from_long(repr)),
+                };
+                match &c.tref {
+                    Some(tref) => {
+                        writeln!(out, "{}    is {} -> {{", indent, case).unwrap();
+                        writeln!(out, "{}{}", inner, set(tag, offset, &raw)).unwrap();
+                        let payload = offset_plus(offset, v.payload_offset());
+                        write(out, tref, &payload, &format!("{}.value", value), &inner);
+                    }
+                    None => {
+                        writeln!(out, "{}    {} -> {{", indent, case).unwrap();
+                        writeln!(out, "{}{}", inner, set(tag, offset, &raw)).unwrap();
+                    }
+                }
+                writeln!(out, "{}    }}", indent).unwrap();
+            }
+            writeln!(out, "{}}}", indent).unwrap();
+        }
+    }
+}
+
+fn kdoc(out: &mut String, docs: &str, indent: &str) {
+    if docs.trim().is_empty() {
+        return;
+    }
+    writeln!(out, "{}/**", indent).unwrap();
+    out.push_str(&super::doc_comment(docs, " *", indent));
+    writeln!(out, "{} */", indent).unwrap();
+}
+
+fn define_type(out: &mut String, nt: &NamedType) {
+    let name = type_name(nt.name.as_str());
+    kdoc(out, &nt.docs, "");
+    let ty = match &nt.tref {
+        TypeRef::Name(other) => {
+            writeln!(
+                out,
+                "typealias {} = {}",
+                name,
+                type_name(other.name.as_str())
+            )
+            .unwrap();
+            define_accessors(out, nt);
+            return;
+        }
+        TypeRef::Value(ty) => ty,
+    };
+    match &**ty {
+        Type::Record(r) => {
+            if let Some(repr) = r.bitflags_repr() {
+                let bits = builtin(repr.to_builtin());
+                writeln!(
+                    out,
+                    "@JvmInline\nvalue class {}(val bits: {}) {{",
+                    name, bits
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    infix fun or(other: {}): {} = {}(bits or other.bits)",
+                    name, name, name
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    operator fun contains(other: {}): Boolean = (bits and other.bits) == other.bits",
+                    name
+                )
+                .unwrap();
+                out.push_str("\n    companion object {\n");
+                for (i, m) in r.members.iter().enumerate() {
+                    kdoc(out, &m.docs, "        ");
+                    // Shifting an unsigned literal keeps its width.
+                    let one = if repr == IntRepr::U64 { "1uL" } else { "1u" };
+                    let value = match repr {
+                        IntRepr::U32 | IntRepr::U64 => format!("{} shl {}", one, i),
+                        _ => format!("({} shl {}).{}()", one, i, from_long(repr)),
+                    };
+                    writeln!(
+                        out,
+                        "        val {} = {}({})",
+                        m.name.as_str().to_shouty_snake_case(),
+                        name,
+                        value
+                    )
+                    .unwrap();
+                }
+                out.push_str("    }\n}\n");
+            } else {
+                writeln!(out, "data class {}(", name).unwrap();
+                for (i, m) in r.members.iter().enumerate() {
+                    kdoc(out, &m.docs, "    ");
+                    let field = if r.is_tuple() {
+                        format!("f{}", i)
+                    } else {
+                        ident(m.name.as_str())
+                    };
+                    writeln!(out, "    val {}: {},", field, tref_ty(&m.tref)).unwrap();
+                }
+                out.push_str(")\n");
+            }
+        }
+        Type::Variant(v) if v.is_bool() => writeln!(out, "typealias {} = Boolean", name).unwrap(),
+        Type::Variant(v) if v.is_enum() => {
+            writeln!(
+                out,
+                "enum class {}(val value: Int, val witxName: String) {{",
+                name
+            )
+            .unwrap();
+            for (i, c) in v.cases.iter().enumerate() {
+                kdoc(out, &c.docs, "    ");
+                writeln!(
+                    out,
+                    "    {}({}, \"{}\"),",
+                    c.name.as_str().to_shouty_snake_case(),
+                    i,
+                    c.name.as_str()
+                )
+                .unwrap();
+            }
+            out.push_str("    ;\n\n    companion object {\n");
+            writeln!(
+                out,
+                "        fun fromValue(value: Int): {} =\n            \
+                 values().firstOrNull {{ it.value == value }} ?: invalid(value, \"{}\")",
+                name,
+                nt.name.as_str()
+            )
+            .unwrap();
+            out.push_str("    }\n}\n");
+        }
+        Type::Variant(v) => {
+            writeln!(out, "sealed class {} {{", name).unwrap();
+            for c in v.cases.iter() {
+                kdoc(out, &c.docs, "    ");
+                let case = type_name(c.name.as_str());
+                match &c.tref {
+                    Some(tref) => writeln!(
+                        out,
+                        "    data class {}(val value: {}) : {}()",
+                        case,
+                        tref_ty(tref),
+                        name
+                    ),
+                    None => writeln!(out, "    object {} : {}()", case, name),
+                }
+                .unwrap();
+            }
+            out.push_str("}\n");
+        }
+        Type::Handle(_) => {
+            writeln!(out, "@JvmInline\nvalue class {}(val handle: UInt)", name).unwrap()
+        }
+        Type::List(_) | Type::Pointer(_) | Type::ConstPointer(_) | Type::Builtin(_) => {
+            writeln!(out, "typealias {} = {}", name, anonymous_ty(ty)).unwrap()
+        }
+    }
+    define_accessors(out, nt);
+}
+
+/// The functions reading and writing `nt` in guest memory.
+fn define_accessors(out: &mut String, nt: &NamedType) {
+    let name = type_name(nt.name.as_str());
+    let value = match &nt.tref {
+        TypeRef::Name(other) => format!("{}(mem, offset)", reader(other)),
+        TypeRef::Value(ty) => read_type(ty, Some(nt.name.as_str()), "offset"),
+    };
+    writeln!(
+        out,
+        "\ninternal fun {}(mem: GuestMemory, offset: Int): {} =",
+        reader(nt),
+        name
+    )
+    .unwrap();
+    for line in value.lines() {
+        writeln!(out, "    {}", line).unwrap();
+    }
+    writeln!(
+        out,
+        "\ninternal fun {}(mem: GuestMemory, offset: Int, value: {}) {{",
+        writer(nt),
+        name
+    )
+    .unwrap();
+    match &nt.tref {
+        TypeRef::Name(other) => writeln!(out, "    {}(mem, offset, value)", writer(other)).unwrap(),
+        TypeRef::Value(ty) => {
+            write_type(out, ty, Some(nt.name.as_str()), "offset", "value", "    ")
+        }
+    }
+    out.push_str("}\n");
+}
+
+/// The Kotlin type of the result of `func`, as returned by its method: the
+/// ok value of an `expected`, whose errors are thrown.
+fn result_ty(func: &InterfaceFunc) -> Option<String> {
+    let result = func.results.first()?;
+    match &**result.tref.type_() {
+        Type::Variant(v) if v.as_expected().is_some() => v.as_expected().unwrap().0.map(tref_ty),
+        _ => Some(tref_ty(&result.tref)),
+    }
+}
+
+fn define_module(out: &mut String, module: &Module) {
+    let name = type_name(module.name.as_str());
+    kdoc(out, &module.docs, "");
+    writeln!(out, "interface {} {{", name).unwrap();
+    for (i, f) in module.funcs().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        kdoc(out, &f.docs, "    ");
+        let mut params = vec!["mem: GuestMemory".to_string()];
+        for p in f.params.iter() {
+            params.push(format!("{}: {}", ident(p.name.as_str()), tref_ty(&p.tref)));
+        }
+        let ret = match (f.noreturn, result_ty(&f)) {
+            (true, _) => ": Nothing".to_string(),
+            (false, Some(ty)) => format!(": {}", ty),
+            (false, None) => String::new(),
+        };
+        writeln!(
+            out,
+            "    fun {}({}){}",
+            ident(f.name.as_str()),
+            params.join(", "),
+            ret
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+
+    writeln!(
+        out,
+        "\n/** Host functions of the `{}` module, calling `impl`. */",
+        module.name.as_str()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "fun {}Functions(impl: {}): List<HostFunction> = listOf(",
+        ident(module.name.as_str()).trim_matches('`'),
+        name
+    )
+    .unwrap();
+    for f in module.funcs() {
+        define_glue(out, module, &f);
+    }
+    out.push_str(")\n");
+}
+
+fn val_types(types: &[WasmType]) -> String {
+    let types = types
+        .iter()
+        .map(|t| match t {
+            WasmType::I32 => "ValType.I32",
+            WasmType::I64 => "ValType.I64",
+            WasmType::F32 => "ValType.F32",
+            WasmType::F64 => "ValType.F64",
+        })
+        .collect::<Vec<_>>();
+    format!("listOf({})", types.join(", "))
+}
+
+fn define_glue(out: &mut String, module: &Module, func: &InterfaceFunc) {
+    let (params, results) = func.wasm_signature();
+    out.push_str("    HostFunction(\n");
+    writeln!(out, "        \"{}\",", module.name.as_str()).unwrap();
+    writeln!(out, "        \"{}\",", func.name.as_str()).unwrap();
+    writeln!(
+        out,
+        "        FunctionType.of({}, {}),",
+        val_types(&params),
+        val_types(&results)
+    )
+    .unwrap();
+    out.push_str("    ) { instance, args ->\n");
+    let mut gen = GlueGen {
+        src: String::new(),
+        blocks: Vec::new(),
+        block_results: Vec::new(),
+        payload: None,
+        tmp: 0,
+    };
+    gen.line("val mem = GuestMemory(instance.memory())");
+    func.call_interface(&func.name, &mut gen);
+    for line in gen.src.lines() {
+        writeln!(out, "        {}", line).unwrap();
+    }
+    out.push_str("    },\n");
+}
+
+/// Generates the body of a host function from the instructions of
+/// [`InterfaceFunc::call_interface`]. Operands are Kotlin expressions, core
+/// wasm values are `Long`s as chicory passes them, and `src` is unindented.
+struct GlueGen {
+    src: String,
+    blocks: Vec<(String, Option<String>)>,
+    block_results: Vec<(String, Option<String>, Option<String>)>,
+    /// Name bound to the payload of the variant matched in the current block.
+    payload: Option<String>,
+    tmp: usize,
+}
+
+impl GlueGen {
+    fn line(&mut self, line: &str) {
+        self.src.push_str(line);
+        self.src.push('\n');
+    }
+
+    fn tmp(&mut self, prefix: &str) -> String {
+        self.tmp += 1;
+        format!("{}{}", prefix, self.tmp - 1)
+    }
+
+    /// The branch for a block from `finish_block` whose payload is `value`,
+    /// assigning its result, or `default` if it has none, to `result`.
+    fn branch(&mut self, value: &str, result: &str, default: &str) -> String {
+        let (src, expr, payload) = self.block_results.pop().unwrap();
+        let mut branch = String::new();
+        if let Some(payload) = payload {
+            writeln!(branch, "    val {} = {}", payload, value).unwrap();
+        }
+        for line in src.lines() {
+            writeln!(branch, "    {}", line).unwrap();
+        }
+        let expr = expr.unwrap_or_else(|| default.to_string());
+        writeln!(branch, "    {} = {}", result, expr).unwrap();
+        branch
+    }
+}
+
+impl Bindgen for GlueGen {
+    type Operand = String;
+
+    fn emit(
+        &mut self,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<String>,
+        results: &mut Vec<String>,
+    ) {
+        use Instruction::*;
+        let op = |i: usize| operands[i].clone();
+        match inst {
+            GetArg { nth } => results.push(format!("args[{}]", nth)),
+
+            // Lifting the arguments.
+            S8FromI32 => results.push(format!("{}.toByte()", op(0))),
+            U8FromI32 | Char8FromI32 => results.push(format!("{}.toUByte()", op(0))),
+            S16FromI32 => results.push(format!("{}.toShort()", op(0))),
+            U16FromI32 => results.push(format!("{}.toUShort()", op(0))),
+            S32FromI32 | CharFromI32 => results.push(format!("{}.toInt()", op(0))),
+            U32FromI32 | UsizeFromI32 | PointerFromI32 { .. } | ConstPointerFromI32 { .. } => {
+                results.push(format!("{}.toUInt()", op(0)))
+            }
+            S64FromI64 => results.push(op(0)),
+            U64FromI64 => results.push(format!("{}.toULong()", op(0))),
+            If32FromF32 => results.push(format!("Float.fromBits({}.toInt())", op(0))),
+            If64FromF64 => results.push(format!("Double.fromBits({})", op(0))),
+            HandleFromI32 { ty } => results.push(format!(
+                "{}({}.toUInt())",
+                type_name(ty.name.as_str()),
+                op(0)
+            )),
+            BitflagsFromI32 { ty } | BitflagsFromI64 { ty } => {
+                let repr = match &**ty.type_() {
+                    Type::Record(r) => r.bitflags_repr().expect("flags have a repr"),
+                    _ => unreachable!("flags are records"),
+                };
+                results.push(format!(
+                    "{}({}.{}())",
+                    type_name(ty.name.as_str()),
+                    op(0),
+                    from_long(repr)
+                ))
+            }
+            EnumLift { ty } => results.push(format!(
+                "{}.fromValue({}.toInt())",
+                type_name(ty.name.as_str()),
+                op(0)
+            )),
+            ListFromPointerLength { ty } => results.push(read_list(
+                ty,
+                &format!("{}.toInt()", op(0)),
+                &format!("{}.toInt()", op(1)),
+            )),
+            Load { ty } => results.push(format!("{}(mem, {}.toInt())", reader(ty), op(0))),
+
+            CallInterface { func, .. } => {
+                let mut args = vec!["mem".to_string()];
+                for operand in operands.iter() {
+                    let param = self.tmp("param");
+                    self.line(&format!("val {} = {}", param, operand));
+                    args.push(param);
+                }
+                let call = format!("impl.{}({})", ident(func.name.as_str()), args.join(", "));
+                let expected = func.results.first().and_then(|r| match &**r.tref.type_() {
+                    Type::Variant(v) => v.as_expected(),
+                    _ => None,
+                });
+                if func.results.is_empty() || func.noreturn {
+                    self.line(&call);
+                } else if let Some((ok, err)) = expected {
+                    // The result is lowered by `ResultLower`, from the value
+                    // returned or the error thrown.
+                    let ret = self.tmp("ret");
+                    let err_ty = err.map(tref_ty).unwrap_or_else(|| "Any".to_string());
+                    if let Some(ok) = ok {
+                        self.line(&format!("var {}: {}? = null", ret, tref_ty(ok)));
+                    }
+                    self.line(&format!("var {}Err: {}? = null", ret, err_ty));
+                    self.line("try {");
+                    match ok {
+                        Some(_) => self.line(&format!("    {} = {}", ret, call)),
+                        None => self.line(&format!("    {}", call)),
+                    }
+                    self.line("} catch (e: WitxException) {");
+                    self.line(&format!("    {}Err = e.error as {}", ret, err_ty));
+                    self.line("}");
+                    results.push(ret);
+                } else {
+                    let ret = self.tmp("ret");
+                    self.line(&format!("val {} = {}", ret, call));
+                    results.push(ret);
+                }
+            }
+
+            // Lowering the results.
+            I32FromChar | I32FromS32 | I32FromU16 | I32FromS16 | I32FromU8 | I32FromS8
+            | I32FromChar8 | I32FromU32 | I32FromUsize | I32FromPointer | I32FromConstPointer
+            | I64FromU64 => results.push(format!("{}.toLong()", op(0))),
+            I64FromS64 => results.push(op(0)),
+            I32FromHandle { .. } => results.push(format!("{}.handle.toLong()", op(0))),
+            I32FromBitflags { .. } | I64FromBitflags { .. } => {
+                results.push(format!("{}.bits.toLong()", op(0)))
+            }
+            F32FromIf32 => results.push(format!("{}.toRawBits().toLong()", op(0))),
+            F64FromIf64 => results.push(format!("{}.toRawBits()", op(0))),
+            EnumLower { .. } => results.push(format!("{}.value.toLong()", op(0))),
+            VariantPayload => {
+                let payload = self.tmp("payload");
+                self.payload = Some(payload.clone());
+                results.push(payload);
+            }
+            TupleLower { amt } => {
+                for i in 0..*amt {
+                    results.push(format!("{}.component{}()", op(0), i + 1));
+                }
+            }
+            Store { ty } => {
+                self.line(&format!(
+                    "{}(mem, {}.toInt(), {})",
+                    writer(ty),
+                    op(1),
+                    op(0)
+                ));
+            }
+            ResultLower { .. } => {
+                let ret = op(0);
+                let result = self.tmp("result");
+                let err = self.branch(&format!("{}Err", ret), &result, "1L");
+                let ok = self.branch(&format!("{}!!", ret), &result, "0L");
+                self.line(&format!("val {}: Long", result));
+                self.line(&format!(
+                    "if ({}Err == null) {{\n{}}} else {{\n{}}}",
+                    ret, ok, err
+                ));
+                results.push(result);
+            }
+            Return { amt: 0 } => self.line("null"),
+            Return { .. } => {
+                let values = operands.join(", ");
+                self.line(&format!("longArrayOf({})", values));
+            }
+
+            AddrOf
+            | ListPointerLength
+            | CallWasm { .. }
+            | ReturnPointerGet { .. }
+            | ResultLift
+            | TupleLift { .. }
+            | ReuseReturn => unreachable!("{:?} is only used when calling wasm", inst),
+        }
+    }
+
+    fn allocate_space(&mut self, _slot: usize, _ty: &NamedType) {
+        unreachable!("return pointers are provided by the caller")
+    }
+
+    fn push_block(&mut self) {
+        let src = mem::take(&mut self.src);
+        let payload = self.payload.take();
+        self.blocks.push((src, payload));
+    }
+
+    fn finish_block(&mut self, operand: Option<String>) {
+        let (src, payload) = self.blocks.pop().unwrap();
+        let block_src = mem::replace(&mut self.src, src);
+        let block_payload = mem::replace(&mut self.payload, payload);
+        self.block_results.push((block_src, operand, block_payload));
+    }
+}
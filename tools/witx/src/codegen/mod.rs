@@ -0,0 +1,1368 @@
+//! Generate language bindings from a witx document.
+//!
+//! Each supported [`Language`] has a backend which turns a validated
+//! [`Document`] into a set of [`OutputFile`]s. Backends which need to
+//! translate between interface values and core wasm values are driven by the
+//! instruction streams in [`crate::abi`], so all of them agree on the ABI.
+//! Other crates can add backends of their own through a [`Registry`].
+
+mod assemblyscript;
+mod backend;
+mod backend_stub;
+mod benchmark;
+mod c;
+mod conformance;
+mod cpp;
+mod flatbuffers;
+mod golden;
+mod grpc;
+mod kotlin;
+mod rust;
+mod rust_guest;
+mod rust_host;
+mod swift;
+mod webnn;
+
+pub use backend::{BackendError, BackendOption, CodegenBackend, Config, Registry};
+pub use golden::{bless_golden, compare_golden, golden_files, GoldenDiff, GoldenError};
+
+use crate::phase_log::Phase;
+use crate::{Case, Document, Id, InterfaceFunc, NamedType, Type, TypeRef, WasmType};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Languages with a backend in this crate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Language {
+    /// A Rust crate for wasm guests importing the document's modules.
+    RustGuest,
+    /// A Rust crate implementing the document's modules in a wasmtime host.
+    WasmtimeHost,
+    /// A Rust crate implementing the document's modules in a wasmer host.
+    WasmerHost,
+    /// A C header declaring the document's types and imports.
+    C,
+    /// A C++17 header wrapping the C header, with classes owning handles.
+    Cpp,
+    /// AssemblyScript declarations of the document's types and imports.
+    AssemblyScript,
+    /// Swift declarations of the document's types and imports, for
+    /// SwiftWasm guests.
+    Swift,
+    /// A conformance suite for wasmtime hosts implementing the document's
+    /// modules.
+    Conformance,
+    /// A JavaScript module implementing the document's modules for guests
+    /// in web runtimes, with the browser's WebNN API for wasi-nn.
+    WebNN,
+    /// Kotlin host functions implementing the document's modules for
+    /// guests run by the chicory JVM runtime.
+    Kotlin,
+    /// A protobuf service mirroring the document's modules, and a Rust
+    /// crate serving it with tonic.
+    Grpc,
+    /// A protobuf schema of the document's types, without services.
+    Protobuf,
+    /// A FlatBuffers schema of the document's types.
+    FlatBuffers,
+    /// A skeleton crate implementing the document's modules with OpenVINO,
+    /// for backend authors to fill in.
+    OpenVinoStub,
+    /// A skeleton crate implementing the document's modules with ONNX
+    /// Runtime, for backend authors to fill in.
+    OnnxStub,
+    /// A criterion benchmark crate driving wasmtime hosts implementing
+    /// wasi-nn through an inference.
+    Benchmark,
+}
+
+impl Language {
+    /// All languages, in the order they are listed by the CLI.
+    pub fn all() -> &'static [Language] {
+        &[
+            Language::RustGuest,
+            Language::WasmtimeHost,
+            Language::WasmerHost,
+            Language::C,
+            Language::Cpp,
+            Language::AssemblyScript,
+            Language::Swift,
+            Language::Conformance,
+            Language::WebNN,
+            Language::Kotlin,
+            Language::Grpc,
+            Language::Protobuf,
+            Language::FlatBuffers,
+            Language::OpenVinoStub,
+            Language::OnnxStub,
+            Language::Benchmark,
+        ]
+    }
+
+    /// The name used to select this language, e.g. on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::RustGuest => "rust-guest",
+            Language::WasmtimeHost => "wasmtime-host",
+            Language::WasmerHost => "wasmer-host",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::AssemblyScript => "assemblyscript",
+            Language::Swift => "swift",
+            Language::Conformance => "conformance",
+            Language::WebNN => "webnn",
+            Language::Kotlin => "kotlin",
+            Language::Grpc => "grpc",
+            Language::Protobuf => "protobuf",
+            Language::FlatBuffers => "flatbuffers",
+            Language::OpenVinoStub => "openvino-stub",
+            Language::OnnxStub => "onnx-stub",
+            Language::Benchmark => "benchmark",
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Language::all()
+            .iter()
+            .find(|l| l.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                let names = Language::all().iter().map(|l| l.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown language `{}`: expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Language-specific settings for a backend. Backends ignore the options
+/// that don't apply to them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Name of the generated Rust crate.
+    pub crate_name: Option<String>,
+    /// Include guard of the generated C header.
+    pub header_guard: Option<String>,
+    /// Namespace to place generated declarations in. For C this is used as
+    /// a prefix for every identifier, and defaults to the module name. For
+    /// C++ it names the namespace, without any trailing `_`, for Kotlin and
+    /// protobuf the package, which defaults to the name of the document, and
+    /// for FlatBuffers the namespace, with the same default.
+    pub namespace: Option<String>,
+    /// Also generate a mock implementation of each module, for testing
+    /// guests without a real host (wasmtime-host, wasmer-host).
+    pub mocks: bool,
+    /// Also generate a wrapper of each module's implementations emitting
+    /// `tracing` spans for every call (wasmtime-host, wasmer-host).
+    pub tracing: bool,
+    /// Also generate a wrapper of wasi-nn implementations enforcing limits on
+    /// graph and tensor sizes and execution contexts (wasmtime-host, wasmer-host).
+    pub limits: bool,
+    /// Generate `#![no_std]` bindings, which only use `core` (rust-guest).
+    pub no_std: bool,
+    /// Only generate the raw ABI layer of the bindings, without the safe
+    /// wrappers on top of it (rust-guest).
+    pub raw_only: bool,
+    /// Extra derives and outer attributes for every Rust type defined for a
+    /// witx type (rust-guest, wasmtime-host, wasmer-host).
+    pub type_attributes: TypeAttributes,
+    /// Extra derives and outer attributes for the Rust types of particular
+    /// witx types, by witx name, on top of `type_attributes`.
+    pub named_type_attributes: BTreeMap<String, TypeAttributes>,
+    /// Implement serde's `Serialize` and `Deserialize` for generated records,
+    /// variants and enums (rust-guest, wasmtime-host, wasmer-host). Fields and cases are
+    /// named as in witx, so `$tensor_type` serializes as `"fp32"` rather than
+    /// `"Fp32"`. Flags are integers, and serialize as their bits. Types
+    /// containing pointers, and rust-guest types borrowing memory, can't be
+    /// serialized and are left out.
+    pub serde: bool,
+    /// Implement proptest's `Arbitrary` for generated records, variants and
+    /// enums, and a strategy function for each flags type, behind an
+    /// optional `proptest` feature of the crate (wasmtime-host, wasmer-host). Enums only
+    /// take their cases and flags their defined bits, lists are bounded, and
+    /// lists with a declared size have it.
+    pub proptest: bool,
+    /// How the wrappers of the C++ header report errors (cpp).
+    pub cpp_errors: CppErrors,
+}
+
+/// How generated C++ functions report the errors of failed calls.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum CppErrors {
+    /// Throw an exception: for error enums, a class holding the code and
+    /// described by the docs of its case.
+    #[default]
+    Exceptions,
+    /// Return an `expected` of the result or the error code, for code built
+    /// without exceptions.
+    Expected,
+}
+
+impl FromStr for CppErrors {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exceptions" => Ok(CppErrors::Exceptions),
+            "expected" => Ok(CppErrors::Expected),
+            _ => Err(format!(
+                "unknown C++ error handling `{}`: expected exceptions or expected",
+                s
+            )),
+        }
+    }
+}
+
+/// Extra derives and outer attributes for generated Rust types. Types which
+/// are generated as aliases, like lists and flags, can't take any and ignore
+/// them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeAttributes {
+    /// Paths of traits to derive, like `Hash` or `serde::Serialize`.
+    pub derives: Vec<String>,
+    /// Attributes, without the surrounding `#[...]`.
+    pub attributes: Vec<String>,
+}
+
+/// A generated file. `path` is relative to the output directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+impl OutputFile {
+    pub fn new<P: Into<PathBuf>>(path: P, contents: String) -> Self {
+        OutputFile {
+            path: path.into(),
+            contents,
+        }
+    }
+}
+
+/// Generate bindings for `doc` in `language`.
+pub fn generate(doc: &Document, language: Language, options: &Options) -> Vec<OutputFile> {
+    let _phase = Phase::start(|| format!("generate {}", language));
+    let files = match language {
+        Language::RustGuest => rust_guest::generate(doc, options),
+        Language::WasmtimeHost => rust_host::generate(doc, options, rust_host::Engine::Wasmtime),
+        Language::WasmerHost => rust_host::generate(doc, options, rust_host::Engine::Wasmer),
+        Language::C => c::generate(doc, options),
+        Language::Cpp => cpp::generate(doc, options),
+        Language::AssemblyScript => assemblyscript::generate(doc, options),
+        Language::Swift => swift::generate(doc),
+        Language::Conformance => conformance::generate(doc, options),
+        Language::WebNN => webnn::generate(doc),
+        Language::Kotlin => kotlin::generate(doc, options),
+        Language::Grpc => grpc::generate(doc, options),
+        Language::Protobuf => grpc::generate_schema(doc, options),
+        Language::FlatBuffers => flatbuffers::generate(doc, options),
+        Language::OpenVinoStub => backend_stub::generate(doc, options, &backend_stub::OPENVINO),
+        Language::OnnxStub => backend_stub::generate(doc, options, &backend_stub::ONNX_RUNTIME),
+        Language::Benchmark => benchmark::generate(doc, options),
+    };
+    for f in files.iter() {
+        event!("generated {:?}, {} bytes", f.path, f.contents.len());
+    }
+    files
+}
+
+/// The core wasm parameters and results of `func`, with a name for each
+/// parameter derived from the interface parameter it's lowered from.
+pub(crate) fn lowered_signature(func: &InterfaceFunc) -> (Vec<(String, WasmType)>, Vec<WasmType>) {
+    let (params, results) = func.wasm_signature();
+    let mut names = Vec::new();
+    for p in func.params.iter() {
+        names.push(p.name.as_str().to_string());
+        if let Type::List(_) = &**p.tref.type_() {
+            names.push(format!("{}_len", p.name.as_str()));
+        }
+    }
+    // Anything past the interface parameters is a return pointer.
+    let nargs = names.len();
+    for i in nargs..params.len() {
+        names.push(format!("retptr{}", i - nargs));
+    }
+    (names.into_iter().zip(params).collect(), results)
+}
+
+/// The name of generated files: bindings for a single module are named after
+/// it.
+fn file_stem(doc: &Document) -> String {
+    let mut modules = doc.modules();
+    match (modules.next(), modules.next()) {
+        (Some(m), None) => m.name.as_str().to_string(),
+        _ => "bindings".to_string(),
+    }
+}
+
+/// Comment lines for `docs`, each starting with `prefix`.
+pub(crate) fn doc_comment(docs: &str, prefix: &str, indent: &str) -> String {
+    let mut out = String::new();
+    for line in docs.trim_end().lines() {
+        out.push_str(indent);
+        out.push_str(prefix);
+        if !line.is_empty() {
+            out.push(' ');
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Whether `nt` defines a handle, which backends with safe wrappers give an
+/// owned type. Aliases of handles stay raw.
+fn is_owned_handle(nt: &NamedType) -> bool {
+    matches!(&nt.tref, TypeRef::Value(ty) if matches!(&**ty, Type::Handle(_)))
+}
+
+/// The handle `func` releases, if it's the `drop_<handle>` function of an
+/// owned handle: one taking the handle as its only param.
+fn drops_handle(func: &InterfaceFunc) -> Option<Id> {
+    let handle = func.name.as_str().strip_prefix("drop_")?;
+    match func.params.as_slice() {
+        [p] => match &p.tref {
+            TypeRef::Name(nt) if nt.name.as_str() == handle && is_owned_handle(nt) => {
+                Some(nt.name.clone())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The named enums which are the errors of function results, and so get
+/// error types in languages which have them.
+fn error_enums(doc: &Document) -> Vec<Rc<NamedType>> {
+    let mut errors = doc
+        .error_types()
+        .filter_map(|tref| match tref {
+            TypeRef::Name(nt) => match &**nt.type_() {
+                Type::Variant(v) if v.is_enum() => Some(nt),
+                _ => None,
+            },
+            TypeRef::Value(_) => None,
+        })
+        .collect::<Vec<_>>();
+    // `error_types` is in the order errors are used, so sort by definition.
+    errors.sort_by_key(|nt| doc.typenames().position(|t| t.name == nt.name));
+    errors
+}
+
+/// The message of an error case: the first sentence of its docs, or its
+/// name if it has none.
+fn error_message(case: &Case) -> String {
+    let summary = crate::docs::summary(&case.docs);
+    let summary = summary.trim_end_matches('.');
+    if summary.is_empty() {
+        case.name.as_str().replace('_', " ")
+    } else {
+        summary.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::Path;
+
+    fn doc() -> Document {
+        crate::parse(
+            "(typename $errno (enum (@witx tag u16) $success $inval))
+             (typename $tensor_type (enum (@witx tag u8) $f32 $q4))
+             (typename $dims (list u32))
+             (typename $tensor
+                (record
+                    (field $dimensions $dims)
+                    (field $type u8)
+                    (field $data (list u8) (@witx size (product $dimensions)))))
+             (typename $graph (handle))
+             (module $nn
+                (@interface func (export \"load\")
+                    (param $builder (list u8))
+                    (param $tensor $tensor)
+                    (result $error (expected $graph (error $errno)))))",
+        )
+        .unwrap()
+    }
+
+    fn generate_one(language: Language, options: &Options, path: &str) -> String {
+        generate(&doc(), language, options)
+            .into_iter()
+            .find(|f| f.path == Path::new(path))
+            .unwrap_or_else(|| panic!("{} generates {}", language, path))
+            .contents
+    }
+
+    #[test]
+    fn language_names() {
+        for language in Language::all() {
+            assert_eq!(language.name().parse::<Language>(), Ok(*language));
+        }
+        assert!("cobol".parse::<Language>().is_err());
+    }
+
+    #[test]
+    fn lowered_names() {
+        let doc = doc();
+        let func = doc
+            .module(&crate::Id::new("nn"))
+            .unwrap()
+            .funcs()
+            .next()
+            .unwrap();
+        let (params, results) = lowered_signature(&func);
+        let names = params.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["builder", "builder_len", "tensor", "retptr0"]);
+        assert_eq!(results, [WasmType::I32]);
+    }
+
+    #[test]
+    fn rust_guest() {
+        let options = Options {
+            crate_name: Some("nn-sys".to_string()),
+            ..Options::default()
+        };
+        let manifest = generate_one(Language::RustGuest, &options, "Cargo.toml");
+        assert!(manifest.contains("name = \"nn-sys\""));
+        let lib = generate_one(Language::RustGuest, &options, "src/lib.rs");
+        assert!(lib.contains("pub const ERRNO_INVAL: Errno = Errno(1);"));
+        assert!(lib.contains("pub struct Tensor<'a> {"));
+        assert!(lib.contains("    pub r#type: u8,"));
+        assert!(lib.contains(
+            "pub fn load(builder: &'_ [u8], tensor: Tensor<'_>) -> Result<Graph, Errno> {"
+        ));
+        assert!(lib.contains("#[link(wasm_import_module = \"nn\")]"));
+        assert!(lib.contains("                1 => Some(4),\n                _ => None,"));
+        assert!(lib.contains("pub fn buffer_size(&self, dimensions: &[u32]) -> Option<usize> {"));
+        assert!(lib.contains(
+            "pub fn load(builder: i32, builder_len: i32, tensor: i32, retptr0: i32) -> i32;"
+        ));
+        assert!(!lib.contains("#![no_std]"));
+    }
+
+    #[test]
+    fn type_attributes() {
+        let mut options = Options {
+            type_attributes: TypeAttributes {
+                derives: vec!["Hash".to_string(), "serde::Serialize".to_string()],
+                attributes: vec!["allow(missing_docs)".to_string()],
+            },
+            ..Options::default()
+        };
+        options.named_type_attributes.insert(
+            "tensor".to_string(),
+            TypeAttributes {
+                derives: vec!["PartialOrd".to_string()],
+                attributes: vec!["serde(rename_all = \"camelCase\")".to_string()],
+            },
+        );
+        let guest = generate_one(Language::RustGuest, &options, "src/lib.rs");
+        assert!(guest.contains(
+            "    #[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, serde::Serialize)]\n    \
+             #[allow(missing_docs)]\n    pub struct Errno("
+        ));
+        assert!(guest.contains(
+            "    #[derive(Copy, Clone, Debug, Hash, serde::Serialize, PartialOrd)]\n    \
+             #[allow(missing_docs)]\n    #[serde(rename_all = \"camelCase\")]\n    \
+             pub struct Tensor<'a> {"
+        ));
+        // Aliases can't take attributes.
+        assert!(guest.contains("    pub type Dims<'a> = &'a [u32];"));
+
+        let host = generate_one(Language::WasmtimeHost, &options, "src/lib.rs");
+        assert!(host.contains(
+            "#[derive(Clone, Debug, PartialEq, Hash, serde::Serialize, PartialOrd)]\n\
+             #[allow(missing_docs)]\n#[serde(rename_all = \"camelCase\")]\npub struct Tensor {"
+        ));
+    }
+
+    #[test]
+    fn error_types() {
+        let lib = generate_one(Language::RustGuest, &Options::default(), "src/lib.rs");
+        assert!(lib.contains("pub enum Errno {\n    Success,\n    Inval,\n"));
+        assert!(lib.contains(
+            "            1 => Errno::Inval,\n            other => Errno::Unknown(other),"
+        ));
+        assert!(lib.contains("            Errno::Inval => raw::Errno(1),"));
+        assert!(lib.contains("            Errno::Inval => f.write_str(\"inval\"),"));
+        assert!(lib.contains("impl std::error::Error for Errno {}"));
+        assert!(lib.contains("_ => Err(Errno::from(raw::Errno(ret as u16))),"));
+        // Only enums returned as errors get error types.
+        assert!(!lib.contains("pub enum TensorType"));
+
+        let no_std = Options {
+            no_std: true,
+            ..Options::default()
+        };
+        let lib = generate_one(Language::RustGuest, &no_std, "src/lib.rs");
+        assert!(lib.contains("impl core::fmt::Display for Errno {"));
+        assert!(!lib.contains("std::error::Error"));
+
+        let host = generate_one(Language::WasmtimeHost, &Options::default(), "src/lib.rs");
+        assert!(host.contains("            Errno::Inval => \"inval\",\n"));
+        assert!(host.contains("impl std::error::Error for Errno {}"));
+    }
+
+    #[test]
+    fn names() {
+        let doc = crate::parse(
+            "(typename $errno (enum (@witx tag u16) $success $inval))
+             (typename $perms (flags (@witx repr u8) $read $write))
+             (module $nn)",
+        )
+        .unwrap();
+        let file = |language: Language, path: &str| {
+            generate(&doc, language, &Options::default())
+                .into_iter()
+                .find(|f| f.path == Path::new(path))
+                .unwrap()
+                .contents
+        };
+
+        let guest = file(Language::RustGuest, "src/lib.rs");
+        assert!(guest.contains("pub const ALL: [Errno; 2] = [\n            ERRNO_SUCCESS,\n"));
+        assert!(
+            guest.contains("                1 => \"inval\",\n                _ => \"unknown\",")
+        );
+        assert!(guest.contains("                \"inval\" => Some(ERRNO_INVAL),"));
+        assert!(guest.contains("    pub const PERMS_FLAGS: [(&str, Perms); 2] = ["));
+        assert!(guest.contains("    pub fn perms_from_str(name: &str) -> Option<Perms> {"));
+
+        let host = file(Language::WasmtimeHost, "src/lib.rs");
+        assert!(host.contains("    pub const ALL: [Errno; 2] = [\n        Errno::Success,\n"));
+        assert!(host.contains("            Errno::Inval => \"inval\",\n"));
+        assert!(host.contains("            \"inval\" => Some(Errno::Inval),"));
+        assert!(host
+            .contains("pub fn perms_names(flags: Perms) -> impl Iterator<Item = &'static str> {"));
+
+        let c = file(Language::C, "nn.h");
+        assert!(c.contains("#define NN_ERRNO_COUNT 2\n"));
+        assert!(c.contains("    case NN_ERRNO_INVAL: return \"inval\";\n"));
+        assert!(c.contains(
+            "static inline bool nn_errno_from_name(const char *name, nn_errno_t *value) {"
+        ));
+        assert!(c.contains("        if (strcmp(name, nn_perms_name((nn_perms_t)1 << i)) == 0) {"));
+    }
+
+    #[test]
+    fn serde() {
+        let doc = crate::parse(
+            "(typename $tensor_type (enum (@witx tag u8) $f16 $f32))
+             (typename $layout (record (field $type $tensor_type) (field $rank u32)))
+             (typename $tensor (record (field $dims (list u32))))
+             (module $nn)",
+        )
+        .unwrap();
+        let options = Options {
+            serde: true,
+            ..Options::default()
+        };
+        let file = |language: Language, path: &str| {
+            generate(&doc, language, &options)
+                .into_iter()
+                .find(|f| f.path == Path::new(path))
+                .unwrap()
+                .contents
+        };
+
+        let guest = file(Language::RustGuest, "src/lib.rs");
+        assert!(guest.contains(
+            "    #[derive(Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]\n    \
+             pub struct Layout {\n        pub r#type: TensorType,"
+        ));
+        assert!(guest.contains("    impl serde::Serialize for TensorType {"));
+        assert!(guest.contains("E::unknown_variant(v, &[\"f16\", \"f32\"])"));
+        // Lists borrow guest memory.
+        assert!(guest.contains("    #[derive(Copy, Clone, Debug)]\n    pub struct Tensor<'a> {"));
+        let manifest = file(Language::RustGuest, "Cargo.toml");
+        assert!(manifest.contains("serde = { version = \"1.0\", features = [\"derive\"] }"));
+
+        let host = file(Language::WasmtimeHost, "src/lib.rs");
+        assert!(host.contains("    #[serde(rename = \"f16\")]\n    F16,"));
+        assert!(host.contains(
+            "#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]\n\
+             pub struct Tensor {"
+        ));
+    }
+
+    #[test]
+    fn golden() {
+        let dir = std::env::temp_dir().join(format!("witx-golden-{}", std::process::id()));
+        let doc = crate::parse("(typename $size u32) (module $nn)").unwrap();
+        let options = Options::default();
+        assert!(compare_golden(&doc, &options, &dir)
+            .unwrap()
+            .iter()
+            .all(|d| matches!(d, GoldenDiff::Added { .. })));
+        bless_golden(&doc, &options, &dir).unwrap();
+        assert_eq!(compare_golden(&doc, &options, &dir).unwrap(), vec![]);
+
+        let header = Path::new("c/nn.h");
+        std::fs::write(dir.join(header), "// edited\n").unwrap();
+        std::fs::write(dir.join("c/old.h"), "// gone\n").unwrap();
+        let diffs = compare_golden(&doc, &options, &dir).unwrap();
+        assert_eq!(
+            diffs.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+            vec!["c/nn.h: changed", "c/old.h: removed"]
+        );
+        bless_golden(&doc, &options, &dir).unwrap();
+        assert!(!dir.join("c/old.h").exists());
+        assert_eq!(compare_golden(&doc, &options, &dir).unwrap(), vec![]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn proptest() {
+        let doc = crate::parse(
+            "(typename $tensor_type (enum (@witx tag u8) $f16 $f32))
+             (typename $perms (flags (@witx repr u8) $read $write))
+             (typename $tensor
+                (record
+                    (field $dimensions (list u32))
+                    (field $type $tensor_type)
+                    (field $data (list u8) (@witx size (tensor_size $dimensions $type)))))
+             (typename $shape (variant (case $none) (case $perms $perms)))
+             (module $nn)",
+        )
+        .unwrap();
+        let options = Options {
+            proptest: true,
+            ..Options::default()
+        };
+        let files = generate(&doc, Language::WasmtimeHost, &options);
+        assert!(files[0]
+            .contents
+            .contains("proptest = { version = \"1\", optional = true }"));
+        let host = &files[1].contents;
+        assert!(host.contains(
+            "#[cfg(feature = \"proptest\")]\n\
+             impl proptest::arbitrary::Arbitrary for TensorType {"
+        ));
+        assert!(host.contains("        proptest::sample::select(Self::ALL.to_vec()).boxed()\n"));
+        assert!(host.contains(
+            "pub fn perms_strategy() -> proptest::strategy::BoxedStrategy<Perms> {\n    \
+             use proptest::prelude::*;\n    \
+             any::<Perms>().prop_map(|flags| flags & 0x3).boxed()\n}"
+        ));
+        // The dimensions are kept small, and the data sized to match them.
+        assert!(host.contains(
+            "        (proptest::collection::vec(0..=8u32, 0..=4), any::<TensorType>())\n            \
+             .prop_flat_map(|(dimensions, r#type)| {\n                \
+             let data_len = r#type.buffer_size(&dimensions).map(|n| n as u64).unwrap_or(0) as usize;\n                \
+             (Just(dimensions), Just(r#type), proptest::collection::vec(any::<u8>(), data_len))\n"
+        ));
+        assert!(host.contains(
+            "        prop_oneof![\n            \
+             Just(Self::None),\n            \
+             perms_strategy().prop_map(Self::Perms),\n        ]\n"
+        ));
+
+        let host = generate(&doc, Language::WasmtimeHost, &Options::default());
+        assert!(!host[1].contents.contains("proptest"));
+    }
+
+    #[test]
+    fn rust_guest_layers() {
+        let doc = crate::parse(
+            "(typename $errno (enum (@witx tag u16) $success $inval))
+             (typename $graph (handle))
+             (module $nn
+                (@interface func (export \"load\")
+                    (result $error (expected $graph (error $errno))))
+                (@interface func (export \"name\")
+                    (param $graph $graph)
+                    (param $buf (@witx pointer u8))
+                    (result $error (expected (error $errno))))
+                (@interface func (export \"drop_graph\")
+                    (param $graph $graph)))",
+        )
+        .unwrap();
+        let lib = |options: &Options| {
+            generate(&doc, Language::RustGuest, options)
+                .into_iter()
+                .find(|f| f.path == Path::new("src/lib.rs"))
+                .unwrap()
+                .contents
+        };
+
+        let layered = lib(&Options::default());
+        assert!(layered.contains("pub mod raw {\n"));
+        assert!(layered.contains("    pub type Graph = u32;\n"));
+        assert!(layered.contains("            pub fn drop_graph(graph: i32);\n"));
+        assert!(layered.contains("pub use raw::*;\n"));
+        assert!(layered.contains("pub struct Graph(raw::Graph);\n"));
+        assert!(layered.contains(
+            "impl Drop for Graph {\n    fn drop(&mut self) {\n        \
+             unsafe { raw::nn::drop_graph(self.0 as i32) };"
+        ));
+        assert!(layered.contains("    pub fn load() -> Result<Graph, Errno> {\n        unsafe {\n"));
+        assert!(layered.contains("0 => Ok(Graph::from_raw(rp0.assume_init())),"));
+        assert!(layered.contains(
+            "    pub unsafe fn name(graph: &Graph, buf: *mut u8) -> Result<(), Errno> {"
+        ));
+        assert!(layered.contains("raw::nn::name(graph.as_raw() as i32, buf as i32)"));
+        assert!(!layered.contains("pub fn drop_graph(graph: &Graph)"));
+
+        let raw = lib(&Options {
+            raw_only: true,
+            ..Options::default()
+        });
+        assert!(raw.contains("            pub fn load(retptr0: i32) -> i32;\n"));
+        assert!(!raw.contains("pub use raw::*;"));
+        assert!(!raw.contains("pub struct Graph("));
+    }
+
+    #[test]
+    fn rust_guest_no_std() {
+        let options = Options {
+            no_std: true,
+            ..Options::default()
+        };
+        let lib = generate_one(Language::RustGuest, &options, "src/lib.rs");
+        assert!(lib.starts_with(
+            "// This file is automatically generated by witx, DO NOT EDIT!\n#![no_std]\n"
+        ));
+        assert!(!lib.contains("std::"));
+    }
+
+    #[test]
+    fn wasmtime_host() {
+        let options = Options::default();
+        let manifest = generate_one(Language::WasmtimeHost, &options, "Cargo.toml");
+        assert!(manifest.contains("wasmtime = {"));
+        let lib = generate_one(Language::WasmtimeHost, &options, "src/lib.rs");
+        assert!(lib.contains("pub enum Errno {\n    Success,\n    Inval,\n}"));
+        assert!(lib.contains("            r#type: <u8>::read(mem, field(offset, 8)?)?,"));
+        assert!(lib.contains(
+            "fn load(&mut self, mem: &mut GuestMemory<'_>, builder: Vec<u8>, tensor: Tensor) -> Result<Graph, Errno>;"
+        ));
+        assert!(lib.contains("        U: Nn,"));
+        assert!(lib.contains(
+            "    pub fn is_valid_buffer(&self, dimensions: &[u32], len: usize) -> bool {"
+        ));
+        assert!(lib.contains("let param0 = mem.read_list::<u8>(arg0 as u32, arg1 as u32)?;"));
+        assert!(lib.contains("payload3.write(&mut mem, arg3 as u32)?;"));
+        assert!(lib.contains(
+            "        check_size(\"tensor.data\", value.data.len(), size_product(&value.dimensions))?;\n        Ok(value)"
+        ));
+    }
+
+    #[test]
+    fn wasmer_host() {
+        let options = Options {
+            mocks: true,
+            ..Options::default()
+        };
+        let manifest = generate_one(Language::WasmerHost, &options, "Cargo.toml");
+        assert!(manifest.contains("wasmer = \""));
+        assert!(!manifest.contains("wasmtime"));
+        let lib = generate_one(Language::WasmerHost, &options, "src/lib.rs");
+        assert!(!lib.contains("wasmtime"));
+        assert!(lib.contains(
+            "fn load(&mut self, mem: &mut GuestMemory<'_>, builder: Vec<u8>, tensor: Tensor) -> Result<Graph, Errno>;"
+        ));
+        assert!(lib.contains("    pub fn set_memory(&mut self, memory: Memory) {"));
+        assert!(lib.contains(
+            "        add_to_imports_with(store, env, imports, get, InvalidArguments::Trap)"
+        ));
+        assert!(lib.contains(
+            "                move |mut ctx: FunctionEnvMut<HostEnv<T>>, arg0: i32, arg1: i32, arg2: i32, arg3: i32| -> Result<i32, RuntimeError> {"
+        ));
+        assert!(lib.contains("        imports.register_namespace(\"nn\", exports);"));
+
+        let doc = crate::parse(
+            "(module $proc (@interface func (export \"exit\") (param $code u32) (@witx noreturn)))",
+        )
+        .unwrap();
+        let lib = generate(&doc, Language::WasmerHost, &options)
+            .into_iter()
+            .find(|f| f.path == Path::new("src/lib.rs"))
+            .unwrap()
+            .contents;
+        assert!(lib.contains(
+            "fn exit(&mut self, mem: &mut GuestMemory<'_>, code: u32) -> wasmer::RuntimeError;"
+        ));
+        assert!(lib.contains("wasmer::RuntimeError::new(\"`exit` does not return\")"));
+    }
+
+    #[test]
+    fn wasmtime_host_validation() {
+        let doc = crate::parse(
+            "(typename $errno (enum (@witx tag u16) $success $inval))
+             (typename $mode (enum (@witx tag u8) $a $b))
+             (module $m
+                (@interface func (export \"read\")
+                    (param $mode $mode)
+                    (param $buf (@witx pointer u8))
+                    (param $len u32)
+                    (result $error (expected (error $errno))))
+                (@interface func (export \"exit\") (param $code u32) (@witx noreturn)))",
+        )
+        .unwrap();
+        let lib = generate(&doc, Language::WasmtimeHost, &Options::default())
+            .into_iter()
+            .find(|f| f.path == Path::new("src/lib.rs"))
+            .unwrap()
+            .contents;
+        assert!(lib.contains("        add_to_linker_with(linker, get, InvalidArguments::Trap)\n"));
+        assert!(lib.contains(
+            "                    let param1 = GuestPtr::new(arg1 as u32);\n\
+             \x20                   let param2 = arg2 as u32;\n\
+             \x20                   check_pointer(\"read.buf\", &param1)?;\n\
+             \x20                   Ok((param0, param1, param2))\n"
+        ));
+        // Enums are checked before the tag is narrowed, not truncated.
+        assert!(lib.contains(
+            "let param0 = Mode::from_raw(<u8 as std::convert::TryFrom<_>>::try_from(arg0).map_err(|_| \
+             GuestError::InvalidEnumValue { ty: \"mode\", value: arg0 as u32 as u64 })?)?;\n"
+        ));
+        assert!(lib.contains(
+            "                    Err(e) if invalid == InvalidArguments::Error && e.is_invalid_argument() => {\n\
+             \x20                       return Ok(Errno::Inval.raw() as i32)\n"
+        ));
+        // Functions without an error to return always trap.
+        assert!(lib.contains("                let (param0,) = checked?;\n"));
+    }
+
+    #[test]
+    fn wasmtime_host_mocks() {
+        let lib = generate_one(Language::WasmtimeHost, &Options::default(), "src/lib.rs");
+        assert!(!lib.contains("pub mod mock {"));
+        let options = Options {
+            mocks: true,
+            ..Options::default()
+        };
+        let lib = generate_one(Language::WasmtimeHost, &options, "src/lib.rs");
+        assert!(lib.contains("        impl Nn for Mock {"));
+        assert!(lib.contains("            Load {\n                builder: Vec<u8>,"));
+        assert!(lib.contains("self.calls.push(Call::Load { builder, tensor });"));
+        assert!(lib.contains("pub load: std::collections::VecDeque<Result<Graph, Errno>>,"));
+    }
+
+    #[test]
+    fn wasmtime_host_tracing() {
+        let options = Options {
+            tracing: true,
+            ..Options::default()
+        };
+        let manifest = generate_one(Language::WasmtimeHost, &options, "Cargo.toml");
+        assert!(manifest.contains("tracing = \"0.1\""));
+        let lib = generate_one(Language::WasmtimeHost, &options, "src/lib.rs");
+        assert!(lib.contains("        impl<T: Nn> Nn for Traced<T> {"));
+        assert!(lib.contains(
+            "tracing::info_span!(\"nn::load\", builder.len = builder.len(), \
+             tensor.dimensions.len = tensor.dimensions.len(), tensor.r#type = ?tensor.r#type, \
+             tensor.data.len = tensor.data.len());"
+        ));
+        assert!(lib.contains("let result = self.inner.load(mem, builder, tensor);"));
+    }
+
+    #[test]
+    fn wasmtime_host_limits() {
+        let options = Options {
+            limits: true,
+            ..Options::default()
+        };
+        // `errno` has no `resource_exhausted` case to fail calls with.
+        let lib = generate_one(Language::WasmtimeHost, &options, "src/lib.rs");
+        assert!(!lib.contains("pub mod limited {"));
+
+        let doc = crate::parse(
+            "(typename $errno (enum (@witx tag u16) $success $resource_exhausted))
+             (typename $graph_builder (list u8))
+             (typename $graph_execution_context (handle))
+             (module $nn
+                (@interface func (export \"load\")
+                    (param $builder (list $graph_builder))
+                    (result $error (expected (error $errno))))
+                (@interface func (export \"init\")
+                    (result $error (expected $graph_execution_context (error $errno)))))",
+        )
+        .unwrap();
+        let lib = generate(&doc, Language::WasmtimeHost, &options)
+            .into_iter()
+            .find(|f| f.path == Path::new("src/lib.rs"))
+            .unwrap()
+            .contents;
+        assert!(lib.contains("        impl<T: Nn> Nn for Limited<T> {"));
+        assert!(!lib.contains("max_tensor_bytes"));
+        assert!(lib.contains(
+            "if matches!(self.limits.max_graph_size, Some(max) if builder.iter().map(|b| b.len()).sum::<usize>() > max) {\n                    return Err(Errno::ResourceExhausted);"
+        ));
+        assert!(lib.contains("                    self.execution_contexts += 1;"));
+    }
+
+    #[test]
+    fn conformance() {
+        let options = Options::default();
+        let lib = generate_one(Language::Conformance, &options, "src/lib.rs");
+        // A zeroed tensor has one element, but no data.
+        assert!(lib.contains("        case: \"zeroed\",\n        expect: Expect::Rejected,"));
+        assert!(lib.contains("include_str!(\"../guests/nn/load-zeroed.wat\")"));
+        let wat = generate_one(Language::Conformance, &options, "guests/nn/load-zeroed.wat");
+        assert!(
+            wat.contains("(import \"nn\" \"load\" (func $f (param i32 i32 i32 i32) (result i32)))")
+        );
+        assert!(wat.contains(
+            "(call $f (i32.const 1024) (i32.const 0) (i32.const 1024) (i32.const 32768))"
+        ));
+    }
+
+    #[test]
+    fn c() {
+        let options = Options {
+            header_guard: Some("NN_API_H".to_string()),
+            namespace: Some("__nn_".to_string()),
+            ..Options::default()
+        };
+        let header = generate_one(Language::C, &options, "nn.h");
+        assert!(header.contains("#ifndef NN_API_H"));
+        assert!(header.contains("#define __NN_ERRNO_INVAL ((__nn_errno_t)1)"));
+        assert!(header.contains("    uint32_t *buf;\n    size_t buf_len;\n} __nn_dims_t;"));
+        assert!(header.contains(
+            "__nn_errno_t __nn_load(\n    const uint8_t *builder,\n    size_t builder_len,\n    \
+             const __nn_tensor_t *tensor,\n    __nn_graph_t *retptr0\n)"
+        ));
+        assert!(header.contains("__import_module__(\"nn\"), __import_name__(\"load\")"));
+        assert!(header
+            .contains("static inline uint32_t __nn_tensor_type_bits(__nn_tensor_type_t ty) {"));
+        assert!(header.contains("    case __NN_TENSOR_TYPE_Q4: return 4;"));
+    }
+
+    #[test]
+    fn layout_assertions() {
+        let lib = generate_one(Language::RustGuest, &Options::default(), "src/lib.rs");
+        assert!(lib.contains("    #[cfg(target_arch = \"wasm32\")]\n    const _: () = {\n"));
+        assert!(lib.contains(
+            "        assert!(size_of::<Tensor<'static>>() == 20 && align_of::<Tensor<'static>>() == 4);"
+        ));
+        assert!(
+            lib.contains("        assert!(size_of::<Errno>() == 2 && align_of::<Errno>() == 2);")
+        );
+
+        // Rust doesn't define the layout of tuples.
+        let doc = crate::parse("(typename $pair (tuple u8 u64))").unwrap();
+        let lib = generate(&doc, Language::RustGuest, &Options::default())
+            .into_iter()
+            .find(|f| f.path == Path::new("src/lib.rs"))
+            .unwrap()
+            .contents;
+        assert!(!lib.contains("size_of"));
+
+        let header = generate_one(Language::C, &Options::default(), "nn.h");
+        assert!(header.contains("#if defined(__wasm32__)\n#ifdef __cplusplus\n"));
+        assert!(header.contains("NN_ASSERT_LAYOUT(nn_tensor_t, 20, 4);\n"));
+        assert!(header.contains("#undef NN_ASSERT_LAYOUT\n#endif\n"));
+    }
+
+    #[test]
+    fn cpp() {
+        let files = generate(&doc(), Language::Cpp, &Options::default());
+        let paths = files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths, [PathBuf::from("nn.h"), PathBuf::from("nn.hpp")]);
+        let hpp = &files[1].contents;
+        assert!(hpp.contains("#include \"nn.h\"\n"));
+        assert!(hpp.contains("namespace nn {\n"));
+        assert!(hpp.contains("using tensor_t = ::nn_tensor_t;\n"));
+        assert!(hpp.contains("class errno_error : public std::exception {"));
+        assert!(hpp.contains("        case NN_ERRNO_INVAL: return \"inval\";\n"));
+        assert!(hpp.contains("class graph {\n"));
+        assert!(hpp.contains("    graph(graph &&) noexcept = default;\n"));
+        assert!(hpp.contains(
+            "inline graph load(span<const uint8_t> builder, const tensor_t &tensor) {\n    \
+             graph_t retptr0;\n    \
+             auto ret = ::nn_load(builder.data(), builder.size(), &tensor, &retptr0);\n    \
+             if (ret != 0) {\n        throw errno_error(ret);\n    }\n    \
+             return graph(retptr0);\n}"
+        ));
+        assert!(!hpp.contains("class [[nodiscard]] expected"));
+
+        let options = Options {
+            namespace: Some("nn_api_".to_string()),
+            cpp_errors: CppErrors::Expected,
+            ..Options::default()
+        };
+        let hpp = generate_one(Language::Cpp, &options, "nn.hpp");
+        assert!(hpp.contains("namespace nn_api {\n"));
+        assert!(hpp.contains("using errno_t = ::nn_api_errno_t;\n"));
+        assert!(hpp.contains("class [[nodiscard]] expected<void, E> {"));
+        assert!(hpp.contains(
+            "inline expected<graph, errno_t> load(span<const uint8_t> builder, const tensor_t &tensor) {"
+        ));
+        assert!(hpp.contains("        return expected<graph, errno_t>::failure(ret);\n"));
+        assert!(!hpp.contains("errno_error"));
+    }
+
+    #[test]
+    fn drop_handles_cpp() {
+        let doc = crate::parse(
+            "(typename $graph (handle))
+             (module $nn
+                (@interface func (export \"drop_graph\") (param $graph $graph))
+                (@interface func (export \"name\") (param $graph $graph) (param $name string)))",
+        )
+        .unwrap();
+        let hpp = generate(&doc, Language::Cpp, &Options::default())
+            .into_iter()
+            .find(|f| f.path == Path::new("nn.hpp"))
+            .unwrap()
+            .contents;
+        assert!(hpp.contains("            (void)::nn_drop_graph(raw_);\n"));
+        assert!(hpp.contains("    ~graph() { reset(); }\n"));
+        // Params are renamed rather than shadow the class of their type.
+        assert!(hpp.contains(
+            "inline void name(const graph &graph_, std::string_view name) {\n    \
+             ::nn_name(graph_.get(), name.data(), name.size());\n}"
+        ));
+        assert!(!hpp.contains("inline void drop_graph"));
+        assert_eq!("expected".parse(), Ok(CppErrors::Expected));
+        assert!("none".parse::<CppErrors>().is_err());
+    }
+
+    #[test]
+    fn assemblyscript() {
+        let options = Options {
+            namespace: Some("nn_api".to_string()),
+            ..Options::default()
+        };
+        let ts = generate_one(Language::AssemblyScript, &options, "nn.ts");
+        assert!(ts.contains("export namespace nn_api {"));
+        assert!(ts.contains("    export const INVAL: errno = 1;"));
+        assert!(ts.contains(
+            "  export class tensor {\n    dimensions: usize;\n    dimensions_len: usize;"
+        ));
+        assert!(ts.contains("    @external(\"nn\", \"load\")"));
+        assert!(ts.contains(
+            "export declare function load(builder: usize, builder_len: usize, tensor: usize, retptr0: usize): errno;"
+        ));
+    }
+
+    #[test]
+    fn tensor_convert() {
+        let lib = |types: &str, language: Language| {
+            let doc = crate::parse(&format!(
+                "(typename $tensor_type (enum (@witx tag u8) {}))",
+                types
+            ))
+            .unwrap();
+            generate(&doc, language, &Options::default())
+                .into_iter()
+                .find(|f| f.path == Path::new("src/lib.rs"))
+                .unwrap()
+                .contents
+        };
+        for language in [Language::RustGuest, Language::WasmtimeHost] {
+            let lib = lib("$f16 $f32 $u8", language);
+            assert!(lib.contains("pub mod tensor_convert {"), "{}", language);
+            assert!(lib.contains("pub fn f32_to_f16(value: f32) -> u16 {"));
+            assert!(lib.contains(
+                "pub fn quantize_u8_data(data: &[u8], scale: f32, zero_point: u8, out: &mut [u8]) -> Option<usize> {"
+            ));
+        }
+
+        // Without `f16` there's only quantization, and without `f32` there's
+        // nothing to convert.
+        let lib_u8 = lib("$f32 $u8", Language::WasmtimeHost);
+        assert!(lib_u8.contains("pub fn dequantize_u8(q: u8, scale: f32, zero_point: u8) -> f32 {"));
+        assert!(!lib_u8.contains("f16_to_f32"));
+        assert!(!lib("$f16 $u8", Language::RustGuest).contains("tensor_convert"));
+    }
+
+    #[test]
+    fn webnn() {
+        let js = generate_one(Language::WebNN, &Options::default(), "nn.js");
+        assert!(js.contains("const cases_errno = [\"success\", \"inval\"];"));
+        assert!(js.contains(
+            "    return { dimensions: read_dims(mem, offset), type: mem.view.getUint8(offset + 8), \
+             data: mem.readBytes(mem.view.getUint32(offset + 12, true), mem.view.getUint32(offset + 16, true)) };"
+        ));
+        assert!(js.contains("\"load\": suspending(async (arg0, arg1, arg2, arg3) => {"));
+        assert!(
+            js.contains("ret2 = { tag: \"ok\", value: await backend.load(mem, param0, param1) };")
+        );
+        assert!(js.contains("write_graph(mem, arg3 >>> 0, payload3);"));
+        // `WebNNBackend` implements wasi-nn, which the document is missing
+        // most of.
+        assert!(!js.contains("class WebNNBackend"));
+    }
+
+    #[test]
+    fn kotlin() {
+        let kt = generate_one(Language::Kotlin, &Options::default(), "nn.kt");
+        assert!(kt.contains("package nn\n"));
+        assert!(kt.contains("    INVAL(1, \"inval\"),\n"));
+        assert!(kt.contains(
+            "data class Tensor(\n    val dimensions: Dims,\n    val type: UByte,\n    val data: ByteArray,\n)"
+        ));
+        assert!(kt.contains("@JvmInline\nvalue class Graph(val handle: UInt)"));
+        assert!(kt.contains(
+            "internal fun readTensor(mem: GuestMemory, offset: Int): Tensor =\n    \
+             Tensor(readDims(mem, offset), mem.getU8(offset + 8), \
+             mem.readBytes(mem.getU32(offset + 12).toInt(), mem.getU32(offset + 16).toInt()))"
+        ));
+        assert!(kt.contains(
+            "    fun load(mem: GuestMemory, builder: ByteArray, tensor: Tensor): Graph\n"
+        ));
+        assert!(kt.contains("fun nnFunctions(impl: Nn): List<HostFunction> = listOf("));
+        assert!(kt.contains("            ret2Err = e.error as Errno\n"));
+        assert!(kt.contains("            writeGraph(mem, args[3].toInt(), payload3)\n"));
+
+        let options = Options {
+            namespace: Some("org.example.nn".to_string()),
+            ..Options::default()
+        };
+        let kt = generate_one(Language::Kotlin, &options, "nn.kt");
+        assert!(kt.contains("package org.example.nn\n"));
+    }
+
+    #[test]
+    fn swift() {
+        let swift = generate_one(Language::Swift, &Options::default(), "nn.swift");
+        assert!(swift.contains("protocol WitxLayout {"));
+        assert!(swift
+            .contains("struct Errno: RawRepresentable, Hashable {\n    var rawValue: UInt16\n"));
+        assert!(swift.contains("    static let inval = Errno(rawValue: 1)\n"));
+        assert!(swift.contains("typealias Dims = (UnsafeRawPointer?, UInt)\n"));
+        assert!(swift.contains(
+            "extension Tensor: WitxLayout {\n    static let witxSize = 20\n    static let witxAlignment = 4\n"
+        ));
+        assert!(swift.contains(
+            "        Tensor(dimensions: (pointer.load(as: UnsafeRawPointer?.self), \
+             pointer.load(fromByteOffset: 4, as: UInt.self)), \
+             type: pointer.load(fromByteOffset: 8, as: UInt8.self), \
+             data: (pointer.load(fromByteOffset: 12, as: UnsafeRawPointer?.self), \
+             pointer.load(fromByteOffset: 16, as: UInt.self)))\n"
+        ));
+        assert!(swift.contains(
+            "        pointer.storeBytes(of: self.data.1, toByteOffset: 16, as: UInt.self)\n"
+        ));
+        assert!(swift.contains(
+            "@_extern(wasm, module: \"nn\", name: \"load\")\n\
+             func nnLoad(_ builder: UnsafeRawPointer?, _ builderLen: UInt, \
+             _ tensor: UnsafeRawPointer, _ retptr0: UnsafeMutableRawPointer) -> UInt16\n"
+        ));
+    }
+
+    #[test]
+    fn swift_variants() {
+        let doc = crate::parse(
+            "(typename $value (variant (case $none) (case $num u64)))
+             (typename $mode (flags (@witx repr u8) $read $write))",
+        )
+        .unwrap();
+        let swift = generate(&doc, Language::Swift, &Options::default()).remove(0);
+        let swift = swift.contents;
+        assert!(swift.contains("enum Value {\n    case none\n    case num(UInt64)\n}"));
+        assert!(swift.contains(
+            "        case 1: return .num(pointer.load(fromByteOffset: 8, as: UInt64.self))\n"
+        ));
+        assert!(swift.contains(
+            "        case .num(let payload):\n            \
+             pointer.storeBytes(of: 1, as: UInt32.self)\n            \
+             pointer.storeBytes(of: payload, toByteOffset: 8, as: UInt64.self)\n"
+        ));
+        assert!(swift.contains("struct Mode: OptionSet {"));
+        assert!(swift.contains("    static let write = Mode(rawValue: 1 << 1)\n"));
+    }
+
+    #[test]
+    fn grpc() {
+        let options = Options {
+            namespace: Some("nn.v1".to_string()),
+            ..Options::default()
+        };
+        let proto = generate_one(Language::Grpc, &options, "proto/nn.proto");
+        assert!(proto.contains("package nn.v1;"));
+        assert!(proto.contains("enum Errno {\n    ERRNO_SUCCESS = 0;\n    ERRNO_INVAL = 1;\n}"));
+        assert!(proto.contains(
+            "message Tensor {\n    repeated uint32 dimensions = 1;\n    uint32 type = 2;\n    bytes data = 3;\n}"
+        ));
+        assert!(proto.contains(
+            "message LoadResponse {\n    oneof error {\n        uint32 ok = 1;\n        Errno err = 2;\n    }\n}"
+        ));
+        assert!(
+            proto.contains("service Nn {\n    rpc Load(LoadRequest) returns (LoadResponse);\n}")
+        );
+        let lib = generate_one(Language::Grpc, &options, "src/lib.rs");
+        assert!(lib.contains("tonic::include_proto!(\"nn.v1\");"));
+        assert!(lib.contains(".add_service(NnServer::new(nn))"));
+        let build = generate_one(Language::Grpc, &options, "build.rs");
+        assert!(build.contains("compile_protos(\"proto/nn.proto\")"));
+    }
+
+    #[test]
+    fn protobuf() {
+        let proto = generate_one(Language::Protobuf, &Options::default(), "nn.proto");
+        assert!(proto.contains("package nn;"));
+        assert!(proto.contains("enum Errno {\n    ERRNO_SUCCESS = 0;\n    ERRNO_INVAL = 1;\n}"));
+        assert!(proto.contains("message Tensor {\n    repeated uint32 dimensions = 1;\n"));
+        assert!(!proto.contains("service"));
+        assert!(!proto.contains("LoadRequest"));
+    }
+
+    #[test]
+    fn flatbuffers() {
+        let options = Options {
+            namespace: Some("nn.v1".to_string()),
+            ..Options::default()
+        };
+        let fbs = generate_one(Language::FlatBuffers, &options, "nn.fbs");
+        assert!(fbs.contains("namespace nn.v1;"));
+        assert!(fbs.contains("enum Errno : ushort {\n    Success,\n    Inval,\n}"));
+        assert!(fbs.contains(
+            "table Tensor {\n    dimensions: [uint];\n    type: ubyte;\n    data: [ubyte];\n}"
+        ));
+
+        let doc = crate::parse(
+            "(typename $graph (handle))
+             (typename $mode (flags (@witx repr u8) $read $write))
+             (typename $value
+                (variant (case $none) (case $num u64) (case $graph $graph)))
+             (typename $batch (record (field $rows (list (list f32))) (field $mode $mode)))",
+        )
+        .unwrap();
+        let fbs = &generate(&doc, Language::FlatBuffers, &Options::default())[0].contents;
+        assert!(fbs.contains("enum Mode : ubyte (bit_flags) {\n    Read,\n    Write,\n}"));
+        assert!(fbs.contains("table ValueNone {\n}\n"));
+        assert!(fbs.contains("table ValueNum {\n    value: ulong;\n}\n"));
+        assert!(fbs.contains("table ValueGraph {\n    value: uint;\n}\n"));
+        assert!(fbs.contains(
+            "union ValueValue {\n    ValueNone,\n    ValueNum,\n    ValueGraph,\n}\n\n\
+             table Value {\n    value: ValueValue;\n}"
+        ));
+        assert!(fbs.contains("table Batch {\n    rows: [RepeatedFloat];\n    mode: Mode;\n}"));
+        assert!(fbs.contains("table RepeatedFloat {\n    items: [float];\n}"));
+    }
+
+    /// The parts of wasi-nn the backend stubs implement.
+    fn wasi_nn_doc() -> Document {
+        crate::parse(
+            "(typename $nn_errno (enum (@witx tag u16) $success $invalid_argument))
+             (typename $tensor_type (enum (@witx tag u8) $f32 $q2))
+             (typename $tensor
+                (record
+                    (field $dimensions (list u32))
+                    (field $type $tensor_type)
+                    (field $data (list u8))))
+             (typename $graph_builder_array (list (list u8)))
+             (typename $graph_encoding (enum (@witx tag u8) $openvino $onnx))
+             (typename $execution_target (enum (@witx tag u8) $cpu $fpga))
+             (typename $graph (handle))
+             (typename $graph_execution_context (handle))
+             (module $nn
+                (@interface func (export \"load\")
+                    (param $builder $graph_builder_array)
+                    (param $encoding $graph_encoding)
+                    (param $target $execution_target)
+                    (result $error (expected $graph (error $nn_errno))))
+                (@interface func (export \"compute\")
+                    (param $context $graph_execution_context)
+                    (result $error (expected (error $nn_errno))))
+                (@interface func (export \"reset\")))",
+        )
+        .unwrap()
+    }
+
+    fn generate_stub(language: Language) -> Vec<OutputFile> {
+        generate(&wasi_nn_doc(), language, &Options::default())
+    }
+
+    #[test]
+    fn openvino_stub() {
+        let files = generate_stub(Language::OpenVinoStub);
+        let lib = &files
+            .iter()
+            .find(|f| f.path == Path::new("src/lib.rs"))
+            .unwrap()
+            .contents;
+        assert!(files.iter().any(|f| f.path == Path::new("src/bindings.rs")));
+        assert!(lib.contains("impl Nn for OpenVino {"));
+        assert!(lib.contains("        ExecutionTarget::Fpga => None,"));
+        assert!(lib.contains("        TensorType::Q2 => None,"));
+        assert!(lib.contains("            [xml, weights] => (xml.clone(), weights.clone()),"));
+        assert!(lib.contains("        // TODO(openvino): read the model"));
+        assert!(lib.contains("        todo!(\"compute with OpenVINO\")"));
+        assert!(lib.contains("        todo!(\"implement `reset` with OpenVINO\")"));
+    }
+
+    #[test]
+    fn onnx_stub() {
+        let files = generate_stub(Language::OnnxStub);
+        let lib = &files
+            .iter()
+            .find(|f| f.path == Path::new("src/lib.rs"))
+            .unwrap()
+            .contents;
+        assert!(lib.contains("impl Nn for OnnxRuntime {"));
+        assert!(lib.contains("        if encoding != GraphEncoding::Onnx {"));
+        assert!(lib.contains("            [model] => model.clone(),"));
+        assert!(lib.contains(
+            "pub const ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT: ONNXTensorElementDataType = 1;"
+        ));
+        assert!(
+            lib.contains("        TensorType::F32 => Some(ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT),")
+        );
+        assert!(
+            lib.contains("        ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT => Some(TensorType::F32),")
+        );
+        assert!(lib.contains("        ExecutionTarget::Cpu => Some(\"CPUExecutionProvider\"),"));
+    }
+
+    #[test]
+    fn benchmark() {
+        let nn = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../phases/ephemeral/witx/wasi_ephemeral_nn.witx");
+        let options = Options {
+            crate_name: Some("nn-bench".to_string()),
+            ..Options::default()
+        };
+        let files = generate(&crate::load(&[nn]).unwrap(), Language::Benchmark, &options);
+        let file = |path: &str| {
+            &files
+                .iter()
+                .find(|f| f.path == Path::new(path))
+                .unwrap_or_else(|| panic!("benchmark generates {}", path))
+                .contents
+        };
+        assert!(file("Cargo.toml").contains("[[bench]]\nname = \"inference\"\nharness = false\n"));
+        let lib = file("src/lib.rs");
+        assert!(lib.contains("pub const MODULE: &str = \"wasi_ephemeral_nn\";"));
+        assert!(
+            lib.contains("const GUEST: &str = include_str!(\"../guests/wasi_ephemeral_nn.wat\");")
+        );
+        assert!(lib.contains("pub const TARGETS: &[&str] = &[\"cpu\", \"gpu\", \"tpu\"];"));
+        assert!(lib.contains(
+            "pub const TENSOR_TYPES: &[(&str, usize)] = &[(\"f16\", 16), (\"f32\", 32), (\"u8\", 8), (\"i32\", 32)];"
+        ));
+        assert!(lib.contains("const TENSOR_SIZE: usize = 20;\n"));
+        assert!(lib.contains("const TENSOR_TYPE: usize = 8;\nconst TENSOR_TYPE_SIZE: usize = 1;\n"));
+        let wat = file("guests/wasi_ephemeral_nn.wat");
+        assert!(wat.contains(
+            "(import \"wasi_ephemeral_nn\" \"set_input\" (func $set_input (param i32 i32 i32) (result i32)))"
+        ));
+        assert!(wat.contains(
+            "(func (export \"compute\") (param i32) (result i32)\n    (call $compute (local.get 0)))"
+        ));
+        assert!(
+            file("benches/inference.rs").contains("nn_bench::bench(c, &linker, store, &config)")
+        );
+
+        // Without wasi-nn's functions there's nothing to benchmark.
+        assert!(generate(&doc(), Language::Benchmark, &options).is_empty());
+    }
+}
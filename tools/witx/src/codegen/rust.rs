@@ -0,0 +1,366 @@
+//! Naming shared by the Rust backends.
+
+use super::Options;
+use crate::tensor::ElementType;
+use crate::{Id, NamedType, Type, TypeRef};
+use heck::{CamelCase, ShoutySnakeCase, SnakeCase};
+use std::fmt::Write;
+
+/// The Rust name of the witx type `name`.
+pub(super) fn type_name(name: &Id) -> String {
+    name.as_str().to_camel_case()
+}
+
+/// The prefix of constants derived from `name`.
+pub(super) fn const_name(name: &Id) -> String {
+    name.as_str().to_shouty_snake_case()
+}
+
+/// A Rust identifier for a witx field, case, param or function name.
+pub(super) fn ident(name: &str) -> String {
+    let name = name.to_snake_case();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return format!("f{}", name);
+    }
+    match name.as_str() {
+        "self" | "super" | "crate" => format!("{}_", name),
+        "as" | "async" | "await" | "break" | "const" | "continue" | "dyn" | "else" | "enum"
+        | "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match"
+        | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "static" | "struct" | "trait"
+        | "true" | "type" | "unsafe" | "use" | "where" | "while" | "abstract" | "become"
+        | "box" | "do" | "final" | "macro" | "override" | "priv" | "try" | "typeof" | "unsized"
+        | "virtual" | "yield" => format!("r#{}", name),
+        _ => name,
+    }
+}
+
+/// The Rust name of the `enum` variant for a witx case.
+pub(super) fn variant_name(name: &Id) -> String {
+    let name = name.as_str().to_camel_case();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// A tuple of `members`, with the trailing comma of a 1-tuple.
+pub(super) fn tuple(members: &[String]) -> String {
+    if members.len() == 1 {
+        format!("({},)", members[0])
+    } else {
+        format!("({})", members.join(", "))
+    }
+}
+
+/// The `#[derive]` and outer attributes of the Rust definition of `name`:
+/// the `derives` the backend needs, then the extra ones in `options`.
+pub(super) fn attributes(options: &Options, name: &Id, derives: &[&str], indent: &str) -> String {
+    let extra = std::iter::once(&options.type_attributes)
+        .chain(options.named_type_attributes.get(name.as_str()));
+    let mut all = derives.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+    let mut attributes = Vec::new();
+    for e in extra {
+        for d in e.derives.iter() {
+            if !all.contains(d) {
+                all.push(d.clone());
+            }
+        }
+        attributes.extend(e.attributes.iter());
+    }
+    let mut out = String::new();
+    if !all.is_empty() {
+        writeln!(out, "{}#[derive({})]", indent, all.join(", ")).unwrap();
+    }
+    for a in attributes {
+        writeln!(out, "{}#[{}]", indent, a).unwrap();
+    }
+    out
+}
+
+/// Whether `tref` contains a pointer, which Rust represents with a raw or
+/// guest pointer.
+pub(super) fn contains_pointer(tref: &TypeRef) -> bool {
+    match &**tref.type_() {
+        Type::Pointer(_) | Type::ConstPointer(_) => true,
+        Type::List(t) => contains_pointer(t),
+        Type::Record(r) => r.members.iter().any(|m| contains_pointer(&m.tref)),
+        Type::Variant(v) => v
+            .cases
+            .iter()
+            .filter_map(|c| c.tref.as_ref())
+            .any(contains_pointer),
+        Type::Handle(_) | Type::Builtin(_) => false,
+    }
+}
+
+/// The serde traits to derive for `tref`, if `options` asks for them and it
+/// can have them.
+pub(super) fn serde_derives(options: &Options, tref: &TypeRef) -> &'static [&'static str] {
+    if options.serde && !contains_pointer(tref) {
+        &["serde::Serialize", "serde::Deserialize"]
+    } else {
+        &[]
+    }
+}
+
+/// `#[serde(rename)]` for an item named `rust` in Rust and `witx` in witx,
+/// so serde uses the witx name, if they differ.
+pub(super) fn serde_rename(witx: &str, rust: &str, indent: &str) -> String {
+    if rust.trim_start_matches("r#") == witx {
+        String::new()
+    } else {
+        format!("{}#[serde(rename = \"{}\")]\n", indent, witx)
+    }
+}
+
+/// Lookups between the flags of the flags type `nt` and their witx names.
+/// Flags are aliases of integers, so these are free functions and a
+/// constant rather than methods.
+pub(super) fn flags_names_impl(out: &mut String, nt: &NamedType, indent: &str) {
+    let members = match &**nt.type_() {
+        Type::Record(r) => &r.members,
+        _ => unreachable!("flags are records"),
+    };
+    let name = type_name(&nt.name);
+    let prefix = const_name(&nt.name);
+    let func = ident(nt.name.as_str());
+    let mut src = String::new();
+    src.push_str("/// Every flag, with its witx name.\n");
+    writeln!(
+        src,
+        "pub const {}_FLAGS: [(&str, {}); {}] = [",
+        prefix,
+        name,
+        members.len()
+    )
+    .unwrap();
+    for m in members.iter() {
+        writeln!(
+            src,
+            "    (\"{}\", {}_{}),",
+            m.name.as_str(),
+            prefix,
+            const_name(&m.name)
+        )
+        .unwrap();
+    }
+    src.push_str("];\n");
+    src.push_str("/// The witx names of the flags set in `flags`.\n");
+    writeln!(
+        src,
+        "pub fn {}_names(flags: {}) -> impl Iterator<Item = &'static str> {{",
+        func, name
+    )
+    .unwrap();
+    writeln!(src, "    {}_FLAGS", prefix).unwrap();
+    src.push_str("        .iter()\n");
+    src.push_str("        .filter(move |(_, flag)| flags & flag != 0)\n");
+    src.push_str("        .map(|(name, _)| *name)\n");
+    src.push_str("}\n");
+    src.push_str("/// The flag named `name` in witx.\n");
+    writeln!(
+        src,
+        "pub fn {}_from_str(name: &str) -> Option<{}> {{",
+        func, name
+    )
+    .unwrap();
+    writeln!(src, "    {}_FLAGS", prefix).unwrap();
+    src.push_str("        .iter()\n");
+    src.push_str("        .find(|(n, _)| *n == name)\n");
+    src.push_str("        .map(|(_, flag)| *flag)\n");
+    src.push_str("}\n");
+    for line in src.lines() {
+        writeln!(out, "{}{}", indent, line).unwrap();
+    }
+}
+
+/// Methods giving the size of tensor data, if `nt` is the enum of tensor
+/// element types. Both backends represent it with a `raw` method.
+pub(super) fn tensor_type_impl(out: &mut String, nt: &NamedType) {
+    let types = match ElementType::cases_of(nt) {
+        Some(types) => types,
+        None => return,
+    };
+    writeln!(out, "impl {} {{", type_name(&nt.name)).unwrap();
+    out.push_str("    /// Size of an element in bits, or `None` if the type isn't known.\n");
+    out.push_str("    pub fn element_bits(&self) -> Option<u32> {\n");
+    out.push_str("        match self.raw() {\n");
+    for (i, ty) in types.iter().enumerate() {
+        writeln!(out, "            {} => Some({}),", i, ty.bits()).unwrap();
+    }
+    out.push_str("            _ => None,\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str(
+        "    /// Bytes of data in a tensor of this type with `dimensions`, or `None`\n    \
+         /// if the type isn't known or the size overflows. Packed elements are\n    \
+         /// padded to a whole byte.\n",
+    );
+    out.push_str("    pub fn buffer_size(&self, dimensions: &[u32]) -> Option<usize> {\n");
+    out.push_str("        let mut bits = self.element_bits()? as usize;\n");
+    out.push_str("        for d in dimensions {\n");
+    out.push_str("            bits = bits.checked_mul(*d as usize)?;\n");
+    out.push_str("        }\n");
+    out.push_str("        Some(bits / 8 + (bits % 8 != 0) as usize)\n");
+    out.push_str("    }\n");
+    out.push_str(
+        "    /// Whether `len` bytes is the size of the data of a tensor of this type\n    \
+         /// with `dimensions`.\n",
+    );
+    out.push_str("    pub fn is_valid_buffer(&self, dimensions: &[u32], len: usize) -> bool {\n");
+    out.push_str("        self.buffer_size(dimensions) == Some(len)\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+}
+
+/// A `tensor_convert` module converting tensor data between the element
+/// types of `nt`, if it's the enum of tensor element types and has `f32` and
+/// `f16` or `u8` elements. Everything in it only uses `core`.
+pub(super) fn tensor_convert_mod(out: &mut String, nt: &NamedType) {
+    let types = match ElementType::cases_of(nt) {
+        Some(types) if types.contains(&ElementType::F32) => types,
+        _ => return,
+    };
+    let (f16, u8) = (
+        types.contains(&ElementType::F16),
+        types.contains(&ElementType::U8),
+    );
+    if !f16 && !u8 {
+        return;
+    }
+    writeln!(
+        out,
+        "/// Conversions of tensor data between the element types of `{}`.",
+        type_name(&nt.name)
+    )
+    .unwrap();
+    out.push_str(TENSOR_CONVERT);
+    if f16 {
+        out.push_str(TENSOR_CONVERT_F16);
+    }
+    if u8 {
+        out.push_str(TENSOR_CONVERT_U8);
+    }
+    out.push_str("}\n");
+}
+
+const TENSOR_CONVERT: &str = "\
+/// Data is little-endian, as in tensors. Functions converting data write it
+/// to `out`, and return the number of bytes written, or `None` if `data`
+/// isn't a whole number of elements or `out` is too small for them.
+pub mod tensor_convert {
+    fn map<const N: usize, const M: usize>(
+        data: &[u8],
+        out: &mut [u8],
+        f: impl Fn([u8; N]) -> [u8; M],
+    ) -> Option<usize> {
+        let len = data.len() / N * M;
+        if data.len() % N != 0 || out.len() < len {
+            return None;
+        }
+        for (from, to) in data.chunks_exact(N).zip(out.chunks_exact_mut(M)) {
+            let mut bytes = [0; N];
+            bytes.copy_from_slice(from);
+            to.copy_from_slice(&f(bytes));
+        }
+        Some(len)
+    }
+";
+
+const TENSOR_CONVERT_F16: &str = "
+    /// The bits of the `f16` nearest to `value`. Ties round to even, values
+    /// too large for an `f16` become infinities, and NaNs stay NaNs.
+    pub fn f32_to_f16(value: f32) -> u16 {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exp = ((bits >> 23) & 0xff) as i32;
+        let man = bits & 0x7f_ffff;
+        if exp == 0xff {
+            let nan = if man != 0 { 0x200 | (man >> 13) as u16 } else { 0 };
+            return sign | 0x7c00 | nan;
+        }
+        let exp = exp - 127 + 15;
+        if exp >= 0x1f {
+            return sign | 0x7c00;
+        }
+        // Subnormals keep fewer bits of the mantissa, with its implicit one.
+        let (man, shift, exp) = if exp <= 0 {
+            if exp < -10 {
+                return sign;
+            }
+            (man | 0x80_0000, (14 - exp) as u32, 0)
+        } else {
+            (man, 13, exp as u32)
+        };
+        let kept = (exp << 10) | (man >> shift);
+        let dropped = man & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        // Rounding up may carry into the exponent, up to infinity.
+        let up = dropped > half || (dropped == half && kept & 1 == 1);
+        sign | (kept + up as u32) as u16
+    }
+
+    /// The value of the `f16` with `bits`, which is exact.
+    pub fn f16_to_f32(bits: u16) -> f32 {
+        let sign = u32::from(bits >> 15) << 31;
+        let exp = u32::from((bits >> 10) & 0x1f);
+        let man = u32::from(bits & 0x3ff);
+        let bits = match (exp, man) {
+            (0, 0) => sign,
+            // Subnormal: normalize the mantissa.
+            (0, _) => {
+                let shift = man.leading_zeros() - 21;
+                sign | ((113 - shift) << 23) | (((man << shift) & 0x3ff) << 13)
+            }
+            (0x1f, _) => sign | 0x7f80_0000 | (man << 13),
+            _ => sign | ((exp + 112) << 23) | (man << 13),
+        };
+        f32::from_bits(bits)
+    }
+
+    /// `f32` data as `f16` data.
+    pub fn f32_to_f16_data(data: &[u8], out: &mut [u8]) -> Option<usize> {
+        map(data, out, |b: [u8; 4]| f32_to_f16(f32::from_le_bytes(b)).to_le_bytes())
+    }
+
+    /// `f16` data as `f32` data.
+    pub fn f16_to_f32_data(data: &[u8], out: &mut [u8]) -> Option<usize> {
+        map(data, out, |b: [u8; 2]| f16_to_f32(u16::from_le_bytes(b)).to_le_bytes())
+    }
+";
+
+const TENSOR_CONVERT_U8: &str = "
+    /// `value` quantized to a `u8` with `scale` and `zero_point`: the nearest
+    /// integer to `value / scale`, plus `zero_point`, saturated. Ties round
+    /// away from zero, and NaNs become `zero_point`.
+    pub fn quantize_u8(value: f32, scale: f32, zero_point: u8) -> u8 {
+        let x = value / scale;
+        // `as` truncates and saturates, and the fraction left is exact.
+        let trunc = x as i32;
+        let frac = x - trunc as f32;
+        let rounded = if frac >= 0.5 {
+            trunc.saturating_add(1)
+        } else if frac <= -0.5 {
+            trunc.saturating_sub(1)
+        } else {
+            trunc
+        };
+        rounded.saturating_add(i32::from(zero_point)).clamp(0, 255) as u8
+    }
+
+    /// The value `q` was quantized from with `scale` and `zero_point`.
+    pub fn dequantize_u8(q: u8, scale: f32, zero_point: u8) -> f32 {
+        (i32::from(q) - i32::from(zero_point)) as f32 * scale
+    }
+
+    /// `f32` data quantized to `u8` data, as by `quantize_u8`.
+    pub fn quantize_u8_data(data: &[u8], scale: f32, zero_point: u8, out: &mut [u8]) -> Option<usize> {
+        map(data, out, |b: [u8; 4]| [quantize_u8(f32::from_le_bytes(b), scale, zero_point)])
+    }
+
+    /// `u8` data dequantized to `f32` data, as by `dequantize_u8`.
+    pub fn dequantize_u8_data(data: &[u8], scale: f32, zero_point: u8, out: &mut [u8]) -> Option<usize> {
+        map(data, out, |b: [u8; 1]| dequantize_u8(b[0], scale, zero_point).to_le_bytes())
+    }
+";
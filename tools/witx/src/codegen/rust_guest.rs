@@ -0,0 +1,1027 @@
+//! Rust bindings for wasm guests.
+//!
+//! The generated crate has two layers. The `raw` module is the exact ABI:
+//! every named type gets a Rust definition with the same memory layout as
+//! its witx counterpart, and every module gets a Rust module of `unsafe`
+//! extern functions taking core wasm values. On top of it, the crate root
+//! re-exports the raw types, wraps each handle in an owned type, and gives
+//! every module wrappers taking and returning Rust values: slices, `Result`s
+//! and owned handles. A handle `$h` is released when dropped if some module
+//! has a `drop_h` function taking it, which gets no wrapper of its own.
+//! Wrappers are only `unsafe` when they pass raw pointers. Enums returned as
+//! errors are replaced by error types implementing `Display` with the docs of
+//! each case, converting to and from the raw enum. On wasm32, the layouts of
+//! the raw types are checked against witx's at compile time.
+
+use super::rust::{
+    attributes, const_name, contains_pointer, flags_names_impl, ident, serde_derives, serde_rename,
+    tensor_convert_mod, tensor_type_impl, tuple, type_name, variant_name,
+};
+use super::{
+    doc_comment, drops_handle, error_enums, error_message, is_owned_handle, lowered_signature,
+    Options, OutputFile,
+};
+use crate::{
+    Bindgen, BuiltinType, Document, Id, Instruction, IntRepr, InterfaceFunc, Layout, NamedType,
+    Type, TypeRef,
+};
+use std::fmt::Write;
+use std::mem;
+
+pub(super) fn generate(doc: &Document, options: &Options) -> Vec<OutputFile> {
+    let crate_name = options.crate_name.as_deref().unwrap_or("bindings");
+    let mut manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[dependencies]\n",
+        crate_name
+    );
+    if options.serde {
+        let default_features = if options.no_std {
+            ", default-features = false"
+        } else {
+            ""
+        };
+        writeln!(
+            manifest,
+            "serde = {{ version = \"1.0\"{}, features = [\"derive\"] }}",
+            default_features
+        )
+        .unwrap();
+    }
+    vec![
+        OutputFile::new("Cargo.toml", manifest),
+        OutputFile::new("src/lib.rs", render(doc, options)),
+    ]
+}
+
+fn render(doc: &Document, options: &Options) -> String {
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n");
+    // Everything generated below only uses `core`, so guests which don't
+    // want `std` just need the attribute.
+    if options.no_std {
+        out.push_str("#![no_std]\n");
+    }
+
+    out.push_str("\n/// The exact ABI of the imports: types with the memory layout of their\n");
+    out.push_str("/// witx definitions, and `unsafe` extern functions taking core wasm values.\n");
+    out.push_str("pub mod raw {\n");
+    for line in render_raw(doc, options).lines() {
+        if !line.is_empty() {
+            out.push_str("    ");
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out.push_str("}\n");
+    if options.raw_only {
+        return out;
+    }
+
+    out.push_str("\npub use raw::*;\n");
+    for nt in doc.typenames() {
+        if is_owned_handle(&nt) {
+            out.push('\n');
+            define_owned_handle(&mut out, doc, &nt);
+        }
+    }
+    let errors = error_enums(doc);
+    for nt in errors.iter() {
+        out.push('\n');
+        define_error(&mut out, options, nt);
+    }
+    let errors = errors.iter().map(|nt| nt.name.clone()).collect::<Vec<_>>();
+    for m in doc.modules() {
+        out.push('\n');
+        out.push_str(&doc_comment(&m.docs, "///", ""));
+        writeln!(out, "pub mod {} {{", ident(m.name.as_str())).unwrap();
+        out.push_str("    #[allow(unused_imports)]\n");
+        out.push_str("    use super::*;\n");
+        for f in m.funcs() {
+            if drops_handle(&f).is_some() {
+                continue;
+            }
+            out.push('\n');
+            define_wrapper(&mut out, &m.name, &f, &errors);
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+/// The contents of the `raw` module.
+fn render_raw(doc: &Document, options: &Options) -> String {
+    let mut out = String::new();
+    for (i, nt) in doc.typenames().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        define_type(&mut out, options, &nt);
+    }
+    layout_assertions(&mut out, doc);
+    for c in doc.constants() {
+        out.push('\n');
+        out.push_str(&doc_comment(&c.docs, "///", ""));
+        let ty = doc.typename(&c.ty).expect("constant type is defined");
+        let value = match &**ty.type_() {
+            Type::Variant(v) if v.is_enum() => format!("{}({})", type_name(&c.ty), c.value),
+            _ => c.value.to_string(),
+        };
+        writeln!(
+            out,
+            "pub const {}_{}: {} = {};",
+            const_name(&c.ty),
+            const_name(&c.name),
+            type_name(&c.ty),
+            value
+        )
+        .unwrap();
+    }
+    for m in doc.modules() {
+        out.push('\n');
+        out.push_str(&doc_comment(&m.docs, "///", ""));
+        writeln!(out, "pub mod {} {{", ident(m.name.as_str())).unwrap();
+        out.push_str("    #[allow(unused_imports)]\n");
+        out.push_str("    use super::*;\n");
+        writeln!(
+            out,
+            "    #[link(wasm_import_module = \"{}\")]",
+            m.name.as_str()
+        )
+        .unwrap();
+        out.push_str("    extern \"C\" {\n");
+        for f in m.funcs() {
+            out.push_str(&doc_comment(&f.docs, "///", "        "));
+            let (params, results) = lowered_signature(&f);
+            let params = params
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", ident(name), ty))
+                .collect::<Vec<_>>();
+            let ret = if f.noreturn {
+                " -> !".to_string()
+            } else {
+                match results.first() {
+                    Some(ty) => format!(" -> {}", ty),
+                    None => String::new(),
+                }
+            };
+            writeln!(
+                out,
+                "        pub fn {}({}){};",
+                ident(f.name.as_str()),
+                params.join(", "),
+                ret
+            )
+            .unwrap();
+        }
+        out.push_str("    }\n");
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn define_owned_handle(out: &mut String, doc: &Document, nt: &NamedType) {
+    let name = type_name(&nt.name);
+    let drop = doc.modules().find_map(|m| {
+        m.funcs()
+            .find(|f| drops_handle(f).as_ref() == Some(&nt.name))
+            .map(|f| (m.name.clone(), f))
+    });
+    out.push_str(&doc_comment(&nt.docs, "///", ""));
+    out.push_str("#[repr(transparent)]\n#[derive(Debug, PartialEq, Eq, Hash)]\n");
+    writeln!(out, "pub struct {}(raw::{});", name, name).unwrap();
+    writeln!(out, "impl {} {{", name).unwrap();
+    out.push_str("    /// Take ownership of a raw handle.\n");
+    out.push_str("    ///\n");
+    out.push_str("    /// # Safety\n");
+    out.push_str("    ///\n");
+    out.push_str("    /// The handle must be valid and not owned by anything else.\n");
+    writeln!(
+        out,
+        "    pub unsafe fn from_raw(raw: raw::{}) -> Self {{",
+        name
+    )
+    .unwrap();
+    writeln!(out, "        {}(raw)", name).unwrap();
+    out.push_str("    }\n");
+    out.push_str("    /// The raw handle, which stays owned by `self`.\n");
+    writeln!(out, "    pub fn as_raw(&self) -> raw::{} {{", name).unwrap();
+    out.push_str("        self.0\n");
+    out.push_str("    }\n");
+    out.push_str("    /// Give up ownership of the handle without releasing it.\n");
+    writeln!(out, "    pub fn into_raw(self) -> raw::{} {{", name).unwrap();
+    if drop.is_some() {
+        out.push_str("        let raw = self.0;\n");
+        out.push_str("        core::mem::forget(self);\n");
+        out.push_str("        raw\n");
+    } else {
+        out.push_str("        self.0\n");
+    }
+    out.push_str("    }\n");
+    out.push_str("}\n");
+
+    if let Some((module, func)) = drop {
+        let (_, results) = lowered_signature(&func);
+        let call = format!(
+            "raw::{}::{}(self.0 as i32)",
+            ident(module.as_str()),
+            ident(func.name.as_str())
+        );
+        writeln!(out, "impl Drop for {} {{", name).unwrap();
+        out.push_str("    fn drop(&mut self) {\n");
+        if results.is_empty() {
+            writeln!(out, "        unsafe {{ {} }};", call).unwrap();
+        } else {
+            writeln!(out, "        let _ = unsafe {{ {} }};", call).unwrap();
+        }
+        out.push_str("    }\n");
+        out.push_str("}\n");
+    }
+}
+
+/// An idiomatic error type for the error enum `nt`, which wrappers return in
+/// place of the raw enum.
+fn define_error(out: &mut String, options: &Options, nt: &NamedType) {
+    let name = type_name(&nt.name);
+    let (repr, cases) = match &**nt.type_() {
+        Type::Variant(v) => (int_repr(v.tag_repr), &v.cases),
+        _ => unreachable!("error types are enums"),
+    };
+    out.push_str(&doc_comment(&nt.docs, "///", ""));
+    out.push_str(&attributes(
+        options,
+        &nt.name,
+        &["Copy", "Clone", "Debug", "Hash", "Eq", "PartialEq"],
+        "",
+    ));
+    writeln!(out, "pub enum {} {{", name).unwrap();
+    for c in cases.iter() {
+        out.push_str(&doc_comment(&c.docs, "///", "    "));
+        writeln!(out, "    {},", variant_name(&c.name)).unwrap();
+    }
+    out.push_str("    /// An error code these bindings don't know.\n");
+    writeln!(out, "    Unknown({}),", repr).unwrap();
+    out.push_str("}\n");
+
+    writeln!(out, "impl From<raw::{}> for {} {{", name, name).unwrap();
+    writeln!(out, "    fn from(e: raw::{}) -> Self {{", name).unwrap();
+    out.push_str("        match e.raw() {\n");
+    for (i, c) in cases.iter().enumerate() {
+        writeln!(
+            out,
+            "            {} => {}::{},",
+            i,
+            name,
+            variant_name(&c.name)
+        )
+        .unwrap();
+    }
+    writeln!(out, "            other => {}::Unknown(other),", name).unwrap();
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+
+    writeln!(out, "impl From<{}> for raw::{} {{", name, name).unwrap();
+    writeln!(out, "    fn from(e: {}) -> Self {{", name).unwrap();
+    out.push_str("        match e {\n");
+    for (i, c) in cases.iter().enumerate() {
+        writeln!(
+            out,
+            "            {}::{} => raw::{}({}),",
+            name,
+            variant_name(&c.name),
+            name,
+            i
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "            {}::Unknown(other) => raw::{}(other),",
+        name, name
+    )
+    .unwrap();
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+
+    writeln!(out, "impl core::fmt::Display for {} {{", name).unwrap();
+    out.push_str("    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {\n");
+    out.push_str("        match self {\n");
+    for c in cases.iter() {
+        writeln!(
+            out,
+            "            {}::{} => f.write_str({:?}),",
+            name,
+            variant_name(&c.name),
+            error_message(c)
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "            {}::Unknown(code) => write!(f, \"unknown error {{}}\", code),",
+        name
+    )
+    .unwrap();
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    if !options.no_std {
+        writeln!(out, "impl std::error::Error for {} {{}}", name).unwrap();
+    }
+}
+
+pub(super) fn builtin(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::Char => "char",
+        BuiltinType::U8 { .. } => "u8",
+        BuiltinType::U16 => "u16",
+        BuiltinType::U32 {
+            lang_ptr_size: false,
+        } => "u32",
+        BuiltinType::U32 {
+            lang_ptr_size: true,
+        } => "usize",
+        BuiltinType::U64 => "u64",
+        BuiltinType::S8 => "i8",
+        BuiltinType::S16 => "i16",
+        BuiltinType::S32 => "i32",
+        BuiltinType::S64 => "i64",
+        BuiltinType::F32 => "f32",
+        BuiltinType::F64 => "f64",
+    }
+}
+
+pub(super) fn int_repr(repr: IntRepr) -> &'static str {
+    builtin(repr.to_builtin())
+}
+
+/// Check the layout witx calculates for each type against the compiler's,
+/// so the build fails if they ever disagree. Layouts are of wasm32 memory,
+/// so they're only checked when compiling for it.
+fn layout_assertions(out: &mut String, doc: &Document) {
+    let types = doc
+        .typenames()
+        .filter(|nt| has_defined_layout(&nt.tref))
+        .collect::<Vec<_>>();
+    if types.is_empty() {
+        return;
+    }
+    out.push_str("\n#[cfg(target_arch = \"wasm32\")]\n");
+    out.push_str("const _: () = {\n");
+    out.push_str("    use core::mem::{align_of, size_of};\n");
+    for nt in types {
+        let ty = if needs_lifetime(&nt.tref) {
+            format!("{}<'static>", type_name(&nt.name))
+        } else {
+            type_name(&nt.name)
+        };
+        let layout = nt.mem_size_align();
+        writeln!(
+            out,
+            "    assert!(size_of::<{}>() == {} && align_of::<{}>() == {});",
+            ty, layout.size, ty, layout.align
+        )
+        .unwrap();
+    }
+    out.push_str("};\n");
+}
+
+/// Whether the Rust definition of `tref` borrows memory, and so takes a
+/// lifetime parameter.
+pub(super) fn needs_lifetime(tref: &TypeRef) -> bool {
+    match &**tref.type_() {
+        Type::List(_) => true,
+        Type::Pointer(t) | Type::ConstPointer(t) => needs_lifetime(t),
+        Type::Record(r) => r.members.iter().any(|m| needs_lifetime(&m.tref)),
+        Type::Variant(v) => v
+            .cases
+            .iter()
+            .filter_map(|c| c.tref.as_ref())
+            .any(needs_lifetime),
+        Type::Handle(_) | Type::Builtin(_) => false,
+    }
+}
+
+/// Whether the Rust definition of `tref` has a layout the compiler
+/// guarantees, unlike the tuples and `Result`s used for anonymous types.
+fn has_defined_layout(tref: &TypeRef) -> bool {
+    match &**tref.type_() {
+        Type::Record(r) => !r.is_tuple() && r.members.iter().all(|m| has_defined_layout(&m.tref)),
+        Type::Variant(v) if v.as_expected().is_some() => false,
+        Type::Variant(v) => v
+            .cases
+            .iter()
+            .filter_map(|c| c.tref.as_ref())
+            .all(has_defined_layout),
+        Type::List(_)
+        | Type::Pointer(_)
+        | Type::ConstPointer(_)
+        | Type::Handle(_)
+        | Type::Builtin(_) => true,
+    }
+}
+
+/// Whether the Rust definition of `tref` contains a `union`, which prevents
+/// deriving `Debug`.
+fn contains_union(tref: &TypeRef) -> bool {
+    match &**tref.type_() {
+        Type::Variant(v) => !v.is_enum() && v.as_expected().is_none(),
+        Type::Record(r) => r.members.iter().any(|m| contains_union(&m.tref)),
+        Type::List(t) => contains_union(t),
+        Type::Pointer(_) | Type::ConstPointer(_) | Type::Handle(_) | Type::Builtin(_) => false,
+    }
+}
+
+/// The Rust type of `tref`, using `lt` for any lifetimes.
+pub(super) fn tref_ty(tref: &TypeRef, lt: &str) -> String {
+    match tref {
+        TypeRef::Name(nt) => named_ty(nt, lt),
+        TypeRef::Value(ty) => anonymous_ty(ty, lt),
+    }
+}
+
+pub(super) fn named_ty(nt: &NamedType, lt: &str) -> String {
+    if needs_lifetime(&nt.tref) {
+        format!("{}<{}>", type_name(&nt.name), lt)
+    } else {
+        type_name(&nt.name)
+    }
+}
+
+fn anonymous_ty(ty: &Type, lt: &str) -> String {
+    match ty {
+        Type::Builtin(b) => builtin(*b).to_string(),
+        Type::List(t) => match &**t.type_() {
+            Type::Builtin(BuiltinType::Char) => format!("&{} str", lt),
+            _ => format!("&{} [{}]", lt, tref_ty(t, lt)),
+        },
+        Type::Pointer(t) => format!("*mut {}", tref_ty(t, lt)),
+        Type::ConstPointer(t) => format!("*const {}", tref_ty(t, lt)),
+        Type::Record(r) if r.is_tuple() => {
+            let members = r
+                .members
+                .iter()
+                .map(|m| tref_ty(&m.tref, lt))
+                .collect::<Vec<_>>();
+            tuple(&members)
+        }
+        Type::Variant(v) if v.is_bool() => "bool".to_string(),
+        Type::Variant(v) if v.as_expected().is_some() => {
+            let (ok, err) = v.as_expected().unwrap();
+            let ok = ok
+                .map(|t| tref_ty(t, lt))
+                .unwrap_or_else(|| "()".to_string());
+            let err = err
+                .map(|t| tref_ty(t, lt))
+                .unwrap_or_else(|| "()".to_string());
+            format!("Result<{}, {}>", ok, err)
+        }
+        Type::Handle(_) => "u32".to_string(),
+        Type::Record(_) | Type::Variant(_) => {
+            unreachable!("anonymous {} types have no Rust representation", ty.kind())
+        }
+    }
+}
+
+fn define_type(out: &mut String, options: &Options, nt: &NamedType) {
+    let name = type_name(&nt.name);
+    let lt = if needs_lifetime(&nt.tref) { "<'a>" } else { "" };
+    out.push_str(&doc_comment(&nt.docs, "///", ""));
+    let ty = match &nt.tref {
+        TypeRef::Name(other) => {
+            writeln!(out, "pub type {}{} = {};", name, lt, named_ty(other, "'a")).unwrap();
+            return;
+        }
+        TypeRef::Value(ty) => ty,
+    };
+    match &**ty {
+        Type::Record(r) => {
+            if let Some(repr) = r.bitflags_repr() {
+                writeln!(out, "pub type {} = {};", name, int_repr(repr)).unwrap();
+                for (i, m) in r.members.iter().enumerate() {
+                    out.push_str(&doc_comment(&m.docs, "///", ""));
+                    writeln!(
+                        out,
+                        "pub const {}_{}: {} = 1 << {};",
+                        const_name(&nt.name),
+                        const_name(&m.name),
+                        name,
+                        i
+                    )
+                    .unwrap();
+                }
+                flags_names_impl(out, nt, "");
+            } else if r.is_tuple() {
+                writeln!(out, "pub type {}{} = {};", name, lt, anonymous_ty(ty, "'a")).unwrap();
+            } else {
+                out.push_str("#[repr(C)]\n");
+                let derives: &[&str] = if contains_union(&nt.tref) {
+                    &["Copy", "Clone"]
+                } else {
+                    &["Copy", "Clone", "Debug"]
+                };
+                let serde = if needs_lifetime(&nt.tref) || contains_union(&nt.tref) {
+                    &[]
+                } else {
+                    serde_derives(options, &nt.tref)
+                };
+                let derives = [derives, serde].concat();
+                out.push_str(&attributes(options, &nt.name, &derives, ""));
+                writeln!(out, "pub struct {}{} {{", name, lt).unwrap();
+                for m in r.members.iter() {
+                    out.push_str(&doc_comment(&m.docs, "///", "    "));
+                    if !serde.is_empty() {
+                        out.push_str(&serde_rename(
+                            m.name.as_str(),
+                            &ident(m.name.as_str()),
+                            "    ",
+                        ));
+                    }
+                    writeln!(
+                        out,
+                        "    pub {}: {},",
+                        ident(m.name.as_str()),
+                        tref_ty(&m.tref, "'a")
+                    )
+                    .unwrap();
+                }
+                out.push_str("}\n");
+            }
+        }
+        Type::Variant(v) if v.is_enum() => {
+            define_enum(out, options, nt, &v.tag_repr, &v.cases);
+            tensor_type_impl(out, nt);
+            tensor_convert_mod(out, nt);
+        }
+        Type::Variant(v) if v.as_expected().is_some() => {
+            writeln!(out, "pub type {}{} = {};", name, lt, anonymous_ty(ty, "'a")).unwrap();
+        }
+        Type::Variant(v) => {
+            out.push_str("#[repr(C)]\n#[derive(Copy, Clone)]\n");
+            writeln!(out, "pub union {}U{} {{", name, lt).unwrap();
+            for c in v.cases.iter() {
+                if let Some(tref) = &c.tref {
+                    out.push_str(&doc_comment(&c.docs, "///", "    "));
+                    writeln!(
+                        out,
+                        "    pub {}: {},",
+                        ident(c.name.as_str()),
+                        tref_ty(tref, "'a")
+                    )
+                    .unwrap();
+                }
+            }
+            out.push_str("}\n");
+            out.push_str("#[repr(C)]\n");
+            out.push_str(&attributes(options, &nt.name, &["Copy", "Clone"], ""));
+            writeln!(out, "pub struct {}{} {{", name, lt).unwrap();
+            writeln!(out, "    pub tag: {},", int_repr(v.tag_repr)).unwrap();
+            writeln!(out, "    pub u: {}U{},", name, lt).unwrap();
+            out.push_str("}\n");
+        }
+        Type::Handle(_) => writeln!(out, "pub type {} = u32;", name).unwrap(),
+        Type::List(_) | Type::Pointer(_) | Type::ConstPointer(_) | Type::Builtin(_) => {
+            writeln!(out, "pub type {}{} = {};", name, lt, anonymous_ty(ty, "'a")).unwrap();
+        }
+    }
+}
+
+fn define_enum(
+    out: &mut String,
+    options: &Options,
+    nt: &NamedType,
+    repr: &IntRepr,
+    cases: &[crate::Case],
+) {
+    let name = type_name(&nt.name);
+    let repr = int_repr(*repr);
+    out.push_str("#[repr(transparent)]\n");
+    out.push_str(&attributes(
+        options,
+        &nt.name,
+        &[
+            "Copy",
+            "Clone",
+            "Hash",
+            "Eq",
+            "PartialEq",
+            "Ord",
+            "PartialOrd",
+        ],
+        "",
+    ));
+    writeln!(out, "pub struct {}(pub(crate) {});", name, repr).unwrap();
+    for (i, c) in cases.iter().enumerate() {
+        out.push_str(&doc_comment(&c.docs, "///", ""));
+        writeln!(
+            out,
+            "pub const {}_{}: {} = {}({});",
+            const_name(&nt.name),
+            const_name(&c.name),
+            name,
+            name,
+            i
+        )
+        .unwrap();
+    }
+    writeln!(out, "impl {} {{", name).unwrap();
+    out.push_str("    /// Every value of the enum, in order.\n");
+    writeln!(out, "    pub const ALL: [{}; {}] = [", name, cases.len()).unwrap();
+    for c in cases.iter() {
+        writeln!(
+            out,
+            "        {}_{},",
+            const_name(&nt.name),
+            const_name(&c.name)
+        )
+        .unwrap();
+    }
+    out.push_str("    ];\n");
+    writeln!(out, "    pub const fn raw(&self) -> {} {{", repr).unwrap();
+    out.push_str("        self.0\n");
+    out.push_str("    }\n");
+    out.push_str(
+        "    /// The witx name of the value, or `unknown` if the bindings don't know it.\n",
+    );
+    out.push_str("    pub const fn as_str(&self) -> &'static str {\n");
+    out.push_str("        match self.0 {\n");
+    for (i, c) in cases.iter().enumerate() {
+        writeln!(out, "            {} => \"{}\",", i, c.name.as_str()).unwrap();
+    }
+    out.push_str("            _ => \"unknown\",\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("    /// The value named `name` in witx.\n");
+    out.push_str("    #[allow(clippy::should_implement_trait)]\n");
+    out.push_str("    pub fn from_str(name: &str) -> Option<Self> {\n");
+    out.push_str("        match name {\n");
+    for c in cases.iter() {
+        writeln!(
+            out,
+            "            \"{}\" => Some({}_{}),",
+            c.name.as_str(),
+            const_name(&nt.name),
+            const_name(&c.name)
+        )
+        .unwrap();
+    }
+    out.push_str("            _ => None,\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    writeln!(out, "impl core::fmt::Debug for {} {{", name).unwrap();
+    out.push_str("    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {\n");
+    out.push_str("        let name = match self.0 {\n");
+    for (i, c) in cases.iter().enumerate() {
+        writeln!(out, "            {} => \"{}\",", i, const_name(&c.name)).unwrap();
+    }
+    out.push_str("            _ => \"unknown\",\n");
+    out.push_str("        };\n");
+    writeln!(out, "        f.debug_struct(\"{}\")", name).unwrap();
+    out.push_str("            .field(\"code\", &self.0)\n");
+    out.push_str("            .field(\"name\", &name)\n");
+    out.push_str("            .finish()\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    if options.serde {
+        define_enum_serde(out, nt, repr, cases);
+    }
+}
+
+/// serde impls for the raw enum `nt`, which can't be derived for a newtype
+/// holding values the bindings may not know: human-readable formats get the
+/// witx name of known values and the code of others, and the rest the code.
+fn define_enum_serde(out: &mut String, nt: &NamedType, repr: &str, cases: &[crate::Case]) {
+    let name = type_name(&nt.name);
+    writeln!(out, "impl serde::Serialize for {} {{", name).unwrap();
+    out.push_str(
+        "    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {\n",
+    );
+    out.push_str("        if serializer.is_human_readable() && Self::ALL.contains(self) {\n");
+    out.push_str("            serializer.serialize_str(self.as_str())\n");
+    out.push_str("        } else {\n");
+    out.push_str("            serde::Serialize::serialize(&self.0, serializer)\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    writeln!(out, "impl<'de> serde::Deserialize<'de> for {} {{", name).unwrap();
+    out.push_str("    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {\n");
+    out.push_str("        struct Visitor;\n");
+    out.push_str("        impl<'de> serde::de::Visitor<'de> for Visitor {\n");
+    writeln!(out, "            type Value = {};", name).unwrap();
+    out.push_str(
+        "            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {\n",
+    );
+    writeln!(
+        out,
+        "                f.write_str(\"the name or code of a `{}`\")",
+        nt.name.as_str()
+    )
+    .unwrap();
+    out.push_str("            }\n");
+    writeln!(
+        out,
+        "            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<{}, E> {{",
+        name
+    )
+    .unwrap();
+    let names = cases
+        .iter()
+        .map(|c| format!("\"{}\"", c.name.as_str()))
+        .collect::<Vec<_>>();
+    writeln!(
+        out,
+        "                {}::from_str(v).ok_or_else(|| E::unknown_variant(v, &[{}]))",
+        name,
+        names.join(", ")
+    )
+    .unwrap();
+    out.push_str("            }\n");
+    writeln!(
+        out,
+        "            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<{}, E> {{",
+        name
+    )
+    .unwrap();
+    out.push_str("                core::convert::TryFrom::try_from(v)\n");
+    writeln!(out, "                    .map({})", name).unwrap();
+    out.push_str("                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))\n");
+    out.push_str("            }\n");
+    out.push_str("        }\n");
+    out.push_str("        if deserializer.is_human_readable() {\n");
+    out.push_str("            deserializer.deserialize_any(Visitor)\n");
+    out.push_str("        } else {\n");
+    writeln!(
+        out,
+        "            <{} as serde::Deserialize>::deserialize(deserializer).map({})",
+        repr, name
+    )
+    .unwrap();
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+}
+
+fn define_wrapper(out: &mut String, module: &Id, func: &InterfaceFunc, errors: &[Id]) {
+    out.push_str(&doc_comment(&func.docs, "///", "    "));
+    let params = func
+        .params
+        .iter()
+        .map(|p| {
+            let ty = match &p.tref {
+                TypeRef::Name(nt) if is_owned_handle(nt) => format!("&{}", type_name(&nt.name)),
+                tref => tref_ty(tref, "'_"),
+            };
+            format!("{}: {}", ident(p.name.as_str()), ty)
+        })
+        .collect::<Vec<_>>();
+    let ret = if func.noreturn {
+        " -> !".to_string()
+    } else {
+        match func.results.first() {
+            Some(r) => format!(" -> {}", tref_ty(&r.tref, "'static")),
+            None => String::new(),
+        }
+    };
+    // Slices and owned handles make calls safe, unless they pass raw
+    // pointers the host will follow.
+    let unsafe_ = func
+        .params
+        .iter()
+        .chain(func.results.iter())
+        .any(|p| contains_pointer(&p.tref));
+    if unsafe_ {
+        out.push_str("    /// # Safety\n");
+        out.push_str("    ///\n");
+        out.push_str("    /// The host reads and writes memory through the pointers passed, so\n");
+        out.push_str("    /// they must be valid for it.\n");
+    }
+    writeln!(
+        out,
+        "    pub {}fn {}({}){} {{",
+        if unsafe_ { "unsafe " } else { "" },
+        ident(func.name.as_str()),
+        params.join(", "),
+        ret
+    )
+    .unwrap();
+    let mut gen = WrapperGen {
+        module: ident(module.as_str()),
+        indent: if unsafe_ { 8 } else { 12 },
+        errors: errors.to_vec(),
+        params: func.params.iter().map(|p| ident(p.name.as_str())).collect(),
+        src: String::new(),
+        blocks: Vec::new(),
+        block_results: Vec::new(),
+    };
+    func.call_wasm(module, &mut gen);
+    if unsafe_ {
+        out.push_str(&gen.src);
+    } else {
+        out.push_str("        unsafe {\n");
+        out.push_str(&gen.src);
+        out.push_str("        }\n");
+    }
+    out.push_str("    }\n");
+}
+
+/// Generates the body of a guest wrapper from the instructions of
+/// [`InterfaceFunc::call_wasm`]. Operands are Rust expressions.
+struct WrapperGen {
+    /// The module of the raw imports, within `raw`.
+    module: String,
+    /// Columns each line of the body is indented by.
+    indent: usize,
+    /// The enums the safe layer replaces with error types.
+    errors: Vec<Id>,
+    params: Vec<String>,
+    src: String,
+    blocks: Vec<String>,
+    block_results: Vec<(String, Option<String>)>,
+}
+
+impl WrapperGen {
+    fn line(&mut self, line: &str) {
+        for l in line.lines() {
+            self.src.push_str(&" ".repeat(self.indent));
+            self.src.push_str(l);
+            self.src.push('\n');
+        }
+    }
+
+    /// The value of a block from `finish_block`, as a single expression.
+    fn block_expr(&mut self) -> String {
+        let (src, expr) = self.block_results.pop().unwrap();
+        let expr = expr.unwrap_or_else(|| "()".to_string());
+        if src.is_empty() {
+            expr
+        } else {
+            format!("{{\n{}{}\n}}", src, expr)
+        }
+    }
+}
+
+impl Bindgen for WrapperGen {
+    type Operand = String;
+
+    fn emit(
+        &mut self,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<String>,
+        results: &mut Vec<String>,
+    ) {
+        use Instruction::*;
+        let op = |i: usize| operands[i].clone();
+        match inst {
+            GetArg { nth } => results.push(self.params[*nth].clone()),
+            AddrOf => results.push(format!("&{} as *const _ as i32", op(0))),
+            I32FromChar
+            | I32FromU32
+            | I32FromS32
+            | I32FromUsize
+            | I32FromU16
+            | I32FromS16
+            | I32FromU8
+            | I32FromS8
+            | I32FromChar8
+            | I32FromPointer
+            | I32FromConstPointer
+            | I32FromBitflags { .. } => results.push(format!("{} as i32", op(0))),
+            I32FromHandle { ty } if is_owned_handle(ty) => {
+                results.push(format!("{}.as_raw() as i32", op(0)))
+            }
+            I32FromHandle { .. } => results.push(format!("{} as i32", op(0))),
+            I64FromU64 | I64FromS64 | I64FromBitflags { .. } => {
+                results.push(format!("{} as i64", op(0)))
+            }
+            F32FromIf32 | F64FromIf64 | If32FromF32 | If64FromF64 => results.push(op(0)),
+            ListPointerLength => {
+                results.push(format!("{}.as_ptr() as i32", op(0)));
+                results.push(format!("{}.len() as i32", op(0)));
+            }
+            CallWasm {
+                name, results: ret, ..
+            } => {
+                let call = format!(
+                    "raw::{}::{}({})",
+                    self.module,
+                    ident(name),
+                    operands.join(", ")
+                );
+                if ret.is_empty() {
+                    self.line(&format!("{};", call));
+                } else {
+                    self.line(&format!("let ret = {};", call));
+                    results.push("ret".to_string());
+                }
+            }
+            S8FromI32 => results.push(format!("{} as i8", op(0))),
+            U8FromI32 | Char8FromI32 => results.push(format!("{} as u8", op(0))),
+            S16FromI32 => results.push(format!("{} as i16", op(0))),
+            U16FromI32 => results.push(format!("{} as u16", op(0))),
+            S32FromI32 => results.push(format!("{} as i32", op(0))),
+            U32FromI32 => results.push(format!("{} as u32", op(0))),
+            S64FromI64 => results.push(format!("{} as i64", op(0))),
+            U64FromI64 => results.push(format!("{} as u64", op(0))),
+            UsizeFromI32 => results.push(format!("{} as usize", op(0))),
+            CharFromI32 => {
+                results.push(format!("core::char::from_u32_unchecked({} as u32)", op(0)))
+            }
+            HandleFromI32 { ty } if is_owned_handle(ty) => results.push(format!(
+                "{}::from_raw({} as u32)",
+                type_name(&ty.name),
+                op(0)
+            )),
+            HandleFromI32 { ty } | BitflagsFromI32 { ty } | BitflagsFromI64 { ty } => {
+                results.push(format!("{} as {}", op(0), named_ty(ty, "'_")))
+            }
+            PointerFromI32 { ty } => {
+                results.push(format!("{} as *mut {}", op(0), tref_ty(ty, "'_")))
+            }
+            ConstPointerFromI32 { ty } => {
+                results.push(format!("{} as *const {}", op(0), tref_ty(ty, "'_")))
+            }
+            ReturnPointerGet { n } => results.push(format!("rp{}.as_mut_ptr() as i32", n)),
+            Load { ty } => {
+                // Return pointers are `MaybeUninit` locals which the call has
+                // just initialized, so read them directly.
+                let operand = op(0);
+                let value = match operand.strip_suffix(".as_mut_ptr() as i32") {
+                    Some(rp) => format!("{}.assume_init()", rp),
+                    None => format!(
+                        "core::ptr::read({} as *const raw::{})",
+                        operand,
+                        named_ty(ty, "'_")
+                    ),
+                };
+                if is_owned_handle(ty) {
+                    results.push(format!("{}::from_raw({})", type_name(&ty.name), value));
+                } else {
+                    results.push(value);
+                }
+            }
+            ResultLift => {
+                let err = self.block_expr();
+                let ok = self.block_expr();
+                results.push(format!(
+                    "match {} {{\n    0 => Ok({}),\n    _ => Err({}),\n}}",
+                    op(0),
+                    ok,
+                    err
+                ));
+            }
+            EnumLift { ty } => {
+                let repr = match &**ty.type_() {
+                    Type::Variant(v) => int_repr(v.tag_repr),
+                    _ => unreachable!(),
+                };
+                let name = type_name(&ty.name);
+                let raw = format!("raw::{}({} as {})", name, op(0), repr);
+                if self.errors.contains(&ty.name) {
+                    results.push(format!("{}::from({})", name, raw));
+                } else {
+                    results.push(raw);
+                }
+            }
+            EnumLower { ty } if self.errors.contains(&ty.name) => results.push(format!(
+                "raw::{}::from({}).0 as i32",
+                type_name(&ty.name),
+                op(0)
+            )),
+            EnumLower { .. } => results.push(format!("{}.0 as i32", op(0))),
+            TupleLift { .. } => results.push(tuple(operands)),
+            ReuseReturn => results.push("ret".to_string()),
+            Return { amt: 0 } => {}
+            Return { .. } => {
+                let ret = op(0);
+                self.line(&ret);
+            }
+            ListFromPointerLength { .. }
+            | CallInterface { .. }
+            | Store { .. }
+            | ResultLower { .. }
+            | TupleLower { .. }
+            | VariantPayload => unreachable!("{:?} is only used when calling an interface", inst),
+        }
+    }
+
+    fn allocate_space(&mut self, slot: usize, ty: &NamedType) {
+        self.line(&format!(
+            "let mut rp{} = core::mem::MaybeUninit::<raw::{}>::uninit();",
+            slot,
+            named_ty(ty, "'_")
+        ));
+    }
+
+    fn push_block(&mut self) {
+        let prev = mem::take(&mut self.src);
+        self.blocks.push(prev);
+    }
+
+    fn finish_block(&mut self, operand: Option<String>) {
+        let prev = self.blocks.pop().unwrap();
+        let src = mem::replace(&mut self.src, prev);
+        self.block_results.push((src, operand));
+    }
+}
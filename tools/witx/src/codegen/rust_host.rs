@@ -0,0 +1,2195 @@
+//! Rust scaffolding for implementing a document's modules in a wasmtime or
+//! wasmer host.
+//!
+//! The generated crate has an owned Rust representation of every named type,
+//! a trait per module with a method per function, and a function per module
+//! which registers the functions with the engine: `add_to_linker`, with a
+//! `wasmtime::Linker`, or `add_to_imports`, with the `wasmer::Imports` an
+//! instance is created with. The glue lifts arguments out of guest memory, calls the
+//! trait and lowers its results back, as described by
+//! [`InterfaceFunc::call_interface`]. A backend implementation only has to
+//! implement the traits.
+//!
+//! Arguments are checked against their declared types before the trait is
+//! called: enums must have one of their cases, pointers can't be null, and
+//! lists must have their declared sizes. By default invalid arguments trap,
+//! and `add_to_linker_with` or `add_to_imports_with` can have functions
+//! return their `invalid_argument` error instead, so hosts choose the policy
+//! in one place.
+//!
+//! Wasmer host functions can't look up the exports of the instance calling
+//! them, so its glue takes the memory from the `HostEnv` holding the
+//! implementation, which the host gives the `memory` export with
+//! `set_memory` once the instance is created.
+
+use super::rust::{
+    attributes, const_name, flags_names_impl, ident, serde_derives, serde_rename,
+    tensor_convert_mod, tensor_type_impl, tuple, type_name, variant_name,
+};
+use super::{doc_comment, error_enums, error_message, Options, OutputFile};
+use crate::{
+    Bindgen, BuiltinType, Case, Document, Id, Instruction, IntRepr, InterfaceFunc, Layout, Module,
+    NamedType, RecordDatatype, RecordMember, SizeExpr, Type, TypeRef,
+};
+use std::fmt::Write;
+use std::mem;
+
+/// Wasmtime release the generated crate is written against.
+pub(super) const WASMTIME_VERSION: &str = "48";
+
+/// Wasmer release the generated crate is written against.
+const WASMER_VERSION: &str = "6";
+
+/// The wasm engine a generated host crate embeds in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum Engine {
+    Wasmtime,
+    Wasmer,
+}
+
+impl Engine {
+    /// The error trapping the calling instance, which functions that don't
+    /// return return.
+    fn trap(&self) -> &'static str {
+        match self {
+            Engine::Wasmtime => "wasmtime::Error",
+            Engine::Wasmer => "wasmer::RuntimeError",
+        }
+    }
+
+    /// The function making a trap of a message.
+    fn trap_msg(&self) -> &'static str {
+        match self {
+            Engine::Wasmtime => "wasmtime::Error::msg",
+            Engine::Wasmer => "wasmer::RuntimeError::new",
+        }
+    }
+}
+
+pub(super) fn generate(doc: &Document, options: &Options, engine: Engine) -> Vec<OutputFile> {
+    let crate_name = options.crate_name.as_deref().unwrap_or("bindings");
+    let dependency = match engine {
+        Engine::Wasmtime => format!(
+            "wasmtime = {{ version = \"{}\", default-features = false, features = [\"runtime\"] }}\n",
+            WASMTIME_VERSION
+        ),
+        Engine::Wasmer => format!("wasmer = \"{}\"\n", WASMER_VERSION),
+    };
+    let mut manifest = format!(
+        "[package]\n\
+         name = \"{}\"\n\
+         version = \"0.1.0\"\n\
+         edition = \"2018\"\n\
+         \n\
+         [dependencies]\n\
+         {}",
+        crate_name, dependency
+    );
+    if options.tracing {
+        manifest.push_str("tracing = \"0.1\"\n");
+    }
+    if options.serde {
+        manifest.push_str("serde = { version = \"1.0\", features = [\"derive\"] }\n");
+    }
+    if options.proptest {
+        manifest.push_str("proptest = { version = \"1\", optional = true }\n");
+    }
+    vec![
+        OutputFile::new("Cargo.toml", manifest),
+        OutputFile::new("src/lib.rs", render(doc, options, engine)),
+    ]
+}
+
+/// Guest memory access shared by every generated crate, after the imports
+/// of the engine.
+const PRELUDE: &str = r#"
+/// An invalid access to guest memory, which traps the calling instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuestError {
+    /// `len` bytes at `offset` are out of bounds of the guest memory.
+    OutOfBounds { offset: u32, len: u32 },
+    /// `value` is not a case of the enum `ty`.
+    InvalidEnumValue { ty: &'static str, value: u64 },
+    /// `value` is not a unicode scalar value.
+    InvalidChar { value: u32 },
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// Lists can't be written to guest memory, since that requires
+    /// allocating in the guest.
+    ListWrite,
+    /// The list `name` has `len` elements, but its declared size is
+    /// `expected`, or overflows.
+    SizeMismatch { name: &'static str, len: u64, expected: Option<u64> },
+    /// The pointer `name` is null.
+    NullPointer { name: &'static str },
+}
+
+impl fmt::Display for GuestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuestError::OutOfBounds { offset, len } => {
+                write!(f, "{} bytes at offset {} are out of bounds", len, offset)
+            }
+            GuestError::InvalidEnumValue { ty, value } => {
+                write!(f, "{} is not a valid `{}`", value, ty)
+            }
+            GuestError::InvalidChar { value } => write!(f, "{:#x} is not a valid char", value),
+            GuestError::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            GuestError::ListWrite => write!(f, "lists can't be written to guest memory"),
+            GuestError::SizeMismatch { name, len, expected: Some(expected) } => {
+                write!(f, "`{}` has {} elements, expected {}", name, len, expected)
+            }
+            GuestError::SizeMismatch { name, expected: None, .. } => {
+                write!(f, "the size of `{}` overflows", name)
+            }
+            GuestError::NullPointer { name } => write!(f, "`{}` is a null pointer", name),
+        }
+    }
+}
+
+impl std::error::Error for GuestError {}
+
+impl GuestError {
+    /// Whether this is an invalid argument, rather than an access to guest
+    /// memory which can't be made.
+    pub fn is_invalid_argument(&self) -> bool {
+        !matches!(self, GuestError::OutOfBounds { .. } | GuestError::ListWrite)
+    }
+}
+
+/// What functions do with arguments which aren't valid values of their
+/// declared types. Accesses out of bounds of guest memory always trap.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum InvalidArguments {
+    /// Trap the calling instance.
+    #[default]
+    Trap,
+    /// Return the `invalid_argument` or `inval` error of functions whose
+    /// error enum has one, and trap in others.
+    Error,
+}
+
+/// Check that the pointer `name` isn't null.
+#[allow(dead_code)]
+fn check_pointer<T>(name: &'static str, ptr: &GuestPtr<T>) -> Result<(), GuestError> {
+    if ptr.offset() == 0 {
+        Err(GuestError::NullPointer { name })
+    } else {
+        Ok(())
+    }
+}
+
+/// A pointer to a `T` in guest memory.
+pub struct GuestPtr<T> {
+    offset: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> GuestPtr<T> {
+    pub fn new(offset: u32) -> Self {
+        GuestPtr {
+            offset,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+}
+
+impl<T: GuestType> GuestPtr<T> {
+    /// A pointer to the `n`th `T` after this one.
+    pub fn add(&self, n: u32) -> Result<Self, GuestError> {
+        n.checked_mul(T::SIZE)
+            .and_then(|bytes| self.offset.checked_add(bytes))
+            .map(GuestPtr::new)
+            .ok_or(GuestError::OutOfBounds {
+                offset: self.offset,
+                len: n.saturating_mul(T::SIZE),
+            })
+    }
+}
+
+impl<T> Clone for GuestPtr<T> {
+    fn clone(&self) -> Self {
+        GuestPtr::new(self.offset)
+    }
+}
+
+impl<T> Copy for GuestPtr<T> {}
+
+impl<T> PartialEq for GuestPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+
+impl<T> fmt::Debug for GuestPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GuestPtr({:#x})", self.offset)
+    }
+}
+
+/// The linear memory of the calling instance.
+pub struct GuestMemory<'a> {
+    bytes: &'a mut [u8],
+}
+
+impl<'a> GuestMemory<'a> {
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        GuestMemory { bytes }
+    }
+
+    pub fn bytes(&self, offset: u32, len: u32) -> Result<&[u8], GuestError> {
+        let start = offset as usize;
+        start
+            .checked_add(len as usize)
+            .and_then(|end| self.bytes.get(start..end))
+            .ok_or(GuestError::OutOfBounds { offset, len })
+    }
+
+    pub fn bytes_mut(&mut self, offset: u32, len: u32) -> Result<&mut [u8], GuestError> {
+        let start = offset as usize;
+        start
+            .checked_add(len as usize)
+            .and_then(move |end| self.bytes.get_mut(start..end))
+            .ok_or(GuestError::OutOfBounds { offset, len })
+    }
+
+    pub fn read<T: GuestType>(&self, ptr: GuestPtr<T>) -> Result<T, GuestError> {
+        T::read(self, ptr.offset())
+    }
+
+    pub fn write<T: GuestType>(&mut self, ptr: GuestPtr<T>, value: &T) -> Result<(), GuestError> {
+        value.write(self, ptr.offset())
+    }
+
+    /// Read the `len` elements of a list starting at `offset`.
+    pub fn read_list<T: GuestType>(&self, offset: u32, len: u32) -> Result<Vec<T>, GuestError> {
+        let base = GuestPtr::<T>::new(offset);
+        (0..len).map(|i| self.read(base.add(i)?)).collect()
+    }
+
+    /// Read the `len` bytes of a string starting at `offset`.
+    pub fn read_string(&self, offset: u32, len: u32) -> Result<String, GuestError> {
+        let bytes = self.bytes(offset, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| GuestError::InvalidUtf8)
+    }
+}
+
+/// Types which can be read from and written to guest memory.
+pub trait GuestType: Sized {
+    const SIZE: u32;
+    const ALIGN: u32;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError>;
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError>;
+}
+
+macro_rules! primitive {
+    ($($ty:ident)*) => {$(
+        impl GuestType for $ty {
+            const SIZE: u32 = std::mem::size_of::<$ty>() as u32;
+            const ALIGN: u32 = std::mem::size_of::<$ty>() as u32;
+            fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+                let mut buf = [0; std::mem::size_of::<$ty>()];
+                buf.copy_from_slice(mem.bytes(offset, Self::SIZE)?);
+                Ok($ty::from_le_bytes(buf))
+            }
+            fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+                mem.bytes_mut(offset, Self::SIZE)?.copy_from_slice(&self.to_le_bytes());
+                Ok(())
+            }
+        }
+    )*};
+}
+
+primitive!(u8 u16 u32 u64 i8 i16 i32 i64 f32 f64);
+
+impl GuestType for char {
+    const SIZE: u32 = 4;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        let value = u32::read(mem, offset)?;
+        std::char::from_u32(value).ok_or(GuestError::InvalidChar { value })
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        (*self as u32).write(mem, offset)
+    }
+}
+
+impl<T> GuestType for GuestPtr<T> {
+    const SIZE: u32 = 4;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        Ok(GuestPtr::new(u32::read(mem, offset)?))
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        self.offset.write(mem, offset)
+    }
+}
+
+impl<T: GuestType> GuestType for Vec<T> {
+    const SIZE: u32 = 8;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        let ptr = u32::read(mem, offset)?;
+        let len = u32::read(mem, field(offset, 4)?)?;
+        mem.read_list(ptr, len)
+    }
+    fn write(&self, _mem: &mut GuestMemory<'_>, _offset: u32) -> Result<(), GuestError> {
+        Err(GuestError::ListWrite)
+    }
+}
+
+impl GuestType for String {
+    const SIZE: u32 = 8;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        let ptr = u32::read(mem, offset)?;
+        let len = u32::read(mem, field(offset, 4)?)?;
+        mem.read_string(ptr, len)
+    }
+    fn write(&self, _mem: &mut GuestMemory<'_>, _offset: u32) -> Result<(), GuestError> {
+        Err(GuestError::ListWrite)
+    }
+}
+
+/// The offset of a field `field_offset` bytes into a value at `offset`.
+fn field(offset: u32, field_offset: u32) -> Result<u32, GuestError> {
+    offset.checked_add(field_offset).ok_or(GuestError::OutOfBounds {
+        offset,
+        len: field_offset,
+    })
+}
+"#;
+
+/// Access to the memory of the calling instance in wasmtime.
+const WASMTIME_PRELUDE: &str = r#"
+fn caller_memory<T>(caller: &mut Caller<'_, T>) -> wasmtime::Result<wasmtime::Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => wasmtime::bail!("missing required memory export"),
+    }
+}
+"#;
+
+/// The environment of host functions in wasmer, which holds the memory of
+/// the instance since it can't be looked up from the caller.
+const WASMER_PRELUDE: &str = r#"
+/// The environment of the host functions of an instance: the value
+/// implementing them, and the memory of the instance, set once it's
+/// instantiated.
+pub struct HostEnv<T> {
+    pub data: T,
+    memory: Option<Memory>,
+}
+
+impl<T> HostEnv<T> {
+    pub fn new(data: T) -> Self {
+        HostEnv { data, memory: None }
+    }
+
+    /// Set the memory guests pass values in, the `memory` export of the
+    /// instance, before it calls any host function.
+    pub fn set_memory(&mut self, memory: Memory) {
+        self.memory = Some(memory);
+    }
+}
+
+impl From<GuestError> for RuntimeError {
+    fn from(e: GuestError) -> Self {
+        RuntimeError::user(Box::new(e))
+    }
+}
+
+fn env_memory<T>(env: &HostEnv<T>) -> Result<Memory, RuntimeError> {
+    env.memory
+        .clone()
+        .ok_or_else(|| RuntimeError::new("missing required memory export"))
+}
+"#;
+
+/// Checks of the sizes declared with `(@witx size ...)`, for documents which
+/// have any.
+const SIZE_PRELUDE: &str = r#"
+/// Check that the list `name` has `len` elements, its size `expected`.
+fn check_size(name: &'static str, len: usize, expected: Option<u64>) -> Result<(), GuestError> {
+    let len = len as u64;
+    if expected == Some(len) {
+        Ok(())
+    } else {
+        Err(GuestError::SizeMismatch { name, len, expected })
+    }
+}
+
+// Only the operations used by the document's sizes are called.
+#[allow(dead_code)]
+fn size_of_value<T>(value: T) -> Option<u64>
+where
+    u64: std::convert::TryFrom<T>,
+{
+    std::convert::TryFrom::try_from(value).ok()
+}
+
+#[allow(dead_code)]
+fn size_product<T: Copy>(values: &[T]) -> Option<u64>
+where
+    u64: std::convert::TryFrom<T>,
+{
+    size_mul(&values.iter().map(|v| size_of_value(*v)).collect::<Vec<_>>())
+}
+
+#[allow(dead_code)]
+fn size_mul(sizes: &[Option<u64>]) -> Option<u64> {
+    sizes.iter().try_fold(1u64, |n, s| n.checked_mul((*s)?))
+}
+
+#[allow(dead_code)]
+fn size_add(sizes: &[Option<u64>]) -> Option<u64> {
+    sizes.iter().try_fold(0u64, |n, s| n.checked_add((*s)?))
+}
+"#;
+
+pub(super) fn render(doc: &Document, options: &Options, engine: Engine) -> String {
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n\n");
+    out.push_str("use std::fmt;\nuse std::marker::PhantomData;\n");
+    out.push_str(match engine {
+        Engine::Wasmtime => "use wasmtime::{Caller, Extern, Linker};\n",
+        Engine::Wasmer => "use wasmer::{\n    AsStoreMut, Exports, Function, FunctionEnv, FunctionEnvMut, Imports, Memory,\n    RuntimeError,\n};\n",
+    });
+    out.push_str(PRELUDE);
+    out.push_str(match engine {
+        Engine::Wasmtime => WASMTIME_PRELUDE,
+        Engine::Wasmer => WASMER_PRELUDE,
+    });
+    if has_sizes(doc) {
+        out.push_str(SIZE_PRELUDE);
+    }
+    let errors = error_enums(doc);
+    for nt in doc.typenames() {
+        out.push('\n');
+        define_type(&mut out, options, &nt);
+        if errors.iter().any(|e| e.name == nt.name) {
+            define_error_impls(&mut out, &nt);
+        }
+        if options.proptest {
+            define_arbitrary(&mut out, &nt);
+        }
+    }
+    for c in doc.constants() {
+        out.push('\n');
+        out.push_str(&doc_comment(&c.docs, "///", ""));
+        let ty = doc.typename(&c.ty).expect("constant type is defined");
+        let value = match &**ty.type_() {
+            Type::Variant(v) if v.is_enum() => match v.cases.get(c.value as usize) {
+                Some(case) => format!("{}::{}", type_name(&c.ty), variant_name(&case.name)),
+                None => continue,
+            },
+            _ => c.value.to_string(),
+        };
+        writeln!(
+            out,
+            "pub const {}_{}: {} = {};",
+            const_name(&c.ty),
+            const_name(&c.name),
+            type_name(&c.ty),
+            value
+        )
+        .unwrap();
+    }
+    for m in doc.modules() {
+        out.push('\n');
+        define_module(&mut out, &m, options, engine);
+    }
+    out
+}
+
+/// Whether any list in `doc` has a declared size.
+fn has_sizes(doc: &Document) -> bool {
+    let records = doc.typenames().any(|nt| match &**nt.type_() {
+        Type::Record(r) => r.members.iter().any(|m| m.size.is_some()),
+        _ => false,
+    });
+    records
+        || doc
+            .modules()
+            .any(|m| m.funcs().any(|f| f.params.iter().any(|p| p.size.is_some())))
+}
+
+/// A call of `check_size` for the list `name`, whose value and that of the
+/// fields or parameters its size depends on are given by `value`.
+fn size_check(name: &str, list: &Id, size: &SizeExpr, value: &dyn Fn(&Id) -> String) -> String {
+    format!(
+        "check_size(\"{}\", {}.len(), {})?",
+        name,
+        value(list),
+        size_expr(size, value)
+    )
+}
+
+/// An `Option<u64>`, `None` if the size overflows.
+fn size_expr(size: &SizeExpr, value: &dyn Fn(&Id) -> String) -> String {
+    let operands = |exprs: &[SizeExpr]| {
+        exprs
+            .iter()
+            .map(|e| size_expr(e, value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match size {
+        SizeExpr::Const(n) => format!("Some({})", n),
+        SizeExpr::Value(id) => format!("size_of_value({})", value(id)),
+        SizeExpr::Len(id) => format!("Some({}.len() as u64)", value(id)),
+        SizeExpr::Product(id) => format!("size_product(&{})", value(id)),
+        SizeExpr::TensorSize { dimensions, ty } => format!(
+            "{}.buffer_size(&{}).map(|n| n as u64)",
+            value(ty),
+            value(dimensions)
+        ),
+        SizeExpr::Mul(exprs) => format!("size_mul(&[{}])", operands(exprs)),
+        SizeExpr::Add(exprs) => format!("size_add(&[{}])", operands(exprs)),
+    }
+}
+
+fn builtin(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::Char => "char",
+        BuiltinType::U8 { .. } => "u8",
+        BuiltinType::U16 => "u16",
+        // Guest pointers are 32 bits wide, whatever the host's `usize`.
+        BuiltinType::U32 { .. } => "u32",
+        BuiltinType::U64 => "u64",
+        BuiltinType::S8 => "i8",
+        BuiltinType::S16 => "i16",
+        BuiltinType::S32 => "i32",
+        BuiltinType::S64 => "i64",
+        BuiltinType::F32 => "f32",
+        BuiltinType::F64 => "f64",
+    }
+}
+
+fn int_repr(repr: IntRepr) -> &'static str {
+    builtin(repr.to_builtin())
+}
+
+/// The host type of `tref`.
+pub(super) fn tref_ty(tref: &TypeRef) -> String {
+    match tref {
+        TypeRef::Name(nt) => type_name(&nt.name),
+        TypeRef::Value(ty) => anonymous_ty(ty),
+    }
+}
+
+fn anonymous_ty(ty: &Type) -> String {
+    match ty {
+        Type::Builtin(b) => builtin(*b).to_string(),
+        Type::List(t) => match &**t.type_() {
+            Type::Builtin(BuiltinType::Char) => "String".to_string(),
+            _ => format!("Vec<{}>", tref_ty(t)),
+        },
+        Type::Pointer(t) | Type::ConstPointer(t) => format!("GuestPtr<{}>", tref_ty(t)),
+        Type::Record(r) if r.is_tuple() => {
+            let members = r
+                .members
+                .iter()
+                .map(|m| tref_ty(&m.tref))
+                .collect::<Vec<_>>();
+            format!("({})", members.join(", "))
+        }
+        Type::Variant(v) if v.as_expected().is_some() => {
+            let (ok, err) = v.as_expected().unwrap();
+            let ok = ok.map(tref_ty).unwrap_or_else(|| "()".to_string());
+            let err = err.map(tref_ty).unwrap_or_else(|| "()".to_string());
+            format!("Result<{}, {}>", ok, err)
+        }
+        Type::Handle(_) => "u32".to_string(),
+        Type::Record(_) | Type::Variant(_) => {
+            unreachable!("anonymous {} types have no Rust representation", ty.kind())
+        }
+    }
+}
+
+fn define_type(out: &mut String, options: &Options, nt: &NamedType) {
+    let name = type_name(&nt.name);
+    out.push_str(&doc_comment(&nt.docs, "///", ""));
+    let ty = match &nt.tref {
+        TypeRef::Name(other) => {
+            writeln!(out, "pub type {} = {};", name, type_name(&other.name)).unwrap();
+            return;
+        }
+        TypeRef::Value(ty) => ty,
+    };
+    let sa = nt.mem_size_align();
+    match &**ty {
+        Type::Record(r) => {
+            if let Some(repr) = r.bitflags_repr() {
+                writeln!(out, "pub type {} = {};", name, int_repr(repr)).unwrap();
+                for (i, m) in r.members.iter().enumerate() {
+                    out.push_str(&doc_comment(&m.docs, "///", ""));
+                    writeln!(
+                        out,
+                        "pub const {}_{}: {} = 1 << {};",
+                        const_name(&nt.name),
+                        const_name(&m.name),
+                        name,
+                        i
+                    )
+                    .unwrap();
+                }
+                flags_names_impl(out, nt, "");
+                return;
+            }
+            let layout = r.member_layout();
+            let serde = serde_derives(options, &nt.tref);
+            let derives = [&["Clone", "Debug", "PartialEq"], serde].concat();
+            out.push_str(&attributes(options, &nt.name, &derives, ""));
+            if r.is_tuple() {
+                let members = r
+                    .members
+                    .iter()
+                    .map(|m| format!("pub {}", tref_ty(&m.tref)))
+                    .collect::<Vec<_>>();
+                writeln!(out, "pub struct {}({});", name, members.join(", ")).unwrap();
+            } else {
+                writeln!(out, "pub struct {} {{", name).unwrap();
+                for m in r.members.iter() {
+                    out.push_str(&doc_comment(&m.docs, "///", "    "));
+                    let field = ident(m.name.as_str());
+                    if !serde.is_empty() {
+                        out.push_str(&serde_rename(m.name.as_str(), &field, "    "));
+                    }
+                    writeln!(out, "    pub {}: {},", field, tref_ty(&m.tref)).unwrap();
+                }
+                out.push_str("}\n");
+            }
+            let field_name = |i: usize| {
+                if r.is_tuple() {
+                    i.to_string()
+                } else {
+                    ident(r.members[i].name.as_str())
+                }
+            };
+            guest_type_impl(
+                out,
+                &name,
+                sa.size,
+                sa.align,
+                |out| {
+                    let checked = r.members.iter().any(|m| m.size.is_some());
+                    out.push_str(if checked {
+                        "        let value = Self {\n"
+                    } else {
+                        "        Ok(Self {\n"
+                    });
+                    for (i, m) in layout.iter().enumerate() {
+                        writeln!(
+                            out,
+                            "            {}: <{}>::read(mem, field(offset, {})?)?,",
+                            field_name(i),
+                            tref_ty(&m.member.tref),
+                            m.offset
+                        )
+                        .unwrap();
+                    }
+                    if !checked {
+                        out.push_str("        })\n");
+                        return;
+                    }
+                    out.push_str("        };\n");
+                    let value = |id: &Id| format!("value.{}", ident(id.as_str()));
+                    for m in r.members.iter() {
+                        if let Some(size) = &m.size {
+                            let name = format!("{}.{}", nt.name.as_str(), m.name.as_str());
+                            writeln!(out, "        {};", size_check(&name, &m.name, size, &value))
+                                .unwrap();
+                        }
+                    }
+                    out.push_str("        Ok(value)\n");
+                },
+                |out| {
+                    for (i, m) in layout.iter().enumerate() {
+                        writeln!(
+                            out,
+                            "        self.{}.write(mem, field(offset, {})?)?;",
+                            field_name(i),
+                            m.offset
+                        )
+                        .unwrap();
+                    }
+                    out.push_str("        Ok(())\n");
+                },
+            );
+        }
+        Type::Variant(v) if v.is_enum() => {
+            define_enum(out, options, nt, v.tag_repr, &v.cases, sa.size, sa.align);
+            tensor_type_impl(out, nt);
+            tensor_convert_mod(out, nt);
+        }
+        Type::Variant(v) if v.as_expected().is_some() => {
+            writeln!(out, "pub type {} = {};", name, anonymous_ty(ty)).unwrap();
+        }
+        Type::Variant(v) => {
+            let repr = int_repr(v.tag_repr);
+            let payload = v.payload_offset();
+            let serde = serde_derives(options, &nt.tref);
+            let derives = [&["Clone", "Debug", "PartialEq"], serde].concat();
+            out.push_str(&attributes(options, &nt.name, &derives, ""));
+            writeln!(out, "pub enum {} {{", name).unwrap();
+            for c in v.cases.iter() {
+                out.push_str(&doc_comment(&c.docs, "///", "    "));
+                if !serde.is_empty() {
+                    out.push_str(&serde_rename(
+                        c.name.as_str(),
+                        &variant_name(&c.name),
+                        "    ",
+                    ));
+                }
+                match &c.tref {
+                    Some(tref) => {
+                        writeln!(out, "    {}({}),", variant_name(&c.name), tref_ty(tref)).unwrap()
+                    }
+                    None => writeln!(out, "    {},", variant_name(&c.name)).unwrap(),
+                }
+            }
+            out.push_str("}\n");
+            guest_type_impl(
+                out,
+                &name,
+                sa.size,
+                sa.align,
+                |out| {
+                    writeln!(out, "        let tag = {}::read(mem, offset)?;", repr).unwrap();
+                    out.push_str("        match tag {\n");
+                    for (i, c) in v.cases.iter().enumerate() {
+                        match &c.tref {
+                            Some(tref) => writeln!(
+                            out,
+                            "            {} => Ok({}::{}(<{}>::read(mem, field(offset, {})?)?)),",
+                            i,
+                            name,
+                            variant_name(&c.name),
+                            tref_ty(tref),
+                            payload
+                        )
+                            .unwrap(),
+                            None => writeln!(
+                                out,
+                                "            {} => Ok({}::{}),",
+                                i,
+                                name,
+                                variant_name(&c.name)
+                            )
+                            .unwrap(),
+                        }
+                    }
+                    writeln!(
+                    out,
+                    "            _ => Err(GuestError::InvalidEnumValue {{ ty: \"{}\", value: tag as u64 }}),",
+                    nt.name.as_str()
+                )
+                .unwrap();
+                    out.push_str("        }\n");
+                },
+                |out| {
+                    out.push_str("        match self {\n");
+                    for (i, c) in v.cases.iter().enumerate() {
+                        match &c.tref {
+                            Some(_) => {
+                                writeln!(
+                                    out,
+                                    "            {}::{}(payload) => {{",
+                                    name,
+                                    variant_name(&c.name)
+                                )
+                                .unwrap();
+                                writeln!(
+                                    out,
+                                    "                ({} as {}).write(mem, offset)?;",
+                                    i, repr
+                                )
+                                .unwrap();
+                                writeln!(
+                                    out,
+                                    "                payload.write(mem, field(offset, {})?)",
+                                    payload
+                                )
+                                .unwrap();
+                                out.push_str("            }\n");
+                            }
+                            None => writeln!(
+                                out,
+                                "            {}::{} => ({} as {}).write(mem, offset),",
+                                name,
+                                variant_name(&c.name),
+                                i,
+                                repr
+                            )
+                            .unwrap(),
+                        }
+                    }
+                    out.push_str("        }\n");
+                },
+            );
+        }
+        Type::Handle(_) => writeln!(out, "pub type {} = u32;", name).unwrap(),
+        Type::List(_) | Type::Pointer(_) | Type::ConstPointer(_) | Type::Builtin(_) => {
+            writeln!(out, "pub type {} = {};", name, anonymous_ty(ty)).unwrap();
+        }
+    }
+}
+
+fn guest_type_impl(
+    out: &mut String,
+    name: &str,
+    size: usize,
+    align: usize,
+    read: impl FnOnce(&mut String),
+    write: impl FnOnce(&mut String),
+) {
+    writeln!(out, "impl GuestType for {} {{", name).unwrap();
+    writeln!(out, "    const SIZE: u32 = {};", size).unwrap();
+    writeln!(out, "    const ALIGN: u32 = {};", align).unwrap();
+    out.push_str("    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {\n");
+    read(out);
+    out.push_str("    }\n");
+    out.push_str(
+        "    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {\n",
+    );
+    write(out);
+    out.push_str("    }\n");
+    out.push_str("}\n");
+}
+
+/// `Display` and `std::error::Error` for the error enum `nt`, so
+/// implementations can propagate it like any other error.
+fn define_error_impls(out: &mut String, nt: &NamedType) {
+    let name = type_name(&nt.name);
+    let cases = match &**nt.type_() {
+        Type::Variant(v) => &v.cases,
+        _ => unreachable!("error types are enums"),
+    };
+    writeln!(out, "impl std::fmt::Display for {} {{", name).unwrap();
+    out.push_str("    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {\n");
+    out.push_str("        f.write_str(match self {\n");
+    for c in cases.iter() {
+        writeln!(
+            out,
+            "            {}::{} => {:?},",
+            name,
+            variant_name(&c.name),
+            error_message(c)
+        )
+        .unwrap();
+    }
+    out.push_str("        })\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    writeln!(out, "impl std::error::Error for {} {{}}", name).unwrap();
+}
+
+fn define_enum(
+    out: &mut String,
+    options: &Options,
+    nt: &NamedType,
+    tag_repr: IntRepr,
+    cases: &[Case],
+    size: usize,
+    align: usize,
+) {
+    let name = type_name(&nt.name);
+    let repr = int_repr(tag_repr);
+    writeln!(out, "#[repr({})]", repr).unwrap();
+    let serde = serde_derives(options, &nt.tref);
+    let derives = [
+        &[
+            "Copy",
+            "Clone",
+            "Debug",
+            "Hash",
+            "Eq",
+            "PartialEq",
+            "Ord",
+            "PartialOrd",
+        ],
+        serde,
+    ]
+    .concat();
+    out.push_str(&attributes(options, &nt.name, &derives, ""));
+    writeln!(out, "pub enum {} {{", name).unwrap();
+    for c in cases.iter() {
+        out.push_str(&doc_comment(&c.docs, "///", "    "));
+        if !serde.is_empty() {
+            out.push_str(&serde_rename(
+                c.name.as_str(),
+                &variant_name(&c.name),
+                "    ",
+            ));
+        }
+        writeln!(out, "    {},", variant_name(&c.name)).unwrap();
+    }
+    out.push_str("}\n");
+    writeln!(out, "impl {} {{", name).unwrap();
+    out.push_str("    /// Every value of the enum, in order.\n");
+    writeln!(out, "    pub const ALL: [{}; {}] = [", name, cases.len()).unwrap();
+    for c in cases.iter() {
+        writeln!(out, "        {}::{},", name, variant_name(&c.name)).unwrap();
+    }
+    out.push_str("    ];\n");
+    writeln!(out, "    pub fn raw(&self) -> {} {{", repr).unwrap();
+    writeln!(out, "        *self as {}", repr).unwrap();
+    out.push_str("    }\n");
+    out.push_str("    /// The witx name of the value.\n");
+    out.push_str("    pub fn as_str(&self) -> &'static str {\n");
+    out.push_str("        match self {\n");
+    for c in cases.iter() {
+        writeln!(
+            out,
+            "            {}::{} => \"{}\",",
+            name,
+            variant_name(&c.name),
+            c.name.as_str()
+        )
+        .unwrap();
+    }
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("    /// The value named `name` in witx.\n");
+    out.push_str("    #[allow(clippy::should_implement_trait)]\n");
+    out.push_str("    pub fn from_str(name: &str) -> Option<Self> {\n");
+    out.push_str("        match name {\n");
+    for c in cases.iter() {
+        writeln!(
+            out,
+            "            \"{}\" => Some({}::{}),",
+            c.name.as_str(),
+            name,
+            variant_name(&c.name)
+        )
+        .unwrap();
+    }
+    out.push_str("            _ => None,\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    writeln!(
+        out,
+        "    pub fn from_raw(raw: {}) -> Result<Self, GuestError> {{",
+        repr
+    )
+    .unwrap();
+    out.push_str("        match raw {\n");
+    for (i, c) in cases.iter().enumerate() {
+        writeln!(
+            out,
+            "            {} => Ok({}::{}),",
+            i,
+            name,
+            variant_name(&c.name)
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "            _ => Err(GuestError::InvalidEnumValue {{ ty: \"{}\", value: raw as u64 }}),",
+        nt.name.as_str()
+    )
+    .unwrap();
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    guest_type_impl(
+        out,
+        &name,
+        size,
+        align,
+        |out| {
+            writeln!(out, "        Self::from_raw({}::read(mem, offset)?)", repr).unwrap();
+        },
+        |out| out.push_str("        self.raw().write(mem, offset)\n"),
+    );
+}
+
+/// Most elements in an arbitrary list.
+const ARBITRARY_LIST_LEN: usize = 16;
+/// Largest arbitrary integer, and most elements in an arbitrary list, that a
+/// declared size depends on, so sized lists stay small.
+const ARBITRARY_SIZE_OPERAND: usize = 8;
+const ARBITRARY_SIZE_OPERAND_LEN: usize = 4;
+
+/// The `proptest::arbitrary::Arbitrary` impl of the record or variant `nt`,
+/// or a strategy function for flags, which are aliases of integers.
+fn define_arbitrary(out: &mut String, nt: &NamedType) {
+    let ty = match &nt.tref {
+        TypeRef::Name(_) => return,
+        TypeRef::Value(ty) => ty,
+    };
+    let name = type_name(&nt.name);
+    let body = match &**ty {
+        Type::Record(r) if r.bitflags_repr().is_some() => {
+            let mask = (1u128 << r.members.len()) - 1;
+            writeln!(
+                out,
+                "/// Values of `{}` with only defined flags set.",
+                nt.name.as_str()
+            )
+            .unwrap();
+            out.push_str("#[cfg(feature = \"proptest\")]\n");
+            writeln!(
+                out,
+                "pub fn {}_strategy() -> proptest::strategy::BoxedStrategy<{}> {{",
+                ident(nt.name.as_str()),
+                name
+            )
+            .unwrap();
+            out.push_str("    use proptest::prelude::*;\n");
+            writeln!(
+                out,
+                "    any::<{}>().prop_map(|flags| flags & {:#x}).boxed()",
+                name, mask
+            )
+            .unwrap();
+            out.push_str("}\n");
+            return;
+        }
+        Type::Record(r) => record_strategy(r),
+        Type::Variant(v) if v.is_enum() => {
+            if v.cases.is_empty() {
+                return;
+            }
+            "proptest::sample::select(Self::ALL.to_vec()).boxed()".to_string()
+        }
+        Type::Variant(v) if v.as_expected().is_some() => return,
+        Type::Variant(v) => {
+            let mut body = "prop_oneof![\n".to_string();
+            for c in v.cases.iter() {
+                match &c.tref {
+                    Some(tref) => writeln!(
+                        body,
+                        "    {}.prop_map(Self::{}),",
+                        strategy(tref, false),
+                        variant_name(&c.name)
+                    ),
+                    None => writeln!(body, "    Just(Self::{}),", variant_name(&c.name)),
+                }
+                .unwrap();
+            }
+            body.push_str("]\n.boxed()");
+            body
+        }
+        _ => return,
+    };
+    out.push_str("#[cfg(feature = \"proptest\")]\n");
+    writeln!(out, "impl proptest::arbitrary::Arbitrary for {} {{", name).unwrap();
+    out.push_str("    type Parameters = ();\n");
+    out.push_str("    type Strategy = proptest::strategy::BoxedStrategy<Self>;\n");
+    out.push_str("    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {\n");
+    out.push_str("        use proptest::prelude::*;\n");
+    for line in body.lines() {
+        writeln!(out, "        {}", line).unwrap();
+    }
+    out.push_str("    }\n");
+    out.push_str("}\n");
+}
+
+/// A strategy for a record. Lists with a declared size are generated after
+/// the fields their size depends on, with exactly that size.
+fn record_strategy(r: &RecordDatatype) -> String {
+    let binding = |i: usize, m: &RecordMember| {
+        if r.is_tuple() {
+            format!("m{}", i)
+        } else {
+            ident(m.name.as_str())
+        }
+    };
+    let mut operands = Vec::new();
+    for m in r.members.iter() {
+        if let Some(size) = &m.size {
+            size_operands(size, &mut operands);
+        }
+    }
+    let (mut free, mut sized) = (Vec::new(), Vec::new());
+    for (i, m) in r.members.iter().enumerate() {
+        if m.size.is_some() {
+            sized.push((binding(i, m), m));
+        } else {
+            let small = operands.contains(&m.name);
+            free.push((binding(i, m), strategy(&m.tref, small)));
+        }
+    }
+    let bindings = r
+        .members
+        .iter()
+        .enumerate()
+        .map(|(i, m)| binding(i, m))
+        .collect::<Vec<_>>();
+    let value = if r.is_tuple() {
+        format!("Self{}", tuple(&bindings))
+    } else {
+        format!("Self {{ {} }}", bindings.join(", "))
+    };
+    let free_bindings = free.iter().map(|(b, _)| b.clone()).collect::<Vec<_>>();
+    let free_strategies = free.iter().map(|(_, s)| s.clone()).collect::<Vec<_>>();
+    let free_strategy = if free.is_empty() {
+        "Just(())".to_string()
+    } else {
+        tuple(&free_strategies)
+    };
+    if sized.is_empty() {
+        return format!(
+            "{}\n    .prop_map(|{}| {})\n    .boxed()",
+            free_strategy,
+            tuple(&free_bindings),
+            value
+        );
+    }
+    let mut body = format!(
+        "{}\n    .prop_flat_map(|{}| {{\n",
+        free_strategy,
+        tuple(&free_bindings)
+    );
+    let value_of = |id: &Id| ident(id.as_str());
+    let mut strategies = free_bindings
+        .iter()
+        .map(|b| format!("Just({})", b))
+        .collect::<Vec<_>>();
+    for (b, m) in sized.iter() {
+        let len = match m.size.as_ref().unwrap() {
+            SizeExpr::Const(n) => n.to_string(),
+            size => format!("{}.unwrap_or(0) as usize", size_expr(size, &value_of)),
+        };
+        writeln!(
+            body,
+            "        let {}_len = {};",
+            b.trim_start_matches("r#"),
+            len
+        )
+        .unwrap();
+        strategies.push(sized_list_strategy(
+            &m.tref,
+            &format!("{}_len", b.trim_start_matches("r#")),
+        ));
+    }
+    writeln!(body, "        {}", tuple(&strategies)).unwrap();
+    body.push_str("    })\n");
+    let bindings = free_bindings
+        .into_iter()
+        .chain(sized.iter().map(|(b, _)| b.clone()))
+        .collect::<Vec<_>>();
+    write!(
+        body,
+        "    .prop_map(|{}| {})\n    .boxed()",
+        tuple(&bindings),
+        value
+    )
+    .unwrap();
+    body
+}
+
+/// The fields that `size` depends on.
+fn size_operands(size: &SizeExpr, operands: &mut Vec<Id>) {
+    match size {
+        SizeExpr::Const(_) => {}
+        SizeExpr::Value(id) | SizeExpr::Len(id) | SizeExpr::Product(id) => {
+            operands.push(id.clone())
+        }
+        SizeExpr::TensorSize { dimensions, ty } => {
+            operands.push(dimensions.clone());
+            operands.push(ty.clone());
+        }
+        SizeExpr::Mul(exprs) | SizeExpr::Add(exprs) => {
+            for e in exprs.iter() {
+                size_operands(e, operands);
+            }
+        }
+    }
+}
+
+/// A strategy for the list `tref` with `len` elements, or bytes of a string.
+fn sized_list_strategy(tref: &TypeRef, len: &str) -> String {
+    match &**tref.type_() {
+        Type::List(t) => match &**t.type_() {
+            Type::Builtin(BuiltinType::Char) => format!(
+                "proptest::collection::vec(proptest::char::range(' ', '~'), {})\n            \
+                 .prop_map(|c| c.into_iter().collect::<String>())",
+                len
+            ),
+            _ => format!("proptest::collection::vec({}, {})", strategy(t, false), len),
+        },
+        _ => unreachable!("only lists have sizes"),
+    }
+}
+
+/// A strategy for values of `tref`. Integers and lists are kept `small` if
+/// a declared size depends on them.
+fn strategy(tref: &TypeRef, small: bool) -> String {
+    let nt = match tref.definition() {
+        Some(nt) => nt,
+        None => return anonymous_strategy(tref.type_(), small),
+    };
+    match &**nt.type_() {
+        Type::Record(r) if r.bitflags_repr().is_some() => {
+            format!("{}_strategy()", ident(nt.name.as_str()))
+        }
+        Type::Record(_) => format!("any::<{}>()", type_name(&nt.name)),
+        Type::Variant(v) if v.as_expected().is_none() => {
+            format!("any::<{}>()", type_name(&nt.name))
+        }
+        ty => anonymous_strategy(ty, small),
+    }
+}
+
+fn anonymous_strategy(ty: &Type, small: bool) -> String {
+    match ty {
+        Type::Builtin(b @ (BuiltinType::Char | BuiltinType::F32 | BuiltinType::F64)) => {
+            format!("any::<{}>()", builtin(*b))
+        }
+        Type::Builtin(b) if small => format!("0..={}{}", ARBITRARY_SIZE_OPERAND, builtin(*b)),
+        Type::Builtin(b) => format!("any::<{}>()", builtin(*b)),
+        Type::List(t) => match &**t.type_() {
+            Type::Builtin(BuiltinType::Char) => format!(
+                "proptest::collection::vec(any::<char>(), 0..={})\n    \
+                 .prop_map(|c| c.into_iter().collect::<String>())",
+                ARBITRARY_LIST_LEN
+            ),
+            _ => format!(
+                "proptest::collection::vec({}, 0..={})",
+                strategy(t, small),
+                if small {
+                    ARBITRARY_SIZE_OPERAND_LEN
+                } else {
+                    ARBITRARY_LIST_LEN
+                }
+            ),
+        },
+        Type::Pointer(_) | Type::ConstPointer(_) => {
+            "any::<u32>().prop_map(GuestPtr::new)".to_string()
+        }
+        Type::Record(r) if r.is_tuple() => tuple(
+            &r.members
+                .iter()
+                .map(|m| strategy(&m.tref, small))
+                .collect::<Vec<_>>(),
+        ),
+        Type::Variant(v) if v.as_expected().is_some() => {
+            let (ok, err) = v.as_expected().unwrap();
+            let arm = |t: Option<&TypeRef>| {
+                t.map(|t| strategy(t, small))
+                    .unwrap_or_else(|| "Just(())".to_string())
+            };
+            format!(
+                "prop_oneof![{}.prop_map(Ok), {}.prop_map(Err)]",
+                arm(ok),
+                arm(err)
+            )
+        }
+        Type::Handle(_) => "any::<u32>()".to_string(),
+        Type::Record(_) | Type::Variant(_) => {
+            unreachable!("anonymous {} types have no Rust representation", ty.kind())
+        }
+    }
+}
+
+/// The name of the trait implementing `module`.
+pub(super) fn trait_name(module: &Module) -> String {
+    type_name(&module.name)
+}
+
+pub(super) fn method_sig(func: &InterfaceFunc, engine: Engine) -> String {
+    let mut params = vec![
+        "&mut self".to_string(),
+        "mem: &mut GuestMemory<'_>".to_string(),
+    ];
+    for p in func.params.iter() {
+        params.push(format!("{}: {}", ident(p.name.as_str()), tref_ty(&p.tref)));
+    }
+    let ret = if func.noreturn {
+        format!(" -> {}", engine.trap())
+    } else {
+        match func.results.first() {
+            Some(r) => format!(" -> {}", tref_ty(&r.tref)),
+            None => String::new(),
+        }
+    };
+    format!(
+        "fn {}({}){}",
+        ident(func.name.as_str()),
+        params.join(", "),
+        ret
+    )
+}
+
+fn define_module(out: &mut String, module: &Module, options: &Options, engine: Engine) {
+    writeln!(out, "pub mod {} {{", ident(module.name.as_str())).unwrap();
+    out.push_str("    #[allow(unused_imports)]\n");
+    out.push_str("    use super::*;\n\n");
+    out.push_str(&doc_comment(&module.docs, "///", "    "));
+    writeln!(out, "    pub trait {} {{", trait_name(module)).unwrap();
+    for f in module.funcs() {
+        out.push_str(&doc_comment(&f.docs, "///", "        "));
+        writeln!(out, "        {};", method_sig(&f, engine)).unwrap();
+    }
+    out.push_str("    }\n\n");
+
+    match engine {
+        Engine::Wasmtime => define_linker(out, module),
+        Engine::Wasmer => define_imports(out, module),
+    }
+    if options.mocks {
+        out.push('\n');
+        define_mock(out, module, engine);
+    }
+    if options.tracing {
+        out.push('\n');
+        define_traced(out, module, engine);
+    }
+    if options.limits {
+        define_limited(out, module, engine);
+    }
+    out.push_str("}\n");
+}
+
+/// The `add_to_linker` functions adding the functions of `module` to a
+/// `wasmtime::Linker`.
+fn define_linker(out: &mut String, module: &Module) {
+    writeln!(
+        out,
+        "    /// Add the functions of `{}` to `linker`, implemented by the",
+        module.name.as_str()
+    )
+    .unwrap();
+    out.push_str("    /// value that `get` returns from the store's data. Invalid arguments\n");
+    out.push_str("    /// trap.\n");
+    out.push_str("    pub fn add_to_linker<T: 'static, U>(\n");
+    out.push_str("        linker: &mut Linker<T>,\n");
+    out.push_str("        get: impl Fn(&mut T) -> &mut U + Send + Sync + Copy + 'static,\n");
+    out.push_str("    ) -> wasmtime::Result<()>\n");
+    out.push_str("    where\n");
+    writeln!(out, "        U: {},", trait_name(module)).unwrap();
+    out.push_str("    {\n");
+    out.push_str("        add_to_linker_with(linker, get, InvalidArguments::Trap)\n");
+    out.push_str("    }\n\n");
+    out.push_str("    /// [`add_to_linker`], handling invalid arguments as `invalid` says.\n");
+    out.push_str("    pub fn add_to_linker_with<T: 'static, U>(\n");
+    out.push_str("        linker: &mut Linker<T>,\n");
+    out.push_str("        get: impl Fn(&mut T) -> &mut U + Send + Sync + Copy + 'static,\n");
+    out.push_str("        invalid: InvalidArguments,\n");
+    out.push_str("    ) -> wasmtime::Result<()>\n");
+    out.push_str("    where\n");
+    writeln!(out, "        U: {},", trait_name(module)).unwrap();
+    out.push_str("    {\n");
+    let mut glue = String::new();
+    let mut uses_invalid = false;
+    for f in module.funcs() {
+        uses_invalid |= define_glue(&mut glue, module, &f, Engine::Wasmtime);
+    }
+    if !uses_invalid {
+        out.push_str("        let _ = invalid;\n");
+    }
+    out.push_str(&glue);
+    out.push_str("        Ok(())\n");
+    out.push_str("    }\n");
+}
+
+/// The `add_to_imports` functions adding the functions of `module` to a
+/// `wasmer::Imports`.
+fn define_imports(out: &mut String, module: &Module) {
+    writeln!(
+        out,
+        "    /// Add the functions of `{}` to `imports`, implemented by the",
+        module.name.as_str()
+    )
+    .unwrap();
+    out.push_str("    /// value that `get` returns from the data of `env`. Invalid arguments\n");
+    out.push_str("    /// trap.\n");
+    out.push_str("    pub fn add_to_imports<T: Send + 'static, U>(\n");
+    out.push_str("        store: &mut impl AsStoreMut,\n");
+    out.push_str("        env: &FunctionEnv<HostEnv<T>>,\n");
+    out.push_str("        imports: &mut Imports,\n");
+    out.push_str("        get: impl Fn(&mut T) -> &mut U + Send + Sync + Copy + 'static,\n");
+    out.push_str("    ) where\n");
+    writeln!(out, "        U: {},", trait_name(module)).unwrap();
+    out.push_str("    {\n");
+    out.push_str("        add_to_imports_with(store, env, imports, get, InvalidArguments::Trap)\n");
+    out.push_str("    }\n\n");
+    out.push_str("    /// [`add_to_imports`], handling invalid arguments as `invalid` says.\n");
+    out.push_str("    pub fn add_to_imports_with<T: Send + 'static, U>(\n");
+    out.push_str("        store: &mut impl AsStoreMut,\n");
+    out.push_str("        env: &FunctionEnv<HostEnv<T>>,\n");
+    out.push_str("        imports: &mut Imports,\n");
+    out.push_str("        get: impl Fn(&mut T) -> &mut U + Send + Sync + Copy + 'static,\n");
+    out.push_str("        invalid: InvalidArguments,\n");
+    out.push_str("    ) where\n");
+    writeln!(out, "        U: {},", trait_name(module)).unwrap();
+    out.push_str("    {\n");
+    let mut glue = String::new();
+    let mut uses_invalid = false;
+    for f in module.funcs() {
+        uses_invalid |= define_glue(&mut glue, module, &f, Engine::Wasmer);
+    }
+    if !uses_invalid {
+        out.push_str("        let _ = invalid;\n");
+    }
+    out.push_str("        let mut exports = Exports::new();\n");
+    out.push_str(&glue);
+    writeln!(
+        out,
+        "        imports.register_namespace(\"{}\", exports);",
+        module.name.as_str()
+    )
+    .unwrap();
+    out.push_str("    }\n");
+}
+
+/// A `mock` module implementing the trait of `module` by recording every
+/// call and returning results queued up by the test.
+fn define_mock(out: &mut String, module: &Module, engine: Engine) {
+    let trait_name = trait_name(module);
+    out.push_str("    /// A fake implementation for testing guests without a real host.\n");
+    out.push_str("    pub mod mock {\n");
+    out.push_str("        use super::*;\n\n");
+
+    writeln!(out, "        /// A call received by [`Mock`].").unwrap();
+    out.push_str("        #[derive(Clone, Debug, PartialEq)]\n");
+    out.push_str("        pub enum Call {\n");
+    for f in module.funcs() {
+        let variant = type_name(&f.name);
+        if f.params.is_empty() {
+            writeln!(out, "            {},", variant).unwrap();
+            continue;
+        }
+        writeln!(out, "            {} {{", variant).unwrap();
+        for p in f.params.iter() {
+            writeln!(
+                out,
+                "                {}: {},",
+                ident(p.name.as_str()),
+                tref_ty(&p.tref)
+            )
+            .unwrap();
+        }
+        out.push_str("            },\n");
+    }
+    out.push_str("        }\n\n");
+
+    writeln!(
+        out,
+        "        /// Implements [`{}`] by recording each call in `calls` and",
+        trait_name
+    )
+    .unwrap();
+    out.push_str("        /// returning the next result queued for the function. A call\n");
+    out.push_str("        /// with no queued result panics.\n");
+    out.push_str("        #[derive(Debug, Default)]\n");
+    out.push_str("        pub struct Mock {\n");
+    out.push_str("            pub calls: Vec<Call>,\n");
+    for f in module.funcs() {
+        if let (false, Some(r)) = (f.noreturn, f.results.first()) {
+            writeln!(
+                out,
+                "            pub {}: std::collections::VecDeque<{}>,",
+                ident(f.name.as_str()),
+                tref_ty(&r.tref)
+            )
+            .unwrap();
+        }
+    }
+    out.push_str("        }\n\n");
+
+    writeln!(out, "        impl {} for Mock {{", trait_name).unwrap();
+    for f in module.funcs() {
+        let sig = method_sig(&f, engine).replacen("mem: ", "_mem: ", 1);
+        writeln!(out, "            {} {{", sig).unwrap();
+        let variant = type_name(&f.name);
+        if f.params.is_empty() {
+            writeln!(out, "                self.calls.push(Call::{});", variant).unwrap();
+        } else {
+            let fields = f
+                .params
+                .iter()
+                .map(|p| ident(p.name.as_str()))
+                .collect::<Vec<_>>();
+            writeln!(
+                out,
+                "                self.calls.push(Call::{} {{ {} }});",
+                variant,
+                fields.join(", ")
+            )
+            .unwrap();
+        }
+        if f.noreturn {
+            writeln!(
+                out,
+                "                {}(\"`{}` does not return\")",
+                engine.trap_msg(),
+                f.name.as_str()
+            )
+            .unwrap();
+        } else if !f.results.is_empty() {
+            let field = ident(f.name.as_str());
+            writeln!(
+                out,
+                "                self.{}.pop_front().expect(\"no result queued for `{}`\")",
+                field,
+                f.name.as_str()
+            )
+            .unwrap();
+        }
+        out.push_str("            }\n");
+    }
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+}
+
+/// A `traced` module wrapping implementations of the trait of `module`,
+/// emitting a span for each call.
+fn define_traced(out: &mut String, module: &Module, engine: Engine) {
+    let trait_name = trait_name(module);
+    out.push_str("    /// Tracing instrumentation for implementations.\n");
+    out.push_str("    pub mod traced {\n");
+    out.push_str("        use super::*;\n\n");
+    writeln!(
+        out,
+        "        /// Implements [`{}`] by calling `inner` in a span named after the",
+        trait_name
+    )
+    .unwrap();
+    out.push_str("        /// function, with a summary of its arguments, and emitting an event\n");
+    out.push_str("        /// with the time it took and any error once it returns. Lists are\n");
+    out.push_str("        /// summarized by their length.\n");
+    out.push_str("        #[derive(Debug, Default)]\n");
+    out.push_str("        pub struct Traced<T> {\n");
+    out.push_str("            pub inner: T,\n");
+    out.push_str("        }\n\n");
+    out.push_str("        impl<T> Traced<T> {\n");
+    out.push_str("            pub fn new(inner: T) -> Self {\n");
+    out.push_str("                Traced { inner }\n");
+    out.push_str("            }\n");
+    out.push_str("        }\n\n");
+
+    writeln!(out, "        impl<T: {0}> {0} for Traced<T> {{", trait_name).unwrap();
+    for f in module.funcs() {
+        writeln!(out, "            {} {{", method_sig(&f, engine)).unwrap();
+        let mut fields = vec![format!("\"{}::{}\"", module.name.as_str(), f.name.as_str())];
+        for p in f.params.iter() {
+            let name = ident(p.name.as_str());
+            summarize(&mut fields, &name, &name, &p.tref);
+        }
+        writeln!(
+            out,
+            "                let span = tracing::info_span!({});",
+            fields.join(", ")
+        )
+        .unwrap();
+        out.push_str("                let _enter = span.enter();\n");
+        out.push_str("                let start = std::time::Instant::now();\n");
+        let args = std::iter::once("mem".to_string())
+            .chain(f.params.iter().map(|p| ident(p.name.as_str())))
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "                let result = self.inner.{}({});",
+            ident(f.name.as_str()),
+            args.join(", ")
+        )
+        .unwrap();
+        let expected = f.results.first().is_some_and(
+            |r| matches!(&**r.tref.type_(), Type::Variant(v) if v.as_expected().is_some()),
+        );
+        if f.noreturn {
+            out.push_str(
+                "                tracing::info!(elapsed = ?start.elapsed(), error = %result, \"exited\");\n",
+            );
+        } else if expected {
+            out.push_str("                match &result {\n");
+            out.push_str(
+                "                    Ok(_) => tracing::info!(elapsed = ?start.elapsed(), \"returned\"),\n",
+            );
+            out.push_str(
+                "                    Err(e) => tracing::info!(elapsed = ?start.elapsed(), error = ?e, \"failed\"),\n",
+            );
+            out.push_str("                }\n");
+        } else {
+            out.push_str(
+                "                tracing::info!(elapsed = ?start.elapsed(), \"returned\");\n",
+            );
+        }
+        out.push_str("                result\n");
+        out.push_str("            }\n");
+    }
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+}
+
+/// Add span fields summarizing `expr`, a `tref`, to `fields`. Lists are
+/// summarized by their length, including in records.
+fn summarize(fields: &mut Vec<String>, name: &str, expr: &str, tref: &TypeRef) {
+    match &**tref.type_() {
+        Type::List(elem) if !matches!(&**elem.type_(), Type::Builtin(BuiltinType::Char)) => {
+            fields.push(format!("{}.len = {}.len()", name, expr))
+        }
+        Type::Record(r) if !r.is_tuple() && r.bitflags_repr().is_none() && has_list(tref) => {
+            for m in r.members.iter() {
+                let member = ident(m.name.as_str());
+                summarize(
+                    fields,
+                    &format!("{}.{}", name, member),
+                    &format!("{}.{}", expr, member),
+                    &m.tref,
+                );
+            }
+        }
+        _ => fields.push(format!("{} = ?{}", name, expr)),
+    }
+}
+
+/// Whether a value of `tref` has a list, other than a string, in it.
+fn has_list(tref: &TypeRef) -> bool {
+    match &**tref.type_() {
+        Type::List(elem) => !matches!(&**elem.type_(), Type::Builtin(BuiltinType::Char)),
+        Type::Record(r) => r.members.iter().any(|m| has_list(&m.tref)),
+        Type::Variant(v) => v
+            .cases
+            .iter()
+            .any(|c| c.tref.as_ref().is_some_and(has_list)),
+        _ => false,
+    }
+}
+
+/// Types whose values are limited by [`define_limited`].
+const GRAPH_BUILDER: &str = "graph_builder";
+const TENSOR: &str = "tensor";
+const EXECUTION_CONTEXT: &str = "graph_execution_context";
+/// The case of an error enum returned when a limit is exceeded.
+const RESOURCE_EXHAUSTED: &str = "resource_exhausted";
+
+/// An expression for the bytes of graph builders in `expr`, a `tref`, if it
+/// has any.
+fn graph_size(expr: &str, tref: &TypeRef) -> Option<String> {
+    if let TypeRef::Name(nt) = tref {
+        if nt.name.as_str() == GRAPH_BUILDER {
+            return Some(format!("{}.len()", expr));
+        }
+    }
+    match &**tref.type_() {
+        Type::List(elem) => {
+            let size = graph_size("b", elem)?;
+            Some(format!("{}.iter().map(|b| {}).sum::<usize>()", expr, size))
+        }
+        _ => None,
+    }
+}
+
+/// The expression for the error `case` of `func`'s error enum, if it has
+/// one.
+pub(super) fn error_case(func: &InterfaceFunc, case: &str) -> Option<String> {
+    let r = func.results.first()?;
+    let (_, err) = match &**r.tref.type_() {
+        Type::Variant(v) => v.as_expected()?,
+        _ => return None,
+    };
+    let nt = match err? {
+        TypeRef::Name(nt) => nt,
+        TypeRef::Value(_) => return None,
+    };
+    match &**nt.type_() {
+        Type::Variant(v) if v.is_enum() => {
+            let case = v.cases.iter().find(|c| c.name.as_str() == case)?;
+            Some(format!(
+                "{}::{}",
+                type_name(&nt.name),
+                variant_name(&case.name)
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Whether `func` returns a new execution context.
+fn creates_execution_context(func: &InterfaceFunc) -> bool {
+    let ok = func.results.first().and_then(|r| match &**r.tref.type_() {
+        Type::Variant(v) => v.as_expected().and_then(|(ok, _)| ok),
+        _ => None,
+    });
+    matches!(ok, Some(TypeRef::Name(nt)) if nt.name.as_str() == EXECUTION_CONTEXT)
+}
+
+/// A `limited` module wrapping implementations of the trait of `module`,
+/// failing calls exceeding limits with the `resource_exhausted` error.
+/// Nothing is generated for modules without functions that can be limited.
+fn define_limited(out: &mut String, module: &Module, engine: Engine) {
+    // The checks of each function, as conditions for failing it.
+    let mut checks = Vec::new();
+    let (mut graphs, mut tensors, mut contexts) = (false, false, false);
+    for f in module.funcs() {
+        let mut conditions = Vec::new();
+        if error_case(&f, RESOURCE_EXHAUSTED).is_some() {
+            for p in f.params.iter() {
+                let name = ident(p.name.as_str());
+                if let Some(size) = graph_size(&name, &p.tref) {
+                    graphs = true;
+                    conditions.push(format!(
+                        "matches!(self.limits.max_graph_size, Some(max) if {} > max)",
+                        size
+                    ));
+                }
+                if matches!(&p.tref, TypeRef::Name(nt) if nt.name.as_str() == TENSOR) {
+                    tensors = true;
+                    conditions.push(format!(
+                        "matches!(self.limits.max_tensor_bytes, Some(max) if {}.data.len() > max)",
+                        name
+                    ));
+                }
+            }
+            if creates_execution_context(&f) {
+                contexts = true;
+                conditions.push(
+                    "matches!(self.limits.max_execution_contexts, Some(max) if self.execution_contexts >= max)"
+                        .to_string(),
+                );
+            }
+        }
+        checks.push((f, conditions));
+    }
+    if !graphs && !tensors && !contexts {
+        return;
+    }
+
+    let trait_name = trait_name(module);
+    out.push('\n');
+    out.push_str("    /// Resource limits for implementations.\n");
+    out.push_str("    pub mod limited {\n");
+    out.push_str("        use super::*;\n\n");
+    out.push_str("        /// Limits enforced by [`Limited`], where `None` is unlimited.\n");
+    out.push_str("        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]\n");
+    out.push_str("        pub struct Limits {\n");
+    if graphs {
+        out.push_str("            /// Bytes of graph builders passed to a call.\n");
+        out.push_str("            pub max_graph_size: Option<usize>,\n");
+    }
+    if tensors {
+        out.push_str("            /// Bytes of data in a tensor.\n");
+        out.push_str("            pub max_tensor_bytes: Option<usize>,\n");
+    }
+    if contexts {
+        out.push_str("            /// Execution contexts created, which are never released.\n");
+        out.push_str("            pub max_execution_contexts: Option<usize>,\n");
+    }
+    out.push_str("        }\n\n");
+    writeln!(
+        out,
+        "        /// Implements [`{}`] by calling `inner`, unless the call exceeds",
+        trait_name
+    )
+    .unwrap();
+    out.push_str("        /// `limits`, which fails it with `resource_exhausted` instead.\n");
+    out.push_str("        #[derive(Debug, Default)]\n");
+    out.push_str("        pub struct Limited<T> {\n");
+    out.push_str("            pub inner: T,\n");
+    out.push_str("            pub limits: Limits,\n");
+    if contexts {
+        out.push_str("            /// Execution contexts created so far.\n");
+        out.push_str("            pub execution_contexts: usize,\n");
+    }
+    out.push_str("        }\n\n");
+    out.push_str("        impl<T> Limited<T> {\n");
+    out.push_str("            pub fn new(inner: T, limits: Limits) -> Self {\n");
+    out.push_str("                Limited {\n");
+    out.push_str("                    inner,\n");
+    out.push_str("                    limits,\n");
+    if contexts {
+        out.push_str("                    execution_contexts: 0,\n");
+    }
+    out.push_str("                }\n");
+    out.push_str("            }\n");
+    out.push_str("        }\n\n");
+
+    writeln!(
+        out,
+        "        impl<T: {0}> {0} for Limited<T> {{",
+        trait_name
+    )
+    .unwrap();
+    for (f, conditions) in checks {
+        writeln!(out, "            {} {{", method_sig(&f, engine)).unwrap();
+        for condition in conditions.iter() {
+            writeln!(out, "                if {} {{", condition).unwrap();
+            writeln!(
+                out,
+                "                    return Err({});",
+                error_case(&f, RESOURCE_EXHAUSTED).unwrap()
+            )
+            .unwrap();
+            out.push_str("                }\n");
+        }
+        let args = std::iter::once("mem".to_string())
+            .chain(f.params.iter().map(|p| ident(p.name.as_str())))
+            .collect::<Vec<_>>();
+        let call = format!("self.inner.{}({})", ident(f.name.as_str()), args.join(", "));
+        if creates_execution_context(&f) && !conditions.is_empty() {
+            writeln!(out, "                let result = {};", call).unwrap();
+            out.push_str("                if result.is_ok() {\n");
+            out.push_str("                    self.execution_contexts += 1;\n");
+            out.push_str("                }\n");
+            out.push_str("                result\n");
+        } else {
+            writeln!(out, "                {}", call).unwrap();
+        }
+        out.push_str("            }\n");
+    }
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+}
+
+/// Cases of error enums returned for invalid arguments.
+const INVALID_ARGUMENT: &[&str] = &["invalid_argument", "inval"];
+
+/// The glue registering `func`, which returns whether it uses the policy for
+/// invalid arguments.
+fn define_glue(out: &mut String, module: &Module, func: &InterfaceFunc, engine: Engine) -> bool {
+    let (params, results) = func.wasm_signature();
+    let mut args = vec![match engine {
+        Engine::Wasmtime => "mut caller: Caller<'_, T>".to_string(),
+        Engine::Wasmer => "mut ctx: FunctionEnvMut<HostEnv<T>>".to_string(),
+    }];
+    for (i, ty) in params.iter().enumerate() {
+        args.push(format!("arg{}: {}", i, ty));
+    }
+    let ret = match results.first() {
+        Some(ty) => ty.to_string(),
+        None => "()".to_string(),
+    };
+    match engine {
+        Engine::Wasmtime => writeln!(
+            out,
+            "        linker.func_wrap(\n            \"{}\",\n            \"{}\",\n            move |{}| -> wasmtime::Result<{}> {{",
+            module.name.as_str(),
+            func.name.as_str(),
+            args.join(", "),
+            ret
+        ),
+        Engine::Wasmer => writeln!(
+            out,
+            "        exports.insert(\n            \"{}\",\n            Function::new_typed_with_env(\n                store,\n                env,\n                move |{}| -> Result<{}, RuntimeError> {{",
+            func.name.as_str(),
+            args.join(", "),
+            ret
+        ),
+    }
+    .unwrap();
+    let mut gen = GlueGen {
+        noreturn: func.noreturn,
+        invalid: INVALID_ARGUMENT
+            .iter()
+            .find_map(|case| error_case(func, case)),
+        src: String::new(),
+        blocks: Vec::new(),
+        block_results: Vec::new(),
+        payload: None,
+        tmp: 0,
+    };
+    match engine {
+        Engine::Wasmtime => {
+            gen.line("let memory = caller_memory(&mut caller)?;");
+            gen.line("let (bytes, data) = memory.data_and_store_mut(&mut caller);");
+            gen.line("let mut mem = GuestMemory::new(bytes);");
+            gen.line("let imp = get(data);");
+        }
+        Engine::Wasmer => {
+            gen.line("let (env, store) = ctx.data_and_store_mut();");
+            gen.line("let memory = env_memory(env)?;");
+            gen.line("let view = memory.view(&store);");
+            gen.line("// SAFETY: the guest is suspended in this call, and nothing else");
+            gen.line("// accesses its memory until the call returns.");
+            gen.line("let mut mem = GuestMemory::new(unsafe { view.data_unchecked_mut() });");
+            gen.line("let imp = get(&mut env.data);");
+        }
+    }
+    func.call_interface(&module.name, &mut gen);
+    let indent = match engine {
+        Engine::Wasmtime => "                ",
+        Engine::Wasmer => "                    ",
+    };
+    for line in gen.src.lines() {
+        writeln!(out, "{}{}", indent, line).unwrap();
+    }
+    match engine {
+        Engine::Wasmtime => {
+            out.push_str("            },\n");
+            out.push_str("        )?;\n");
+        }
+        Engine::Wasmer => {
+            out.push_str("                },\n");
+            out.push_str("            ),\n");
+            out.push_str("        );\n");
+        }
+    }
+    gen.invalid.is_some() && !func.params.is_empty()
+}
+
+/// Generates the body of a `func_wrap` closure from the instructions of
+/// [`InterfaceFunc::call_interface`]. Operands are Rust expressions, and
+/// `src` is unindented.
+struct GlueGen {
+    noreturn: bool,
+    /// The error returned for invalid arguments, if the function has one.
+    invalid: Option<String>,
+    src: String,
+    blocks: Vec<(String, Option<String>)>,
+    block_results: Vec<(String, Option<String>, Option<String>)>,
+    /// Name bound to the payload of the variant matched in the current block.
+    payload: Option<String>,
+    tmp: usize,
+}
+
+impl GlueGen {
+    fn line(&mut self, line: &str) {
+        self.src.push_str(line);
+        self.src.push('\n');
+    }
+
+    fn tmp(&mut self, prefix: &str) -> String {
+        self.tmp += 1;
+        format!("{}{}", prefix, self.tmp - 1)
+    }
+
+    /// A match arm for a block from `finish_block`, evaluating to `default`
+    /// if the block has no result.
+    fn arm(&mut self, variant: &str, default: &str) -> String {
+        let (src, expr, payload) = self.block_results.pop().unwrap();
+        let pattern = format!("{}({})", variant, payload.as_deref().unwrap_or("()"));
+        let expr = expr.unwrap_or_else(|| default.to_string());
+        if src.is_empty() {
+            format!("    {} => {},\n", pattern, expr)
+        } else {
+            let mut arm = format!("    {} => {{\n", pattern);
+            for line in src.lines().chain(std::iter::once(expr.as_str())) {
+                arm.push_str("        ");
+                arm.push_str(line);
+                arm.push('\n');
+            }
+            arm.push_str("    }\n");
+            arm
+        }
+    }
+}
+
+impl Bindgen for GlueGen {
+    type Operand = String;
+
+    fn emit(
+        &mut self,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<String>,
+        results: &mut Vec<String>,
+    ) {
+        use Instruction::*;
+        let op = |i: usize| operands[i].clone();
+        match inst {
+            GetArg { nth } => results.push(format!("arg{}", nth)),
+
+            // Lifting the arguments.
+            S8FromI32 => results.push(format!("{} as i8", op(0))),
+            U8FromI32 | Char8FromI32 => results.push(format!("{} as u8", op(0))),
+            S16FromI32 => results.push(format!("{} as i16", op(0))),
+            U16FromI32 => results.push(format!("{} as u16", op(0))),
+            S32FromI32 | S64FromI64 | If32FromF32 | If64FromF64 => results.push(op(0)),
+            U32FromI32 | UsizeFromI32 | HandleFromI32 { .. } => {
+                results.push(format!("{} as u32", op(0)))
+            }
+            U64FromI64 => results.push(format!("{} as u64", op(0))),
+            CharFromI32 => {
+                let value = self.tmp("value");
+                self.line(&format!("let {} = {} as u32;", value, op(0)));
+                results.push(format!(
+                    "std::char::from_u32({}).ok_or(GuestError::InvalidChar {{ value: {} }})?",
+                    value, value
+                ));
+            }
+            PointerFromI32 { .. } | ConstPointerFromI32 { .. } => {
+                results.push(format!("GuestPtr::new({} as u32)", op(0)))
+            }
+            BitflagsFromI32 { ty } | BitflagsFromI64 { ty } => {
+                results.push(format!("{} as {}", op(0), type_name(&ty.name)))
+            }
+            EnumLift { ty } => {
+                let tag_repr = match &**ty.type_() {
+                    Type::Variant(v) => v.tag_repr,
+                    _ => unreachable!(),
+                };
+                // Checked before it's narrowed to the tag, so that a value
+                // out of its range isn't truncated to a valid one.
+                let raw = match tag_repr {
+                    IntRepr::U64 => format!("{} as u64", op(0)),
+                    _ => format!("{} as u32 as u64", op(0)),
+                };
+                results.push(format!(
+                    "{}::from_raw(<{} as std::convert::TryFrom<_>>::try_from({}).map_err(|_| GuestError::InvalidEnumValue {{ ty: \"{}\", value: {} }})?)?",
+                    type_name(&ty.name),
+                    int_repr(tag_repr),
+                    op(0),
+                    ty.name.as_str(),
+                    raw
+                ));
+            }
+            ListFromPointerLength { ty } => {
+                let read = match &**ty.type_() {
+                    Type::Builtin(BuiltinType::Char) => "read_string".to_string(),
+                    _ => format!("read_list::<{}>", tref_ty(ty)),
+                };
+                results.push(format!("mem.{}({} as u32, {} as u32)?", read, op(0), op(1)));
+            }
+            Load { ty } => results.push(format!(
+                "<{}>::read(&mem, {} as u32)?",
+                type_name(&ty.name),
+                op(0)
+            )),
+
+            CallInterface { func, .. } => {
+                // Lifting may read from `mem`, so it's done before the call
+                // borrows it mutably. Lifting and checking the arguments is
+                // a closure, so that errors in them are handled together.
+                let mut args = vec!["&mut mem".to_string()];
+                let mut checks = String::new();
+                for operand in operands.iter() {
+                    let param = self.tmp("param");
+                    writeln!(checks, "    let {} = {};", param, operand).unwrap();
+                    args.push(param);
+                }
+                let value = |id: &Id| {
+                    let i = func.params.iter().position(|p| p.name == *id).unwrap();
+                    args[i + 1].clone()
+                };
+                for p in func.params.iter() {
+                    let name = format!("{}.{}", func.name.as_str(), p.name.as_str());
+                    if let Some(size) = &p.size {
+                        let check = size_check(&name, &p.name, size, &value);
+                        writeln!(checks, "    {};", check).unwrap();
+                    }
+                    if let Type::Pointer(_) | Type::ConstPointer(_) = &**p.tref.type_() {
+                        let check = format!("check_pointer(\"{}\", &{})?", name, value(&p.name));
+                        writeln!(checks, "    {};", check).unwrap();
+                    }
+                }
+                if !operands.is_empty() {
+                    let params = match &args[1..] {
+                        [param] => format!("({},)", param),
+                        params => format!("({})", params.join(", ")),
+                    };
+                    self.line("#[allow(clippy::redundant_closure_call)]");
+                    self.line("let checked = (|| -> Result<_, GuestError> {");
+                    self.src.push_str(&checks);
+                    self.line(&format!("    Ok({})", params));
+                    self.line("})();");
+                    match &self.invalid {
+                        Some(error) => {
+                            let error = error.clone();
+                            self.line(&format!("let {} = match checked {{", params));
+                            self.line("    Ok(params) => params,");
+                            self.line(
+                                "    Err(e) if invalid == InvalidArguments::Error && e.is_invalid_argument() => {",
+                            );
+                            self.line(&format!("        return Ok({}.raw() as i32)", error));
+                            self.line("    }");
+                            self.line("    Err(e) => return Err(e.into()),");
+                            self.line("};");
+                        }
+                        None => self.line(&format!("let {} = checked?;", params)),
+                    }
+                }
+                let call = format!("imp.{}({})", ident(func.name.as_str()), args.join(", "));
+                if self.noreturn {
+                    self.line(&format!("Err({})", call));
+                } else if func.results.is_empty() {
+                    self.line(&format!("{};", call));
+                } else {
+                    let ret = self.tmp("ret");
+                    self.line(&format!("let {} = {};", ret, call));
+                    results.push(ret);
+                }
+            }
+
+            // Lowering the results.
+            I32FromChar
+            | I32FromU32
+            | I32FromS32
+            | I32FromUsize
+            | I32FromU16
+            | I32FromS16
+            | I32FromU8
+            | I32FromS8
+            | I32FromChar8
+            | I32FromHandle { .. }
+            | I32FromBitflags { .. } => results.push(format!("{} as i32", op(0))),
+            I64FromU64 | I64FromS64 | I64FromBitflags { .. } => {
+                results.push(format!("{} as i64", op(0)))
+            }
+            F32FromIf32 | F64FromIf64 => results.push(op(0)),
+            I32FromPointer | I32FromConstPointer => {
+                results.push(format!("{}.offset() as i32", op(0)))
+            }
+            EnumLower { .. } => results.push(format!("{}.raw() as i32", op(0))),
+            VariantPayload => {
+                let payload = self.tmp("payload");
+                self.payload = Some(payload.clone());
+                results.push(payload);
+            }
+            TupleLower { amt } => {
+                for i in 0..*amt {
+                    results.push(format!("{}.{}", op(0), i));
+                }
+            }
+            Store { ty } => {
+                let _ = ty;
+                self.line(&format!("{}.write(&mut mem, {} as u32)?;", op(0), op(1)));
+            }
+            ResultLower { .. } => {
+                let err = self.arm("Err", "1");
+                let ok = self.arm("Ok", "0");
+                let result = self.tmp("result");
+                self.line(&format!(
+                    "let {} = match {} {{\n{}{}}};",
+                    result,
+                    op(0),
+                    ok,
+                    err
+                ));
+                results.push(result);
+            }
+            Return { amt: 0 } => {
+                if !self.noreturn {
+                    self.line("Ok(())");
+                }
+            }
+            Return { .. } => {
+                let ret = op(0);
+                self.line(&format!("Ok({})", ret));
+            }
+
+            AddrOf
+            | ListPointerLength
+            | CallWasm { .. }
+            | ReturnPointerGet { .. }
+            | ResultLift
+            | TupleLift { .. }
+            | ReuseReturn => unreachable!("{:?} is only used when calling wasm", inst),
+        }
+    }
+
+    fn allocate_space(&mut self, _slot: usize, _ty: &NamedType) {
+        unreachable!("return pointers are provided by the caller")
+    }
+
+    fn push_block(&mut self) {
+        let src = mem::take(&mut self.src);
+        let payload = self.payload.take();
+        self.blocks.push((src, payload));
+    }
+
+    fn finish_block(&mut self, operand: Option<String>) {
+        let (src, payload) = self.blocks.pop().unwrap();
+        let block_src = mem::replace(&mut self.src, src);
+        let block_payload = mem::replace(&mut self.payload, payload);
+        self.block_results.push((block_src, operand, block_payload));
+    }
+}
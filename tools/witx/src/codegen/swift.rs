@@ -0,0 +1,528 @@
+//! Swift declarations of the types and imports of a document, for guests
+//! built with SwiftWasm.
+//!
+//! Swift doesn't promise to lay out structs like C does, so generated types
+//! carry the layout witx gives them instead: each conforms to `WitxLayout`,
+//! with its size and alignment and functions copying it from and to guest
+//! memory at the offsets of the layout engine. Handles and enums are structs
+//! wrapping their raw value, which tolerate values the document doesn't
+//! name, flags are `OptionSet`s, and other variants are enums with
+//! associated values. Lists are a base address and a length.
+//!
+//! Imports are declared with `@_extern(wasm)`, which needs the `Extern`
+//! experimental feature, and take the raw values of their parameters in the
+//! same way as the C header's functions.
+
+use super::{doc_comment, file_stem, OutputFile};
+use crate::{
+    BuiltinType, Document, IntRepr, InterfaceFunc, Layout, Module, NamedType, Type, TypeRef,
+};
+use heck::{CamelCase, MixedCase};
+use std::fmt::Write;
+
+pub(super) fn generate(doc: &Document) -> Vec<OutputFile> {
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n");
+    out.push_str(PRELUDE);
+    for nt in doc.typenames() {
+        out.push('\n');
+        define_type(&mut out, &nt);
+    }
+    for c in doc.constants() {
+        out.push('\n');
+        comment(&mut out, &c.docs, "");
+        let ty = type_name(c.ty.as_str());
+        let nominal = doc.typename(&c.ty).is_some_and(|nt| is_nominal(&nt));
+        if nominal {
+            writeln!(
+                out,
+                "extension {} {{\n    static let {} = {}(rawValue: {})\n}}",
+                ty,
+                ident(c.name.as_str()),
+                ty,
+                c.value
+            )
+        } else {
+            writeln!(
+                out,
+                "let {}{}: {} = {}",
+                c.ty.as_str().to_mixed_case(),
+                c.name.as_str().to_camel_case(),
+                ty,
+                c.value
+            )
+        }
+        .unwrap();
+    }
+    for m in doc.modules() {
+        out.push('\n');
+        declare_module(&mut out, &m);
+    }
+    vec![OutputFile::new(format!("{}.swift", file_stem(doc)), out)]
+}
+
+const PRELUDE: &str = "
+/// A type laid out in guest memory as witx describes it, which needn't be
+/// how Swift lays it out.
+protocol WitxLayout {
+    static var witxSize: Int { get }
+    static var witxAlignment: Int { get }
+    /// The value at `pointer`, which is aligned to `witxAlignment`.
+    static func load(from pointer: UnsafeRawPointer) -> Self
+    /// Write the value to the `witxSize` bytes at `pointer`.
+    func store(to pointer: UnsafeMutableRawPointer)
+}
+";
+
+fn comment(out: &mut String, docs: &str, indent: &str) {
+    if docs.trim().is_empty() {
+        return;
+    }
+    out.push_str(&doc_comment(docs, "///", indent));
+}
+
+fn type_name(name: &str) -> String {
+    name.to_camel_case()
+}
+
+/// A Swift identifier for a witx field, case, param or function name.
+fn ident(name: &str) -> String {
+    let name = name.to_mixed_case();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return format!("_{}", name);
+    }
+    match name.as_str() {
+        "as" | "break" | "case" | "catch" | "class" | "continue" | "default" | "defer" | "do"
+        | "else" | "enum" | "extension" | "fallthrough" | "false" | "for" | "func" | "guard"
+        | "if" | "import" | "in" | "init" | "inout" | "internal" | "is" | "let" | "nil"
+        | "operator" | "private" | "protocol" | "public" | "repeat" | "return" | "self"
+        | "static" | "struct" | "subscript" | "super" | "switch" | "throw" | "throws" | "true"
+        | "try" | "typealias" | "var" | "where" | "while" => {
+            format!("`{}`", name)
+        }
+        _ => name,
+    }
+}
+
+fn builtin(b: BuiltinType) -> &'static str {
+    match b {
+        BuiltinType::U8 { .. } => "UInt8",
+        BuiltinType::S8 => "Int8",
+        BuiltinType::U16 => "UInt16",
+        BuiltinType::S16 => "Int16",
+        // `UInt` is 32 bits wide in wasm32 guests.
+        BuiltinType::U32 {
+            lang_ptr_size: true,
+        } => "UInt",
+        BuiltinType::U32 {
+            lang_ptr_size: false,
+        }
+        | BuiltinType::Char => "UInt32",
+        BuiltinType::S32 => "Int32",
+        BuiltinType::U64 => "UInt64",
+        BuiltinType::S64 => "Int64",
+        BuiltinType::F32 => "Float",
+        BuiltinType::F64 => "Double",
+    }
+}
+
+fn int_repr(repr: IntRepr) -> &'static str {
+    builtin(repr.to_builtin())
+}
+
+const LIST: &str = "(UnsafeRawPointer?, UInt)";
+
+/// Whether `nt` is defined as a struct or enum, rather than an alias.
+fn is_nominal(nt: &NamedType) -> bool {
+    match &nt.tref {
+        TypeRef::Name(_) => false,
+        TypeRef::Value(ty) => match &**ty {
+            Type::Handle(_) | Type::Record(_) => true,
+            Type::Variant(v) => !v.is_bool(),
+            Type::Builtin(_) | Type::List(_) | Type::Pointer(_) | Type::ConstPointer(_) => false,
+        },
+    }
+}
+
+fn tref_ty(tref: &TypeRef) -> String {
+    match tref {
+        TypeRef::Name(nt) => type_name(nt.name.as_str()),
+        TypeRef::Value(ty) => anonymous_ty(ty),
+    }
+}
+
+fn anonymous_ty(ty: &Type) -> String {
+    match ty {
+        Type::Builtin(b) => builtin(*b).to_string(),
+        Type::List(_) => LIST.to_string(),
+        Type::Pointer(_) => "UnsafeMutableRawPointer?".to_string(),
+        Type::ConstPointer(_) => "UnsafeRawPointer?".to_string(),
+        Type::Handle(_) => "UInt32".to_string(),
+        Type::Variant(v) if v.is_bool() => "Bool".to_string(),
+        Type::Variant(v) if v.is_enum() => int_repr(v.tag_repr).to_string(),
+        Type::Record(r) => match r.bitflags_repr() {
+            Some(repr) => int_repr(repr).to_string(),
+            None => {
+                let members = r
+                    .members
+                    .iter()
+                    .map(|m| tref_ty(&m.tref))
+                    .collect::<Vec<_>>();
+                format!("({})", members.join(", "))
+            }
+        },
+        Type::Variant(_) => unreachable!("anonymous variants have no Swift representation"),
+    }
+}
+
+/// `pointer + offset`, or `pointer` itself for a zero offset.
+fn at(offset: usize) -> String {
+    match offset {
+        0 => "pointer".to_string(),
+        n => format!("pointer + {}", n),
+    }
+}
+
+fn load_raw(ty: &str, offset: usize) -> String {
+    match offset {
+        0 => format!("pointer.load(as: {}.self)", ty),
+        n => format!("pointer.load(fromByteOffset: {}, as: {}.self)", n, ty),
+    }
+}
+
+fn store_raw(value: &str, ty: &str, offset: usize) -> String {
+    match offset {
+        0 => format!("pointer.storeBytes(of: {}, as: {}.self)", value, ty),
+        n => format!(
+            "pointer.storeBytes(of: {}, toByteOffset: {}, as: {}.self)",
+            value, n, ty
+        ),
+    }
+}
+
+/// An expression loading a `tref` at `offset` from `pointer`.
+fn load(tref: &TypeRef, offset: usize) -> String {
+    match tref {
+        TypeRef::Name(nt) if is_nominal(nt) => {
+            format!("{}.load(from: {})", type_name(nt.name.as_str()), at(offset))
+        }
+        TypeRef::Name(nt) => load(&nt.tref, offset),
+        TypeRef::Value(ty) => match &**ty {
+            Type::Variant(v) if v.is_bool() => {
+                format!("{} != 0", load_raw(int_repr(v.tag_repr), offset))
+            }
+            Type::List(_) => format!(
+                "({}, {})",
+                load_raw("UnsafeRawPointer?", offset),
+                load_raw("UInt", offset + 4)
+            ),
+            Type::Record(r) if r.bitflags_repr().is_none() => {
+                let members = r
+                    .member_layout()
+                    .iter()
+                    .map(|m| load(&m.member.tref, offset + m.offset))
+                    .collect::<Vec<_>>();
+                format!("({})", members.join(", "))
+            }
+            ty => load_raw(&anonymous_ty(ty), offset),
+        },
+    }
+}
+
+/// The statements storing `value`, a `tref`, at `offset` from `pointer`.
+fn store(out: &mut String, tref: &TypeRef, offset: usize, value: &str, indent: &str) {
+    match tref {
+        TypeRef::Name(nt) if is_nominal(nt) => {
+            writeln!(out, "{}{}.store(to: {})", indent, value, at(offset)).unwrap()
+        }
+        TypeRef::Name(nt) => store(out, &nt.tref, offset, value, indent),
+        TypeRef::Value(ty) => match &**ty {
+            Type::Variant(v) if v.is_bool() => {
+                let raw = format!("{} ? 1 : 0", value);
+                let raw = store_raw(&raw, int_repr(v.tag_repr), offset);
+                writeln!(out, "{}{}", indent, raw).unwrap()
+            }
+            Type::List(_) => {
+                let base = store_raw(&format!("{}.0", value), "UnsafeRawPointer?", offset);
+                let len = store_raw(&format!("{}.1", value), "UInt", offset + 4);
+                writeln!(out, "{}{}\n{}{}", indent, base, indent, len).unwrap()
+            }
+            Type::Record(r) if r.bitflags_repr().is_none() => {
+                for (i, m) in r.member_layout().iter().enumerate() {
+                    let field = format!("{}.{}", value, i);
+                    store(out, &m.member.tref, offset + m.offset, &field, indent);
+                }
+            }
+            ty => {
+                let raw = store_raw(value, &anonymous_ty(ty), offset);
+                writeln!(out, "{}{}", indent, raw).unwrap()
+            }
+        },
+    }
+}
+
+fn define_type(out: &mut String, nt: &NamedType) {
+    let name = type_name(nt.name.as_str());
+    comment(out, &nt.docs, "");
+    let ty = match &nt.tref {
+        TypeRef::Name(other) => {
+            writeln!(
+                out,
+                "typealias {} = {}",
+                name,
+                type_name(other.name.as_str())
+            )
+            .unwrap();
+            return;
+        }
+        TypeRef::Value(ty) if !is_nominal(nt) => {
+            writeln!(out, "typealias {} = {}", name, anonymous_ty(ty)).unwrap();
+            return;
+        }
+        TypeRef::Value(ty) => ty,
+    };
+
+    let mut load_body = String::new();
+    let mut store_body = String::new();
+    match &**ty {
+        Type::Handle(_) => {
+            raw_value_struct(out, &name, "UInt32", "Hashable", &[]);
+            raw_value_layout(&mut load_body, &mut store_body, &name, "UInt32");
+        }
+        Type::Variant(v) if v.is_enum() => {
+            let cases = v
+                .cases
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (ident(c.name.as_str()), &c.docs, i.to_string()))
+                .collect::<Vec<_>>();
+            let repr = int_repr(v.tag_repr);
+            raw_value_struct(out, &name, repr, "Hashable", &cases);
+            raw_value_layout(&mut load_body, &mut store_body, &name, repr);
+        }
+        Type::Record(r) if r.bitflags_repr().is_some() => {
+            let consts = r
+                .members
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (ident(m.name.as_str()), &m.docs, format!("1 << {}", i)))
+                .collect::<Vec<_>>();
+            let repr = int_repr(r.bitflags_repr().unwrap());
+            raw_value_struct(out, &name, repr, "OptionSet", &consts);
+            raw_value_layout(&mut load_body, &mut store_body, &name, repr);
+        }
+        Type::Record(r) => {
+            let field = |i: usize, m: &crate::RecordMember| {
+                if r.is_tuple() {
+                    format!("f{}", i)
+                } else {
+                    ident(m.name.as_str())
+                }
+            };
+            writeln!(out, "struct {} {{", name).unwrap();
+            let mut fields = Vec::new();
+            for (i, m) in r.member_layout().iter().enumerate() {
+                comment(out, &m.member.docs, "    ");
+                let field = field(i, m.member);
+                writeln!(out, "    var {}: {}", field, tref_ty(&m.member.tref)).unwrap();
+                fields.push(format!(
+                    "{}: {}",
+                    field.trim_matches('`'),
+                    load(&m.member.tref, m.offset)
+                ));
+                let value = format!("self.{}", field);
+                store(
+                    &mut store_body,
+                    &m.member.tref,
+                    m.offset,
+                    &value,
+                    "        ",
+                );
+            }
+            out.push_str("}\n");
+            writeln!(load_body, "        {}({})", name, fields.join(", ")).unwrap();
+        }
+        Type::Variant(v) => {
+            writeln!(out, "enum {} {{", name).unwrap();
+            let tag = int_repr(v.tag_repr);
+            let payload = v.payload_offset();
+            writeln!(load_body, "        switch {} {{", load_raw(tag, 0)).unwrap();
+            store_body.push_str("        switch self {\n");
+            for (i, c) in v.cases.iter().enumerate() {
+                comment(out, &c.docs, "    ");
+                let case = ident(c.name.as_str());
+                let set_tag = store_raw(&i.to_string(), tag, 0);
+                match &c.tref {
+                    Some(tref) => {
+                        writeln!(out, "    case {}({})", case, tref_ty(tref)).unwrap();
+                        writeln!(
+                            load_body,
+                            "        case {}: return .{}({})",
+                            i,
+                            case,
+                            load(tref, payload)
+                        )
+                        .unwrap();
+                        writeln!(store_body, "        case .{}(let payload):", case).unwrap();
+                        writeln!(store_body, "            {}", set_tag).unwrap();
+                        store(&mut store_body, tref, payload, "payload", "            ");
+                    }
+                    None => {
+                        writeln!(out, "    case {}", case).unwrap();
+                        writeln!(load_body, "        case {}: return .{}", i, case).unwrap();
+                        writeln!(store_body, "        case .{}:", case).unwrap();
+                        writeln!(store_body, "            {}", set_tag).unwrap();
+                    }
+                }
+            }
+            out.push_str("}\n");
+            writeln!(
+                load_body,
+                "        case let tag: fatalError(\"\\(tag) is not a valid `{}`\")",
+                nt.name.as_str()
+            )
+            .unwrap();
+            load_body.push_str("        }\n");
+            store_body.push_str("        }\n");
+        }
+        _ => unreachable!("only nominal types have a layout of their own"),
+    }
+
+    let sa = nt.mem_size_align();
+    writeln!(out, "\nextension {}: WitxLayout {{", name).unwrap();
+    writeln!(out, "    static let witxSize = {}", sa.size).unwrap();
+    writeln!(out, "    static let witxAlignment = {}", sa.align).unwrap();
+    writeln!(
+        out,
+        "\n    static func load(from pointer: UnsafeRawPointer) -> {} {{",
+        name
+    )
+    .unwrap();
+    out.push_str(&load_body);
+    out.push_str("    }\n\n    func store(to pointer: UnsafeMutableRawPointer) {\n");
+    out.push_str(&store_body);
+    out.push_str("    }\n}\n");
+}
+
+/// A struct wrapping a `repr`, with a constant for each of `consts`.
+fn raw_value_struct(
+    out: &mut String,
+    name: &str,
+    repr: &str,
+    conformance: &str,
+    consts: &[(String, &String, String)],
+) {
+    let protocols = match conformance {
+        "OptionSet" => "OptionSet".to_string(),
+        other => format!("RawRepresentable, {}", other),
+    };
+    writeln!(out, "struct {}: {} {{", name, protocols).unwrap();
+    writeln!(out, "    var rawValue: {}", repr).unwrap();
+    writeln!(
+        out,
+        "\n    init(rawValue: {}) {{\n        self.rawValue = rawValue\n    }}",
+        repr
+    )
+    .unwrap();
+    if !consts.is_empty() {
+        out.push('\n');
+    }
+    for (c, docs, value) in consts {
+        comment(out, docs, "    ");
+        writeln!(out, "    static let {} = {}(rawValue: {})", c, name, value).unwrap();
+    }
+    out.push_str("}\n");
+}
+
+fn raw_value_layout(load_body: &mut String, store_body: &mut String, name: &str, repr: &str) {
+    writeln!(
+        load_body,
+        "        {}(rawValue: {})",
+        name,
+        load_raw(repr, 0)
+    )
+    .unwrap();
+    writeln!(store_body, "        {}", store_raw("rawValue", repr, 0)).unwrap();
+}
+
+/// The Swift type of values of `tref` passed to or returned from an import
+/// as a single core wasm value.
+fn raw_ty(tref: &TypeRef) -> Option<String> {
+    match &**tref.type_() {
+        Type::Builtin(b) => Some(builtin(*b).to_string()),
+        Type::Pointer(_) => Some("UnsafeMutableRawPointer?".to_string()),
+        Type::ConstPointer(_) => Some("UnsafeRawPointer?".to_string()),
+        Type::Handle(_) => Some("UInt32".to_string()),
+        Type::Variant(v) if v.is_enum() => Some(int_repr(v.tag_repr).to_string()),
+        Type::Record(r) => r.bitflags_repr().map(|repr| int_repr(repr).to_string()),
+        Type::Variant(_) | Type::List(_) => None,
+    }
+}
+
+fn declare_module(out: &mut String, module: &Module) {
+    comment(out, &module.docs, "");
+    for (i, f) in module.funcs().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        declare_func(out, module, &f);
+    }
+}
+
+fn declare_func(out: &mut String, module: &Module, func: &InterfaceFunc) {
+    comment(out, &func.docs, "");
+    let mut params = Vec::new();
+    for p in func.params.iter() {
+        let name = ident(p.name.as_str());
+        match raw_ty(&p.tref) {
+            Some(ty) => params.push(format!("_ {}: {}", name, ty)),
+            None => match &**p.tref.type_() {
+                Type::List(_) => {
+                    params.push(format!("_ {}: UnsafeRawPointer?", name));
+                    let len = ident(&format!("{}_len", p.name.as_str()));
+                    params.push(format!("_ {}: UInt", len));
+                }
+                _ => params.push(format!("_ {}: UnsafeRawPointer", name)),
+            },
+        }
+    }
+    let mut ret = None;
+    if let Some(result) = func.results.first() {
+        match &**result.tref.type_() {
+            Type::Variant(v) if !v.is_enum() => {
+                let (ok, err) = v.as_expected().expect("results are `expected` variants");
+                ret = Some(match err.and_then(raw_ty) {
+                    Some(ty) => ty,
+                    None => int_repr(v.tag_repr).to_string(),
+                });
+                if let Some(ok) = ok {
+                    let n = match &**ok.type_() {
+                        Type::Record(r) if r.is_tuple() => r.members.len(),
+                        _ => 1,
+                    };
+                    for i in 0..n {
+                        params.push(format!("_ retptr{}: UnsafeMutableRawPointer", i));
+                    }
+                }
+            }
+            _ => ret = raw_ty(&result.tref),
+        }
+    }
+    writeln!(
+        out,
+        "@_extern(wasm, module: \"{}\", name: \"{}\")",
+        module.name.as_str(),
+        func.name.as_str()
+    )
+    .unwrap();
+    let ret = ret.map(|ty| format!(" -> {}", ty)).unwrap_or_default();
+    writeln!(
+        out,
+        "func {}{}({}){}",
+        module.name.as_str().to_mixed_case(),
+        func.name.as_str().to_camel_case(),
+        params.join(", "),
+        ret
+    )
+    .unwrap();
+}
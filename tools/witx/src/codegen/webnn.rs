@@ -0,0 +1,823 @@
+//! A JavaScript polyfill running wasi-nn guests on the browser's WebNN API.
+//!
+//! The generated ES module has an `imports` function returning the import
+//! object for a guest. Its functions lift their arguments out of guest memory,
+//! call a backend object and lower the results back, as described by
+//! [`InterfaceFunc::call_interface`]. Backend methods are named after the
+//! functions in camel case and take the guest memory followed by the lifted
+//! parameters. Errors of functions returning an `expected` are thrown as a
+//! `WitxError`. Methods may return promises, since WebNN is asynchronous: the
+//! imports are wrapped with `WebAssembly.Suspending`, so guests have to be
+//! called through `WebAssembly.promising`.
+//!
+//! Values are represented as numbers, or `BigInt`s for 64-bit integers, case
+//! names for enums, objects with camel case fields for records, arrays for
+//! tuples and lists, `{ tag, value }` for other variants, strings for lists of
+//! `char`, and `Uint8Array`s for lists of `u8`.
+//!
+//! For documents with wasi-nn's functions the module also exports a
+//! `WebNNBackend`. WebNN has no model formats of its own, so graphs are built
+//! by a loader provided for each graph encoding, and computed with an
+//! `MLContext` for the execution target.
+
+use super::{file_stem, OutputFile};
+use crate::{
+    Bindgen, BuiltinType, Document, Instruction, InterfaceFunc, Layout, Module, NamedType, Type,
+    TypeRef,
+};
+use heck::MixedCase;
+use std::fmt::Write;
+use std::mem;
+
+pub(super) fn generate(doc: &Document) -> Vec<OutputFile> {
+    let mut out = String::new();
+    out.push_str("// This file is automatically generated by witx, DO NOT EDIT!\n\n");
+    out.push_str(PRELUDE);
+    for nt in doc.typenames() {
+        out.push('\n');
+        define_type(&mut out, &nt);
+    }
+    out.push_str(
+        "\n/**\n \
+         * The import object for a guest, implemented by `backend`. `memory` is the\n \
+         * guest's exported memory, or a function returning it, since it's only known\n \
+         * once the guest is instantiated.\n \
+         */\n",
+    );
+    out.push_str("export function imports(backend, memory) {\n");
+    out.push_str(
+        "    const buffer = () => (typeof memory === \"function\" ? memory() : memory).buffer;\n",
+    );
+    out.push_str("    return {\n");
+    for m in doc.modules() {
+        define_module(&mut out, &m);
+    }
+    out.push_str("    };\n");
+    out.push_str("}\n");
+    if is_wasi_nn(doc) {
+        out.push_str(WEBNN_BACKEND);
+    }
+    vec![OutputFile::new(format!("{}.js", file_stem(doc)), out)]
+}
+
+/// Whether `doc` has a module with the functions `WebNNBackend` implements.
+fn is_wasi_nn(doc: &Document) -> bool {
+    const FUNCS: &[&str] = &[
+        "load",
+        "init_execution_context",
+        "set_input",
+        "compute",
+        "get_output",
+    ];
+    doc.modules().any(|m| {
+        FUNCS
+            .iter()
+            .all(|f| m.funcs().any(|func| func.name.as_str() == *f))
+    })
+}
+
+/// Guest memory access shared by every generated module.
+const PRELUDE: &str = r#"/** An error returned by a function, such as a case of an errno enum. */
+export class WitxError extends Error {
+    constructor(error) {
+        super(`witx error: ${error}`);
+        this.error = error;
+    }
+}
+
+/** The linear memory of a guest. Out of bounds accesses throw a `RangeError`. */
+export class GuestMemory {
+    constructor(buffer) {
+        this.view = new DataView(buffer);
+        this.bytes = new Uint8Array(buffer);
+    }
+
+    check(offset, len) {
+        if (offset + len > this.bytes.length) {
+            throw new RangeError(`${len} bytes at offset ${offset} are out of bounds`);
+        }
+    }
+
+    /** A copy of `len` bytes at `offset`. */
+    readBytes(offset, len) {
+        this.check(offset, len);
+        return this.bytes.slice(offset, offset + len);
+    }
+
+    readString(offset, len) {
+        return new TextDecoder("utf-8", { fatal: true }).decode(this.readBytes(offset, len));
+    }
+
+    /** `len` elements of `size` bytes at `offset`, each read by `read`. */
+    readList(read, size, offset, len) {
+        this.check(offset, size * len);
+        const list = [];
+        for (let i = 0; i < len; i++) {
+            list.push(read(this, offset + i * size));
+        }
+        return list;
+    }
+
+    writeBytes(offset, bytes) {
+        this.check(offset, bytes.length);
+        this.bytes.set(bytes, offset);
+    }
+}
+
+function liftCase(cases, ty, value) {
+    if (value >= cases.length) {
+        throw new RangeError(`${value} is not a valid \`${ty}\``);
+    }
+    return cases[value];
+}
+
+function lowerCase(cases, ty, value) {
+    const raw = cases.indexOf(value);
+    if (raw < 0) {
+        throw new RangeError(`${value} is not a valid \`${ty}\``);
+    }
+    return raw;
+}
+
+function listWrite() {
+    throw new RangeError("lists can't be written to guest memory");
+}
+
+const suspending = (f) =>
+    typeof WebAssembly.Suspending === "function" ? new WebAssembly.Suspending(f) : f;
+"#;
+
+/// The name of the JavaScript field or method for a witx name.
+fn js_name(name: &str) -> String {
+    name.to_mixed_case()
+}
+
+fn reader(nt: &NamedType) -> String {
+    format!("read_{}", nt.name.as_str())
+}
+
+fn writer(nt: &NamedType) -> String {
+    format!("write_{}", nt.name.as_str())
+}
+
+fn cases_name(nt: &NamedType) -> String {
+    format!("cases_{}", nt.name.as_str())
+}
+
+/// The `DataView` accessor suffix of `b`, and whether it takes an
+/// endianness argument.
+fn accessor(b: BuiltinType) -> (&'static str, bool) {
+    match b {
+        BuiltinType::U8 { .. } => ("Uint8", false),
+        BuiltinType::S8 => ("Int8", false),
+        BuiltinType::U16 => ("Uint16", true),
+        BuiltinType::S16 => ("Int16", true),
+        BuiltinType::U32 { .. } | BuiltinType::Char => ("Uint32", true),
+        BuiltinType::S32 => ("Int32", true),
+        BuiltinType::U64 => ("BigUint64", true),
+        BuiltinType::S64 => ("BigInt64", true),
+        BuiltinType::F32 => ("Float32", true),
+        BuiltinType::F64 => ("Float64", true),
+    }
+}
+
+fn get(b: BuiltinType, offset: &str) -> String {
+    let (name, le) = accessor(b);
+    let value = format!(
+        "mem.view.get{}({}{})",
+        name,
+        offset,
+        if le { ", true" } else { "" }
+    );
+    match b {
+        BuiltinType::Char => format!("String.fromCodePoint({})", value),
+        _ => value,
+    }
+}
+
+fn set(b: BuiltinType, offset: &str, value: &str) -> String {
+    let (name, le) = accessor(b);
+    let value = match b {
+        BuiltinType::Char => format!("{}.codePointAt(0)", value),
+        BuiltinType::U64 | BuiltinType::S64 => format!("BigInt({})", value),
+        _ => value.to_string(),
+    };
+    format!(
+        "mem.view.set{}({}, {}{});",
+        name,
+        offset,
+        value,
+        if le { ", true" } else { "" }
+    )
+}
+
+/// `offset + n`, folding `n` into a constant already added to `offset`.
+fn offset_plus(offset: &str, n: usize) -> String {
+    if n == 0 {
+        return offset.to_string();
+    }
+    if let Some((base, m)) = offset.rsplit_once(" + ") {
+        if let Ok(m) = m.parse::<usize>() {
+            return format!("{} + {}", base, m + n);
+        }
+    }
+    format!("{} + {}", offset, n)
+}
+
+/// An expression reading a `tref` at `offset` from `mem`.
+fn read(tref: &TypeRef, offset: &str) -> String {
+    match tref {
+        TypeRef::Name(nt) => format!("{}(mem, {})", reader(nt), offset),
+        TypeRef::Value(ty) => read_type(ty, offset),
+    }
+}
+
+/// An expression reading the list of `elem` with pointer `ptr` and length
+/// `len`.
+fn read_list(elem: &TypeRef, ptr: &str, len: &str) -> String {
+    match &**elem.type_() {
+        Type::Builtin(BuiltinType::U8 { .. }) => format!("mem.readBytes({}, {})", ptr, len),
+        Type::Builtin(BuiltinType::Char) => format!("mem.readString({}, {})", ptr, len),
+        _ => format!(
+            "mem.readList((mem, offset) => {}, {}, {}, {})",
+            read(elem, "offset"),
+            elem.mem_size_align().size,
+            ptr,
+            len
+        ),
+    }
+}
+
+fn read_type(ty: &Type, offset: &str) -> String {
+    match ty {
+        Type::Builtin(b) => get(*b, offset),
+        Type::Pointer(_) | Type::ConstPointer(_) | Type::Handle(_) => get(
+            BuiltinType::U32 {
+                lang_ptr_size: false,
+            },
+            offset,
+        ),
+        Type::List(elem) => read_list(
+            elem,
+            &read_type(
+                &Type::Builtin(BuiltinType::U32 {
+                    lang_ptr_size: true,
+                }),
+                offset,
+            ),
+            &read_type(
+                &Type::Builtin(BuiltinType::U32 {
+                    lang_ptr_size: true,
+                }),
+                &offset_plus(offset, 4),
+            ),
+        ),
+        Type::Record(r) => {
+            if let Some(repr) = r.bitflags_repr() {
+                return get(repr.to_builtin(), offset);
+            }
+            let fields = r
+                .member_layout()
+                .iter()
+                .map(|m| {
+                    let value = read(&m.member.tref, &offset_plus(offset, m.offset));
+                    if r.is_tuple() {
+                        value
+                    } else {
+                        format!("{}: {}", js_name(m.member.name.as_str()), value)
+                    }
+                })
+                .collect::<Vec<_>>();
+            if r.is_tuple() {
+                format!("[{}]", fields.join(", "))
+            } else {
+                format!("{{ {} }}", fields.join(", "))
+            }
+        }
+        Type::Variant(v) => {
+            let tag = get(v.tag_repr.to_builtin(), offset);
+            if v.is_bool() {
+                return format!("{} !== 0", tag);
+            }
+            let payload = v.payload_offset();
+            let cases = v
+                .cases
+                .iter()
+                .map(|c| match &c.tref {
+                    Some(tref) => format!(
+                        "() => ({{ tag: \"{}\", value: {} }})",
+                        c.name.as_str(),
+                        read(tref, &offset_plus(offset, payload))
+                    ),
+                    None => format!("() => ({{ tag: \"{}\" }})", c.name.as_str()),
+                })
+                .collect::<Vec<_>>();
+            format!("liftCase([{}], \"variant\", {})()", cases.join(", "), tag)
+        }
+    }
+}
+
+/// A statement writing `value`, a `tref`, at `offset` to `mem`.
+fn write(out: &mut String, tref: &TypeRef, offset: &str, value: &str, indent: &str) {
+    match tref {
+        TypeRef::Name(nt) => {
+            writeln!(out, "{}{}(mem, {}, {});", indent, writer(nt), offset, value).unwrap()
+        }
+        TypeRef::Value(ty) => write_type(out, ty, offset, value, indent),
+    }
+}
+
+fn write_type(out: &mut String, ty: &Type, offset: &str, value: &str, indent: &str) {
+    let u32 = BuiltinType::U32 {
+        lang_ptr_size: false,
+    };
+    match ty {
+        Type::Builtin(b) => writeln!(out, "{}{}", indent, set(*b, offset, value)).unwrap(),
+        Type::Pointer(_) | Type::ConstPointer(_) | Type::Handle(_) => {
+            writeln!(out, "{}{}", indent, set(u32, offset, value)).unwrap()
+        }
+        Type::List(_) => writeln!(out, "{}listWrite();", indent).unwrap(),
+        Type::Record(r) => {
+            if let Some(repr) = r.bitflags_repr() {
+                writeln!(out, "{}{}", indent, set(repr.to_builtin(), offset, value)).unwrap();
+                return;
+            }
+            for (i, m) in r.member_layout().iter().enumerate() {
+                let field = if r.is_tuple() {
+                    format!("{}[{}]", value, i)
+                } else {
+                    format!("{}.{}", value, js_name(m.member.name.as_str()))
+                };
+                write(
+                    out,
+                    &m.member.tref,
+                    &offset_plus(offset, m.offset),
+                    &field,
+                    indent,
+                );
+            }
+        }
+        Type::Variant(v) => {
+            let tag = v.tag_repr.to_builtin();
+            if v.is_bool() {
+                let value = format!("{} ? 1 : 0", value);
+                writeln!(out, "{}{}", indent, set(tag, offset, &value)).unwrap();
+                return;
+            }
+            writeln!(out, "{}switch ({}.tag) {{", indent, value).unwrap();
+            for (i, c) in v.cases.iter().enumerate() {
+                writeln!(out, "{}    case \"{}\":", indent, c.name.as_str()).unwrap();
+                let inner = format!("{}        ", indent);
+                writeln!(out, "{}{}", inner, set(tag, offset, &i.to_string())).unwrap();
+                if let Some(tref) = &c.tref {
+                    let payload = offset_plus(offset, v.payload_offset());
+                    write(out, tref, &payload, &format!("{}.value", value), &inner);
+                }
+                writeln!(out, "{}break;", inner).unwrap();
+            }
+            writeln!(out, "{}    default:", indent).unwrap();
+            writeln!(
+                out,
+                "{}        throw new RangeError(`${{{}.tag}} is not a valid case`);",
+                indent, value
+            )
+            .unwrap();
+            writeln!(out, "{}}}", indent).unwrap();
+        }
+    }
+}
+
+fn define_type(out: &mut String, nt: &NamedType) {
+    let name = nt.name.as_str();
+    if let Type::Variant(v) = &**nt.type_() {
+        if v.is_enum() {
+            let cases = v
+                .cases
+                .iter()
+                .map(|c| format!("\"{}\"", c.name.as_str()))
+                .collect::<Vec<_>>();
+            writeln!(out, "const {} = [{}];", cases_name(nt), cases.join(", ")).unwrap();
+            writeln!(out, "function lift_{}(value) {{", name).unwrap();
+            writeln!(
+                out,
+                "    return liftCase({}, \"{}\", value);",
+                cases_name(nt),
+                name
+            )
+            .unwrap();
+            out.push_str("}\n");
+            writeln!(out, "function lower_{}(value) {{", name).unwrap();
+            writeln!(
+                out,
+                "    return lowerCase({}, \"{}\", value);",
+                cases_name(nt),
+                name
+            )
+            .unwrap();
+            out.push_str("}\n");
+            writeln!(out, "function {}(mem, offset) {{", reader(nt)).unwrap();
+            writeln!(
+                out,
+                "    return lift_{}({});",
+                name,
+                get(v.tag_repr.to_builtin(), "offset")
+            )
+            .unwrap();
+            out.push_str("}\n");
+            writeln!(out, "function {}(mem, offset, value) {{", writer(nt)).unwrap();
+            writeln!(
+                out,
+                "    {}",
+                set(
+                    v.tag_repr.to_builtin(),
+                    "offset",
+                    &format!("lower_{}(value)", name)
+                )
+            )
+            .unwrap();
+            out.push_str("}\n");
+            return;
+        }
+    }
+    writeln!(out, "function {}(mem, offset) {{", reader(nt)).unwrap();
+    let value = match &nt.tref {
+        TypeRef::Name(other) => format!("{}(mem, offset)", reader(other)),
+        TypeRef::Value(ty) => read_type(ty, "offset"),
+    };
+    writeln!(out, "    return {};", value).unwrap();
+    out.push_str("}\n");
+    writeln!(out, "function {}(mem, offset, value) {{", writer(nt)).unwrap();
+    match &nt.tref {
+        TypeRef::Name(other) => {
+            writeln!(out, "    {}(mem, offset, value);", writer(other)).unwrap()
+        }
+        TypeRef::Value(ty) => write_type(out, ty, "offset", "value", "    "),
+    }
+    out.push_str("}\n");
+}
+
+fn define_module(out: &mut String, module: &Module) {
+    writeln!(out, "        \"{}\": {{", module.name.as_str()).unwrap();
+    for f in module.funcs() {
+        define_glue(out, &f);
+    }
+    out.push_str("        },\n");
+}
+
+fn define_glue(out: &mut String, func: &InterfaceFunc) {
+    let (params, _) = func.wasm_signature();
+    let args = (0..params.len())
+        .map(|i| format!("arg{}", i))
+        .collect::<Vec<_>>();
+    writeln!(
+        out,
+        "            \"{}\": suspending(async ({}) => {{",
+        func.name.as_str(),
+        args.join(", ")
+    )
+    .unwrap();
+    let mut gen = GlueGen {
+        src: String::new(),
+        blocks: Vec::new(),
+        block_results: Vec::new(),
+        payload: None,
+        tmp: 0,
+    };
+    gen.line("const mem = new GuestMemory(buffer());");
+    func.call_interface(&func.name, &mut gen);
+    if func.noreturn {
+        gen.line(&format!(
+            "throw new Error(\"`{}` returned\");",
+            func.name.as_str()
+        ));
+    }
+    for line in gen.src.lines() {
+        writeln!(out, "                {}", line).unwrap();
+    }
+    out.push_str("            }),\n");
+}
+
+/// Generates the body of an import from the instructions of
+/// [`InterfaceFunc::call_interface`]. Operands are JavaScript expressions,
+/// and `src` is unindented.
+struct GlueGen {
+    src: String,
+    blocks: Vec<(String, Option<String>)>,
+    block_results: Vec<(String, Option<String>, Option<String>)>,
+    /// Name bound to the payload of the variant matched in the current block.
+    payload: Option<String>,
+    tmp: usize,
+}
+
+impl GlueGen {
+    fn line(&mut self, line: &str) {
+        self.src.push_str(line);
+        self.src.push('\n');
+    }
+
+    fn tmp(&mut self, prefix: &str) -> String {
+        self.tmp += 1;
+        format!("{}{}", prefix, self.tmp - 1)
+    }
+
+    /// The branch for a block from `finish_block` matching `ret`, assigning
+    /// its result, or `default` if it has none, to `result`.
+    fn branch(&mut self, ret: &str, result: &str, default: &str) -> String {
+        let (src, expr, payload) = self.block_results.pop().unwrap();
+        let mut branch = String::new();
+        if let Some(payload) = payload {
+            writeln!(branch, "    const {} = {}.value;", payload, ret).unwrap();
+        }
+        for line in src.lines() {
+            writeln!(branch, "    {}", line).unwrap();
+        }
+        let expr = expr.unwrap_or_else(|| default.to_string());
+        writeln!(branch, "    {} = {};", result, expr).unwrap();
+        branch
+    }
+}
+
+impl Bindgen for GlueGen {
+    type Operand = String;
+
+    fn emit(
+        &mut self,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<String>,
+        results: &mut Vec<String>,
+    ) {
+        use Instruction::*;
+        let op = |i: usize| operands[i].clone();
+        match inst {
+            GetArg { nth } => results.push(format!("arg{}", nth)),
+
+            // Lifting the arguments.
+            S8FromI32 => results.push(format!("({} << 24) >> 24", op(0))),
+            U8FromI32 | Char8FromI32 => results.push(format!("{} & 0xff", op(0))),
+            S16FromI32 => results.push(format!("({} << 16) >> 16", op(0))),
+            U16FromI32 => results.push(format!("{} & 0xffff", op(0))),
+            S32FromI32 | S64FromI64 | If32FromF32 | If64FromF64 => results.push(op(0)),
+            U32FromI32
+            | UsizeFromI32
+            | HandleFromI32 { .. }
+            | PointerFromI32 { .. }
+            | ConstPointerFromI32 { .. }
+            | BitflagsFromI32 { .. } => results.push(format!("{} >>> 0", op(0))),
+            U64FromI64 | BitflagsFromI64 { .. } => {
+                results.push(format!("BigInt.asUintN(64, {})", op(0)))
+            }
+            CharFromI32 => results.push(format!("String.fromCodePoint({} >>> 0)", op(0))),
+            EnumLift { ty } => results.push(format!("lift_{}({})", ty.name.as_str(), op(0))),
+            ListFromPointerLength { ty } => results.push(read_list(
+                ty,
+                &format!("{} >>> 0", op(0)),
+                &format!("{} >>> 0", op(1)),
+            )),
+            Load { ty } => results.push(format!("{}(mem, {} >>> 0)", reader(ty), op(0))),
+
+            CallInterface { func, .. } => {
+                let mut args = vec!["mem".to_string()];
+                for operand in operands.iter() {
+                    let param = self.tmp("param");
+                    self.line(&format!("const {} = {};", param, operand));
+                    args.push(param);
+                }
+                let call = format!(
+                    "await backend.{}({})",
+                    js_name(func.name.as_str()),
+                    args.join(", ")
+                );
+                let returns_expected = func.results.first().is_some_and(
+                    |r| matches!(&**r.tref.type_(), Type::Variant(v) if v.as_expected().is_some()),
+                );
+                if func.results.is_empty() || func.noreturn {
+                    self.line(&format!("{};", call));
+                } else if returns_expected {
+                    // The result is lowered by `ResultLower`, as
+                    // `${ret}.value`.
+                    let ret = self.tmp("ret");
+                    self.line(&format!("let {};", ret));
+                    self.line("try {");
+                    self.line(&format!(
+                        "    {} = {{ tag: \"ok\", value: {} }};",
+                        ret, call
+                    ));
+                    self.line("} catch (e) {");
+                    self.line("    if (!(e instanceof WitxError)) {");
+                    self.line("        throw e;");
+                    self.line("    }");
+                    self.line(&format!(
+                        "    {} = {{ tag: \"err\", value: e.error }};",
+                        ret
+                    ));
+                    self.line("}");
+                    results.push(ret);
+                } else {
+                    let ret = self.tmp("ret");
+                    self.line(&format!("const {} = {};", ret, call));
+                    results.push(ret);
+                }
+            }
+
+            // Lowering the results.
+            I32FromChar => results.push(format!("{}.codePointAt(0)", op(0))),
+            I32FromU32
+            | I32FromS32
+            | I32FromUsize
+            | I32FromU16
+            | I32FromS16
+            | I32FromU8
+            | I32FromS8
+            | I32FromChar8
+            | I32FromHandle { .. }
+            | I32FromBitflags { .. }
+            | F32FromIf32
+            | F64FromIf64
+            | I32FromPointer
+            | I32FromConstPointer => results.push(op(0)),
+            I64FromU64 | I64FromS64 | I64FromBitflags { .. } => {
+                results.push(format!("BigInt.asIntN(64, BigInt({}))", op(0)))
+            }
+            EnumLower { ty } => results.push(format!("lower_{}({})", ty.name.as_str(), op(0))),
+            VariantPayload => {
+                let payload = self.tmp("payload");
+                self.payload = Some(payload.clone());
+                results.push(payload);
+            }
+            TupleLower { amt } => {
+                for i in 0..*amt {
+                    results.push(format!("{}[{}]", op(0), i));
+                }
+            }
+            Store { ty } => {
+                self.line(&format!("{}(mem, {} >>> 0, {});", writer(ty), op(1), op(0)));
+            }
+            ResultLower { .. } => {
+                let ret = op(0);
+                let result = self.tmp("result");
+                let err = self.branch(&ret, &result, "1");
+                let ok = self.branch(&ret, &result, "0");
+                self.line(&format!("let {};", result));
+                self.line(&format!(
+                    "if ({}.tag === \"ok\") {{\n{}}} else {{\n{}}}",
+                    ret, ok, err
+                ));
+                results.push(result);
+            }
+            Return { amt: 0 } => {}
+            Return { .. } => {
+                let ret = op(0);
+                self.line(&format!("return {};", ret));
+            }
+
+            AddrOf
+            | ListPointerLength
+            | CallWasm { .. }
+            | ReturnPointerGet { .. }
+            | ResultLift
+            | TupleLift { .. }
+            | ReuseReturn => unreachable!("{:?} is only used when calling wasm", inst),
+        }
+    }
+
+    fn allocate_space(&mut self, _slot: usize, _ty: &NamedType) {
+        unreachable!("return pointers are provided by the caller")
+    }
+
+    fn push_block(&mut self) {
+        let src = mem::take(&mut self.src);
+        let payload = self.payload.take();
+        self.blocks.push((src, payload));
+    }
+
+    fn finish_block(&mut self, operand: Option<String>) {
+        let (src, payload) = self.blocks.pop().unwrap();
+        let block_src = mem::replace(&mut self.src, src);
+        let block_payload = mem::replace(&mut self.payload, payload);
+        self.block_results.push((block_src, operand, block_payload));
+    }
+}
+
+/// Implements wasi-nn's functions with WebNN.
+const WEBNN_BACKEND: &str = r#"
+const DEVICE_TYPES = { cpu: "cpu", gpu: "gpu", tpu: "npu" };
+const DATA_TYPES = { f16: "float16", f32: "float32", u8: "uint8", i32: "int32" };
+const ARRAY_TYPES = {
+    float16: typeof Float16Array === "function" ? Float16Array : Uint16Array,
+    float32: Float32Array,
+    uint8: Uint8Array,
+    int32: Int32Array,
+};
+
+/**
+ * Implements wasi-nn with the WebNN API.
+ *
+ * `loaders` maps graph encodings, such as `openvino`, to async functions
+ * `(builders, context)` building a graph from the graph builders passed to
+ * `load`, with an `MLGraphBuilder` for `context`. They return
+ * `{ graph, inputs, outputs }`, where `inputs` and `outputs` describe the
+ * graph's operands in the order wasi-nn indexes them, as
+ * `{ name, descriptor }` with an `MLOperandDescriptor`.
+ */
+export class WebNNBackend {
+    constructor(loaders, ml = globalThis.navigator && globalThis.navigator.ml) {
+        this.loaders = loaders;
+        this.ml = ml;
+        this.graphs = [];
+        this.contexts = [];
+    }
+
+    async load(mem, builders, encoding, target) {
+        const loader = this.loaders[encoding];
+        if (!loader || !this.ml) {
+            throw new WitxError("invalid_argument");
+        }
+        const context = await this.ml.createContext({ deviceType: DEVICE_TYPES[target] });
+        let model;
+        try {
+            model = await loader(builders, context);
+        } catch (e) {
+            throw new WitxError("invalid_argument");
+        }
+        this.graphs.push({ context, ...model });
+        return this.graphs.length - 1;
+    }
+
+    initExecutionContext(mem, graph) {
+        const model = this.graphs[graph];
+        if (!model) {
+            throw new WitxError("invalid_argument");
+        }
+        this.contexts.push({ model, inputs: new Array(model.inputs.length).fill(undefined), outputs: null });
+        return this.contexts.length - 1;
+    }
+
+    executionContext(context) {
+        const c = this.contexts[context];
+        if (!c) {
+            throw new WitxError("invalid_argument");
+        }
+        return c;
+    }
+
+    setInput(mem, context, index, tensor) {
+        const c = this.executionContext(context);
+        const input = c.model.inputs[index];
+        if (!input || input.descriptor.dataType !== DATA_TYPES[tensor.type]) {
+            throw new WitxError("invalid_argument");
+        }
+        c.inputs[index] = tensor.data;
+        c.outputs = null;
+    }
+
+    async compute(mem, context) {
+        const c = this.executionContext(context);
+        const { model, inputs } = c;
+        if (inputs.some((data) => data === undefined)) {
+            throw new WitxError("invalid_argument");
+        }
+        const ml = model.context;
+        if (typeof ml.dispatch === "function") {
+            const ins = {};
+            const outs = {};
+            for (const [i, input] of model.inputs.entries()) {
+                const tensor = await ml.createTensor({ ...input.descriptor, writable: true });
+                ml.writeTensor(tensor, inputs[i]);
+                ins[input.name] = tensor;
+            }
+            for (const output of model.outputs) {
+                outs[output.name] = await ml.createTensor({ ...output.descriptor, readable: true });
+            }
+            ml.dispatch(model.graph, ins, outs);
+            c.outputs = await Promise.all(model.outputs.map((o) => ml.readTensor(outs[o.name])));
+        } else {
+            const array = (descriptor, buffer) => {
+                const ArrayType = ARRAY_TYPES[descriptor.dataType];
+                if (buffer) {
+                    return new ArrayType(buffer.buffer, buffer.byteOffset, buffer.byteLength / ArrayType.BYTES_PER_ELEMENT);
+                }
+                return new ArrayType(descriptor.shape.reduce((n, d) => n * d, 1));
+            };
+            const ins = {};
+            const outs = {};
+            model.inputs.forEach((input, i) => (ins[input.name] = array(input.descriptor, inputs[i])));
+            model.outputs.forEach((output) => (outs[output.name] = array(output.descriptor)));
+            const result = await ml.compute(model.graph, ins, outs);
+            c.outputs = model.outputs.map((o) => result.outputs[o.name].buffer);
+        }
+    }
+
+    getOutput(mem, context, index, outBuffer, outBufferMaxSize) {
+        const c = this.executionContext(context);
+        if (!c.outputs || index >= c.outputs.length) {
+            throw new WitxError("invalid_argument");
+        }
+        const bytes = new Uint8Array(c.outputs[index]);
+        if (bytes.length > outBufferMaxSize) {
+            throw new WitxError("invalid_argument");
+        }
+        mem.writeBytes(outBuffer, bytes);
+        return bytes.length;
+    }
+}
+"#;
@@ -0,0 +1,364 @@
+//! Stable codes for the errors loading a document can report.
+//!
+//! Every error of the parser and the validator has a code, like `WITX0003`
+//! for an unknown name, which appears in its report. A code is never reused
+//! for a different error, even after the error it was assigned to is
+//! removed, so suppression files and documentation can refer to it. The
+//! [`REGISTRY`] has a long-form explanation of each code, which `witx
+//! explain` prints.
+
+use crate::{ValidationError, WitxError};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The code of an error, displayed as `WITX` and four digits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErrorCode(u16);
+
+impl ErrorCode {
+    pub const IO: ErrorCode = ErrorCode(1);
+    pub const PARSE: ErrorCode = ErrorCode(2);
+    pub const UNKNOWN_NAME: ErrorCode = ErrorCode(3);
+    pub const NAME_ALREADY_EXISTS: ErrorCode = ErrorCode(4);
+    pub const SHADOWED: ErrorCode = ErrorCode(5);
+    pub const WRONG_KIND_NAME: ErrorCode = ErrorCode(6);
+    pub const RECURSIVE: ErrorCode = ErrorCode(7);
+    pub const INVALID_REPR: ErrorCode = ErrorCode(8);
+    pub const INVALID_INSTANCE: ErrorCode = ErrorCode(9);
+    pub const ABI: ErrorCode = ErrorCode(10);
+    pub const ANONYMOUS_RECORD: ErrorCode = ErrorCode(11);
+    pub const TOO_MANY_CASES: ErrorCode = ErrorCode(12);
+    pub const UNION_SIZE_MISMATCH: ErrorCode = ErrorCode(13);
+    pub const INVALID_UNION_TAG: ErrorCode = ErrorCode(14);
+    pub const INVALID_UNION_FIELD: ErrorCode = ErrorCode(15);
+    pub const INVALID_SIZE: ErrorCode = ErrorCode(16);
+    pub const DUPLICATE_META: ErrorCode = ErrorCode(17);
+    pub const INVALID_VERSION: ErrorCode = ErrorCode(18);
+    pub const UNSUPPORTED_FEATURE: ErrorCode = ErrorCode(19);
+    pub const NO_CASES: ErrorCode = ErrorCode(20);
+    pub const LIMIT: ErrorCode = ErrorCode(21);
+    pub const UNKNOWN_MODULE: ErrorCode = ErrorCode(22);
+
+    pub fn number(&self) -> u16 {
+        self.0
+    }
+
+    /// The explanation of this code, if it's one of the registry's.
+    pub fn explanation(&self) -> Option<&'static Explanation> {
+        REGISTRY.iter().find(|e| e.code == *self)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WITX{:04}", self.0)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Invalid error code {0:?}: expected `WITX` and four digits")]
+pub struct InvalidErrorCode(String);
+
+impl FromStr for ErrorCode {
+    type Err = InvalidErrorCode;
+    /// Parses a code as displayed, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidErrorCode(s.to_string());
+        let digits = match s.get(..4) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("WITX") => &s[4..],
+            _ => return Err(invalid()),
+        };
+        if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        digits.parse().map(ErrorCode).map_err(|_| invalid())
+    }
+}
+
+/// The long-form explanation of an error code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub code: ErrorCode,
+    /// A one-line summary of the error.
+    pub title: &'static str,
+    /// Paragraphs on what causes the error and how to fix it.
+    pub text: &'static str,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}\n\n{}", self.code, self.title, self.text)
+    }
+}
+
+/// The explanation of `code`, if it's one of the registry's.
+pub fn explain(code: ErrorCode) -> Option<&'static Explanation> {
+    code.explanation()
+}
+
+/// Every error code, in order.
+pub const REGISTRY: &[Explanation] = &[
+    Explanation {
+        code: ErrorCode::IO,
+        title: "a witx file couldn't be read",
+        text: "\
+A file given on the command line, or brought in by a `use` declaration,
+doesn't exist or couldn't be read. The path of a `use` is relative to the
+file with the declaration, or else to one of the include directories given
+with `-I`.",
+    },
+    Explanation {
+        code: ErrorCode::PARSE,
+        title: "a witx file isn't valid syntax",
+        text: "\
+The file isn't made of balanced s-expressions, or they don't have the shape
+of witx declarations, such as a `typename` without a name:
+
+    (typename (list u8))
+
+The report points at the first token that couldn't be parsed.",
+    },
+    Explanation {
+        code: ErrorCode::UNKNOWN_NAME,
+        title: "a name is used but never defined",
+        text: "\
+A type, or a field named by the size of a list, is referred to by a name
+that isn't defined. Names in scope that are close to it are suggested:
+
+    (typename $tensor_dimensions (list u32))
+    (typename $tensor (record (field $dimensions $tensor_dimenions)))
+
+A type defined in another file has to be brought in with `use`.",
+    },
+    Explanation {
+        code: ErrorCode::NAME_ALREADY_EXISTS,
+        title: "a name is defined twice",
+        text: "\
+Two types, modules, functions, params, fields, cases or flags in the same
+scope have the same name. The report points at both definitions, and at the
+`use` declarations that brought in the first one, if any.",
+    },
+    Explanation {
+        code: ErrorCode::SHADOWED,
+        title: "a definition shadows one brought in by `use`",
+        text: "\
+A file defines a type with the same name as a type defined in a file it
+brings in with `use`, directly or not. Rename one of the two, or remove the
+local definition to use the other.",
+    },
+    Explanation {
+        code: ErrorCode::WRONG_KIND_NAME,
+        title: "a name refers to the wrong kind of definition",
+        text: "\
+A name is used where a different kind of definition is expected, such as a
+module where a type is expected, a type that isn't generic given type
+arguments, or a union tag that is neither an enum nor an integer type.",
+    },
+    Explanation {
+        code: ErrorCode::RECURSIVE,
+        title: "a type is defined in terms of itself",
+        text: "\
+A type refers to itself, directly or through other types. witx types have a
+fixed size in memory, so they can't be recursive; a list of the type, or a
+handle, can break the cycle.",
+    },
+    Explanation {
+        code: ErrorCode::INVALID_REPR,
+        title: "a type can't be represented as the given type",
+        text: "\
+The `(@witx tag ...)` or `(@witx repr ...)` of an enum, flags or variant
+isn't an unsigned integer type: `u8`, `u16`, `u32` or `u64`.",
+    },
+    Explanation {
+        code: ErrorCode::INVALID_INSTANCE,
+        title: "a generic type is instantiated wrongly",
+        text: "\
+An instance of a generic type has a different number of type arguments than
+the type has parameters, or an argument isn't a valid type.",
+    },
+    Explanation {
+        code: ErrorCode::ABI,
+        title: "a function can't be given its ABI",
+        text: "\
+The params or results of a function can't be lowered to core wasm types
+with the ABI it's declared with, such as a function with more than one
+result, or with an anonymous type in its result.",
+    },
+    Explanation {
+        code: ErrorCode::ANONYMOUS_RECORD,
+        title: "a structured type has no name",
+        text: "\
+Records, unions, enums, flags and handles have to be defined with
+`typename`, and referred to by their name, rather than written inline in a
+field or param.",
+    },
+    Explanation {
+        code: ErrorCode::TOO_MANY_CASES,
+        title: "an enum, flags or variant has too many cases",
+        text: "\
+The cases of an enum or variant must be numbered within its tag type, and
+each flag must be a bit of its representation: an enum with a `u8` tag has
+at most 256 cases, and flags represented as `u8` at most 8 flags. Widen the
+representation, or split the type.",
+    },
+    Explanation {
+        code: ErrorCode::UNION_SIZE_MISMATCH,
+        title: "a union has a different number of variants than its tag",
+        text: "\
+Each variant of a union corresponds to a case of its tag enum, so the union
+has to have as many variants as the enum has cases.",
+    },
+    Explanation {
+        code: ErrorCode::INVALID_UNION_TAG,
+        title: "a union's tag isn't an enum",
+        text: "\
+The tag of a union has to be an enum, or a variant whose cases all have empty
+payloads, whose cases name the union's variants.",
+    },
+    Explanation {
+        code: ErrorCode::INVALID_UNION_FIELD,
+        title: "a union field doesn't match its tag",
+        text: "\
+A case of a union is named after a case its tag doesn't have.",
+    },
+    Explanation {
+        code: ErrorCode::INVALID_SIZE,
+        title: "a list's size is invalid",
+        text: "\
+The `(@witx size ...)` of a field or param is given on something other than a
+list, or refers to a field or param that isn't an integer, or isn't a list
+when it takes its length.",
+    },
+    Explanation {
+        code: ErrorCode::DUPLICATE_META,
+        title: "a metadata key is given twice",
+        text: "\
+`(@witx meta ...)` annotations on the same item give the same key more than
+once. Each key has one value per item.",
+    },
+    Explanation {
+        code: ErrorCode::INVALID_VERSION,
+        title: "a witx version declaration is invalid",
+        text: "\
+The `(@witx version ...)` of a file isn't a version of the witx language
+this tool reads, or a file declares its version more than once.",
+    },
+    Explanation {
+        code: ErrorCode::UNSUPPORTED_FEATURE,
+        title: "a feature is used that the declared witx version lacks",
+        text: "\
+The file uses syntax introduced by a later version of the witx language than
+the one it declares. Declare the later version, or avoid the feature.",
+    },
+    Explanation {
+        code: ErrorCode::NO_CASES,
+        title: "a variant or union has no cases",
+        text: "\
+A variant or union with no cases has no values, so there's no way to pass
+one to or from a function. Give it a case, or remove it.",
+    },
+    Explanation {
+        code: ErrorCode::LIMIT,
+        title: "the input exceeds a limit on its size",
+        text: "\
+A file is larger than allowed, nests deeper than allowed, has an identifier
+longer than allowed, or the document follows more `use` declarations than
+allowed. Only documents loaded with limits, such as those a service accepts
+from its users, are checked against them.",
+    },
+    Explanation {
+        code: ErrorCode::UNKNOWN_MODULE,
+        title: "a module to load isn't in the document",
+        text: "\
+The document was loaded with only some of its modules, and one of them isn't
+declared by any of its files. Modules in disabled `(@witx cfg ...)` blocks are
+declared.",
+    },
+];
+
+impl ValidationError {
+    pub fn code(&self) -> ErrorCode {
+        use ValidationError::*;
+        match self {
+            UnknownName { .. } => ErrorCode::UNKNOWN_NAME,
+            NameAlreadyExists { .. } => ErrorCode::NAME_ALREADY_EXISTS,
+            Shadowed { .. } => ErrorCode::SHADOWED,
+            WrongKindName { .. } => ErrorCode::WRONG_KIND_NAME,
+            Recursive { .. } => ErrorCode::RECURSIVE,
+            InvalidRepr { .. } => ErrorCode::INVALID_REPR,
+            InvalidInstance { .. } => ErrorCode::INVALID_INSTANCE,
+            Abi { .. } => ErrorCode::ABI,
+            AnonymousRecord { .. } => ErrorCode::ANONYMOUS_RECORD,
+            TooManyCases { .. } => ErrorCode::TOO_MANY_CASES,
+            UnionSizeMismatch { .. } => ErrorCode::UNION_SIZE_MISMATCH,
+            InvalidUnionTag { .. } => ErrorCode::INVALID_UNION_TAG,
+            InvalidUnionField { .. } => ErrorCode::INVALID_UNION_FIELD,
+            InvalidSize { .. } => ErrorCode::INVALID_SIZE,
+            DuplicateMeta { .. } => ErrorCode::DUPLICATE_META,
+            InvalidVersion { .. } => ErrorCode::INVALID_VERSION,
+            UnsupportedFeature { .. } => ErrorCode::UNSUPPORTED_FEATURE,
+            NoCases { .. } => ErrorCode::NO_CASES,
+        }
+    }
+}
+
+impl WitxError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            WitxError::Io(..) => ErrorCode::IO,
+            WitxError::Parse(_) => ErrorCode::PARSE,
+            WitxError::Validation(e) => e.code(),
+            WitxError::Limit(_) => ErrorCode::LIMIT,
+            WitxError::UnknownModule { .. } => ErrorCode::UNKNOWN_MODULE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        for (i, explanation) in REGISTRY.iter().enumerate() {
+            assert_eq!(explanation.code.number() as usize, i + 1);
+            assert!(!explanation.text.ends_with('\n'));
+        }
+        assert_eq!(
+            explain(ErrorCode::UNKNOWN_NAME).unwrap().title,
+            "a name is used but never defined"
+        );
+        assert!(explain(ErrorCode(9999)).is_none());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(ErrorCode::UNKNOWN_NAME.to_string(), "WITX0003");
+        assert_eq!("WITX0003".parse(), Ok(ErrorCode::UNKNOWN_NAME));
+        assert_eq!("witx0019".parse(), Ok(ErrorCode::UNSUPPORTED_FEATURE));
+        for invalid in &["WITX3", "WITX00003", "E0003", "WITX00a3", "WITX+003", ""] {
+            assert!(invalid.parse::<ErrorCode>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn codes() {
+        let code = |source| crate::parse(source).err().unwrap().code();
+        assert_eq!(code("(typename $t"), ErrorCode::PARSE);
+        assert_eq!(code("(typename $t $u)"), ErrorCode::UNKNOWN_NAME);
+        assert_eq!(
+            code("(typename $t u8) (typename $t u8)"),
+            ErrorCode::NAME_ALREADY_EXISTS
+        );
+        let report = crate::parse("(typename $t $u)")
+            .err()
+            .unwrap()
+            .report_with(&crate::MockFs::new(&[("-", "(typename $t $u)")]));
+        assert!(
+            report.contains("error[WITX0003]: Unknown name `u`"),
+            "bad report: {}",
+            report
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Rendering of errors and other diagnostics for a terminal.
+//!
+//! A [`Style`] renders a diagnostic as a header naming its severity and,
+//! for errors loading a document, its [code](crate::codes), followed by the
+//! source lines it points at. The plain style gives the reports of
+//! [`WitxError::report`]; [`Style::detect`] adds ANSI colors and wraps
+//! messages to the terminal's width when stderr is a terminal:
+//!
+//! ```no_run
+//! let style = witx::diagnostic::Style::detect(false);
+//! if let Err(e) = witx::load(&["nn.witx"]) {
+//!     eprintln!("{}", style.witx_error(&e, &witx::Filesystem));
+//! }
+//! ```
+
+use crate::codes::ErrorCode;
+use crate::limits::LimitError;
+use crate::{Location, ValidationError, WitxError, WitxIo};
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+
+    /// The ANSI escape parameters of the severity's color, in bold.
+    fn ansi(&self) -> &'static str {
+        match self {
+            Severity::Error => "1;31",
+            Severity::Warning => "1;33",
+            Severity::Note => "1;36",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The ANSI escape parameters of line numbers and their gutter.
+const GUTTER: &str = "1;34";
+/// The ANSI escape parameters of the text of a header.
+const BOLD: &str = "1";
+
+/// The width messages are wrapped to when the terminal's isn't known.
+const DEFAULT_WIDTH: usize = 80;
+
+/// How diagnostics are rendered. The default style is plain: no colors and
+/// no wrapping.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Style {
+    /// Whether to color diagnostics with ANSI escapes.
+    pub color: bool,
+    /// The width to wrap messages to, if any. Source lines aren't wrapped.
+    pub width: Option<usize>,
+}
+
+impl Style {
+    pub fn plain() -> Self {
+        Self::default()
+    }
+
+    /// Colored and wrapped to the terminal's width when stderr is a
+    /// terminal, and plain otherwise. Colors are left out with `no_color`
+    /// or when the `NO_COLOR` environment variable is set.
+    pub fn detect(no_color: bool) -> Self {
+        if !io::stderr().is_terminal() {
+            return Self::plain();
+        }
+        let no_color = no_color || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let width = env::var("COLUMNS")
+            .ok()
+            .and_then(|c| c.parse().ok())
+            .filter(|w| *w > 0)
+            .unwrap_or(DEFAULT_WIDTH);
+        Style {
+            color: !no_color,
+            width: Some(width),
+        }
+    }
+
+    fn paint(&self, ansi: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", ansi, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// A header line like ``error[WITX0003]: Unknown name `t` ``, wrapping
+    /// `message` with continuation lines indented under its start.
+    pub fn message(&self, severity: Severity, code: Option<ErrorCode>, message: &str) -> String {
+        let label = match code {
+            Some(code) => format!("{}[{}]", severity, code),
+            None => severity.to_string(),
+        };
+        let indent = label.len() + 2;
+        let message = match self.width {
+            Some(width) => wrap(message, width.saturating_sub(indent), indent),
+            None => message.to_string(),
+        };
+        let mut lines = message.splitn(2, '\n');
+        let first = lines.next().unwrap_or_default();
+        let mut out = format!(
+            "{}: {}",
+            self.paint(severity.ansi(), &label),
+            self.paint(BOLD, first)
+        );
+        if let Some(rest) = lines.next() {
+            out.push('\n');
+            out.push_str(rest);
+        }
+        out
+    }
+
+    pub fn error(&self, message: &dyn fmt::Display) -> String {
+        self.message(Severity::Error, None, &message.to_string())
+    }
+
+    pub fn warning(&self, message: &dyn fmt::Display) -> String {
+        self.message(Severity::Warning, None, &message.to_string())
+    }
+
+    pub fn note(&self, message: &dyn fmt::Display) -> String {
+        self.message(Severity::Note, None, &message.to_string())
+    }
+
+    /// The file of `location`, with its line and a caret under its column
+    /// if the line can be read.
+    pub fn location(&self, location: &Location, witxio: &dyn WitxIo) -> String {
+        let mut msg = format!("in {:?}:\n", location.path);
+        if let Ok(src_line) = witxio.fget_line(&location.path, location.line) {
+            msg += &format!(
+                "{} {}\n{}    {}{}",
+                self.paint(GUTTER, &format!("{: >5} |", location.line)),
+                src_line,
+                " ".repeat(5),
+                " ".repeat(location.column.saturating_sub(1)),
+                self.paint(Severity::Error.ansi(), "^"),
+            );
+        }
+        msg
+    }
+
+    /// The report of an error loading a document.
+    pub fn witx_error(&self, error: &WitxError, witxio: &dyn WitxIo) -> String {
+        match error {
+            WitxError::Io(path, ioerr) => self.message(
+                Severity::Error,
+                Some(error.code()),
+                &format!("with file {:?}: {}", path, ioerr),
+            ),
+            WitxError::Parse(parse) => {
+                self.message(Severity::Error, Some(error.code()), &parse.to_string())
+            }
+            WitxError::UnknownModule { .. } => {
+                self.message(Severity::Error, Some(error.code()), &error.to_string())
+            }
+            WitxError::Validation(validation) => self.validation_error(validation, witxio),
+            WitxError::Limit(limit) => {
+                let header = self.message(Severity::Error, Some(error.code()), &limit.to_string());
+                match limit {
+                    LimitError::Nesting { location, .. }
+                    | LimitError::IdentLength { location, .. } => {
+                        format!("{}\n{}", self.location(location, witxio), header)
+                    }
+                    LimitError::FileSize { .. } | LimitError::Uses { .. } => header,
+                }
+            }
+        }
+    }
+
+    /// The report of a validation error, with the source of each location
+    /// it involves.
+    pub fn validation_error(&self, error: &ValidationError, witxio: &dyn WitxIo) -> String {
+        use ValidationError::*;
+        let header = self.message(Severity::Error, Some(error.code()), &error.to_string());
+        match error {
+            UnknownName { location, .. }
+            | WrongKindName { location, .. }
+            | Recursive { location, .. }
+            | InvalidRepr { location, .. }
+            | Abi { location, .. }
+            | AnonymousRecord { location, .. }
+            | TooManyCases { location, .. }
+            | NoCases { location, .. }
+            | UnionSizeMismatch { location, .. }
+            | InvalidUnionField { location, .. }
+            | InvalidSize { location, .. }
+            | InvalidInstance { location, .. }
+            | InvalidVersion { location, .. }
+            | DuplicateMeta { location, .. }
+            | UnsupportedFeature { location, .. }
+            | InvalidUnionTag { location, .. } => {
+                format!("{}\n{}", self.location(location, witxio), header)
+            }
+            NameAlreadyExists {
+                at_location,
+                previous_location,
+                previous_uses,
+                ..
+            } => {
+                let mut report = format!(
+                    "{}\n{}\nOriginally defined at:\n{}",
+                    self.location(at_location, witxio),
+                    header,
+                    self.location(previous_location, witxio),
+                );
+                if !previous_uses.is_empty() {
+                    report += &format!("\nbrought in by `use` from {}", use_chain(previous_uses));
+                }
+                report
+            }
+            Shadowed { location, used, .. } => format!(
+                "{}\n{}\nBrought in by `use` from {} at:\n{}",
+                self.location(location, witxio),
+                header,
+                use_chain(&used.uses),
+                self.location(&used.location, witxio),
+            ),
+        }
+    }
+}
+
+fn use_chain(uses: &[PathBuf]) -> String {
+    uses.iter()
+        .map(|p| format!("{:?}", p))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// Wraps each line of `text` at spaces to `width` columns, indenting the
+/// lines after the first by `indent`. Words longer than `width` are left
+/// whole.
+fn wrap(text: &str, width: usize, indent: usize) -> String {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut current));
+            } else if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        lines.push(current);
+    }
+    lines.join(&format!("\n{}", " ".repeat(indent)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockFs;
+
+    #[test]
+    fn plain() {
+        let source = "(typename $t\n  (list $missing))";
+        let fs = MockFs::new(&[("-", source)]);
+        let e = crate::parse(source).err().unwrap();
+        assert_eq!(
+            Style::plain().witx_error(&e, &fs),
+            "in \"-\":\n    2 |   (list $missing))\n                 ^\nerror[WITX0003]: Unknown name `missing`"
+        );
+        assert_eq!(Style::plain().witx_error(&e, &fs), e.report_with(&fs));
+    }
+
+    #[test]
+    fn colored() {
+        let colored = Style {
+            color: true,
+            width: None,
+        };
+        let source = "(typename $t $missing)";
+        let fs = MockFs::new(&[("-", source)]);
+        let e = crate::parse(source).err().unwrap();
+        assert_eq!(
+            colored.witx_error(&e, &fs),
+            "in \"-\":\n\x1b[1;34m    1 |\x1b[0m (typename $t $missing)\n                      \x1b[1;31m^\x1b[0m\n\
+             \x1b[1;31merror[WITX0003]\x1b[0m: \x1b[1mUnknown name `missing`\x1b[0m"
+        );
+        let warning = colored.warning(&"stale baseline entry");
+        assert_eq!(
+            warning,
+            "\x1b[1;33mwarning\x1b[0m: \x1b[1mstale baseline entry\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn wrapped() {
+        let style = Style {
+            color: false,
+            width: Some(30),
+        };
+        assert_eq!(
+            style.note(&"inferred module mapping wasi_ephemeral_nn=wasi_ephemeral_nn"),
+            "note: inferred module mapping\n      \
+             wasi_ephemeral_nn=wasi_ephemeral_nn"
+        );
+        assert_eq!(style.error(&"a b c\nd e"), "error: a b c\n       d e");
+    }
+}
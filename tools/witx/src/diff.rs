@@ -0,0 +1,668 @@
+//! Differences between two versions of a document.
+//!
+//! Types are matched by name (or, when ignoring type names, by definition),
+//! modules by name (or an explicit mapping), and functions by name within
+//! their module. Items present in both versions
+//! are compared structurally, ignoring documentation, and each change is
+//! classified by whether users of the old definition can keep using the new
+//! one: through representational equality of the types involved and, for
+//! functions, an unchanged core wasm signature.
+
+use crate::representation::length_representable;
+use crate::{
+    Divergence, Document, Id, InterfaceFunc, Module, RepContext, RepEquality, Representable, Type,
+    TypeRef,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// How an item changed between the old and new versions of a document.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    /// Present in both versions with a different definition. `compat`
+    /// describes whether values of the old definition are representable by
+    /// the new one.
+    Changed {
+        compat: RepEquality,
+    },
+}
+
+impl Change {
+    /// Whether users of the old version may be broken by this change.
+    pub fn is_breaking(&self) -> bool {
+        match self {
+            Change::Added => false,
+            Change::Removed => true,
+            Change::Changed { compat } => *compat == RepEquality::NotEq,
+        }
+    }
+
+    /// The version increment this change calls for on its own.
+    pub fn semver(&self) -> SemverBump {
+        if self.is_breaking() {
+            SemverBump::Major
+        } else {
+            SemverBump::Minor
+        }
+    }
+}
+
+/// The semantic versioning increment between two versions of a document.
+///
+/// Breaking changes are major, and every other change, whether it adds items,
+/// extends them compatibly or only renames them, is minor. Since differences
+/// in documentation aren't changes, two versions differing only in their docs
+/// (or not at all) are a patch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SemverBump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl fmt::Display for SemverBump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SemverBump::Patch => "patch",
+            SemverBump::Minor => "minor",
+            SemverBump::Major => "major",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeDiff {
+    /// Name of the type in the new version, or the old version if it was
+    /// removed.
+    pub name: Id,
+    /// Name of the type in the old version, if it was renamed.
+    pub old_name: Option<Id>,
+    pub change: Change,
+    /// Where the definitions diverge, when the change is incompatible.
+    pub divergence: Option<Divergence>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuncDiff {
+    pub name: Id,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleDiff {
+    /// Name of the module in the new version, or the old version if it was
+    /// removed.
+    pub name: Id,
+    /// Name of the module in the old version, if it was mapped from a
+    /// different name.
+    pub old_name: Option<Id>,
+    pub change: Change,
+    /// Changed functions of a module present in both versions.
+    pub funcs: Vec<FuncDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentDiff {
+    pub types: Vec<TypeDiff>,
+    pub modules: Vec<ModuleDiff>,
+}
+
+impl DocumentDiff {
+    /// Compare `old` and `new`, matching modules by name.
+    pub fn new(old: &Document, new: &Document) -> Self {
+        Self::with_module_mapping(old, new, &HashMap::new())
+    }
+
+    /// Compare `old` and `new`, where `module_mapping` maps the names of
+    /// modules in `new` to their names in `old`. Modules not in the mapping
+    /// are matched by name.
+    pub fn with_module_mapping(
+        old: &Document,
+        new: &Document,
+        module_mapping: &HashMap<String, String>,
+    ) -> Self {
+        Self::with_context(old, new, module_mapping, &RepContext::new())
+    }
+
+    /// Like `with_module_mapping`, comparing types in the context `cx`. If
+    /// `cx` ignores type names, types only in `old` are matched to types
+    /// only in `new` with the same definition, as renames.
+    pub fn with_context(
+        old: &Document,
+        new: &Document,
+        module_mapping: &HashMap<String, String>,
+        cx: &RepContext,
+    ) -> Self {
+        let renames = if cx.ignores_type_names() {
+            renamed_types(old, new, cx)
+        } else {
+            HashMap::new()
+        };
+        let mut types = Vec::new();
+        for nt in new.typenames() {
+            let old_name = renames
+                .iter()
+                .find(|(_, n)| **n == nt.name)
+                .map(|(o, _)| o.clone());
+            let (change, divergence) = match old.typename(old_name.as_ref().unwrap_or(&nt.name)) {
+                None => (Change::Added, None),
+                Some(o) if same_tref(&o.tref, &nt.tref, &renames) => match old_name {
+                    None => continue,
+                    Some(_) => (
+                        Change::Changed {
+                            compat: RepEquality::Eq,
+                        },
+                        None,
+                    ),
+                },
+                Some(o) => (
+                    Change::Changed {
+                        compat: o.representable_in(&nt, cx),
+                    },
+                    TypeRef::Name(o).divergence_in(&TypeRef::Name(nt.clone()), cx),
+                ),
+            };
+            types.push(TypeDiff {
+                name: nt.name.clone(),
+                old_name,
+                change,
+                divergence,
+            });
+        }
+        for ot in old.typenames() {
+            if new.typename(&ot.name).is_none() && !renames.contains_key(&ot.name) {
+                types.push(TypeDiff {
+                    name: ot.name.clone(),
+                    old_name: None,
+                    change: Change::Removed,
+                    divergence: None,
+                });
+            }
+        }
+
+        let mut modules = Vec::new();
+        let mut matched_old = Vec::new();
+        for nm in new.modules() {
+            let old_name = module_mapping
+                .get(nm.name.as_str())
+                .map(Id::new)
+                .unwrap_or_else(|| nm.name.clone());
+            let diff = match old.module(&old_name) {
+                Some(om) => {
+                    matched_old.push(old_name.clone());
+                    match ModuleDiff::changed(&om, &nm, &renames, cx) {
+                        Some(diff) => diff,
+                        None => continue,
+                    }
+                }
+                None => ModuleDiff {
+                    name: nm.name.clone(),
+                    old_name: None,
+                    change: Change::Added,
+                    funcs: Vec::new(),
+                },
+            };
+            modules.push(diff);
+        }
+        for om in old.modules() {
+            if !matched_old.contains(&om.name) {
+                modules.push(ModuleDiff {
+                    name: om.name.clone(),
+                    old_name: None,
+                    change: Change::Removed,
+                    funcs: Vec::new(),
+                });
+            }
+        }
+
+        DocumentDiff { types, modules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.modules.is_empty()
+    }
+
+    /// Whether any change may break users of the old version.
+    pub fn is_breaking(&self) -> bool {
+        self.types.iter().any(|t| t.change.is_breaking())
+            || self.modules.iter().any(|m| m.change.is_breaking())
+    }
+
+    /// The version increment from the old to the new version.
+    pub fn semver(&self) -> SemverBump {
+        self.types
+            .iter()
+            .map(|t| t.change)
+            .chain(self.modules.iter().map(|m| m.change))
+            .map(|c| c.semver())
+            .max()
+            .unwrap_or(SemverBump::Patch)
+    }
+}
+
+impl ModuleDiff {
+    fn changed(
+        old: &Rc<Module>,
+        new: &Rc<Module>,
+        renames: &HashMap<Id, Id>,
+        cx: &RepContext,
+    ) -> Option<Self> {
+        let mut funcs = Vec::new();
+        for nf in new.funcs() {
+            let change = match old.func(&nf.name) {
+                None => Change::Added,
+                Some(of) if same_func(&of, &nf, renames) => continue,
+                Some(of) => Change::Changed {
+                    compat: func_compat(&of, &nf, cx),
+                },
+            };
+            funcs.push(FuncDiff {
+                name: nf.name.clone(),
+                change,
+            });
+        }
+        for of in old.funcs() {
+            if new.func(&of.name).is_none() {
+                funcs.push(FuncDiff {
+                    name: of.name.clone(),
+                    change: Change::Removed,
+                });
+            }
+        }
+        if funcs.is_empty() {
+            return None;
+        }
+        let compat = if funcs.iter().any(|f| f.change.is_breaking()) {
+            RepEquality::NotEq
+        } else if funcs.iter().all(|f| {
+            f.change
+                == Change::Changed {
+                    compat: RepEquality::Eq,
+                }
+        }) {
+            RepEquality::Eq
+        } else {
+            RepEquality::Superset
+        };
+        Some(ModuleDiff {
+            name: new.name.clone(),
+            old_name: if old.name != new.name {
+                Some(old.name.clone())
+            } else {
+                None
+            },
+            change: Change::Changed { compat },
+            funcs,
+        })
+    }
+}
+
+/// The weaker of two compatibilities.
+fn worst(a: RepEquality, b: RepEquality) -> RepEquality {
+    match (a, b) {
+        (RepEquality::NotEq, _) | (_, RepEquality::NotEq) => RepEquality::NotEq,
+        (RepEquality::Superset, _) | (_, RepEquality::Superset) => RepEquality::Superset,
+        (RepEquality::Eq, RepEquality::Eq) => RepEquality::Eq,
+    }
+}
+
+/// Whether callers of `old` can call `new` instead: the core wasm signature
+/// must be unchanged, every argument of `old` must be representable by the
+/// corresponding parameter of `new` and have an accepted length, and every
+/// result of `new` must be representable by the corresponding result of
+/// `old`.
+fn func_compat(old: &InterfaceFunc, new: &InterfaceFunc, cx: &RepContext) -> RepEquality {
+    if old.wasm_signature() != new.wasm_signature()
+        || old.params.len() != new.params.len()
+        || old.results.len() != new.results.len()
+        || old.noreturn != new.noreturn
+    {
+        return RepEquality::NotEq;
+    }
+    let params = old.params.iter().zip(new.params.iter()).map(|(o, n)| {
+        worst(
+            o.tref.representable_in(&n.tref, cx),
+            length_representable(o.size.as_ref(), n.size.as_ref()),
+        )
+    });
+    let results = old
+        .results
+        .iter()
+        .zip(new.results.iter())
+        .map(|(o, n)| n.tref.representable_in(&o.tref, cx));
+    params.chain(results).fold(RepEquality::Eq, worst)
+}
+
+fn same_func(a: &InterfaceFunc, b: &InterfaceFunc, renames: &HashMap<Id, Id>) -> bool {
+    let same_params = |a: &[crate::InterfaceFuncParam], b: &[crate::InterfaceFuncParam]| {
+        a.len() == b.len()
+            && a.iter().zip(b.iter()).all(|(a, b)| {
+                a.name == b.name && a.size == b.size && same_tref(&a.tref, &b.tref, renames)
+            })
+    };
+    a.noreturn == b.noreturn
+        && same_params(&a.params, &b.params)
+        && same_params(&a.results, &b.results)
+}
+
+/// Types only in `old` with the same definition as a type only in `new`,
+/// mapped to the name of that type.
+fn renamed_types(old: &Document, new: &Document, cx: &RepContext) -> HashMap<Id, Id> {
+    let mut added = new
+        .typenames()
+        .filter(|nt| old.typename(&nt.name).is_none())
+        .collect::<Vec<_>>();
+    let mut renames = HashMap::new();
+    for ot in old.typenames() {
+        if new.typename(&ot.name).is_some() {
+            continue;
+        }
+        let same = added.iter().position(|nt| {
+            ot.representable_in(nt, cx) == RepEquality::Eq
+                && nt.representable_in(&ot, cx) == RepEquality::Eq
+        });
+        if let Some(i) = same {
+            renames.insert(ot.name.clone(), added.remove(i).name.clone());
+        }
+    }
+    renames
+}
+
+/// Structural equality of two type references, ignoring documentation.
+/// Named types are compared by name only, since their definitions are
+/// compared on their own: a name in `a` also matches the name it was renamed
+/// to in `renames`.
+fn same_tref(a: &TypeRef, b: &TypeRef, renames: &HashMap<Id, Id>) -> bool {
+    match (a, b) {
+        (TypeRef::Name(a), TypeRef::Name(b)) => {
+            a.name == b.name || renames.get(&a.name) == Some(&b.name)
+        }
+        (TypeRef::Value(a), TypeRef::Value(b)) => same_type(a, b, renames),
+        _ => false,
+    }
+}
+
+fn same_type(a: &Type, b: &Type, renames: &HashMap<Id, Id>) -> bool {
+    match (a, b) {
+        (Type::Record(a), Type::Record(b)) => {
+            a.kind == b.kind
+                && a.members.len() == b.members.len()
+                && a.members.iter().zip(b.members.iter()).all(|(a, b)| {
+                    a.name == b.name && a.size == b.size && same_tref(&a.tref, &b.tref, renames)
+                })
+        }
+        (Type::Variant(a), Type::Variant(b)) => {
+            a.tag_repr == b.tag_repr
+                && a.cases.len() == b.cases.len()
+                && a.cases.iter().zip(b.cases.iter()).all(|(a, b)| {
+                    a.name == b.name
+                        && match (&a.tref, &b.tref) {
+                            (Some(a), Some(b)) => same_tref(a, b, renames),
+                            (None, None) => true,
+                            _ => false,
+                        }
+                })
+        }
+        (Type::Handle(_), Type::Handle(_)) => true,
+        (Type::List(a), Type::List(b))
+        | (Type::Pointer(a), Type::Pointer(b))
+        | (Type::ConstPointer(a), Type::ConstPointer(b)) => same_tref(a, b, renames),
+        (Type::Builtin(a), Type::Builtin(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> DocumentDiff {
+        DocumentDiff::new(&crate::parse(old).unwrap(), &crate::parse(new).unwrap())
+    }
+
+    #[test]
+    fn unchanged_ignores_docs() {
+        let d = diff(
+            "(typename $e (enum $a $b)) (module $m (@interface func (export \"f\") (param $x $e)))",
+            ";;; docs
+             (typename $e (enum $a $b))
+             (module $m ;;; more docs
+                (@interface func (export \"f\") (param $x $e)))",
+        );
+        assert!(d.is_empty());
+    }
+
+    #[test]
+    fn types() {
+        let d = diff(
+            "(typename $e (enum $a $b))
+             (typename $r (record (field $x u8)))
+             (typename $gone u32)",
+            "(typename $e (enum $a $b $c))
+             (typename $r (record (field $y u8)))
+             (typename $new u32)",
+        );
+        assert_eq!(
+            d.types,
+            vec![
+                TypeDiff {
+                    old_name: None,
+                    name: Id::new("e"),
+                    change: Change::Changed {
+                        compat: RepEquality::Superset
+                    },
+                    divergence: None,
+                },
+                TypeDiff {
+                    old_name: None,
+                    name: Id::new("r"),
+                    change: Change::Changed {
+                        compat: RepEquality::NotEq
+                    },
+                    divergence: Some(Divergence {
+                        path: vec![crate::PathSegment::Type(Id::new("r"))],
+                        reason: crate::DivergenceReason::Member(crate::RecordMismatch::Renamed {
+                            member: Id::new("x"),
+                            by: Id::new("y"),
+                        }),
+                    }),
+                },
+                TypeDiff {
+                    old_name: None,
+                    name: Id::new("new"),
+                    change: Change::Added,
+                    divergence: None,
+                },
+                TypeDiff {
+                    old_name: None,
+                    name: Id::new("gone"),
+                    change: Change::Removed,
+                    divergence: None,
+                },
+            ]
+        );
+        assert!(d.is_breaking());
+    }
+
+    #[test]
+    fn funcs() {
+        let d = diff(
+            "(module $m
+                (@interface func (export \"widen\") (param $x u8))
+                (@interface func (export \"retype\") (param $x u32))
+                (@interface func (export \"gone\")))",
+            "(module $m
+                (@interface func (export \"widen\") (param $x u16))
+                (@interface func (export \"retype\") (param $x u64))
+                (@interface func (export \"new\")))",
+        );
+        assert_eq!(d.modules.len(), 1);
+        let changes = d.modules[0]
+            .funcs
+            .iter()
+            .map(|f| (f.name.as_str(), f.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    "widen",
+                    Change::Changed {
+                        compat: RepEquality::Superset
+                    }
+                ),
+                // u32 and u64 are lowered to different wasm types.
+                (
+                    "retype",
+                    Change::Changed {
+                        compat: RepEquality::NotEq
+                    }
+                ),
+                ("new", Change::Added),
+                ("gone", Change::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn semver() {
+        let old = "(typename $e (enum $a $b))
+             (module $m (@interface func (export \"f\") (param $x $e)))";
+        assert_eq!(
+            diff(old, &format!(";;; docs\n{}", old)).semver(),
+            SemverBump::Patch
+        );
+        assert_eq!(
+            diff(
+                old,
+                "(typename $e (enum $a $b))
+                 (module $m
+                    (@interface func (export \"f\") (param $x $e))
+                    (@interface func (export \"g\")))"
+            )
+            .semver(),
+            SemverBump::Minor
+        );
+        assert_eq!(
+            diff(
+                old,
+                "(typename $e (enum $a $b $c))
+                 (module $m (@interface func (export \"f\") (param $x $e)))"
+            )
+            .semver(),
+            SemverBump::Minor
+        );
+        assert_eq!(
+            diff(
+                old,
+                "(typename $e (enum $a))
+                 (module $m (@interface func (export \"f\") (param $x $e)))"
+            )
+            .semver(),
+            SemverBump::Major
+        );
+        assert_eq!(SemverBump::Major.to_string(), "major");
+    }
+
+    #[test]
+    fn list_lengths() {
+        let d = diff(
+            "(module $m
+                (@interface func (export \"relax\")
+                    (param $n u32)
+                    (param $x (list u8) (@witx size $n)))
+                (@interface func (export \"constrain\")
+                    (param $n u32)
+                    (param $x (list u8))))",
+            "(module $m
+                (@interface func (export \"relax\")
+                    (param $n u32)
+                    (param $x (list u8)))
+                (@interface func (export \"constrain\")
+                    (param $n u32)
+                    (param $x (list u8) (@witx size $n))))",
+        );
+        let changes = d.modules[0]
+            .funcs
+            .iter()
+            .map(|f| (f.name.as_str(), f.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    "relax",
+                    Change::Changed {
+                        compat: RepEquality::Superset
+                    }
+                ),
+                (
+                    "constrain",
+                    Change::Changed {
+                        compat: RepEquality::NotEq
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn renamed_types() {
+        let old = crate::parse(
+            "(typename $fd_t (handle))
+             (typename $size_t u32)
+             (typename $gone u64)
+             (module $m (@interface func (export \"f\") (param $fd $fd_t) (param $n $size_t)))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(typename $fd (handle))
+             (typename $size u32)
+             (module $m (@interface func (export \"f\") (param $fd $fd) (param $n $size)))",
+        )
+        .unwrap();
+        let by_name = DocumentDiff::new(&old, &new);
+        assert_eq!(by_name.types.len(), 5);
+        assert!(by_name.is_breaking());
+
+        let cx = RepContext::new().ignore_type_names();
+        let d = DocumentDiff::with_context(&old, &new, &HashMap::new(), &cx);
+        let types = d
+            .types
+            .iter()
+            .map(|t| {
+                (
+                    t.name.as_str(),
+                    t.old_name.as_ref().map(|n| n.as_str()),
+                    t.change,
+                )
+            })
+            .collect::<Vec<_>>();
+        let renamed = Change::Changed {
+            compat: RepEquality::Eq,
+        };
+        assert_eq!(
+            types,
+            vec![
+                ("fd", Some("fd_t"), renamed),
+                ("size", Some("size_t"), renamed),
+                ("gone", None, Change::Removed),
+            ]
+        );
+        // The function only changed the names of its types.
+        assert!(d.modules.is_empty());
+    }
+
+    #[test]
+    fn module_mapping() {
+        let old = crate::parse("(module $old (@interface func (export \"f\")))").unwrap();
+        let new = crate::parse("(module $new (@interface func (export \"f\")))").unwrap();
+        assert_eq!(DocumentDiff::new(&old, &new).modules.len(), 2);
+        let mut mapping = HashMap::new();
+        mapping.insert("new".to_string(), "old".to_string());
+        assert!(DocumentDiff::with_module_mapping(&old, &new, &mapping).is_empty());
+    }
+}
@@ -1,15 +1,41 @@
 use super::{
-    md::{MdFunc, MdHeading, MdNamedType, MdNodeRef, MdSection, ToMarkdown},
+    md::{MdCode, MdFunc, MdHeading, MdNamedType, MdNodeRef, MdSection, MdTable, ToMarkdown},
     Documentation,
 };
 use crate::{
     ast::*,
+    capabilities::{CapabilityKind, SupportMatrix},
+    diff::{Change, DocumentDiff},
+    graph::module_to_mermaid,
     layout::Layout,
     polyfill::{FuncPolyfill, ModulePolyfill, ParamPolyfill, Polyfill, TypePolyfill},
     RepEquality,
 };
 use std::collections::HashMap;
 
+/// A note saying which file brought in a definition, if a `use` did.
+fn used_from(provenance: Option<&Provenance>) -> String {
+    let name = |p: &std::path::Path| {
+        let name = p
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        format!("`{}`", name)
+    };
+    match provenance {
+        Some(p) if p.is_used() => format!(
+            "\nDefined in {}, brought in by `use` from {}.\n",
+            name(p.path()),
+            p.uses
+                .iter()
+                .map(|u| name(u))
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        ),
+        _ => String::new(),
+    }
+}
+
 fn heading_from_node(node: &MdNodeRef, levels_down: usize) -> MdHeading {
     MdHeading::new_header(node.borrow().ancestors().len() + levels_down)
 }
@@ -31,10 +57,11 @@ impl ToMarkdown for Document {
                 name,
                 name,
                 format!(
-                    "{}\nSize: {}\n\nAlignment: {}\n",
+                    "{}\nSize: {}\n\nAlignment: {}\n{}",
                     &d.docs,
                     &d.mem_size(),
-                    &d.mem_align()
+                    &d.mem_align(),
+                    used_from(self.provenance(&d.name))
                 )
                 .as_str(),
             ));
@@ -59,6 +86,19 @@ impl ToMarkdown for Document {
             content.id = Some(d.name.as_str().to_owned());
             let child = modules.new_child(content);
             d.generate(child.clone());
+            for func in d.funcs() {
+                let note = used_from(self.func_provenance(&d.name, &func.name));
+                if note.is_empty() {
+                    continue;
+                }
+                for node in child.borrow().children() {
+                    let mut content = node.any_ref_mut();
+                    if content.id() == Some(func.name.as_str()) {
+                        let docs = format!("{}{}", content.docs().unwrap_or_default(), note);
+                        content.set_docs(&docs);
+                    }
+                }
+            }
         }
 
         assert!(constants_by_name.is_empty());
@@ -100,12 +140,50 @@ impl ToMarkdown for Type {
     }
 }
 
+/// A table cell naming `tref`, linked if it's a named type.
+fn type_cell(tref: &TypeRef) -> String {
+    match tref {
+        TypeRef::Name(n) => format!("[`{0}`](#{0})", n.name.as_str()),
+        TypeRef::Value(_) => format!("`{}`", tref.type_name()),
+    }
+}
+
 impl ToMarkdown for RecordDatatype {
     fn generate(&self, node: MdNodeRef) {
         let heading = heading_from_node(&node, 1);
+        let layout = self.member_layout();
+        let table = if let Some(repr) = self.bitflags_repr() {
+            node.new_child(MdSection::new(heading, "Flag values"));
+            let mut table = MdTable::new(&["Flag", "Bit", "Value"], &["Bit", "Value"]);
+            let digits = repr.mem_size() * 2;
+            for m in layout.iter() {
+                let bit = m.offset / 4;
+                table.rows.push(vec![
+                    format!("`{}`", m.member.name.as_str()),
+                    bit.to_string(),
+                    format!("`0x{:0digits$x}`", 1u64 << bit, digits = digits),
+                ]);
+            }
+            table
+        } else {
+            node.new_child(MdSection::new(heading, "Record layout"));
+            let mut table =
+                MdTable::new(&["Member", "Type", "Offset", "Size"], &["Offset", "Size"]);
+            for m in layout.iter() {
+                table.rows.push(vec![
+                    format!("`{}`", m.member.name.as_str()),
+                    type_cell(&m.member.tref),
+                    m.offset.to_string(),
+                    m.member.tref.mem_size().to_string(),
+                ]);
+            }
+            table
+        };
+        node.new_child(table);
+
         node.new_child(MdSection::new(heading, "Record members"));
 
-        for member_layout in &self.member_layout() {
+        for member_layout in &layout {
             let member = member_layout.member;
             let offset = member_layout.offset;
             let name = member.name.as_str();
@@ -119,11 +197,15 @@ impl ToMarkdown for RecordDatatype {
             } else {
                 (1, "Offset")
             };
+            let mut docs = format!("{}\n{}: {}\n", &member.docs, offset_desc, offset / div);
+            if let Some(size) = &member.size {
+                docs.push_str(&format!("Size: `{}`\n", size));
+            }
             let n = node.new_child(MdNamedType::new(
                 MdHeading::new_bullet(),
                 id.as_str(),
                 name,
-                format!("{}\n{}: {}\n", &member.docs, offset_desc, offset / div).as_str(),
+                docs.as_str(),
             ));
             member.tref.generate(n.clone());
         }
@@ -154,8 +236,34 @@ impl ToMarkdown for Variant {
                 MdHeading::new_bullet(),
                 format!("tag_size: {}", tag.size),
             ));
+            node.new_child(MdSection::new(
+                MdHeading::new_bullet(),
+                format!("payload_offset: {}", self.payload_offset()),
+            ));
         }
 
+        let heading = heading_from_node(&node, 1);
+        node.new_child(MdSection::new(heading, "Case values"));
+        let mut table = if self.is_enum() {
+            MdTable::new(&["Case", "Value"], &["Value"])
+        } else {
+            MdTable::new(&["Case", "Value", "Payload", "Size"], &["Value", "Size"])
+        };
+        for (i, case) in self.cases.iter().enumerate() {
+            let mut row = vec![format!("`{}`", case.name.as_str()), i.to_string()];
+            if !self.is_enum() {
+                match &case.tref {
+                    Some(tref) => {
+                        row.push(type_cell(tref));
+                        row.push(tref.mem_size().to_string());
+                    }
+                    None => row.extend(vec![String::new(), String::new()]),
+                }
+            }
+            table.rows.push(row);
+        }
+        node.new_child(table);
+
         let heading = heading_from_node(&node, 1);
         node.new_child(MdSection::new(heading, "Variant cases"));
 
@@ -196,6 +304,11 @@ impl ToMarkdown for Module {
             import.generate(child.clone());
         }
 
+        if self.funcs().next().is_some() {
+            node.new_child(MdSection::new(heading, "Diagram"));
+            node.new_child(MdCode::new("mermaid", &module_to_mermaid(self)));
+        }
+
         let funcs = node.new_child(MdSection::new(heading, "Functions"));
         for func in self.funcs() {
             let name = func.name.as_str();
@@ -220,8 +333,22 @@ impl ToMarkdown for ModuleImport {
     }
 }
 
+/// The core wasm signature `func` is imported with, in the text format.
+pub(super) fn core_signature(func: &InterfaceFunc) -> String {
+    let (params, results) = func.wasm_signature();
+    let mut parts = Vec::new();
+    for (kind, types) in [("param", params), ("result", results)] {
+        if !types.is_empty() {
+            let types = types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+            parts.push(format!("({} {})", kind, types.join(" ")));
+        }
+    }
+    parts.join(" ")
+}
+
 impl ToMarkdown for InterfaceFunc {
     fn generate(&self, node: MdNodeRef) {
+        node.content_ref_mut::<MdFunc>().core = Some(core_signature(self));
         let heading = heading_from_node(&node, 1);
         node.new_child(MdSection::new(heading, "Params"));
         for param in &self.params {
@@ -270,7 +397,11 @@ impl ToMarkdown for InterfaceFunc {
 impl ToMarkdown for InterfaceFuncParam {
     fn generate(&self, node: MdNodeRef) {
         self.tref.generate(node.clone());
-        node.content_ref_mut::<MdNamedType>().docs = self.docs.clone();
+        let mut docs = self.docs.clone();
+        if let Some(size) = &self.size {
+            docs.push_str(&format!("\nSize: `{}`\n", size));
+        }
+        node.content_ref_mut::<MdNamedType>().docs = docs;
     }
 }
 
@@ -320,15 +451,11 @@ impl TypeRef {
                         }
                         ret.push_str(&member.tref.type_name());
                     }
-                    ret.push_str(")");
+                    ret.push(')');
                     ret
                 }
-                Type::Record { .. } => {
-                    format!("Record")
-                }
-                Type::Handle { .. } => {
-                    format!("Handle")
-                }
+                Type::Record { .. } => "Record".to_string(),
+                Type::Handle { .. } => "Handle".to_string(),
                 Type::Variant(v) => {
                     if let Some((ok, err)) = v.as_expected() {
                         let ok = match ok {
@@ -341,9 +468,9 @@ impl TypeRef {
                         };
                         format!("Result<{}, {}>", ok, err)
                     } else if v.is_bool() {
-                        format!("bool")
+                        "bool".to_string()
                     } else {
-                        format!("Variant")
+                        "Variant".to_string()
                     }
                 }
             },
@@ -389,6 +516,11 @@ impl Documentation for ModulePolyfill {
             self.funcs
                 .iter()
                 .map(|f| f.to_md())
+                .chain(
+                    self.added_funcs
+                        .iter()
+                        .map(|f| format!("* `{}`: added", f.name.as_str()))
+                )
                 .collect::<Vec<String>>()
                 .join("\n"),
         )
@@ -472,32 +604,157 @@ impl Documentation for ParamPolyfill {
                 self.new.tref.type_name()
             ),
         };
-        format!("{}: {}", name, repr)
+        let report = self.report();
+        let notes = if report.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", report.join("; "))
+        };
+        format!("{}: {}{}", name, repr, notes)
     }
 }
 
 impl Documentation for TypePolyfill {
     fn to_md(&self) -> String {
-        fn repeq_name(r: RepEquality) -> &'static str {
-            match r {
-                RepEquality::Eq => ": compatible",
-                RepEquality::Superset => ": superset",
-                RepEquality::NotEq => "",
-            }
-        }
+        let repeq = match (self.repeq(), self.conversion()) {
+            (RepEquality::Eq, _) => ": compatible".to_string(),
+            (RepEquality::Superset, Some(conversion)) => format!(": superset ({})", conversion),
+            (RepEquality::Superset, None) => ": superset".to_string(),
+            (RepEquality::NotEq, _) => String::new(),
+        };
         match self {
             TypePolyfill::OldToNew(o, n) => format!(
                 "* old `{}` => new `{}`{}",
                 o.type_name(),
                 n.type_name(),
-                repeq_name(self.repeq())
+                repeq
             ),
             TypePolyfill::NewToOld(n, o) => format!(
                 "* new `{}` => old `{}`{}",
                 n.type_name(),
                 o.type_name(),
-                repeq_name(self.repeq())
+                repeq
             ),
         }
     }
 }
+
+// Generate a changelog for the differences between two documents
+impl Documentation for DocumentDiff {
+    fn to_md(&self) -> String {
+        fn entry(change: &Change, item: String) -> String {
+            let compat = match change {
+                Change::Changed {
+                    compat: RepEquality::Eq,
+                } => ": compatible",
+                Change::Changed {
+                    compat: RepEquality::Superset,
+                } => ": compatible, extends the previous definition",
+                Change::Changed {
+                    compat: RepEquality::NotEq,
+                } => ": incompatible with the previous definition",
+                Change::Added | Change::Removed => "",
+            };
+            let marker = if change.is_breaking() {
+                "**breaking** "
+            } else {
+                ""
+            };
+            format!("* {}{}{}", marker, item, compat)
+        }
+
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        let mut removed = Vec::new();
+        let mut push = |change: &Change, item: String| {
+            let section = match change {
+                Change::Added => &mut added,
+                Change::Changed { .. } => &mut changed,
+                Change::Removed => &mut removed,
+            };
+            section.push(entry(change, item));
+        };
+        for t in self.types.iter() {
+            let mut item = format!("type `{}`", t.name.as_str());
+            if let Some(old_name) = &t.old_name {
+                item.push_str(&format!(" (previously `{}`)", old_name.as_str()));
+            }
+            if let Some(divergence) = &t.divergence {
+                item.push_str(&format!(" ({})", divergence));
+            }
+            push(&t.change, item);
+        }
+        for m in self.modules.iter() {
+            if let Change::Changed { .. } = m.change {
+                if let Some(old_name) = &m.old_name {
+                    push(
+                        &m.change,
+                        format!(
+                            "module `{}` (previously `{}`)",
+                            m.name.as_str(),
+                            old_name.as_str()
+                        ),
+                    );
+                }
+                for f in m.funcs.iter() {
+                    push(
+                        &f.change,
+                        format!("function `{}::{}`", m.name.as_str(), f.name.as_str()),
+                    );
+                }
+            } else {
+                push(&m.change, format!("module `{}`", m.name.as_str()));
+            }
+        }
+
+        let mut md = String::from("# Changelog\n");
+        if self.is_empty() {
+            md.push_str("\nNo changes.\n");
+        }
+        for (title, entries) in &[("Added", added), ("Changed", changed), ("Removed", removed)] {
+            if !entries.is_empty() {
+                md.push_str(&format!("\n## {}\n\n{}\n", title, entries.join("\n")));
+            }
+        }
+        md
+    }
+}
+
+impl Documentation for SupportMatrix {
+    fn to_md(&self) -> String {
+        let mut md = String::from("# Support matrix\n\n");
+        if self.capabilities.is_empty() {
+            md.push_str("No optional features.\n");
+            return md;
+        }
+        md.push_str("| Feature | Capability |");
+        for i in self.implementations.iter() {
+            md.push_str(&format!(" {} |", i.name));
+        }
+        md.push_str("\n| --- | --- |");
+        md.push_str(&" :---: |".repeat(self.implementations.len()));
+        md.push('\n');
+        for c in self.capabilities.iter() {
+            let capability = match &c.kind {
+                CapabilityKind::Case { ty, case } => {
+                    format!("`{}::{}`", ty.as_str(), case.as_str())
+                }
+                CapabilityKind::Funcs(funcs) => funcs
+                    .iter()
+                    .map(|(m, f)| format!("`{}::{}`", m.as_str(), f.as_str()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+            md.push_str(&format!("| `{}` | {} |", c.feature.as_str(), capability));
+            for i in self.implementations.iter() {
+                md.push_str(if self.supports(i, c) {
+                    " yes |"
+                } else {
+                    " no |"
+                });
+            }
+            md.push('\n');
+        }
+        md
+    }
+}
@@ -0,0 +1,385 @@
+//! Standalone HTML pages comparing two versions of a document, for sharing
+//! reviews of a snapshot with readers who won't run the tool.
+//!
+//! Each module is a collapsible section listing its changed items, with the
+//! old and new definitions side by side and the verdict color-coded.
+
+use crate::diff::{Change, DocumentDiff};
+use crate::fingerprint::strip_docs;
+use crate::polyfill::{FuncPolyfill, ParamUnknown, Polyfill};
+use crate::{Document, InterfaceFunc, InterfaceFuncParam, NamedType, RepEquality};
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; margin: 0.5em 0 1.5em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+pre { margin: 0; white-space: pre-wrap; }
+summary { font-size: 1.2em; font-weight: bold; cursor: pointer; margin: 0.5em 0; }
+ul { margin: 0.3em 0 0; padding-left: 1.2em; }
+.eq { background: #e6f4ea; }
+.superset { background: #fef7e0; }
+.noteq { background: #fce8e6; }
+.added { background: #e8f0fe; }
+.removed { background: #f1f3f4; }
+";
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\n{style}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n",
+        title = escape(title),
+        style = STYLE,
+        body = body
+    )
+}
+
+/// The CSS class and description of a verdict.
+fn verdict(change: &Change) -> (&'static str, &'static str) {
+    match change {
+        Change::Added => ("added", "added"),
+        Change::Removed => ("removed", "removed"),
+        Change::Changed { compat } => compat_verdict(*compat),
+    }
+}
+
+fn compat_verdict(compat: RepEquality) -> (&'static str, &'static str) {
+    match compat {
+        RepEquality::Eq => ("eq", "compatible"),
+        RepEquality::Superset => ("superset", "compatible superset"),
+        RepEquality::NotEq => ("noteq", "incompatible"),
+    }
+}
+
+fn param(p: &InterfaceFuncParam) -> String {
+    format!("{}: {}", p.name.as_str(), p.tref.type_name())
+}
+
+/// A function's signature, one param or result per line.
+fn signature(func: &InterfaceFunc) -> String {
+    let list = |params: &[InterfaceFuncParam]| {
+        params
+            .iter()
+            .map(|p| format!("\n    {},", param(p)))
+            .collect::<String>()
+    };
+    let mut out = format!("{}({}\n)", func.name.as_str(), list(&func.params));
+    if !func.results.is_empty() {
+        out.push_str(&format!(" -> ({}\n)", list(&func.results)));
+    }
+    if func.noreturn {
+        out.push_str(" noreturn");
+    }
+    out
+}
+
+fn definition(nt: &NamedType) -> String {
+    strip_docs(nt.to_sexpr()).to_string()
+}
+
+/// A row of a comparison table. `notes` are listed under the verdict.
+fn row(
+    name: &str,
+    (class, verdict): (&str, &str),
+    notes: &[String],
+    old: Option<String>,
+    new: Option<String>,
+) -> String {
+    let notes = if notes.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<ul>{}</ul>",
+            notes
+                .iter()
+                .map(|n| format!("<li>{}</li>", escape(n)))
+                .collect::<String>()
+        )
+    };
+    let cell = |s: Option<String>| match s {
+        Some(s) => format!("<td><pre>{}</pre></td>", escape(&s)),
+        None => "<td></td>".to_string(),
+    };
+    format!(
+        "<tr class=\"{}\"><td><code>{}</code></td><td>{}{}</td>{}{}</tr>\n",
+        class,
+        escape(name),
+        verdict,
+        notes,
+        cell(old),
+        cell(new)
+    )
+}
+
+fn table(rows: &str) -> String {
+    format!(
+        "<table>\n<tr><th>Item</th><th>Verdict</th><th>Old</th><th>New</th></tr>\n{}</table>\n",
+        rows
+    )
+}
+
+/// A collapsible section, with its verdict if it has one.
+fn section(summary: &str, verdict: Option<(&str, &str)>, contents: &str) -> String {
+    let summary = match verdict {
+        Some((class, verdict)) => format!(
+            "<summary class=\"{}\">{} ({})</summary>",
+            class, summary, verdict
+        ),
+        None => format!("<summary>{}</summary>", summary),
+    };
+    format!("<details open>\n{}\n{}</details>\n", summary, contents)
+}
+
+impl DocumentDiff {
+    /// An HTML page of this diff between `old` and `new`, the documents it
+    /// compares.
+    pub fn to_html(&self, old: &Document, new: &Document) -> String {
+        let mut body = String::new();
+        if self.is_empty() {
+            body.push_str("<p>No changes.</p>\n");
+        }
+        if !self.types.is_empty() {
+            let rows = self
+                .types
+                .iter()
+                .map(|t| {
+                    let notes = t
+                        .old_name
+                        .iter()
+                        .map(|o| format!("previously `{}`", o.as_str()))
+                        .chain(t.divergence.iter().map(|d| d.to_string()))
+                        .collect::<Vec<_>>();
+                    let old_name = t.old_name.as_ref().unwrap_or(&t.name);
+                    row(
+                        t.name.as_str(),
+                        verdict(&t.change),
+                        &notes,
+                        old.typename(old_name).map(|nt| definition(&nt)),
+                        new.typename(&t.name).map(|nt| definition(&nt)),
+                    )
+                })
+                .collect::<String>();
+            body.push_str(&section("Types", None, &table(&rows)));
+        }
+        for m in self.modules.iter() {
+            let old_module = old.module(m.old_name.as_ref().unwrap_or(&m.name));
+            let new_module = new.module(&m.name);
+            let mut summary = format!("Module <code>{}</code>", escape(m.name.as_str()));
+            if let Some(old_name) = &m.old_name {
+                summary.push_str(&format!(
+                    ", previously <code>{}</code>",
+                    escape(old_name.as_str())
+                ));
+            }
+            let rows = m
+                .funcs
+                .iter()
+                .map(|f| {
+                    let sig = |module: &Option<std::rc::Rc<crate::Module>>| {
+                        module
+                            .as_ref()
+                            .and_then(|m| m.func(&f.name))
+                            .map(|f| signature(&f))
+                    };
+                    row(
+                        f.name.as_str(),
+                        verdict(&f.change),
+                        &[],
+                        sig(&old_module),
+                        sig(&new_module),
+                    )
+                })
+                .collect::<String>();
+            let contents = if rows.is_empty() {
+                String::new()
+            } else {
+                table(&rows)
+            };
+            body.push_str(&section(&summary, Some(verdict(&m.change)), &contents));
+        }
+        page("Changelog", &body)
+    }
+}
+
+impl FuncPolyfill {
+    fn compat(&self) -> RepEquality {
+        if self.full_compat() {
+            return RepEquality::Eq;
+        }
+        let params = self.mapped_params.iter().chain(self.mapped_results.iter());
+        if !self.unknown_params.is_empty()
+            || !self.unknown_results.is_empty()
+            || params.clone().any(|p| p.repeq() == RepEquality::NotEq)
+        {
+            RepEquality::NotEq
+        } else {
+            RepEquality::Superset
+        }
+    }
+
+    fn notes(&self) -> Vec<String> {
+        let mut notes = Vec::new();
+        for (kind, params) in [
+            ("param", &self.mapped_params),
+            ("result", &self.mapped_results),
+        ] {
+            for p in params.iter().filter(|p| !p.full_compat()) {
+                let (_, verdict) = compat_verdict(p.repeq());
+                let mut note = format!("{} `{}`: {}", kind, p.new.name.as_str(), verdict);
+                let report = p.report();
+                if !report.is_empty() {
+                    note.push_str(&format!(" ({})", report.join("; ")));
+                }
+                notes.push(note);
+            }
+        }
+        for (kind, unknown) in [
+            ("param", &self.unknown_params),
+            ("result", &self.unknown_results),
+        ] {
+            for u in unknown.iter() {
+                let change = match u {
+                    ParamUnknown::New(_) => "added",
+                    ParamUnknown::Old(_) => "removed",
+                };
+                notes.push(format!("{} `{}` {}", kind, u.param().name.as_str(), change));
+            }
+        }
+        notes
+    }
+}
+
+impl Polyfill {
+    /// An HTML page of this polyfill, function by function.
+    pub fn to_html(&self) -> String {
+        let mut body = String::new();
+        for m in self.modules.iter() {
+            let mut rows = table_rows(&m.funcs);
+            for f in m.added_funcs.iter() {
+                rows.push_str(&row(
+                    f.name.as_str(),
+                    ("added", "added"),
+                    &[],
+                    None,
+                    Some(signature(f)),
+                ));
+            }
+            let compat = m
+                .funcs
+                .iter()
+                .map(|f| f.compat())
+                .fold(RepEquality::Eq, |a, b| a.then(&b));
+            body.push_str(&section(
+                &format!(
+                    "<code>{}</code> in terms of <code>{}</code>",
+                    escape(m.new.name.as_str()),
+                    escape(m.old.name.as_str())
+                ),
+                Some(compat_verdict(compat)),
+                &table(&rows),
+            ));
+        }
+        page("Polyfill", &body)
+    }
+}
+
+fn table_rows(funcs: &[FuncPolyfill]) -> String {
+    funcs
+        .iter()
+        .map(|f| {
+            row(
+                f.old.name.as_str(),
+                compat_verdict(f.compat()),
+                &f.notes(),
+                Some(signature(&f.old)),
+                Some(signature(&f.new)),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn docs() -> (Document, Document) {
+        let old = crate::parse(
+            "(typename $e (enum $a $b))
+             (module $m
+                (@interface func (export \"same\") (param $x u32))
+                (@interface func (export \"f\") (param $n u32) (param $e $e))
+                (@interface func (export \"gone\")))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(typename $e (enum $a $b $c))
+             (module $m
+                (@interface func (export \"same\") (param $x u32))
+                (@interface func (export \"f\") (param $n u8) (param $e $e))
+                (@interface func (export \"gone\"))
+                (@interface func (export \"g\") (result $r u32)))",
+        )
+        .unwrap();
+        (old, new)
+    }
+
+    #[test]
+    fn diff() {
+        let (old, new) = docs();
+        let html = DocumentDiff::new(&old, &new).to_html(&old, &new);
+        assert!(html.starts_with("<!DOCTYPE html>"), "{}", html);
+        assert!(html.contains(
+            "<tr class=\"superset\"><td><code>e</code></td><td>compatible superset</td>\
+             <td><pre>(typename $e (enum (@witx tag u32) $a $b))</pre></td>"
+        ));
+        assert!(
+            html.contains("<tr class=\"noteq\"><td><code>f</code></td>"),
+            "{}",
+            html
+        );
+        assert!(html.contains(
+            "<tr class=\"added\"><td><code>g</code></td><td>added</td><td></td>\
+             <td><pre>g(\n) -&gt; (\n    r: u32,\n)</pre></td></tr>"
+        ));
+        assert!(html.contains("<details open>\n<summary class=\"noteq\">Module <code>m</code>"));
+    }
+
+    #[test]
+    fn polyfill() {
+        let (old, new) = docs();
+        let mut mapping = HashMap::new();
+        mapping.insert("m".to_string(), "m".to_string());
+        let html = Polyfill::new(&new, &old, &mapping).unwrap().to_html();
+        assert!(html.contains("<tr class=\"eq\"><td><code>same</code></td><td>compatible</td>"));
+        assert!(
+            html.contains(
+                "<td>incompatible<ul><li>param `n`: incompatible (u32 vs u8)</li>\
+             <li>param `e`: compatible superset (case `c` added)</li></ul></td>\
+             <td><pre>f(\n    n: u32,\n    e: e,\n)</pre></td>"
+            ),
+            "{}",
+            html
+        );
+        assert!(html.contains("<tr class=\"added\"><td><code>g</code></td>"));
+        assert!(html.contains("<summary class=\"noteq\"><code>m</code> in terms of <code>m</code>"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(escape("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+}
@@ -0,0 +1,222 @@
+//! Documentation as JSON, for tools which track the items of a document.
+
+use crate::{
+    Document, InterfaceFunc, InterfaceFuncParam, Meta, Module, NamedType, Provenance, Type,
+};
+use std::fmt::{self, Write};
+
+enum Json {
+    Str(String),
+    Bool(bool),
+    Num(usize),
+    Arr(Vec<Json>),
+    Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn str(s: &str) -> Json {
+        Json::Str(s.to_string())
+    }
+
+    fn obj(fields: Vec<(&str, Json)>) -> Json {
+        Json::Obj(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    fn write(&self, out: &mut String, indent: usize) -> fmt::Result {
+        let pad = |n: usize| "  ".repeat(n);
+        match self {
+            Json::Str(s) => {
+                out.push('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\t' => out.push_str("\\t"),
+                        c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+            Json::Bool(b) => write!(out, "{}", b)?,
+            Json::Num(n) => write!(out, "{}", n)?,
+            Json::Arr(items) if items.is_empty() => out.push_str("[]"),
+            Json::Arr(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(&pad(indent + 1));
+                    item.write(out, indent + 1)?;
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&pad(indent));
+                out.push(']');
+            }
+            Json::Obj(fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Obj(fields) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    out.push_str(&pad(indent + 1));
+                    Json::str(key).write(out, indent + 1)?;
+                    out.push_str(": ");
+                    value.write(out, indent + 1)?;
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&pad(indent));
+                out.push('}');
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Metadata as an object, since validation ensures its keys are unique.
+fn meta(meta: &[Meta]) -> Json {
+    Json::Obj(
+        meta.iter()
+            .map(|m| (m.key.clone(), Json::str(&m.value)))
+            .collect(),
+    )
+}
+
+fn provenance(p: &Provenance) -> Json {
+    let path = |p: &std::path::Path| Json::Str(p.display().to_string());
+    Json::obj(vec![
+        ("path", path(p.path())),
+        ("line", Json::Num(p.location.line)),
+        ("column", Json::Num(p.location.column)),
+        ("uses", Json::Arr(p.uses.iter().map(|u| path(u)).collect())),
+    ])
+}
+
+/// `fields`, with where the item was defined if that's known.
+fn defined(mut fields: Vec<(&str, Json)>, p: Option<&Provenance>) -> Json {
+    if let Some(p) = p {
+        fields.push(("defined_in", provenance(p)));
+    }
+    Json::obj(fields)
+}
+
+fn kind(t: &NamedType) -> &'static str {
+    match &**t.type_() {
+        Type::Record(r) if r.bitflags_repr().is_some() => "flags",
+        Type::Record(_) => "record",
+        Type::Variant(v) if v.is_enum() => "enum",
+        Type::Variant(_) => "variant",
+        Type::Handle(_) => "handle",
+        Type::List(_) => "list",
+        Type::Pointer(_) | Type::ConstPointer(_) => "pointer",
+        Type::Builtin(_) => "builtin",
+    }
+}
+
+fn typename(t: &NamedType, doc: &Document) -> Json {
+    defined(
+        vec![
+            ("name", Json::str(t.name.as_str())),
+            ("kind", Json::str(kind(t))),
+            ("docs", Json::str(&t.docs)),
+            ("meta", meta(&t.meta)),
+        ],
+        doc.provenance(&t.name),
+    )
+}
+
+fn param(p: &InterfaceFuncParam) -> Json {
+    Json::obj(vec![
+        ("name", Json::str(p.name.as_str())),
+        ("type", Json::str(&p.tref.type_name())),
+        ("docs", Json::str(&p.docs)),
+    ])
+}
+
+fn func(f: &InterfaceFunc, m: &Module, doc: &Document) -> Json {
+    defined(
+        vec![
+            ("name", Json::str(f.name.as_str())),
+            ("docs", Json::str(&f.docs)),
+            ("meta", meta(&f.meta)),
+            ("params", Json::Arr(f.params.iter().map(param).collect())),
+            ("results", Json::Arr(f.results.iter().map(param).collect())),
+            ("noreturn", Json::Bool(f.noreturn)),
+        ],
+        doc.func_provenance(&m.name, &f.name),
+    )
+}
+
+fn module(m: &Module, doc: &Document) -> Json {
+    defined(
+        vec![
+            ("name", Json::str(m.name.as_str())),
+            ("docs", Json::str(&m.docs)),
+            ("meta", meta(&m.meta)),
+            (
+                "functions",
+                Json::Arr(m.funcs().map(|f| func(&f, m, doc)).collect()),
+            ),
+        ],
+        doc.provenance(&m.name),
+    )
+}
+
+impl Document {
+    /// The types and modules of the document, with their documentation,
+    /// metadata and where they were defined, as JSON.
+    pub fn to_json(&self) -> String {
+        let doc = Json::obj(vec![
+            ("version", Json::Str(self.version().to_string())),
+            (
+                "types",
+                Json::Arr(self.typenames().map(|t| typename(&t, self)).collect()),
+            ),
+            (
+                "modules",
+                Json::Arr(self.modules().map(|m| module(&m, self)).collect()),
+            ),
+        ]);
+        let mut out = String::new();
+        doc.write(&mut out, 0)
+            .expect("writing to a string can't fail");
+        out.push('\n');
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn json() {
+        let doc = crate::parse(
+            ";;; A \"tensor\".
+             (typename $tensor (list u8) (@witx meta owner \"ml-wg\"))
+             (module $nn
+                (@witx meta stability \"experimental\")
+                (@interface func (export \"set_input\")
+                    (param $t $tensor)))",
+        )
+        .unwrap();
+        let json = doc.to_json();
+        assert!(
+            json.starts_with("{\n  \"version\": \"0.10\",\n"),
+            "{}",
+            json
+        );
+        assert!(json.contains(
+            "\"name\": \"tensor\",\n      \"kind\": \"list\",\n      \
+             \"docs\": \"A \\\"tensor\\\".\\n\",\n      \
+             \"meta\": {\n        \"owner\": \"ml-wg\"\n      }"
+        ));
+        assert!(json.contains("\"meta\": {\n        \"stability\": \"experimental\"\n      }"));
+        assert!(json.contains("\"name\": \"set_input\""));
+        assert!(json.contains("\"meta\": {},\n"));
+        assert!(json.contains(
+            "\"defined_in\": {\n        \"path\": \"-\",\n        \"line\": 2,\n        \
+             \"column\": 24,\n        \"uses\": []\n      }"
+        ));
+    }
+}
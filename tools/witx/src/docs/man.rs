@@ -0,0 +1,221 @@
+//! Man pages, in roff, for the modules of a document.
+
+use crate::{graph::module_types, Document, InterfaceFuncParam, Module, NamedType, Type};
+
+/// Escape `text` for roff: backslashes are escaped, and lines starting with
+/// a control character are protected. Backticked spans are set in bold.
+fn roff(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.lines() {
+        let line = line.replace('\\', "\\e");
+        if line.starts_with('.') || line.starts_with('\'') {
+            out.push_str("\\&");
+        }
+        let mut bold = false;
+        for ch in line.chars() {
+            match ch {
+                '`' => {
+                    out.push_str(if bold { "\\fR" } else { "\\fB" });
+                    bold = !bold;
+                }
+                '-' => out.push_str("\\-"),
+                ch => out.push(ch),
+            }
+        }
+        if bold {
+            out.push_str("\\fR");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The paragraphs of `docs`, separated the way roff expects.
+fn paragraphs(docs: &str) -> String {
+    docs.split("\n\n")
+        .filter(|p| !p.trim().is_empty())
+        .map(roff)
+        .collect::<Vec<_>>()
+        .join(".PP\n")
+}
+
+fn param(p: &InterfaceFuncParam) -> String {
+    let mut out = format!(".TP\n.B {}\n{}", p.name.as_str(), roff(&p.tref.type_name()));
+    if !p.docs.trim().is_empty() {
+        out.push_str(".br\n");
+        out.push_str(&paragraphs(&p.docs));
+    }
+    out
+}
+
+fn typename(t: &NamedType) -> String {
+    let mut out = format!(".SS {}\n", t.name.as_str());
+    out.push_str(&roff(&format!("`{}`", t.tref.type_name())));
+    if !t.docs.trim().is_empty() {
+        out.push_str(".PP\n");
+        out.push_str(&paragraphs(&t.docs));
+    }
+    let members = match &**t.type_() {
+        Type::Record(r) => r
+            .members
+            .iter()
+            .map(|m| (m.name.as_str(), Some(m.tref.type_name()), &m.docs))
+            .collect(),
+        Type::Variant(v) => v
+            .cases
+            .iter()
+            .map(|c| {
+                (
+                    c.name.as_str(),
+                    c.tref.as_ref().map(|t| t.type_name()),
+                    &c.docs,
+                )
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    for (name, ty, docs) in members {
+        out.push_str(&format!(".TP\n.B {}\n", name));
+        if let Some(ty) = ty {
+            out.push_str(&roff(&ty));
+            out.push_str(".br\n");
+        }
+        out.push_str(&paragraphs(docs));
+    }
+    out
+}
+
+impl Module {
+    /// A man page documenting this module, its functions and the types they
+    /// use, in section 3.
+    pub fn to_man(&self) -> String {
+        let name = self.name.as_str();
+        let mut out = format!(
+            ".TH {} 3 \"\" \"witx\" \"WebAssembly Interfaces\"\n",
+            name.to_uppercase()
+        );
+        out.push_str(".SH NAME\n");
+        let summary = super::summary(&self.docs);
+        if summary.is_empty() {
+            out.push_str(&format!("{} \\- witx module\n", name));
+        } else {
+            out.push_str(&format!("{} \\- {}", name, roff(&summary)));
+        }
+
+        out.push_str(".SH SYNOPSIS\n.nf\n");
+        for f in self.funcs() {
+            let params = f
+                .params
+                .iter()
+                .map(|p| format!("{}: {}", p.name.as_str(), p.tref.type_name()))
+                .collect::<Vec<_>>();
+            let results = f
+                .results
+                .iter()
+                .map(|r| r.tref.type_name())
+                .collect::<Vec<_>>();
+            let results = match results.len() {
+                0 => String::new(),
+                1 => format!(" -> {}", results[0]),
+                _ => format!(" -> ({})", results.join(", ")),
+            };
+            out.push_str(&roff(&format!(
+                "{}({}){}",
+                f.name.as_str(),
+                params.join(", "),
+                results
+            )));
+        }
+        out.push_str(".fi\n");
+
+        if !self.docs.trim().is_empty() {
+            out.push_str(".SH DESCRIPTION\n");
+            out.push_str(&paragraphs(&self.docs));
+        }
+
+        out.push_str(".SH FUNCTIONS\n");
+        for f in self.funcs() {
+            out.push_str(&format!(".SS {}\n", f.name.as_str()));
+            out.push_str(&paragraphs(&f.docs));
+            if !f.params.is_empty() {
+                out.push_str(".PP\nParams:\n");
+                for p in f.params.iter() {
+                    out.push_str(&param(p));
+                }
+            }
+            if !f.results.is_empty() {
+                out.push_str(".PP\nResults:\n");
+                for r in f.results.iter() {
+                    out.push_str(&param(r));
+                }
+            }
+        }
+
+        let types = module_types(self);
+        if !types.is_empty() {
+            out.push_str(".SH TYPES\n");
+            for t in types {
+                out.push_str(&typename(&t));
+            }
+        }
+        out
+    }
+}
+
+impl Document {
+    /// A man page for each module, with the file name it's installed as.
+    pub fn man_pages(&self) -> Vec<(String, String)> {
+        self.modules()
+            .map(|m| (format!("{}.3", m.name.as_str()), m.to_man()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn man_page() {
+        let doc = crate::parse(
+            ";;; The size of a buffer.
+             (typename $size u32)
+             (typename $graph (handle))
+             (typename $unused u8)
+             ;;; Neural networks. Load and run them.
+             (module $nn
+                ;;; Load a graph.
+                ;;;
+                ;;; .See `size` \\ for details.
+                (@interface func (export \"load\")
+                    ;;; Bytes to read.
+                    (param $n $size)
+                    (result $g $graph)))",
+        )
+        .unwrap();
+        let pages = doc.man_pages();
+        assert_eq!(pages.len(), 1);
+        let (file, man) = &pages[0];
+        assert_eq!(file, "nn.3");
+        assert!(man.starts_with(".TH NN 3 "), "{}", man);
+        assert!(
+            man.contains(".SH NAME\nnn \\- Neural networks.\n"),
+            "{}",
+            man
+        );
+        assert!(
+            man.contains(".nf\nload(n: size) \\-> graph\n.fi\n"),
+            "{}",
+            man
+        );
+        assert!(
+            man.contains(".SS load\nLoad a graph.\n.PP\n\\&.See \\fBsize\\fR \\e for details.\n")
+        );
+        assert!(
+            man.contains(".TP\n.B n\nsize\n.br\nBytes to read.\n"),
+            "{}",
+            man
+        );
+        assert!(man.contains(".SS size\n"));
+        assert!(man.contains(".SS graph\n"));
+        assert!(!man.contains("unused"));
+    }
+}
@@ -30,7 +30,6 @@ pub(super) trait MdElement: fmt::Display + fmt::Debug + 'static {
     /// Sets `docs`, the "docs" of this `MdElement`.
     fn set_docs(&mut self, docs: &str);
 
-    fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
@@ -45,6 +44,9 @@ pub(super) struct MdNode {
     content: Box<dyn MdElement>,
     parent: Option<Weak<RefCell<MdNode>>>,
     children: Vec<MdNodeRef>,
+    /// A template the rendering of this node and its children is substituted
+    /// into as the `content` token, along with the other tokens given.
+    template: Option<(String, Vec<(String, String)>)>,
 }
 
 /// Helper function for walking the tree up from some starting `MdNode`, all the way up
@@ -62,6 +64,7 @@ impl MdNode {
             content: Box::new(item),
             parent: None,
             children: vec![],
+            template: None,
         }
     }
 
@@ -84,6 +87,19 @@ impl MdNode {
 
 impl fmt::Display for MdNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((template, tokens)) = &self.template {
+            let mut content = self.content.to_string();
+            for child in &self.children {
+                content.push_str(&child.to_string());
+            }
+            let mut tokens = tokens
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>();
+            tokens.push(("content", &content));
+            return f.write_str(&super::substitute(template, &tokens));
+        }
+
         self.content.fmt(f)?;
 
         for child in &self.children {
@@ -113,23 +129,29 @@ impl MdNodeRef {
         child_ref
     }
 
-    pub fn borrow(&self) -> cell::Ref<MdNode> {
+    /// Renders this node and its children through `template`. See
+    /// `MdNode::template`.
+    pub fn set_template(&self, template: &str, tokens: Vec<(String, String)>) {
+        self.borrow_mut().template = Some((template.to_owned(), tokens));
+    }
+
+    pub fn borrow(&self) -> cell::Ref<'_, MdNode> {
         self.0.borrow()
     }
 
-    pub fn borrow_mut(&self) -> cell::RefMut<MdNode> {
+    pub fn borrow_mut(&self) -> cell::RefMut<'_, MdNode> {
         self.0.borrow_mut()
     }
 
     /// Returns an immutable reference to `MdNode`'s `content` as-is, that
     /// is as some type implementing the `MdElement` trait.
-    pub fn any_ref(&self) -> cell::Ref<Box<dyn MdElement>> {
+    pub fn any_ref(&self) -> cell::Ref<'_, Box<dyn MdElement>> {
         cell::Ref::map(self.borrow(), |b| &b.content)
     }
 
     /// Returns a mutable reference to `MdNode`'s `content` as-is, that
     /// is as some type implementing the `MdElement` trait.
-    pub fn any_ref_mut(&self) -> cell::RefMut<Box<dyn MdElement>> {
+    pub fn any_ref_mut(&self) -> cell::RefMut<'_, Box<dyn MdElement>> {
         cell::RefMut::map(self.borrow_mut(), |b| &mut b.content)
     }
 
@@ -137,7 +159,7 @@ impl MdNodeRef {
     /// `T` which implements `MdElement` trait.
     ///
     /// Panics if `content` cannot be downcast to `T`.
-    pub fn content_ref_mut<T: MdElement + 'static>(&self) -> cell::RefMut<T> {
+    pub fn content_ref_mut<T: MdElement + 'static>(&self) -> cell::RefMut<'_, T> {
         cell::RefMut::map(self.borrow_mut(), |b| {
             let r = b.content.as_any_mut();
             r.downcast_mut::<T>().expect("reference is not T type")
@@ -180,10 +202,6 @@ impl MdElement for MdRoot {
 
     fn set_docs(&mut self, _: &str) {}
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
@@ -264,7 +282,7 @@ impl MdSection {
 
 impl MdElement for MdSection {
     fn id(&self) -> Option<&str> {
-        self.id.as_ref().map(|s| s.as_str())
+        self.id.as_deref()
     }
 
     fn docs(&self) -> Option<&str> {
@@ -273,10 +291,6 @@ impl MdElement for MdSection {
 
     fn set_docs(&mut self, _: &str) {}
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
@@ -350,10 +364,6 @@ impl MdElement for MdNamedType {
         self.docs = docs.to_owned();
     }
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
@@ -383,12 +393,15 @@ impl fmt::Display for MdNamedType {
 /// * referencable `id`,
 /// * some `name`, e.g., `path_open`,
 /// * function `inputs`, i.e., arguments,
-/// * function `outputs`, i.e., results, and
+/// * function `outputs`, i.e., results,
+/// * maybe the `core` wasm signature it's imported with, and
 /// * `docs` paragraph.
 ///
 /// Example rendering:
 ///
 /// ### <a href="#args_get" name="args_get"></a> Fn args_get(argv: `Pointer<Pointer<u8>>`, ...) -> `errno`
+/// Core signature: `(param i32 i32) (result i32)`
+///
 /// Read command-line...
 ///
 /// #### Params
@@ -405,6 +418,7 @@ pub(super) struct MdFunc {
     pub name: String,
     pub inputs: Vec<(String, String)>,
     pub outputs: Vec<String>,
+    pub core: Option<String>,
     pub docs: String,
 }
 
@@ -416,6 +430,7 @@ impl MdFunc {
             name: name.as_ref().to_owned(),
             inputs: vec![],
             outputs: vec![],
+            core: None,
             docs: docs.as_ref().to_owned(),
         }
     }
@@ -434,10 +449,6 @@ impl MdElement for MdFunc {
         self.docs = docs.to_owned();
     }
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
@@ -456,7 +467,7 @@ impl fmt::Display for MdFunc {
         let outputs: Vec<_> = self
             .outputs
             .iter()
-            .map(|r#type| format!("{}", r#type))
+            .map(|r#type| r#type.to_string())
             .collect();
         let outputs = match outputs.len() {
             0 => "".to_owned(),
@@ -475,6 +486,115 @@ impl fmt::Display for MdFunc {
             outputs = outputs,
         ))?;
 
+        if let Some(core) = &self.core {
+            writeln!(f, "\nCore signature: `{}`", core)?;
+        }
+
         writeln!(f, "\n{}", self.docs)
     }
 }
+
+/// Record representing a Markdown table, with a `header` row and any number
+/// of `rows` with a cell for each column. Columns named in `numeric` are
+/// right-aligned.
+///
+/// Example rendering:
+///
+/// | Member | Type | Offset | Size |
+/// | :--- | :--- | ---: | ---: |
+/// | `x` | `u32` | 0 | 4 |
+///
+#[derive(Debug)]
+pub(super) struct MdTable {
+    pub header: Vec<String>,
+    pub numeric: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl MdTable {
+    pub fn new(header: &[&str], numeric: &[&str]) -> Self {
+        Self {
+            header: header.iter().map(|s| s.to_string()).collect(),
+            numeric: numeric.iter().map(|s| s.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+}
+
+impl MdElement for MdTable {
+    fn id(&self) -> Option<&str> {
+        None
+    }
+
+    fn docs(&self) -> Option<&str> {
+        None
+    }
+
+    fn set_docs(&mut self, _: &str) {}
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for MdTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "| {} |", self.header.join(" | "))?;
+        let align = self
+            .header
+            .iter()
+            .map(|h| {
+                if self.numeric.contains(h) {
+                    "---:"
+                } else {
+                    ":---"
+                }
+            })
+            .collect::<Vec<_>>();
+        writeln!(f, "| {} |", align.join(" | "))?;
+        for row in self.rows.iter() {
+            writeln!(f, "| {} |", row.join(" | "))?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Record representing a fenced Markdown code block of some `lang`.
+#[derive(Debug)]
+pub(super) struct MdCode {
+    pub lang: String,
+    pub code: String,
+}
+
+impl MdCode {
+    pub fn new<S: AsRef<str>>(lang: S, code: S) -> Self {
+        Self {
+            lang: lang.as_ref().to_owned(),
+            code: code.as_ref().to_owned(),
+        }
+    }
+}
+
+impl MdElement for MdCode {
+    fn id(&self) -> Option<&str> {
+        None
+    }
+
+    fn docs(&self) -> Option<&str> {
+        None
+    }
+
+    fn set_docs(&mut self, _: &str) {}
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for MdCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "```{}", self.lang)?;
+        f.write_str(&self.code)?;
+        writeln!(f, "```\n")
+    }
+}
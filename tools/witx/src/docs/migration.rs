@@ -0,0 +1,313 @@
+use super::ast::core_signature;
+use crate::{
+    polyfill::{FuncPolyfill, ModulePolyfill, ParamPolyfill, ParamUnknown, Polyfill, TypePolyfill},
+    BuiltinType, InterfaceFunc, RepEquality, Type,
+};
+
+impl Polyfill {
+    /// A migration guide for moving users of the old interface to the new
+    /// one: for each function that changed, what changed, which adapters a
+    /// polyfill needs, and the old and new core wasm signatures.
+    pub fn migration_guide(&self) -> String {
+        let mut md = String::from("# Migration guide\n");
+        for m in self.modules.iter() {
+            md.push_str(&m.migration_guide());
+        }
+        md
+    }
+}
+
+impl ModulePolyfill {
+    fn migration_guide(&self) -> String {
+        let mut md = format!(
+            "\n## From `{}` to `{}`\n",
+            self.old.name.as_str(),
+            self.new.name.as_str()
+        );
+        let unchanged = self
+            .funcs
+            .iter()
+            .filter(|f| f.full_compat())
+            .map(|f| format!("`{}`", f.new.name.as_str()))
+            .collect::<Vec<_>>();
+        let added = self
+            .added_funcs
+            .iter()
+            .map(|f| format!("`{}`", f.name.as_str()))
+            .collect::<Vec<_>>();
+        if unchanged.len() == self.funcs.len() && added.is_empty() {
+            md.push_str("\nNo functions changed.\n");
+            return md;
+        }
+        if !unchanged.is_empty() {
+            md.push_str(&format!("\nUnchanged: {}.\n", unchanged.join(", ")));
+        }
+        if !added.is_empty() {
+            md.push_str(&format!(
+                "\nAdded, with no counterpart in the old module: {}.\n",
+                added.join(", ")
+            ));
+        }
+        for f in self.funcs.iter().filter(|f| !f.full_compat()) {
+            md.push_str(&f.migration_guide());
+        }
+        md
+    }
+}
+
+/// The lowered signature of `func`, as a core wasm function type.
+fn wat(func: &InterfaceFunc) -> String {
+    let signature = core_signature(func);
+    if signature.is_empty() {
+        format!("(func ${})", func.name.as_str())
+    } else {
+        format!("(func ${} {})", func.name.as_str(), signature)
+    }
+}
+
+impl FuncPolyfill {
+    fn migration_guide(&self) -> String {
+        let mut changes = Vec::new();
+        let mut adapters = Vec::new();
+        for (kind, params) in [
+            ("param", &self.mapped_params),
+            ("result", &self.mapped_results),
+        ] {
+            for p in params.iter().filter(|p| !p.full_compat()) {
+                changes.push(p.change(kind));
+                adapters.push(p.adapter(kind));
+            }
+        }
+        for (kind, unknown) in [
+            ("param", &self.unknown_params),
+            ("result", &self.unknown_results),
+        ] {
+            for u in unknown.iter() {
+                let p = u.param();
+                let (change, adapter) = match (u, kind) {
+                    (ParamUnknown::New(_), "param") => ("was added", "must supply a value for it"),
+                    (ParamUnknown::Old(_), "param") => ("was removed", "drops it"),
+                    (ParamUnknown::New(_), _) => ("was added", "discards it"),
+                    (ParamUnknown::Old(_), _) => ("was removed", "must produce it"),
+                };
+                changes.push(format!(
+                    "{} `{}` (`{}`) {}.",
+                    capitalize(kind),
+                    p.name.as_str(),
+                    p.tref.type_name(),
+                    change
+                ));
+                adapters.push(format!(
+                    "{} `{}`: the adapter {}.",
+                    capitalize(kind),
+                    p.name.as_str(),
+                    adapter
+                ));
+            }
+        }
+
+        let mut md = format!("\n### `{}`\n", self.new.name.as_str());
+        md.push_str("\nWhat changed:\n\n");
+        for c in changes {
+            md.push_str(&format!("- {}\n", c));
+        }
+        md.push_str("\nAdapters needed:\n\n");
+        for a in adapters {
+            md.push_str(&format!("- {}\n", a));
+        }
+        md.push_str(&format!(
+            "\nLowered signatures:\n\n```wat\n;; old\n{}\n;; new\n{}\n```\n",
+            wat(&self.old),
+            wat(&self.new)
+        ));
+        md
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl ParamPolyfill {
+    fn name(&self) -> String {
+        if self.new.name != self.old.name {
+            format!(
+                "`{}` (now `{}`)",
+                self.old.name.as_str(),
+                self.new.name.as_str()
+            )
+        } else {
+            format!("`{}`", self.new.name.as_str())
+        }
+    }
+
+    /// What changed about the param, as a sentence.
+    fn change(&self, kind: &str) -> String {
+        let old = self.old.tref.type_name();
+        let new = self.new.tref.type_name();
+        let mut change = if old == new {
+            format!("{} {} (`{}`) changed", capitalize(kind), self.name(), new)
+        } else {
+            format!(
+                "{} {} changed from `{}` to `{}`",
+                capitalize(kind),
+                self.name(),
+                old,
+                new
+            )
+        };
+        let report = self.report();
+        if !report.is_empty() {
+            change.push_str(&format!(": {}", report.join("; ")));
+        }
+        change.push('.');
+        change
+    }
+
+    /// What an adapter does with the param, as a sentence.
+    fn adapter(&self, kind: &str) -> String {
+        let name = format!("{} {}", capitalize(kind), self.name());
+        // Params are passed from old callers to the new function, and results
+        // returned from the new function to old callers.
+        let (from, to) = match kind {
+            "param" => ("old", "new"),
+            _ => ("new", "old"),
+        };
+        if let Some(remap) = self.type_polyfill.enum_remap().filter(|r| !r.is_identity()) {
+            let values = remap
+                .values
+                .iter()
+                .map(|v| format!("`{}` {} => {}", v.name.as_str(), v.from, v.to))
+                .collect::<Vec<_>>();
+            let mut adapter = format!("{}: remap values ({}).", name, values.join(", "));
+            if !remap.unmappable.is_empty() {
+                let cases = remap
+                    .unmappable
+                    .iter()
+                    .map(|v| format!("`{}`", v.name.as_str()))
+                    .collect::<Vec<_>>();
+                adapter.push_str(&format!(
+                    " {} values {} have no {} counterpart, so need a hand-written adapter.",
+                    capitalize(from),
+                    cases.join(", "),
+                    to
+                ));
+            }
+            return adapter;
+        }
+        match self.repeq() {
+            RepEquality::Eq => format!("{}: no conversion needed.", name),
+            RepEquality::Superset => match self.instruction() {
+                Some(instruction) => format!("{}: convert with `{}`.", name, instruction),
+                None => format!(
+                    "{}: every {} value is a valid {} value, so it passes through as-is.",
+                    name, from, to
+                ),
+            },
+            RepEquality::NotEq => format!(
+                "{}: {} values can't all be represented as {} values, so this needs a \
+                 hand-written adapter.",
+                name, from, to
+            ),
+        }
+    }
+
+    /// The core wasm instruction widening the param, if it needs one.
+    fn instruction(&self) -> Option<&'static str> {
+        let conversion = self.type_polyfill.conversion()?;
+        let builtin = |t: &crate::TypeRef| match &**t.type_() {
+            Type::Builtin(b) => Some(*b),
+            _ => None,
+        };
+        let (from, to): (BuiltinType, BuiltinType) = match &self.type_polyfill {
+            TypePolyfill::OldToNew(from, to) | TypePolyfill::NewToOld(from, to) => {
+                (builtin(from)?, builtin(to)?)
+            }
+        };
+        conversion.instruction(&from, &to)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::polyfill::Polyfill;
+    use std::collections::HashMap;
+
+    #[test]
+    fn migration_guide() {
+        let old = crate::parse(
+            "(typename $e (enum $a $b))
+             (typename $size u64)
+             (module $m
+                (@interface func (export \"same\") (param $x u32))
+                (@interface func (export \"f\")
+                    (param $n u32)
+                    (param $e $e)
+                    (param $gone u8)
+                    (result $r (expected $size (error $e)))))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(typename $e (enum $a $b $c))
+             (typename $size u64)
+             (module $m
+                (@interface func (export \"same\") (param $x u32))
+                (@interface func (export \"f\")
+                    (param $n u64)
+                    (param $e $e)
+                    (param $extra u8)
+                    (result $r (expected $size (error $e)))))",
+        )
+        .unwrap();
+        let mapping = vec![("m".to_string(), "m".to_string())]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let guide = Polyfill::new(&new, &old, &mapping)
+            .unwrap()
+            .migration_guide();
+        assert!(guide.contains("Unchanged: `same`."), "{}", guide);
+        assert!(guide.contains("### `f`"), "{}", guide);
+        assert!(guide.contains("- Param `n` changed from `u32` to `u64`."));
+        assert!(guide.contains("- Param `n`: convert with `i64.extend_i32_u`."));
+        assert!(guide.contains("- Param `e`: every old value is a valid new value"));
+        assert!(guide.contains("- Result `r`: new values can't all be represented as old values"));
+        assert!(guide.contains("- Param `gone` (`u8`) was removed."));
+        assert!(guide.contains("- Param `extra`: the adapter must supply a value for it."));
+        assert!(guide.contains(
+            ";; old\n(func $f (param i32 i32 i32 i32) (result i32))\n\
+             ;; new\n(func $f (param i64 i32 i32 i32) (result i32))"
+        ));
+    }
+
+    #[test]
+    fn enum_remap() {
+        let old = crate::parse(
+            "(typename $e (enum $a $b $c))
+             (module $m (@interface func (export \"f\") (param $e $e)))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(typename $e (enum $b $a))
+             (module $m (@interface func (export \"f\") (param $e $e)))",
+        )
+        .unwrap();
+        let mapping = vec![("m".to_string(), "m".to_string())]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let guide = Polyfill::new(&new, &old, &mapping)
+            .unwrap()
+            .migration_guide();
+        assert!(
+            guide.contains(
+                "- Param `e`: remap values (`a` 0 => 1, `b` 1 => 0). Old values `c` have no \
+                 new counterpart, so need a hand-written adapter."
+            ),
+            "{}",
+            guide
+        );
+    }
+}
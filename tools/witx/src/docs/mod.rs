@@ -1,8 +1,12 @@
 mod ast;
+mod html;
+mod json;
+mod man;
 mod md;
+mod migration;
 
 use crate::ast::Document;
-use md::{MdNodeRef, MdRoot, ToMarkdown};
+use md::{MdFunc, MdHeading, MdNamedType, MdNodeRef, MdRoot, ToMarkdown};
 use std::{
     collections::{hash_map, HashSet},
     iter::FromIterator,
@@ -13,19 +17,57 @@ pub trait Documentation {
     fn to_md(&self) -> String;
 }
 
+/// Resolve a reference to `name` from the docs of the item with id `scope`
+/// to the id of the item it refers to, if any. In Markdown, scoping is done
+/// with ".", so any "::" in `name` is replaced with ".".
+///
+/// Names are looked up among top-level items first. Witx identifiers, which
+/// start with "$", are then looked up in the scope of the item and of each
+/// item it is nested in, so a function's docs can refer to `$param`.
+fn resolve_link(
+    name: &str,
+    scope: Option<&str>,
+    existing_links: &HashSet<String>,
+) -> Option<String> {
+    let (ident, name) = match name.strip_prefix('$') {
+        Some(name) => (true, name.replace("::", ".")),
+        None => (false, name.replace("::", ".")),
+    };
+    if existing_links.contains(&name) {
+        return Some(name);
+    }
+    let mut scope = scope.filter(|_| ident);
+    while let Some(s) = scope {
+        let candidate = format!("{}.{}", s, name);
+        if existing_links.contains(&candidate) {
+            return Some(candidate);
+        }
+        scope = s.rfind('.').map(|i| &s[..i]);
+    }
+    None
+}
+
 /// Helper function which given input `text` and a `HashSet` of existing links converts
 /// any slice of the form '`{link}`' into either
-/// 1. "[`{link}`](#{md_link})" where `md_link` is `link` with "::" replaced with "."
-///    (in Markdown, scoping should be done with ".") if `md_link` exists in the `HashSet`
-/// 2. "`{link}`" otherwise. That is, if `md_link` could not be found in the `HashSet`, we
+/// 1. "[`{link}`](#{md_link})" where `md_link` is the item `link` refers to (see
+///    `resolve_link`), if there is one
+/// 2. "`{link}`" otherwise. That is, if `link` could not be resolved, we
 ///    just leave what we've consumed.
-fn parse_links<S: AsRef<str>>(text: S, existing_links: &HashSet<String>) -> String {
+///
+/// References of the form `$name` outside of backticks are linked the same way,
+/// and left as-is if they can't be resolved.
+fn parse_links<S: AsRef<str>>(
+    text: S,
+    scope: Option<&str>,
+    existing_links: &HashSet<String>,
+) -> String {
     let text = text.as_ref();
     let mut parsed_text = String::with_capacity(text.len());
     let mut link = String::with_capacity(text.len());
     let mut is_link = false;
+    let mut chars = text.chars().peekable();
 
-    for ch in text.chars() {
+    while let Some(ch) = chars.next() {
         match (ch, is_link) {
             // Found the beginning of a link!
             ('`', false) => {
@@ -33,24 +75,42 @@ fn parse_links<S: AsRef<str>>(text: S, existing_links: &HashSet<String>) -> Stri
             }
             // Reached the end, expand into a link!
             ('`', true) => {
-                // Sanitise scoping by replacing "::" with '.'
-                let md_link = link.replace("::", ".");
                 // Before committing to pasting the link in,
                 // first verify that it actually exists.
-                let expanded = if let Some(_) = existing_links.get(&md_link) {
+                let expanded = if let Some(md_link) = resolve_link(&link, scope, existing_links) {
                     format!("[`{}`](#{})", link, md_link)
                 } else {
-                    log::warn!(
-                        "Link [`{}`](#{}) could not be found in the document!",
-                        link,
-                        md_link
-                    );
+                    log::warn!("Link [`{}`] could not be found in the document!", link);
                     format!("`{}`", link)
                 };
                 parsed_text.push_str(&expanded);
                 link.drain(..);
                 is_link = false;
             }
+            // A `$name` reference, which may be scoped by "::" or ".".
+            ('$', false) => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == ':' || c == '.') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                // Punctuation ending a sentence isn't part of the name.
+                let end = name.trim_end_matches(['.', ':']).len();
+                let trailing = name.split_off(end);
+                match resolve_link(&format!("${}", name), scope, existing_links) {
+                    Some(md_link) if !name.is_empty() => {
+                        parsed_text.push_str(&format!("[`{}`](#{})", name, md_link))
+                    }
+                    _ => {
+                        parsed_text.push('$');
+                        parsed_text.push_str(&name);
+                    }
+                }
+                parsed_text.push_str(&trailing);
+            }
             (ch, false) => parsed_text.push(ch),
             (ch, true) => link.push(ch),
         }
@@ -59,29 +119,348 @@ fn parse_links<S: AsRef<str>>(text: S, existing_links: &HashSet<String>) -> Stri
     parsed_text
 }
 
-impl Documentation for Document {
+/// Render the doc comment `docs` of the item with id `scope` as Markdown.
+///
+/// Doc comments are Markdown already, but fenced code blocks are copied
+/// verbatim rather than having their references linked, and a list that
+/// directly follows a paragraph is separated from it by a blank line, which
+/// some renderers require.
+fn render_docs(docs: &str, scope: Option<&str>, existing_links: &HashSet<String>) -> String {
+    fn is_list_item(line: &str) -> bool {
+        let line = line.trim_start();
+        let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        line.starts_with("- ")
+            || line.starts_with("* ")
+            || (digits > 0 && line[digits..].starts_with(". "))
+    }
+
+    let mut out = String::with_capacity(docs.len());
+    let mut text = String::new();
+    let mut fenced = false;
+    let mut in_paragraph = false;
+    let mut in_list = false;
+    for line in docs.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            if !fenced {
+                out.push_str(&parse_links(&text, scope, existing_links));
+                text.clear();
+            }
+            out.push_str(line);
+            fenced = !fenced;
+            in_paragraph = false;
+            in_list = false;
+        } else if fenced {
+            out.push_str(line);
+        } else {
+            if line.trim().is_empty() {
+                in_paragraph = false;
+                in_list = false;
+            } else if is_list_item(line) {
+                if in_paragraph && !in_list {
+                    text.push('\n');
+                }
+                in_list = true;
+            } else {
+                in_paragraph = !in_list;
+            }
+            text.push_str(line);
+        }
+    }
+    out.push_str(&parse_links(&text, scope, existing_links));
+    out
+}
+
+/// Templates for customizing generated documentation.
+///
+/// Templates use a simple token substitution format: `{{token}}` is replaced
+/// by the value of `token`, and all other text, including unknown tokens, is
+/// copied as-is.
+#[derive(Debug, Clone, Default)]
+pub struct DocTemplate {
+    /// Text before the documentation, with the token `modules`, the names of
+    /// the document's modules separated by commas.
+    pub header: Option<String>,
+    /// Text after the documentation, with the same tokens as `header`.
+    pub footer: Option<String>,
+    /// Replaces the documentation of each named type, with the tokens `name`,
+    /// `id` (its link anchor) and `content` (the documentation generated
+    /// otherwise).
+    pub typename: Option<String>,
+    /// Replaces the documentation of each function, with the tokens `module`,
+    /// `name`, `id` and `content`.
+    pub func: Option<String>,
+}
+
+/// Replace each `{{token}}` in `template` with its value in `tokens`.
+fn substitute(template: &str, tokens: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let token = after.find("}}").and_then(|end| {
+            let name = after[..end].trim();
+            let value = tokens.iter().find(|(k, _)| *k == name)?.1;
+            Some((value, end))
+        });
+        match token {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+impl DocTemplate {
+    /// Generate the documentation of `doc` with these templates.
+    pub fn render(&self, doc: &Document) -> String {
+        let root = generate(doc);
+        for child in root.borrow().children() {
+            let item = {
+                let mut content = child.any_ref_mut();
+                let any = content.as_any_mut();
+                if let Some(t) = any.downcast_mut::<MdNamedType>() {
+                    match t.heading {
+                        MdHeading::Header { .. } => self
+                            .typename
+                            .as_ref()
+                            .map(|template| (template, t.name.clone(), t.id.clone(), false)),
+                        MdHeading::Bullet => None,
+                    }
+                } else if let Some(f) = any.downcast_mut::<MdFunc>() {
+                    self.func
+                        .as_ref()
+                        .map(|template| (template, f.name.clone(), f.id.clone(), true))
+                } else {
+                    None
+                }
+            };
+            if let Some((template, name, id, is_func)) = item {
+                let mut tokens = vec![("name".to_string(), name), ("id".to_string(), id)];
+                if is_func {
+                    // The closest ancestor with an id is the module section.
+                    let module = child
+                        .borrow()
+                        .ancestors()
+                        .iter()
+                        .find_map(|a| a.any_ref().id().map(String::from));
+                    tokens.push(("module".to_string(), module.unwrap_or_default()));
+                }
+                child.set_template(template, tokens);
+            }
+        }
+
+        let modules = doc
+            .modules()
+            .map(|m| m.name.as_str().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let tokens = [("modules", modules.as_str())];
+        let mut out = String::new();
+        if let Some(header) = &self.header {
+            out.push_str(&substitute(header, &tokens));
+        }
+        out.push_str(&root.to_string());
+        if let Some(footer) = &self.footer {
+            out.push_str(&substitute(footer, &tokens));
+        }
+        out
+    }
+}
+
+/// An index page for the documentation of a number of documents, each on its
+/// own page, listing every module with its function count and a summary: the
+/// first sentence of its docs, or if it has none, the names of its functions.
+#[derive(Debug, Clone, Default)]
+pub struct DocIndex {
+    pages: Vec<(String, Document)>,
+}
+
+impl DocIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the document whose documentation is at the relative URL `link`.
+    pub fn page<S: Into<String>>(&mut self, link: S, doc: Document) {
+        self.pages.push((link.into(), doc));
+    }
+}
+
+/// The first sentence of `docs`, on one line.
+pub(crate) fn summary(docs: &str) -> String {
+    let paragraph = docs
+        .split("\n\n")
+        .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|p| !p.is_empty())
+        .unwrap_or_default();
+    match paragraph.find(". ") {
+        Some(end) => paragraph[..=end].to_string(),
+        None => paragraph,
+    }
+}
+
+impl Documentation for DocIndex {
     fn to_md(&self) -> String {
-        let root = MdNodeRef::new(MdRoot::default());
-        self.generate(root.clone());
-        // Get all children of the `root` element.
-        let children = root.borrow().children();
-        // Gather all existing links in the document into a set.
-        let existing_links: HashSet<String, hash_map::RandomState> = HashSet::from_iter(
-            children
-                .iter()
-                .filter_map(|x| x.any_ref().id().map(String::from)),
-        );
-        // Traverse each docs section of each child, and parse links
-        // logging a warning in case the generated is invalid.
-        for child in children {
-            let docs_with_links = child
-                .any_ref()
-                .docs()
-                .map(|docs| parse_links(docs, &existing_links));
-            if let Some(docs) = docs_with_links {
-                child.any_ref_mut().set_docs(&docs);
+        let mut table = md::MdTable::new(&["Module", "Functions", "Summary"], &["Functions"]);
+        for (link, doc) in self.pages.iter() {
+            for m in doc.modules() {
+                let name = m.name.as_str();
+                let mut summary = summary(&m.docs).replace('|', "\\|");
+                if summary.is_empty() {
+                    summary = m
+                        .funcs()
+                        .map(|f| format!("`{}`", f.name.as_str()))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                }
+                table.rows.push(vec![
+                    format!("[`{}`]({}#{})", name, link, name),
+                    m.funcs().count().to_string(),
+                    summary,
+                ]);
             }
         }
-        format!("{}", root)
+        format!("# Modules\n\n{}", table)
+    }
+}
+
+/// Generate the Markdown tree of `doc`, with links in docs resolved.
+fn generate(doc: &Document) -> MdNodeRef {
+    let root = MdNodeRef::new(MdRoot);
+    doc.generate(root.clone());
+    // Get all children of the `root` element.
+    let children = root.borrow().children();
+    // Gather all existing links in the document into a set.
+    let existing_links: HashSet<String, hash_map::RandomState> = HashSet::from_iter(
+        children
+            .iter()
+            .filter_map(|x| x.any_ref().id().map(String::from)),
+    );
+    // Traverse each docs section of each child, and parse links
+    // logging a warning in case the generated is invalid.
+    for child in children {
+        let docs_with_links = {
+            let content = child.any_ref();
+            content
+                .docs()
+                .map(|docs| render_docs(docs, content.id(), &existing_links))
+        };
+        if let Some(docs) = docs_with_links {
+            child.any_ref_mut().set_docs(&docs);
+        }
+    }
+    root
+}
+
+impl Documentation for Document {
+    fn to_md(&self) -> String {
+        format!("{}", generate(self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn doc_comments() {
+        let links = ["tensor", "f", "f.out"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<HashSet<_>>();
+        let render = |docs: &str| render_docs(docs, Some("f"), &links);
+
+        assert_eq!(
+            render("A $tensor, or `tensor`. Writes to $out, not $missing."),
+            "A [`tensor`](#tensor), or [`tensor`](#tensor). Writes to [`out`](#f.out), not $missing."
+        );
+        assert_eq!(render("See `$out`."), "See [`$out`](#f.out).");
+        assert_eq!(
+            render("Takes:\n- a $tensor\n- more\n\nDone.\n"),
+            "Takes:\n\n- a [`tensor`](#tensor)\n- more\n\nDone.\n"
+        );
+        assert_eq!(
+            render("Like:\n```\nf($tensor, `x`)\n```\nwith $tensor"),
+            "Like:\n```\nf($tensor, `x`)\n```\nwith [`tensor`](#tensor)"
+        );
+    }
+
+    #[test]
+    fn index() {
+        let a = crate::parse(
+            ";;; Load graphs. And then run them.
+             (module $nn (@interface func (export \"load\")) (@interface func (export \"run\")))",
+        )
+        .unwrap();
+        let b = crate::parse("(module $empty)").unwrap();
+        let mut index = DocIndex::new();
+        index.page("nn.md", a);
+        index.page("empty.md", b);
+        let md = index.to_md();
+        assert!(md.contains("| [`nn`](nn.md#nn) | 2 | Load graphs. |\n"));
+        assert!(md.contains("| [`empty`](empty.md#empty) | 0 |  |\n"));
+
+        let mut index = DocIndex::new();
+        index.page(
+            "nn.md",
+            crate::parse("(module $nn (@interface func (export \"load\")))").unwrap(),
+        );
+        assert!(index
+            .to_md()
+            .contains("| [`nn`](nn.md#nn) | 1 | `load` |\n"));
+    }
+
+    #[test]
+    fn template() {
+        let doc = crate::parse(
+            "(typename $t u8)
+             (module $m (@interface func (export \"f\") (param $x $t)))",
+        )
+        .unwrap();
+        let template = DocTemplate {
+            header: Some("<!-- {{modules}} -->\n".to_string()),
+            footer: Some("<!-- end {{unknown}} -->\n".to_string()),
+            typename: Some("<div id=\"{{id}}\">\n{{ content }}</div>\n".to_string()),
+            func: Some("<div class=\"{{module}}\">{{name}}</div>\n".to_string()),
+        };
+        let md = template.render(&doc);
+        assert!(md.starts_with("<!-- m -->\n"));
+        assert!(md.ends_with("<!-- end {{unknown}} -->\n"));
+        assert!(md.contains("<div id=\"t\">\n## <a href=\"#t\" name=\"t\"></a> `t`: `u8`"));
+        assert!(md.contains("<div class=\"m\">f</div>\n"));
+        assert!(!md.contains("#### Params"));
+
+        assert_eq!(DocTemplate::default().render(&doc), doc.to_md());
+    }
+
+    #[test]
+    fn provenance() {
+        let doc = crate::load_with(
+            &[std::path::Path::new("/nn.witx")],
+            &crate::MockFs::new(&[
+                ("/nn.witx", "(use \"types.witx\") (typename $own u8)"),
+                (
+                    "/types.witx",
+                    "(typename $t u8) (module $m (@interface func (export \"f\")))",
+                ),
+            ]),
+        )
+        .unwrap();
+        let md = doc.to_md();
+        assert_eq!(
+            md.matches("Defined in `types.witx`, brought in by `use` from `nn.witx`.")
+                .count(),
+            2,
+            "{}",
+            md
+        );
     }
 }
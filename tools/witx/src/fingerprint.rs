@@ -0,0 +1,290 @@
+//! Stable content hashes of documents and their items.
+//!
+//! A fingerprint hashes the canonical rendering of an item with its
+//! documentation removed, so it is independent of formatting and comments.
+//! Named types referenced by an item contribute their own fingerprints, so an
+//! item's fingerprint changes whenever any type it is built from does.
+//! Definitions in a document or module are hashed in name order, since their
+//! order carries no meaning.
+//!
+//! Fingerprints use 64-bit FNV-1a and are stable across platforms and
+//! releases of this crate, unless the canonical rendering itself changes.
+
+use crate::{Document, Id, InterfaceFunc, Module, NamedType, SExpr, Type, TypeRef};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    /// A stable hash of some source text, as it was read.
+    pub fn of_source(source: &str) -> Self {
+        let mut hasher = Fnv::new();
+        hasher.write(source.as_bytes());
+        hasher.finish()
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// An FNV-1a hasher. `std`'s hashers are not stable across releases.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    /// Write a section of input, delimited so that adjacent sections can't
+    /// run together.
+    fn section(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+
+    fn finish(&self) -> Fingerprint {
+        Fingerprint(self.0)
+    }
+}
+
+/// `sexpr` with all its documentation removed.
+pub(crate) fn strip_docs(sexpr: SExpr) -> SExpr {
+    match sexpr {
+        SExpr::Docs(_, s) => strip_docs(*s),
+        SExpr::Vec(v) => SExpr::Vec(v.into_iter().map(strip_docs).collect()),
+        s => s,
+    }
+}
+
+/// The named types directly referenced by `tref`, in order of appearance.
+fn named_deps(tref: &TypeRef, deps: &mut Vec<Rc<NamedType>>) {
+    match tref {
+        TypeRef::Name(nt) => {
+            if !deps.iter().any(|d| d.name == nt.name) {
+                deps.push(nt.clone());
+            }
+        }
+        TypeRef::Value(ty) => match &**ty {
+            Type::Record(r) => {
+                for m in r.members.iter() {
+                    named_deps(&m.tref, deps);
+                }
+            }
+            Type::Variant(v) => {
+                for tref in v.cases.iter().filter_map(|c| c.tref.as_ref()) {
+                    named_deps(tref, deps);
+                }
+            }
+            Type::List(t) | Type::Pointer(t) | Type::ConstPointer(t) => named_deps(t, deps),
+            Type::Handle(_) | Type::Builtin(_) => {}
+        },
+    }
+}
+
+/// Computes fingerprints, remembering those of the named types seen so far.
+#[derive(Default)]
+struct Fingerprinter {
+    types: HashMap<Id, Fingerprint>,
+}
+
+impl Fingerprinter {
+    /// Hash the canonical rendering `sexpr` of an item built from the named
+    /// types `deps`.
+    fn item(&mut self, sexpr: SExpr, deps: &[Rc<NamedType>]) -> Fingerprint {
+        let mut hasher = Fnv::new();
+        hasher.section(&strip_docs(sexpr).to_string());
+        for dep in deps {
+            hasher.section(dep.name.as_str());
+            hasher.write(&self.typename(dep).0.to_le_bytes());
+        }
+        hasher.finish()
+    }
+
+    fn typename(&mut self, nt: &NamedType) -> Fingerprint {
+        if let Some(fp) = self.types.get(&nt.name) {
+            return *fp;
+        }
+        let mut deps = Vec::new();
+        named_deps(&nt.tref, &mut deps);
+        let fp = self.item(nt.to_sexpr(), &deps);
+        self.types.insert(nt.name.clone(), fp);
+        fp
+    }
+
+    fn func(&mut self, func: &InterfaceFunc) -> Fingerprint {
+        let mut deps = Vec::new();
+        for p in func.params.iter().chain(func.results.iter()) {
+            named_deps(&p.tref, &mut deps);
+        }
+        self.item(func.to_sexpr(), &deps)
+    }
+
+    fn module(&mut self, module: &Module) -> Fingerprint {
+        let mut hasher = Fnv::new();
+        hasher.section(module.name.as_str());
+        let mut imports = module
+            .imports()
+            .map(|i| strip_docs(i.to_sexpr()).to_string())
+            .collect::<Vec<_>>();
+        imports.sort();
+        for import in imports {
+            hasher.section(&import);
+        }
+        let mut funcs = module.funcs().collect::<Vec<_>>();
+        funcs.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+        for func in funcs {
+            hasher.write(&self.func(&func).0.to_le_bytes());
+        }
+        hasher.finish()
+    }
+
+    fn document(&mut self, doc: &Document) -> Fingerprint {
+        let mut hasher = Fnv::new();
+        let mut types = doc.typenames().collect::<Vec<_>>();
+        types.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+        for nt in types {
+            hasher.write(&self.typename(&nt).0.to_le_bytes());
+        }
+        let mut modules = doc.modules().collect::<Vec<_>>();
+        modules.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+        for module in modules {
+            hasher.write(&self.module(&module).0.to_le_bytes());
+        }
+        hasher.finish()
+    }
+}
+
+impl NamedType {
+    /// A stable hash of this type's definition and the types it is built from.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprinter::default().typename(self)
+    }
+}
+
+impl InterfaceFunc {
+    /// A stable hash of this function's signature and the types it uses.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprinter::default().func(self)
+    }
+}
+
+impl Module {
+    /// A stable hash of this module's name, imports and functions.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprinter::default().module(self)
+    }
+}
+
+impl Document {
+    /// A stable hash of all of this document's types and modules.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprinter::default().document(self)
+    }
+
+    /// The fingerprints of the document and each of its items, labeled
+    /// `document`, `type <name>`, `module <name>` and
+    /// `func <module>::<name>`.
+    pub fn fingerprints(&self) -> Vec<(String, Fingerprint)> {
+        let mut fp = Fingerprinter::default();
+        let mut items = vec![("document".to_string(), fp.document(self))];
+        for nt in self.typenames() {
+            items.push((format!("type {}", nt.name.as_str()), fp.typename(&nt)));
+        }
+        for module in self.modules() {
+            items.push((
+                format!("module {}", module.name.as_str()),
+                fp.module(&module),
+            ));
+            for func in module.funcs() {
+                items.push((
+                    format!("func {}::{}", module.name.as_str(), func.name.as_str()),
+                    fp.func(&func),
+                ));
+            }
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(source: &str) -> Document {
+        crate::parse(source).unwrap()
+    }
+
+    #[test]
+    fn ignores_docs_and_formatting() {
+        let a = parse(
+            "(typename $e (enum $a $b))
+             (module $m (@interface func (export \"f\") (param $x $e)))",
+        );
+        let b = parse(
+            ";;; An enum.
+             (typename $e
+                (enum
+                    ;;; The first case.
+                    $a
+                    $b))
+             ;;; A module.
+             (module $m
+                (@interface func (export \"f\")
+                    ;;; A param.
+                    (param $x $e)))",
+        );
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.fingerprints(), b.fingerprints());
+    }
+
+    #[test]
+    fn ignores_definition_order() {
+        let a = parse(
+            "(typename $a u8) (typename $b u16)
+             (module $m (@interface func (export \"f\")) (@interface func (export \"g\")))",
+        );
+        let b = parse(
+            "(typename $b u16) (typename $a u8)
+             (module $m (@interface func (export \"g\")) (@interface func (export \"f\")))",
+        );
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn follows_dependencies() {
+        let a = parse(
+            "(typename $e (enum $a $b))
+             (typename $r (record (field $x $e)))
+             (module $m
+                (@interface func (export \"f\") (param $r $r))
+                (@interface func (export \"g\") (param $x u8)))",
+        );
+        let b = parse(
+            "(typename $e (enum $a $b $c))
+             (typename $r (record (field $x $e)))
+             (module $m
+                (@interface func (export \"f\") (param $r $r))
+                (@interface func (export \"g\") (param $x u8)))",
+        );
+        let fps = |doc: &Document| doc.fingerprints().into_iter().collect::<HashMap<_, _>>();
+        let (a, b) = (fps(&a), fps(&b));
+        for changed in &["document", "type e", "type r", "module m", "func m::f"] {
+            assert_ne!(a[*changed], b[*changed], "{}", changed);
+        }
+        assert_eq!(a["func m::g"], b["func m::g"]);
+    }
+}
@@ -0,0 +1,135 @@
+//! Entry points of the fuzz targets in `fuzz/`, which cargo-fuzz runs with
+//! arbitrary bytes. Each returns quietly on invalid input, so a panic is a
+//! bug.
+//!
+//! The targets' corpora are seeded from the witx files of each phase, and
+//! then run with a nightly toolchain:
+//!
+//! ```text
+//! cd fuzz
+//! cargo run --bin seed_corpus
+//! cargo +nightly fuzz run validate
+//! ```
+
+use crate::parser::TopLevelDocument;
+use crate::{WitxError, WitxIo};
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+/// Lexes `data` as s-expressions, up to its first invalid token.
+pub fn lex(data: &[u8]) {
+    if let Ok(source) = std::str::from_utf8(data) {
+        for token in wast::lexer::Lexer::new(source) {
+            if token.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Parses `data` as witx syntax, without validating it or resolving the
+/// files it `use`s.
+pub fn parse(data: &[u8]) {
+    if let Ok(source) = std::str::from_utf8(data) {
+        if crate::parser::check_nesting(source).is_err() {
+            return;
+        }
+        if let Ok(buf) = wast::parser::ParseBuffer::new(source) {
+            let _ = wast::parser::parse::<TopLevelDocument>(&buf);
+        }
+    }
+}
+
+/// Validates `data` as a witx document, each file it `use`s being empty, and
+/// prints it if it's valid.
+pub fn validate(data: &[u8]) {
+    if let Ok(source) = std::str::from_utf8(data) {
+        if let Ok(doc) = crate::load_with(&[ROOT], &StubIo { source }) {
+            let _ = doc.to_string();
+        }
+    }
+}
+
+/// The witx files of every phase, wasi-nn's among them, by a file name
+/// unique across phases, to seed the fuzz targets' corpora.
+pub fn seed_corpus() -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    let mut seeds = Vec::new();
+    for phase in crate::phases::available()? {
+        for file in phase.files()? {
+            let name = match file.file_name().and_then(|n| n.to_str()) {
+                Some(name) => format!("{}-{}", phase.name.replace('/', "-"), name),
+                None => continue,
+            };
+            seeds.push((name, std::fs::read(&file)?));
+        }
+    }
+    Ok(seeds)
+}
+
+const ROOT: &str = "-";
+
+/// Reads the fuzzed source as the root file, and every other file as empty.
+struct StubIo<'a> {
+    source: &'a str,
+}
+
+impl StubIo<'_> {
+    fn contents(&self, path: &Path) -> &str {
+        if path == Path::new(ROOT) {
+            self.source
+        } else {
+            ""
+        }
+    }
+}
+
+impl WitxIo for StubIo<'_> {
+    fn fgets(&self, path: &Path) -> Result<String, WitxError> {
+        Ok(self.contents(path).to_string())
+    }
+    fn fget_line(&self, path: &Path, line: usize) -> Result<String, WitxError> {
+        self.contents(path)
+            .lines()
+            .nth(line.saturating_sub(1))
+            .map(|s| s.to_string())
+            .ok_or_else(|| WitxError::Io(path.to_path_buf(), Error::other("line not found")))
+    }
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, WitxError> {
+        Ok(path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seeds() {
+        let seeds = seed_corpus().unwrap();
+        let nn = seeds
+            .iter()
+            .find(|(name, _)| name == "ephemeral-wasi_ephemeral_nn.witx")
+            .unwrap();
+        lex(&nn.1);
+        parse(&nn.1);
+        validate(&nn.1);
+        for data in [
+            &b"(typename"[..],
+            b"\xff",
+            b"(use \"missing.witx\") (typename $t u8)",
+        ] {
+            lex(data);
+            parse(data);
+            validate(data);
+        }
+    }
+
+    #[test]
+    fn stubbed_uses() {
+        let io = StubIo {
+            source: "(use \"types.witx\") (typename $t u8)",
+        };
+        let doc = crate::load_with(&[ROOT], &io).unwrap();
+        assert!(doc.typename(&crate::Id::new("t")).is_some());
+    }
+}
@@ -0,0 +1,693 @@
+//! Dependency graphs between the types and functions of a document.
+//!
+//! Every named type is a node, and so is every function, belonging to the
+//! module which defines it. Edges point from a type or a function to the
+//! named types it is built from, labeled with the field, case, param or result
+//! through which the dependency is introduced.
+//!
+//! [`Graph`] holds the nodes and edges, with helpers for walking them in the
+//! style of `petgraph`. The renderers and [`module_types`] are built on it.
+
+use crate::{Document, Id, InterfaceFunc, Module, NamedType, Type, TypeRef};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::rc::Rc;
+use thiserror::Error;
+
+/// Collect the named types directly referenced by `tref`, without looking
+/// through those names themselves.
+fn named_deps(tref: &TypeRef, label: &str, deps: &mut Vec<(String, Rc<NamedType>)>) {
+    match tref {
+        TypeRef::Name(nt) => deps.push((label.to_string(), nt.clone())),
+        TypeRef::Value(ty) => type_deps(ty, label, deps),
+    }
+}
+
+fn type_deps(ty: &Type, label: &str, deps: &mut Vec<(String, Rc<NamedType>)>) {
+    match ty {
+        Type::Record(r) => {
+            for m in r.members.iter() {
+                named_deps(&m.tref, m.name.as_str(), deps);
+            }
+        }
+        Type::Variant(v) => {
+            for c in v.cases.iter() {
+                if let Some(tref) = &c.tref {
+                    named_deps(tref, c.name.as_str(), deps);
+                }
+            }
+        }
+        Type::List(t) | Type::Pointer(t) | Type::ConstPointer(t) => named_deps(t, label, deps),
+        Type::Handle(_) | Type::Builtin(_) => {}
+    }
+}
+
+/// The index of a node in a [`Graph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeIndex(usize);
+
+impl NodeIndex {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Type(Rc<NamedType>),
+    Func { module: Id, func: Rc<InterfaceFunc> },
+}
+
+impl Node {
+    /// A name for the node, unique within its graph.
+    pub fn id(&self) -> String {
+        match self {
+            Node::Type(t) => format!("type.{}", t.name.as_str()),
+            Node::Func { module, func } => {
+                format!("func.{}.{}", module.as_str(), func.name.as_str())
+            }
+        }
+    }
+
+    /// The name of the type or function.
+    pub fn name(&self) -> &Id {
+        match self {
+            Node::Type(t) => &t.name,
+            Node::Func { func, .. } => &func.name,
+        }
+    }
+}
+
+/// How the source of an edge depends on its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// A type is built from the target type.
+    Uses,
+    /// A function takes a param of the target type.
+    Param,
+    /// A function returns a result of the target type.
+    Result,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub source: NodeIndex,
+    pub target: NodeIndex,
+    pub kind: EdgeKind,
+    /// The field, case, param or result introducing the dependency, empty
+    /// for the elements of lists and pointers.
+    pub label: String,
+}
+
+/// Which end of its edges a node is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Edges from the node to the types it depends on.
+    Outgoing,
+    /// Edges to the node from the types and functions depending on it.
+    Incoming,
+}
+
+/// The dependency graph of a document or module.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    types: HashMap<Id, NodeIndex>,
+}
+
+impl Graph {
+    /// The graph of every type and function of `doc`. Types come first, in
+    /// the order they are defined, followed by the functions of each module.
+    pub fn new(doc: &Document) -> Self {
+        let mut graph = Graph::default();
+        for t in doc.typenames() {
+            graph.add_type(&t);
+        }
+        for m in doc.modules() {
+            graph.add_funcs(&m);
+        }
+        graph.add_edges();
+        graph
+    }
+
+    /// The graph of the functions of `module` and the types they use,
+    /// directly or through other types. Functions come first, followed by
+    /// the types in the order they are first reached.
+    pub fn module(module: &Module) -> Self {
+        let mut graph = Graph::default();
+        graph.add_funcs(module);
+        graph.add_edges();
+        graph
+    }
+
+    fn add_type(&mut self, t: &Rc<NamedType>) -> NodeIndex {
+        if let Some(ix) = self.types.get(&t.name) {
+            return *ix;
+        }
+        let ix = NodeIndex(self.nodes.len());
+        self.nodes.push(Node::Type(t.clone()));
+        self.types.insert(t.name.clone(), ix);
+        ix
+    }
+
+    fn add_funcs(&mut self, module: &Module) {
+        for func in module.funcs() {
+            self.nodes.push(Node::Func {
+                module: module.name.clone(),
+                func,
+            });
+        }
+    }
+
+    /// Add the edges of every node, adding the types they reach which aren't
+    /// nodes yet as it goes.
+    fn add_edges(&mut self) {
+        let mut i = 0;
+        while i < self.nodes.len() {
+            let mut deps = Vec::new();
+            match self.nodes[i].clone() {
+                Node::Type(t) => {
+                    let mut uses = Vec::new();
+                    type_deps(t.type_(), "", &mut uses);
+                    deps.extend(uses.into_iter().map(|(l, t)| (EdgeKind::Uses, l, t)));
+                }
+                Node::Func { func, .. } => {
+                    for (kind, params) in [
+                        (EdgeKind::Param, &func.params),
+                        (EdgeKind::Result, &func.results),
+                    ] {
+                        for p in params.iter() {
+                            let mut uses = Vec::new();
+                            named_deps(&p.tref, p.name.as_str(), &mut uses);
+                            deps.extend(uses.into_iter().map(|(l, t)| (kind, l, t)));
+                        }
+                    }
+                }
+            }
+            for (kind, label, dep) in deps {
+                let target = self.add_type(&dep);
+                self.edges.push(Edge {
+                    source: NodeIndex(i),
+                    target,
+                    kind,
+                    label,
+                });
+            }
+            i += 1;
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn node_indices(&self) -> impl Iterator<Item = NodeIndex> {
+        (0..self.nodes.len()).map(NodeIndex)
+    }
+
+    pub fn node(&self, ix: NodeIndex) -> &Node {
+        &self.nodes[ix.0]
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeIndex, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (NodeIndex(i), n))
+    }
+
+    /// Every edge, grouped by source node.
+    pub fn edge_references(&self) -> impl Iterator<Item = &Edge> {
+        self.edges.iter()
+    }
+
+    /// The node of the named type `name`.
+    pub fn find_type(&self, name: &Id) -> Option<NodeIndex> {
+        self.types.get(name).copied()
+    }
+
+    /// The node of the function `func` of `module`.
+    pub fn find_func(&self, module: &Id, func: &Id) -> Option<NodeIndex> {
+        self.nodes().find_map(|(ix, n)| match n {
+            Node::Func { module: m, func: f } if m == module && f.name == *func => Some(ix),
+            _ => None,
+        })
+    }
+
+    /// The edges of `ix` in `dir`.
+    pub fn edges_directed(&self, ix: NodeIndex, dir: Direction) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |e| match dir {
+            Direction::Outgoing => e.source == ix,
+            Direction::Incoming => e.target == ix,
+        })
+    }
+
+    /// The edges from `ix` to the types it depends on.
+    pub fn edges(&self, ix: NodeIndex) -> impl Iterator<Item = &Edge> {
+        self.edges_directed(ix, Direction::Outgoing)
+    }
+
+    /// The nodes at the other end of the edges of `ix` in `dir`, once each.
+    pub fn neighbors_directed(&self, ix: NodeIndex, dir: Direction) -> Vec<NodeIndex> {
+        let mut neighbors = Vec::new();
+        for e in self.edges_directed(ix, dir) {
+            let n = match dir {
+                Direction::Outgoing => e.target,
+                Direction::Incoming => e.source,
+            };
+            if !neighbors.contains(&n) {
+                neighbors.push(n);
+            }
+        }
+        neighbors
+    }
+
+    /// The types `ix` depends on directly.
+    pub fn neighbors(&self, ix: NodeIndex) -> Vec<NodeIndex> {
+        self.neighbors_directed(ix, Direction::Outgoing)
+    }
+
+    /// The nodes reachable from `starts` following edges in `dir`, starting
+    /// with `starts` themselves, breadth first.
+    pub fn bfs(
+        &self,
+        starts: impl IntoIterator<Item = NodeIndex>,
+        dir: Direction,
+    ) -> Vec<NodeIndex> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        for s in starts {
+            if seen.insert(s) {
+                queue.push_back(s);
+            }
+        }
+        let mut order = Vec::new();
+        while let Some(ix) = queue.pop_front() {
+            order.push(ix);
+            for n in self.neighbors_directed(ix, dir) {
+                if seen.insert(n) {
+                    queue.push_back(n);
+                }
+            }
+        }
+        order
+    }
+
+    /// The nodes reachable from `start` following edges in `dir`, starting
+    /// with `start` itself, depth first in preorder.
+    pub fn dfs(&self, start: NodeIndex, dir: Direction) -> Vec<NodeIndex> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![start];
+        let mut order = Vec::new();
+        while let Some(ix) = stack.pop() {
+            if !seen.insert(ix) {
+                continue;
+            }
+            order.push(ix);
+            for n in self.neighbors_directed(ix, dir).into_iter().rev() {
+                if !seen.contains(&n) {
+                    stack.push(n);
+                }
+            }
+        }
+        order
+    }
+
+    /// The named types which no function uses, directly or through other
+    /// types.
+    pub fn unused_types(&self) -> Vec<Rc<NamedType>> {
+        let funcs = self
+            .nodes()
+            .filter(|(_, n)| matches!(n, Node::Func { .. }))
+            .map(|(ix, _)| ix);
+        let used = self
+            .bfs(funcs, Direction::Outgoing)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        self.nodes()
+            .filter(|(ix, _)| !used.contains(ix))
+            .filter_map(|(_, n)| match n {
+                Node::Type(t) => Some(t.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn funcs_of<'a>(&'a self, module: &'a Id) -> impl Iterator<Item = (NodeIndex, &'a Node)> {
+        self.nodes()
+            .filter(move |(_, n)| matches!(n, Node::Func { module: m, .. } if m == module))
+    }
+}
+
+/// Render the dependency graph of `doc` in the graphviz DOT language.
+pub fn to_dot(doc: &Document) -> String {
+    let graph = Graph::new(doc);
+    let mut out = String::new();
+    out.push_str("digraph witx {\n");
+    out.push_str("    rankdir=LR;\n");
+    for (_, n) in graph.nodes().filter(|(_, n)| matches!(n, Node::Type(_))) {
+        writeln!(
+            out,
+            "    \"{}\" [label=\"{}\", shape=box];",
+            n.id(),
+            n.name().as_str()
+        )
+        .unwrap();
+    }
+    for m in doc.modules() {
+        writeln!(out, "    subgraph \"cluster_{}\" {{", m.name.as_str()).unwrap();
+        writeln!(out, "        label=\"{}\";", m.name.as_str()).unwrap();
+        for (_, n) in graph.funcs_of(&m.name) {
+            writeln!(
+                out,
+                "        \"{}\" [label=\"{}\", shape=ellipse];",
+                n.id(),
+                n.name().as_str()
+            )
+            .unwrap();
+        }
+        out.push_str("    }\n");
+    }
+    for e in graph.edge_references() {
+        writeln!(
+            out,
+            "    \"{}\" -> \"{}\" [label=\"{}\"];",
+            graph.node(e.source).id(),
+            graph.node(e.target).id(),
+            e.label
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Mermaid node ids may only contain alphanumerics and underscores.
+fn mermaid_id(node: &Node) -> String {
+    node.id()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Render the dependency graph of `doc` as a Mermaid flowchart.
+pub fn to_mermaid(doc: &Document) -> String {
+    let graph = Graph::new(doc);
+    let mut out = String::new();
+    out.push_str("graph LR\n");
+    for (_, n) in graph.nodes().filter(|(_, n)| matches!(n, Node::Type(_))) {
+        writeln!(out, "    {}[\"{}\"]", mermaid_id(n), n.name().as_str()).unwrap();
+    }
+    for m in doc.modules() {
+        writeln!(out, "    subgraph {}", m.name.as_str()).unwrap();
+        for (_, n) in graph.funcs_of(&m.name) {
+            writeln!(
+                out,
+                "        {}([\"{}\"])",
+                mermaid_id(n),
+                n.name().as_str()
+            )
+            .unwrap();
+        }
+        out.push_str("    end\n");
+    }
+    for e in graph.edge_references() {
+        let arrow = if e.label.is_empty() {
+            "-->".to_string()
+        } else {
+            format!("-- {} -->", e.label)
+        };
+        writeln!(
+            out,
+            "    {} {} {}",
+            mermaid_id(graph.node(e.source)),
+            arrow,
+            mermaid_id(graph.node(e.target))
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Named types which depend on themselves through each other, listed from
+/// the first type reached on the cycle back around to it.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("types depend on each other in a cycle: {}", cycle_names(.types))]
+pub struct TypeCycle {
+    pub types: Vec<Id>,
+}
+
+fn cycle_names(types: &[Id]) -> String {
+    types
+        .iter()
+        .map(|t| format!("`{}`", t.as_str()))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+impl Document {
+    /// The named types of the document, sorted so that every type comes after
+    /// the types it is built from. Types which don't depend on each other
+    /// keep the order they are defined in.
+    ///
+    /// Validation rejects references to types which aren't defined yet, so
+    /// a validated document never has a cycle.
+    pub fn types_topo_order(&self) -> Result<Vec<Rc<NamedType>>, TypeCycle> {
+        let mut done = HashMap::new();
+        let mut path = Vec::new();
+        let mut order = Vec::new();
+        for t in self.typenames() {
+            visit(&t, &type_names, &mut done, &mut path, &mut order)?;
+        }
+        Ok(order)
+    }
+}
+
+/// The named types `t` is directly built from.
+pub(crate) fn type_names(t: &NamedType) -> Vec<Rc<NamedType>> {
+    let mut deps = Vec::new();
+    type_deps(t.type_(), "", &mut deps);
+    deps.into_iter().map(|(_, dep)| dep).collect()
+}
+
+/// Visit `t` depth first, adding it to `order` after the types `deps` says
+/// it's built from. `done` records whether each type reached so far has been
+/// added, and `path` holds the types still being visited.
+pub(crate) fn visit(
+    t: &Rc<NamedType>,
+    deps: &dyn Fn(&NamedType) -> Vec<Rc<NamedType>>,
+    done: &mut HashMap<Id, bool>,
+    path: &mut Vec<Id>,
+    order: &mut Vec<Rc<NamedType>>,
+) -> Result<(), TypeCycle> {
+    match done.get(&t.name) {
+        Some(true) => return Ok(()),
+        Some(false) => {
+            let start = path.iter().position(|p| *p == t.name).unwrap();
+            let mut types = path[start..].to_vec();
+            types.push(t.name.clone());
+            return Err(TypeCycle { types });
+        }
+        None => {}
+    }
+    done.insert(t.name.clone(), false);
+    path.push(t.name.clone());
+    for dep in deps(t) {
+        visit(&dep, deps, done, path, order)?;
+    }
+    path.pop();
+    done.insert(t.name.clone(), true);
+    order.push(t.clone());
+    Ok(())
+}
+
+/// The named types the functions of `module` use, directly or through other
+/// types, in the order they are first reached.
+pub fn module_types(module: &Module) -> Vec<Rc<NamedType>> {
+    Graph::module(module)
+        .nodes
+        .into_iter()
+        .filter_map(|n| match n {
+            Node::Type(t) => Some(t),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Render the object model of `module` as a Mermaid flowchart: the named
+/// types its functions use, directly or through other types, and its
+/// functions. Params point from their type to the function consuming them,
+/// and results from the function to the type it produces. Handles are drawn
+/// as hexagons and functions as rounded boxes.
+pub fn module_to_mermaid(module: &Module) -> String {
+    let graph = Graph::module(module);
+    let mut out = String::new();
+    out.push_str("graph LR\n");
+    let (funcs, types): (Vec<_>, Vec<_>) = graph
+        .nodes()
+        .partition(|(_, n)| matches!(n, Node::Func { .. }));
+    for (_, n) in types.iter().chain(funcs.iter()) {
+        match n {
+            Node::Type(t) => match &**t.type_() {
+                Type::Handle(_) => {
+                    writeln!(out, "    {}{{{{\"{}\"}}}}", mermaid_id(n), t.name.as_str())
+                }
+                _ => writeln!(out, "    {}[\"{}\"]", mermaid_id(n), t.name.as_str()),
+            },
+            Node::Func { func, .. } => {
+                writeln!(out, "    {}([\"{}\"])", mermaid_id(n), func.name.as_str())
+            }
+        }
+        .unwrap();
+    }
+    for (ix, _) in types.iter().chain(funcs.iter()) {
+        for e in graph.edges(*ix) {
+            let (from, to) = match e.kind {
+                EdgeKind::Param => (e.target, e.source),
+                _ => (e.source, e.target),
+            };
+            let (from, to) = (mermaid_id(graph.node(from)), mermaid_id(graph.node(to)));
+            if e.label.is_empty() {
+                writeln!(out, "    {} --> {}", from, to).unwrap();
+            } else {
+                writeln!(out, "    {} -- {} --> {}", from, e.label, to).unwrap();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn doc() -> Document {
+        crate::parse(
+            "(typename $dims (list u32))
+             (typename $tensor (record (field $dimensions $dims) (field $data (list u8))))
+             (typename $graph (handle))
+             (module $nn
+                (@interface func (export \"set_input\")
+                    (param $graph $graph)
+                    (param $tensor $tensor)))",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn dot() {
+        let dot = to_dot(&doc());
+        assert!(dot.contains("subgraph \"cluster_nn\""));
+        assert!(dot.contains("\"type.tensor\" -> \"type.dims\" [label=\"dimensions\"];"));
+        assert!(dot.contains("\"func.nn.set_input\" -> \"type.graph\" [label=\"graph\"];"));
+        assert!(dot.contains("\"func.nn.set_input\" -> \"type.tensor\" [label=\"tensor\"];"));
+    }
+
+    #[test]
+    fn mermaid() {
+        let mermaid = to_mermaid(&doc());
+        assert!(mermaid.contains("subgraph nn"));
+        assert!(mermaid.contains("type_tensor -- dimensions --> type_dims"));
+        assert!(mermaid.contains("func_nn_set_input -- graph --> type_graph"));
+    }
+
+    #[test]
+    fn graph() {
+        let mut doc = String::from("(typename $unused (list u8))\n");
+        doc.push_str(
+            "(typename $dims (list u32))
+             (typename $tensor (record (field $dimensions $dims) (field $data (list u8))))
+             (typename $graph (handle))
+             (module $nn
+                (@interface func (export \"set_input\")
+                    (param $graph $graph)
+                    (param $tensor $tensor))
+                (@interface func (export \"load\")
+                    (result $graph $graph)))",
+        );
+        let graph = Graph::new(&crate::parse(&doc).unwrap());
+        assert_eq!(graph.node_count(), 6);
+        assert_eq!(graph.edge_count(), 4);
+
+        let tensor = graph.find_type(&Id::new("tensor")).unwrap();
+        let dims = graph.find_type(&Id::new("dims")).unwrap();
+        let set_input = graph
+            .find_func(&Id::new("nn"), &Id::new("set_input"))
+            .unwrap();
+        let load = graph.find_func(&Id::new("nn"), &Id::new("load")).unwrap();
+        assert_eq!(graph.neighbors(tensor), [dims]);
+        assert_eq!(
+            graph.dfs(set_input, Direction::Outgoing),
+            [
+                set_input,
+                graph.find_type(&Id::new("graph")).unwrap(),
+                tensor,
+                dims
+            ]
+        );
+        assert_eq!(
+            graph.bfs([dims], Direction::Incoming),
+            [dims, tensor, set_input]
+        );
+        let kinds = graph
+            .edges(load)
+            .map(|e| (e.kind, e.label.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, [(EdgeKind::Result, "graph")]);
+
+        let unused = graph.unused_types();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].name.as_str(), "unused");
+    }
+
+    #[test]
+    fn topo_order() {
+        let doc = crate::parse(
+            "(typename $graph (handle))
+             (typename $dims (list u32))
+             (typename $tensor (record (field $dimensions $dims) (field $graph $graph)))
+             (typename $size u32)",
+        )
+        .unwrap();
+        let order = doc.types_topo_order().unwrap();
+        let names = order.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["graph", "dims", "tensor", "size"]);
+
+        assert_eq!(
+            TypeCycle {
+                types: vec![Id::new("a"), Id::new("b"), Id::new("a")]
+            }
+            .to_string(),
+            "types depend on each other in a cycle: `a` -> `b` -> `a`"
+        );
+    }
+
+    #[test]
+    fn module_mermaid() {
+        let doc = crate::parse(
+            "(typename $dims (list u32))
+             (typename $tensor (record (field $dimensions $dims) (field $data (list u8))))
+             (typename $graph (handle))
+             (typename $unused u8)
+             (module $nn
+                (@interface func (export \"load\")
+                    (result $graph $graph))
+                (@interface func (export \"set_input\")
+                    (param $graph $graph)
+                    (param $tensor $tensor)))",
+        )
+        .unwrap();
+        let mermaid = module_to_mermaid(&doc.module(&"nn".into()).unwrap());
+        assert!(mermaid.contains("type_graph{{\"graph\"}}"));
+        assert!(mermaid.contains("type_dims[\"dims\"]"));
+        assert!(!mermaid.contains("unused"));
+        assert!(mermaid.contains("func_nn_load -- graph --> type_graph"));
+        assert!(mermaid.contains("type_graph -- graph --> func_nn_set_input"));
+        assert!(mermaid.contains("type_tensor -- dimensions --> type_dims"));
+    }
+}
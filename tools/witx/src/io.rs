@@ -1,7 +1,7 @@
 use crate::WitxError;
 use std::collections::HashMap;
 use std::fs::{read_to_string, File};
-use std::io::{BufRead, BufReader, Error, ErrorKind};
+use std::io::{BufRead, BufReader, Error};
 use std::path::{Path, PathBuf};
 
 pub trait WitxIo {
@@ -11,6 +11,15 @@ pub trait WitxIo {
     fn fget_line(&self, path: &Path, line_num: usize) -> Result<String, WitxError>;
     /// Return the canonical (non-symlinked) path of a file. Used to resolve `use` declarations.
     fn canonicalize(&self, path: &Path) -> Result<PathBuf, WitxError>;
+    /// Whether there's a file at `path`. Used to search include paths.
+    fn exists(&self, path: &Path) -> bool {
+        self.fgets(path).is_ok()
+    }
+    /// The size in bytes of the file at `path`, if it's known without
+    /// reading the file. Used to reject files over a size limit up front.
+    fn size(&self, _path: &Path) -> Option<u64> {
+        None
+    }
 }
 
 impl<T: WitxIo + ?Sized> WitxIo for &'_ T {
@@ -23,6 +32,12 @@ impl<T: WitxIo + ?Sized> WitxIo for &'_ T {
     fn canonicalize(&self, path: &Path) -> Result<PathBuf, WitxError> {
         T::canonicalize(self, path)
     }
+    fn exists(&self, path: &Path) -> bool {
+        T::exists(self, path)
+    }
+    fn size(&self, path: &Path) -> Option<u64> {
+        T::size(self, path)
+    }
 }
 
 pub struct Filesystem;
@@ -34,13 +49,10 @@ impl WitxIo for Filesystem {
     fn fget_line(&self, path: &Path, line_num: usize) -> Result<String, WitxError> {
         let f = File::open(path).map_err(|e| WitxError::Io(path.into(), e))?;
         let buf = BufReader::new(f);
-        let l = buf
-            .lines()
-            .skip(line_num - 1)
-            .next()
-            .ok_or_else(|| {
-                WitxError::Io(path.into(), Error::new(ErrorKind::Other, "Line not found"))
-            })?
+        let l = line_num
+            .checked_sub(1)
+            .and_then(|n| buf.lines().nth(n))
+            .ok_or_else(|| WitxError::Io(path.into(), Error::other("Line not found")))?
             .map_err(|e| WitxError::Io(path.into(), e))?;
 
         Ok(l)
@@ -49,6 +61,12 @@ impl WitxIo for Filesystem {
         path.canonicalize()
             .map_err(|e| WitxError::Io(path.to_path_buf(), e))
     }
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+    fn size(&self, path: &Path) -> Option<u64> {
+        path.metadata().ok().map(|m| m.len())
+    }
 }
 
 pub struct MockFs {
@@ -68,36 +86,39 @@ impl MockFs {
 
 impl WitxIo for MockFs {
     fn fgets(&self, path: &Path) -> Result<String, WitxError> {
-        if let Some(entry) = self.map.get(path.to_str().unwrap()) {
+        if let Some(entry) = path.to_str().and_then(|p| self.map.get(p)) {
             Ok(entry.to_string())
         } else {
             Err(WitxError::Io(
                 path.to_path_buf(),
-                Error::new(ErrorKind::Other, "mock fs: file not found"),
+                Error::other("mock fs: file not found"),
             ))
         }
     }
     fn fget_line(&self, path: &Path, line: usize) -> Result<String, WitxError> {
-        if let Some(entry) = self.map.get(path.to_str().unwrap()) {
-            entry
-                .lines()
-                .skip(line - 1)
-                .next()
+        if let Some(entry) = path.to_str().and_then(|p| self.map.get(p)) {
+            line.checked_sub(1)
+                .and_then(|n| entry.lines().nth(n))
                 .map(|s| s.to_string())
                 .ok_or_else(|| {
-                    WitxError::Io(
-                        path.to_path_buf(),
-                        Error::new(ErrorKind::Other, "mock fs: file not found"),
-                    )
+                    WitxError::Io(path.to_path_buf(), Error::other("mock fs: file not found"))
                 })
         } else {
             Err(WitxError::Io(
                 path.to_path_buf(),
-                Error::new(ErrorKind::Other, "mock fs: file not found"),
+                Error::other("mock fs: file not found"),
             ))
         }
     }
     fn canonicalize(&self, path: &Path) -> Result<PathBuf, WitxError> {
         Ok(PathBuf::from(path))
     }
+    fn exists(&self, path: &Path) -> bool {
+        path.to_str().is_some_and(|p| self.map.contains_key(p))
+    }
+    fn size(&self, path: &Path) -> Option<u64> {
+        path.to_str()
+            .and_then(|p| self.map.get(p))
+            .map(|s| s.len() as u64)
+    }
 }
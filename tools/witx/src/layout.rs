@@ -1,5 +1,8 @@
 use crate::ast::*;
+use crate::WasmType;
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SizeAlign {
@@ -8,8 +11,10 @@ pub struct SizeAlign {
 }
 
 impl SizeAlign {
+    /// The layout of an empty record: no bytes, aligned like a byte, so that
+    /// it can be a field like any other.
     fn zero() -> SizeAlign {
-        SizeAlign { size: 0, align: 0 }
+        SizeAlign { size: 0, align: 1 }
     }
     fn append_field(&mut self, other: &SizeAlign) {
         self.align = self.align.max(other.align);
@@ -18,8 +23,68 @@ impl SizeAlign {
     }
 }
 
+/// The wasm target whose linear memory the layout is calculated for.
+///
+/// Only pointer-sized values are affected by the target: pointers, the
+/// pointer and length of lists, and `(@witx usize)`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Target {
+    #[default]
+    Wasm32,
+    Wasm64,
+}
+
+impl Target {
+    /// Size (and alignment) in bytes of a pointer on this target.
+    pub fn pointer_size(&self) -> usize {
+        match self {
+            Target::Wasm32 => 4,
+            Target::Wasm64 => 8,
+        }
+    }
+
+    /// The core wasm type that pointers are lowered to on this target.
+    pub fn pointer_type(&self) -> WasmType {
+        match self {
+            Target::Wasm32 => WasmType::I32,
+            Target::Wasm64 => WasmType::I64,
+        }
+    }
+
+    fn pointer_size_align(&self) -> SizeAlign {
+        SizeAlign {
+            size: self.pointer_size(),
+            align: self.pointer_size(),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Wasm32 => f.write_str("wasm32"),
+            Target::Wasm64 => f.write_str("wasm64"),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wasm32" => Ok(Target::Wasm32),
+            "wasm64" => Ok(Target::Wasm64),
+            _ => Err(format!("unknown target `{}`: expected wasm32 or wasm64", s)),
+        }
+    }
+}
+
 pub trait Layout {
-    fn mem_size_align(&self) -> SizeAlign;
+    /// Size and alignment of this type in the linear memory of `target`.
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign;
+    fn mem_size_align(&self) -> SizeAlign {
+        self.mem_size_align_for(Target::Wasm32)
+    }
     fn mem_size(&self) -> usize {
         self.mem_size_align().size
     }
@@ -29,13 +94,13 @@ pub trait Layout {
 }
 
 impl TypeRef {
-    fn layout(&self, cache: &mut HashMap<TypeRef, SizeAlign>) -> SizeAlign {
+    fn layout(&self, cache: &mut HashMap<TypeRef, SizeAlign>, target: Target) -> SizeAlign {
         if let Some(hit) = cache.get(self) {
             return *hit;
         }
         let layout = match &self {
-            TypeRef::Name(nt) => nt.layout(cache),
-            TypeRef::Value(v) => v.layout(cache),
+            TypeRef::Name(nt) => nt.layout(cache, target),
+            TypeRef::Value(v) => v.layout(cache, target),
         };
         cache.insert(self.clone(), layout);
         layout
@@ -43,50 +108,54 @@ impl TypeRef {
 }
 
 impl Layout for TypeRef {
-    fn mem_size_align(&self) -> SizeAlign {
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign {
         let mut cache = HashMap::new();
-        self.layout(&mut cache)
+        self.layout(&mut cache, target)
     }
 }
 
 impl NamedType {
-    fn layout(&self, cache: &mut HashMap<TypeRef, SizeAlign>) -> SizeAlign {
-        self.tref.layout(cache)
+    fn layout(&self, cache: &mut HashMap<TypeRef, SizeAlign>, target: Target) -> SizeAlign {
+        self.tref.layout(cache, target)
     }
 }
 impl Layout for NamedType {
-    fn mem_size_align(&self) -> SizeAlign {
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign {
         let mut cache = HashMap::new();
-        self.layout(&mut cache)
+        self.layout(&mut cache, target)
     }
 }
 
 impl Type {
-    fn layout(&self, cache: &mut HashMap<TypeRef, SizeAlign>) -> SizeAlign {
+    fn layout(&self, cache: &mut HashMap<TypeRef, SizeAlign>, target: Target) -> SizeAlign {
         match &self {
             Type::Record(s) => match s.bitflags_repr() {
                 Some(repr) => repr.mem_size_align(),
-                None => s.layout(cache),
+                None => s.layout(cache, target),
             },
-            Type::Variant(s) => s.mem_size_align(),
-            Type::Handle(h) => h.mem_size_align(),
-            Type::List { .. } => SizeAlign { size: 8, align: 4 }, // Pointer and Length
-            Type::Pointer { .. } | Type::ConstPointer { .. } => BuiltinType::S32.mem_size_align(),
-            Type::Builtin(b) => b.mem_size_align(),
+            Type::Variant(s) => s.mem_size_align_for(target),
+            Type::Handle(h) => h.mem_size_align_for(target),
+            // Pointer and Length
+            Type::List { .. } => SizeAlign {
+                size: 2 * target.pointer_size(),
+                align: target.pointer_size(),
+            },
+            Type::Pointer { .. } | Type::ConstPointer { .. } => target.pointer_size_align(),
+            Type::Builtin(b) => b.mem_size_align_for(target),
         }
     }
 }
 
 impl Layout for Type {
-    fn mem_size_align(&self) -> SizeAlign {
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign {
         let mut cache = HashMap::new();
-        self.layout(&mut cache)
+        self.layout(&mut cache, target)
     }
 }
 
 impl Layout for IntRepr {
-    fn mem_size_align(&self) -> SizeAlign {
-        self.to_builtin().mem_size_align()
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign {
+        self.to_builtin().mem_size_align_for(target)
     }
 }
 
@@ -96,18 +165,23 @@ pub struct RecordMemberLayout<'a> {
 }
 
 impl RecordDatatype {
-    pub fn member_layout(&self) -> Vec<RecordMemberLayout> {
-        self.member_layout_(&mut HashMap::new()).1
+    pub fn member_layout(&self) -> Vec<RecordMemberLayout<'_>> {
+        self.member_layout_for(Target::Wasm32)
+    }
+
+    pub fn member_layout_for(&self, target: Target) -> Vec<RecordMemberLayout<'_>> {
+        self.member_layout_(&mut HashMap::new(), target).1
     }
 
     fn member_layout_(
         &self,
         cache: &mut HashMap<TypeRef, SizeAlign>,
-    ) -> (SizeAlign, Vec<RecordMemberLayout>) {
+        target: Target,
+    ) -> (SizeAlign, Vec<RecordMemberLayout<'_>>) {
         let mut members = Vec::new();
         let mut sa = SizeAlign::zero();
         for m in self.members.iter() {
-            let member = m.tref.layout(cache);
+            let member = m.tref.layout(cache, target);
             sa.append_field(&member);
             members.push(RecordMemberLayout {
                 member: m,
@@ -118,30 +192,30 @@ impl RecordDatatype {
         (sa, members)
     }
 
-    fn layout(&self, cache: &mut HashMap<TypeRef, SizeAlign>) -> SizeAlign {
-        self.member_layout_(cache).0
+    fn layout(&self, cache: &mut HashMap<TypeRef, SizeAlign>, target: Target) -> SizeAlign {
+        self.member_layout_(cache, target).0
     }
 }
 
 impl Layout for RecordDatatype {
-    fn mem_size_align(&self) -> SizeAlign {
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign {
         match self.bitflags_repr() {
             Some(repr) => repr.mem_size_align(),
             None => {
                 let mut cache = HashMap::new();
-                self.layout(&mut cache)
+                self.layout(&mut cache, target)
             }
         }
     }
 }
 
 impl Layout for Variant {
-    fn mem_size_align(&self) -> SizeAlign {
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign {
         let mut max = SizeAlign { size: 0, align: 0 };
         for case in self.cases.iter() {
             let mut size = self.tag_repr.mem_size_align();
             if let Some(payload) = &case.tref {
-                size.append_field(&payload.mem_size_align());
+                size.append_field(&payload.mem_size_align_for(target));
             }
             size.size = align_to(size.size, size.align);
             max.size = max.size.max(size.size);
@@ -153,10 +227,14 @@ impl Layout for Variant {
 
 impl Variant {
     pub fn payload_offset(&self) -> usize {
+        self.payload_offset_for(Target::Wasm32)
+    }
+
+    pub fn payload_offset_for(&self, target: Target) -> usize {
         let mut offset = self.tag_repr.mem_size_align().size;
         for case in self.cases.iter() {
             if let Some(payload) = &case.tref {
-                offset = offset.max(align_to(offset, payload.mem_size_align().align));
+                offset = offset.max(align_to(offset, payload.mem_size_align_for(target).align));
             }
         }
         offset
@@ -170,9 +248,33 @@ fn align_to(offs: usize, alignment: usize) -> usize {
     offs + alignment - 1 - ((offs + alignment - 1) % alignment)
 }
 
+impl Layout for HandleDatatype {
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign {
+        BuiltinType::S32.mem_size_align_for(target)
+    }
+}
+
+impl Layout for BuiltinType {
+    fn mem_size_align_for(&self, target: Target) -> SizeAlign {
+        match self {
+            BuiltinType::U8 { .. } | BuiltinType::S8 => SizeAlign { size: 1, align: 1 },
+            BuiltinType::U16 | BuiltinType::S16 => SizeAlign { size: 2, align: 2 },
+            BuiltinType::U32 {
+                lang_ptr_size: true,
+            } => target.pointer_size_align(),
+            BuiltinType::Char | BuiltinType::U32 { .. } | BuiltinType::S32 | BuiltinType::F32 => {
+                SizeAlign { size: 4, align: 4 }
+            }
+            BuiltinType::U64 | BuiltinType::S64 | BuiltinType::F64 => {
+                SizeAlign { size: 8, align: 8 }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::align_to;
+    use super::*;
     #[test]
     fn align() {
         assert_eq!(0, align_to(0, 1));
@@ -195,25 +297,52 @@ mod test {
         assert_eq!(8, align_to(5, 4));
         assert_eq!(8, align_to(5, 8));
     }
-}
 
-impl Layout for HandleDatatype {
-    fn mem_size_align(&self) -> SizeAlign {
-        BuiltinType::S32.mem_size_align()
+    #[test]
+    fn empty_fields() {
+        let doc = crate::parse(
+            "(typename $unit (tuple))
+             (typename $r (record (field $a u8) (field $b $unit) (field $c u16)))",
+        )
+        .unwrap();
+        let unit = doc.typename(&Id::new("unit")).unwrap();
+        assert_eq!(unit.mem_size_align(), SizeAlign { size: 0, align: 1 });
+        let r = doc.typename(&Id::new("r")).unwrap();
+        assert_eq!(r.mem_size_align(), SizeAlign { size: 4, align: 2 });
     }
-}
 
-impl Layout for BuiltinType {
-    fn mem_size_align(&self) -> SizeAlign {
-        match self {
-            BuiltinType::U8 { .. } | BuiltinType::S8 => SizeAlign { size: 1, align: 1 },
-            BuiltinType::U16 | BuiltinType::S16 => SizeAlign { size: 2, align: 2 },
-            BuiltinType::Char | BuiltinType::U32 { .. } | BuiltinType::S32 | BuiltinType::F32 => {
-                SizeAlign { size: 4, align: 4 }
-            }
-            BuiltinType::U64 | BuiltinType::S64 | BuiltinType::F64 => {
-                SizeAlign { size: 8, align: 8 }
-            }
-        }
+    #[test]
+    fn pointer_sized_targets() {
+        let doc = crate::parse(
+            "(typename $buf (record (field $tag u8) (field $data (list u8)) (field $len (@witx usize))))",
+        )
+        .unwrap();
+        let buf = doc.typename(&Id::new("buf")).unwrap();
+        let record = match &**buf.type_() {
+            Type::Record(r) => r,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            buf.mem_size_align_for(Target::Wasm32),
+            SizeAlign { size: 16, align: 4 }
+        );
+        let offsets = record
+            .member_layout_for(Target::Wasm32)
+            .iter()
+            .map(|m| m.offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![0, 4, 12]);
+
+        assert_eq!(
+            buf.mem_size_align_for(Target::Wasm64),
+            SizeAlign { size: 32, align: 8 }
+        );
+        let offsets = record
+            .member_layout_for(Target::Wasm64)
+            .iter()
+            .map(|m| m.offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![0, 8, 24]);
     }
 }
@@ -0,0 +1,258 @@
+//! A lexer of witx s-expressions that borrows every token from its input.
+//!
+//! The scans made before parsing, for how deep a file nests and how long
+//! its identifiers are, only need to know where the parens and identifiers
+//! of a file are. [`Lexer`] finds them without decoding strings or numbers,
+//! and its tokens carry their byte offset in the input, so that a scan
+//! doesn't work it out by comparing pointers.
+//!
+//! ```
+//! use witx::lexer::{Lexer, TokenKind};
+//!
+//! let ids = Lexer::new("(typename $t u8) ;; $not_an_id")
+//!     .filter_map(|t| t.ok())
+//!     .filter(|t| t.kind == TokenKind::Id)
+//!     .map(|t| (t.text, t.offset))
+//!     .collect::<Vec<_>>();
+//! assert_eq!(ids, [("$t", 10)]);
+//! ```
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// `(`, also starting an annotation like `(@interface`.
+    LParen,
+    RParen,
+    /// An identifier like `$t`, with its `$`.
+    Id,
+    /// A string literal, with its quotes and escapes.
+    String,
+    /// A keyword, number or any other run of identifier characters.
+    Atom,
+}
+
+/// A token of the input, without the whitespace and comments between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    /// The source text of the token.
+    pub text: &'a str,
+    /// Where the token starts in the input, in bytes.
+    pub offset: usize,
+}
+
+/// Where the input stopped lexing. Only the parser reports why, so this is
+/// just the place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LexError {
+    pub offset: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "input doesn't lex at byte {}", self.offset)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// The tokens of `source`, in order, ending after the first error.
+pub struct Lexer<'a> {
+    source: &'a str,
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Lexer {
+            source,
+            offset: 0,
+            failed: false,
+        }
+    }
+
+    /// Skip whitespace and comments, failing at an unterminated block
+    /// comment.
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
+        let bytes = self.source.as_bytes();
+        loop {
+            match bytes.get(self.offset..self.offset + 2) {
+                Some(b";;") => {
+                    self.offset = bytes[self.offset..]
+                        .iter()
+                        .position(|b| *b == b'\n')
+                        .map_or(bytes.len(), |n| self.offset + n + 1);
+                }
+                Some(b"(;") => self.skip_block_comment()?,
+                _ => match bytes.get(self.offset) {
+                    Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') => self.offset += 1,
+                    _ => return Ok(()),
+                },
+            }
+        }
+    }
+
+    /// Skip the block comment at the offset, and any nested in it.
+    fn skip_block_comment(&mut self) -> Result<(), LexError> {
+        let bytes = self.source.as_bytes();
+        let start = self.offset;
+        let mut depth = 0usize;
+        while let Some(pair) = bytes.get(self.offset..self.offset + 2) {
+            match pair {
+                b"(;" => {
+                    depth += 1;
+                    self.offset += 2;
+                }
+                b";)" => {
+                    depth -= 1;
+                    self.offset += 2;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => self.offset += 1,
+            }
+        }
+        Err(LexError { offset: start })
+    }
+
+    /// The end of the string literal starting at the offset.
+    fn string_end(&self) -> Result<usize, LexError> {
+        let bytes = self.source.as_bytes();
+        let mut i = self.offset + 1;
+        while let Some(b) = bytes.get(i) {
+            match b {
+                b'"' => return Ok(i + 1),
+                b'\\' => i += 2,
+                _ => i += 1,
+            }
+        }
+        Err(LexError {
+            offset: self.offset,
+        })
+    }
+}
+
+/// Whether `b` may be part of an identifier or keyword.
+fn is_idchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-./:<=>?@\\^_`|~".contains(&b)
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if let Err(e) = self.skip_trivia() {
+            self.failed = true;
+            return Some(Err(e));
+        }
+        let bytes = self.source.as_bytes();
+        let start = self.offset;
+        let (kind, end) = match bytes.get(start)? {
+            b'(' => (TokenKind::LParen, start + 1),
+            b')' => (TokenKind::RParen, start + 1),
+            b'"' => match self.string_end() {
+                Ok(end) => (TokenKind::String, end),
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            },
+            b if is_idchar(*b) => {
+                let len = bytes[start..].iter().take_while(|b| is_idchar(**b)).count();
+                let kind = if *b == b'$' && len > 1 {
+                    TokenKind::Id
+                } else {
+                    TokenKind::Atom
+                };
+                (kind, start + len)
+            }
+            _ => {
+                self.failed = true;
+                return Some(Err(LexError { offset: start }));
+            }
+        };
+        self.offset = end;
+        Some(Ok(Token {
+            kind,
+            text: &self.source[start..end],
+            offset: start,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<(TokenKind, &str, usize)> {
+        Lexer::new(source)
+            .map(|t| t.unwrap())
+            .map(|t| (t.kind, t.text, t.offset))
+            .collect()
+    }
+
+    #[test]
+    fn tokens() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("(@interface func (export \"a\\\"(\"))\n;;; $doc\n(; ( $c (; ;) ;)$id"),
+            vec![
+                (LParen, "(", 0),
+                (Atom, "@interface", 1),
+                (Atom, "func", 12),
+                (LParen, "(", 17),
+                (Atom, "export", 18),
+                (String, "\"a\\\"(\"", 25),
+                (RParen, ")", 31),
+                (RParen, ")", 32),
+                (Id, "$id", 59),
+            ]
+        );
+        assert_eq!(kinds("$ 0x1_0"), vec![(Atom, "$", 0), (Atom, "0x1_0", 2)]);
+
+        let errors = |source| {
+            Lexer::new(source)
+                .filter_map(|t| t.err())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(errors("( \"open"), [LexError { offset: 2 }]);
+        assert_eq!(errors("(; (; ;)"), [LexError { offset: 0 }]);
+        assert_eq!(errors("(é)"), [LexError { offset: 1 }]);
+        assert_eq!(errors("\"\\é\""), []);
+    }
+
+    /// The parens and identifiers of real documents are where the wast lexer
+    /// finds them.
+    #[test]
+    fn agrees_with_wast() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../phases");
+        for phase in ["snapshot", "ephemeral", "old/snapshot_0"].iter() {
+            let dir = root.join(phase).join("witx");
+            for file in crate::workspace::witx_files(&dir).unwrap() {
+                let source = std::fs::read_to_string(&file).unwrap();
+                let ours = Lexer::new(&source)
+                    .map(|t| t.unwrap())
+                    .filter(|t| t.kind != TokenKind::Atom && t.kind != TokenKind::String)
+                    .map(|t| (t.text, t.offset))
+                    .collect::<Vec<_>>();
+                let theirs = wast::lexer::Lexer::new(&source)
+                    .map(|t| t.unwrap())
+                    .filter_map(|t| match t {
+                        wast::lexer::Token::LParen(s)
+                        | wast::lexer::Token::RParen(s)
+                        | wast::lexer::Token::Id(s) => Some(s),
+                        _ => None,
+                    })
+                    .map(|s| (s, s.as_ptr() as usize - source.as_ptr() as usize))
+                    .collect::<Vec<_>>();
+                assert_eq!(ours, theirs, "{:?}", file);
+            }
+        }
+    }
+}
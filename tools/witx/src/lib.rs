@@ -1,13 +1,53 @@
+/// Debug logs of the phases of a run, and their timing
+#[macro_use]
+mod phase_log;
 /// Map witx types to core (wasm standard) types
 mod abi;
 /// Types describing a validated witx document
 mod ast;
+/// Incompatibilities between interfaces that have been accepted
+pub mod baseline;
+/// Generate bindings from build scripts
+pub mod build;
+/// Draft witx modules from C headers
+#[cfg(feature = "c-import")]
+pub mod c_import;
+/// Binary cache of validated documents
+#[cfg(feature = "cache")]
+pub mod cache;
+/// Optional features and the implementations supporting them
+pub mod capabilities;
+/// Generate language bindings
+pub mod codegen;
+/// Stable codes of errors, with their explanations
+pub mod codes;
+/// Render errors and other diagnostics for a terminal
+pub mod diagnostic;
+/// Differences between two versions of a document
+pub mod diff;
 /// Render documentation
 mod docs;
+/// Stable content hashes of documents and their items
+pub mod fingerprint;
+/// Entry points of the fuzz targets
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+/// Dependency graphs of types and functions
+pub mod graph;
 /// Interface for filesystem or mock IO
 mod io;
 /// Calculate memory layout of types
 mod layout;
+/// Lex s-expressions without decoding them
+pub mod lexer;
+/// Limits on the size of input, for loading untrusted documents
+pub mod limits;
+/// Lockfiles of the fingerprints of an interface's items
+pub mod lockfile;
+/// Three-way merges of document versions
+pub mod merge;
+/// Near-miss invalid variants of documents, for testing the validator
+pub mod mutate;
 /// Witx syntax parsing from SExprs
 pub mod parser;
 /// Paths to witx documents for various proposal phases
@@ -18,19 +58,44 @@ pub mod polyfill;
 mod render;
 /// Representational equality of types
 mod representation;
+/// Check that documents survive printing and parsing back
+pub mod roundtrip;
+/// Serde support for the ast
+#[cfg(feature = "serde")]
+mod serialize;
+/// Types with their aliases resolved
+mod shape;
+/// Tensor element types and data sizes
+pub mod tensor;
+/// Golden test vectors for inference results
+pub mod test_vector;
+/// Time and memory spent in each phase of a run
+pub mod timings;
 /// Resolve toplevel `use` declarations across files
 mod toplevel;
 /// Validate declarations into ast
 mod validate;
+/// Versions of the witx language
+mod version;
+/// Conversion to and from WIT
+pub mod wit;
+/// Named sets of documents, for queries and comparisons across them
+pub mod workspace;
 
 pub use abi::*;
 pub use ast::*;
-pub use docs::Documentation;
+pub use docs::{DocIndex, DocTemplate, Documentation};
 pub use io::{Filesystem, MockFs, WitxIo};
-pub use layout::{Layout, RecordMemberLayout, SizeAlign};
+pub use layout::{Layout, RecordMemberLayout, SizeAlign, Target};
 pub use render::SExpr;
-pub use representation::{RepEquality, Representable};
+pub use representation::{
+    CaseChange, CaseDiff, Conversion, Divergence, DivergenceReason, PathSegment, RecordMismatch,
+    RepContext, RepEquality, Representable,
+};
+pub use shape::{CaseShape, EnumShape, FieldShape, FlagsShape, RecordShape, Shape, VariantShape};
+pub use toplevel::LoadOptions;
 pub use validate::{DocValidation, ValidationError};
+pub use version::{GrammarFeature, WitxVersion};
 
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -40,6 +105,24 @@ pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Document, WitxError> {
     toplevel::parse_witx(paths)
 }
 
+/// Load a witx document, reading its files through `witxio`
+pub fn load_with<P: AsRef<Path>>(paths: &[P], witxio: &dyn WitxIo) -> Result<Document, WitxError> {
+    toplevel::parse_witx_with(paths, witxio)
+}
+
+/// Load a witx document from the filesystem, looking for each file `use`d
+/// that isn't relative to the root file in the `include` directories, in
+/// order
+pub fn load_including<P: AsRef<Path>, I: AsRef<Path>>(
+    paths: &[P],
+    include: &[I],
+) -> Result<Document, WitxError> {
+    include
+        .iter()
+        .fold(LoadOptions::new(), |options, dir| options.include(dir))
+        .load(paths)
+}
+
 /// Parse a witx document from a str. `(use ...)` directives are not permitted.
 pub fn parse(source: &str) -> Result<Document, WitxError> {
     let mockfs = MockFs::new(&[("-", source)]);
@@ -62,16 +145,19 @@ pub enum WitxError {
     Parse(#[from] wast::Error),
     #[error("Validation error")]
     Validation(#[from] ValidationError),
+    #[error("Limit exceeded")]
+    Limit(#[from] limits::LimitError),
+    #[error("Unknown module `{name}`{}", validate::did_you_mean(suggestions))]
+    UnknownModule {
+        name: String,
+        /// Modules of the document close to `name`, closest first.
+        suggestions: Vec<String>,
+    },
 }
 
 impl WitxError {
     pub fn report_with(&self, witxio: &dyn WitxIo) -> String {
-        use WitxError::*;
-        match self {
-            Io(path, ioerr) => format!("with file {:?}: {}", path, ioerr),
-            Parse(parse) => parse.to_string(),
-            Validation(validation) => validation.report_with(witxio),
-        }
+        diagnostic::Style::plain().witx_error(self, witxio)
     }
     pub fn report(&self) -> String {
         self.report_with(&Filesystem)
@@ -80,18 +166,7 @@ impl WitxError {
 
 impl Location {
     pub fn highlight_source_with(&self, witxio: &dyn WitxIo) -> String {
-        let mut msg = format!("in {:?}:\n", self.path);
-        if let Ok(src_line) = witxio.fget_line(&self.path, self.line) {
-            msg += &format!(
-                "{line_num: >5} | {src_line}\n{blank: >5}    {caret: >column$}",
-                line_num = self.line,
-                src_line = src_line,
-                blank = " ",
-                caret = "^",
-                column = self.column,
-            );
-        }
-        msg
+        diagnostic::Style::plain().location(self, witxio)
     }
     pub fn highlight_source(&self) -> String {
         self.highlight_source_with(&Filesystem)
@@ -0,0 +1,300 @@
+//! Limits on the input a document is loaded from.
+//!
+//! A service loading witx or WIT text it's given can't trust it to be of a
+//! reasonable size. [`Limits`] bound how large its files may be, how deep
+//! its s-expressions may nest, how many files it may `use` and how long its
+//! identifiers may be, so that loading pathological input fails early with a
+//! [`LimitError`] instead of exhausting the service. Loading is unlimited by
+//! default, apart from the [`MAX_NESTING`] the parser always enforces.
+//!
+//! ```
+//! use std::path::Path;
+//! use witx::limits::{LimitError, Limits};
+//! use witx::{LoadOptions, MockFs, WitxError};
+//!
+//! let source = "(typename $t (list (list (list u8))))";
+//! let fs = MockFs::new(&[("-", source)]);
+//! let options = LoadOptions::new().limits(Limits::new().nesting(3));
+//! match options.load_with(&[Path::new("-")], &fs) {
+//!     Err(WitxError::Limit(LimitError::Nesting { limit, .. })) => assert_eq!(limit, 3),
+//!     other => panic!("{:?}", other),
+//! }
+//! ```
+
+use crate::lexer::{Lexer, TokenKind};
+pub use crate::parser::MAX_NESTING;
+use crate::Location;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LimitError {
+    #[error("File {path:?} is {size} bytes, more than the limit of {limit}")]
+    FileSize {
+        path: PathBuf,
+        size: usize,
+        limit: usize,
+    },
+    #[error("Nesting deeper than the limit of {limit} levels")]
+    Nesting { limit: usize, location: Location },
+    #[error("More than the limit of {limit} uses, in {path:?}")]
+    Uses { path: PathBuf, limit: usize },
+    #[error("Identifier `{prefix}...` is {len} bytes, longer than the limit of {limit}")]
+    IdentLength {
+        /// The start of the identifier, which could be long.
+        prefix: String,
+        len: usize,
+        limit: usize,
+        location: Location,
+    },
+}
+
+/// How large the input of a document may be. Each limit is unlimited until
+/// it's set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    file_size: Option<usize>,
+    nesting: Option<usize>,
+    uses: Option<usize>,
+    ident_len: Option<usize>,
+}
+
+/// How much of a long identifier errors quote.
+const PREFIX_LEN: usize = 32;
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits for input from an untrusted source, generous for any real
+    /// interface: files of 1 MiB, nesting 32 deep, 64 `use`s and
+    /// identifiers of 256 bytes.
+    pub fn untrusted() -> Self {
+        Limits::new()
+            .file_size(1 << 20)
+            .nesting(32)
+            .uses(64)
+            .ident_len(256)
+    }
+
+    /// Fail on files of more than `bytes`.
+    pub fn file_size(mut self, bytes: usize) -> Self {
+        self.file_size = Some(bytes);
+        self
+    }
+
+    /// Fail on s-expressions, or WIT types and blocks, nested more than
+    /// `levels` deep. Nesting past [`MAX_NESTING`] always fails.
+    pub fn nesting(mut self, levels: usize) -> Self {
+        self.nesting = Some(levels);
+        self
+    }
+
+    /// Fail once more than `count` `use` declarations have been followed,
+    /// across all of a document's files.
+    pub fn uses(mut self, count: usize) -> Self {
+        self.uses = Some(count);
+        self
+    }
+
+    /// Fail on identifiers of more than `bytes`, not counting a `$` or `%`
+    /// before them.
+    pub fn ident_len(mut self, bytes: usize) -> Self {
+        self.ident_len = Some(bytes);
+        self
+    }
+
+    /// Check that a file of `size` bytes, at `path`, is allowed.
+    pub(crate) fn check_file_size(&self, path: &Path, size: usize) -> Result<(), LimitError> {
+        match self.file_size {
+            Some(limit) if size > limit => Err(LimitError::FileSize {
+                path: path.to_path_buf(),
+                size,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the `count`th `use`, in the file `path`, is allowed.
+    pub(crate) fn check_uses(&self, path: &Path, count: usize) -> Result<(), LimitError> {
+        match self.uses {
+            Some(limit) if count > limit => Err(LimitError::Uses {
+                path: path.to_path_buf(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_nesting(
+        &self,
+        depth: usize,
+        location: impl FnOnce() -> Location,
+    ) -> Result<(), LimitError> {
+        match self.nesting {
+            Some(limit) if depth > limit => Err(LimitError::Nesting {
+                limit,
+                location: location(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_ident(
+        &self,
+        ident: &str,
+        location: impl FnOnce() -> Location,
+    ) -> Result<(), LimitError> {
+        match self.ident_len {
+            Some(limit) if ident.len() > limit => {
+                let mut end = PREFIX_LEN.min(ident.len());
+                while !ident.is_char_boundary(end) {
+                    end -= 1;
+                }
+                Err(LimitError::IdentLength {
+                    prefix: ident[..end].to_string(),
+                    len: ident.len(),
+                    limit,
+                    location: location(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the witx file `path`, with the contents `source`, against every
+    /// limit but the number of uses. Input that doesn't lex is left for the
+    /// parser to report.
+    pub(crate) fn check_witx(&self, path: &Path, source: &str) -> Result<(), LimitError> {
+        self.check_file_size(path, source.len())?;
+        if self.nesting.is_none() && self.ident_len.is_none() {
+            return Ok(());
+        }
+        let location = |offset| {
+            let (line, column) = wast::Span::from_offset(offset).linecol_in(source);
+            Location {
+                path: path.to_path_buf(),
+                line: line + 1,
+                column: column + 1,
+            }
+        };
+        let mut depth = 0usize;
+        for token in Lexer::new(source) {
+            let token = match token {
+                Ok(token) => token,
+                Err(_) => break,
+            };
+            match token.kind {
+                TokenKind::LParen => {
+                    depth += 1;
+                    self.check_nesting(depth, || location(token.offset))?;
+                }
+                TokenKind::RParen => depth = depth.saturating_sub(1),
+                TokenKind::Id => self.check_ident(&token.text[1..], || location(token.offset))?,
+                TokenKind::String | TokenKind::Atom => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{LoadOptions, MockFs, WitxError, WitxIo};
+
+    fn load(limits: Limits, files: &[(&str, &str)]) -> Result<(), LimitError> {
+        let fs = MockFs::new(files);
+        match LoadOptions::new()
+            .limits(limits)
+            .load_with(&[Path::new("/a")], &fs)
+        {
+            Ok(_) => Ok(()),
+            Err(WitxError::Limit(e)) => Err(e),
+            Err(e) => panic!("not a limit error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let source = "(typename $tensor_data (list (list u8)))";
+        assert_eq!(load(Limits::untrusted(), &[("/a", source)]), Ok(()));
+        assert_eq!(
+            load(Limits::new().file_size(8), &[("/a", source)]),
+            Err(LimitError::FileSize {
+                path: PathBuf::from("/a"),
+                size: source.len(),
+                limit: 8
+            })
+        );
+        // A file known to be too large isn't read.
+        struct Large;
+        impl WitxIo for Large {
+            fn fgets(&self, _path: &Path) -> Result<String, WitxError> {
+                panic!("read a file over the size limit")
+            }
+            fn fget_line(&self, _path: &Path, _line_num: usize) -> Result<String, WitxError> {
+                panic!("read a file over the size limit")
+            }
+            fn canonicalize(&self, path: &Path) -> Result<PathBuf, WitxError> {
+                Ok(path.to_path_buf())
+            }
+            fn size(&self, _path: &Path) -> Option<u64> {
+                Some(1 << 20)
+            }
+        }
+        match LoadOptions::new()
+            .limits(Limits::new().file_size(8))
+            .load_with(&[Path::new("/a")], &Large)
+        {
+            Err(WitxError::Limit(LimitError::FileSize { size, .. })) => assert_eq!(size, 1 << 20),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        assert_eq!(
+            load(Limits::new().nesting(2), &[("/a", source)]),
+            Err(LimitError::Nesting {
+                limit: 2,
+                location: Location {
+                    path: PathBuf::from("/a"),
+                    line: 1,
+                    column: 30,
+                },
+            })
+        );
+        match load(Limits::new().ident_len(8), &[("/a", source)]) {
+            Err(LimitError::IdentLength {
+                prefix,
+                len,
+                location,
+                ..
+            }) => {
+                assert_eq!((prefix.as_str(), len), ("tensor_data", 11));
+                assert_eq!((location.line, location.column), (1, 11));
+            }
+            other => panic!("{:?}", other),
+        }
+        let long = format!("(typename ${} u8)", "a".repeat(40));
+        match load(Limits::new().ident_len(8), &[("/a", &long)]) {
+            Err(LimitError::IdentLength { prefix, len, .. }) => {
+                assert_eq!((prefix.len(), len), (PREFIX_LEN, 40));
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let uses = [
+            ("/a", "(use \"b\") (use \"c\")"),
+            ("/b", "(use \"c\")"),
+            ("/c", ""),
+        ];
+        assert_eq!(load(Limits::new().uses(3), &uses), Ok(()));
+        assert_eq!(
+            load(Limits::new().uses(2), &uses),
+            Err(LimitError::Uses {
+                path: PathBuf::from("/a"),
+                limit: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Lockfiles of the fingerprints of an interface's items.
+//!
+//! A lockfile is a checked-in record of the fingerprint of every type,
+//! module and function of a spec. Verifying the spec against it fails on any
+//! semantic change, so that changing the interface takes updating the
+//! lockfile in the same commit, where reviewers see it. Changes to
+//! documentation and formatting aren't semantic, and leave fingerprints as
+//! they were. Each line of a lockfile is an item, as output by
+//! `witx fingerprint`:
+//!
+//! ```text
+//! # Comments and blank lines are ignored.
+//! 5d2f0a9c3b7e1846 type tensor
+//! 0e4b8c2a6f1d3957 module wasi_ephemeral_nn
+//! 7c1a9e3f5b2d0468 func wasi_ephemeral_nn::load
+//! ```
+
+use crate::fingerprint::Fingerprint;
+use crate::Document;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LockfileError {
+    #[error("Invalid lockfile entry on line {line}: {text:?}")]
+    InvalidEntry { line: usize, text: String },
+}
+
+/// An item whose fingerprint differs from the lockfile's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockChange {
+    /// In the document, but not the lockfile.
+    Added { item: String, found: Fingerprint },
+    /// In the lockfile, but not the document.
+    Removed { item: String, locked: Fingerprint },
+    Changed {
+        item: String,
+        locked: Fingerprint,
+        found: Fingerprint,
+    },
+}
+
+impl LockChange {
+    /// The item, labeled like `type <name>` or `func <module>::<name>`.
+    pub fn item(&self) -> &str {
+        match self {
+            LockChange::Added { item, .. }
+            | LockChange::Removed { item, .. }
+            | LockChange::Changed { item, .. } => item,
+        }
+    }
+}
+
+impl fmt::Display for LockChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockChange::Added { item, found } => write!(f, "added {} ({})", item, found),
+            LockChange::Removed { item, locked } => write!(f, "removed {} ({})", item, locked),
+            LockChange::Changed {
+                item,
+                locked,
+                found,
+            } => write!(f, "changed {} ({} -> {})", item, locked, found),
+        }
+    }
+}
+
+/// The fingerprints of a document's items, by label.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lockfile {
+    entries: Vec<(String, Fingerprint)>,
+}
+
+impl Lockfile {
+    /// The lockfile of every type, module and function of `doc`.
+    pub fn new(doc: &Document) -> Self {
+        let entries = doc
+            .fingerprints()
+            .into_iter()
+            .filter(|(item, _)| item != "document")
+            .collect();
+        Lockfile { entries }
+    }
+
+    /// Parses the contents of a lockfile.
+    pub fn parse(source: &str) -> Result<Self, LockfileError> {
+        let mut entries = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match &words[..] {
+                [fp, kind, name] if fp.len() == 16 && ["type", "module", "func"].contains(kind) => {
+                    if let Ok(fp) = u64::from_str_radix(fp, 16) {
+                        entries.push((format!("{} {}", kind, name), Fingerprint(fp)));
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            return Err(LockfileError::InvalidEntry {
+                line: i + 1,
+                text: line.to_string(),
+            });
+        }
+        Ok(Lockfile { entries })
+    }
+
+    pub fn get(&self, item: &str) -> Option<Fingerprint> {
+        self.entries
+            .iter()
+            .find(|(i, _)| i == item)
+            .map(|(_, fp)| *fp)
+    }
+
+    /// The items of `doc` whose fingerprints differ from this lockfile's:
+    /// those of the document first, in order, and then those only the
+    /// lockfile has.
+    pub fn changes(&self, doc: &Document) -> Vec<LockChange> {
+        let current = Lockfile::new(doc);
+        let mut changes = Vec::new();
+        for (item, found) in current.entries.iter() {
+            match self.get(item) {
+                None => changes.push(LockChange::Added {
+                    item: item.clone(),
+                    found: *found,
+                }),
+                Some(locked) if locked != *found => changes.push(LockChange::Changed {
+                    item: item.clone(),
+                    locked,
+                    found: *found,
+                }),
+                Some(_) => {}
+            }
+        }
+        for (item, locked) in self.entries.iter() {
+            if current.get(item).is_none() {
+                changes.push(LockChange::Removed {
+                    item: item.clone(),
+                    locked: *locked,
+                });
+            }
+        }
+        changes
+    }
+
+    /// Checks that `doc` is as this lockfile records it, returning its
+    /// changes otherwise.
+    pub fn verify(&self, doc: &Document) -> Result<(), Vec<LockChange>> {
+        let changes = self.changes(doc);
+        if changes.is_empty() {
+            Ok(())
+        } else {
+            Err(changes)
+        }
+    }
+}
+
+impl fmt::Display for Lockfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (item, fp) in self.entries.iter() {
+            writeln!(f, "{} {}", fp, item)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(source: &str) -> Document {
+        crate::parse(source).unwrap()
+    }
+
+    const SPEC: &str = "(typename $e (enum $a $b))
+         (module $m
+            (@interface func (export \"f\") (param $e $e))
+            (@interface func (export \"g\") (param $x u8)))";
+
+    #[test]
+    fn roundtrip() {
+        let lock = Lockfile::new(&parse(SPEC));
+        let text = lock.to_string();
+        assert_eq!(text.lines().count(), 4);
+        assert!(text.lines().all(|l| !l.ends_with(" document")));
+        let source = format!("# Interface of m.\n\n{}", text);
+        assert_eq!(Lockfile::parse(&source).unwrap(), lock);
+        assert_eq!(
+            Lockfile::parse("0123 type e"),
+            Err(LockfileError::InvalidEntry {
+                line: 1,
+                text: "0123 type e".to_string()
+            })
+        );
+        assert!(Lockfile::parse("0123456789abcdef func").is_err());
+    }
+
+    #[test]
+    fn verify() {
+        let lock = Lockfile::new(&parse(SPEC));
+        let documented = parse(&format!(";;; Docs aren't semantic.\n{}", SPEC));
+        assert_eq!(lock.verify(&documented), Ok(()));
+
+        let changed = parse(
+            "(typename $e (enum $a $b $c))
+             (module $m
+                (@interface func (export \"f\") (param $e $e))
+                (@interface func (export \"h\")))",
+        );
+        let changes = lock.verify(&changed).unwrap_err();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|c| c.to_string().split(" (").next().unwrap().to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "changed type e",
+                "changed module m",
+                "changed func m::f",
+                "added func m::h",
+                "removed func m::g",
+            ]
+        );
+        assert_eq!(Lockfile::new(&changed).verify(&changed), Ok(()));
+    }
+}
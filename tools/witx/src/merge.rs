@@ -0,0 +1,692 @@
+//! Three-way merges of document versions.
+//!
+//! [`merge3`] merges two versions of a document edited independently from a
+//! common base. The witx version, and each type, constant, module and
+//! function, is merged on its own: an item edited in only one version takes
+//! that edit, and an item edited in both must have been edited the same way.
+//! Items are compared by their definitions, documentation included, with
+//! references to named types compared by name only, so a change to a type
+//! isn't also a change to every item using it.
+//!
+//! The merged items are then linked into a document of their own, which
+//! finds the conflicts no single item shows: an item using a type the other
+//! version removed, or types edited to refer to each other. Items are kept
+//! in the order `ours` defines them, with those only `theirs` defines placed
+//! after the item preceding them there.
+
+use crate::{
+    Case, Constant, Definition, Document, Entry, Id, InterfaceFunc, InterfaceFuncParam, Module,
+    ModuleDefinition, ModuleEntry, NamedType, RecordDatatype, RecordMember, SExpr, Type, TypeRef,
+    Variant,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// An item of a document, as merged on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Item {
+    /// The version of the witx language the document declares.
+    Version,
+    Type(Id),
+    Constant {
+        ty: Id,
+        name: Id,
+    },
+    /// A module's documentation, metadata and imports.
+    Module(Id),
+    Func {
+        module: Id,
+        name: Id,
+    },
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Item::Version => f.write_str("the witx version"),
+            Item::Type(name) => write!(f, "type `{}`", name.as_str()),
+            Item::Constant { ty, name } => {
+                write!(f, "constant `{}::{}`", ty.as_str(), name.as_str())
+            }
+            Item::Module(name) => write!(f, "module `{}`", name.as_str()),
+            Item::Func { module, name } => write!(
+                f,
+                "function `{}` of module `{}`",
+                name.as_str(),
+                module.as_str()
+            ),
+        }
+    }
+}
+
+/// How one version edited an item of the base.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Edit::Added => "added",
+            Edit::Removed => "removed",
+            Edit::Changed => "changed",
+        })
+    }
+}
+
+/// A reason the two versions can't be merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// Both versions edited `item`, differently.
+    Edits {
+        item: Item,
+        ours: Edit,
+        theirs: Edit,
+    },
+    /// `item` uses type `name`, which the merge doesn't define.
+    UnknownType { item: Item, name: Id },
+    /// Type `name` of the merge is defined in terms of itself.
+    Cycle { name: Id },
+    /// The merge isn't a valid document, as reported by the validator.
+    Invalid(String),
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Conflict::Edits { item, ours, theirs } => {
+                write!(f, "{}: ours {} it, theirs {} it", item, ours, theirs)
+            }
+            Conflict::UnknownType { item, name } => write!(
+                f,
+                "{} uses type `{}`, which one version removed",
+                item,
+                name.as_str()
+            ),
+            Conflict::Cycle { name } => {
+                write!(f, "type `{}` is defined in terms of itself", name.as_str())
+            }
+            Conflict::Invalid(msg) => write!(f, "the merge is invalid: {}", msg),
+        }
+    }
+}
+
+/// Merge `ours` and `theirs`, two versions of `base`, or report every
+/// conflict between them.
+pub fn merge3(
+    base: &Document,
+    ours: &Document,
+    theirs: &Document,
+) -> Result<Document, Vec<Conflict>> {
+    let mut merge = Merge {
+        conflicts: Vec::new(),
+    };
+    let version = merge
+        .item(
+            Item::Version,
+            Some(&base.version()),
+            Some(&ours.version()),
+            Some(&theirs.version()),
+            |v| v.to_string(),
+        )
+        .unwrap_or_else(|| ours.version());
+
+    let items = order(&top_items(ours), &top_items(theirs));
+    let mut types = Vec::new();
+    let mut constants = Vec::new();
+    let mut modules = Vec::new();
+    for item in items {
+        match &item {
+            Item::Type(name) => {
+                let pick = merge.item(
+                    item.clone(),
+                    base.typename(name).as_ref(),
+                    ours.typename(name).as_ref(),
+                    theirs.typename(name).as_ref(),
+                    |nt| nt.to_sexpr().to_string(),
+                );
+                types.extend(pick);
+            }
+            Item::Constant { ty, name } => {
+                let find = |doc: &Document| {
+                    doc.constants()
+                        .find(|c| c.ty == *ty && c.name == *name)
+                        .cloned()
+                };
+                let pick = merge.item(
+                    item.clone(),
+                    find(base).as_ref(),
+                    find(ours).as_ref(),
+                    find(theirs).as_ref(),
+                    |c: &Constant| format!("{:?}", c),
+                );
+                constants.extend(pick);
+            }
+            Item::Module(name) => {
+                let versions = [base, ours, theirs].map(|doc| doc.module(name));
+                modules.extend(merge.module(name, &versions));
+            }
+            Item::Version | Item::Func { .. } => unreachable!("not a top-level item"),
+        }
+    }
+
+    let mut linker = Linker {
+        sources: types
+            .iter()
+            .map(|nt| (nt.name.clone(), nt.clone()))
+            .collect(),
+        linked: HashMap::new(),
+        in_progress: Vec::new(),
+        definitions: Vec::new(),
+        conflicts: &mut merge.conflicts,
+    };
+    for nt in types.iter() {
+        linker.typename(&nt.name, &Item::Type(nt.name.clone()));
+    }
+    for c in constants {
+        let item = Item::Constant {
+            ty: c.ty.clone(),
+            name: c.name.clone(),
+        };
+        if linker.typename(&c.ty, &item).is_some() {
+            linker.definitions.push(Definition::Constant(c));
+        }
+    }
+    for (module, funcs) in modules {
+        let funcs = funcs
+            .iter()
+            .filter_map(|f| linker.func(&module.name, f))
+            .collect::<Vec<_>>();
+        let module = link_module(&module, funcs);
+        linker.definitions.push(Definition::Module(Rc::new(module)));
+    }
+    let definitions = std::mem::take(&mut linker.definitions);
+    if !merge.conflicts.is_empty() {
+        return Err(merge.conflicts);
+    }
+
+    let mut entries = HashMap::new();
+    for d in definitions.iter() {
+        match d {
+            Definition::Typename(nt) => {
+                entries.insert(nt.name.clone(), Entry::Typename(Rc::downgrade(nt)));
+            }
+            Definition::Module(m) => {
+                entries.insert(m.name.clone(), Entry::Module(Rc::downgrade(m)));
+            }
+            Definition::Constant(_) => {}
+        }
+    }
+    let doc = Document::new(definitions, entries, version);
+    // Each version is valid, but rules spanning items, such as which types
+    // may be results, can still be broken by combining them.
+    match crate::parse(&doc.to_string()) {
+        Ok(_) => Ok(doc),
+        Err(e) => Err(vec![Conflict::Invalid(e.report())]),
+    }
+}
+
+/// The top-level items of `doc`, in the order it defines them.
+fn top_items(doc: &Document) -> Vec<Item> {
+    let mut items = Vec::new();
+    for nt in doc.typenames() {
+        items.push(Item::Type(nt.name.clone()));
+    }
+    for c in doc.constants() {
+        items.push(Item::Constant {
+            ty: c.ty.clone(),
+            name: c.name.clone(),
+        });
+    }
+    for m in doc.modules() {
+        items.push(Item::Module(m.name.clone()));
+    }
+    items
+}
+
+/// The items of `ours` followed, wherever they were inserted, by the items
+/// only `theirs` has. Items removed from either are dropped by merging them.
+fn order<K: PartialEq + Clone>(ours: &[K], theirs: &[K]) -> Vec<K> {
+    let mut out = ours.to_vec();
+    let mut next = 0;
+    for k in theirs {
+        match out.iter().position(|o| o == k) {
+            Some(i) => next = i + 1,
+            None => {
+                out.insert(next, k.clone());
+                next += 1;
+            }
+        }
+    }
+    out
+}
+
+struct Merge {
+    conflicts: Vec<Conflict>,
+}
+
+impl Merge {
+    /// The merged value of `item`, from its value in each version, compared
+    /// by `key`. Conflicting items are recorded and merged as `ours`.
+    fn item<T: Clone>(
+        &mut self,
+        item: Item,
+        base: Option<&T>,
+        ours: Option<&T>,
+        theirs: Option<&T>,
+        key: impl Fn(&T) -> String,
+    ) -> Option<T> {
+        let (b, o, t) = (base.map(&key), ours.map(&key), theirs.map(&key));
+        if o == t || b == t {
+            return ours.cloned();
+        }
+        if b == o {
+            return theirs.cloned();
+        }
+        let edit = |v: Option<&T>| match (base, v) {
+            (None, _) => Edit::Added,
+            (Some(_), None) => Edit::Removed,
+            (Some(_), Some(_)) => Edit::Changed,
+        };
+        self.conflicts.push(Conflict::Edits {
+            item,
+            ours: edit(ours),
+            theirs: edit(theirs),
+        });
+        ours.cloned()
+    }
+
+    /// The merged module `name`, with its merged functions, from the module
+    /// in the base, ours and theirs.
+    fn module(
+        &mut self,
+        name: &Id,
+        versions: &[Option<Rc<Module>>; 3],
+    ) -> Option<(Rc<Module>, Vec<Rc<InterfaceFunc>>)> {
+        let [base, ours, theirs] = versions;
+        let item = Item::Module(name.clone());
+        let header = self.item(
+            item.clone(),
+            base.as_ref(),
+            ours.as_ref(),
+            theirs.as_ref(),
+            module_header,
+        );
+
+        let names = |m: &Option<Rc<Module>>| {
+            m.iter()
+                .flat_map(|m| m.funcs().map(|f| f.name.clone()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        let mut funcs = Vec::new();
+        for func in order(&names(ours), &names(theirs)) {
+            let find = |m: &Option<Rc<Module>>| m.as_ref().and_then(|m| m.func(&func));
+            let pick = self.item(
+                Item::Func {
+                    module: name.clone(),
+                    name: func.clone(),
+                },
+                find(base).as_ref(),
+                find(ours).as_ref(),
+                find(theirs).as_ref(),
+                |f| f.to_sexpr().to_string(),
+            );
+            funcs.extend(pick);
+        }
+
+        match header {
+            Some(module) => Some((module, funcs)),
+            None if funcs.is_empty() => None,
+            None => {
+                // One version removed the module, and the other edited its
+                // functions, which `item` forgives since the base module had
+                // none of those edits.
+                let edit = |v: &Option<Rc<Module>>| match v {
+                    None => Edit::Removed,
+                    Some(_) => Edit::Changed,
+                };
+                self.conflicts.push(Conflict::Edits {
+                    item,
+                    ours: edit(ours),
+                    theirs: edit(theirs),
+                });
+                None
+            }
+        }
+    }
+}
+
+/// A module's documentation, metadata and imports, as compared by `merge3`.
+fn module_header(m: &Rc<Module>) -> String {
+    let mut header = m.meta.iter().map(|m| m.to_sexpr()).collect::<Vec<_>>();
+    header.extend(m.imports().map(|i| i.to_sexpr()));
+    SExpr::docs(&m.docs, SExpr::Vec(header)).to_string()
+}
+
+fn link_module(module: &Module, funcs: Vec<Rc<InterfaceFunc>>) -> Module {
+    let mut definitions = Vec::new();
+    let mut entries = HashMap::new();
+    for i in module.imports() {
+        entries.insert(i.name.clone(), ModuleEntry::Import(Rc::downgrade(&i)));
+        definitions.push(ModuleDefinition::Import(i));
+    }
+    for f in funcs {
+        entries.insert(f.name.clone(), ModuleEntry::Func(Rc::downgrade(&f)));
+        definitions.push(ModuleDefinition::Func(f));
+    }
+    Module::new(
+        module.name.clone(),
+        definitions,
+        entries,
+        module.docs.clone(),
+        module.meta.clone(),
+    )
+}
+
+/// Rebuilds merged items so they refer to the merged types, rather than to
+/// those of the version each was taken from.
+struct Linker<'a> {
+    /// Merged types, as defined by the version they were taken from.
+    sources: HashMap<Id, Rc<NamedType>>,
+    /// Linked types, or `None` for those which couldn't be.
+    linked: HashMap<Id, Option<Rc<NamedType>>>,
+    in_progress: Vec<Id>,
+    /// Linked definitions, with each type after those it uses.
+    definitions: Vec<Definition>,
+    conflicts: &'a mut Vec<Conflict>,
+}
+
+impl Linker<'_> {
+    /// The merged type `name`, used by `user`.
+    fn typename(&mut self, name: &Id, user: &Item) -> Option<Rc<NamedType>> {
+        if let Some(nt) = self.linked.get(name) {
+            return nt.clone();
+        }
+        if self.in_progress.contains(name) {
+            self.conflicts.push(Conflict::Cycle { name: name.clone() });
+            return None;
+        }
+        let source = match self.sources.get(name) {
+            Some(nt) => nt.clone(),
+            None => {
+                self.conflicts.push(Conflict::UnknownType {
+                    item: user.clone(),
+                    name: name.clone(),
+                });
+                return None;
+            }
+        };
+        self.in_progress.push(name.clone());
+        let tref = self.tref(&source.tref, &Item::Type(name.clone()));
+        self.in_progress.pop();
+        // Types which can't be linked are only reported once.
+        let nt = tref.map(|tref| {
+            Rc::new(NamedType {
+                name: name.clone(),
+                tref,
+                docs: source.docs.clone(),
+                meta: source.meta.clone(),
+            })
+        });
+        self.linked.insert(name.clone(), nt.clone());
+        if let Some(nt) = &nt {
+            self.definitions.push(Definition::Typename(nt.clone()));
+        }
+        nt
+    }
+
+    fn tref(&mut self, tref: &TypeRef, user: &Item) -> Option<TypeRef> {
+        match tref {
+            TypeRef::Name(nt) => self.typename(&nt.name, user).map(TypeRef::Name),
+            TypeRef::Value(ty) => self.type_(ty, user).map(|ty| TypeRef::Value(Rc::new(ty))),
+        }
+    }
+
+    /// `ty` with its references linked, or `None` if any of them can't be.
+    /// Every reference is linked either way, to find all the conflicts.
+    fn type_(&mut self, ty: &Type, user: &Item) -> Option<Type> {
+        Some(match ty {
+            Type::Record(r) => {
+                let members = r
+                    .members
+                    .iter()
+                    .map(|m| {
+                        Some(RecordMember {
+                            tref: self.tref(&m.tref, user)?,
+                            ..m.clone()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Type::Record(RecordDatatype {
+                    kind: r.kind.clone(),
+                    members: members.into_iter().collect::<Option<_>>()?,
+                })
+            }
+            Type::Variant(v) => {
+                let cases = v
+                    .cases
+                    .iter()
+                    .map(|c| {
+                        let tref = match &c.tref {
+                            Some(tref) => Some(self.tref(tref, user)?),
+                            None => None,
+                        };
+                        Some(Case { tref, ..c.clone() })
+                    })
+                    .collect::<Vec<_>>();
+                Type::Variant(Variant {
+                    cases: cases.into_iter().collect::<Option<_>>()?,
+                    ..v.clone()
+                })
+            }
+            Type::List(tref) => Type::List(self.tref(tref, user)?),
+            Type::Pointer(tref) => Type::Pointer(self.tref(tref, user)?),
+            Type::ConstPointer(tref) => Type::ConstPointer(self.tref(tref, user)?),
+            Type::Handle(_) | Type::Builtin(_) => ty.clone(),
+        })
+    }
+
+    fn func(&mut self, module: &Id, func: &InterfaceFunc) -> Option<Rc<InterfaceFunc>> {
+        let user = Item::Func {
+            module: module.clone(),
+            name: func.name.clone(),
+        };
+        let mut link = |params: &[InterfaceFuncParam]| {
+            params
+                .iter()
+                .map(|p| {
+                    Some(InterfaceFuncParam {
+                        tref: self.tref(&p.tref, &user)?,
+                        ..p.clone()
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let params = link(&func.params);
+        let results = link(&func.results);
+        Some(Rc::new(InterfaceFunc {
+            params: params.into_iter().collect::<Option<_>>()?,
+            results: results.into_iter().collect::<Option<_>>()?,
+            ..func.clone()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> Result<Document, Vec<Conflict>> {
+        let parse = |s: &str| crate::parse(s).unwrap();
+        merge3(&parse(base), &parse(ours), &parse(theirs))
+    }
+
+    const BASE: &str = "
+        (typename $errno (enum (@witx tag u16) $success $inval))
+        (typename $graph (handle))
+        (module $nn
+            (@interface func (export \"load\")
+                (param $builder (list u8))
+                (result $error (expected $graph (error $errno))))
+            (@interface func (export \"compute\")
+                (param $graph $graph)
+                (result $error (expected (error $errno)))))";
+
+    #[test]
+    fn independent_edits() {
+        let ours = BASE.replace("$success $inval", "$success $inval $busy");
+        let theirs = BASE.replace(
+            "(@interface func (export \"compute\")",
+            "(@interface func (export \"unload\")
+                (param $graph $graph)
+                (result $error (expected (error $errno))))
+             (@interface func (export \"compute\")",
+        );
+        let doc = merge(BASE, &ours, &theirs).unwrap();
+        let errno = doc.typename(&Id::new("errno")).unwrap();
+        assert_eq!(
+            errno.to_sexpr(),
+            crate::parse(&ours)
+                .unwrap()
+                .typename(&Id::new("errno"))
+                .unwrap()
+                .to_sexpr()
+        );
+        let nn = doc.module(&Id::new("nn")).unwrap();
+        let funcs = nn.funcs().map(|f| f.name.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            funcs,
+            [Id::new("load"), Id::new("unload"), Id::new("compute")]
+        );
+        // Functions refer to the merged types, not those of their version.
+        let unload = nn.func(&Id::new("unload")).unwrap();
+        match &**unload.results[0].tref.type_() {
+            Type::Variant(v) => {
+                let err = v.as_expected().unwrap().1.unwrap();
+                assert!(Rc::ptr_eq(
+                    match err {
+                        TypeRef::Name(nt) => nt,
+                        _ => panic!("errno is named"),
+                    },
+                    &errno
+                ));
+            }
+            _ => panic!("result is expected"),
+        }
+
+        // The same edit in both versions is no conflict.
+        assert!(merge(BASE, &ours, &ours).is_ok());
+        assert_eq!(
+            merge(BASE, BASE, BASE).unwrap(),
+            crate::parse(BASE).unwrap()
+        );
+    }
+
+    #[test]
+    fn conflicting_edits() {
+        let ours = BASE.replace("$success $inval", "$success $inval $busy");
+        let theirs = BASE
+            .replace("$success $inval", "$success $inval $timeout")
+            .replace(
+                "(param $graph $graph)",
+                "(param $graph $graph) (param $n u32)",
+            );
+        let errs = merge(BASE, &ours, &theirs).unwrap_err();
+        assert_eq!(
+            errs,
+            [Conflict::Edits {
+                item: Item::Type(Id::new("errno")),
+                ours: Edit::Changed,
+                theirs: Edit::Changed,
+            }]
+        );
+        assert_eq!(
+            errs[0].to_string(),
+            "type `errno`: ours changed it, theirs changed it"
+        );
+
+        let removed = BASE.replace("(export \"compute\")", "(export \"run\")");
+        let errs = merge(BASE, &removed, &theirs.replace("$timeout", "")).unwrap_err();
+        assert_eq!(
+            errs,
+            [Conflict::Edits {
+                item: Item::Func {
+                    module: Id::new("nn"),
+                    name: Id::new("compute"),
+                },
+                ours: Edit::Removed,
+                theirs: Edit::Changed,
+            }]
+        );
+    }
+
+    #[test]
+    fn docs_are_edits() {
+        let ours = BASE.replace("(typename $graph", ";;; A model.\n(typename $graph");
+        let theirs = BASE.replace("(typename $graph", ";;; A graph.\n(typename $graph");
+        let errs = merge(BASE, &ours, &theirs).unwrap_err();
+        assert_eq!(errs.len(), 1);
+        let doc = merge(BASE, &ours, BASE).unwrap();
+        assert_eq!(doc.typename(&Id::new("graph")).unwrap().docs, "A model.\n");
+    }
+
+    #[test]
+    fn unknown_types() {
+        let ours = "
+            (typename $errno (enum (@witx tag u16) $success $inval))
+            (module $nn)";
+        let base = format!("{}\n(typename $graph (handle))", ours);
+        let theirs = format!(
+            "{}\n(module $ext (@interface func (export \"f\") (param $g $graph)))",
+            base
+        );
+        let errs = merge(&base, ours, &theirs).unwrap_err();
+        assert_eq!(
+            errs,
+            [Conflict::UnknownType {
+                item: Item::Func {
+                    module: Id::new("ext"),
+                    name: Id::new("f"),
+                },
+                name: Id::new("graph"),
+            }]
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let base = "(typename $a u32) (typename $b u32)";
+        let ours = "(typename $b u32) (typename $a (list $b))";
+        let theirs = "(typename $a u32) (typename $b (list $a))";
+        let errs = merge(base, ours, theirs).unwrap_err();
+        assert!(matches!(&errs[..], [Conflict::Cycle { .. }]), "{:?}", errs);
+    }
+
+    #[test]
+    fn invalid() {
+        let base = "(typename $size u32)";
+        let ours = "(typename $size (record (field $n u32)))";
+        let theirs = "(typename $size u32)
+            (module $nn
+                (@interface func (export \"f\")
+                    (param $n $size)
+                    (param $data (list u8) (@witx size $n))))";
+        let errs = merge(base, ours, theirs).unwrap_err();
+        match &errs[..] {
+            [Conflict::Invalid(msg)] => assert!(msg.contains("`n` is not an integer"), "{}", msg),
+            _ => panic!("{:?}", errs),
+        }
+    }
+
+    #[test]
+    fn order() {
+        let ours = ["a", "b", "d"];
+        let theirs = ["a", "c", "d", "e"];
+        assert_eq!(super::order(&ours, &theirs), ["a", "c", "b", "d", "e"]);
+        assert_eq!(super::order(&["b"], &["a", "b"]), ["a", "b"]);
+    }
+}
@@ -0,0 +1,425 @@
+//! Near-miss invalid variants of valid documents, for testing the validator.
+//!
+//! Each [`Mutant`] is the source of a document that differs from a valid one
+//! by a single systematic change, along with the error validating it should
+//! report:
+//!
+//! * a type that is referenced is no longer defined,
+//! * a type, a case, field or flag of a type, a function or a parameter is
+//!   defined twice,
+//! * an enum, variant or flags has more cases or flags than its
+//!   representation holds.
+//!
+//! Checking every mutant of a document is rejected as expected ensures the
+//! rules covering its definitions have negative coverage.
+
+use crate::{
+    Case, Document, Id, NamedType, RecordDatatype, RecordKind, SExpr, Type, TypeRef,
+    ValidationError, WitxError, WitxVersion,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+use thiserror::Error;
+
+/// The error a mutant is expected to be rejected with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// `ValidationError::UnknownName` for the name.
+    UnknownName(Id),
+    /// `ValidationError::NameAlreadyExists` for the name.
+    NameAlreadyExists(Id),
+    /// `ValidationError::TooManyCases`.
+    TooManyCases,
+}
+
+impl Expected {
+    pub fn matches(&self, error: &WitxError) -> bool {
+        let error = match error {
+            WitxError::Validation(e) => e,
+            _ => return false,
+        };
+        match (self, error) {
+            (Expected::UnknownName(id), ValidationError::UnknownName { name, .. })
+            | (Expected::NameAlreadyExists(id), ValidationError::NameAlreadyExists { name, .. }) => {
+                id.as_str() == name
+            }
+            (Expected::TooManyCases, ValidationError::TooManyCases { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expected::UnknownName(id) => write!(f, "unknown name `{}`", id.as_str()),
+            Expected::NameAlreadyExists(id) => write!(f, "redefinition of `{}`", id.as_str()),
+            Expected::TooManyCases => f.write_str("too many cases"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MutantError {
+    #[error("{description}: accepted, expected {expected}")]
+    Accepted {
+        description: String,
+        expected: Expected,
+    },
+    #[error("{description}: rejected with \"{error}\", expected {expected}")]
+    WrongError {
+        description: String,
+        expected: Expected,
+        error: String,
+    },
+}
+
+/// An invalid variant of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutant {
+    /// What was changed, like "typename $errno defined twice".
+    pub description: String,
+    pub source: String,
+    pub expected: Expected,
+}
+
+impl Mutant {
+    /// Parses the mutant, checking it is rejected with the expected error.
+    pub fn check(&self) -> Result<(), MutantError> {
+        match crate::parse(&self.source) {
+            Ok(_) => Err(MutantError::Accepted {
+                description: self.description.clone(),
+                expected: self.expected.clone(),
+            }),
+            Err(e) if self.expected.matches(&e) => Ok(()),
+            Err(e) => Err(MutantError::WrongError {
+                description: self.description.clone(),
+                expected: self.expected.clone(),
+                error: match &e {
+                    WitxError::Validation(v) => v.to_string(),
+                    e => e.to_string(),
+                },
+            }),
+        }
+    }
+}
+
+/// Every mutant of `doc`, in the order of the definitions they change.
+pub fn mutants(doc: &Document) -> Vec<Mutant> {
+    let types = doc.typenames().collect::<Vec<_>>();
+    let modules = doc.modules().collect::<Vec<_>>();
+    let defs = types
+        .iter()
+        .map(|nt| nt.to_sexpr())
+        .chain(modules.iter().map(|m| m.to_sexpr()))
+        .collect::<Vec<_>>();
+    let referenced = referenced_types(doc);
+    let mut mutants = Vec::new();
+    let mut push = |description: String, defs: Vec<SExpr>, expected: Expected| {
+        mutants.push(Mutant {
+            description,
+            source: render(doc.version(), &defs),
+            expected,
+        })
+    };
+
+    for (i, nt) in types.iter().enumerate() {
+        let name = nt.name.as_str();
+        if referenced.contains(&nt.name) {
+            let mut removed = defs.clone();
+            removed.remove(i);
+            push(
+                format!("typename ${} removed", name),
+                removed,
+                Expected::UnknownName(nt.name.clone()),
+            );
+        }
+        let mut twice = defs.clone();
+        twice.insert(i + 1, defs[i].clone());
+        push(
+            format!("typename ${} defined twice", name),
+            twice,
+            Expected::NameAlreadyExists(nt.name.clone()),
+        );
+        if let Some((ty, member)) = duplicate_member(nt) {
+            let mut duplicated = defs.clone();
+            duplicated[i] = with_type(nt, ty).to_sexpr();
+            push(
+                format!("${} of typename ${} defined twice", member.as_str(), name),
+                duplicated,
+                Expected::NameAlreadyExists(member),
+            );
+        }
+        if let Some(ty) = oversized(nt) {
+            let mut oversized = defs.clone();
+            oversized[i] = with_type(nt, ty).to_sexpr();
+            push(
+                format!("typename ${} oversized", name),
+                oversized,
+                Expected::TooManyCases,
+            );
+        }
+    }
+
+    for (j, m) in modules.iter().enumerate() {
+        let index = types.len() + j;
+        let funcs = m.funcs().collect::<Vec<_>>();
+        // Functions are the last items of a module.
+        let first_func = items(&defs[index]).len() - funcs.len();
+        if let Some(last) = funcs.last() {
+            let mut module = defs[index].clone();
+            let copy = last.to_sexpr();
+            items_mut(&mut module).push(copy);
+            let mut twice = defs.clone();
+            twice[index] = module;
+            push(
+                format!(
+                    "func {} of module ${} defined twice",
+                    last.name.as_str(),
+                    m.name.as_str()
+                ),
+                twice,
+                Expected::NameAlreadyExists(last.name.clone()),
+            );
+        }
+        for (k, func) in funcs.iter().enumerate() {
+            let param = match func.params.first() {
+                Some(param) => param.clone(),
+                None => continue,
+            };
+            let mut func = (**func).clone();
+            func.params.insert(1, param.clone());
+            let mut module = defs[index].clone();
+            items_mut(&mut module)[first_func + k] = func.to_sexpr();
+            let mut duplicated = defs.clone();
+            duplicated[index] = module;
+            push(
+                format!(
+                    "param ${} of func {} of module ${} defined twice",
+                    param.name.as_str(),
+                    func.name.as_str(),
+                    m.name.as_str()
+                ),
+                duplicated,
+                Expected::NameAlreadyExists(param.name),
+            );
+        }
+    }
+    mutants
+}
+
+fn render(version: WitxVersion, defs: &[SExpr]) -> String {
+    let mut out = String::new();
+    if version != WitxVersion::LATEST {
+        out.push_str(&format!("(@witx version \"{}\")\n", version));
+    }
+    for def in defs {
+        out.push_str(&format!("{}\n", def));
+    }
+    out
+}
+
+fn items(def: &SExpr) -> &[SExpr] {
+    match def {
+        SExpr::Docs(_, def) => items(def),
+        SExpr::Vec(items) => items,
+        _ => &[],
+    }
+}
+
+fn items_mut(def: &mut SExpr) -> &mut Vec<SExpr> {
+    match def {
+        SExpr::Docs(_, def) => items_mut(def),
+        SExpr::Vec(items) => items,
+        _ => unreachable!("definitions are lists"),
+    }
+}
+
+fn with_type(nt: &NamedType, ty: Type) -> NamedType {
+    NamedType {
+        tref: TypeRef::Value(Rc::new(ty)),
+        ..nt.clone()
+    }
+}
+
+/// The names of the types referenced by the definitions of `doc`.
+fn referenced_types(doc: &Document) -> HashSet<Id> {
+    fn visit(tref: &TypeRef, found: &mut HashSet<Id>) {
+        let ty = match tref {
+            TypeRef::Name(nt) => {
+                found.insert(nt.name.clone());
+                return;
+            }
+            TypeRef::Value(ty) => ty,
+        };
+        match &**ty {
+            Type::Record(r) => r.members.iter().for_each(|m| visit(&m.tref, found)),
+            Type::Variant(v) => v
+                .cases
+                .iter()
+                .filter_map(|c| c.tref.as_ref())
+                .for_each(|t| visit(t, found)),
+            Type::List(t) | Type::Pointer(t) | Type::ConstPointer(t) => visit(t, found),
+            Type::Handle(_) | Type::Builtin(_) => {}
+        }
+    }
+    let mut found = HashSet::new();
+    for nt in doc.typenames() {
+        visit(&nt.tref, &mut found);
+    }
+    for m in doc.modules() {
+        for f in m.funcs() {
+            for p in f.params.iter().chain(f.results.iter()) {
+                visit(&p.tref, &mut found);
+            }
+        }
+    }
+    found
+}
+
+/// The type of `nt` with its last case, field or flag repeated, and the
+/// repeated name, if it has named members.
+fn duplicate_member(nt: &NamedType) -> Option<(Type, Id)> {
+    let ty = match &nt.tref {
+        TypeRef::Name(_) => return None,
+        TypeRef::Value(ty) => ty,
+    };
+    match &**ty {
+        Type::Record(r) if !r.is_tuple() => {
+            let last = r.members.last()?.clone();
+            let mut r = r.clone();
+            r.members.push(last.clone());
+            Some((Type::Record(r), last.name))
+        }
+        Type::Variant(v) if !v.is_bool() => {
+            let last = v.cases.last()?.clone();
+            let mut v = v.clone();
+            v.cases.push(last.clone());
+            Some((Type::Variant(v), last.name))
+        }
+        _ => None,
+    }
+}
+
+/// The type of `nt` with a `u8` representation and one case or flag more
+/// than it holds, if it is an enum, variant or flags.
+fn oversized(nt: &NamedType) -> Option<Type> {
+    let ty = match &nt.tref {
+        TypeRef::Name(_) => return None,
+        TypeRef::Value(ty) => ty,
+    };
+    let padding = |existing: &mut dyn Iterator<Item = &Id>, count: usize| {
+        let existing = existing.cloned().collect::<HashSet<_>>();
+        (0..)
+            .map(|i| Id::new(format!("oversized_{}", i)))
+            .filter(|id| !existing.contains(id))
+            .take(count)
+            .collect::<Vec<_>>()
+    };
+    match &**ty {
+        Type::Record(RecordDatatype {
+            kind: RecordKind::Bitflags(_),
+            members,
+        }) => {
+            let template = members.first()?;
+            let count = 9usize.saturating_sub(members.len());
+            let mut members = members.clone();
+            for name in padding(&mut members.iter().map(|m| &m.name), count) {
+                members.push(crate::RecordMember {
+                    name,
+                    docs: String::new(),
+                    ..template.clone()
+                });
+            }
+            Some(Type::Record(RecordDatatype {
+                kind: RecordKind::Bitflags(crate::IntRepr::U8),
+                members,
+            }))
+        }
+        Type::Variant(v) if !v.is_bool() && v.as_expected().is_none() => {
+            let count = 257usize.saturating_sub(v.cases.len());
+            let mut v = v.clone();
+            for name in padding(&mut v.cases.iter().map(|c| &c.name), count) {
+                v.cases.push(Case {
+                    name,
+                    tref: None,
+                    docs: String::new(),
+                });
+            }
+            v.tag_repr = crate::IntRepr::U8;
+            Some(Type::Variant(v))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn wasi_nn() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../phases/ephemeral/witx/wasi_ephemeral_nn.witx");
+        let doc = crate::load(&[path]).unwrap();
+        let mutants = mutants(&doc);
+        let failures = mutants
+            .iter()
+            .filter_map(|m| m.check().err())
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert!(failures.is_empty(), "{:#?}", failures);
+        for expected in [
+            "typename $tensor_type removed",
+            "typename $tensor defined twice",
+            "$data of typename $tensor defined twice",
+            "typename $nn_errno oversized",
+            "func compute of module $wasi_ephemeral_nn defined twice",
+            "param $builder of func load of module $wasi_ephemeral_nn defined twice",
+        ] {
+            assert!(
+                mutants.iter().any(|m| m.description == expected),
+                "{}",
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn mutants_of() {
+        let doc = crate::parse(
+            "(typename $perms (flags (@witx repr u16) $read $write))
+             (typename $unused u32)",
+        )
+        .unwrap();
+        let mutants = mutants(&doc);
+        assert_eq!(
+            mutants
+                .iter()
+                .map(|m| m.description.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "typename $perms defined twice",
+                "$write of typename $perms defined twice",
+                "typename $perms oversized",
+                "typename $unused defined twice",
+            ]
+        );
+        assert!(mutants[2]
+            .source
+            .starts_with("(typename $perms (flags (@witx repr u8) $read $write $oversized_0"));
+        assert!(mutants.iter().all(|m| m.check().is_ok()));
+
+        let valid = Mutant {
+            description: "unchanged".to_string(),
+            source: "(typename $unused u32)".to_string(),
+            expected: Expected::TooManyCases,
+        };
+        assert_eq!(
+            valid.check().unwrap_err().to_string(),
+            "unchanged: accepted, expected too many cases"
+        );
+    }
+}
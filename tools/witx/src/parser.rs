@@ -1,23 +1,28 @@
+use crate::lexer::{Lexer, Token, TokenKind};
 use crate::BuiltinType;
 use wast::parser::{Parse, Parser, Peek, Result};
 
-///! Parser turns s-expressions into unvalidated syntax constructs.
-///! conventions:
-///! `Type::starts_parsing(s-expr) -> bool` is for look-ahead: we use
-///! this predicate to combine parsers for different `Type`s where both
-///! alternatives are accepted.
-///! `Type::parse(sexpr: &SExpr) -> Result<Self, ParseError>` takes a single
-///! s-expression and parses it into a `Self`.
-///! for parsers that take a subset of a vector s-expression, the signature
-///! `Type::parse(sexprs: &[SExpr], location: Location) -> Result<Self, ParseError>`
-///! has an additional `Location` argument, which should point to the parent SExpr::Vec.
-///! This is used for error reporting in case the slice doesn't have the number of elements
-///! expected.
+// Parser turns s-expressions into unvalidated syntax constructs.
+// conventions:
+// `Type::starts_parsing(s-expr) -> bool` is for look-ahead: we use
+// this predicate to combine parsers for different `Type`s where both
+// alternatives are accepted.
+// `Type::parse(sexpr: &SExpr) -> Result<Self, ParseError>` takes a single
+// s-expression and parses it into a `Self`.
+// for parsers that take a subset of a vector s-expression, the signature
+// `Type::parse(sexprs: &[SExpr], location: Location) -> Result<Self, ParseError>`
+// has an additional `Location` argument, which should point to the parent SExpr::Vec.
+// This is used for error reporting in case the slice doesn't have the number of elements
+// expected.
 
 mod kw {
     pub use wast::kw::{export, func, import, memory, module, param, result};
 
+    wast::custom_keyword!(add);
+    wast::custom_keyword!(all);
+    wast::custom_keyword!(any);
     wast::custom_keyword!(case);
+    wast::custom_keyword!(cfg);
     wast::custom_keyword!(char8);
     wast::custom_keyword!(char);
     wast::custom_keyword!(const_pointer);
@@ -27,11 +32,18 @@ mod kw {
     wast::custom_keyword!(empty);
     wast::custom_keyword!(error);
     wast::custom_keyword!(expected);
+    wast::custom_keyword!(feature);
     wast::custom_keyword!(flags);
+    wast::custom_keyword!(generic);
     wast::custom_keyword!(handle);
+    wast::custom_keyword!(len);
+    wast::custom_keyword!(meta);
     wast::custom_keyword!(list);
+    wast::custom_keyword!(mul);
     wast::custom_keyword!(noreturn);
+    wast::custom_keyword!(not);
     wast::custom_keyword!(pointer);
+    wast::custom_keyword!(product);
     wast::custom_keyword!(record);
     wast::custom_keyword!(r#const = "const");
     wast::custom_keyword!(r#enum = "enum");
@@ -42,8 +54,10 @@ mod kw {
     wast::custom_keyword!(s32);
     wast::custom_keyword!(s64);
     wast::custom_keyword!(s8);
+    wast::custom_keyword!(size);
     wast::custom_keyword!(string);
     wast::custom_keyword!(tag);
+    wast::custom_keyword!(tensor_size);
     wast::custom_keyword!(tuple);
     wast::custom_keyword!(typename);
     wast::custom_keyword!(u16);
@@ -52,6 +66,7 @@ mod kw {
     wast::custom_keyword!(u8);
     wast::custom_keyword!(usize);
     wast::custom_keyword!(variant);
+    wast::custom_keyword!(version);
     wast::custom_keyword!(bool_ = "bool");
 }
 
@@ -60,6 +75,42 @@ mod annotation {
     wast::annotation!(witx);
 }
 
+/// How deep s-expressions may nest. Parsing, validating and rendering
+/// recurse into each nested expression, so deeper input could overflow the
+/// stack. Real documents nest a handful of levels.
+pub const MAX_NESTING: usize = 100;
+
+/// Checks that no s-expression in `source` nests deeper than
+/// [`MAX_NESTING`], failing at the first paren that does. Input that doesn't
+/// lex is left for the parser to report.
+pub fn check_nesting(source: &str) -> Result<()> {
+    let mut depth = 0usize;
+    for token in Lexer::new(source) {
+        match token {
+            Ok(Token {
+                kind: TokenKind::LParen,
+                offset,
+                ..
+            }) => {
+                depth += 1;
+                if depth > MAX_NESTING {
+                    return Err(wast::Error::new(
+                        wast::Span::from_offset(offset),
+                        format!("s-expressions nest deeper than {} levels", MAX_NESTING),
+                    ));
+                }
+            }
+            Ok(Token {
+                kind: TokenKind::RParen,
+                ..
+            }) => depth = depth.saturating_sub(1),
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
 impl Parse<'_> for BuiltinType {
     fn parse(parser: Parser<'_>) -> Result<Self> {
         let mut l = parser.lookahead1();
@@ -133,16 +184,11 @@ impl<'a> Parse<'a> for CommentSyntax<'a> {
     fn parse(parser: Parser<'a>) -> Result<CommentSyntax<'a>> {
         let comments = parser.step(|mut cursor| {
             let mut comments = Vec::new();
-            loop {
-                let (comment, c) = match cursor.comment() {
-                    Some(pair) => pair,
-                    None => break,
-                };
+            while let Some((comment, c)) = cursor.comment() {
                 cursor = c;
-                comments.push(if comment.starts_with(";;") {
-                    &comment[2..]
-                } else {
-                    &comment[2..comment.len() - 2]
+                comments.push(match comment.strip_prefix(";;") {
+                    Some(comment) => comment,
+                    None => &comment[2..comment.len() - 2],
                 });
             }
             Ok((comments, cursor))
@@ -160,13 +206,7 @@ impl<'a> CommentSyntax<'a> {
             .comments
             .iter()
             .map(|d| d.trim_end())
-            .filter_map(|d| {
-                if d.starts_with(";") {
-                    Some(&d[1..])
-                } else {
-                    None
-                }
-            })
+            .filter_map(|d| d.strip_prefix(';'))
             .collect::<Vec<_>>();
 
         // Figure out how much leading whitespace we're going to be trimming from
@@ -185,9 +225,9 @@ impl<'a> CommentSyntax<'a> {
             if !doc.is_empty() {
                 ret.push_str(doc[to_trim..].trim_end());
             }
-            ret.push_str("\n");
+            ret.push('\n');
         }
-        return ret;
+        ret
     }
 }
 
@@ -214,6 +254,9 @@ pub struct TopLevelDocument<'a> {
 
 impl<'a> Parse<'a> for TopLevelDocument<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
+        // Without registering `@witx`, a file holding only a pragma would
+        // look empty.
+        let _r1 = parser.register_annotation("witx");
         let mut items = Vec::new();
         while !parser.is_empty() {
             items.push(parser.parse()?);
@@ -226,6 +269,15 @@ impl<'a> Parse<'a> for TopLevelDocument<'a> {
 pub enum TopLevelSyntax<'a> {
     Decl(DeclSyntax<'a>),
     Use(&'a str),
+    Version(VersionSyntax<'a>),
+    Cfg(CfgSyntax<'a, TopLevelSyntax<'a>>),
+}
+
+/// The `(@witx version "MAJOR.MINOR")` pragma.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionSyntax<'a> {
+    pub version: &'a str,
+    pub span: wast::Span,
 }
 
 impl<'a> Parse<'a> for TopLevelSyntax<'a> {
@@ -234,6 +286,25 @@ impl<'a> Parse<'a> for TopLevelSyntax<'a> {
             if p.peek::<kw::r#use>() {
                 p.parse::<kw::r#use>()?;
                 Ok(TopLevelSyntax::Use(p.parse()?))
+            } else if p.peek::<annotation::witx>() && p.peek2::<kw::version>() {
+                p.parse::<annotation::witx>()?;
+                p.parse::<kw::version>()?;
+                let span = p.cur_span();
+                Ok(TopLevelSyntax::Version(VersionSyntax {
+                    version: p.parse()?,
+                    span,
+                }))
+            } else if p.peek::<annotation::witx>() && p.peek2::<kw::cfg>() {
+                p.parse::<annotation::witx>()?;
+                let cfg = CfgSyntax::parse_rest(p)?;
+                if cfg
+                    .items
+                    .iter()
+                    .any(|i| matches!(i.item, TopLevelSyntax::Version(_)))
+                {
+                    return Err(p.error("a version pragma can't be conditional"));
+                }
+                Ok(TopLevelSyntax::Cfg(cfg))
             } else {
                 Ok(TopLevelSyntax::Decl(p.parse()?))
             }
@@ -241,6 +312,87 @@ impl<'a> Parse<'a> for TopLevelSyntax<'a> {
     }
 }
 
+/// A condition on the flags a document is parsed with: a flag, or `not`,
+/// `all` or `any` of conditions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgExprSyntax<'a> {
+    Flag(wast::Id<'a>),
+    Not(Box<CfgExprSyntax<'a>>),
+    All(Vec<CfgExprSyntax<'a>>),
+    Any(Vec<CfgExprSyntax<'a>>),
+}
+
+impl<'a> Parse<'a> for CfgExprSyntax<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        if parser.peek::<wast::Id>() {
+            return Ok(CfgExprSyntax::Flag(parser.parse()?));
+        }
+        parser.parens(|p| {
+            let mut l = p.lookahead1();
+            let rest = || {
+                let mut exprs = Vec::new();
+                while !p.is_empty() {
+                    exprs.push(p.parse()?);
+                }
+                Ok(exprs)
+            };
+            if l.peek::<kw::not>() {
+                p.parse::<kw::not>()?;
+                Ok(CfgExprSyntax::Not(Box::new(p.parse()?)))
+            } else if l.peek::<kw::all>() {
+                p.parse::<kw::all>()?;
+                Ok(CfgExprSyntax::All(rest()?))
+            } else if l.peek::<kw::any>() {
+                p.parse::<kw::any>()?;
+                Ok(CfgExprSyntax::Any(rest()?))
+            } else {
+                Err(l.error())
+            }
+        })
+    }
+}
+
+/// A `(@witx cfg COND ITEM...)` block, whose items are only part of the
+/// document when its condition holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfgSyntax<'a, T> {
+    pub cond: CfgExprSyntax<'a>,
+    pub items: Vec<Documented<'a, T>>,
+    pub span: wast::Span,
+}
+
+impl<'a, T: Parse<'a>> CfgSyntax<'a, T> {
+    /// Parses the block after its `@witx`.
+    fn parse_rest(parser: Parser<'a>) -> Result<Self> {
+        let span = parser.cur_span();
+        parser.parse::<kw::cfg>()?;
+        let cond = parser.parse()?;
+        let mut items = Vec::new();
+        while !parser.is_empty() {
+            items.push(parser.parse()?);
+        }
+        Ok(CfgSyntax { cond, items, span })
+    }
+}
+
+/// Looks ahead for a `(@witx K`, such as the `(@witx cfg` starting a
+/// conditional block, where other annotations may appear too.
+struct WitxStart<K>(std::marker::PhantomData<K>);
+
+impl<K: Peek> Peek for WitxStart<K> {
+    fn peek(cursor: wast::parser::Cursor<'_>) -> bool {
+        cursor
+            .lparen()
+            .and_then(|c| c.annotation())
+            .filter(|(a, _)| *a == "witx")
+            .is_some_and(|(_, c)| K::peek(c))
+    }
+
+    fn display() -> &'static str {
+        K::display()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DeclSyntax<'a> {
     Typename(TypenameSyntax<'a>),
@@ -266,15 +418,76 @@ impl<'a> Parse<'a> for DeclSyntax<'a> {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TypenameSyntax<'a> {
     pub ident: wast::Id<'a>,
+    /// The type parameters of a generic type, declared with
+    /// `(@witx generic $T ...)` before its definition.
+    pub params: Vec<wast::Id<'a>>,
     pub def: TypedefSyntax<'a>,
+    pub meta: Vec<MetaSyntax<'a>>,
 }
 
 impl<'a> Parse<'a> for TypenameSyntax<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
         parser.parse::<kw::typename>()?;
         let ident = parser.parse()?;
+        let mut params = Vec::new();
+        if parser.peek::<WitxStart<kw::generic>>() {
+            parser.parens(|p| {
+                p.parse::<annotation::witx>()?;
+                p.parse::<kw::generic>()?;
+                params.push(p.parse()?);
+                while !p.is_empty() {
+                    params.push(p.parse()?);
+                }
+                Ok(())
+            })?;
+        }
         let def = parser.parse()?;
-        Ok(TypenameSyntax { ident, def })
+        let mut meta = Vec::new();
+        while !parser.is_empty() {
+            meta.push(parser.parens(|p| {
+                p.parse::<annotation::witx>()?;
+                MetaSyntax::parse_rest(p)
+            })?);
+        }
+        Ok(TypenameSyntax {
+            ident,
+            params,
+            def,
+            meta,
+        })
+    }
+}
+
+/// A `(@witx meta key "value")` annotation, without its parens and `@witx`.
+#[derive(Debug, Clone)]
+pub struct MetaSyntax<'a> {
+    pub key: &'a str,
+    pub value: &'a str,
+    pub span: wast::Span,
+}
+
+impl PartialEq for MetaSyntax<'_> {
+    fn eq(&self, other: &MetaSyntax<'_>) -> bool {
+        // skip the `span` field
+        self.key == other.key && self.value == other.value
+    }
+}
+
+impl Eq for MetaSyntax<'_> {}
+
+impl<'a> MetaSyntax<'a> {
+    fn parse_rest(parser: Parser<'a>) -> Result<Self> {
+        parser.parse::<kw::meta>()?;
+        let span = parser.cur_span();
+        let key = parser.step(|c| {
+            c.keyword()
+                .ok_or_else(|| c.error("expected a metadata key"))
+        })?;
+        Ok(MetaSyntax {
+            key,
+            value: parser.parse()?,
+            span,
+        })
     }
 }
 
@@ -293,6 +506,8 @@ pub enum TypedefSyntax<'a> {
     ConstPointer(Box<TypedefSyntax<'a>>),
     Builtin(BuiltinType),
     Ident(wast::Id<'a>),
+    /// A generic type with its arguments, as `($name arg...)`.
+    Instance(wast::Id<'a>, Vec<TypedefSyntax<'a>>),
     String,
     Bool,
 }
@@ -313,7 +528,14 @@ impl<'a> Parse<'a> for TypedefSyntax<'a> {
         } else if l.peek::<wast::LParen>() {
             parser.parens(|parser| {
                 let mut l = parser.lookahead1();
-                if l.peek::<kw::r#enum>() {
+                if l.peek::<wast::Id>() {
+                    let name = parser.parse()?;
+                    let mut args = Vec::new();
+                    while !parser.is_empty() {
+                        args.push(parser.parse()?);
+                    }
+                    Ok(TypedefSyntax::Instance(name, args))
+                } else if l.peek::<kw::r#enum>() {
                     Ok(TypedefSyntax::Enum(parser.parse()?))
                 } else if l.peek::<kw::tuple>() {
                     Ok(TypedefSyntax::Tuple(parser.parse()?))
@@ -367,27 +589,42 @@ impl<'a> Parse<'a> for TypedefSyntax<'a> {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EnumSyntax<'a> {
     pub repr: Option<BuiltinType>,
+    /// Feature whose optional capabilities are the cases of the enum.
+    pub feature: Option<wast::Id<'a>>,
     pub members: Vec<Documented<'a, wast::Id<'a>>>,
 }
 
 impl<'a> Parse<'a> for EnumSyntax<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
         parser.parse::<kw::r#enum>()?;
-        let repr = if parser.peek2::<annotation::witx>() {
-            Some(parser.parens(|p| {
+        let mut repr = None;
+        let mut feature = None;
+        while parser.peek2::<annotation::witx>() {
+            parser.parens(|p| {
                 p.parse::<annotation::witx>()?;
-                p.parse::<kw::tag>()?;
-                p.parse()
-            })?)
-        } else {
-            None
-        };
+                let mut l = p.lookahead1();
+                if l.peek::<kw::tag>() {
+                    p.parse::<kw::tag>()?;
+                    repr = Some(p.parse()?);
+                } else if l.peek::<kw::feature>() {
+                    p.parse::<kw::feature>()?;
+                    feature = Some(p.parse()?);
+                } else {
+                    return Err(l.error());
+                }
+                Ok(())
+            })?;
+        }
         let mut members = Vec::new();
         members.push(parser.parse()?);
         while !parser.is_empty() {
             members.push(parser.parse()?);
         }
-        Ok(EnumSyntax { repr, members })
+        Ok(EnumSyntax {
+            repr,
+            feature,
+            members,
+        })
     }
 }
 
@@ -494,23 +731,156 @@ impl<'a> Parse<'a> for RecordSyntax<'a> {
     }
 }
 
+impl<'a> TypedefSyntax<'a> {
+    /// Add the names of the types this definition refers to, in order, to
+    /// `refs`.
+    pub fn refs(&self, refs: &mut Vec<wast::Id<'a>>) {
+        match self {
+            TypedefSyntax::Ident(id) => refs.push(*id),
+            TypedefSyntax::Instance(id, args) => {
+                refs.push(*id);
+                for a in args {
+                    a.refs(refs);
+                }
+            }
+            TypedefSyntax::Tuple(t) => {
+                for ty in t.types.iter() {
+                    ty.refs(refs);
+                }
+            }
+            TypedefSyntax::Expected(e) => {
+                for ty in e.ok.iter().chain(e.err.iter()) {
+                    ty.refs(refs);
+                }
+            }
+            TypedefSyntax::Record(r) => {
+                for f in r.fields.iter() {
+                    f.item.type_.refs(refs);
+                }
+            }
+            TypedefSyntax::Union(u) => {
+                for ty in u
+                    .tag
+                    .iter()
+                    .map(|t| &**t)
+                    .chain(u.fields.iter().map(|f| &f.item))
+                {
+                    ty.refs(refs);
+                }
+            }
+            TypedefSyntax::Variant(v) => {
+                if let Some(tag) = &v.tag {
+                    tag.refs(refs);
+                }
+                for c in v.cases.iter() {
+                    if let Some(ty) = &c.item.ty {
+                        ty.refs(refs);
+                    }
+                }
+            }
+            TypedefSyntax::List(elem)
+            | TypedefSyntax::Pointer(elem)
+            | TypedefSyntax::ConstPointer(elem) => elem.refs(refs),
+            TypedefSyntax::Enum(_)
+            | TypedefSyntax::Flags(_)
+            | TypedefSyntax::Handle(_)
+            | TypedefSyntax::Builtin(_)
+            | TypedefSyntax::String
+            | TypedefSyntax::Bool => {}
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FieldSyntax<'a> {
     pub name: wast::Id<'a>,
     pub type_: TypedefSyntax<'a>,
+    pub size: Option<SizeExprSyntax<'a>>,
 }
 
 impl<'a> Parse<'a> for FieldSyntax<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
         parser.parens(|p| {
             p.parse::<kw::field>()?;
-            let name = p.parse()?;
-            let type_ = p.parse()?;
-            Ok(FieldSyntax { name, type_ })
+            FieldSyntax::parse_rest(p)
         })
     }
 }
 
+impl<'a> FieldSyntax<'a> {
+    /// The name, type and optional `(@witx size expr)` of a field, param or
+    /// result.
+    fn parse_rest(parser: Parser<'a>) -> Result<Self> {
+        let name = parser.parse()?;
+        let type_ = parser.parse()?;
+        let size = if parser.is_empty() {
+            None
+        } else {
+            Some(parser.parens(|p| {
+                p.parse::<annotation::witx>()?;
+                p.parse::<kw::size>()?;
+                p.parse()
+            })?)
+        };
+        Ok(FieldSyntax { name, type_, size })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SizeExprSyntax<'a> {
+    Const(u64),
+    Value(wast::Id<'a>),
+    Len(wast::Id<'a>),
+    Product(wast::Id<'a>),
+    TensorSize(wast::Id<'a>, wast::Id<'a>),
+    Mul(Vec<SizeExprSyntax<'a>>),
+    Add(Vec<SizeExprSyntax<'a>>),
+}
+
+impl<'a> Parse<'a> for SizeExprSyntax<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        let mut l = parser.lookahead1();
+        if l.peek::<u64>() {
+            Ok(SizeExprSyntax::Const(parser.parse()?))
+        } else if l.peek::<wast::Id>() {
+            Ok(SizeExprSyntax::Value(parser.parse()?))
+        } else if l.peek::<wast::LParen>() {
+            parser.parens(|p| {
+                let mut l = p.lookahead1();
+                if l.peek::<kw::len>() {
+                    p.parse::<kw::len>()?;
+                    Ok(SizeExprSyntax::Len(p.parse()?))
+                } else if l.peek::<kw::product>() {
+                    p.parse::<kw::product>()?;
+                    Ok(SizeExprSyntax::Product(p.parse()?))
+                } else if l.peek::<kw::tensor_size>() {
+                    p.parse::<kw::tensor_size>()?;
+                    Ok(SizeExprSyntax::TensorSize(p.parse()?, p.parse()?))
+                } else if l.peek::<kw::mul>() {
+                    p.parse::<kw::mul>()?;
+                    Ok(SizeExprSyntax::Mul(parse_size_operands(p)?))
+                } else if l.peek::<kw::add>() {
+                    p.parse::<kw::add>()?;
+                    Ok(SizeExprSyntax::Add(parse_size_operands(p)?))
+                } else {
+                    Err(l.error())
+                }
+            })
+        } else {
+            Err(l.error())
+        }
+    }
+}
+
+/// One or more operands of `mul` or `add`.
+fn parse_size_operands<'a>(parser: Parser<'a>) -> Result<Vec<SizeExprSyntax<'a>>> {
+    let mut operands = vec![parser.parse()?];
+    while !parser.is_empty() {
+        operands.push(parser.parse()?);
+    }
+    Ok(operands)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UnionSyntax<'a> {
     pub tag: Option<Box<TypedefSyntax<'a>>>,
@@ -599,17 +969,25 @@ impl<'a> Parse<'a> for HandleSyntax {
 pub struct ModuleSyntax<'a> {
     pub name: wast::Id<'a>,
     pub decls: Vec<Documented<'a, ModuleDeclSyntax<'a>>>,
+    pub meta: Vec<MetaSyntax<'a>>,
 }
 
 impl<'a> Parse<'a> for ModuleSyntax<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
         parser.parse::<kw::module>()?;
         let name = parser.parse()?;
+        let mut meta = Vec::new();
+        while parser.peek2::<annotation::witx>() && !parser.peek::<WitxStart<kw::cfg>>() {
+            meta.push(parser.parens(|p| {
+                p.parse::<annotation::witx>()?;
+                MetaSyntax::parse_rest(p)
+            })?);
+        }
         let mut decls = Vec::new();
         while !parser.is_empty() {
             decls.push(parser.parse()?);
         }
-        Ok(ModuleSyntax { name, decls })
+        Ok(ModuleSyntax { name, decls, meta })
     }
 }
 
@@ -617,6 +995,7 @@ impl<'a> Parse<'a> for ModuleSyntax<'a> {
 pub enum ModuleDeclSyntax<'a> {
     Import(ModuleImportSyntax<'a>),
     Func(InterfaceFuncSyntax<'a>),
+    Cfg(CfgSyntax<'a, ModuleDeclSyntax<'a>>),
 }
 
 impl<'a> Parse<'a> for ModuleDeclSyntax<'a> {
@@ -627,6 +1006,9 @@ impl<'a> Parse<'a> for ModuleDeclSyntax<'a> {
                 Ok(ModuleDeclSyntax::Import(p.parse()?))
             } else if l.peek::<annotation::interface>() {
                 Ok(ModuleDeclSyntax::Func(p.parse()?))
+            } else if l.peek::<annotation::witx>() {
+                p.parse::<annotation::witx>()?;
+                Ok(ModuleDeclSyntax::Cfg(CfgSyntax::parse_rest(p)?))
             } else {
                 Err(l.error())
             }
@@ -681,6 +1063,8 @@ pub struct InterfaceFuncSyntax<'a> {
     pub params: Vec<Documented<'a, FieldSyntax<'a>>>,
     pub results: Vec<Documented<'a, FieldSyntax<'a>>>,
     pub noreturn: bool,
+    pub feature: Option<wast::Id<'a>>,
+    pub meta: Vec<MetaSyntax<'a>>,
 }
 
 impl<'a> Parse<'a> for InterfaceFuncSyntax<'a> {
@@ -696,6 +1080,8 @@ impl<'a> Parse<'a> for InterfaceFuncSyntax<'a> {
         let mut params = Vec::new();
         let mut results = Vec::new();
         let mut noreturn = false;
+        let mut feature = None;
+        let mut meta = Vec::new();
 
         while !parser.is_empty() {
             let func_field = parser.parse::<Documented<InterfaceFuncField>>()?;
@@ -715,6 +1101,10 @@ impl<'a> Parse<'a> for InterfaceFuncSyntax<'a> {
                 InterfaceFuncField::Noreturn => {
                     noreturn = true;
                 }
+                InterfaceFuncField::Feature(id) => {
+                    feature = Some(id);
+                }
+                InterfaceFuncField::Meta(m) => meta.push(m),
             }
         }
 
@@ -724,6 +1114,8 @@ impl<'a> Parse<'a> for InterfaceFuncSyntax<'a> {
             params,
             results,
             noreturn,
+            feature,
+            meta,
         })
     }
 }
@@ -732,6 +1124,8 @@ enum InterfaceFuncField<'a> {
     Param(FieldSyntax<'a>),
     Result(FieldSyntax<'a>),
     Noreturn,
+    Feature(wast::Id<'a>),
+    Meta(MetaSyntax<'a>),
 }
 impl<'a> Parse<'a> for InterfaceFuncField<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
@@ -739,22 +1133,21 @@ impl<'a> Parse<'a> for InterfaceFuncField<'a> {
             let mut l = p.lookahead1();
             if l.peek::<kw::param>() {
                 parser.parse::<kw::param>()?;
-                Ok(InterfaceFuncField::Param(FieldSyntax {
-                    name: parser.parse()?,
-                    type_: parser.parse()?,
-                }))
+                Ok(InterfaceFuncField::Param(FieldSyntax::parse_rest(parser)?))
             } else if l.peek::<kw::result>() {
                 parser.parse::<kw::result>()?;
-                Ok(InterfaceFuncField::Result(FieldSyntax {
-                    name: parser.parse()?,
-                    type_: parser.parse()?,
-                }))
+                Ok(InterfaceFuncField::Result(FieldSyntax::parse_rest(parser)?))
             } else if l.peek::<annotation::witx>() {
                 parser.parse::<annotation::witx>()?;
                 let mut l = parser.lookahead1();
                 if l.peek::<kw::noreturn>() {
                     parser.parse::<kw::noreturn>()?;
                     Ok(InterfaceFuncField::Noreturn)
+                } else if l.peek::<kw::feature>() {
+                    parser.parse::<kw::feature>()?;
+                    Ok(InterfaceFuncField::Feature(parser.parse()?))
+                } else if l.peek::<kw::meta>() {
+                    Ok(InterfaceFuncField::Meta(MetaSyntax::parse_rest(parser)?))
                 } else {
                     Err(l.error())
                 }
@@ -772,6 +1165,8 @@ impl PartialEq for InterfaceFuncSyntax<'_> {
             && self.params == other.params
             && self.results == other.results
             && self.noreturn == other.noreturn
+            && self.feature == other.feature
+            && self.meta == other.meta
     }
 }
 
@@ -4,7 +4,7 @@ use std::path::{Path, PathBuf};
 
 pub fn docs_path(phase_paths: &[PathBuf]) -> PathBuf {
     phase_paths
-        .get(0)
+        .first()
         .expect("at least one path")
         .parent()
         .expect("drop file")
@@ -74,3 +74,135 @@ fn ensure_exists(paths: &[PathBuf]) -> Result<()> {
     }
     Ok(())
 }
+
+/// A phase of the WASI repository: `ephemeral`, `snapshot`, or the old
+/// snapshot `old/<version>`, with the directory holding its witx files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phase {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Phase {
+    /// The witx files of this phase, in name order.
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        Ok(crate::workspace::witx_files(&self.path)?)
+    }
+
+    /// All of the witx files of this phase, as one document.
+    pub fn load(&self) -> Result<crate::Document> {
+        Ok(crate::load(&self.files()?)?)
+    }
+}
+
+/// The phases laid out under `root` by the WASI repository's convention:
+/// `phases/ephemeral/witx`, `phases/snapshot/witx` and
+/// `phases/old/<version>/witx`, in that order.
+pub fn discover(root: &Path) -> Result<Vec<Phase>> {
+    let phases = root.join("phases");
+    let mut found = Vec::new();
+    for name in ["ephemeral", "snapshot"] {
+        let path = phases.join(name).join("witx");
+        if path.is_dir() {
+            found.push(Phase {
+                name: name.to_string(),
+                path,
+            });
+        }
+    }
+    if let Ok(entries) = std::fs::read_dir(phases.join("old")) {
+        let mut old = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path().join("witx");
+            if path.is_dir() {
+                if let Some(version) = entry.file_name().to_str() {
+                    old.push(Phase {
+                        name: format!("old/{}", version),
+                        path,
+                    });
+                }
+            }
+        }
+        old.sort_by(|a, b| a.name.cmp(&b.name));
+        found.extend(old);
+    }
+    Ok(found)
+}
+
+/// The phases of the WASI repository.
+pub fn available() -> Result<Vec<Phase>> {
+    discover(&repo_root()?)
+}
+
+/// The phases of a range `OLD..NEW` of phases under `root`, such as
+/// `snapshot..ephemeral`.
+pub fn range_in(root: &Path, range: &str) -> Result<(Phase, Phase)> {
+    let (old, new) = match range.split_once("..") {
+        Some(names) => names,
+        None => bail!("invalid phase range `{}`: expected OLD..NEW", range),
+    };
+    let phases = discover(root)?;
+    let find = |name: &str| match phases.iter().find(|p| p.name == name) {
+        Some(p) => Ok(p.clone()),
+        None => bail!(
+            "no phase named `{}`: expected one of {}",
+            name,
+            phases
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    Ok((find(old)?, find(new)?))
+}
+
+/// The phases of a range `OLD..NEW` of phases of the WASI repository.
+pub fn range(range: &str) -> Result<(Phase, Phase)> {
+    range_in(&repo_root()?, range)
+}
+
+/// Every phase of the WASI repository, loaded into a workspace by name.
+pub fn workspace() -> Result<crate::workspace::Workspace> {
+    let mut ws = crate::workspace::Workspace::new();
+    for phase in available()? {
+        ws.insert(&phase.name, phase.load()?);
+    }
+    Ok(ws)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn root() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../..")
+    }
+
+    #[test]
+    fn discovers_phases() {
+        let phases = discover(&root()).unwrap();
+        assert_eq!(
+            phases.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(),
+            vec!["ephemeral", "snapshot", "old/snapshot_0"]
+        );
+        let doc = phases[0].load().unwrap();
+        assert!(doc.module(&crate::Id::new("wasi_ephemeral_nn")).is_some());
+    }
+
+    #[test]
+    fn ranges() {
+        let (old, new) = range_in(&root(), "old/snapshot_0..snapshot").unwrap();
+        assert_eq!(
+            (old.name.as_str(), new.name.as_str()),
+            ("old/snapshot_0", "snapshot")
+        );
+        let err = range_in(&root(), "snapshot..preview2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no phase named `preview2`: expected one of ephemeral, snapshot, old/snapshot_0"
+        );
+        assert!(range_in(&root(), "snapshot").is_err());
+    }
+}
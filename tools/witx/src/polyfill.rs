@@ -1,8 +1,11 @@
+use crate::phase_log::Phase;
 use crate::{
-    Document, Id, InterfaceFunc, InterfaceFuncParam, Module, RepEquality, Representable, Type,
-    TypeRef,
+    CaseDiff, Conversion, Divergence, Document, Id, InterfaceFunc, InterfaceFuncParam, Layout,
+    LoadOptions, Module, RepContext, RepEquality, Representable, Target, Type, TypeRef, WitxError,
 };
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
 use std::rc::Rc;
 use thiserror::Error;
 
@@ -12,6 +15,8 @@ pub enum PolyfillError {
     ModuleNotPresent { name: Id },
     #[error("Function not present: {name:?}")]
     FuncNotPresent { module: Id, name: Id },
+    #[error("Failed to load snapshot")]
+    Load(#[from] WitxError),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -25,39 +30,282 @@ impl Polyfill {
         old: &Document,
         module_mapping: &HashMap<String, String>, // Will need a more sophisticated mapping - what about function names, argument names?
     ) -> Result<Self, PolyfillError> {
+        Self::with_context(new, old, module_mapping, &RepContext::default())
+    }
+
+    /// Like `new`, comparing types in the context `cx`.
+    pub fn with_context(
+        new: &Document,
+        old: &Document,
+        module_mapping: &HashMap<String, String>,
+        cx: &RepContext,
+    ) -> Result<Self, PolyfillError> {
+        let _phase = Phase::start(|| "polyfill".to_string());
+        // The same types are usually compared for many functions.
+        let cx = &cx.memoized();
+        // In the order of the new document's modules, rather than the
+        // mapping's, so that the polyfill is the same on every run.
+        let mut mapping = module_mapping.iter().collect::<Vec<_>>();
+        mapping.sort_by_key(|(newname, _)| {
+            let position = new
+                .modules()
+                .position(|m| m.name.as_str() == newname.as_str());
+            (position, newname.as_str())
+        });
         let mut modules = Vec::new();
-        for (newname, oldname) in module_mapping {
+        for (newname, oldname) in mapping {
             let newname = Id::new(newname);
             let oldname = Id::new(oldname);
             let newmod = new
                 .module(&newname)
-                .ok_or_else(|| PolyfillError::ModuleNotPresent { name: newname })?;
+                .ok_or(PolyfillError::ModuleNotPresent { name: newname })?;
             let oldmod = old
                 .module(&oldname)
-                .ok_or_else(|| PolyfillError::ModuleNotPresent { name: oldname })?;
-            modules.push(ModulePolyfill::new(newmod, oldmod)?);
+                .ok_or(PolyfillError::ModuleNotPresent { name: oldname })?;
+            let module = ModulePolyfill::new(newmod, oldmod, cx)?;
+            event!(
+                "polyfilled {} from {}: {} funcs, {} added",
+                module.new.name.as_str(),
+                module.old.name.as_str(),
+                module.funcs.len(),
+                module.added_funcs.len()
+            );
+            modules.push(module);
         }
         Ok(Polyfill { modules })
     }
 
-    pub fn type_polyfills(&self) -> HashSet<TypePolyfill> {
-        self.modules
+    /// The polyfill between every module of the witx files in `old_root`
+    /// and the module of the same name in `new_root`, where files are named
+    /// by the convention `wasi_<phase>_<name>.witx`: so
+    /// `wasi_snapshot_nn.witx` is paired with `wasi_ephemeral_nn.witx`.
+    pub fn from_snapshots(old_root: &Path, new_root: &Path) -> Result<Self, PolyfillError> {
+        Self::from_snapshots_with(
+            old_root,
+            new_root,
+            &LoadOptions::new(),
+            &HashMap::new(),
+            &RepContext::default(),
+        )
+    }
+
+    /// Like `from_snapshots`, loading the files with `options`, with
+    /// `exceptions` mapping the new names of modules to their old names where
+    /// the convention doesn't pair them, comparing types in the context `cx`.
+    pub fn from_snapshots_with(
+        old_root: &Path,
+        new_root: &Path,
+        options: &LoadOptions,
+        exceptions: &HashMap<String, String>,
+        cx: &RepContext,
+    ) -> Result<Self, PolyfillError> {
+        let (old, old_modules) = load_snapshot(old_root, options)?;
+        let (new, new_modules) = load_snapshot(new_root, options)?;
+        let mut mapping = snapshot_mapping(&new_modules, &old_modules);
+        mapping.retain(|n, o| !exceptions.contains_key(n) && !exceptions.values().any(|e| e == o));
+        mapping.extend(exceptions.iter().map(|(n, o)| (n.clone(), o.clone())));
+        Self::with_context(&new, &old, &mapping, cx)
+    }
+
+    /// The conversions of the polyfill's types, without duplicates, in
+    /// the order they're first needed.
+    pub fn type_polyfills(&self) -> Vec<TypePolyfill> {
+        unique(self.modules.iter().flat_map(|m| m.type_polyfills()))
+    }
+
+    /// The polyfill from the old interface of `self` to the new interface
+    /// of `next`, where `next` polyfills the new interface of `self`: so
+    /// v1→v2 and v2→v3 chain to v1→v3.
+    pub fn chain(&self, next: &Polyfill) -> Result<Self, PolyfillError> {
+        let modules = self
+            .modules
             .iter()
-            .map(|m| m.type_polyfills())
-            .flatten()
-            .collect()
+            .map(|m| {
+                let n = next
+                    .modules
+                    .iter()
+                    .find(|n| n.old.name == m.new.name)
+                    .ok_or_else(|| PolyfillError::ModuleNotPresent {
+                        name: m.new.name.clone(),
+                    })?;
+                m.chain(n)
+            })
+            .collect::<Result<_, PolyfillError>>()?;
+        Ok(Polyfill { modules })
+    }
+}
+
+/// The distinct items of `items`, in order.
+fn unique<T: Clone + Eq + std::hash::Hash>(items: impl Iterator<Item = T>) -> Vec<T> {
+    let mut seen = HashSet::new();
+    items.filter(|i| seen.insert(i.clone())).collect()
+}
+
+/// The name a snapshot file documents by convention: the stem of
+/// `wasi_<phase>_<name>.witx` without its phase, or the whole stem otherwise.
+fn snapshot_name(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let name = stem
+        .strip_prefix("wasi_")
+        .and_then(|s| s.split_once('_'))
+        .map(|(_phase, name)| name)
+        .unwrap_or(stem);
+    Some(name.to_string())
+}
+
+/// Load every witx file in `root` with `options` as one document, and the
+/// module defined by each file named by the snapshot convention.
+fn load_snapshot(
+    root: &Path,
+    options: &LoadOptions,
+) -> Result<(Document, HashMap<String, String>), WitxError> {
+    let files = crate::workspace::witx_files(root)?;
+    let mut modules = HashMap::new();
+    for file in files.iter() {
+        let doc = options.load(std::slice::from_ref(file))?;
+        let names = doc.modules().map(|m| m.name.clone()).collect::<Vec<_>>();
+        match (&names[..], snapshot_name(file)) {
+            ([m], Some(name)) => {
+                modules.insert(name, m.as_str().to_string());
+            }
+            ([], _) => {}
+            _ => log::warn!(
+                "{:?} doesn't define exactly one module, so its modules must be mapped by hand",
+                file
+            ),
+        }
+    }
+    Ok((options.load(&files)?, modules))
+}
+
+/// Pair new and old modules documented under the same name.
+fn snapshot_mapping(
+    new: &HashMap<String, String>,
+    old: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    new.iter()
+        .filter_map(|(name, newmod)| Some((newmod.clone(), old.get(name)?.clone())))
+        .collect()
+}
+
+/// A module of a new document paired with a module of an old document by
+/// `infer_module_mapping`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferredModule {
+    pub new: Id,
+    pub old: Id,
+    /// How likely the pairing is to be right, from 0 to 1: the mean of the
+    /// similarity of the modules' names without their snapshot prefixes, and
+    /// the fraction of the old module's functions the new module has.
+    pub confidence: f64,
+}
+
+/// Pairings below this confidence aren't inferred.
+const MIN_CONFIDENCE: f64 = 0.5;
+
+/// Pair modules of `new` with modules of `old`, for when no mapping is given.
+/// The most likely pairings are made first, and each module is paired at
+/// most once, so the result is in order of decreasing confidence.
+pub fn infer_module_mapping(new: &Document, old: &Document) -> Vec<InferredModule> {
+    let mut candidates = Vec::new();
+    for newmod in new.modules() {
+        for oldmod in old.modules() {
+            let name = similarity(
+                module_stem(newmod.name.as_str()),
+                module_stem(oldmod.name.as_str()),
+            );
+            let oldfuncs = oldmod.funcs().count();
+            let overlap = match oldfuncs {
+                0 => 1.0,
+                n => {
+                    oldmod
+                        .funcs()
+                        .filter(|f| newmod.func(&f.name).is_some())
+                        .count() as f64
+                        / n as f64
+                }
+            };
+            candidates.push(InferredModule {
+                new: newmod.name.clone(),
+                old: oldmod.name.clone(),
+                confidence: (name + overlap) / 2.0,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.confidence
+            .partial_cmp(&a.confidence)
+            .unwrap()
+            .then_with(|| a.new.as_str().cmp(b.new.as_str()))
+            .then_with(|| a.old.as_str().cmp(b.old.as_str()))
+    });
+    let mut pairs: Vec<InferredModule> = Vec::new();
+    for c in candidates {
+        if c.confidence >= MIN_CONFIDENCE && !pairs.iter().any(|p| p.new == c.new || p.old == c.old)
+        {
+            pairs.push(c);
+        }
+    }
+    pairs
+}
+
+/// The name of a module without its snapshot prefix: `wasi_`, a phase, and
+/// any version, so `wasi_ephemeral_nn` and `wasi_snapshot_preview1_nn` are
+/// both `nn`, and `wasi_unstable` and `wasi_snapshot_preview1` are both empty.
+fn module_stem(name: &str) -> &str {
+    let rest = match name.strip_prefix("wasi_") {
+        Some(rest) => rest,
+        None => return name,
+    };
+    let (phase, rest) = rest.split_once('_').unwrap_or((rest, ""));
+    if !["ephemeral", "snapshot", "unstable", "old"].contains(&phase) {
+        return name;
+    }
+    let (version, stem) = rest.split_once('_').unwrap_or((rest, ""));
+    let is_version = |prefix: &str| {
+        version
+            .strip_prefix(prefix)
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+    };
+    if is_version("preview") || is_version("v") {
+        stem
+    } else {
+        rest
     }
 }
 
+/// The similarity of two names from 0 to 1, by their edit distance.
+fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (diagonal + (ca != cb) as usize)
+                .min(row[j] + 1)
+                .min(row[j + 1] + 1);
+            diagonal = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    1.0 - row[b.len()] as f64 / a.len().max(b.len()) as f64
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ModulePolyfill {
     pub new: Rc<Module>,
     pub old: Rc<Module>,
     pub funcs: Vec<FuncPolyfill>,
+    /// Functions only the new module has.
+    pub added_funcs: Vec<Rc<InterfaceFunc>>,
 }
 
 impl ModulePolyfill {
-    pub fn new(new: Rc<Module>, old: Rc<Module>) -> Result<Self, PolyfillError> {
+    pub fn new(new: Rc<Module>, old: Rc<Module>, cx: &RepContext) -> Result<Self, PolyfillError> {
         let mut funcs = Vec::new();
         for oldfunc in old.funcs() {
             let newfunc = new
@@ -66,16 +314,56 @@ impl ModulePolyfill {
                     module: new.name.clone(),
                     name: oldfunc.name.clone(),
                 })?;
-            funcs.push(FuncPolyfill::new(newfunc, oldfunc));
+            funcs.push(FuncPolyfill::new(newfunc, oldfunc, cx));
         }
-        Ok(ModulePolyfill { new, old, funcs })
+        let added_funcs = new
+            .funcs()
+            .filter(|f| old.func(&f.name).is_none())
+            .collect();
+        Ok(ModulePolyfill {
+            new,
+            old,
+            funcs,
+            added_funcs,
+        })
     }
-    pub fn type_polyfills(&self) -> HashSet<TypePolyfill> {
-        self.funcs
+    pub fn type_polyfills(&self) -> Vec<TypePolyfill> {
+        unique(self.funcs.iter().flat_map(|f| f.type_polyfills()))
+    }
+
+    fn chain(&self, next: &ModulePolyfill) -> Result<Self, PolyfillError> {
+        let next_funcs = next
+            .funcs
+            .iter()
+            .map(|n| (&n.old.name, n))
+            .collect::<HashMap<_, _>>();
+        let funcs =
+            self.funcs
+                .iter()
+                .map(|f| {
+                    let n = next_funcs.get(&f.new.name).ok_or_else(|| {
+                        PolyfillError::FuncNotPresent {
+                            module: next.new.name.clone(),
+                            name: f.new.name.clone(),
+                        }
+                    })?;
+                    Ok(f.chain(n))
+                })
+                .collect::<Result<_, PolyfillError>>()?;
+        // Functions added by either step, if the last one still has them.
+        let added_funcs = self
+            .added_funcs
             .iter()
-            .map(|f| f.type_polyfills())
-            .flatten()
-            .collect()
+            .filter_map(|a| next_funcs.get(&a.name))
+            .map(|n| n.new.clone())
+            .chain(next.added_funcs.iter().cloned())
+            .collect();
+        Ok(ModulePolyfill {
+            new: next.new.clone(),
+            old: self.old.clone(),
+            funcs,
+            added_funcs,
+        })
     }
 }
 
@@ -90,27 +378,31 @@ pub struct FuncPolyfill {
 }
 
 impl FuncPolyfill {
-    pub fn new(new: Rc<InterfaceFunc>, old: Rc<InterfaceFunc>) -> FuncPolyfill {
+    pub fn new(new: Rc<InterfaceFunc>, old: Rc<InterfaceFunc>, cx: &RepContext) -> FuncPolyfill {
         let mut mapped_params = Vec::new();
         let mut unknown_params = Vec::new();
 
         // Old function is called. Need to map each of its parameters to the new function:
-        for old_param in old.params.iter() {
-            if let Some(new_param) = new.params.iter().find(|p| p.name == old_param.name) {
-                mapped_params.push(ParamPolyfill::param(new_param.clone(), old_param.clone()))
+        for (i, old_param) in old.params.iter().enumerate() {
+            if let Some(j) = new.params.iter().position(|p| p.name == old_param.name) {
+                mapped_params.push(ParamPolyfill::param(
+                    FuncParam::param(&new, j),
+                    FuncParam::param(&old, i),
+                    cx,
+                ))
             } else {
-                unknown_params.push(ParamUnknown::Old(old_param.clone()));
+                unknown_params.push(ParamUnknown::Old(FuncParam::param(&old, i)));
             }
         }
         // Are any new params not covered by the old params?
         // This search is O(n^2), but n ought to be small.
-        for new_param in new.params.iter() {
+        for (j, new_param) in new.params.iter().enumerate() {
             if mapped_params
                 .iter()
                 .find(|m| m.new.name == new_param.name)
                 .is_none()
             {
-                unknown_params.push(ParamUnknown::New(new_param.clone()));
+                unknown_params.push(ParamUnknown::New(FuncParam::param(&new, j)));
             }
         }
 
@@ -118,25 +410,26 @@ impl FuncPolyfill {
         let mut unknown_results = Vec::new();
 
         // New function has returned. Need to map each of its results to the old function:
-        for new_result in new.results.iter() {
-            if let Some(old_result) = old.results.iter().find(|p| p.name == new_result.name) {
+        for (j, new_result) in new.results.iter().enumerate() {
+            if let Some(i) = old.results.iter().position(|p| p.name == new_result.name) {
                 mapped_results.push(ParamPolyfill::result(
-                    new_result.clone(),
-                    old_result.clone(),
+                    FuncParam::result(&new, j),
+                    FuncParam::result(&old, i),
+                    cx,
                 ))
             } else {
-                unknown_results.push(ParamUnknown::New(new_result.clone()));
+                unknown_results.push(ParamUnknown::New(FuncParam::result(&new, j)));
             }
         }
 
         // Are any old results not covered by the new results?
-        for old_result in old.results.iter() {
+        for (i, old_result) in old.results.iter().enumerate() {
             if mapped_results
                 .iter()
                 .find(|m| m.old.name == old_result.name)
                 .is_none()
             {
-                unknown_results.push(ParamUnknown::Old(old_result.clone()));
+                unknown_results.push(ParamUnknown::Old(FuncParam::result(&old, i)));
             }
         }
 
@@ -158,20 +451,157 @@ impl FuncPolyfill {
             && self.unknown_results.is_empty()
     }
 
-    pub fn type_polyfills(&self) -> HashSet<TypePolyfill> {
-        self.mapped_params
-            .iter()
-            .map(|p| p.type_polyfill.clone())
-            .chain(self.mapped_results.iter().map(|p| p.type_polyfill.clone()))
-            .collect()
+    pub fn type_polyfills(&self) -> Vec<TypePolyfill> {
+        unique(
+            self.mapped_params
+                .iter()
+                .chain(self.mapped_results.iter())
+                .map(|p| p.type_polyfill.clone()),
+        )
+    }
+
+    fn chain(&self, next: &FuncPolyfill) -> FuncPolyfill {
+        let (mapped_params, unknown_params) = chain_params(
+            &self.mapped_params,
+            &self.unknown_params,
+            &next.mapped_params,
+            &next.unknown_params,
+        );
+        let (mapped_results, unknown_results) = chain_params(
+            &self.mapped_results,
+            &self.unknown_results,
+            &next.mapped_results,
+            &next.unknown_results,
+        );
+        FuncPolyfill {
+            new: next.new.clone(),
+            old: self.old.clone(),
+            mapped_params,
+            unknown_params,
+            mapped_results,
+            unknown_results,
+        }
+    }
+}
+
+/// Chains the params (or results) of a function across two polyfills,
+/// pairing them by their names in the interface in between. A param new
+/// to the middle interface stays new if the last one keeps it, and one
+/// the last interface drops is unknown if it came from the first.
+fn chain_params(
+    first: &[ParamPolyfill],
+    first_unknown: &[ParamUnknown],
+    next: &[ParamPolyfill],
+    next_unknown: &[ParamUnknown],
+) -> (Vec<ParamPolyfill>, Vec<ParamUnknown>) {
+    let mut mapped = Vec::new();
+    let mut unknown = Vec::new();
+    for p in first {
+        match next.iter().find(|n| n.old.name == p.new.name) {
+            Some(n) => mapped.push(p.chain(n)),
+            None => unknown.push(ParamUnknown::Old(p.old.clone())),
+        }
+    }
+    for u in first_unknown {
+        match u {
+            ParamUnknown::Old(_) => unknown.push(u.clone()),
+            ParamUnknown::New(p) => {
+                if let Some(n) = next.iter().find(|n| n.old.name == p.name) {
+                    unknown.push(ParamUnknown::New(n.new.clone()));
+                }
+            }
+        }
+    }
+    for u in next_unknown {
+        if let ParamUnknown::New(_) = u {
+            unknown.push(u.clone());
+        }
+    }
+    (mapped, unknown)
+}
+
+/// A param or result of a function, referred to by its index in the
+/// function rather than cloned out of it. It derefs to the param, and
+/// compares and hashes like it.
+#[derive(Clone)]
+pub struct FuncParam {
+    func: Rc<InterfaceFunc>,
+    result: bool,
+    index: usize,
+}
+
+impl FuncParam {
+    /// The `index`th param of `func`, which must exist.
+    pub(crate) fn param(func: &Rc<InterfaceFunc>, index: usize) -> Self {
+        assert!(index < func.params.len());
+        FuncParam {
+            func: func.clone(),
+            result: false,
+            index,
+        }
+    }
+
+    /// The `index`th result of `func`, which must exist.
+    pub(crate) fn result(func: &Rc<InterfaceFunc>, index: usize) -> Self {
+        assert!(index < func.results.len());
+        FuncParam {
+            func: func.clone(),
+            result: true,
+            index,
+        }
+    }
+
+    /// The function this is a param or result of.
+    pub fn func(&self) -> &Rc<InterfaceFunc> {
+        &self.func
+    }
+}
+
+impl std::ops::Deref for FuncParam {
+    type Target = InterfaceFuncParam;
+    fn deref(&self) -> &InterfaceFuncParam {
+        if self.result {
+            &self.func.results[self.index]
+        } else {
+            &self.func.params[self.index]
+        }
+    }
+}
+
+impl PartialEq for FuncParam {
+    fn eq(&self, other: &FuncParam) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for FuncParam {}
+
+impl std::hash::Hash for FuncParam {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl fmt::Debug for FuncParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ParamPolyfill {
-    pub new: InterfaceFuncParam,
-    pub old: InterfaceFuncParam,
+    pub new: FuncParam,
+    pub old: FuncParam,
     pub type_polyfill: TypePolyfill,
+    /// Compatibility of the conversion, in the context it was compared in.
+    pub compat: RepEquality,
+    /// The cases that differ, when the param is a variant.
+    pub cases: Vec<CaseDiff>,
+    /// Where the types diverge, when the conversion is incompatible.
+    pub divergence: Option<Divergence>,
+    /// The types whose size depends on the target, when the interfaces are
+    /// compared for different targets.
+    pub sizes: Vec<SizeChange>,
 }
 
 impl ParamPolyfill {
@@ -187,41 +617,299 @@ impl ParamPolyfill {
         }
     }
 
-    pub fn param(new: InterfaceFuncParam, old: InterfaceFuncParam) -> Self {
+    /// The compatibility of `type_polyfill` for the param `old`, including
+    /// the addresses of the pointers or lists `common_denominator` removed.
+    fn compat(
+        old: &InterfaceFuncParam,
+        type_polyfill: &TypePolyfill,
+        cx: &RepContext,
+    ) -> RepEquality {
+        let compat = type_polyfill.repeq_in(cx);
+        match &old.tref {
+            TypeRef::Value(ty) => match &**ty {
+                Type::List(_) | Type::Pointer(_) | Type::ConstPointer(_) => {
+                    cx.pointer_width().then(&compat)
+                }
+                _ => compat,
+            },
+            _ => compat,
+        }
+    }
+
+    pub fn param(new: FuncParam, old: FuncParam, cx: &RepContext) -> Self {
         let (told, tnew) = Self::common_denominator(old.tref.clone(), new.tref.clone());
+        let sizes = SizeChange::between(&old.tref, &new.tref, &told, &tnew, cx.compared_targets());
         // Call new param type with old param:
         let type_polyfill = TypePolyfill::OldToNew(told, tnew);
         ParamPolyfill {
+            compat: Self::compat(&old, &type_polyfill, cx),
             new,
             old,
+            cases: type_polyfill.case_diffs_in(cx),
+            divergence: type_polyfill.divergence_in(cx),
+            sizes,
             type_polyfill,
         }
     }
 
-    pub fn result(new: InterfaceFuncParam, old: InterfaceFuncParam) -> Self {
+    pub fn result(new: FuncParam, old: FuncParam, cx: &RepContext) -> Self {
         let (told, tnew) = Self::common_denominator(old.tref.clone(), new.tref.clone());
+        let sizes = SizeChange::between(&old.tref, &new.tref, &told, &tnew, cx.compared_targets());
+        // Results are converted from the new target to the old one.
+        let cx = &cx.reversed();
         // Return old result type from new result:
         let type_polyfill = TypePolyfill::NewToOld(tnew, told);
         ParamPolyfill {
+            compat: Self::compat(&old, &type_polyfill, cx),
             new,
             old,
+            cases: type_polyfill.case_diffs_in(cx),
+            divergence: type_polyfill.divergence_in(cx),
+            sizes,
             type_polyfill,
         }
     }
 
     pub fn full_compat(&self) -> bool {
-        self.new.name == self.old.name && self.repeq() == RepEquality::Eq
+        self.new.name == self.old.name
+            && self.repeq() == RepEquality::Eq
+            && self.handle_rename().is_none()
+            && self.type_rename().is_none()
+    }
+
+    /// The old and new names of the param's type, when it is a named type
+    /// other than a handle that was renamed.
+    pub fn type_rename(&self) -> Option<(&Id, &Id)> {
+        match (&self.old.tref, &self.new.tref) {
+            (TypeRef::Name(old), TypeRef::Name(new))
+                if old.name != new.name && old.resource().is_none() =>
+            {
+                Some((&old.name, &new.name))
+            }
+            _ => None,
+        }
+    }
+
+    /// The old and new resource, when the param is a handle to a renamed
+    /// resource.
+    pub fn handle_rename(&self) -> Option<(&Id, &Id)> {
+        let (old, new) = match &self.type_polyfill {
+            TypePolyfill::OldToNew(old, new) | TypePolyfill::NewToOld(new, old) => (old, new),
+        };
+        match (old, new) {
+            (TypeRef::Name(old), TypeRef::Name(new)) => match (old.resource(), new.resource()) {
+                (Some(old), Some(new)) if old != new => Some((old, new)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// How the param changed between a `pointer` and a `const_pointer`, if
+    /// it did.
+    pub fn pointer_direction(&self) -> Option<PointerDirection> {
+        match (&**self.old.tref.type_(), &**self.new.tref.type_()) {
+            (Type::Pointer(_), Type::ConstPointer(_)) => Some(PointerDirection::Downgraded),
+            (Type::ConstPointer(_), Type::Pointer(_)) => Some(PointerDirection::Upgraded),
+            _ => None,
+        }
     }
 
     pub fn repeq(&self) -> RepEquality {
-        self.type_polyfill.repeq()
+        self.compat
     }
+
+    fn chain(&self, next: &ParamPolyfill) -> ParamPolyfill {
+        // Params are converted through `self` first, and results through
+        // `next` first.
+        let (type_polyfill, compat) = match (&self.type_polyfill, &next.type_polyfill) {
+            (TypePolyfill::OldToNew(old, _), TypePolyfill::OldToNew(_, new)) => (
+                TypePolyfill::OldToNew(old.clone(), new.clone()),
+                self.compat.then(&next.compat),
+            ),
+            (TypePolyfill::NewToOld(_, old), TypePolyfill::NewToOld(new, _)) => (
+                TypePolyfill::NewToOld(new.clone(), old.clone()),
+                next.compat.then(&self.compat),
+            ),
+            _ => unreachable!("params chain with params, and results with results"),
+        };
+        ParamPolyfill {
+            new: next.new.clone(),
+            old: self.old.clone(),
+            type_polyfill,
+            compat,
+            cases: self
+                .cases
+                .iter()
+                .chain(next.cases.iter())
+                .cloned()
+                .collect(),
+            divergence: self.divergence.clone().or_else(|| next.divergence.clone()),
+            sizes: self
+                .sizes
+                .iter()
+                .chain(next.sizes.iter())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Notes on what changed about the param, for display.
+    pub fn report(&self) -> Vec<String> {
+        let mut notes = Vec::new();
+        if let Some(divergence) = &self.divergence {
+            notes.push(divergence.to_string());
+        }
+        for case in self.cases.iter() {
+            notes.push(format!("case `{}` {}", case.name.as_str(), case.change));
+        }
+        for size in self.sizes.iter() {
+            notes.push(size.to_string());
+        }
+        match self.pointer_direction() {
+            Some(PointerDirection::Downgraded) => {
+                notes.push("pointer downgraded to `const_pointer`".to_string())
+            }
+            Some(PointerDirection::Upgraded) => {
+                notes.push("`const_pointer` upgraded to pointer".to_string())
+            }
+            None => {}
+        }
+        if let Some((old, new)) = self.type_rename() {
+            notes.push(format!(
+                "type `{}` renamed to `{}`",
+                old.as_str(),
+                new.as_str()
+            ));
+        }
+        if let Some((old, new)) = self.handle_rename() {
+            notes.push(format!(
+                "handle `{}` renamed to `{}`",
+                old.as_str(),
+                new.as_str()
+            ));
+        }
+        notes
+    }
+}
+
+/// A type whose size differs between the targets of the old and new
+/// interfaces, because it's pointer-sized or built from pointer-sized types.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SizeChange {
+    pub old_type: String,
+    pub old_target: Target,
+    pub old_size: usize,
+    pub new_type: String,
+    pub new_target: Target,
+    pub new_size: usize,
+}
+
+impl SizeChange {
+    /// The changes in size of a param of type `old` becoming `new`, and of
+    /// the types `told` and `tnew` they point to or list, if those differ.
+    fn between(
+        old: &TypeRef,
+        new: &TypeRef,
+        told: &TypeRef,
+        tnew: &TypeRef,
+        targets: Option<(Target, Target)>,
+    ) -> Vec<SizeChange> {
+        let (old_target, new_target) = match targets {
+            Some((o, n)) if o != n => (o, n),
+            _ => return Vec::new(),
+        };
+        let change = |old: &TypeRef, new: &TypeRef| {
+            let size = |t: &TypeRef, target| t.mem_size_align_for(target).size;
+            let sensitive = |t: &TypeRef| size(t, old_target) != size(t, new_target);
+            if sensitive(old) || sensitive(new) {
+                Some(SizeChange {
+                    old_type: old.type_name(),
+                    old_target,
+                    old_size: size(old, old_target),
+                    new_type: new.type_name(),
+                    new_target,
+                    new_size: size(new, new_target),
+                })
+            } else {
+                None
+            }
+        };
+        let mut changes = change(old, new).into_iter().collect::<Vec<_>>();
+        if old != told || new != tnew {
+            changes.extend(change(told, tnew));
+        }
+        changes
+    }
+}
+
+impl fmt::Display for SizeChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.old_type == self.new_type {
+            write!(
+                f,
+                "`{}` is {} bytes on {} and {} bytes on {}",
+                self.old_type, self.old_size, self.old_target, self.new_size, self.new_target
+            )
+        } else {
+            write!(
+                f,
+                "`{}` is {} bytes on {} and `{}` is {} bytes on {}",
+                self.old_type,
+                self.old_size,
+                self.old_target,
+                self.new_type,
+                self.new_size,
+                self.new_target
+            )
+        }
+    }
+}
+
+/// The values of an enum converted to another enum, pairing their cases by
+/// name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct EnumRemap {
+    /// Each case of the enum converted from that the other has, in order.
+    pub values: Vec<EnumValue>,
+    /// The cases the other enum doesn't have, whose values can't be
+    /// converted. Their `to` is meaningless.
+    pub unmappable: Vec<EnumValue>,
+}
+
+/// A case of an enum with its values before and after conversion.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EnumValue {
+    pub name: Id,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl EnumRemap {
+    /// The value `from` is converted to, if it can be.
+    pub fn get(&self, from: usize) -> Option<usize> {
+        self.values.iter().find(|v| v.from == from).map(|v| v.to)
+    }
+
+    /// Whether every value converts to itself.
+    pub fn is_identity(&self) -> bool {
+        self.unmappable.is_empty() && self.values.iter().all(|v| v.from == v.to)
+    }
+}
+
+/// A change in whether the memory behind a pointer may be written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PointerDirection {
+    /// A `pointer` became a `const_pointer`.
+    Downgraded,
+    /// A `const_pointer` became a `pointer`.
+    Upgraded,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ParamUnknown {
-    Old(InterfaceFuncParam),
-    New(InterfaceFuncParam),
+    Old(FuncParam),
+    New(FuncParam),
 }
 
 impl ParamUnknown {
@@ -233,8 +921,8 @@ impl ParamUnknown {
     }
     pub fn param(&self) -> &InterfaceFuncParam {
         match self {
-            ParamUnknown::Old(p) => &p,
-            ParamUnknown::New(p) => &p,
+            ParamUnknown::Old(p) => p,
+            ParamUnknown::New(p) => p,
         }
     }
 }
@@ -246,10 +934,643 @@ pub enum TypePolyfill {
 }
 
 impl TypePolyfill {
-    pub fn repeq(&self) -> RepEquality {
+    /// The types values are converted from and to.
+    fn endpoints(&self) -> (&TypeRef, &TypeRef) {
         match self {
-            TypePolyfill::NewToOld(new, old) => old.type_().representable(&new.type_()),
-            TypePolyfill::OldToNew(old, new) => new.type_().representable(&old.type_()),
+            TypePolyfill::NewToOld(new, old) => (new, old),
+            TypePolyfill::OldToNew(old, new) => (old, new),
         }
     }
+
+    pub fn repeq(&self) -> RepEquality {
+        self.repeq_in(&RepContext::default())
+    }
+
+    pub fn repeq_in(&self, cx: &RepContext) -> RepEquality {
+        let (from, to) = self.endpoints();
+        from.representable_in(to, cx)
+    }
+
+    /// Where the types diverge, when the conversion is incompatible.
+    pub fn divergence_in(&self, cx: &RepContext) -> Option<Divergence> {
+        let (from, to) = self.endpoints();
+        from.divergence_in(to, cx)
+    }
+
+    /// The cases that differ, when both types are variants.
+    pub fn case_diffs_in(&self, cx: &RepContext) -> Vec<CaseDiff> {
+        let (from, to) = self.endpoints();
+        match (&**from.type_(), &**to.type_()) {
+            (Type::Variant(from), Type::Variant(to)) => from.case_diffs_in(to, cx),
+            _ => Vec::new(),
+        }
+    }
+
+    /// How values are converted, case by case, when both types are enums.
+    pub fn enum_remap(&self) -> Option<EnumRemap> {
+        let (from, to) = self.endpoints();
+        match (&**from.type_(), &**to.type_()) {
+            (Type::Variant(from), Type::Variant(to)) if from.is_enum() && to.is_enum() => {
+                let mut remap = EnumRemap::default();
+                for (i, case) in from.cases.iter().enumerate() {
+                    match to.cases.iter().position(|c| c.name == case.name) {
+                        Some(j) => remap.values.push(EnumValue {
+                            name: case.name.clone(),
+                            from: i,
+                            to: j,
+                        }),
+                        None => remap.unmappable.push(EnumValue {
+                            name: case.name.clone(),
+                            from: i,
+                            to: i,
+                        }),
+                    }
+                }
+                Some(remap)
+            }
+            _ => None,
+        }
+    }
+
+    /// The conversion an adapter performs, when a number is widened.
+    pub fn conversion(&self) -> Option<Conversion> {
+        let (from, to) = self.endpoints();
+        match (&**from.type_(), &**to.type_()) {
+            (Type::Builtin(from), Type::Builtin(to)) => from.widening(to),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Documentation;
+
+    /// The mapping of the module `m` to itself.
+    fn same_module() -> HashMap<String, String> {
+        let mut mapping = HashMap::new();
+        mapping.insert("m".to_string(), "m".to_string());
+        mapping
+    }
+
+    #[test]
+    fn snapshot_names() {
+        let name = |p: &str| snapshot_name(Path::new(p)).unwrap();
+        assert_eq!(name("/w/wasi_ephemeral_nn.witx"), "nn");
+        assert_eq!(name("wasi_snapshot_nn_extra.witx"), "nn_extra");
+        assert_eq!(name("wasi_unstable.witx"), "wasi_unstable");
+        assert_eq!(name("typenames.witx"), "typenames");
+    }
+
+    #[test]
+    fn module_stems() {
+        assert_eq!(module_stem("wasi_ephemeral_nn"), "nn");
+        assert_eq!(module_stem("wasi_snapshot_preview1_nn"), "nn");
+        assert_eq!(module_stem("wasi_snapshot_preview1"), "");
+        assert_eq!(module_stem("wasi_unstable"), "");
+        assert_eq!(module_stem("wasi_nn"), "wasi_nn");
+        assert_eq!(module_stem("nn"), "nn");
+        assert_eq!(similarity("nn", "nn"), 1.0);
+        assert_eq!(similarity("fd", "fs"), 0.5);
+        assert_eq!(similarity("", "nn"), 0.0);
+    }
+
+    #[test]
+    fn inferred_mapping() {
+        let old = crate::parse(
+            "(module $wasi_snapshot_preview1_nn
+                (@interface func (export \"load\"))
+                (@interface func (export \"compute\")))
+             (module $wasi_snapshot_preview1_io (@interface func (export \"read\")))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(module $wasi_ephemeral_nn
+                (@interface func (export \"load\"))
+                (@interface func (export \"compute\"))
+                (@interface func (export \"init\")))
+             (module $wasi_ephemeral_stream (@interface func (export \"read\")))
+             (module $wasi_ephemeral_clock (@interface func (export \"now\")))",
+        )
+        .unwrap();
+        let pairs = infer_module_mapping(&new, &old);
+        let names = pairs
+            .iter()
+            .map(|p| (p.new.as_str(), p.old.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("wasi_ephemeral_nn", "wasi_snapshot_preview1_nn"),
+                ("wasi_ephemeral_stream", "wasi_snapshot_preview1_io")
+            ]
+        );
+        assert_eq!(pairs[0].confidence, 1.0);
+        // Only the functions match.
+        assert_eq!(pairs[1].confidence, 0.5);
+
+        let mapping = pairs
+            .iter()
+            .map(|p| (p.new.as_str().to_string(), p.old.as_str().to_string()))
+            .collect();
+        let polyfill = Polyfill::new(&new, &old, &mapping).unwrap();
+        assert_eq!(polyfill.modules.len(), 2);
+    }
+
+    #[test]
+    fn from_snapshots() {
+        let root = std::env::temp_dir().join(format!("witx-snapshots-{}", std::process::id()));
+        let write = |dir: &str, file: &str, source: &str| {
+            let dir = root.join(dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(file), source).unwrap();
+        };
+        write("old", "typenames.witx", "(typename $n u32)");
+        write(
+            "old",
+            "wasi_snapshot_nn.witx",
+            "(use \"typenames.witx\")
+             (module $wasi_snapshot_nn (@interface func (export \"f\") (param $n $n)))",
+        );
+        write(
+            "old",
+            "wasi_snapshot_io.witx",
+            "(module $wasi_snapshot_io (@interface func (export \"g\")))",
+        );
+        write("new", "typenames.witx", "(typename $n u64)");
+        write(
+            "new",
+            "wasi_ephemeral_nn.witx",
+            "(use \"typenames.witx\")
+             (module $wasi_ephemeral_nn
+                (@interface func (export \"f\") (param $n $n))
+                (@witx cfg $extra (@interface func (export \"h\"))))",
+        );
+        write(
+            "new",
+            "wasi_ephemeral_stream.witx",
+            "(module $wasi_ephemeral_stream (@interface func (export \"g\")))",
+        );
+
+        let polyfill = Polyfill::from_snapshots(&root.join("old"), &root.join("new")).unwrap();
+        let mut pairs = polyfill
+            .modules
+            .iter()
+            .map(|m| (m.new.name.as_str(), m.old.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [("wasi_ephemeral_nn", "wasi_snapshot_nn")]);
+        assert!(!polyfill.modules[0].funcs[0].full_compat());
+        assert!(polyfill.modules[0].added_funcs.is_empty());
+
+        let exceptions = vec![(
+            "wasi_ephemeral_stream".to_string(),
+            "wasi_snapshot_io".to_string(),
+        )]
+        .into_iter()
+        .collect();
+        let polyfill = Polyfill::from_snapshots_with(
+            &root.join("old"),
+            &root.join("new"),
+            &LoadOptions::new().cfg("extra"),
+            &exceptions,
+            &RepContext::default(),
+        )
+        .unwrap();
+        pairs = polyfill
+            .modules
+            .iter()
+            .map(|m| (m.new.name.as_str(), m.old.name.as_str()))
+            .collect();
+        pairs.sort();
+        assert_eq!(
+            pairs,
+            [
+                ("wasi_ephemeral_nn", "wasi_snapshot_nn"),
+                ("wasi_ephemeral_stream", "wasi_snapshot_io")
+            ]
+        );
+        let nn = polyfill
+            .modules
+            .iter()
+            .find(|m| m.new.name.as_str() == "wasi_ephemeral_nn")
+            .unwrap();
+        assert_eq!(nn.added_funcs.len(), 1);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn pointer_direction() {
+        let old = crate::parse(
+            "(module $m
+                (@interface func (export \"f\")
+                    (param $r (@witx const_pointer u8))
+                    (param $w (@witx pointer u8))
+                    (param $x (@witx pointer u8))))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(module $m
+                (@interface func (export \"f\")
+                    (param $r (@witx pointer u8))
+                    (param $w (@witx const_pointer u8))
+                    (param $x (@witx pointer u8))))",
+        )
+        .unwrap();
+        let mapping = same_module();
+        let polyfill = Polyfill::new(&new, &old, &mapping).unwrap();
+        let params = &polyfill.modules[0].funcs[0].mapped_params;
+        assert_eq!(
+            params
+                .iter()
+                .map(|p| p.pointer_direction())
+                .collect::<Vec<_>>(),
+            vec![
+                Some(PointerDirection::Upgraded),
+                Some(PointerDirection::Downgraded),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn widening() {
+        let old = crate::parse(
+            "(module $m
+                (@interface func (export \"f\")
+                    (param $a u32)
+                    (param $b s8)
+                    (param $c f32)
+                    (result $r u64)))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(module $m
+                (@interface func (export \"f\")
+                    (param $a u64)
+                    (param $b s16)
+                    (param $c f64)
+                    (result $r u32)))",
+        )
+        .unwrap();
+        let mapping = same_module();
+        let polyfill = Polyfill::new(&new, &old, &mapping).unwrap();
+        let func = &polyfill.modules[0].funcs[0];
+        assert_eq!(
+            func.mapped_params
+                .iter()
+                .map(|p| (p.repeq(), p.type_polyfill.conversion()))
+                .collect::<Vec<_>>(),
+            vec![
+                (RepEquality::Superset, Some(Conversion::ZeroExtend)),
+                (RepEquality::Superset, Some(Conversion::SignExtend)),
+                (RepEquality::Superset, Some(Conversion::Promote)),
+            ]
+        );
+        // A narrower result doesn't fit the old one, but is widened to it.
+        let result = &func.mapped_results[0];
+        assert_eq!(result.repeq(), RepEquality::Superset);
+        assert_eq!(
+            result.type_polyfill.conversion(),
+            Some(Conversion::ZeroExtend)
+        );
+    }
+
+    #[test]
+    fn params_refer_to_funcs() {
+        let old = crate::parse(
+            "(module $m (@interface func (export \"f\")
+               (param $a u8) (param $b u8) (result $r u8)))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(module $m (@interface func (export \"f\")
+               (param $c u8) (param $b u16) (result $r u8)))",
+        )
+        .unwrap();
+        let mapping = same_module();
+        let polyfill = Polyfill::new(&new, &old, &mapping).unwrap();
+        let func = &polyfill.modules[0].funcs[0];
+        let b = &func.mapped_params[0];
+        assert!(Rc::ptr_eq(b.old.func(), &func.old));
+        assert!(Rc::ptr_eq(b.new.func(), &func.new));
+        assert_eq!(*b.old, func.old.params[1]);
+        assert_eq!(*b.new, func.new.params[1]);
+        assert_eq!(*func.mapped_results[0].old, func.old.results[0]);
+        let unknown = func
+            .unknown_params
+            .iter()
+            .map(|u| (u.which(), u.param().name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(unknown, vec![("old", "a"), ("new", "c")]);
+    }
+
+    #[test]
+    fn handle_rename() {
+        let old = crate::parse(
+            "(typename $fd (handle))
+             (module $m (@interface func (export \"f\") (param $h $fd)))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(typename $handle (handle))
+             (module $m (@interface func (export \"f\") (param $h $handle)))",
+        )
+        .unwrap();
+        let mapping = same_module();
+        let param = |cx: &RepContext| {
+            let polyfill = Polyfill::with_context(&new, &old, &mapping, cx).unwrap();
+            polyfill.modules[0].funcs[0].mapped_params[0].clone()
+        };
+        let renamed = param(&RepContext::new());
+        assert_eq!(renamed.repeq(), RepEquality::NotEq);
+        assert_eq!(
+            renamed.handle_rename(),
+            Some((&Id::new("fd"), &Id::new("handle")))
+        );
+        let aliased = param(&RepContext::new().handle_alias(Id::new("fd"), Id::new("handle")));
+        assert_eq!(aliased.repeq(), RepEquality::Eq);
+        assert!(!aliased.full_compat());
+    }
+
+    #[test]
+    fn chain() {
+        let parse = |params: &str| {
+            crate::parse(&format!(
+                "(module $m (@interface func (export \"f\") {}))",
+                params
+            ))
+            .unwrap()
+        };
+        let v1 = parse("(param $a u8) (param $b u8) (param $c u8) (result $r u64)");
+        let v2 = parse("(param $a u8) (param $b u16) (param $c u8) (param $d u8) (result $r u32)");
+        let v3 = parse("(param $a u16) (param $b u32) (param $d u8) (param $e u8) (result $r u32)");
+        let mapping = same_module();
+        let first = Polyfill::new(&v2, &v1, &mapping).unwrap();
+        let next = Polyfill::new(&v3, &v2, &mapping).unwrap();
+        let chained = first.chain(&next).unwrap();
+        let func = &chained.modules[0].funcs[0];
+        assert!(Rc::ptr_eq(&func.old, &first.modules[0].funcs[0].old));
+        assert!(Rc::ptr_eq(&func.new, &next.modules[0].funcs[0].new));
+        assert_eq!(
+            func.mapped_params
+                .iter()
+                .map(|p| (p.old.name.as_str(), p.repeq()))
+                .collect::<Vec<_>>(),
+            vec![("a", RepEquality::Superset), ("b", RepEquality::Superset)]
+        );
+        assert_eq!(
+            func.unknown_params
+                .iter()
+                .map(|p| (p.which(), p.param().name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("old", "c"), ("new", "d"), ("new", "e")]
+        );
+        assert_eq!(func.mapped_results[0].repeq(), RepEquality::Superset);
+        assert_eq!(
+            func.mapped_params[1].type_polyfill,
+            TypePolyfill::OldToNew(
+                first.modules[0].funcs[0].mapped_params[1].old.tref.clone(),
+                next.modules[0].funcs[0].mapped_params[1].new.tref.clone(),
+            )
+        );
+
+        // A function the last version drops can't be chained.
+        let v4 = crate::parse("(module $m)").unwrap();
+        let last = Polyfill::new(&v4, &v4, &mapping).unwrap();
+        assert!(matches!(
+            chained.chain(&last),
+            Err(PolyfillError::FuncNotPresent { .. })
+        ));
+    }
+
+    #[test]
+    fn added_funcs() {
+        let v1 = crate::parse("(module $m (@interface func (export \"f\")))").unwrap();
+        let v2 = crate::parse(
+            "(module $m
+                (@interface func (export \"f\"))
+                (@interface func (export \"g\")))",
+        )
+        .unwrap();
+        let v3 = crate::parse(
+            "(module $m
+                (@interface func (export \"f\"))
+                (@interface func (export \"g\"))
+                (@interface func (export \"h\")))",
+        )
+        .unwrap();
+        let mapping = same_module();
+        let first = Polyfill::new(&v2, &v1, &mapping).unwrap();
+        let names = |p: &Polyfill| {
+            p.modules[0]
+                .added_funcs
+                .iter()
+                .map(|f| f.name.as_str().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&first), vec!["g"]);
+        assert_eq!(
+            first.modules[0].to_md(),
+            "## `m` in terms of `m`\n* `f`: full compatibility\n* `g`: added"
+        );
+        let next = Polyfill::new(&v3, &v2, &mapping).unwrap();
+        assert_eq!(names(&first.chain(&next).unwrap()), vec!["g", "h"]);
+    }
+
+    #[test]
+    fn deterministic() {
+        let spec = |ty| {
+            let modules = ["c", "a", "d", "b"]
+                .iter()
+                .map(|m| {
+                    format!(
+                        "(module ${} (@interface func (export \"f\") (param $x ${}_t)))",
+                        m, m
+                    )
+                })
+                .collect::<String>();
+            let types = ["c", "a", "d", "b"]
+                .iter()
+                .map(|m| format!("(typename ${}_t {})", m, ty))
+                .collect::<String>();
+            crate::parse(&format!("{}{}", types, modules)).unwrap()
+        };
+        let (old, new) = (spec("u8"), spec("u16"));
+        let render = || {
+            let mapping = ["a", "b", "c", "d"]
+                .iter()
+                .map(|m| (m.to_string(), m.to_string()))
+                .collect::<HashMap<_, _>>();
+            Polyfill::new(&new, &old, &mapping).unwrap()
+        };
+        let polyfill = render();
+        let names = polyfill
+            .modules
+            .iter()
+            .map(|m| m.new.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["c", "a", "d", "b"]);
+        let md = polyfill.to_md();
+        for _ in 0..8 {
+            assert_eq!(render().to_md(), md);
+        }
+    }
+
+    #[test]
+    fn targets() {
+        let old = crate::parse(
+            "(module $m
+                (@interface func (export \"f\")
+                    (param $a (@witx usize))
+                    (param $b u32)
+                    (param $p (@witx pointer u8))
+                    (result $r (@witx usize))))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(module $m
+                (@interface func (export \"f\")
+                    (param $a (@witx usize))
+                    (param $b (@witx usize))
+                    (param $p (@witx pointer u8))
+                    (result $r (@witx usize))))",
+        )
+        .unwrap();
+        let mapping = same_module();
+        let func = |cx: &RepContext| {
+            let polyfill = Polyfill::with_context(&new, &old, &mapping, cx).unwrap();
+            polyfill.modules[0].funcs[0].clone()
+        };
+        let repeqs = |f: &FuncPolyfill| {
+            f.mapped_params
+                .iter()
+                .chain(f.mapped_results.iter())
+                .map(|p| p.repeq())
+                .collect::<Vec<_>>()
+        };
+
+        // By name, `usize` and `u32` differ wherever they are.
+        let nominal = func(&RepContext::new());
+        assert_eq!(
+            repeqs(&nominal),
+            vec![
+                RepEquality::Eq,
+                RepEquality::NotEq,
+                RepEquality::Eq,
+                RepEquality::Eq
+            ]
+        );
+        let same = func(&RepContext::new().targets(Target::Wasm32, Target::Wasm32));
+        assert_eq!(repeqs(&same), vec![RepEquality::Eq; 4]);
+        assert!(same.mapped_params.iter().all(|p| p.sizes.is_empty()));
+
+        // Wider pointers hold old addresses, but wider results don't fit.
+        let wider = func(&RepContext::new().targets(Target::Wasm32, Target::Wasm64));
+        assert_eq!(
+            repeqs(&wider),
+            vec![
+                RepEquality::Superset,
+                RepEquality::Superset,
+                RepEquality::Superset,
+                RepEquality::NotEq
+            ]
+        );
+        assert_eq!(
+            wider.mapped_params[1].report(),
+            vec!["`u32` is 4 bytes on wasm32 and `usize` is 8 bytes on wasm64"]
+        );
+        assert_eq!(
+            wider.mapped_params[2].report(),
+            vec!["`Pointer<u8>` is 4 bytes on wasm32 and 8 bytes on wasm64"]
+        );
+
+        // Records are read in place, so their members move with the width
+        // of a pointer.
+        let doc = crate::parse(
+            "(typename $r (record (field $p (@witx pointer u8)) (field $n (@witx usize))))",
+        )
+        .unwrap();
+        let r = doc.typename(&Id::new("r")).unwrap();
+        let record = |cx: &RepContext| r.tref.representable_in(&r.tref, cx);
+        let wasm64 = RepContext::new().targets(Target::Wasm64, Target::Wasm64);
+        assert_eq!(record(&wasm64), RepEquality::Eq);
+        let wider = RepContext::new().targets(Target::Wasm32, Target::Wasm64);
+        assert_eq!(record(&wider), RepEquality::NotEq);
+        assert_eq!(record(&wider.reversed()), RepEquality::NotEq);
+    }
+
+    #[test]
+    fn enum_remap() {
+        let remap = |old: &str, new: &str| {
+            let old = crate::parse(&format!("(typename $e (enum {}))", old)).unwrap();
+            let new = crate::parse(&format!("(typename $e (enum {}))", new)).unwrap();
+            let e = Id::new("e");
+            let (old, new) = (old.typename(&e).unwrap(), new.typename(&e).unwrap());
+            TypePolyfill::OldToNew(TypeRef::Name(old), TypeRef::Name(new))
+        };
+        let values = |remap: &EnumRemap| {
+            remap
+                .values
+                .iter()
+                .map(|v| (v.from, v.to))
+                .collect::<Vec<_>>()
+        };
+
+        let superset = remap("$a $b", "$a $b $c");
+        assert_eq!(superset.repeq(), RepEquality::Superset);
+        let table = superset.enum_remap().unwrap();
+        assert!(table.is_identity());
+        assert_eq!(values(&table), vec![(0, 0), (1, 1)]);
+
+        let reordered = remap("$a $b $c $d", "$b $a $d");
+        assert_eq!(reordered.repeq(), RepEquality::NotEq);
+        let table = reordered.enum_remap().unwrap();
+        assert!(!table.is_identity());
+        assert_eq!(values(&table), vec![(0, 1), (1, 0), (3, 2)]);
+        assert_eq!(table.get(3), Some(2));
+        assert_eq!(table.get(2), None);
+        assert_eq!(
+            table
+                .unmappable
+                .iter()
+                .map(|v| (v.name.as_str(), v.from))
+                .collect::<Vec<_>>(),
+            vec![("c", 2)]
+        );
+
+        let u8_ = TypeRef::Value(Rc::new(Type::Builtin(crate::BuiltinType::U8 {
+            lang_c_char: false,
+        })));
+        assert_eq!(TypePolyfill::OldToNew(u8_.clone(), u8_).enum_remap(), None);
+    }
+
+    #[test]
+    fn variant_cases() {
+        let old = crate::parse(
+            "(typename $v (variant (case $a u8) (case $b) (case $c u32) (case $d)))
+             (module $m (@interface func (export \"f\") (param $v $v)))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(typename $v (variant (case $a u16) (case $b u8) (case $c) (case $e)))
+             (module $m (@interface func (export \"f\") (param $v $v)))",
+        )
+        .unwrap();
+        let mapping = same_module();
+        let polyfill = Polyfill::new(&new, &old, &mapping).unwrap();
+        let param = &polyfill.modules[0].funcs[0].mapped_params[0];
+        assert_eq!(param.repeq(), RepEquality::NotEq);
+        assert_eq!(
+            param.report(),
+            vec![
+                "v: case `c` payload removed",
+                "case `a` payload widened",
+                "case `b` payload added",
+                "case `c` payload removed",
+                "case `d` removed",
+                "case `e` added",
+            ]
+        );
+    }
 }
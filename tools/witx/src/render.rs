@@ -3,11 +3,14 @@ use std::fmt;
 
 impl fmt::Display for Document {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.version() != crate::WitxVersion::LATEST {
+            writeln!(f, "(@witx version \"{}\")", self.version())?;
+        }
         for d in self.typenames() {
-            write!(f, "{}\n", d.to_sexpr())?;
+            writeln!(f, "{}", d.to_sexpr())?;
         }
         for m in self.modules() {
-            write!(f, "{}\n", m.to_sexpr())?;
+            writeln!(f, "{}", m.to_sexpr())?;
         }
         Ok(())
     }
@@ -103,10 +106,20 @@ impl BuiltinType {
 impl NamedType {
     pub fn to_sexpr(&self) -> SExpr {
         let body = self.tref.to_sexpr();
-        SExpr::docs(
-            &self.docs,
-            SExpr::Vec(vec![SExpr::word("typename"), self.name.to_sexpr(), body]),
-        )
+        let mut def = vec![SExpr::word("typename"), self.name.to_sexpr(), body];
+        def.extend(self.meta.iter().map(Meta::to_sexpr));
+        SExpr::docs(&self.docs, SExpr::Vec(def))
+    }
+}
+
+impl Meta {
+    pub fn to_sexpr(&self) -> SExpr {
+        SExpr::Vec(vec![
+            SExpr::annot("witx"),
+            SExpr::word("meta"),
+            SExpr::word(&self.key),
+            SExpr::quote(&self.value),
+        ])
     }
 }
 
@@ -171,14 +184,10 @@ impl RecordDatatype {
                     .members
                     .iter()
                     .map(|m| {
-                        SExpr::docs(
-                            &m.docs,
-                            SExpr::Vec(vec![
-                                SExpr::word("field"),
-                                m.name.to_sexpr(),
-                                m.tref.to_sexpr(),
-                            ]),
-                        )
+                        let mut field =
+                            vec![SExpr::word("field"), m.name.to_sexpr(), m.tref.to_sexpr()];
+                        field.extend(m.size.as_ref().map(SizeExpr::to_annotation));
+                        SExpr::docs(&m.docs, SExpr::Vec(field))
                     })
                     .collect::<Vec<SExpr>>();
                 SExpr::Vec([header, members].concat())
@@ -199,6 +208,13 @@ impl Variant {
                 SExpr::word("tag"),
                 self.tag_repr.to_sexpr(),
             ]));
+            if let Some(feature) = &self.feature {
+                list.push(SExpr::Vec(vec![
+                    SExpr::word("@witx"),
+                    SExpr::word("feature"),
+                    feature.to_sexpr(),
+                ]));
+            }
             for case in self.cases.iter() {
                 list.push(SExpr::docs(&case.docs, case.name.to_sexpr()));
             }
@@ -221,6 +237,40 @@ impl Variant {
     }
 }
 
+impl SizeExpr {
+    pub fn to_sexpr(&self) -> SExpr {
+        let op = |name: &str, operands: Vec<SExpr>| {
+            SExpr::Vec([vec![SExpr::word(name)], operands].concat())
+        };
+        match self {
+            SizeExpr::Const(n) => SExpr::Word(n.to_string()),
+            SizeExpr::Value(id) => id.to_sexpr(),
+            SizeExpr::Len(id) => op("len", vec![id.to_sexpr()]),
+            SizeExpr::Product(id) => op("product", vec![id.to_sexpr()]),
+            SizeExpr::TensorSize { dimensions, ty } => {
+                op("tensor_size", vec![dimensions.to_sexpr(), ty.to_sexpr()])
+            }
+            SizeExpr::Mul(exprs) => op("mul", exprs.iter().map(|e| e.to_sexpr()).collect()),
+            SizeExpr::Add(exprs) => op("add", exprs.iter().map(|e| e.to_sexpr()).collect()),
+        }
+    }
+
+    /// The `(@witx size ...)` annotation declaring the size.
+    pub fn to_annotation(&self) -> SExpr {
+        SExpr::Vec(vec![
+            SExpr::annot("witx"),
+            SExpr::word("size"),
+            self.to_sexpr(),
+        ])
+    }
+}
+
+impl fmt::Display for SizeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_sexpr())
+    }
+}
+
 impl HandleDatatype {
     pub fn to_sexpr(&self) -> SExpr {
         SExpr::Vec(vec![SExpr::word("handle")])
@@ -240,7 +290,8 @@ impl IntRepr {
 
 impl Module {
     pub fn to_sexpr(&self) -> SExpr {
-        let header = vec![SExpr::word("module"), self.name.to_sexpr()];
+        let mut header = vec![SExpr::word("module"), self.name.to_sexpr()];
+        header.extend(self.meta.iter().map(Meta::to_sexpr));
         let definitions = self
             .imports()
             .map(|i| i.to_sexpr())
@@ -280,14 +331,9 @@ impl InterfaceFunc {
             .params
             .iter()
             .map(|f| {
-                SExpr::docs(
-                    &f.docs,
-                    SExpr::Vec(vec![
-                        SExpr::word("param"),
-                        f.name.to_sexpr(),
-                        f.tref.to_sexpr(),
-                    ]),
-                )
+                let mut param = vec![SExpr::word("param"), f.name.to_sexpr(), f.tref.to_sexpr()];
+                param.extend(f.size.as_ref().map(SizeExpr::to_annotation));
+                SExpr::docs(&f.docs, SExpr::Vec(param))
             })
             .collect();
         let results = self
@@ -304,14 +350,21 @@ impl InterfaceFunc {
                 )
             })
             .collect();
-        let attrs = if self.noreturn {
-            vec![SExpr::Vec(vec![
+        let mut attrs = Vec::new();
+        if self.noreturn {
+            attrs.push(SExpr::Vec(vec![
                 SExpr::annot("witx"),
                 SExpr::word("noreturn"),
-            ])]
-        } else {
-            vec![]
-        };
+            ]));
+        }
+        if let Some(feature) = &self.feature {
+            attrs.push(SExpr::Vec(vec![
+                SExpr::annot("witx"),
+                SExpr::word("feature"),
+                feature.to_sexpr(),
+            ]));
+        }
+        attrs.extend(self.meta.iter().map(Meta::to_sexpr));
         SExpr::docs(
             &self.docs,
             SExpr::Vec([header, params, results, attrs].concat()),
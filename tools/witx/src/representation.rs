@@ -1,8 +1,14 @@
-use crate::{BuiltinType, IntRepr, NamedType, RecordDatatype, Type, TypeRef, Variant};
+use crate::{
+    BuiltinType, Id, IntRepr, Layout, NamedType, RecordDatatype, RecordKind, SizeExpr, Target,
+    Type, TypeRef, Variant,
+};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
 
 // A lattice. Eq + Eq = Eq, SuperSet + any = NotEq, NotEq + any = NotEq.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum RepEquality {
     Eq,
     Superset,
@@ -16,41 +22,213 @@ impl RepEquality {
             _ => RepEquality::NotEq,
         }
     }
+
+    /// The compatibility of converting through two steps in turn, the first
+    /// with compatibility `self` and the second with `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        match (self, next) {
+            (RepEquality::NotEq, _) | (_, RepEquality::NotEq) => RepEquality::NotEq,
+            (RepEquality::Eq, RepEquality::Eq) => RepEquality::Eq,
+            _ => RepEquality::Superset,
+        }
+    }
+}
+
+/// Settings for comparing types.
+#[derive(Debug, Clone, Default)]
+pub struct RepContext {
+    handle_aliases: HashMap<Id, Id>,
+    ignore_type_names: bool,
+    /// The targets of the types compared and of the types representing
+    /// them, when they are laid out for specific targets.
+    targets: Option<(Target, Target)>,
+    /// Comparisons of type references already made, keyed by their
+    /// addresses. Only enabled by `memoized`, for a pass over documents
+    /// that outlive it.
+    cache: Option<RefCell<HashMap<(usize, usize), RepEquality>>>,
+}
+
+impl RepContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat handles to the resource `old` as representable by handles to the
+    /// resource `new`, for resources renamed between versions of a document.
+    pub fn handle_alias(mut self, old: Id, new: Id) -> Self {
+        self.handle_aliases.insert(old, new);
+        self
+    }
+
+    /// A copy of this context that remembers the comparisons made through
+    /// it. The documents compared must outlive it, since type references are
+    /// identified by address.
+    pub(crate) fn memoized(&self) -> Self {
+        RepContext {
+            cache: Some(RefCell::default()),
+            ..self.clone()
+        }
+    }
+
+    /// Compare named types by their definitions alone, so that handles to
+    /// differently named resources are `Eq` and diffs match types renamed
+    /// without changing their definition.
+    pub fn ignore_type_names(mut self) -> Self {
+        self.ignore_type_names = true;
+        self
+    }
+
+    pub fn ignores_type_names(&self) -> bool {
+        self.ignore_type_names
+    }
+
+    /// Compare types laid out for `from` with the types representing them
+    /// laid out for `to`, such as a wasm32 interface with a wasm64 one.
+    /// Numbers and pointers are then compared by their sizes on each target
+    /// rather than by name, so `usize` on wasm32 is `Eq` to `u32` and is a
+    /// `Superset` of `usize` on wasm64.
+    pub fn targets(mut self, from: Target, to: Target) -> Self {
+        self.targets = Some((from, to));
+        self
+    }
+
+    pub fn compared_targets(&self) -> Option<(Target, Target)> {
+        self.targets
+    }
+
+    /// This context for comparing in the opposite direction, from the
+    /// target types are represented on to the target of the types compared.
+    pub(crate) fn reversed(&self) -> Self {
+        RepContext {
+            targets: self.targets.map(|(from, to)| (to, from)),
+            cache: self.cache.as_ref().map(|_| RefCell::default()),
+            ..self.clone()
+        }
+    }
+
+    /// The compatibility of a pointer on the target compared with one on the
+    /// target representing it: an address fits in a wider pointer, but not
+    /// in a narrower one.
+    pub(crate) fn pointer_width(&self) -> RepEquality {
+        match self.targets {
+            Some((from, to)) if from.pointer_size() < to.pointer_size() => RepEquality::Superset,
+            Some((from, to)) if from.pointer_size() > to.pointer_size() => RepEquality::NotEq,
+            _ => RepEquality::Eq,
+        }
+    }
+
+    /// Whether handles to the resource `a` are handles to the resource `b`.
+    fn same_resource(&self, a: &Id, b: &Id) -> bool {
+        self.ignore_type_names || a == b || self.handle_aliases.get(a) == Some(b)
+    }
 }
 
 pub trait Representable {
-    fn representable(&self, by: &Self) -> RepEquality;
+    fn representable(&self, by: &Self) -> RepEquality {
+        self.representable_in(by, &RepContext::default())
+    }
+    fn representable_in(&self, by: &Self, cx: &RepContext) -> RepEquality;
+}
+
+/// How a value is converted to a wider type representing all of its values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Conversion {
+    /// Zero-extend an unsigned integer.
+    ZeroExtend,
+    /// Sign-extend a signed integer.
+    SignExtend,
+    /// Promote a float to a wider float.
+    Promote,
+}
+
+impl Conversion {
+    /// The core wasm instruction converting the lowered `from` to the lowered
+    /// `to`, or `None` if both are lowered to the same wasm type.
+    pub fn instruction(&self, from: &BuiltinType, to: &BuiltinType) -> Option<&'static str> {
+        if lowered_width(from) == lowered_width(to) {
+            return None;
+        }
+        Some(match self {
+            Conversion::ZeroExtend => "i64.extend_i32_u",
+            Conversion::SignExtend => "i64.extend_i32_s",
+            Conversion::Promote => "f64.promote_f32",
+        })
+    }
+}
+
+impl fmt::Display for Conversion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Conversion::ZeroExtend => "zero extend",
+            Conversion::SignExtend => "sign extend",
+            Conversion::Promote => "promote",
+        })
+    }
+}
+
+/// The conversion of a builtin and its width in bytes, for numeric types.
+fn numeric_width(ty: &BuiltinType) -> Option<(Conversion, usize)> {
+    Some(match ty {
+        BuiltinType::U8 { .. } => (Conversion::ZeroExtend, 1),
+        BuiltinType::U16 => (Conversion::ZeroExtend, 2),
+        BuiltinType::U32 { .. } => (Conversion::ZeroExtend, 4),
+        BuiltinType::U64 => (Conversion::ZeroExtend, 8),
+        BuiltinType::S8 => (Conversion::SignExtend, 1),
+        BuiltinType::S16 => (Conversion::SignExtend, 2),
+        BuiltinType::S32 => (Conversion::SignExtend, 4),
+        BuiltinType::S64 => (Conversion::SignExtend, 8),
+        BuiltinType::F32 => (Conversion::Promote, 4),
+        BuiltinType::F64 => (Conversion::Promote, 8),
+        BuiltinType::Char => return None,
+    })
+}
+
+/// The width of the wasm type a builtin is lowered to: integers narrower
+/// than 32 bits are lowered to `i32`.
+fn lowered_width(ty: &BuiltinType) -> Option<usize> {
+    numeric_width(ty).map(|(_, width)| width.max(4))
+}
+
+impl BuiltinType {
+    /// How values of `self` are converted to `to`, if `to` is a wider type of
+    /// the same kind: unsigned and signed integers and floats are each only
+    /// widened to their own kind.
+    pub fn widening(&self, to: &Self) -> Option<Conversion> {
+        match (numeric_width(self), numeric_width(to)) {
+            (Some((a, from)), Some((b, to))) if a == b && from < to => Some(a),
+            _ => None,
+        }
+    }
 }
 
 impl Representable for BuiltinType {
-    fn representable(&self, by: &Self) -> RepEquality {
-        // An unsigned integer can be used to represent an unsigned integer of smaller width.
+    fn representable_in(&self, by: &Self, cx: &RepContext) -> RepEquality {
+        // On known targets, numbers are compared by their sizes there.
+        if let (Some((from, to)), Some((a, _)), Some((b, _))) =
+            (cx.targets, numeric_width(self), numeric_width(by))
+        {
+            let size = self.mem_size_align_for(from).size;
+            let by_size = by.mem_size_align_for(to).size;
+            return match (a == b, size.cmp(&by_size)) {
+                (true, std::cmp::Ordering::Equal) => RepEquality::Eq,
+                (true, std::cmp::Ordering::Less) => RepEquality::Superset,
+                _ => RepEquality::NotEq,
+            };
+        }
+        // A number can be represented by a wider number of the same kind.
         // Otherwise, types must be equal.
         if self == by {
             return RepEquality::Eq;
         }
-        match self {
-            BuiltinType::U8 { .. } => match by {
-                BuiltinType::U64 | BuiltinType::U32 { .. } | BuiltinType::U16 => {
-                    RepEquality::Superset
-                }
-                _ => RepEquality::NotEq,
-            },
-            BuiltinType::U16 => match by {
-                BuiltinType::U64 | BuiltinType::U32 { .. } => RepEquality::Superset,
-                _ => RepEquality::NotEq,
-            },
-            BuiltinType::U32 { .. } => match by {
-                BuiltinType::U64 => RepEquality::Superset,
-                _ => RepEquality::NotEq,
-            },
-            _ => RepEquality::NotEq,
+        match self.widening(by) {
+            Some(_) => RepEquality::Superset,
+            None => RepEquality::NotEq,
         }
     }
 }
 
 impl Representable for IntRepr {
-    fn representable(&self, by: &Self) -> RepEquality {
+    fn representable_in(&self, by: &Self, _cx: &RepContext) -> RepEquality {
         if self == by {
             return RepEquality::Eq;
         }
@@ -69,42 +247,119 @@ impl Representable for IntRepr {
     }
 }
 
-impl Representable for Variant {
-    fn representable(&self, by: &Self) -> RepEquality {
-        let mut superset = false;
-        // Integer representation must be compatible
-        match self.tag_repr.representable(&by.tag_repr) {
-            RepEquality::NotEq => return RepEquality::NotEq,
-            RepEquality::Eq => {}
-            RepEquality::Superset => superset = true,
+/// How a case of a variant differs in another variant.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CaseChange {
+    /// The case is only in the other variant.
+    Added,
+    /// The case is missing from the other variant.
+    Removed,
+    /// The case is at a different position, so has a different tag, in the
+    /// other variant.
+    Reordered,
+    /// The case has a payload only in the other variant.
+    PayloadAdded,
+    /// The case has a payload that is missing from the other variant.
+    PayloadRemoved,
+    /// The case's payload is representable by the other case's payload with
+    /// this compatibility, which is not `Eq`.
+    PayloadChanged(RepEquality),
+}
+
+impl CaseChange {
+    /// Compatibility of values of the case with the other variant.
+    pub fn repeq(&self) -> RepEquality {
+        match self {
+            CaseChange::Added | CaseChange::PayloadAdded => RepEquality::Superset,
+            CaseChange::Removed | CaseChange::Reordered | CaseChange::PayloadRemoved => {
+                RepEquality::NotEq
+            }
+            CaseChange::PayloadChanged(repeq) => *repeq,
+        }
+    }
+}
+
+impl fmt::Display for CaseChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaseChange::Added => f.write_str("added"),
+            CaseChange::Removed => f.write_str("removed"),
+            CaseChange::Reordered => f.write_str("reordered"),
+            CaseChange::PayloadAdded => f.write_str("payload added"),
+            CaseChange::PayloadRemoved => f.write_str("payload removed"),
+            CaseChange::PayloadChanged(RepEquality::Superset) => f.write_str("payload widened"),
+            CaseChange::PayloadChanged(_) => f.write_str("payload changed"),
         }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CaseDiff {
+    pub name: Id,
+    pub change: CaseChange,
+}
+
+impl Variant {
+    /// The cases that differ between `self` and `by`: those of `self` in
+    /// order, then those added in `by`.
+    pub fn case_diffs_in(&self, by: &Self, cx: &RepContext) -> Vec<CaseDiff> {
         let other_by_name = by
             .cases
             .iter()
             .enumerate()
             .map(|(i, c)| (&c.name, (c, i)))
             .collect::<HashMap<_, _>>();
+        let mut diffs = Vec::new();
         // For each variant in self, must have variant of same name in by:
         for (i, v) in self.cases.iter().enumerate() {
-            let other_ty = match other_by_name.get(&v.name) {
-                Some((_, j)) if i != *j => return RepEquality::NotEq,
-                Some((other, _)) => &other.tref,
-                None => return RepEquality::NotEq,
-            };
-            match (&v.tref, other_ty) {
-                (Some(me), Some(other)) => match me.representable(other) {
-                    RepEquality::NotEq => return RepEquality::NotEq,
-                    RepEquality::Eq => {}
-                    RepEquality::Superset => superset = true,
+            let change = match other_by_name.get(&v.name) {
+                None => Some(CaseChange::Removed),
+                Some((_, j)) if i != *j => Some(CaseChange::Reordered),
+                Some((other, _)) => match (&v.tref, &other.tref) {
+                    (Some(me), Some(other)) => match me.representable_in(other, cx) {
+                        RepEquality::Eq => None,
+                        repeq => Some(CaseChange::PayloadChanged(repeq)),
+                    },
+                    (Some(_), None) => Some(CaseChange::PayloadRemoved),
+                    (None, Some(_)) => Some(CaseChange::PayloadAdded),
+                    (None, None) => None,
                 },
-                // We added fields, that's not ok
-                (Some(_), None) => return RepEquality::NotEq,
-                // Fields were deleted, that's ok
-                (None, Some(_)) => superset = true,
-                (None, None) => {}
+            };
+            if let Some(change) = change {
+                diffs.push(CaseDiff {
+                    name: v.name.clone(),
+                    change,
+                });
+            }
+        }
+        for c in by.cases.iter() {
+            if !self.cases.iter().any(|v| v.name == c.name) {
+                diffs.push(CaseDiff {
+                    name: c.name.clone(),
+                    change: CaseChange::Added,
+                });
             }
         }
-        if superset || self.cases.len() < by.cases.len() {
+        diffs
+    }
+}
+
+impl Representable for Variant {
+    fn representable_in(&self, by: &Self, cx: &RepContext) -> RepEquality {
+        // Integer representation must be compatible
+        let tag = self.tag_repr.representable(&by.tag_repr);
+        if tag == RepEquality::NotEq {
+            return RepEquality::NotEq;
+        }
+        let mut superset = tag == RepEquality::Superset;
+        for diff in self.case_diffs_in(by, cx) {
+            match diff.change.repeq() {
+                RepEquality::NotEq => return RepEquality::NotEq,
+                RepEquality::Superset => superset = true,
+                RepEquality::Eq => {}
+            }
+        }
+        if superset {
             RepEquality::Superset
         } else {
             RepEquality::Eq
@@ -112,50 +367,532 @@ impl Representable for Variant {
     }
 }
 
-impl Representable for RecordDatatype {
-    fn representable(&self, by: &Self) -> RepEquality {
-        // Records must have exact structural equality - same members, must
-        // be Eq, in the same order.
-        // We would require require a more expressive RepEquality enum to describe which members
-        // might be supersets.
-        if self.members.len() != by.members.len() {
-            return RepEquality::NotEq;
+/// The first member of a record that keeps it from being representable by
+/// another record.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RecordMismatch {
+    /// The records are bitflags with incompatible representations.
+    Kind,
+    /// A member is missing from the other record.
+    Removed { member: Id },
+    /// The other record has the member `by` in this member's position.
+    Renamed { member: Id, by: Id },
+    /// The member's type is not representable by the other member's type.
+    Type { member: Id },
+    /// The member is a list whose length is not accepted by the other
+    /// member.
+    Length { member: Id },
+    /// The member is at `offset`, but at `by` in the other record.
+    Offset {
+        member: Id,
+        offset: usize,
+        by: usize,
+    },
+}
+
+impl fmt::Display for RecordMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordMismatch::Kind => write!(f, "bitflags representations differ"),
+            RecordMismatch::Removed { member } => write!(f, "`{}` was removed", member.as_str()),
+            RecordMismatch::Renamed { member, by } => {
+                write!(f, "`{}` was replaced by `{}`", member.as_str(), by.as_str())
+            }
+            RecordMismatch::Type { member } => write!(f, "`{}` changed type", member.as_str()),
+            RecordMismatch::Length { member } => {
+                write!(f, "`{}` changed length", member.as_str())
+            }
+            RecordMismatch::Offset { member, offset, by } => write!(
+                f,
+                "`{}` moved from offset {} to {}",
+                member.as_str(),
+                offset,
+                by
+            ),
         }
-        for (m, bym) in self.members.iter().zip(by.members.iter()) {
-            if m.name != bym.name {
-                return RepEquality::NotEq;
+    }
+}
+
+impl RecordDatatype {
+    /// Like `representable`, but describes the first member at fault when
+    /// `self` is not representable by `by`.
+    ///
+    /// Each member must keep its name, offset and size and have a
+    /// representable type. `by` may add trailing members, which makes it a
+    /// superset.
+    pub fn member_representable(&self, by: &Self) -> Result<RepEquality, RecordMismatch> {
+        self.member_representable_in(by, &RepContext::default())
+    }
+
+    pub fn member_representable_in(
+        &self,
+        by: &Self,
+        cx: &RepContext,
+    ) -> Result<RepEquality, RecordMismatch> {
+        let mut superset = match (&self.kind, &by.kind) {
+            (RecordKind::Bitflags(a), RecordKind::Bitflags(b)) => match a.representable(b) {
+                RepEquality::NotEq => return Err(RecordMismatch::Kind),
+                rep => rep == RepEquality::Superset,
+            },
+            // Other kinds are only hints for bindings.
+            _ => false,
+        };
+        let (from, to) = cx.compared_targets().unwrap_or_default();
+        let by_layout = by.member_layout_for(to);
+        for (i, m) in self.member_layout_for(from).iter().enumerate() {
+            let member = &m.member.name;
+            let bym = match by_layout.get(i) {
+                Some(bym) => bym,
+                None => {
+                    return Err(RecordMismatch::Removed {
+                        member: member.clone(),
+                    })
+                }
+            };
+            if *member != bym.member.name {
+                return Err(RecordMismatch::Renamed {
+                    member: member.clone(),
+                    by: bym.member.name.clone(),
+                });
             }
-            if m.tref.type_().representable(&*bym.tref.type_()) != RepEquality::Eq {
-                return RepEquality::NotEq;
+            if m.offset != bym.offset {
+                return Err(RecordMismatch::Offset {
+                    member: member.clone(),
+                    offset: m.offset,
+                    by: bym.offset,
+                });
             }
+            // Members are read in place, so a wider type doesn't fit even
+            // though its values would.
+            if m.member.tref.mem_size_align_for(from).size
+                != bym.member.tref.mem_size_align_for(to).size
+            {
+                return Err(RecordMismatch::Type {
+                    member: member.clone(),
+                });
+            }
+            match m.member.tref.representable_in(&bym.member.tref, cx) {
+                RepEquality::NotEq => {
+                    return Err(RecordMismatch::Type {
+                        member: member.clone(),
+                    })
+                }
+                RepEquality::Superset => superset = true,
+                RepEquality::Eq => {}
+            }
+            match length_representable(m.member.size.as_ref(), bym.member.size.as_ref()) {
+                RepEquality::NotEq => {
+                    return Err(RecordMismatch::Length {
+                        member: member.clone(),
+                    })
+                }
+                RepEquality::Superset => superset = true,
+                RepEquality::Eq => {}
+            }
+        }
+        if superset || self.members.len() < by.members.len() {
+            Ok(RepEquality::Superset)
+        } else {
+            Ok(RepEquality::Eq)
+        }
+    }
+}
+
+/// Whether a list of length `len` is accepted where a list of length `by` is
+/// expected. A list of any length is accepted where no length is declared.
+pub(crate) fn length_representable(len: Option<&SizeExpr>, by: Option<&SizeExpr>) -> RepEquality {
+    match (len, by) {
+        (a, b) if a == b => RepEquality::Eq,
+        (_, None) => RepEquality::Superset,
+        _ => RepEquality::NotEq,
+    }
+}
+
+impl Representable for RecordDatatype {
+    fn representable_in(&self, by: &Self, cx: &RepContext) -> RepEquality {
+        self.member_representable_in(by, cx)
+            .unwrap_or(RepEquality::NotEq)
+    }
+}
+
+impl TypeRef {
+    fn addr(&self) -> usize {
+        match self {
+            TypeRef::Name(nt) => Rc::as_ptr(nt) as usize,
+            TypeRef::Value(ty) => Rc::as_ptr(ty) as usize,
         }
-        RepEquality::Eq
     }
 }
 
 impl Representable for TypeRef {
-    fn representable(&self, by: &Self) -> RepEquality {
-        self.type_().representable(&*by.type_())
+    fn representable_in(&self, by: &Self, cx: &RepContext) -> RepEquality {
+        let key = (self.addr(), by.addr());
+        if let Some(cache) = &cx.cache {
+            if let Some(repeq) = cache.borrow().get(&key) {
+                return *repeq;
+            }
+        }
+        let repeq = match (self, by) {
+            (TypeRef::Name(s), TypeRef::Name(b)) => s.representable_in(b, cx),
+            _ => self.type_().representable_in(by.type_(), cx),
+        };
+        if let Some(cache) = &cx.cache {
+            cache.borrow_mut().insert(key, repeq);
+        }
+        repeq
+    }
+}
+
+impl NamedType {
+    /// The name of the resource a handle type refers to, through any aliases.
+    pub fn resource(&self) -> Option<&Id> {
+        let def = self.definition();
+        match &**def.type_() {
+            Type::Handle(_) => Some(&def.name),
+            _ => None,
+        }
     }
 }
 
 impl Representable for NamedType {
-    fn representable(&self, by: &Self) -> RepEquality {
-        self.tref.representable(&by.tref)
+    fn representable_in(&self, by: &Self, cx: &RepContext) -> RepEquality {
+        // Handles are nominal, not structural
+        match (self.resource(), by.resource()) {
+            (Some(s), Some(b)) if cx.same_resource(s, b) => RepEquality::Eq,
+            (Some(_), Some(_)) => RepEquality::NotEq,
+            _ => self.tref.representable_in(&by.tref, cx),
+        }
     }
 }
 
 impl Representable for Type {
-    fn representable(&self, by: &Self) -> RepEquality {
+    fn representable_in(&self, by: &Self, cx: &RepContext) -> RepEquality {
         match (&self, &by) {
-            (Type::Variant(s), Type::Variant(b)) => s.representable(b),
-            (Type::Record(s), Type::Record(b)) => s.representable(b),
-            (Type::Handle(_), Type::Handle(_)) => RepEquality::Eq, // Handles are nominal, not structural
-            (Type::List(s), Type::List(b)) => s.representable(b),
-            (Type::Pointer(s), Type::Pointer(b)) => s.representable(b),
-            (Type::ConstPointer(s), Type::ConstPointer(b)) => s.representable(b),
-            (Type::Builtin(s), Type::Builtin(b)) => s.representable(b),
+            (Type::Variant(s), Type::Variant(b)) => s.representable_in(b, cx),
+            (Type::Record(s), Type::Record(b)) => s.representable_in(b, cx),
+            // Anonymous handles can't be told apart
+            (Type::Handle(_), Type::Handle(_)) => RepEquality::Eq,
+            // Lists are converted element by element, so only their
+            // elements need to be representable, as well as their addresses.
+            (Type::List(s), Type::List(b))
+            | (Type::Pointer(s), Type::Pointer(b))
+            | (Type::ConstPointer(s), Type::ConstPointer(b)) => {
+                cx.pointer_width().then(&s.representable_in(b, cx))
+            }
+            // A pointer that may be written can stand in for one that may
+            // only be read, but not the other way around.
+            (Type::ConstPointer(s), Type::Pointer(b)) => {
+                match cx.pointer_width().then(&s.representable_in(b, cx)) {
+                    RepEquality::NotEq => RepEquality::NotEq,
+                    _ => RepEquality::Superset,
+                }
+            }
+            (Type::Builtin(s), Type::Builtin(b)) => s.representable_in(b, cx),
             _ => RepEquality::NotEq,
         }
     }
 }
+
+/// A step from a type into one of its parts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// The named type the comparison started from.
+    Type(Id),
+    Member(Id),
+    /// The payload of a case.
+    Case(Id),
+    /// The elements of a list.
+    Element,
+    /// The memory behind a pointer.
+    Pointee,
+}
+
+/// What differs where two types diverge.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DivergenceReason {
+    /// The types differ, described by their names or kinds.
+    Types {
+        from: String,
+        to: String,
+    },
+    Member(RecordMismatch),
+    Case(CaseDiff),
+    /// The variants' tags are incompatible.
+    Tag {
+        from: IntRepr,
+        to: IntRepr,
+    },
+    /// The handles refer to different resources.
+    Resource {
+        from: Id,
+        to: Id,
+    },
+}
+
+/// The first point at which a type is not representable by another: why a
+/// comparison was `NotEq`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Divergence {
+    /// The path from the compared types to where they diverge.
+    pub path: Vec<PathSegment>,
+    pub reason: DivergenceReason,
+}
+
+impl fmt::Display for Divergence {
+    /// Named steps of the path are joined with dots and the rest are spelled
+    /// out, e.g. `tensor.dimensions: list element u32 vs u64`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let named = self
+            .path
+            .iter()
+            .rposition(|s| !matches!(s, PathSegment::Element | PathSegment::Pointee))
+            .map_or(0, |i| i + 1);
+        let mut prefix = String::new();
+        for segment in &self.path[..named] {
+            match segment {
+                PathSegment::Type(name) | PathSegment::Member(name) | PathSegment::Case(name) => {
+                    if !prefix.is_empty() {
+                        prefix.push('.');
+                    }
+                    prefix.push_str(name.as_str());
+                }
+                PathSegment::Element => prefix.push_str("[]"),
+                PathSegment::Pointee => prefix.push('*'),
+            }
+        }
+        if !prefix.is_empty() {
+            write!(f, "{}: ", prefix)?;
+        }
+        for segment in &self.path[named..] {
+            match segment {
+                PathSegment::Element => f.write_str("list element ")?,
+                _ => f.write_str("pointee ")?,
+            }
+        }
+        match &self.reason {
+            DivergenceReason::Types { from, to } => write!(f, "{} vs {}", from, to),
+            DivergenceReason::Member(mismatch) => write!(f, "{}", mismatch),
+            DivergenceReason::Case(diff) => {
+                write!(f, "case `{}` {}", diff.name.as_str(), diff.change)
+            }
+            DivergenceReason::Tag { from, to } => write!(
+                f,
+                "tag {} vs {}",
+                from.to_builtin().type_name(),
+                to.to_builtin().type_name()
+            ),
+            DivergenceReason::Resource { from, to } => {
+                write!(f, "handle `{}` vs `{}`", from.as_str(), to.as_str())
+            }
+        }
+    }
+}
+
+impl TypeRef {
+    /// Where `self` first diverges from `by`, if it isn't representable by
+    /// it.
+    pub fn divergence_in(&self, by: &Self, cx: &RepContext) -> Option<Divergence> {
+        let mut path = Vec::new();
+        if let TypeRef::Name(nt) = self {
+            path.push(PathSegment::Type(nt.name.clone()));
+        }
+        diverge_tref(self, by, cx, &mut path).map(|reason| Divergence { path, reason })
+    }
+}
+
+/// Why `a` isn't representable by `b`, extending `path` to where they
+/// diverge.
+fn diverge_tref(
+    a: &TypeRef,
+    b: &TypeRef,
+    cx: &RepContext,
+    path: &mut Vec<PathSegment>,
+) -> Option<DivergenceReason> {
+    if a.representable_in(b, cx) != RepEquality::NotEq {
+        return None;
+    }
+    if let (TypeRef::Name(a), TypeRef::Name(b)) = (a, b) {
+        if let (Some(from), Some(to)) = (a.resource(), b.resource()) {
+            return Some(DivergenceReason::Resource {
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+    }
+    let describe = |ty: &Type| match ty {
+        Type::Builtin(b) => b.type_name().to_string(),
+        ty => ty.kind().to_string(),
+    };
+    let nested = |segment, a: &TypeRef, b: &TypeRef, path: &mut Vec<PathSegment>| {
+        path.push(segment);
+        let reason = diverge_tref(a, b, cx, path);
+        if reason.is_none() {
+            path.pop();
+        }
+        reason
+    };
+    let reason = match (&**a.type_(), &**b.type_()) {
+        (Type::Record(ra), Type::Record(rb)) => match ra.member_representable_in(rb, cx) {
+            Err(RecordMismatch::Type { member }) => {
+                let am = ra.members.iter().find(|m| m.name == member).unwrap();
+                let bm = rb.members.iter().find(|m| m.name == member).unwrap();
+                nested(
+                    PathSegment::Member(member.clone()),
+                    &am.tref,
+                    &bm.tref,
+                    path,
+                )
+                .or(Some(DivergenceReason::Member(RecordMismatch::Type {
+                    member,
+                })))
+            }
+            Err(mismatch) => Some(DivergenceReason::Member(mismatch)),
+            Ok(_) => None,
+        },
+        (Type::Variant(va), Type::Variant(vb)) => {
+            if va.tag_repr.representable(&vb.tag_repr) == RepEquality::NotEq {
+                return Some(DivergenceReason::Tag {
+                    from: va.tag_repr,
+                    to: vb.tag_repr,
+                });
+            }
+            let diff = va
+                .case_diffs_in(vb, cx)
+                .into_iter()
+                .find(|d| d.change.repeq() == RepEquality::NotEq)?;
+            let payloads = match diff.change {
+                CaseChange::PayloadChanged(_) => {
+                    let ca = va.cases.iter().find(|c| c.name == diff.name).unwrap();
+                    let cb = vb.cases.iter().find(|c| c.name == diff.name).unwrap();
+                    ca.tref.as_ref().zip(cb.tref.as_ref())
+                }
+                _ => None,
+            };
+            payloads
+                .and_then(|(pa, pb)| nested(PathSegment::Case(diff.name.clone()), pa, pb, path))
+                .or(Some(DivergenceReason::Case(diff)))
+        }
+        (Type::List(ea), Type::List(eb)) => nested(PathSegment::Element, ea, eb, path),
+        (Type::Pointer(pa), Type::Pointer(pb))
+        | (Type::ConstPointer(pa), Type::ConstPointer(pb))
+        | (Type::ConstPointer(pa), Type::Pointer(pb)) => nested(PathSegment::Pointee, pa, pb, path),
+        _ => None,
+    };
+    reason.or_else(|| {
+        Some(DivergenceReason::Types {
+            from: describe(a.type_()),
+            to: describe(b.type_()),
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(doc: &str) -> RecordDatatype {
+        let doc = crate::parse(doc).unwrap();
+        match &**doc.typename(&Id::new("r")).unwrap().type_() {
+            Type::Record(r) => r.clone(),
+            _ => panic!("not a record"),
+        }
+    }
+
+    #[test]
+    fn record_mismatch() {
+        let old = record("(typename $r (record (field $a u8) (field $b u32)))");
+        let cases = vec![
+            (
+                "(typename $r (record (field $a u8)))",
+                RecordMismatch::Removed {
+                    member: Id::new("b"),
+                },
+            ),
+            (
+                "(typename $r (record (field $c u8) (field $b u32)))",
+                RecordMismatch::Renamed {
+                    member: Id::new("a"),
+                    by: Id::new("c"),
+                },
+            ),
+            (
+                "(typename $r (record (field $a u8) (field $b f32)))",
+                RecordMismatch::Type {
+                    member: Id::new("b"),
+                },
+            ),
+            (
+                "(typename $r (record (field $a u64) (field $b u32)))",
+                RecordMismatch::Type {
+                    member: Id::new("a"),
+                },
+            ),
+            (
+                "(typename $r (record (field $a u8) (field $b u64)))",
+                RecordMismatch::Offset {
+                    member: Id::new("b"),
+                    offset: 4,
+                    by: 8,
+                },
+            ),
+        ];
+        for (new, mismatch) in cases {
+            assert_eq!(old.member_representable(&record(new)), Err(mismatch));
+        }
+        assert_eq!(
+            old.member_representable(&record(
+                "(typename $r (record (field $a u8) (field $b u32) (field $c u8)))"
+            )),
+            Ok(RepEquality::Superset)
+        );
+    }
+
+    #[test]
+    fn divergence() {
+        let old = crate::parse(
+            "(typename $tensor (record (field $dimensions (list u64)) (field $data (list u8))))",
+        )
+        .unwrap();
+        let new = crate::parse(
+            "(typename $tensor (record (field $dimensions (list u32)) (field $data (list u8))))",
+        )
+        .unwrap();
+        let tensor =
+            |doc: &crate::Document| TypeRef::Name(doc.typename(&Id::new("tensor")).unwrap());
+        let divergence = tensor(&old)
+            .divergence_in(&tensor(&new), &RepContext::new())
+            .unwrap();
+        assert_eq!(
+            divergence.path,
+            vec![
+                PathSegment::Type(Id::new("tensor")),
+                PathSegment::Member(Id::new("dimensions")),
+                PathSegment::Element,
+            ]
+        );
+        assert_eq!(
+            divergence.to_string(),
+            "tensor.dimensions: list element u64 vs u32"
+        );
+        assert_eq!(
+            tensor(&old).divergence_in(&tensor(&old), &RepContext::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn memoized() {
+        let doc = crate::parse(
+            "(typename $e (enum $a $b))
+             (typename $r (record (field $x $e) (field $y $e)))",
+        )
+        .unwrap();
+        let r = TypeRef::Name(doc.typename(&Id::new("r")).unwrap());
+        let cx = RepContext::new().memoized();
+        assert_eq!(r.representable_in(&r, &cx), RepEquality::Eq);
+        // `r` and `e`, each by name and by definition: the second member
+        // is found in the cache.
+        assert_eq!(cx.cache.as_ref().unwrap().borrow().len(), 4);
+        assert_eq!(r.representable_in(&r, &cx), RepEquality::Eq);
+    }
+}
@@ -0,0 +1,228 @@
+//! Checks that printing a document and parsing the text back gives the same
+//! document.
+//!
+//! A [`Corpus`] is a list of named documents to check, each loaded from witx
+//! files or parsed from source. [`Corpus::phases`] registers every phase of
+//! the WASI repository, wasi-nn's witx files among them, so a file added to a
+//! phase is covered without registering it:
+//!
+//! ```no_run
+//! let corpus = witx::roundtrip::Corpus::phases().unwrap();
+//! corpus.assert_roundtrip();
+//! ```
+
+use crate::phases::Phase;
+use crate::{Document, WitxError};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RoundtripError {
+    #[error("Failed to load {name}")]
+    Load {
+        name: String,
+        #[source]
+        error: Box<WitxError>,
+    },
+    #[error("Failed to parse {name} as printed:\n{printed}")]
+    Reparse {
+        name: String,
+        printed: String,
+        #[source]
+        error: Box<WitxError>,
+    },
+    #[error("{name} changed when printed and parsed back, at {item}:\n{before}\n   !=\n{after}")]
+    Mismatch {
+        name: String,
+        /// The first item that differs, like `typename $errno`.
+        item: String,
+        before: String,
+        after: String,
+    },
+}
+
+/// Prints `doc`, parses it back and checks the result is equal to `doc`,
+/// reporting the first item that differs otherwise. `name` identifies the
+/// document in errors.
+pub fn check(name: &str, doc: &Document) -> Result<(), RoundtripError> {
+    let printed = doc.to_string();
+    let reparsed = crate::parse(&printed).map_err(|error| RoundtripError::Reparse {
+        name: name.to_string(),
+        printed: printed.clone(),
+        error: Box::new(error),
+    })?;
+    if *doc == reparsed {
+        return Ok(());
+    }
+    let mismatch = |item: String, before: &dyn fmt::Debug, after: &dyn fmt::Debug| {
+        Err(RoundtripError::Mismatch {
+            name: name.to_string(),
+            item,
+            before: format!("{:?}", before),
+            after: format!("{:?}", after),
+        })
+    };
+    for nt in doc.typenames() {
+        let other = reparsed.typename(&nt.name);
+        if other.as_ref() != Some(&nt) {
+            return mismatch(format!("typename ${}", nt.name.as_str()), &nt, &other);
+        }
+    }
+    for m in doc.modules() {
+        let item = format!("module ${}", m.name.as_str());
+        let other = match reparsed.module(&m.name) {
+            Some(other) => other,
+            None => return mismatch(item, &m.name, &None::<Document>),
+        };
+        for import in m.imports() {
+            let other = other.import(&import.name);
+            if other.as_ref() != Some(&import) {
+                return mismatch(
+                    format!("{} import ${}", item, import.name.as_str()),
+                    &import,
+                    &other,
+                );
+            }
+        }
+        for func in m.funcs() {
+            let other = other.func(&func.name);
+            if other.as_ref() != Some(&func) {
+                return mismatch(
+                    format!("{} func ${}", item, func.name.as_str()),
+                    &func,
+                    &other,
+                );
+            }
+        }
+    }
+    mismatch("the document".to_string(), doc, &reparsed)
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Files(Vec<PathBuf>),
+    Text(String),
+}
+
+/// Named documents to check, in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct Corpus {
+    entries: Vec<(String, Source)>,
+}
+
+impl Corpus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every phase of the WASI repository, by phase name.
+    pub fn phases() -> anyhow::Result<Self> {
+        let mut corpus = Self::new();
+        for phase in crate::phases::available()? {
+            corpus.add_phase(&phase)?;
+        }
+        Ok(corpus)
+    }
+
+    /// Add the document loaded from `paths` as `name`.
+    pub fn add_files<P: AsRef<Path>>(&mut self, name: &str, paths: &[P]) {
+        let paths = paths.iter().map(|p| p.as_ref().to_path_buf()).collect();
+        self.entries.push((name.to_string(), Source::Files(paths)));
+    }
+
+    /// Add the document parsed from `source` as `name`.
+    pub fn add_source(&mut self, name: &str, source: &str) {
+        self.entries
+            .push((name.to_string(), Source::Text(source.to_string())));
+    }
+
+    /// Add the witx files of `phase`, as it has them now, as one document.
+    pub fn add_phase(&mut self, phase: &Phase) -> Result<(), WitxError> {
+        let files = crate::workspace::witx_files(&phase.path)?;
+        self.add_files(&phase.name, &files);
+        Ok(())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(n, _)| n.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Checks every document, returning the failures.
+    pub fn check(&self) -> Vec<RoundtripError> {
+        self.entries
+            .iter()
+            .filter_map(|(name, source)| {
+                let doc = match source {
+                    Source::Files(paths) => crate::load(paths),
+                    Source::Text(text) => crate::parse(text),
+                };
+                let result = doc
+                    .map_err(|error| RoundtripError::Load {
+                        name: name.clone(),
+                        error: Box::new(error),
+                    })
+                    .and_then(|doc| check(name, &doc));
+                result.err()
+            })
+            .collect()
+    }
+
+    /// Checks every document, panicking with the failures, if any.
+    pub fn assert_roundtrip(&self) {
+        let failures = self.check();
+        if !failures.is_empty() {
+            let report = failures
+                .iter()
+                .map(|e| match std::error::Error::source(e) {
+                    Some(source) => format!("{}: {}", e, source),
+                    None => e.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            panic!(
+                "{} of {} documents failed to round-trip:\n\n{}",
+                failures.len(),
+                self.len(),
+                report
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn phases() {
+        let corpus = Corpus::phases().unwrap();
+        assert!(corpus.names().any(|n| n == "ephemeral"));
+        corpus.assert_roundtrip();
+    }
+
+    #[test]
+    fn sources() {
+        let mut corpus = Corpus::new();
+        corpus.add_source(
+            "nn",
+            "(typename $errno (enum (@witx tag u16) $success $inval))
+             (module $nn (@interface func (export \"f\") (result $e (expected (error $errno)))))",
+        );
+        corpus.add_source("broken", "(typename $t (list $missing))");
+        let failures = corpus.check();
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(
+            &failures[0],
+            RoundtripError::Load { name, .. } if name == "broken"
+        ));
+    }
+}
@@ -0,0 +1,199 @@
+//! `serde` support for documents, enabled with the `serde` feature.
+//!
+//! Most of the ast derives its implementations. Named types are shared with
+//! `Rc`s, so a type referring to a named type serializes as just its name, and
+//! a named type is defined where its `typename` definition appears. Names are
+//! resolved against the typenames already read while deserializing a
+//! document, which always works for a serialized document because a type can
+//! only refer to types defined before it. A `TypeRef` naming a type can't be
+//! deserialized outside of a document.
+//!
+//! Deserialized documents aren't validated.
+
+use crate::{
+    Constant, Definition, Document, Entry, Id, Meta, Module, ModuleDefinition, ModuleEntry,
+    NamedType, Type, TypeRef, WitxVersion,
+};
+use serde::de::Error as _;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+thread_local! {
+    /// The named types defined so far in each document being deserialized.
+    static SCOPES: RefCell<Vec<HashMap<Id, Rc<NamedType>>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The typenames of a document being deserialized, for as long as this lives.
+struct Scope;
+
+impl Scope {
+    fn enter() -> Self {
+        SCOPES.with(|s| s.borrow_mut().push(HashMap::new()));
+        Scope
+    }
+
+    fn define(nt: &Rc<NamedType>) {
+        SCOPES.with(|s| {
+            if let Some(scope) = s.borrow_mut().last_mut() {
+                scope.insert(nt.name.clone(), nt.clone());
+            }
+        })
+    }
+
+    fn get(name: &Id) -> Option<Rc<NamedType>> {
+        SCOPES.with(|s| s.borrow().last().and_then(|scope| scope.get(name).cloned()))
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        SCOPES.with(|s| s.borrow_mut().pop());
+    }
+}
+
+impl Serialize for TypeRef {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TypeRef::Name(nt) => {
+                serializer.serialize_newtype_variant("TypeRef", 0, "name", &nt.name)
+            }
+            TypeRef::Value(ty) => {
+                serializer.serialize_newtype_variant("TypeRef", 1, "value", &**ty)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "TypeRef", rename_all = "snake_case")]
+enum TypeRefRepr {
+    Name(Id),
+    Value(Rc<Type>),
+}
+
+impl<'de> Deserialize<'de> for TypeRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match TypeRefRepr::deserialize(deserializer)? {
+            TypeRefRepr::Name(name) => Scope::get(&name)
+                .map(TypeRef::Name)
+                .ok_or_else(|| D::Error::custom(format!("unknown type name `{}`", name.as_str()))),
+            TypeRefRepr::Value(ty) => Ok(TypeRef::Value(ty)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Definition", rename_all = "snake_case")]
+enum DefinitionRepr {
+    Typename(Rc<NamedType>),
+    Module(Rc<Module>),
+    Constant(Constant),
+}
+
+impl<'de> Deserialize<'de> for Definition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match DefinitionRepr::deserialize(deserializer)? {
+            DefinitionRepr::Typename(nt) => {
+                Scope::define(&nt);
+                Definition::Typename(nt)
+            }
+            DefinitionRepr::Module(m) => Definition::Module(m),
+            DefinitionRepr::Constant(c) => Definition::Constant(c),
+        })
+    }
+}
+
+impl Serialize for Document {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Document", 2)?;
+        s.serialize_field("definitions", self.definitions())?;
+        s.serialize_field("version", &self.version())?;
+        s.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Document")]
+struct DocumentRepr {
+    definitions: Vec<Definition>,
+    version: WitxVersion,
+}
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let DocumentRepr {
+            definitions,
+            version,
+        } = {
+            let _scope = Scope::enter();
+            DocumentRepr::deserialize(deserializer)?
+        };
+        let mut entries = HashMap::new();
+        for d in definitions.iter() {
+            let (name, entry) = match d {
+                Definition::Typename(nt) => (nt.name.clone(), Entry::Typename(Rc::downgrade(nt))),
+                Definition::Module(m) => (m.name.clone(), Entry::Module(Rc::downgrade(m))),
+                Definition::Constant(_) => continue,
+            };
+            if entries.insert(name.clone(), entry).is_some() {
+                return Err(D::Error::custom(format!(
+                    "`{}` is defined more than once",
+                    name.as_str()
+                )));
+            }
+        }
+        Ok(Document::new(definitions, entries, version))
+    }
+}
+
+impl Serialize for Module {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Module", 4)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("definitions", self.definitions())?;
+        s.serialize_field("docs", &self.docs)?;
+        s.serialize_field("meta", &self.meta)?;
+        s.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Module")]
+struct ModuleRepr {
+    name: Id,
+    definitions: Vec<ModuleDefinition>,
+    docs: String,
+    meta: Vec<Meta>,
+}
+
+impl<'de> Deserialize<'de> for Module {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = ModuleRepr::deserialize(deserializer)?;
+        let mut entries = HashMap::new();
+        for d in repr.definitions.iter() {
+            let (name, entry) = match d {
+                ModuleDefinition::Import(i) => {
+                    (i.name.clone(), ModuleEntry::Import(Rc::downgrade(i)))
+                }
+                ModuleDefinition::Func(f) => (f.name.clone(), ModuleEntry::Func(Rc::downgrade(f))),
+            };
+            if entries.insert(name.clone(), entry).is_some() {
+                return Err(D::Error::custom(format!(
+                    "`{}` is defined more than once in module `{}`",
+                    name.as_str(),
+                    repr.name.as_str()
+                )));
+            }
+        }
+        Ok(Module::new(
+            repr.name,
+            repr.definitions,
+            entries,
+            repr.docs,
+            repr.meta,
+        ))
+    }
+}
@@ -0,0 +1,277 @@
+use crate::ast::*;
+
+/// The structure of a type, with every alias resolved, down to builtins.
+///
+/// A type's shape tells a backend what it is without chasing a `TypeRef`
+/// through the names it's defined with: a field of type `$dims`, an alias
+/// of `$tensor_dimensions`, has the shape of a list of `u32`. Nominal
+/// shapes (records, flags, enums, variants and handles) keep the name of
+/// the type that defines them, after any aliases.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Shape {
+    Builtin(BuiltinType),
+    /// A variant with the two empty cases `false` and `true`.
+    Bool,
+    /// A list of `char`.
+    String,
+    List(Box<Shape>),
+    Pointer(Box<Shape>),
+    ConstPointer(Box<Shape>),
+    Record(RecordShape),
+    Tuple(Vec<Shape>),
+    Flags(FlagsShape),
+    Enum(EnumShape),
+    Variant(VariantShape),
+    Handle {
+        /// The name of the resource, unless the handle is anonymous.
+        resource: Option<Id>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecordShape {
+    pub name: Option<Id>,
+    pub fields: Vec<FieldShape>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldShape {
+    pub name: Id,
+    pub shape: Shape,
+    /// The length of a list field, in terms of the other fields.
+    pub size: Option<SizeExpr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FlagsShape {
+    pub name: Option<Id>,
+    pub repr: IntRepr,
+    /// Each flag with its bit.
+    pub flags: Vec<(Id, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EnumShape {
+    pub name: Option<Id>,
+    pub repr: IntRepr,
+    /// Each case with its value.
+    pub cases: Vec<(Id, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VariantShape {
+    pub name: Option<Id>,
+    pub tag_repr: IntRepr,
+    pub cases: Vec<CaseShape>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CaseShape {
+    pub name: Id,
+    pub payload: Option<Shape>,
+}
+
+impl Shape {
+    /// The name of the type defining a nominal shape.
+    pub fn name(&self) -> Option<&Id> {
+        match self {
+            Shape::Record(r) => r.name.as_ref(),
+            Shape::Flags(f) => f.name.as_ref(),
+            Shape::Enum(e) => e.name.as_ref(),
+            Shape::Variant(v) => v.name.as_ref(),
+            Shape::Handle { resource } => resource.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn of(name: Option<&Id>, ty: &Type) -> Shape {
+        let name = name.cloned();
+        let boxed = |tref: &TypeRef| Box::new(tref.shape());
+        match ty {
+            Type::Builtin(b) => Shape::Builtin(*b),
+            Type::List(elem) => match &**elem.type_() {
+                Type::Builtin(BuiltinType::Char) => Shape::String,
+                _ => Shape::List(boxed(elem)),
+            },
+            Type::Pointer(tref) => Shape::Pointer(boxed(tref)),
+            Type::ConstPointer(tref) => Shape::ConstPointer(boxed(tref)),
+            Type::Handle(_) => Shape::Handle { resource: name },
+            Type::Record(r) => match r.kind {
+                RecordKind::Tuple => {
+                    Shape::Tuple(r.members.iter().map(|m| m.tref.shape()).collect())
+                }
+                RecordKind::Bitflags(repr) => Shape::Flags(FlagsShape {
+                    name,
+                    repr,
+                    flags: r
+                        .members
+                        .iter()
+                        .enumerate()
+                        .map(|(i, m)| (m.name.clone(), 1 << i))
+                        .collect(),
+                }),
+                RecordKind::Other => Shape::Record(RecordShape {
+                    name,
+                    fields: r
+                        .members
+                        .iter()
+                        .map(|m| FieldShape {
+                            name: m.name.clone(),
+                            shape: m.tref.shape(),
+                            size: m.size.clone(),
+                        })
+                        .collect(),
+                }),
+            },
+            Type::Variant(v) if v.is_bool() => Shape::Bool,
+            Type::Variant(v) if v.is_enum() => Shape::Enum(EnumShape {
+                name,
+                repr: v.tag_repr,
+                cases: v
+                    .cases
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| (c.name.clone(), i as u64))
+                    .collect(),
+            }),
+            Type::Variant(v) => Shape::Variant(VariantShape {
+                name,
+                tag_repr: v.tag_repr,
+                cases: v
+                    .cases
+                    .iter()
+                    .map(|c| CaseShape {
+                        name: c.name.clone(),
+                        payload: c.tref.as_ref().map(|t| t.shape()),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl TypeRef {
+    /// The named type this refers to, after any aliases, unless it's
+    /// anonymous.
+    pub fn definition(&self) -> Option<&NamedType> {
+        match self {
+            TypeRef::Name(nt) => Some(nt.definition()),
+            TypeRef::Value(_) => None,
+        }
+    }
+
+    pub fn shape(&self) -> Shape {
+        match self {
+            TypeRef::Name(nt) => nt.shape(),
+            TypeRef::Value(ty) => Shape::of(None, ty),
+        }
+    }
+}
+
+impl NamedType {
+    /// The type at the end of this type's chain of aliases, which defines
+    /// its structure: `self` itself unless it's an alias.
+    pub fn definition(&self) -> &NamedType {
+        let mut nt = self;
+        while let TypeRef::Name(next) = &nt.tref {
+            nt = next;
+        }
+        nt
+    }
+
+    pub fn shape(&self) -> Shape {
+        let def = self.definition();
+        Shape::of(Some(&def.name), def.type_())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aliases() {
+        let doc = crate::parse(
+            "(typename $dims (list u32))
+             (typename $dimensions $dims)
+             (typename $kind (enum (@witx tag u8) $fp16 $fp32))
+             (typename $element $kind)
+             (typename $graph (handle))
+             (typename $graph_alias $graph)
+             (typename $tensor
+               (record
+                 (field $dimensions $dimensions)
+                 (field $type $element)
+                 (field $data (list u8))))
+             (typename $options (flags (@witx repr u8) $a $b))
+             (typename $result (expected $graph_alias (error $element)))
+             (typename $pair (tuple string bool))",
+        )
+        .unwrap();
+        let shape = |name| doc.typename(&Id::new(name)).unwrap().shape();
+        let dims = Shape::List(Box::new(Shape::Builtin(BuiltinType::U32 {
+            lang_ptr_size: false,
+        })));
+        let kind = Shape::Enum(EnumShape {
+            name: Some(Id::new("kind")),
+            repr: IntRepr::U8,
+            cases: vec![(Id::new("fp16"), 0), (Id::new("fp32"), 1)],
+        });
+        let graph = Shape::Handle {
+            resource: Some(Id::new("graph")),
+        };
+
+        assert_eq!(shape("dimensions"), dims);
+        assert_eq!(shape("element"), kind);
+        assert_eq!(shape("graph_alias"), graph);
+        assert_eq!(
+            shape("tensor"),
+            Shape::Record(RecordShape {
+                name: Some(Id::new("tensor")),
+                fields: vec![
+                    FieldShape {
+                        name: Id::new("dimensions"),
+                        shape: dims,
+                        size: None,
+                    },
+                    FieldShape {
+                        name: Id::new("type"),
+                        shape: kind.clone(),
+                        size: None,
+                    },
+                    FieldShape {
+                        name: Id::new("data"),
+                        shape: Shape::List(Box::new(Shape::Builtin(BuiltinType::U8 {
+                            lang_c_char: false,
+                        }))),
+                        size: None,
+                    },
+                ],
+            })
+        );
+        assert_eq!(
+            shape("options"),
+            Shape::Flags(FlagsShape {
+                name: Some(Id::new("options")),
+                repr: IntRepr::U8,
+                flags: vec![(Id::new("a"), 1), (Id::new("b"), 2)],
+            })
+        );
+        match shape("result") {
+            Shape::Variant(v) => {
+                assert_eq!(v.name, Some(Id::new("result")));
+                assert_eq!(v.cases[0].payload, Some(graph));
+                assert_eq!(v.cases[1].payload, Some(kind));
+            }
+            s => panic!("not a variant: {:?}", s),
+        }
+        assert_eq!(
+            shape("pair"),
+            Shape::Tuple(vec![Shape::String, Shape::Bool])
+        );
+
+        let element = doc.typename(&Id::new("element")).unwrap();
+        assert_eq!(element.definition().name, Id::new("kind"));
+        assert_eq!(element.shape().name(), Some(&Id::new("kind")));
+    }
+}
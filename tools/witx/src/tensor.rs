@@ -0,0 +1,315 @@
+//! Element types of tensors, and the size of tensor data.
+//!
+//! Tensors are passed as a list of dimensions, an element type, and a buffer
+//! of little-endian elements whose length must match both. Element types are
+//! the cases of wasi-nn's `tensor_type` enum, named after the type: `f16`,
+//! `f32`, `u8` and `i32`. Quantized models store elements in fewer bits than a
+//! byte, so `q<bits>`, such as `q4`, names a type of unsigned integers packed
+//! `bits` at a time, starting from the least significant bit of each byte.
+
+use crate::{NamedType, Type};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The enum whose cases are element types.
+pub const TENSOR_TYPE: &str = "tensor_type";
+
+/// The type of a tensor's elements.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ElementType {
+    F16,
+    F32,
+    U8,
+    I32,
+    /// Unsigned integers of `bits` bits, packed without padding.
+    Quantized {
+        bits: u8,
+    },
+}
+
+impl ElementType {
+    /// Size of an element in bits.
+    pub fn bits(&self) -> u32 {
+        match self {
+            ElementType::F16 => 16,
+            ElementType::F32 | ElementType::I32 => 32,
+            ElementType::U8 => 8,
+            ElementType::Quantized { bits } => u32::from(*bits),
+        }
+    }
+
+    /// Number of elements in a tensor with `dimensions`, or `None` if it
+    /// overflows.
+    pub fn elements(dimensions: &[u32]) -> Option<usize> {
+        dimensions
+            .iter()
+            .try_fold(1usize, |n, d| n.checked_mul(*d as usize))
+    }
+
+    /// Bytes of data in a tensor with `dimensions`, or `None` if it
+    /// overflows. Packed elements are padded to a whole byte.
+    pub fn buffer_size(&self, dimensions: &[u32]) -> Option<usize> {
+        let bits = Self::elements(dimensions)?.checked_mul(self.bits() as usize)?;
+        Some(bits / 8 + (bits % 8 != 0) as usize)
+    }
+
+    /// Check that `len` bytes is the size of a tensor with `dimensions`.
+    pub fn validate_buffer(&self, dimensions: &[u32], len: usize) -> Result<(), TensorError> {
+        match self.buffer_size(dimensions) {
+            Some(expected) if expected == len => Ok(()),
+            Some(expected) => Err(TensorError::BufferSize {
+                ty: *self,
+                expected,
+                actual: len,
+            }),
+            None => Err(TensorError::Overflow(dimensions.to_vec())),
+        }
+    }
+
+    /// The little-endian encoding of `values`. Values are converted as by
+    /// `as`, so out of range integers are saturated.
+    pub fn encode(&self, values: &[f64]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (i, v) in values.iter().enumerate() {
+            match self {
+                ElementType::F16 => out.extend_from_slice(&f32_to_f16(*v as f32).to_le_bytes()),
+                ElementType::F32 => out.extend_from_slice(&(*v as f32).to_le_bytes()),
+                ElementType::U8 => out.push(*v as u8),
+                ElementType::I32 => out.extend_from_slice(&(*v as i32).to_le_bytes()),
+                ElementType::Quantized { bits } => {
+                    let bits = usize::from(*bits);
+                    let value = (*v as u64).min((1 << bits) - 1);
+                    for bit in 0..bits {
+                        let at = i * bits + bit;
+                        if at / 8 == out.len() {
+                            out.push(0);
+                        }
+                        out[at / 8] |= (((value >> bit) & 1) as u8) << (at % 8);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// The first `len` elements encoded in `bytes`, which must hold at least
+    /// that many.
+    pub fn decode(&self, bytes: &[u8], len: usize) -> Vec<f64> {
+        let size = self.bits() as usize / 8;
+        (0..len)
+            .map(|i| match self {
+                ElementType::F16 => {
+                    let b = &bytes[i * size..];
+                    f64::from(f16_to_f32(u16::from_le_bytes([b[0], b[1]])))
+                }
+                ElementType::F32 => {
+                    let b = &bytes[i * size..];
+                    f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                }
+                ElementType::U8 => f64::from(bytes[i]),
+                ElementType::I32 => {
+                    let b = &bytes[i * size..];
+                    f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                }
+                ElementType::Quantized { bits } => {
+                    let bits = usize::from(*bits);
+                    let mut value = 0u64;
+                    for bit in 0..bits {
+                        let at = i * bits + bit;
+                        value |= u64::from((bytes[at / 8] >> (at % 8)) & 1) << bit;
+                    }
+                    value as f64
+                }
+            })
+            .collect()
+    }
+
+    /// The element types of the cases of `nt`, in order, if it's the
+    /// [`TENSOR_TYPE`] enum and every case names an element type.
+    pub fn cases_of(nt: &NamedType) -> Option<Vec<ElementType>> {
+        if nt.name.as_str() != TENSOR_TYPE {
+            return None;
+        }
+        match &**nt.type_() {
+            Type::Variant(v) if v.is_enum() => v
+                .cases
+                .iter()
+                .map(|c| c.name.as_str().parse().ok())
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ElementType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ElementType::F16 => f.write_str("f16"),
+            ElementType::F32 => f.write_str("f32"),
+            ElementType::U8 => f.write_str("u8"),
+            ElementType::I32 => f.write_str("i32"),
+            ElementType::Quantized { bits } => write!(f, "q{}", bits),
+        }
+    }
+}
+
+impl FromStr for ElementType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f16" => Ok(ElementType::F16),
+            "f32" => Ok(ElementType::F32),
+            "u8" => Ok(ElementType::U8),
+            "i32" => Ok(ElementType::I32),
+            _ => match s.strip_prefix('q').and_then(|b| b.parse::<u8>().ok()) {
+                Some(bits) if (1..=32).contains(&bits) => Ok(ElementType::Quantized { bits }),
+                _ => Err(format!("unknown tensor element type `{}`", s)),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TensorError {
+    #[error("the size of a tensor with dimensions {0:?} overflows")]
+    Overflow(Vec<u32>),
+    #[error(
+        "tensor of {ty} elements has {actual} bytes of data, but its dimensions require {expected}"
+    )]
+    BufferSize {
+        ty: ElementType,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = u32::from(h >> 15) << 31;
+    let exp = u32::from((h >> 10) & 0x1f);
+    let man = u32::from(h & 0x3ff);
+    let bits = match (exp, man) {
+        (0, 0) => sign,
+        // Subnormal: normalize the mantissa.
+        (0, _) => {
+            let shift = man.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | (((man << shift) & 0x3ff) << 13)
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (man << 13),
+        _ => sign | ((exp + 112) << 23) | (man << 13),
+    };
+    f32::from_bits(bits)
+}
+
+fn f32_to_f16(f: f32) -> u16 {
+    let bits = f.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let man = bits & 0x7f_ffff;
+    if exp == 0xff {
+        let nan = if man != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let exp = exp - 127 + 15;
+    if exp >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // Subnormals keep fewer bits of the mantissa, with its implicit one.
+    let (man, shift, exp) = if exp <= 0 {
+        if exp < -10 {
+            return sign;
+        }
+        (man | 0x80_0000, (14 - exp) as u32, 0)
+    } else {
+        (man, 13, exp as u32)
+    };
+    let kept = (exp << 10) | (man >> shift);
+    let dropped = man & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    // Round to nearest, ties to even, carrying into the exponent if needed.
+    let up = dropped > half || (dropped == half && kept & 1 == 1);
+    sign | (kept + up as u32) as u16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        for ty in ["f16", "f32", "u8", "i32", "q4", "q1"].iter() {
+            assert_eq!(ty.parse::<ElementType>().unwrap().to_string(), *ty);
+        }
+        assert!("q0".parse::<ElementType>().is_err());
+        assert!("q33".parse::<ElementType>().is_err());
+        assert!("f64".parse::<ElementType>().is_err());
+
+        let doc = crate::parse("(typename $tensor_type (enum (@witx tag u8) $f16 $q4))").unwrap();
+        let nt = doc.typename(&crate::Id::new("tensor_type")).unwrap();
+        assert_eq!(
+            ElementType::cases_of(&nt),
+            Some(vec![ElementType::F16, ElementType::Quantized { bits: 4 }])
+        );
+    }
+
+    #[test]
+    fn buffer_size() {
+        let q4 = ElementType::Quantized { bits: 4 };
+        assert_eq!(
+            ElementType::F32.buffer_size(&[1, 3, 224, 224]),
+            Some(602112)
+        );
+        assert_eq!(ElementType::F16.buffer_size(&[]), Some(2));
+        assert_eq!(q4.buffer_size(&[3]), Some(2));
+        assert_eq!(ElementType::U8.buffer_size(&[2, 0]), Some(0));
+        assert_eq!(
+            ElementType::I32.buffer_size(&[u32::MAX, u32::MAX, u32::MAX]),
+            None
+        );
+        assert_eq!(ElementType::U8.validate_buffer(&[2, 2], 4), Ok(()));
+        assert_eq!(
+            q4.validate_buffer(&[5], 2),
+            Err(TensorError::BufferSize {
+                ty: q4,
+                expected: 3,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        let q3 = ElementType::Quantized { bits: 3 };
+        let bytes = q3.encode(&[1.0, 7.0, 2.0, 9.0]);
+        assert_eq!(bytes, [0b1011_1001, 0b0000_1110]);
+        assert_eq!(q3.decode(&bytes, 4), [1.0, 7.0, 2.0, 7.0]);
+        let values = [0.5, -1.0, 3.0];
+        for ty in [ElementType::F16, ElementType::F32].iter() {
+            assert_eq!(ty.decode(&ty.encode(&values), 3), values);
+        }
+        assert_eq!(ElementType::I32.encode(&[-1.0]), [0xff; 4]);
+    }
+
+    #[test]
+    fn f16() {
+        // Including the smallest normal and subnormal numbers.
+        let values = [
+            0.0,
+            1.0,
+            -2.5,
+            65504.0,
+            2f32.powi(-14),
+            3.0 * 2f32.powi(-24),
+        ];
+        for v in values.iter() {
+            assert_eq!(f16_to_f32(f32_to_f16(*v)), *v);
+        }
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        assert_eq!(f16_to_f32(f32_to_f16(1e6)), f32::INFINITY);
+        // Ties round to even, in normal and subnormal numbers.
+        assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert_eq!(f32_to_f16(2f32.powi(-25)), 0);
+        assert_eq!(f32_to_f16(3.0 * 2f32.powi(-25)), 2);
+    }
+}
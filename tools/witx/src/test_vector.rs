@@ -0,0 +1,442 @@
+//! Golden test vectors for inference results.
+//!
+//! A test vector names a model and the encoding and execution target to load
+//! it with, the tensors to set as inputs, and the outputs inference must
+//! produce, each with the tolerance its elements are compared with. Vectors
+//! are written as s-expressions, like witx documents:
+//!
+//! ```text
+//! (test_vector "mobilenet"
+//!   (model (encoding "openvino") (target "cpu") (file "mobilenet.xml") (file "mobilenet.bin"))
+//!   (input 0 f32 (dimensions 1 3 224 224) (file "tensor-1x3x224x224-f32.bgr"))
+//!   (output 0 f32 (dimensions 1 1001) (file "expected.bin") (tolerance 0.0001)))
+//! ```
+//!
+//! Tensor data is either listed inline with `(data ...)`, or read from a
+//! file of little-endian elements. Paths are relative to the directory of
+//! the vector file.
+
+use crate::render::SExpr;
+use crate::tensor::{ElementType, TensorError};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use wast::parser::{Parse, Parser, Result};
+
+mod kw {
+    wast::custom_keyword!(data);
+    wast::custom_keyword!(dimensions);
+    wast::custom_keyword!(encoding);
+    wast::custom_keyword!(file);
+    wast::custom_keyword!(input);
+    wast::custom_keyword!(model);
+    wast::custom_keyword!(output);
+    wast::custom_keyword!(target);
+    wast::custom_keyword!(test_vector);
+    wast::custom_keyword!(tolerance);
+}
+
+#[derive(Debug, Error)]
+pub enum TestVectorError {
+    #[error("IO error with file {0:?}")]
+    Io(PathBuf, #[source] std::io::Error),
+    #[error("Parse error")]
+    Parse(#[from] wast::Error),
+    #[error("tensor {index}: {error}")]
+    Tensor {
+        index: u32,
+        #[source]
+        error: TensorError,
+    },
+    #[error("output {index} element {element} is {actual}, expected {expected}")]
+    Mismatch {
+        index: u32,
+        element: usize,
+        expected: f64,
+        actual: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestVector {
+    pub name: String,
+    pub model: Model,
+    pub inputs: Vec<Tensor>,
+    pub outputs: Vec<ExpectedOutput>,
+}
+
+/// The model to load, as passed to `load`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Model {
+    /// Case of `graph_encoding`.
+    pub encoding: String,
+    /// Case of `execution_target`.
+    pub target: String,
+    /// One file per graph builder.
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TensorData {
+    /// Element values.
+    Inline(Vec<f64>),
+    /// A file of little-endian elements.
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    /// Index of the input or output.
+    pub index: u32,
+    pub ty: ElementType,
+    pub dimensions: Vec<u32>,
+    pub data: TensorData,
+}
+
+impl Tensor {
+    /// Number of elements given by the dimensions.
+    pub fn len(&self) -> usize {
+        ElementType::elements(&self.dimensions).unwrap_or(usize::MAX)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check that `bytes` is the right amount of data for the tensor.
+    pub fn validate(&self, bytes: &[u8]) -> std::result::Result<(), TestVectorError> {
+        self.ty
+            .validate_buffer(&self.dimensions, bytes.len())
+            .map_err(|error| TestVectorError::Tensor {
+                index: self.index,
+                error,
+            })
+    }
+
+    /// The tensor's data as passed to `set_input`, reading files relative to
+    /// `base`.
+    pub fn bytes(&self, base: &Path) -> std::result::Result<Vec<u8>, TestVectorError> {
+        let bytes = match &self.data {
+            TensorData::Inline(values) => self.ty.encode(values),
+            TensorData::File(path) => {
+                let path = base.join(path);
+                fs::read(&path).map_err(|e| TestVectorError::Io(path, e))?
+            }
+        };
+        self.validate(&bytes)?;
+        Ok(bytes)
+    }
+
+    /// The tensor's elements, reading files relative to `base`.
+    pub fn values(&self, base: &Path) -> std::result::Result<Vec<f64>, TestVectorError> {
+        let bytes = self.bytes(base)?;
+        Ok(self.ty.decode(&bytes, self.len()))
+    }
+}
+
+/// An output of inference, and how close the actual output must be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedOutput {
+    pub tensor: Tensor,
+    /// Largest absolute difference allowed between expected and actual
+    /// elements.
+    pub tolerance: f64,
+}
+
+impl ExpectedOutput {
+    /// Check `actual`, the bytes written by `get_output`, against the
+    /// expected tensor, reading files relative to `base`.
+    pub fn check(&self, actual: &[u8], base: &Path) -> std::result::Result<(), TestVectorError> {
+        let expected = self.tensor.values(base)?;
+        self.tensor.validate(actual)?;
+        let actual = self.tensor.ty.decode(actual, expected.len());
+        for (element, (e, a)) in expected.iter().zip(actual).enumerate() {
+            let same = (e.is_nan() && a.is_nan()) || (e - a).abs() <= self.tolerance || *e == a;
+            if !same {
+                return Err(TestVectorError::Mismatch {
+                    index: self.tensor.index,
+                    element,
+                    expected: *e,
+                    actual: a,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TestVector {
+    /// Load a test vector from the filesystem.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::result::Result<Self, TestVectorError> {
+        let path = path.as_ref();
+        let source =
+            fs::read_to_string(path).map_err(|e| TestVectorError::Io(path.to_path_buf(), e))?;
+        Self::parse(&source).map_err(|e| match e {
+            TestVectorError::Parse(mut e) => {
+                e.set_path(path);
+                e.set_text(&source);
+                TestVectorError::Parse(e)
+            }
+            e => e,
+        })
+    }
+
+    /// Parse a test vector from a str.
+    pub fn parse(source: &str) -> std::result::Result<Self, TestVectorError> {
+        crate::parser::check_nesting(source)?;
+        let buf = wast::parser::ParseBuffer::new(source)?;
+        Ok(wast::parser::parse::<TestVector>(&buf)?)
+    }
+
+    /// Write the test vector to the filesystem.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::result::Result<(), TestVectorError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_string()).map_err(|e| TestVectorError::Io(path.to_path_buf(), e))
+    }
+}
+
+impl Parse<'_> for TestVector {
+    fn parse(parser: Parser<'_>) -> Result<Self> {
+        parser.parens(|p| {
+            p.parse::<kw::test_vector>()?;
+            let name = p.parse::<&str>()?.to_string();
+            let model = p.parse()?;
+            let mut inputs = Vec::new();
+            let mut outputs = Vec::new();
+            while !p.is_empty() {
+                p.parens(|p| {
+                    let mut l = p.lookahead1();
+                    if l.peek::<kw::input>() {
+                        p.parse::<kw::input>()?;
+                        inputs.push(parse_tensor(p)?);
+                        Ok(())
+                    } else if l.peek::<kw::output>() {
+                        p.parse::<kw::output>()?;
+                        let tensor = parse_tensor(p)?;
+                        let tolerance = if p.is_empty() {
+                            0.0
+                        } else {
+                            p.parens(|p| {
+                                p.parse::<kw::tolerance>()?;
+                                parse_f64(p)
+                            })?
+                        };
+                        outputs.push(ExpectedOutput { tensor, tolerance });
+                        Ok(())
+                    } else {
+                        Err(l.error())
+                    }
+                })?;
+            }
+            Ok(TestVector {
+                name,
+                model,
+                inputs,
+                outputs,
+            })
+        })
+    }
+}
+
+impl Parse<'_> for Model {
+    fn parse(parser: Parser<'_>) -> Result<Self> {
+        parser.parens(|p| {
+            p.parse::<kw::model>()?;
+            let encoding = p.parens(|p| {
+                p.parse::<kw::encoding>()?;
+                Ok(p.parse::<&str>()?.to_string())
+            })?;
+            let target = p.parens(|p| {
+                p.parse::<kw::target>()?;
+                Ok(p.parse::<&str>()?.to_string())
+            })?;
+            let mut files = Vec::new();
+            while !p.is_empty() {
+                files.push(p.parens(parse_file)?);
+            }
+            Ok(Model {
+                encoding,
+                target,
+                files,
+            })
+        })
+    }
+}
+
+/// The fields of a tensor, after the `input` or `output` keyword.
+fn parse_tensor(parser: Parser<'_>) -> Result<Tensor> {
+    let index = parser.parse()?;
+    let ty = parser.step(|c| match c.keyword() {
+        Some((name, rest)) => match name.parse() {
+            Ok(ty) => Ok((ty, rest)),
+            Err(e) => Err(c.error(e)),
+        },
+        None => Err(c.error("expected a tensor element type")),
+    })?;
+    let dimensions = parser.parens(|p| {
+        p.parse::<kw::dimensions>()?;
+        let mut dimensions = Vec::new();
+        while !p.is_empty() {
+            dimensions.push(p.parse()?);
+        }
+        Ok(dimensions)
+    })?;
+    let data = parser.parens(|p| {
+        let mut l = p.lookahead1();
+        if l.peek::<kw::data>() {
+            p.parse::<kw::data>()?;
+            let mut values = Vec::new();
+            while !p.is_empty() {
+                values.push(parse_f64(p)?);
+            }
+            Ok(TensorData::Inline(values))
+        } else if l.peek::<kw::file>() {
+            Ok(TensorData::File(parse_file(p)?))
+        } else {
+            Err(l.error())
+        }
+    })?;
+    Ok(Tensor {
+        index,
+        ty,
+        dimensions,
+        data,
+    })
+}
+
+fn parse_file(parser: Parser<'_>) -> Result<PathBuf> {
+    parser.parse::<kw::file>()?;
+    Ok(PathBuf::from(parser.parse::<&str>()?))
+}
+
+fn parse_f64(parser: Parser<'_>) -> Result<f64> {
+    Ok(f64::from_bits(parser.parse::<wast::Float64>()?.bits))
+}
+
+fn f64_sexpr(v: f64) -> SExpr {
+    if v.is_nan() {
+        SExpr::word("nan")
+    } else if v.is_infinite() {
+        SExpr::word(if v > 0.0 { "inf" } else { "-inf" })
+    } else {
+        SExpr::Word(v.to_string())
+    }
+}
+
+fn file_sexpr(path: &Path) -> SExpr {
+    SExpr::Vec(vec![
+        SExpr::word("file"),
+        SExpr::quote(&path.to_string_lossy()),
+    ])
+}
+
+impl Tensor {
+    fn to_sexpr(&self, kind: &str) -> Vec<SExpr> {
+        let mut dimensions = vec![SExpr::word("dimensions")];
+        dimensions.extend(self.dimensions.iter().map(|d| SExpr::Word(d.to_string())));
+        let data = match &self.data {
+            TensorData::Inline(values) => {
+                let mut data = vec![SExpr::word("data")];
+                data.extend(values.iter().map(|v| f64_sexpr(*v)));
+                SExpr::Vec(data)
+            }
+            TensorData::File(path) => file_sexpr(path),
+        };
+        vec![
+            SExpr::word(kind),
+            SExpr::Word(self.index.to_string()),
+            SExpr::Word(self.ty.to_string()),
+            SExpr::Vec(dimensions),
+            data,
+        ]
+    }
+}
+
+impl fmt::Display for TestVector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "(test_vector {}", SExpr::quote(&self.name))?;
+        let mut model = vec![
+            SExpr::word("model"),
+            SExpr::Vec(vec![
+                SExpr::word("encoding"),
+                SExpr::quote(&self.model.encoding),
+            ]),
+            SExpr::Vec(vec![
+                SExpr::word("target"),
+                SExpr::quote(&self.model.target),
+            ]),
+        ];
+        model.extend(self.model.files.iter().map(|p| file_sexpr(p)));
+        write!(f, "  {}", SExpr::Vec(model))?;
+        for input in self.inputs.iter() {
+            write!(f, "\n  {}", SExpr::Vec(input.to_sexpr("input")))?;
+        }
+        for output in self.outputs.iter() {
+            let mut sexpr = output.tensor.to_sexpr("output");
+            if output.tolerance != 0.0 {
+                sexpr.push(SExpr::Vec(vec![
+                    SExpr::word("tolerance"),
+                    f64_sexpr(output.tolerance),
+                ]));
+            }
+            write!(f, "\n  {}", SExpr::Vec(sexpr))?;
+        }
+        writeln!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const VECTOR: &str = r#"(test_vector "mobilenet"
+  (model (encoding "openvino") (target "cpu") (file "mobilenet.xml") (file "mobilenet.bin"))
+  (input 0 f32 (dimensions 1 3 224 224) (file "input.bgr"))
+  (input 1 q4 (dimensions 3) (data 1 2 15))
+  (output 0 f32 (dimensions 2 2) (data 0.5 -1 inf nan) (tolerance 0.001)))
+"#;
+
+    #[test]
+    fn round_trip() {
+        let vector = TestVector::parse(VECTOR).unwrap();
+        assert_eq!(vector.model.encoding, "openvino");
+        assert_eq!(vector.model.files.len(), 2);
+        assert_eq!(vector.inputs[0].dimensions, [1, 3, 224, 224]);
+        assert_eq!(vector.inputs[0].len(), 150528);
+        assert_eq!(
+            vector.inputs[1].bytes(Path::new(".")).unwrap(),
+            [0x21, 0x0f]
+        );
+        assert_eq!(vector.outputs[0].tolerance, 0.001);
+        assert_eq!(vector.to_string(), VECTOR);
+        assert!(TestVector::parse("(test_vector \"x\" (model (target \"cpu\")))").is_err());
+    }
+
+    #[test]
+    fn check() {
+        let vector = TestVector::parse(VECTOR).unwrap();
+        let output = &vector.outputs[0];
+        let actual = |values: &[f32]| {
+            values
+                .iter()
+                .flat_map(|v| v.to_le_bytes().to_vec())
+                .collect::<Vec<_>>()
+        };
+        let base = Path::new(".");
+        output
+            .check(&actual(&[0.5005, -1.0, f32::INFINITY, f32::NAN]), base)
+            .unwrap();
+        match output.check(&actual(&[0.5, -1.1, f32::INFINITY, f32::NAN]), base) {
+            Err(TestVectorError::Mismatch { element: 1, .. }) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        match output.check(&actual(&[0.5]), base) {
+            Err(TestVectorError::Tensor {
+                error: TensorError::BufferSize { expected: 16, .. },
+                ..
+            }) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! How long each phase of loading a document and generating code from it
+//! takes, to guide performance work on large spec trees.
+//!
+//! A [`Timer`] is threaded through the work itself: loading a document with
+//! it times reading, lexing, parsing and validating its files as the load
+//! does them, and [`Timer::time`] times the rest, like generating code with
+//! a backend. Memory is measured as the bytes allocated during each phase,
+//! when the caller can count them, as the CLI does with a counting global
+//! allocator.
+
+use crate::{Document, LoadOptions, WitxError, WitxIo};
+use std::cell::RefCell;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The time and memory a phase took.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseTiming {
+    /// The phase, like `parse` or `codegen c`.
+    pub phase: String,
+    pub time: Duration,
+    /// The bytes allocated during the phase, if they were counted.
+    pub allocated: Option<usize>,
+}
+
+/// What the phases processed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub files: usize,
+    /// The size of the files.
+    pub bytes: usize,
+    pub modules: usize,
+    pub funcs: usize,
+    pub types: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timings {
+    pub phases: Vec<PhaseTiming>,
+    pub counts: Counts,
+}
+
+impl Timings {
+    pub fn phase(&self, phase: &str) -> Option<&PhaseTiming> {
+        self.phases.iter().find(|p| p.phase == phase)
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.phases.iter().map(|p| p.phase.len()).max().unwrap_or(0);
+        for p in self.phases.iter() {
+            write!(
+                f,
+                "{:width$}  {:>10.3}ms",
+                p.phase,
+                p.time.as_secs_f64() * 1000.0,
+                width = width
+            )?;
+            if let Some(bytes) = p.allocated {
+                write!(f, "  {:>10.1} KiB", bytes as f64 / 1024.0)?;
+            }
+            writeln!(f)?;
+        }
+        let c = &self.counts;
+        write!(
+            f,
+            "{} files of {} bytes: {} modules, {} funcs, {} types",
+            c.files, c.bytes, c.modules, c.funcs, c.types
+        )
+    }
+}
+
+/// Times the phases of a run, in the order they first run. A phase run more
+/// than once, like parsing each file of a document, is timed as the sum.
+#[derive(Default)]
+pub struct Timer<'a> {
+    allocated: Option<&'a dyn Fn() -> usize>,
+    phases: RefCell<Vec<PhaseTiming>>,
+    /// The path and size of each file read.
+    files: RefCell<Vec<(PathBuf, usize)>>,
+    counts: RefCell<Counts>,
+}
+
+impl<'a> Timer<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Measure the memory of each phase with `allocated`, which returns
+    /// the bytes allocated so far.
+    pub fn allocations(mut self, allocated: &'a dyn Fn() -> usize) -> Self {
+        self.allocated = Some(allocated);
+        self
+    }
+
+    /// Load a document with `options`, timing its phases.
+    pub fn load(
+        &self,
+        options: &LoadOptions,
+        paths: &[impl AsRef<Path>],
+    ) -> Result<Document, WitxError> {
+        self.load_with(options, paths, &crate::Filesystem)
+    }
+
+    /// Like `load`, reading files through `witxio`.
+    pub fn load_with(
+        &self,
+        options: &LoadOptions,
+        paths: &[impl AsRef<Path>],
+        witxio: &dyn WitxIo,
+    ) -> Result<Document, WitxError> {
+        let doc = options.load_timed(paths, witxio, self)?;
+        let mut counts = self.counts.borrow_mut();
+        counts.modules += doc.modules().count();
+        counts.funcs += doc.modules().map(|m| m.funcs().count()).sum::<usize>();
+        counts.types += doc.typenames().count();
+        Ok(doc)
+    }
+
+    /// Run `f` as part of `phase`.
+    pub fn time<T>(&self, phase: &str, f: impl FnOnce() -> T) -> T {
+        let before = self.allocated.map(|a| a());
+        let start = Instant::now();
+        let result = f();
+        let time = start.elapsed();
+        let allocated = self
+            .allocated
+            .zip(before)
+            .map(|(a, before)| a().saturating_sub(before));
+
+        let mut phases = self.phases.borrow_mut();
+        match phases.iter_mut().find(|p| p.phase == phase) {
+            Some(p) => {
+                p.time += time;
+                p.allocated = p.allocated.zip(allocated).map(|(a, b)| a + b);
+            }
+            None => phases.push(PhaseTiming {
+                phase: phase.to_string(),
+                time,
+                allocated,
+            }),
+        }
+        result
+    }
+
+    /// Note that a file of `size` bytes was read from `path`.
+    pub(crate) fn read(&self, path: &Path, size: usize) {
+        let mut files = self.files.borrow_mut();
+        if !files.iter().any(|(p, _)| p == path) {
+            files.push((path.to_path_buf(), size));
+        }
+    }
+
+    /// The phases timed so far, and what they processed.
+    pub fn timings(&self) -> Timings {
+        let files = self.files.borrow();
+        Timings {
+            phases: self.phases.borrow().clone(),
+            counts: Counts {
+                files: files.len(),
+                bytes: files.iter().map(|(_, size)| size).sum(),
+                ..self.counts.borrow().clone()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::{self, Language};
+    use crate::MockFs;
+    use std::cell::Cell;
+
+    #[test]
+    fn timings() {
+        let a = "(use \"b\")\n(module $m (@interface func (export \"f\") (param $x $t)))";
+        let b = "(typename $t (record (field $n u32) (field $d (list u8))))";
+        let fs = MockFs::new(&[("/a", a), ("/b", b)]);
+        let allocated = Cell::new(0);
+        let counter = || {
+            allocated.set(allocated.get() + 8);
+            allocated.get()
+        };
+        let timer = Timer::new().allocations(&counter);
+        let doc = timer
+            .load_with(&LoadOptions::new(), &[Path::new("/a")], &fs)
+            .unwrap();
+        for language in [Language::C, Language::Protobuf].iter() {
+            timer.time(&format!("codegen {}", language), || {
+                codegen::generate(&doc, *language, &codegen::Options::default())
+            });
+        }
+        let timings = timer.timings();
+        let phases = timings
+            .phases
+            .iter()
+            .map(|p| p.phase.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            phases,
+            vec![
+                "read",
+                "lex",
+                "parse",
+                "validate",
+                "codegen c",
+                "codegen protobuf"
+            ]
+        );
+        assert_eq!(timings.phase("codegen c").unwrap().allocated, Some(8));
+        // Both files are read, as two runs of the phase.
+        assert_eq!(timings.phase("read").unwrap().allocated, Some(16));
+        assert_eq!(
+            timings.counts,
+            Counts {
+                files: 2,
+                bytes: a.len() + b.len(),
+                modules: 1,
+                funcs: 1,
+                types: 1,
+            }
+        );
+        let report = timings.to_string();
+        assert!(report.starts_with("read  "), "{}", report);
+        assert!(report.ends_with(&format!(
+            "2 files of {} bytes: 1 modules, 1 funcs, 1 types",
+            a.len() + b.len()
+        )));
+    }
+}
@@ -1,95 +1,496 @@
 use crate::ast::{Definition, Document};
 use crate::io::{Filesystem, WitxIo};
-use crate::parser::{TopLevelDocument, TopLevelSyntax};
-use crate::validate::DocValidation;
+use crate::limits::Limits;
+use crate::parser::{
+    check_nesting, DeclSyntax, Documented, FieldSyntax, ModuleDeclSyntax, TopLevelDocument,
+    TopLevelSyntax, TypedefSyntax,
+};
+use crate::phase_log::Phase;
+use crate::timings::Timer;
+use crate::validate::{suggestions, DocValidation};
 use crate::WitxError;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io;
 use std::path::{Path, PathBuf};
 
 pub fn parse_witx(i: &[impl AsRef<Path>]) -> Result<Document, WitxError> {
     let paths = i.iter().map(|p| p.as_ref()).collect::<Vec<&Path>>();
-    _parse_witx_with(&paths, &Filesystem)
+    _parse_witx_with(&paths, &LoadOptions::new(), &Filesystem, None)
 }
 
 pub fn parse_witx_with(i: &[impl AsRef<Path>], witxio: impl WitxIo) -> Result<Document, WitxError> {
     let paths = i.iter().map(|p| p.as_ref()).collect::<Vec<&Path>>();
-    _parse_witx_with(&paths, &witxio)
+    _parse_witx_with(&paths, &LoadOptions::new(), &witxio, None)
 }
 
-fn _parse_witx_with(paths: &[&Path], io: &dyn WitxIo) -> Result<Document, WitxError> {
-    let mut validator = DocValidation::new();
+/// How to load a document: where to look for the files it `use`s, and the
+/// flags its `(@witx cfg ...)` blocks are evaluated against. Loading one
+/// source tree with different flags projects different documents from it.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    include: Vec<PathBuf>,
+    cfg: HashSet<String>,
+    modules: Vec<String>,
+    limits: Limits,
+}
+
+impl LoadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look in `dir` for each file `use`d that isn't relative to the root
+    /// file, after any directories included already.
+    pub fn include(mut self, dir: impl AsRef<Path>) -> Self {
+        self.include.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Enable `flag`, so that `(@witx cfg ...)` blocks conditional on it
+    /// are part of the document.
+    pub fn cfg(mut self, flag: &str) -> Self {
+        self.cfg.insert(flag.to_string());
+        self
+    }
+
+    /// Resolve only the module `name`, after any modules given already, and
+    /// the types and constants they use. The rest of the document has its
+    /// names checked, as a full load would, but isn't validated any further,
+    /// so that tools needing one module of a large tree of shared types
+    /// don't pay for all of it, and it is left out of the document loaded.
+    /// So a tree whose unneeded declarations are invalid, which a full load
+    /// rejects, loads for a module that doesn't use them.
+    /// Loading fails with a `WitxError::UnknownModule` if no file declares
+    /// the module.
+    pub fn module(mut self, name: &str) -> Self {
+        self.modules.push(name.to_string());
+        self
+    }
+
+    /// Fail with a `WitxError::Limit` on input exceeding `limits`.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn load(&self, paths: &[impl AsRef<Path>]) -> Result<Document, WitxError> {
+        self.load_with(paths, &Filesystem)
+    }
+
+    /// Like `load`, reading files through `witxio`.
+    pub fn load_with(
+        &self,
+        paths: &[impl AsRef<Path>],
+        witxio: &dyn WitxIo,
+    ) -> Result<Document, WitxError> {
+        let paths = paths.iter().map(|p| p.as_ref()).collect::<Vec<&Path>>();
+        _parse_witx_with(&paths, self, witxio, None)
+    }
+
+    /// Like `load_with`, timing its phases with `timer`.
+    pub(crate) fn load_timed(
+        &self,
+        paths: &[impl AsRef<Path>],
+        witxio: &dyn WitxIo,
+        timer: &Timer,
+    ) -> Result<Document, WitxError> {
+        let paths = paths.iter().map(|p| p.as_ref()).collect::<Vec<&Path>>();
+        _parse_witx_with(&paths, self, witxio, Some(timer))
+    }
+}
+
+/// The files parsed, and where to look for the files they use.
+struct Search<'a> {
+    io: &'a dyn WitxIo,
+    include: &'a [PathBuf],
+    parsed: HashSet<PathBuf>,
+    /// The names of the declarations to validate, unless all of them are.
+    needed: Option<HashSet<String>>,
+    limits: Limits,
+    /// The `use` declarations followed so far.
+    uses: usize,
+    timer: Option<&'a Timer<'a>>,
+}
+
+impl Search<'_> {
+    /// The file `path` refers to from `root`, falling back to the first
+    /// include directory with it. A file found nowhere resolves relative to
+    /// `root`, failing there.
+    fn resolve(&self, root: &Path, path: &Path) -> PathBuf {
+        let relative = root.join(path);
+        if path.is_absolute() || self.include.is_empty() || self.io.exists(&relative) {
+            return relative;
+        }
+        self.include
+            .iter()
+            .map(|dir| dir.join(path))
+            .find(|p| self.io.exists(p))
+            .unwrap_or(relative)
+    }
+
+    /// The contents of the file `path`, failing before reading it if its
+    /// size is known to be over the limit. The size is checked again once
+    /// read, for files whose size isn't known or has changed since.
+    fn read(&self, path: &Path) -> Result<String, WitxError> {
+        if let Some(size) = self.io.size(path) {
+            let size = usize::try_from(size).unwrap_or(usize::MAX);
+            self.limits.check_file_size(path, size)?;
+        }
+        let input = self.time("read", || self.io.fgets(path))?;
+        if let Some(timer) = self.timer {
+            timer.read(path, input.len());
+        }
+        Ok(input)
+    }
+
+    /// Run `f` as part of `phase`, timing it if the load is timed.
+    fn time<T>(&self, phase: &str, f: impl FnOnce() -> T) -> T {
+        match self.timer {
+            Some(timer) => timer.time(phase, f),
+            None => f(),
+        }
+    }
+
+    /// Count a `use` in the file `path`, failing past the limit.
+    fn count_use(&mut self, path: &Path) -> Result<(), WitxError> {
+        self.uses += 1;
+        Ok(self.limits.check_uses(path, self.uses)?)
+    }
+
+    fn needs(&self, decl: &DeclSyntax) -> bool {
+        let name = match decl {
+            DeclSyntax::Typename(t) => t.ident.name(),
+            DeclSyntax::Module(m) => m.name.name(),
+            // Constants are needed with their type.
+            DeclSyntax::Const(c) => c.item.ty.name(),
+        };
+        match &self.needed {
+            Some(needed) => needed.contains(name),
+            None => true,
+        }
+    }
+}
+
+fn _parse_witx_with(
+    paths: &[&Path],
+    options: &LoadOptions,
+    io: &dyn WitxIo,
+    timer: Option<&Timer>,
+) -> Result<Document, WitxError> {
+    let _phase = Phase::start(|| format!("load {:?}", paths));
+    let mut search = Search {
+        io,
+        include: &options.include,
+        parsed: HashSet::new(),
+        needed: None,
+        limits: options.limits,
+        uses: 0,
+        timer,
+    };
+    if !options.modules.is_empty() {
+        let mut refs = Refs::default();
+        for path in paths {
+            let root = path.parent().unwrap_or(Path::new("."));
+            collect_refs(path, &mut search, root, &mut refs)?;
+        }
+        if let Some(name) = options
+            .modules
+            .iter()
+            .find(|m| !refs.modules.contains(m.as_str()))
+        {
+            return Err(WitxError::UnknownModule {
+                name: name.clone(),
+                suggestions: suggestions(name, refs.modules.iter().map(|m| m.as_str())),
+            });
+        }
+        search.parsed.clear();
+        search.uses = 0;
+        let needed = needed(&options.modules, &refs.names);
+        event!(
+            "modules {:?} need {} of {} types and modules",
+            options.modules,
+            needed.len(),
+            refs.names.len()
+        );
+        search.needed = Some(needed);
+    }
+    let mut validator = DocValidation::new().with_cfg(options.cfg.iter());
     let mut definitions = Vec::new();
-    let mut parsed = HashSet::new();
     for path in paths {
         let root = path.parent().unwrap_or(Path::new("."));
+        let file_name = path.file_name().ok_or_else(|| {
+            WitxError::Io(
+                path.to_path_buf(),
+                io::Error::new(io::ErrorKind::InvalidInput, "not a path to a file"),
+            )
+        })?;
 
         parse_file(
-            path.file_name().unwrap().as_ref(),
-            io,
+            &root.join(file_name),
+            &mut search,
             root,
             &mut validator,
             &mut definitions,
-            &mut parsed,
         )?;
     }
-    Ok(validator.into_document(definitions))
+    Ok(search.time("validate", || validator.into_document(definitions)))
 }
 
 fn parse_file(
     path: &Path,
-    io: &dyn WitxIo,
+    search: &mut Search,
     root: &Path,
     validator: &mut DocValidation,
     definitions: &mut Vec<Definition>,
-    parsed: &mut HashSet<PathBuf>,
 ) -> Result<(), WitxError> {
-    let path = io.canonicalize(&root.join(path))?;
-    if !parsed.insert(path.clone()) {
+    let path = search.io.canonicalize(path)?;
+    if !search.parsed.insert(path.clone()) {
         return Ok(());
     }
-    let input = io.fgets(&path)?;
+    let input = search.read(&path)?;
+    event!("read {:?}, {} bytes", path, input.len());
+    let parsing = Phase::start(|| format!("parse {:?}", path));
+    let buf = lex(&path, &input, search)?;
+    let doc = parse(&path, &input, &buf, search)?;
+    drop(parsing);
+    // Includes validating the files it uses.
+    let _validating = Phase::start(|| format!("validate {:?}", path));
 
-    let adjust_err = |mut error: wast::Error| {
-        error.set_path(&path);
-        error.set_text(&input);
-        WitxError::Parse(error)
+    let pragmas = doc
+        .items
+        .iter()
+        .filter_map(|t| match &t.item {
+            TopLevelSyntax::Version(v) => Some(v),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let outer = search
+        .time("validate", || {
+            validator.scope(&input, &path).enter_file(&pragmas)
+        })
+        .map_err(WitxError::Validation)?;
+
+    let file = File {
+        path: &path,
+        input: &input,
+        root,
     };
-    let buf = wast::parser::ParseBuffer::new(&input).map_err(adjust_err)?;
-    let doc = wast::parser::parse::<TopLevelDocument>(&buf).map_err(adjust_err)?;
+    parse_items(&doc.items, &file, search, validator, definitions)?;
+
+    validator.leave_file(outer);
+    Ok(())
+}
 
-    for t in doc.items {
-        match t.item {
+/// The tokens of `input`, read from `path`, having checked them against the
+/// limits and for how deeply they nest.
+fn lex<'a>(
+    path: &Path,
+    input: &'a str,
+    search: &Search,
+) -> Result<wast::parser::ParseBuffer<'a>, WitxError> {
+    let adjust_err = |error| parse_error(error, path, input);
+    search.time("lex", || {
+        search.limits.check_witx(path, input)?;
+        check_nesting(input).map_err(adjust_err)?;
+        wast::parser::ParseBuffer::new(input).map_err(adjust_err)
+    })
+}
+
+/// The document of the tokens `buf` of `input`, read from `path`.
+fn parse<'a>(
+    path: &Path,
+    input: &str,
+    buf: &'a wast::parser::ParseBuffer<'a>,
+    search: &Search,
+) -> Result<TopLevelDocument<'a>, WitxError> {
+    search
+        .time("parse", || wast::parser::parse::<TopLevelDocument>(buf))
+        .map_err(|error| parse_error(error, path, input))
+}
+
+/// A file being parsed, and the root its `use`s are relative to.
+struct File<'a> {
+    path: &'a Path,
+    input: &'a str,
+    root: &'a Path,
+}
+
+fn parse_items(
+    items: &[Documented<TopLevelSyntax>],
+    file: &File,
+    search: &mut Search,
+    validator: &mut DocValidation,
+    definitions: &mut Vec<Definition>,
+) -> Result<(), WitxError> {
+    for t in items {
+        match &t.item {
+            TopLevelSyntax::Decl(d) if !search.needs(d) => {
+                search
+                    .time("validate", || {
+                        validator.scope(file.input, file.path).resolve_decl(d)
+                    })
+                    .map_err(WitxError::Validation)?;
+            }
             TopLevelSyntax::Decl(d) => {
-                validator
-                    .scope(&input, &path)
-                    .validate_decl(&d, &t.comments, definitions)
+                search
+                    .time("validate", || {
+                        validator.scope(file.input, file.path).validate_decl(
+                            d,
+                            &t.comments,
+                            definitions,
+                        )
+                    })
                     .map_err(WitxError::Validation)?;
             }
             TopLevelSyntax::Use(u) => {
-                parse_file(u.as_ref(), io, root, validator, definitions, parsed)?;
+                search.count_use(file.path)?;
+                let used = search.resolve(file.root, u.as_ref());
+                validator.enter_use(file.path);
+                parse_file(&used, search, file.root, validator, definitions)?;
+                validator.leave_use();
+            }
+            TopLevelSyntax::Cfg(cfg) => {
+                let enabled = search
+                    .time("validate", || {
+                        validator.scope(file.input, file.path).cfg(cfg)
+                    })
+                    .map_err(WitxError::Validation)?;
+                if enabled {
+                    parse_items(&cfg.items, file, search, validator, definitions)?;
+                }
             }
+            TopLevelSyntax::Version(_) => {}
         }
     }
+    Ok(())
+}
+
+fn parse_error(mut error: wast::Error, path: &Path, input: &str) -> WitxError {
+    error.set_path(path);
+    error.set_text(input);
+    WitxError::Parse(error)
+}
+
+/// What the types and modules of a document refer to.
+#[derive(Default)]
+struct Refs {
+    /// The names each type and module refers to, by its name.
+    names: HashMap<String, HashSet<String>>,
+    /// The names of the modules.
+    modules: HashSet<String>,
+}
+
+/// Parse the file `path` and those it uses, adding the names each of their
+/// types and modules refers to to `refs`, without validating them.
+fn collect_refs(
+    path: &Path,
+    search: &mut Search,
+    root: &Path,
+    refs: &mut Refs,
+) -> Result<(), WitxError> {
+    let path = search.io.canonicalize(path)?;
+    if !search.parsed.insert(path.clone()) {
+        return Ok(());
+    }
+    let input = search.read(&path)?;
+    event!("read {:?} for its references, {} bytes", path, input.len());
+    let buf = lex(&path, &input, search)?;
+    let doc = parse(&path, &input, &buf, search)?;
+    collect_item_refs(&doc.items, &path, search, root, refs)
+}
 
+fn collect_item_refs(
+    items: &[Documented<TopLevelSyntax>],
+    path: &Path,
+    search: &mut Search,
+    root: &Path,
+    refs: &mut Refs,
+) -> Result<(), WitxError> {
+    for t in items {
+        match &t.item {
+            TopLevelSyntax::Decl(DeclSyntax::Typename(t)) => {
+                let names = refs.names.entry(t.ident.name().to_string()).or_default();
+                typedef_refs(&t.def, names);
+            }
+            TopLevelSyntax::Decl(DeclSyntax::Module(m)) => {
+                refs.modules.insert(m.name.name().to_string());
+                let names = refs.names.entry(m.name.name().to_string()).or_default();
+                for d in m.decls.iter() {
+                    module_decl_refs(&d.item, names);
+                }
+            }
+            TopLevelSyntax::Use(u) => {
+                search.count_use(path)?;
+                let used = search.resolve(root, u.as_ref());
+                collect_refs(&used, search, root, refs)?;
+            }
+            // Whether a block is enabled doesn't matter here: validating
+            // the types of a disabled one too is only wasted effort.
+            TopLevelSyntax::Cfg(cfg) => collect_item_refs(&cfg.items, path, search, root, refs)?,
+            TopLevelSyntax::Decl(DeclSyntax::Const(_)) | TopLevelSyntax::Version(_) => {}
+        }
+    }
     Ok(())
 }
 
+fn module_decl_refs(decl: &ModuleDeclSyntax, names: &mut HashSet<String>) {
+    match decl {
+        ModuleDeclSyntax::Import(_) => {}
+        ModuleDeclSyntax::Func(f) => {
+            for p in f.params.iter().chain(f.results.iter()) {
+                field_refs(&p.item, names);
+            }
+        }
+        ModuleDeclSyntax::Cfg(cfg) => {
+            for d in cfg.items.iter() {
+                module_decl_refs(&d.item, names);
+            }
+        }
+    }
+}
+
+fn field_refs(field: &FieldSyntax, names: &mut HashSet<String>) {
+    typedef_refs(&field.type_, names);
+}
+
+fn typedef_refs(def: &TypedefSyntax, names: &mut HashSet<String>) {
+    let mut refs = Vec::new();
+    def.refs(&mut refs);
+    names.extend(refs.iter().map(|id| id.name().to_string()));
+}
+
+/// The names of `modules` and of the types they refer to, directly or
+/// through other types.
+fn needed(modules: &[String], refs: &HashMap<String, HashSet<String>>) -> HashSet<String> {
+    let mut needed = HashSet::new();
+    let mut stack = modules.to_vec();
+    while let Some(name) = stack.pop() {
+        if let Some(names) = refs.get(&name) {
+            stack.extend(names.iter().filter(|n| !needed.contains(*n)).cloned());
+        }
+        needed.insert(name);
+    }
+    needed
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::ast::*;
     use crate::io::MockFs;
+    use crate::{ValidationError, WitxVersion};
 
     #[test]
     fn empty() {
-        parse_witx_with(&[Path::new("/a")], &MockFs::new(&[("/a", ";; empty")])).expect("parse");
+        parse_witx_with(&[Path::new("/a")], MockFs::new(&[("/a", ";; empty")])).expect("parse");
     }
 
     #[test]
     fn one_use() {
         parse_witx_with(
             &[Path::new("/a")],
-            &MockFs::new(&[("/a", "(use \"b\")"), ("/b", ";; empty")]),
+            MockFs::new(&[("/a", "(use \"b\")"), ("/b", ";; empty")]),
         )
         .unwrap();
     }
@@ -98,7 +499,7 @@ mod test {
     fn multi_use() {
         let doc = parse_witx_with(
             &[Path::new("/a")],
-            &MockFs::new(&[
+            MockFs::new(&[
                 ("/a", "(use \"b\")"),
                 ("/b", "(use \"c\")\n(typename $b_float f64)"),
                 ("/c", "(typename $c_int u32)"),
@@ -122,7 +523,7 @@ mod test {
     fn diamond_dependency() {
         let doc = parse_witx_with(
             &[Path::new("/a")],
-            &MockFs::new(&[
+            MockFs::new(&[
                 ("/a", "(use \"b\")\n(use \"c\")"),
                 ("/b", "(use \"d\")"),
                 ("/c", "(use \"d\")"),
@@ -138,9 +539,139 @@ mod test {
         );
     }
 
+    #[test]
+    fn provenance() {
+        let doc = parse_witx_with(
+            &[Path::new("/a")],
+            MockFs::new(&[
+                ("/a", "(use \"b\")\n(typename $a_int u64)"),
+                (
+                    "/b",
+                    "(use \"c\")\n(module $m (@interface func (export \"f\")))",
+                ),
+                ("/c", "(typename $c_int u32)"),
+            ]),
+        )
+        .expect("parse");
+
+        let a_int = doc.provenance(&Id::new("a_int")).unwrap();
+        assert_eq!(a_int.to_string(), "/a:2:11");
+        assert!(!a_int.is_used());
+
+        let c_int = doc.provenance(&Id::new("c_int")).unwrap();
+        assert_eq!(c_int.uses, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        assert_eq!(c_int.to_string(), "/c:1:11 (used from /a -> /b)");
+        assert_eq!(
+            c_int.chain().collect::<Vec<_>>(),
+            vec![Path::new("/a"), Path::new("/b"), Path::new("/c")]
+        );
+
+        let f = doc.func_provenance(&Id::new("m"), &Id::new("f")).unwrap();
+        assert_eq!(f.to_string(), "/b:2:37 (used from /a)");
+        assert_eq!(doc.provenance(&Id::new("m")).unwrap().uses, f.uses);
+    }
+
+    #[test]
+    fn redefinition_reports_use_chain() {
+        let fs = MockFs::new(&[
+            ("/a", "(use \"b\")\n(use \"c\")"),
+            ("/b", "(typename $t u8)"),
+            ("/c", "(typename $t u32)"),
+        ]);
+        match parse_witx_with(&[Path::new("/a")], &fs).err().unwrap() {
+            WitxError::Validation(e) => {
+                let report = e.report_with(&fs);
+                assert!(report.contains("Redefinition of name `t`"));
+                assert!(
+                    report.ends_with("brought in by `use` from \"/a\""),
+                    "bad report: {}",
+                    report
+                );
+            }
+            e => panic!("wrong error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn shadowing() {
+        // Whether the local definition comes before or after the `use`, it's
+        // the one reported as shadowing the used one.
+        for a in &[
+            "(use \"b\")\n(typename $t u8)",
+            "(typename $t u8)\n(use \"b\")",
+        ] {
+            let fs = MockFs::new(&[
+                ("/a", a),
+                ("/b", "(use \"c\")"),
+                ("/c", "(typename $t u32)"),
+            ]);
+            match parse_witx_with(&[Path::new("/a")], &fs).err().unwrap() {
+                WitxError::Validation(ValidationError::Shadowed {
+                    name,
+                    location,
+                    used,
+                }) => {
+                    assert_eq!(name, "t");
+                    assert_eq!(location.path, PathBuf::from("/a"));
+                    assert_eq!(used.path(), Path::new("/c"));
+                    assert_eq!(used.uses, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+                }
+                e => panic!("wrong error: {:?}", e),
+            }
+        }
+
+        let fs = MockFs::new(&[
+            ("/a", "(use \"b\") (typename $t u8)"),
+            ("/b", "(typename $t u32)"),
+        ]);
+        let report = parse_witx_with(&[Path::new("/a")], &fs)
+            .err()
+            .unwrap()
+            .report_with(&fs);
+        assert!(
+            report.contains(
+                "Definition of `t` shadows the one brought in by `use`\n\
+                 Brought in by `use` from \"/a\" at:\nin \"/b\":"
+            ),
+            "bad report: {}",
+            report
+        );
+    }
+
+    #[test]
+    fn include_paths() {
+        let fs = MockFs::new(&[
+            ("/nn/a", "(use \"types\") (use \"local\")"),
+            ("/nn/local", "(typename $local u8)"),
+            ("/first/other", ";; empty"),
+            ("/lib/types", "(use \"more\") (typename $t u32)"),
+            ("/lib/more", "(typename $more u64)"),
+        ]);
+        let doc = LoadOptions::new()
+            .include("/first")
+            .include("/lib")
+            .load_with(&[Path::new("/nn/a")], &fs)
+            .expect("parse");
+        assert!(doc.typename(&Id::new("t")).is_some());
+        assert_eq!(
+            doc.provenance(&Id::new("more")).unwrap().path(),
+            Path::new("/lib/more")
+        );
+        assert_eq!(
+            doc.provenance(&Id::new("local")).unwrap().path(),
+            Path::new("/nn/local")
+        );
+
+        // Without the include paths, `use` is only relative to the root.
+        match parse_witx_with(&[Path::new("/nn/a")], &fs).err().unwrap() {
+            WitxError::Io(path, _error) => assert_eq!(path, PathBuf::from("/nn/types")),
+            e => panic!("wrong error: {:?}", e),
+        }
+    }
+
     #[test]
     fn use_not_found() {
-        match parse_witx_with(&[Path::new("/a")], &MockFs::new(&[("/a", "(use \"b\")")]))
+        match parse_witx_with(&[Path::new("/a")], MockFs::new(&[("/a", "(use \"b\")")]))
             .err()
             .unwrap()
         {
@@ -151,7 +682,7 @@ mod test {
 
     #[test]
     fn use_invalid() {
-        match parse_witx_with(&[Path::new("/a")], &MockFs::new(&[("/a", "(use bbbbbbb)")]))
+        match parse_witx_with(&[Path::new("/a")], MockFs::new(&[("/a", "(use bbbbbbb)")]))
             .err()
             .unwrap()
         {
@@ -163,4 +694,229 @@ mod test {
             e => panic!("wrong error: {:?}", e),
         }
     }
+
+    #[test]
+    fn version() {
+        let doc = parse_witx_with(&[Path::new("/a")], MockFs::new(&[("/a", ";; empty")])).unwrap();
+        assert_eq!(doc.version(), WitxVersion::LATEST);
+
+        let doc = parse_witx_with(
+            &[Path::new("/a")],
+            MockFs::new(&[
+                ("/a", "(@witx version \"0.8\") (use \"b\")"),
+                (
+                    "/b",
+                    "(@witx version \"0.9\")
+                     (module $m (@interface func (export \"f\") (@witx feature $x)))",
+                ),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(doc.version(), WitxVersion::V0_8);
+        assert!(doc.to_string().starts_with("(@witx version \"0.8\")\n"));
+    }
+
+    #[test]
+    fn cfg() {
+        let fs = MockFs::new(&[
+            (
+                "/a",
+                "(@witx cfg $gpu (use \"gpu\"))
+                 (@witx cfg (not $gpu) (typename $device (enum $cpu)))
+                 (module $nn
+                    (@witx meta stability \"experimental\")
+                    (@interface func (export \"load\"))
+                    (@witx cfg (any $gpu $experimental-async)
+                        ;;; Waits for the device.
+                        (@interface func (export \"wait\"))
+                        (@witx cfg (all $gpu $experimental-async)
+                            (@interface func (export \"compute_async\")))))",
+            ),
+            ("/gpu", "(typename $device (enum $cpu $gpu))"),
+        ]);
+        let load = |flags: &[&str]| {
+            flags
+                .iter()
+                .fold(LoadOptions::new(), |o, f| o.cfg(f))
+                .load_with(&[Path::new("/a")], &fs)
+                .expect("parse")
+        };
+        let funcs = |doc: &Document| {
+            doc.module(&Id::new("nn"))
+                .unwrap()
+                .funcs()
+                .map(|f| f.name.as_str().to_string())
+                .collect::<Vec<_>>()
+        };
+        let cases = |doc: &Document| match &**doc.typename(&Id::new("device")).unwrap().type_() {
+            Type::Variant(v) => v.cases.len(),
+            t => panic!("not an enum: {:?}", t),
+        };
+
+        let plain = load(&[]);
+        assert_eq!(funcs(&plain), vec!["load"]);
+        assert_eq!(cases(&plain), 1);
+
+        let gpu = load(&["gpu"]);
+        assert_eq!(funcs(&gpu), vec!["load", "wait"]);
+        assert_eq!(cases(&gpu), 2);
+        let wait = gpu.module(&Id::new("nn")).unwrap().func(&Id::new("wait"));
+        assert_eq!(wait.unwrap().docs, "Waits for the device.\n");
+
+        let both = load(&["gpu", "experimental-async"]);
+        assert_eq!(funcs(&both), vec!["load", "wait", "compute_async"]);
+        assert_eq!(funcs(&load(&["experimental-async"])), vec!["load", "wait"]);
+
+        let fs = MockFs::new(&[("/a", "(@witx cfg $x (@witx version \"0.9\"))")]);
+        match parse_witx_with(&[Path::new("/a")], &fs).err().unwrap() {
+            WitxError::Parse(e) => assert!(e.to_string().contains("can't be conditional")),
+            e => panic!("wrong error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn malformed_input() {
+        let deep = format!(
+            "(typename $t {}u8{})",
+            "(list ".repeat(100_000),
+            ")".repeat(100_000)
+        );
+        let err = parse_witx_with(&[Path::new("/a")], MockFs::new(&[("/a", &deep)]));
+        match err.err().unwrap() {
+            WitxError::Parse(e) => assert!(e.to_string().contains("nest deeper than 100 levels")),
+            e => panic!("wrong error: {:?}", e),
+        }
+
+        match parse_witx_with(&[Path::new("/")], MockFs::new(&[]))
+            .err()
+            .unwrap()
+        {
+            WitxError::Io(path, _) => assert_eq!(path, Path::new("/")),
+            e => panic!("wrong error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn version_gates_features() {
+        let err = |source| match parse_witx_with(&[Path::new("/a")], MockFs::new(&[("/a", source)]))
+        {
+            Err(WitxError::Validation(e)) => e.to_string(),
+            other => panic!("expected a validation error, got {:?}", other),
+        };
+        assert_eq!(
+            err("(@witx version \"0.8\")
+                 (module $m (@interface func (export \"f\") (@witx feature $x)))"),
+            "`(@witx feature ...)` requires witx 0.9, but the document declares version 0.8"
+        );
+        assert_eq!(
+            err("(@witx version \"0.8\")
+                 (typename $r (record (field $n u32) (field $l (list u8) (@witx size $n))))"),
+            "`(@witx size ...)` requires witx 0.9, but the document declares version 0.8"
+        );
+        assert_eq!(
+            err("(@witx version \"0.9\") (@witx cfg $x (typename $t u8))"),
+            "`(@witx cfg ...)` requires witx 0.10, but the document declares version 0.9"
+        );
+        assert_eq!(
+            err("(@witx version \"0.9\") (typename $box (@witx generic $T) (tuple $T))"),
+            "`(@witx generic ...)` requires witx 0.10, but the document declares version 0.9"
+        );
+        assert_eq!(
+            err("(@witx version \"0.9\") (typename $t u8 (@witx meta owner \"ml-wg\"))"),
+            "`(@witx meta ...)` requires witx 0.10, but the document declares version 0.9"
+        );
+        assert!(err("(@witx version \"7.0\")").contains("`7.0` is not supported"));
+        assert!(err("(@witx version \"x\")").contains("not a version"));
+        assert!(err("(@witx version \"0.9\") (@witx version \"0.9\")")
+            .contains("declared more than once"));
+    }
+
+    #[test]
+    fn one_module() {
+        let fs = MockFs::new(&[
+            (
+                "/a",
+                "(use \"b\")\n
+                (module $m (@interface func (export \"f\") (param $x $x) (result $e $e)))
+                (module $n (@interface func (export \"g\") (param $y $y)))",
+            ),
+            (
+                "/b",
+                "(typename $len u32)
+                (typename $item (tuple $len (@witx pointer u8)))
+                (typename $x (record (field $items (list $item))))
+                (typename $e (expected (error $len)))
+                (typename $y (list $len))
+                (@witx const $len $max 8)
+                (@witx const $byte $max 1)",
+            ),
+        ]);
+        let doc = LoadOptions::new()
+            .module("m")
+            .load_with(&[Path::new("/a")], &fs)
+            .unwrap();
+        assert!(doc.module(&Id::new("m")).is_some());
+        assert!(doc.module(&Id::new("n")).is_none());
+        let types = doc
+            .typenames()
+            .map(|t| t.name.as_str().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(types, vec!["len", "item", "x", "e"]);
+        let constants = doc
+            .constants()
+            .map(|c| c.ty.as_str().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(constants, vec!["len"]);
+
+        match LoadOptions::new()
+            .module("mm")
+            .load_with(&[Path::new("/a")], &fs)
+        {
+            Err(WitxError::UnknownModule { name, suggestions }) => {
+                assert_eq!(name, "mm");
+                assert_eq!(suggestions, vec!["m"]);
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+
+        // The declarations left out still have to resolve, as they do when
+        // loading all of them.
+        let load = |b: &str| {
+            let fs = MockFs::new(&[("/a", "(use \"b\") (module $m)"), ("/b", b)]);
+            let full = parse_witx_with(&[Path::new("/a")], &fs).map(|_| ());
+            let one = LoadOptions::new()
+                .module("m")
+                .load_with(&[Path::new("/a")], &fs)
+                .map(|_| ());
+            assert_eq!(full.is_err(), one.is_err());
+            one
+        };
+        load("(typename $y u8) (module $n (@interface func (export \"g\") (param $y $y)))")
+            .unwrap();
+        match load("(typename $y $unknown)") {
+            Err(WitxError::Validation(ValidationError::UnknownName { name, .. })) => {
+                assert_eq!(name, "unknown")
+            }
+            other => panic!("{:?}", other),
+        }
+        match load("(typename $y u8) (typename $y u16)") {
+            Err(WitxError::Validation(ValidationError::NameAlreadyExists { name, .. })) => {
+                assert_eq!(name, "y")
+            }
+            other => panic!("{:?}", other),
+        }
+        match load("(typename $y u8) (module $n (@interface func (export \"g\") (param $x $x)))") {
+            Err(WitxError::Validation(ValidationError::UnknownName { name, .. })) => {
+                assert_eq!(name, "x")
+            }
+            other => panic!("{:?}", other),
+        }
+        match load("(module $n (@interface func (export \"g\")) (@interface func (export \"g\")))")
+        {
+            Err(WitxError::Validation(ValidationError::NameAlreadyExists { name, .. })) => {
+                assert_eq!(name, "g")
+            }
+            other => panic!("{:?}", other),
+        }
+    }
 }
@@ -1,29 +1,48 @@
 use crate::{
+    diagnostic::Style,
     io::{Filesystem, WitxIo},
     parser::{
-        CommentSyntax, DeclSyntax, Documented, EnumSyntax, ExpectedSyntax, FlagsSyntax,
-        HandleSyntax, ImportTypeSyntax, ModuleDeclSyntax, RecordSyntax, TupleSyntax, TypedefSyntax,
-        UnionSyntax, VariantSyntax,
+        CfgExprSyntax, CfgSyntax, CommentSyntax, DeclSyntax, Documented, EnumSyntax,
+        ExpectedSyntax, FieldSyntax, FlagsSyntax, HandleSyntax, ImportTypeSyntax, MetaSyntax,
+        ModuleDeclSyntax, RecordSyntax, SizeExprSyntax, TupleSyntax, TypedefSyntax, TypenameSyntax,
+        UnionSyntax, VariantSyntax, VersionSyntax,
     },
-    Abi, BuiltinType, Case, Constant, Definition, Document, Entry, HandleDatatype, Id, IntRepr,
-    InterfaceFunc, InterfaceFuncParam, Location, Module, ModuleDefinition, ModuleEntry,
-    ModuleImport, ModuleImportVariant, NamedType, RecordDatatype, RecordKind, RecordMember, Type,
-    TypeRef, Variant,
+    tensor::ElementType,
+    Abi, BuiltinType, Case, Constant, Definition, Document, Entry, GrammarFeature, HandleDatatype,
+    Id, IntRepr, InterfaceFunc, InterfaceFuncParam, Location, Meta, Module, ModuleDefinition,
+    ModuleEntry, ModuleImport, ModuleImportVariant, NamedType, Provenance, RecordDatatype,
+    RecordKind, RecordMember, SizeExpr, Type, TypeRef, Variant, WitxVersion,
 };
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ValidationError {
-    #[error("Unknown name `{name}`")]
-    UnknownName { name: String, location: Location },
+    #[error("Unknown name `{name}`{}", did_you_mean(suggestions))]
+    UnknownName {
+        name: String,
+        /// Names in scope close to `name`, closest first.
+        suggestions: Vec<String>,
+        location: Location,
+    },
     #[error("Redefinition of name `{name}`")]
     NameAlreadyExists {
         name: String,
         at_location: Location,
         previous_location: Location,
+        /// The `use` chain that brought in the previous definition.
+        previous_uses: Box<[PathBuf]>,
+    },
+    #[error("Definition of `{name}` shadows the one brought in by `use`")]
+    Shadowed {
+        name: String,
+        /// The definition in the file with the `use`.
+        location: Location,
+        /// The definition brought in by the `use`.
+        used: Box<Provenance>,
     },
     #[error("Wrong kind of name `{name}`: expected {expected}, got {got}")]
     WrongKindName {
@@ -39,10 +58,26 @@ pub enum ValidationError {
         repr: BuiltinType,
         location: Location,
     },
+    #[error("Invalid instance of `{name}`: {reason}")]
+    InvalidInstance {
+        name: String,
+        reason: String,
+        location: Location,
+    },
     #[error("ABI error: {reason}")]
     Abi { reason: String, location: Location },
     #[error("Anonymous structured types (struct, union, enum, flags, handle) are not permitted")]
     AnonymousRecord { location: Location },
+    #[error("{count} {kind} don't fit in representation `{repr:?}`")]
+    TooManyCases {
+        /// `cases` or `flags`.
+        kind: &'static str,
+        count: usize,
+        repr: IntRepr,
+        location: Location,
+    },
+    #[error("Variants must have at least one case")]
+    NoCases { location: Location },
     #[error("Union expected {expected} variants, found {found}")]
     UnionSizeMismatch {
         expected: usize,
@@ -57,40 +92,89 @@ pub enum ValidationError {
         reason: String,
         location: Location,
     },
+    #[error("Invalid size of `{name}`: {reason}")]
+    InvalidSize {
+        name: String,
+        reason: String,
+        location: Location,
+    },
+    #[error("Metadata `{key}` is given more than once")]
+    DuplicateMeta { key: String, location: Location },
+    #[error("Invalid version: {reason}")]
+    InvalidVersion { reason: String, location: Location },
+    #[error("{feature} requires witx {since}, but the document declares version {version}")]
+    UnsupportedFeature {
+        feature: GrammarFeature,
+        since: WitxVersion,
+        version: WitxVersion,
+        location: Location,
+    },
 }
 
 impl ValidationError {
     pub fn report_with(&self, witxio: &dyn WitxIo) -> String {
-        use ValidationError::*;
-        match self {
-            UnknownName { location, .. }
-            | WrongKindName { location, .. }
-            | Recursive { location, .. }
-            | InvalidRepr { location, .. }
-            | Abi { location, .. }
-            | AnonymousRecord { location, .. }
-            | UnionSizeMismatch { location, .. }
-            | InvalidUnionField { location, .. }
-            | InvalidUnionTag { location, .. } => {
-                format!("{}\n{}", location.highlight_source_with(witxio), &self)
-            }
-            NameAlreadyExists {
-                at_location,
-                previous_location,
-                ..
-            } => format!(
-                "{}\n{}\nOriginally defined at:\n{}",
-                at_location.highlight_source_with(witxio),
-                &self,
-                previous_location.highlight_source_with(witxio),
-            ),
-        }
+        Style::plain().validation_error(self, witxio)
     }
     pub fn report(&self) -> String {
         self.report_with(&Filesystem)
     }
 }
 
+pub(crate) fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [one] => format!("; did you mean `{}`?", one),
+        [rest @ .., last] => format!(
+            "; did you mean {} or `{}`?",
+            rest.iter()
+                .map(|s| format!("`{}`", s))
+                .collect::<Vec<_>>()
+                .join(", "),
+            last
+        ),
+    }
+}
+
+/// The most suggestions an unknown name gets.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The `candidates` within a few edits of `name`, closest first, to suggest
+/// in place of it.
+pub(crate) fn suggestions<'a>(
+    name: &str,
+    candidates: impl Iterator<Item = &'a str>,
+) -> Vec<String> {
+    // A third of the name's length, so short names don't match everything.
+    let max = (name.chars().count() / 3).max(1);
+    let mut close = candidates
+        .filter(|c| *c != name)
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, _)| *d <= max)
+        .collect::<Vec<_>>();
+    close.sort();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.to_string())
+        .collect()
+}
+
+/// The Levenshtein distance between `a` and `b`, in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 struct IdentValidation {
     names: HashMap<String, Location>,
 }
@@ -108,6 +192,7 @@ impl IdentValidation {
                 name: syntax.to_string(),
                 at_location: location,
                 previous_location: introduced.clone(),
+                previous_uses: Box::new([]),
             })
         } else {
             self.names.insert(syntax.to_string(), location);
@@ -115,12 +200,17 @@ impl IdentValidation {
         }
     }
 
+    fn remove(&mut self, syntax: &str) {
+        self.names.remove(syntax);
+    }
+
     fn get(&self, syntax: &str, location: Location) -> Result<Id, ValidationError> {
-        if self.names.get(syntax).is_some() {
+        if self.names.contains_key(syntax) {
             Ok(Id::new(syntax))
         } else {
             Err(ValidationError::UnknownName {
                 name: syntax.to_string(),
+                suggestions: suggestions(syntax, self.names.keys().map(|n| n.as_str())),
                 location,
             })
         }
@@ -132,6 +222,55 @@ pub struct DocValidation {
     entries: HashMap<Id, Entry>,
     constant_scopes: HashMap<Id, IdentValidation>,
     bool_ty: TypeRef,
+    /// The version of the file being validated.
+    version: WitxVersion,
+    /// The version of the first file validated, the root of the document.
+    root_version: Option<WitxVersion>,
+    /// The files whose `use` led to the file being validated.
+    uses: Vec<PathBuf>,
+    provenance: HashMap<Id, Provenance>,
+    func_provenance: HashMap<(Id, Id), Provenance>,
+    /// The flags enabled for `(@witx cfg ...)` blocks.
+    cfg: HashSet<String>,
+    generics: HashMap<Id, Generic>,
+    /// The type parameters of the generic type being validated.
+    type_params: Vec<Rc<NamedType>>,
+    /// The variants of the generic type being validated which are tagged by
+    /// one of its type parameters, with the parameter's position.
+    param_tags: RefCell<Vec<ParamTag>>,
+    /// The instances of generic types, by name.
+    instances: RefCell<HashMap<Id, Instance>>,
+    /// Instances to add to the document before the declaration using them.
+    new_instances: RefCell<Vec<(Rc<NamedType>, Location)>>,
+}
+
+/// A generic type, validated with stand-ins for its type parameters which
+/// each instance replaces with its type arguments.
+struct Generic {
+    params: Vec<Rc<NamedType>>,
+    tref: TypeRef,
+    /// The variants of `tref` tagged by a type parameter, whose tags are
+    /// only known in an instance.
+    tags: Vec<ParamTag>,
+    docs: String,
+    meta: Vec<Meta>,
+}
+
+/// An instance of a generic type.
+struct Instance {
+    generic: Id,
+    args: Vec<TypeRef>,
+    named: Rc<NamedType>,
+}
+
+/// A variant of a generic type tagged by one of its type parameters.
+struct ParamTag {
+    variant: Rc<Type>,
+    /// The position of the type parameter.
+    param: usize,
+    /// Whether the variant is a union, whose cases are named after those
+    /// of its tag.
+    union: bool,
 }
 
 pub struct DocValidationScope<'a> {
@@ -140,6 +279,12 @@ pub struct DocValidationScope<'a> {
     path: &'a Path,
 }
 
+impl Default for DocValidation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DocValidation {
     pub fn new() -> Self {
         Self {
@@ -160,10 +305,30 @@ impl DocValidation {
                         docs: String::new(),
                     },
                 ],
+                feature: None,
             }))),
+            version: WitxVersion::default(),
+            root_version: None,
+            uses: Vec::new(),
+            provenance: HashMap::new(),
+            func_provenance: HashMap::new(),
+            cfg: HashSet::new(),
+            generics: HashMap::new(),
+            type_params: Vec::new(),
+            param_tags: RefCell::new(Vec::new()),
+            instances: RefCell::new(HashMap::new()),
+            new_instances: RefCell::new(Vec::new()),
         }
     }
 
+    /// Validate with `flags` enabled, so that the `(@witx cfg ...)` blocks
+    /// conditional on them are validated, and the others skipped.
+    pub fn with_cfg<S: AsRef<str>>(mut self, flags: impl IntoIterator<Item = S>) -> Self {
+        self.cfg
+            .extend(flags.into_iter().map(|f| f.as_ref().to_string()));
+        self
+    }
+
     pub fn scope<'a>(&'a mut self, text: &'a str, path: &'a Path) -> DocValidationScope<'a> {
         DocValidationScope {
             doc: self,
@@ -172,8 +337,30 @@ impl DocValidation {
         }
     }
 
-    pub fn into_document(self, defs: Vec<Definition>) -> Document {
-        Document::new(defs, self.entries)
+    /// Go back to validating the file with `version`, which included the
+    /// file just validated.
+    pub fn leave_file(&mut self, version: WitxVersion) {
+        self.version = version;
+    }
+
+    /// Start validating the file used by `path`, until the matching
+    /// `leave_use`.
+    pub fn enter_use(&mut self, path: &Path) {
+        self.uses.push(path.to_path_buf());
+    }
+
+    pub fn leave_use(&mut self) {
+        self.uses.pop();
+    }
+
+    pub fn into_document(mut self, defs: Vec<Definition>) -> Document {
+        // Declarations only resolved have provenance, but aren't part of
+        // the document.
+        let (entries, generics) = (&self.entries, &self.generics);
+        self.provenance
+            .retain(|id, _| entries.contains_key(id) || generics.contains_key(id));
+        Document::new(defs, self.entries, self.root_version.unwrap_or_default())
+            .with_provenance(self.provenance, self.func_provenance)
     }
 }
 
@@ -188,9 +375,156 @@ impl DocValidationScope<'_> {
         }
     }
 
+    /// Start validating a file with the version `pragmas` declare, returning
+    /// the version of the file being validated before, to restore with
+    /// `DocValidation::leave_file` once done.
+    pub fn enter_file(
+        &mut self,
+        pragmas: &[&VersionSyntax],
+    ) -> Result<WitxVersion, ValidationError> {
+        let outer = self.doc.version;
+        let version = match pragmas {
+            [] => WitxVersion::default(),
+            [pragma] => {
+                let invalid = |reason| ValidationError::InvalidVersion {
+                    reason,
+                    location: self.location(pragma.span),
+                };
+                let version = pragma.version.parse::<WitxVersion>().map_err(invalid)?;
+                if !version.is_supported() {
+                    let supported = WitxVersion::SUPPORTED
+                        .iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>();
+                    return Err(invalid(format!(
+                        "`{}` is not supported: this version of witx reads {}",
+                        version,
+                        supported.join(", ")
+                    )));
+                }
+                version
+            }
+            [_, pragma, ..] => {
+                return Err(ValidationError::InvalidVersion {
+                    reason: "declared more than once".to_string(),
+                    location: self.location(pragma.span),
+                })
+            }
+        };
+        self.doc.version = version;
+        self.doc.root_version.get_or_insert(version);
+        Ok(outer)
+    }
+
+    /// Whether the items of `cfg` are part of the document, given the flags
+    /// enabled.
+    pub fn cfg<T>(&self, cfg: &CfgSyntax<T>) -> Result<bool, ValidationError> {
+        self.require(GrammarFeature::Conditionals, cfg.span)?;
+        fn eval(expr: &CfgExprSyntax, flags: &HashSet<String>) -> bool {
+            match expr {
+                CfgExprSyntax::Flag(flag) => flags.contains(flag.name()),
+                CfgExprSyntax::Not(expr) => !eval(expr, flags),
+                CfgExprSyntax::All(exprs) => exprs.iter().all(|e| eval(e, flags)),
+                CfgExprSyntax::Any(exprs) => exprs.iter().any(|e| eval(e, flags)),
+            }
+        }
+        Ok(eval(&cfg.cond, &self.doc.cfg))
+    }
+
+    /// Reject `feature` if the file's version predates it.
+    fn require(&self, feature: GrammarFeature, span: wast::Span) -> Result<(), ValidationError> {
+        if self.doc.version.allows(feature) {
+            Ok(())
+        } else {
+            Err(ValidationError::UnsupportedFeature {
+                feature,
+                since: feature.since(),
+                version: self.doc.version,
+                location: self.location(span),
+            })
+        }
+    }
+
+    fn validate_meta(&self, syntax: &[MetaSyntax]) -> Result<Vec<Meta>, ValidationError> {
+        let mut meta = Vec::<Meta>::new();
+        for m in syntax {
+            self.require(GrammarFeature::Metadata, m.span)?;
+            if meta.iter().any(|prev| prev.key == m.key) {
+                return Err(ValidationError::DuplicateMeta {
+                    key: m.key.to_string(),
+                    location: self.location(m.span),
+                });
+            }
+            meta.push(Meta {
+                key: m.key.to_string(),
+                value: m.value.to_string(),
+            });
+        }
+        Ok(meta)
+    }
+
     fn introduce(&mut self, name: &wast::Id<'_>) -> Result<Id, ValidationError> {
         let loc = self.location(name.span());
-        self.doc.scope.introduce(name.name(), loc)
+        let id = match self.doc.scope.introduce(name.name(), loc.clone()) {
+            Ok(id) => id,
+            Err(e) => return Err(self.redefinition(e)),
+        };
+        let provenance = self.provenance(loc);
+        self.doc.provenance.insert(id.clone(), provenance);
+        Ok(id)
+    }
+
+    /// Say how a redefinition relates to the previous definition: whether
+    /// one of them is in a file that `use`s the other's, shadowing it, no
+    /// matter which of them was validated first.
+    fn redefinition(&self, error: ValidationError) -> ValidationError {
+        let (name, at_location, previous_location) = match error {
+            ValidationError::NameAlreadyExists {
+                name,
+                at_location,
+                previous_location,
+                ..
+            } => (name, at_location, previous_location),
+            e => return e,
+        };
+        let previous = match self.doc.provenance.get(&Id::new(&name)) {
+            Some(previous) => previous,
+            None => {
+                return ValidationError::NameAlreadyExists {
+                    name,
+                    at_location,
+                    previous_location,
+                    previous_uses: Box::new([]),
+                }
+            }
+        };
+        if previous.uses.iter().any(|p| p == self.path) {
+            ValidationError::Shadowed {
+                name,
+                location: at_location,
+                used: Box::new(previous.clone()),
+            }
+        } else if self.doc.uses.iter().any(|p| p == previous.path()) {
+            ValidationError::Shadowed {
+                name,
+                location: previous_location,
+                used: Box::new(self.provenance(at_location)),
+            }
+        } else {
+            ValidationError::NameAlreadyExists {
+                name,
+                at_location,
+                previous_location,
+                previous_uses: previous.uses.clone().into(),
+            }
+        }
+    }
+
+    fn provenance(&self, location: Location) -> Provenance {
+        Provenance {
+            location,
+            uses: self.doc.uses.clone(),
+        }
     }
 
     fn get(&self, name: &wast::Id<'_>) -> Result<Id, ValidationError> {
@@ -205,15 +539,32 @@ impl DocValidationScope<'_> {
         definitions: &mut Vec<Definition>,
     ) -> Result<(), ValidationError> {
         match decl {
+            DeclSyntax::Typename(decl) if !decl.params.is_empty() => {
+                self.require(GrammarFeature::Generics, decl.ident.span())?;
+                let name = self.introduce(&decl.ident)?;
+                let generic = self.validate_generic(decl, comments);
+                for p in self.doc.type_params.drain(..) {
+                    self.doc.scope.remove(p.name.as_str());
+                    self.doc.entries.remove(&p.name);
+                }
+                let tags = self.doc.param_tags.take();
+                let generic = Generic { tags, ..generic? };
+                self.define_instances(definitions)?;
+                self.doc.generics.insert(name, generic);
+            }
+
             DeclSyntax::Typename(decl) => {
                 let name = self.introduce(&decl.ident)?;
                 let docs = comments.docs();
                 let tref = self.validate_datatype(&decl.def, true, decl.ident.span())?;
+                let meta = self.validate_meta(&decl.meta)?;
+                self.define_instances(definitions)?;
 
                 let rc_datatype = Rc::new(NamedType {
                     name: name.clone(),
                     tref,
                     docs,
+                    meta,
                 });
                 self.doc
                     .entries
@@ -223,19 +574,32 @@ impl DocValidationScope<'_> {
 
             DeclSyntax::Module(syntax) => {
                 let name = self.introduce(&syntax.name)?;
+                let meta = self.validate_meta(&syntax.meta)?;
                 let mut module_validator = ModuleValidation::new(self);
-                let decls = syntax
-                    .decls
-                    .iter()
-                    .map(|d| module_validator.validate_decl(&d))
-                    .collect::<Result<Vec<_>, _>>()?;
+                let mut decls = Vec::new();
+                module_validator.validate_decls(&syntax.decls, &mut decls)?;
 
+                let funcs = std::mem::take(&mut module_validator.funcs);
                 let rc_module = Rc::new(Module::new(
                     name.clone(),
                     decls,
                     module_validator.entries,
                     comments.docs(),
+                    meta,
                 ));
+                self.define_instances(definitions)?;
+                event!(
+                    "resolved module {} with {} funcs, in {:?}",
+                    name.as_str(),
+                    funcs.len(),
+                    self.path
+                );
+                for (func, loc) in funcs {
+                    let provenance = self.provenance(loc);
+                    self.doc
+                        .func_provenance
+                        .insert((name.clone(), func), provenance);
+                }
                 self.doc
                     .entries
                     .insert(name, Entry::Module(Rc::downgrade(&rc_module)));
@@ -264,6 +628,212 @@ impl DocValidationScope<'_> {
         Ok(())
     }
 
+    /// Check the names `decl` defines and refers to, without validating it
+    /// any further or adding it to the document: what a declaration left
+    /// out of it by `LoadOptions::module` still has to get right.
+    pub fn resolve_decl(&mut self, decl: &DeclSyntax) -> Result<(), ValidationError> {
+        match decl {
+            DeclSyntax::Typename(decl) => {
+                self.introduce(&decl.ident)?;
+                let mut params = IdentValidation::new();
+                for p in decl.params.iter() {
+                    params.introduce(p.name(), self.location(p.span()))?;
+                }
+                let mut refs = Vec::new();
+                decl.def.refs(&mut refs);
+                for r in refs.iter() {
+                    if !params.names.contains_key(r.name()) {
+                        self.get(r)?;
+                    }
+                }
+            }
+            DeclSyntax::Module(syntax) => {
+                self.introduce(&syntax.name)?;
+                self.resolve_module_decls(&syntax.decls, &mut IdentValidation::new())?;
+            }
+            DeclSyntax::Const(syntax) => {
+                let loc = self.location(syntax.item.name.span());
+                self.doc
+                    .constant_scopes
+                    .entry(Id::new(syntax.item.ty.name()))
+                    .or_insert_with(IdentValidation::new)
+                    .introduce(syntax.item.name.name(), loc)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_module_decls(
+        &self,
+        decls: &[Documented<ModuleDeclSyntax>],
+        scope: &mut IdentValidation,
+    ) -> Result<(), ValidationError> {
+        for decl in decls {
+            match &decl.item {
+                ModuleDeclSyntax::Cfg(cfg) => {
+                    if self.cfg(cfg)? {
+                        self.resolve_module_decls(&cfg.items, scope)?;
+                    }
+                }
+                ModuleDeclSyntax::Import(syntax) => {
+                    scope.introduce(syntax.name, self.location(syntax.name_loc))?;
+                }
+                ModuleDeclSyntax::Func(syntax) => {
+                    scope.introduce(syntax.export, self.location(syntax.export_loc))?;
+                    let mut argnames = IdentValidation::new();
+                    for f in syntax.params.iter().chain(syntax.results.iter()) {
+                        let name = &f.item.name;
+                        argnames.introduce(name.name(), self.location(name.span()))?;
+                        let mut refs = Vec::new();
+                        f.item.type_.refs(&mut refs);
+                        for r in refs.iter() {
+                            self.get(r)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the definition of a generic type, with its type parameters
+    /// in scope until the caller removes them.
+    fn validate_generic(
+        &mut self,
+        decl: &TypenameSyntax,
+        comments: &CommentSyntax,
+    ) -> Result<Generic, ValidationError> {
+        for p in decl.params.iter() {
+            let loc = self.location(p.span());
+            let name = self.doc.scope.introduce(p.name(), loc)?;
+            // The stand-in's type only matters to the validation of the
+            // definition, such as of a list size of the parameter's type.
+            let param = Rc::new(NamedType {
+                name: name.clone(),
+                tref: TypeRef::Value(Rc::new(Type::Builtin(BuiltinType::U32 {
+                    lang_ptr_size: false,
+                }))),
+                docs: String::new(),
+                meta: Vec::new(),
+            });
+            self.doc
+                .entries
+                .insert(name, Entry::Typename(Rc::downgrade(&param)));
+            self.doc.type_params.push(param);
+        }
+        Ok(Generic {
+            params: self.doc.type_params.clone(),
+            tref: self.validate_datatype(&decl.def, true, decl.ident.span())?,
+            tags: Vec::new(),
+            docs: comments.docs(),
+            meta: self.validate_meta(&decl.meta)?,
+        })
+    }
+
+    /// The instance `name` of a generic type with `args`, named after them.
+    fn validate_instance(
+        &self,
+        name: &wast::Id<'_>,
+        args: &[TypedefSyntax],
+        span: wast::Span,
+    ) -> Result<TypeRef, ValidationError> {
+        self.require(GrammarFeature::Generics, name.span())?;
+        let i = self.get(name)?;
+        let invalid = |reason: String| ValidationError::InvalidInstance {
+            name: i.as_str().to_string(),
+            reason,
+            location: self.location(name.span()),
+        };
+        let generic = match self.doc.generics.get(&i) {
+            Some(generic) => generic,
+            None => {
+                return Err(ValidationError::WrongKindName {
+                    name: i.as_str().to_string(),
+                    location: self.location(name.span()),
+                    expected: "generic type",
+                    got: self
+                        .doc
+                        .entries
+                        .get(&i)
+                        .map_or("generic type", |e| e.kind()),
+                })
+            }
+        };
+        if args.len() != generic.params.len() {
+            return Err(invalid(format!(
+                "expected {} type arguments, got {}",
+                generic.params.len(),
+                args.len()
+            )));
+        }
+        let args = args
+            .iter()
+            .map(|a| self.validate_datatype(a, false, span))
+            .collect::<Result<Vec<_>, _>>()?;
+        if args.iter().any(|a| mentions(a, &self.doc.type_params)) {
+            return Err(invalid(
+                "type parameters can't be the type arguments of an instance".to_string(),
+            ));
+        }
+        let mut instance_name = i.as_str().to_string();
+        for a in args.iter() {
+            instance_name.push('_');
+            instance_name.push_str(&mangle(a));
+        }
+        let instance_name = Id::new(instance_name);
+        if let Some(instance) = self.doc.instances.borrow().get(&instance_name) {
+            // Names only abbreviate type arguments, so that `(list u8)` and
+            // a type named `list_u8` give instances of the same name.
+            if instance.generic != i || instance.args != args {
+                return Err(invalid(format!(
+                    "its name `{}` is already the name of an instance with other type arguments",
+                    instance_name.as_str()
+                )));
+            }
+            return Ok(TypeRef::Name(instance.named.clone()));
+        }
+        let instance = Rc::new(NamedType {
+            name: instance_name.clone(),
+            tref: substitute(&generic.tref, generic, &args).map_err(&invalid)?,
+            docs: generic.docs.clone(),
+            meta: generic.meta.clone(),
+        });
+        self.doc.instances.borrow_mut().insert(
+            instance_name,
+            Instance {
+                generic: i.clone(),
+                args,
+                named: instance.clone(),
+            },
+        );
+        self.doc
+            .new_instances
+            .borrow_mut()
+            .push((instance.clone(), self.location(name.span())));
+        Ok(TypeRef::Name(instance))
+    }
+
+    /// Add the instances of generic types first used by the declaration
+    /// being validated.
+    fn define_instances(
+        &mut self,
+        definitions: &mut Vec<Definition>,
+    ) -> Result<(), ValidationError> {
+        for (instance, loc) in self.doc.new_instances.take() {
+            let name = self
+                .doc
+                .scope
+                .introduce(instance.name.as_str(), loc.clone())?;
+            let provenance = self.provenance(loc);
+            self.doc.provenance.insert(name.clone(), provenance);
+            self.doc
+                .entries
+                .insert(name, Entry::Typename(Rc::downgrade(&instance)));
+            definitions.push(Definition::Typename(instance));
+        }
+        Ok(())
+    }
+
     fn validate_datatype(
         &self,
         syntax: &TypedefSyntax,
@@ -271,6 +841,17 @@ impl DocValidationScope<'_> {
         span: wast::Span,
     ) -> Result<TypeRef, ValidationError> {
         match syntax {
+            TypedefSyntax::Instance(name, args) => self.validate_instance(name, args, span),
+            TypedefSyntax::Ident(syntax)
+                if self.doc.generics.contains_key(&Id::new(syntax.name())) =>
+            {
+                Err(ValidationError::WrongKindName {
+                    name: syntax.name().to_string(),
+                    location: self.location(syntax.span()),
+                    expected: "datatype",
+                    got: "generic type",
+                })
+            }
             TypedefSyntax::Ident(syntax) => {
                 let i = self.get(syntax)?;
                 match self.doc.entries.get(&i) {
@@ -300,38 +881,69 @@ impl DocValidationScope<'_> {
                     location: self.location(span),
                 })
             }
-            other => Ok(TypeRef::Value(Rc::new(match other {
-                TypedefSyntax::Enum(syntax) => Type::Variant(self.validate_enum(&syntax, span)?),
-                TypedefSyntax::Tuple(syntax) => Type::Record(self.validate_tuple(&syntax, span)?),
-                TypedefSyntax::Expected(syntax) => {
-                    Type::Variant(self.validate_expected(&syntax, span)?)
-                }
-                TypedefSyntax::Flags(syntax) => Type::Record(self.validate_flags(&syntax, span)?),
-                TypedefSyntax::Record(syntax) => Type::Record(self.validate_record(&syntax, span)?),
-                TypedefSyntax::Union(syntax) => Type::Variant(self.validate_union(&syntax, span)?),
-                TypedefSyntax::Variant(syntax) => {
-                    Type::Variant(self.validate_variant(&syntax, span)?)
-                }
-                TypedefSyntax::Handle(syntax) => Type::Handle(self.validate_handle(syntax, span)?),
-                TypedefSyntax::List(syntax) => {
-                    Type::List(self.validate_datatype(syntax, false, span)?)
-                }
-                TypedefSyntax::Pointer(syntax) => {
-                    Type::Pointer(self.validate_datatype(syntax, false, span)?)
-                }
-                TypedefSyntax::ConstPointer(syntax) => {
-                    Type::ConstPointer(self.validate_datatype(syntax, false, span)?)
-                }
-                TypedefSyntax::Builtin(builtin) => Type::Builtin(*builtin),
-                TypedefSyntax::String => {
-                    Type::List(TypeRef::Value(Rc::new(Type::Builtin(BuiltinType::Char))))
+            TypedefSyntax::Bool => Ok(self.doc.bool_ty.clone()),
+            other => {
+                let tag = match other {
+                    TypedefSyntax::Union(syntax) => self.tag_param(&syntax.tag).zip(Some(true)),
+                    TypedefSyntax::Variant(syntax) => self.tag_param(&syntax.tag).zip(Some(false)),
+                    _ => None,
+                };
+                let ty = Rc::new(self.validate_value_type(other, span)?);
+                if let Some((param, union)) = tag {
+                    self.doc.param_tags.borrow_mut().push(ParamTag {
+                        variant: ty.clone(),
+                        param,
+                        union,
+                    });
                 }
-                TypedefSyntax::Bool => return Ok(self.doc.bool_ty.clone()),
-                TypedefSyntax::Ident { .. } => unreachable!(),
-            }))),
+                Ok(TypeRef::Value(ty))
+            }
+        }
+    }
+
+    /// The position of the type parameter `tag` is, if it's one.
+    fn tag_param(&self, tag: &Option<Box<TypedefSyntax<'_>>>) -> Option<usize> {
+        match tag.as_deref() {
+            Some(TypedefSyntax::Ident(id)) => self
+                .doc
+                .type_params
+                .iter()
+                .position(|p| p.name.as_str() == id.name()),
+            _ => None,
         }
     }
 
+    fn validate_value_type(
+        &self,
+        syntax: &TypedefSyntax,
+        span: wast::Span,
+    ) -> Result<Type, ValidationError> {
+        Ok(match syntax {
+            TypedefSyntax::Enum(syntax) => Type::Variant(self.validate_enum(syntax, span)?),
+            TypedefSyntax::Tuple(syntax) => Type::Record(self.validate_tuple(syntax, span)?),
+            TypedefSyntax::Expected(syntax) => Type::Variant(self.validate_expected(syntax, span)?),
+            TypedefSyntax::Flags(syntax) => Type::Record(self.validate_flags(syntax, span)?),
+            TypedefSyntax::Record(syntax) => Type::Record(self.validate_record(syntax, span)?),
+            TypedefSyntax::Union(syntax) => Type::Variant(self.validate_union(syntax, span)?),
+            TypedefSyntax::Variant(syntax) => Type::Variant(self.validate_variant(syntax, span)?),
+            TypedefSyntax::Handle(syntax) => Type::Handle(self.validate_handle(syntax, span)?),
+            TypedefSyntax::List(syntax) => Type::List(self.validate_datatype(syntax, false, span)?),
+            TypedefSyntax::Pointer(syntax) => {
+                Type::Pointer(self.validate_datatype(syntax, false, span)?)
+            }
+            TypedefSyntax::ConstPointer(syntax) => {
+                Type::ConstPointer(self.validate_datatype(syntax, false, span)?)
+            }
+            TypedefSyntax::Builtin(builtin) => Type::Builtin(*builtin),
+            TypedefSyntax::String => {
+                Type::List(TypeRef::Value(Rc::new(Type::Builtin(BuiltinType::Char))))
+            }
+            TypedefSyntax::Ident { .. } | TypedefSyntax::Instance { .. } | TypedefSyntax::Bool => {
+                unreachable!()
+            }
+        })
+    }
+
     fn validate_enum(
         &self,
         syntax: &EnumSyntax,
@@ -355,8 +967,17 @@ impl DocValidationScope<'_> {
                 })
             })
             .collect::<Result<Vec<_>, _>>()?;
+        self.check_cases("cases", cases.len(), tag_repr, span)?;
+        if let Some(f) = &syntax.feature {
+            self.require(GrammarFeature::OptionalFeatures, f.span())?;
+        }
+        let feature = syntax.feature.as_ref().map(|f| Id::new(f.name()));
 
-        Ok(Variant { tag_repr, cases })
+        Ok(Variant {
+            tag_repr,
+            cases,
+            feature,
+        })
     }
 
     fn validate_tuple(
@@ -372,6 +993,7 @@ impl DocValidationScope<'_> {
                 Ok(RecordMember {
                     name: Id::new(i.to_string()),
                     tref: self.validate_datatype(ty, false, span)?,
+                    size: None,
                     docs: String::new(),
                 })
             })
@@ -410,6 +1032,7 @@ impl DocValidationScope<'_> {
                     docs: String::new(),
                 },
             ],
+            feature: None,
         })
     }
 
@@ -431,8 +1054,10 @@ impl DocValidationScope<'_> {
                 name,
                 docs,
                 tref: self.doc.bool_ty.clone(),
+                size: None,
             });
         }
+        self.check_cases("flags", members.len(), repr, span)?;
         Ok(RecordDatatype {
             kind: RecordKind::Bitflags(repr),
             members,
@@ -445,7 +1070,7 @@ impl DocValidationScope<'_> {
         _span: wast::Span,
     ) -> Result<RecordDatatype, ValidationError> {
         let mut member_scope = IdentValidation::new();
-        let members = syntax
+        let mut members = syntax
             .fields
             .iter()
             .map(|f| {
@@ -453,9 +1078,21 @@ impl DocValidationScope<'_> {
                     .introduce(f.item.name.name(), self.location(f.item.name.span()))?;
                 let tref = self.validate_datatype(&f.item.type_, false, f.item.name.span())?;
                 let docs = f.comments.docs();
-                Ok(RecordMember { name, tref, docs })
+                Ok(RecordMember {
+                    name,
+                    tref,
+                    size: None,
+                    docs,
+                })
             })
             .collect::<Result<Vec<RecordMember>, _>>()?;
+        let scope = members
+            .iter()
+            .map(|m| (m.name.clone(), m.tref.clone()))
+            .collect::<Vec<_>>();
+        for (m, f) in members.iter_mut().zip(syntax.fields.iter()) {
+            m.size = self.validate_size(&f.item, &m.tref, &scope)?;
+        }
 
         Ok(RecordDatatype {
             kind: RecordKind::Other,
@@ -463,6 +1100,79 @@ impl DocValidationScope<'_> {
         })
     }
 
+    /// The size of `field`, a list of type `tref`, in terms of the other
+    /// fields or parameters in `scope`.
+    fn validate_size(
+        &self,
+        field: &FieldSyntax,
+        tref: &TypeRef,
+        scope: &[(Id, TypeRef)],
+    ) -> Result<Option<SizeExpr>, ValidationError> {
+        let syntax = match &field.size {
+            Some(syntax) => syntax,
+            None => return Ok(None),
+        };
+        self.require(GrammarFeature::ListSizes, field.name.span())?;
+        let invalid = |reason: String| ValidationError::InvalidSize {
+            name: field.name.name().to_string(),
+            reason,
+            location: self.location(field.name.span()),
+        };
+        if !matches!(&**tref.type_(), Type::List(_)) {
+            return Err(invalid("only lists have a size".to_string()));
+        }
+        Ok(Some(self.validate_size_expr(syntax, scope, &invalid)?))
+    }
+
+    fn validate_size_expr(
+        &self,
+        syntax: &SizeExprSyntax,
+        scope: &[(Id, TypeRef)],
+        invalid: &dyn Fn(String) -> ValidationError,
+    ) -> Result<SizeExpr, ValidationError> {
+        let expr = self.resolve_size_expr(syntax, scope)?;
+        check_size_expr(&expr, scope).map_err(invalid)?;
+        Ok(expr)
+    }
+
+    /// The size expression `syntax`, naming the fields or parameters in
+    /// `scope`.
+    fn resolve_size_expr(
+        &self,
+        syntax: &SizeExprSyntax,
+        scope: &[(Id, TypeRef)],
+    ) -> Result<SizeExpr, ValidationError> {
+        let lookup = |id: &wast::Id| match scope.iter().find(|(name, _)| name.as_str() == id.name())
+        {
+            Some((name, _)) => Ok(name.clone()),
+            None => Err(ValidationError::UnknownName {
+                name: id.name().to_string(),
+                suggestions: suggestions(id.name(), scope.iter().map(|(n, _)| n.as_str())),
+                location: self.location(id.span()),
+            }),
+        };
+        Ok(match syntax {
+            SizeExprSyntax::Const(n) => SizeExpr::Const(*n),
+            SizeExprSyntax::Value(id) => SizeExpr::Value(lookup(id)?),
+            SizeExprSyntax::Len(id) => SizeExpr::Len(lookup(id)?),
+            SizeExprSyntax::Product(id) => SizeExpr::Product(lookup(id)?),
+            SizeExprSyntax::TensorSize(dimensions, ty) => SizeExpr::TensorSize {
+                dimensions: lookup(dimensions)?,
+                ty: lookup(ty)?,
+            },
+            SizeExprSyntax::Mul(operands) | SizeExprSyntax::Add(operands) => {
+                let operands = operands
+                    .iter()
+                    .map(|e| self.resolve_size_expr(e, scope))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match syntax {
+                    SizeExprSyntax::Mul(_) => SizeExpr::Mul(operands),
+                    _ => SizeExpr::Add(operands),
+                }
+            }
+        })
+    }
+
     fn validate_union(
         &self,
         syntax: &UnionSyntax,
@@ -495,7 +1205,12 @@ impl DocValidationScope<'_> {
                 })
             })
             .collect::<Result<Vec<_>, _>>()?;
-        Ok(Variant { tag_repr, cases })
+        self.check_cases("cases", cases.len(), tag_repr, span)?;
+        Ok(Variant {
+            tag_repr,
+            cases,
+            feature: None,
+        })
     }
 
     fn validate_variant(
@@ -519,17 +1234,19 @@ impl DocValidationScope<'_> {
             .as_ref()
             .map(|names| names.iter().collect::<HashSet<_>>());
 
+        let mut case_scope = IdentValidation::new();
         let mut cases = syntax
             .cases
             .iter()
             .map(|case| {
-                let name = Id::new(case.item.name.name());
+                let name = case_scope
+                    .introduce(case.item.name.name(), self.location(case.item.name.span()))?;
                 if let Some(names) = &mut name_set {
                     if !names.remove(&name) {
                         return Err(ValidationError::InvalidUnionField {
                             name: name.as_str().to_string(),
                             location: self.location(case.item.name.span()),
-                            reason: format!("does not correspond to variant in tag `tag`"),
+                            reason: "does not correspond to variant in tag `tag`".to_string(),
                         });
                     }
                 }
@@ -556,8 +1273,13 @@ impl DocValidationScope<'_> {
                 .collect::<HashMap<_, _>>();
             cases.sort_by_key(|c| name_pos[&&c.name]);
         }
+        self.check_cases("cases", cases.len(), tag_repr, span)?;
 
-        Ok(Variant { tag_repr, cases })
+        Ok(Variant {
+            tag_repr,
+            cases,
+            feature: None,
+        })
     }
 
     fn union_tag_repr(
@@ -569,33 +1291,19 @@ impl DocValidationScope<'_> {
             Some(tag) => self.validate_datatype(tag, false, span)?,
             None => return Ok((IntRepr::U32, None)),
         };
-        match &**ty.type_() {
-            Type::Variant(e) => {
-                let mut names = Vec::new();
-                for c in e.cases.iter() {
-                    if c.tref.is_some() {
-                        return Err(ValidationError::InvalidUnionTag {
-                            location: self.location(span),
-                            reason: format!("all variant cases should have empty payloads"),
-                        });
-                    }
-                    names.push(c.name.clone());
-                }
-                return Ok((e.tag_repr, Some(names)));
-            }
-            Type::Builtin(BuiltinType::U8 { .. }) => return Ok((IntRepr::U8, None)),
-            Type::Builtin(BuiltinType::U16) => return Ok((IntRepr::U16, None)),
-            Type::Builtin(BuiltinType::U32 { .. }) => return Ok((IntRepr::U32, None)),
-            Type::Builtin(BuiltinType::U64) => return Ok((IntRepr::U64, None)),
-            _ => {}
+        match tag_of(&ty) {
+            Ok(tag) => Ok(tag),
+            Err(TagError::Payload) => Err(ValidationError::InvalidUnionTag {
+                location: self.location(span),
+                reason: "all variant cases should have empty payloads".to_string(),
+            }),
+            Err(TagError::Kind) => Err(ValidationError::WrongKindName {
+                name: "tag".to_string(),
+                location: self.location(span),
+                expected: "enum or builtin",
+                got: ty.type_().kind(),
+            }),
         }
-
-        Err(ValidationError::WrongKindName {
-            name: "tag".to_string(),
-            location: self.location(span),
-            expected: "enum or builtin",
-            got: ty.type_().kind(),
-        })
     }
 
     fn validate_handle(
@@ -606,6 +1314,36 @@ impl DocValidationScope<'_> {
         Ok(HandleDatatype {})
     }
 
+    /// Checks that `count` cases of an enum or variant, or flags, fit in
+    /// `repr`.
+    fn check_cases(
+        &self,
+        kind: &'static str,
+        count: usize,
+        repr: IntRepr,
+        span: wast::Span,
+    ) -> Result<(), ValidationError> {
+        if kind == "cases" && count == 0 {
+            return Err(ValidationError::NoCases {
+                location: self.location(span),
+            });
+        }
+        let capacity = if kind == "flags" {
+            u128::from(repr.bits())
+        } else {
+            1u128 << repr.bits()
+        };
+        if count as u128 > capacity {
+            return Err(ValidationError::TooManyCases {
+                kind,
+                count,
+                repr,
+                location: self.location(span),
+            });
+        }
+        Ok(())
+    }
+
     fn validate_int_repr(
         &self,
         type_: &BuiltinType,
@@ -617,7 +1355,7 @@ impl DocValidationScope<'_> {
             BuiltinType::U32 { .. } => Ok(IntRepr::U32),
             BuiltinType::U64 => Ok(IntRepr::U64),
             _ => Err(ValidationError::InvalidRepr {
-                repr: type_.clone(),
+                repr: *type_,
                 location: self.location(span),
             }),
         }
@@ -628,6 +1366,8 @@ struct ModuleValidation<'a> {
     doc: &'a DocValidationScope<'a>,
     scope: IdentValidation,
     pub entries: HashMap<Id, ModuleEntry>,
+    /// Where each function was defined.
+    funcs: Vec<(Id, Location)>,
 }
 
 impl<'a> ModuleValidation<'a> {
@@ -636,14 +1376,34 @@ impl<'a> ModuleValidation<'a> {
             doc,
             scope: IdentValidation::new(),
             entries: HashMap::new(),
+            funcs: Vec::new(),
         }
     }
 
+    fn validate_decls(
+        &mut self,
+        decls: &[Documented<ModuleDeclSyntax>],
+        definitions: &mut Vec<ModuleDefinition>,
+    ) -> Result<(), ValidationError> {
+        for decl in decls {
+            match &decl.item {
+                ModuleDeclSyntax::Cfg(cfg) => {
+                    if self.doc.cfg(cfg)? {
+                        self.validate_decls(&cfg.items, definitions)?;
+                    }
+                }
+                _ => definitions.push(self.validate_decl(decl)?),
+            }
+        }
+        Ok(())
+    }
+
     fn validate_decl(
         &mut self,
         decl: &Documented<ModuleDeclSyntax>,
     ) -> Result<ModuleDefinition, ValidationError> {
         match &decl.item {
+            ModuleDeclSyntax::Cfg(_) => unreachable!("cfg blocks are validated by validate_decls"),
             ModuleDeclSyntax::Import(syntax) => {
                 let loc = self.doc.location(syntax.name_loc);
                 let name = self.scope.introduce(syntax.name, loc)?;
@@ -661,9 +1421,10 @@ impl<'a> ModuleValidation<'a> {
             }
             ModuleDeclSyntax::Func(syntax) => {
                 let loc = self.doc.location(syntax.export_loc);
-                let name = self.scope.introduce(syntax.export, loc)?;
+                let name = self.scope.introduce(syntax.export, loc.clone())?;
+                self.funcs.push((name.clone(), loc));
                 let mut argnames = IdentValidation::new();
-                let params = syntax
+                let mut params = syntax
                     .params
                     .iter()
                     .map(|f| {
@@ -677,10 +1438,18 @@ impl<'a> ModuleValidation<'a> {
                                 false,
                                 f.item.name.span(),
                             )?,
+                            size: None,
                             docs: f.comments.docs(),
                         })
                     })
                     .collect::<Result<Vec<_>, _>>()?;
+                let scope = params
+                    .iter()
+                    .map(|p| (p.name.clone(), p.tref.clone()))
+                    .collect::<Vec<_>>();
+                for (p, f) in params.iter_mut().zip(syntax.params.iter()) {
+                    p.size = self.doc.validate_size(&f.item, &p.tref, &scope)?;
+                }
                 let results = syntax
                     .results
                     .iter()
@@ -688,16 +1457,30 @@ impl<'a> ModuleValidation<'a> {
                         let tref =
                             self.doc
                                 .validate_datatype(&f.item.type_, false, f.item.name.span())?;
+                        if f.item.size.is_some() {
+                            self.doc
+                                .require(GrammarFeature::ListSizes, f.item.name.span())?;
+                            return Err(ValidationError::InvalidSize {
+                                name: f.item.name.name().to_string(),
+                                reason: "results can't have a size".to_string(),
+                                location: self.doc.location(f.item.name.span()),
+                            });
+                        }
                         Ok(InterfaceFuncParam {
                             name: argnames.introduce(
                                 f.item.name.name(),
                                 self.doc.location(f.item.name.span()),
                             )?,
                             tref,
+                            size: None,
                             docs: f.comments.docs(),
                         })
                     })
                     .collect::<Result<Vec<_>, _>>()?;
+                if let Some(f) = &syntax.feature {
+                    self.doc
+                        .require(GrammarFeature::OptionalFeatures, f.span())?;
+                }
                 let noreturn = syntax.noreturn;
                 let abi = Abi::Preview1;
                 abi.validate(&params, &results)
@@ -711,7 +1494,9 @@ impl<'a> ModuleValidation<'a> {
                     params,
                     results,
                     noreturn,
+                    feature: syntax.feature.as_ref().map(|f| Id::new(f.name())),
                     docs: decl.comments.docs(),
+                    meta: self.doc.validate_meta(&syntax.meta)?,
                 });
                 self.entries
                     .insert(name, ModuleEntry::Func(Rc::downgrade(&rc_func)));
@@ -720,3 +1505,228 @@ impl<'a> ModuleValidation<'a> {
         }
     }
 }
+
+/// `tref`, from the body of `generic`, with each of its type parameters
+/// replaced by the type argument in the same position of `args`. What the
+/// body was validated with stand-ins for is checked again with the
+/// arguments: the tags of variants, and the fields sizes are in terms of.
+fn substitute(tref: &TypeRef, generic: &Generic, args: &[TypeRef]) -> Result<TypeRef, String> {
+    let sub = |tref: &TypeRef| substitute(tref, generic, args);
+    let ty = match tref {
+        TypeRef::Name(nt) => {
+            return Ok(
+                match generic.params.iter().position(|p| Rc::ptr_eq(p, nt)) {
+                    Some(i) => args[i].clone(),
+                    None => tref.clone(),
+                },
+            )
+        }
+        TypeRef::Value(ty) => ty,
+    };
+    Ok(TypeRef::Value(Rc::new(match &**ty {
+        Type::Record(r) => {
+            let members = r
+                .members
+                .iter()
+                .map(|m| {
+                    Ok(RecordMember {
+                        tref: sub(&m.tref)?,
+                        ..m.clone()
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let scope = members
+                .iter()
+                .map(|m| (m.name.clone(), m.tref.clone()))
+                .collect::<Vec<_>>();
+            for m in members.iter() {
+                if let Some(size) = &m.size {
+                    check_size_expr(size, &scope)
+                        .map_err(|e| format!("size of `{}`: {}", m.name.as_str(), e))?;
+                }
+            }
+            Type::Record(RecordDatatype {
+                kind: r.kind.clone(),
+                members,
+            })
+        }
+        Type::Variant(v) => {
+            let mut cases = v
+                .cases
+                .iter()
+                .map(|c| {
+                    Ok(Case {
+                        tref: c.tref.as_ref().map(sub).transpose()?,
+                        ..c.clone()
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let mut tag_repr = v.tag_repr;
+            if let Some(t) = generic.tags.iter().find(|t| Rc::ptr_eq(&t.variant, ty)) {
+                let tag = &args[t.param];
+                let invalid = |reason: &str| format!("tag `{}` {}", mangle(tag), reason);
+                let (repr, names) = match tag_of(tag) {
+                    Ok(tag) => tag,
+                    Err(TagError::Payload) => return Err(invalid("has cases with payloads")),
+                    Err(TagError::Kind) => {
+                        return Err(invalid("is not an enum or unsigned integer"))
+                    }
+                };
+                match names {
+                    Some(names) if t.union => {
+                        if names.len() != cases.len() {
+                            return Err(invalid("has another number of cases than the union"));
+                        }
+                        for (case, name) in cases.iter_mut().zip(names) {
+                            case.name = name;
+                        }
+                    }
+                    Some(names) => {
+                        if names.len() != cases.len()
+                            || cases.iter().any(|c| !names.contains(&c.name))
+                        {
+                            return Err(invalid("has other cases than the variant"));
+                        }
+                        cases.sort_by_key(|c| names.iter().position(|n| *n == c.name));
+                    }
+                    None => {}
+                }
+                if cases.len() as u128 > 1u128 << repr.bits() {
+                    return Err(invalid("is too narrow for the cases of the variant"));
+                }
+                tag_repr = repr;
+            }
+            Type::Variant(Variant {
+                tag_repr,
+                cases,
+                ..v.clone()
+            })
+        }
+        Type::List(t) => Type::List(sub(t)?),
+        Type::Pointer(t) => Type::Pointer(sub(t)?),
+        Type::ConstPointer(t) => Type::ConstPointer(sub(t)?),
+        Type::Handle(_) | Type::Builtin(_) => return Ok(tref.clone()),
+    })))
+}
+
+/// Why a type can't tag a variant.
+enum TagError {
+    /// It's an enum with payloads.
+    Payload,
+    /// It's neither an enum nor an unsigned integer.
+    Kind,
+}
+
+/// The representation of the tag of a variant tagged by `tag`, and the
+/// names of its cases if they are those of an enum.
+fn tag_of(tag: &TypeRef) -> Result<(IntRepr, Option<Vec<Id>>), TagError> {
+    match &**tag.type_() {
+        Type::Variant(e) => {
+            if e.cases.iter().any(|c| c.tref.is_some()) {
+                return Err(TagError::Payload);
+            }
+            Ok((
+                e.tag_repr,
+                Some(e.cases.iter().map(|c| c.name.clone()).collect()),
+            ))
+        }
+        Type::Builtin(BuiltinType::U8 { .. }) => Ok((IntRepr::U8, None)),
+        Type::Builtin(BuiltinType::U16) => Ok((IntRepr::U16, None)),
+        Type::Builtin(BuiltinType::U32 { .. }) => Ok((IntRepr::U32, None)),
+        Type::Builtin(BuiltinType::U64) => Ok((IntRepr::U64, None)),
+        _ => Err(TagError::Kind),
+    }
+}
+
+/// Whether `tref` refers to any of the type parameters `params`.
+fn mentions(tref: &TypeRef, params: &[Rc<NamedType>]) -> bool {
+    match tref {
+        TypeRef::Name(nt) => params.iter().any(|p| Rc::ptr_eq(p, nt)),
+        TypeRef::Value(ty) => match &**ty {
+            Type::Record(r) => r.members.iter().any(|m| mentions(&m.tref, params)),
+            Type::Variant(v) => v
+                .cases
+                .iter()
+                .any(|c| c.tref.as_ref().is_some_and(|t| mentions(t, params))),
+            Type::List(t) | Type::Pointer(t) | Type::ConstPointer(t) => mentions(t, params),
+            Type::Handle(_) | Type::Builtin(_) => false,
+        },
+    }
+}
+
+/// A type argument as part of the name of an instance, such as `list_u8`
+/// for `(list u8)`.
+fn mangle(tref: &TypeRef) -> String {
+    let name = match tref {
+        TypeRef::Name(nt) => return nt.name.as_str().to_string(),
+        TypeRef::Value(_) => tref.type_name().to_lowercase(),
+    };
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Check that the fields or parameters `expr` names, in `scope`, are of
+/// the types it needs, with the reason if they aren't.
+fn check_size_expr(expr: &SizeExpr, scope: &[(Id, TypeRef)]) -> Result<(), String> {
+    let tref = |name: &Id| {
+        scope
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, tref)| tref.clone())
+            .expect("size expression names are resolved in scope")
+    };
+    let list_of = |tref: &TypeRef| match &**tref.type_() {
+        Type::List(elem) => Some(elem.type_().clone()),
+        _ => None,
+    };
+    match expr {
+        SizeExpr::Const(_) => {}
+        SizeExpr::Value(name) => {
+            if !is_integer(tref(name).type_()) {
+                return Err(format!("`{}` is not an integer", name.as_str()));
+            }
+        }
+        SizeExpr::Len(name) => {
+            if list_of(&tref(name)).is_none() {
+                return Err(format!("`{}` is not a list", name.as_str()));
+            }
+        }
+        SizeExpr::Product(name) => {
+            if !list_of(&tref(name)).is_some_and(|elem| is_integer(&elem)) {
+                return Err(format!("`{}` is not a list of integers", name.as_str()));
+            }
+        }
+        SizeExpr::TensorSize { dimensions, ty } => {
+            let is_u32 = |elem: &Type| matches!(elem, Type::Builtin(BuiltinType::U32 { .. }));
+            if !list_of(&tref(dimensions)).is_some_and(|elem| is_u32(&elem)) {
+                return Err(format!("`{}` is not a list of u32", dimensions.as_str()));
+            }
+            let is_tensor_type = match &tref(ty) {
+                TypeRef::Name(nt) => ElementType::cases_of(nt).is_some(),
+                TypeRef::Value(_) => false,
+            };
+            if !is_tensor_type {
+                return Err(format!(
+                    "`{}` is not a `{}`",
+                    ty.as_str(),
+                    crate::tensor::TENSOR_TYPE
+                ));
+            }
+        }
+        SizeExpr::Mul(operands) | SizeExpr::Add(operands) => {
+            for operand in operands {
+                check_size_expr(operand, scope)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_integer(ty: &Type) -> bool {
+    match ty {
+        Type::Builtin(b) => !matches!(b, BuiltinType::Char | BuiltinType::F32 | BuiltinType::F64),
+        _ => false,
+    }
+}
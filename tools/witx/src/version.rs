@@ -0,0 +1,127 @@
+//! Versions of the witx language.
+//!
+//! A document declares the version of the grammar it is written in with a
+//! `(@witx version "0.9")` pragma, which applies to the file it appears in.
+//! Files without one are read with the latest version. Constructs added to
+//! the grammar after the declared version are rejected, so that a document
+//! keeps meaning the same thing as the grammar grows.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WitxVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl WitxVersion {
+    /// The grammar before optional features and list sizes.
+    pub const V0_8: WitxVersion = WitxVersion::new(0, 8);
+    pub const V0_9: WitxVersion = WitxVersion::new(0, 9);
+    /// The grammar with metadata, `cfg` blocks and generic types, added
+    /// after 0.9 was released.
+    pub const V0_10: WitxVersion = WitxVersion::new(0, 10);
+    /// The latest version, which this crate reads documents without a
+    /// pragma as.
+    pub const LATEST: WitxVersion = WitxVersion::V0_10;
+    /// Every version this crate can read.
+    pub const SUPPORTED: &'static [WitxVersion] =
+        &[WitxVersion::V0_8, WitxVersion::V0_9, WitxVersion::V0_10];
+
+    pub const fn new(major: u32, minor: u32) -> Self {
+        WitxVersion { major, minor }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        WitxVersion::SUPPORTED.contains(self)
+    }
+
+    /// Whether documents of this version may use `feature`.
+    pub fn allows(&self, feature: GrammarFeature) -> bool {
+        *self >= feature.since()
+    }
+}
+
+impl Default for WitxVersion {
+    fn default() -> Self {
+        WitxVersion::LATEST
+    }
+}
+
+impl fmt::Display for WitxVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for WitxVersion {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let invalid = || format!("`{}` is not a version: expected MAJOR.MINOR", s);
+        let (major, minor) = s.split_once('.').ok_or_else(invalid)?;
+        Ok(WitxVersion {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Constructs of the grammar which weren't in its first version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GrammarFeature {
+    /// `(@witx feature $name)` on functions and enums.
+    OptionalFeatures,
+    /// `(@witx size ...)` on list fields and params.
+    ListSizes,
+    /// `(@witx meta key "value")` on modules, functions and types.
+    Metadata,
+    /// `(@witx cfg ...)` blocks of declarations.
+    Conditionals,
+    /// `(@witx generic ...)` types, and their instances.
+    Generics,
+}
+
+impl GrammarFeature {
+    /// The version which added the construct.
+    pub fn since(&self) -> WitxVersion {
+        match self {
+            GrammarFeature::OptionalFeatures | GrammarFeature::ListSizes => WitxVersion::V0_9,
+            GrammarFeature::Metadata | GrammarFeature::Conditionals | GrammarFeature::Generics => {
+                WitxVersion::V0_10
+            }
+        }
+    }
+}
+
+impl fmt::Display for GrammarFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            GrammarFeature::OptionalFeatures => "`(@witx feature ...)`",
+            GrammarFeature::ListSizes => "`(@witx size ...)`",
+            GrammarFeature::Metadata => "`(@witx meta ...)`",
+            GrammarFeature::Conditionals => "`(@witx cfg ...)`",
+            GrammarFeature::Generics => "`(@witx generic ...)`",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("0.9".parse(), Ok(WitxVersion::V0_9));
+        assert_eq!(WitxVersion::new(1, 12).to_string(), "1.12");
+        assert!("1".parse::<WitxVersion>().is_err());
+        assert!("a.b".parse::<WitxVersion>().is_err());
+        assert!(WitxVersion::V0_9.allows(GrammarFeature::ListSizes));
+        assert!(!WitxVersion::V0_8.allows(GrammarFeature::OptionalFeatures));
+        assert!(!WitxVersion::V0_9.allows(GrammarFeature::Metadata));
+        assert!(WitxVersion::LATEST.allows(GrammarFeature::Metadata));
+        assert_eq!("0.10".parse(), Ok(WitxVersion::V0_10));
+        assert!(WitxVersion::V0_9 < WitxVersion::V0_10);
+    }
+}
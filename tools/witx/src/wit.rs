@@ -0,0 +1,1712 @@
+//! Conversion between witx documents and WIT, the interface language of the
+//! component model, for projects migrating from one to the other.
+//!
+//! The conversion is between a resolved [`Document`] and WIT source text,
+//! which `wit-parser` and `wit-bindgen` read and write, so that a package
+//! can be checked with this crate's polyfill and ABI analyses while it's
+//! defined in WIT, and a witx spec can be handed to WIT tooling.
+//!
+//! [`to_wit`] puts every type of a document in an interface `types`, and
+//! each module in an interface of its own which `use`s the types its
+//! functions take. Names are kebab-cased, and escaped with `%` where they
+//! are WIT keywords. WIT has no pointers, so they are `u32` addresses, and
+//! handles are resources. Enums and flags lose their reprs, since the
+//! canonical ABI sizes them by their number of cases.
+//!
+//! [`from_wit`] reads the interfaces of a package, with their `use`s, types,
+//! resources and functions, into a witx document: each interface with
+//! functions is a module of the same name, snake-cased. Resource methods are
+//! functions of the interface, named after the resource, taking it as
+//! `$self`. Worlds aren't interfaces, and are skipped.
+//!
+//! [`to_wit_world`] renders just the modules of a component-model
+//! [`World`], and the types they use, with the world importing and
+//! exporting them, to package a witx spec as a component interface.
+//!
+//! Both directions leave [`Note`]s on what they can't carry over exactly.
+//!
+//! ```
+//! let wit = witx::wit::to_wit(
+//!     &witx::parse(
+//!         "(typename $graph (handle))
+//!          (module $nn (@interface func (export \"load\") (result $graph $graph)))",
+//!     )
+//!     .unwrap(),
+//!     "wasi:nn",
+//! );
+//! assert!(wit.source.contains("load: func() -> graph;"));
+//!
+//! let import = witx::wit::from_wit(&wit.source).unwrap();
+//! assert!(import.source.contains("(typename $graph (handle))"));
+//! import.document().unwrap();
+//! ```
+
+use crate::graph::module_types;
+use crate::limits::{LimitError, Limits, MAX_NESTING};
+use crate::{
+    BuiltinType, Document, Id, IntRepr, InterfaceFunc, Location, Module, NamedType, RecordKind,
+    Type, TypeRef, Variant, WitxError,
+};
+use heck::{KebabCase, SnakeCase};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum WitError {
+    #[error("Unterminated comment starting on line {line}")]
+    UnterminatedComment { line: usize },
+    #[error("Syntax error on line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("Unsupported WIT on line {line}: {message}")]
+    Unsupported { line: usize, message: String },
+    #[error("Limit exceeded")]
+    Limit(#[from] LimitError),
+}
+
+/// Something the conversion leaves out or changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    /// The item the note is about, like `type tensor` or `func nn::load`.
+    pub item: String,
+    pub message: String,
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.item, self.message)
+    }
+}
+
+/// A document, as a WIT package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wit {
+    /// The WIT source of the package.
+    pub source: String,
+    pub notes: Vec<Note>,
+}
+
+/// A witx document read from a WIT package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WitImport {
+    /// The witx source of the document.
+    pub source: String,
+    pub notes: Vec<Note>,
+}
+
+impl WitImport {
+    /// The document, validated.
+    pub fn document(&self) -> Result<Document, WitxError> {
+        crate::parse(&self.source)
+    }
+}
+
+/// WIT's keywords, which can only be names escaped with `%`.
+const KEYWORDS: &[&str] = &[
+    "as",
+    "bool",
+    "borrow",
+    "char",
+    "constructor",
+    "enum",
+    "export",
+    "f32",
+    "f64",
+    "flags",
+    "float32",
+    "float64",
+    "from",
+    "func",
+    "future",
+    "import",
+    "include",
+    "interface",
+    "list",
+    "option",
+    "own",
+    "package",
+    "record",
+    "resource",
+    "result",
+    "s16",
+    "s32",
+    "s64",
+    "s8",
+    "static",
+    "stream",
+    "string",
+    "tuple",
+    "type",
+    "u16",
+    "u32",
+    "u64",
+    "u8",
+    "use",
+    "variant",
+    "with",
+    "world",
+];
+
+/// The WIT name of the witx name `name`.
+fn wit_name(name: &str) -> String {
+    let name = name.to_kebab_case();
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("%{}", name)
+    } else {
+        name
+    }
+}
+
+/// The repr the canonical ABI gives an enum or variant of `cases` cases.
+fn canonical_tag(cases: usize) -> IntRepr {
+    if cases <= 1 << 8 {
+        IntRepr::U8
+    } else if cases <= 1 << 16 {
+        IntRepr::U16
+    } else {
+        IntRepr::U32
+    }
+}
+
+/// The repr the canonical ABI gives `flags` flags.
+fn canonical_flags(flags: usize) -> IntRepr {
+    match flags {
+        0..=8 => IntRepr::U8,
+        9..=16 => IntRepr::U16,
+        17..=32 => IntRepr::U32,
+        _ => IntRepr::U64,
+    }
+}
+
+fn repr_name(repr: IntRepr) -> &'static str {
+    match repr {
+        IntRepr::U8 => "u8",
+        IntRepr::U16 => "u16",
+        IntRepr::U32 => "u32",
+        IntRepr::U64 => "u64",
+    }
+}
+
+/// Whether `v` has the cases `none` and `some`, with a payload, of an
+/// `option`.
+fn as_option(v: &Variant) -> Option<&TypeRef> {
+    match &v.cases[..] {
+        [none, some] if none.name == "none" && none.tref.is_none() && some.name == "some" => {
+            some.tref.as_ref()
+        }
+        _ => None,
+    }
+}
+
+fn docs(docs: &str, indent: &str, prefix: &str) -> String {
+    let mut out = String::new();
+    for line in docs.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            writeln!(out, "{}{}", indent, prefix).unwrap();
+        } else {
+            writeln!(out, "{}{} {}", indent, prefix, line).unwrap();
+        }
+    }
+    out
+}
+
+/// Render `doc` as the WIT package `package`, like `wasi:nn`.
+pub fn to_wit(doc: &Document, package: &str) -> Wit {
+    let types = doc.typenames().collect::<Vec<_>>();
+    let modules = doc.modules().collect::<Vec<_>>();
+    render(package, &types, &modules, "")
+}
+
+/// A component-model world: modules of a document the component imports,
+/// and those it exports.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct World {
+    pub name: String,
+    pub imports: Vec<Id>,
+    pub exports: Vec<Id>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum WorldError {
+    #[error("Module {} not present in the document", .0.as_str())]
+    ModuleNotPresent(Id),
+    #[error("Module {} is both imported and exported", .0.as_str())]
+    ImportedAndExported(Id),
+}
+
+/// Render the modules of `world` in `doc` as the WIT package `package`,
+/// with the world importing and exporting their interfaces. Only the types
+/// the modules use are defined, handles among them as resources.
+pub fn to_wit_world(doc: &Document, package: &str, world: &World) -> Result<Wit, WorldError> {
+    if let Some(name) = world.imports.iter().find(|m| world.exports.contains(m)) {
+        return Err(WorldError::ImportedAndExported(name.clone()));
+    }
+    let modules = world
+        .imports
+        .iter()
+        .chain(world.exports.iter())
+        .map(|name| {
+            doc.module(name)
+                .ok_or_else(|| WorldError::ModuleNotPresent(name.clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let used = modules
+        .iter()
+        .flat_map(|m| module_types(m))
+        .map(|t| t.name.clone())
+        .collect::<HashSet<_>>();
+    let types = doc
+        .typenames()
+        .filter(|t| used.contains(&t.name))
+        .collect::<Vec<_>>();
+
+    let mut out = format!("\nworld {} {{\n", wit_name(&world.name));
+    for (kind, names) in [("import", &world.imports), ("export", &world.exports)] {
+        for name in names.iter() {
+            writeln!(out, "    {} {};", kind, wit_name(name.as_str())).unwrap();
+        }
+    }
+    out.push_str("}\n");
+    Ok(render(package, &types, &modules, &out))
+}
+
+/// The package `package` of the interface `types`, of `types`, and an
+/// interface for each of `modules`, followed by `world`.
+fn render(package: &str, types: &[Rc<NamedType>], modules: &[Rc<Module>], world: &str) -> Wit {
+    let mut writer = WitWriter::default();
+    for nt in types {
+        writer.typename(nt);
+    }
+    let mut interfaces = String::new();
+    for module in modules {
+        interfaces.push('\n');
+        interfaces.push_str(&writer.module(module));
+    }
+
+    let mut source = String::new();
+    writeln!(source, "package {};", package).unwrap();
+    if !writer.types.is_empty() {
+        source.push_str("\ninterface types {");
+        source.push_str(&writer.types);
+        source.push_str("}\n");
+    }
+    source.push_str(&interfaces);
+    source.push_str(world);
+    Wit {
+        source,
+        notes: writer.notes,
+    }
+}
+
+#[derive(Default)]
+struct WitWriter {
+    /// The definitions of the `types` interface.
+    types: String,
+    notes: Vec<Note>,
+    /// The item being written, for notes.
+    item: String,
+    /// The types the functions of the module being written use.
+    used: Vec<String>,
+}
+
+impl WitWriter {
+    fn note(&mut self, message: impl Into<String>) {
+        let note = Note {
+            item: self.item.clone(),
+            message: message.into(),
+        };
+        if !self.notes.contains(&note) {
+            self.notes.push(note);
+        }
+    }
+
+    fn typename(&mut self, nt: &NamedType) {
+        self.item = format!("type {}", nt.name.as_str());
+        match &nt.tref {
+            TypeRef::Name(other) => {
+                let out = format!(
+                    "{}    type {} = {};\n",
+                    docs(&nt.docs, "    ", "///"),
+                    wit_name(nt.name.as_str()),
+                    wit_name(other.name.as_str())
+                );
+                self.define(out);
+            }
+            TypeRef::Value(ty) => self.definition(nt.name.as_str(), ty, &nt.docs),
+        }
+    }
+
+    fn define(&mut self, out: String) {
+        self.types.push('\n');
+        self.types.push_str(&out);
+    }
+
+    /// Define the type `name` as `ty` in the `types` interface, after the
+    /// anonymous types it uses.
+    fn definition(&mut self, name: &str, ty: &Type, doc: &str) {
+        let wit = wit_name(name);
+        let mut out = docs(doc, "    ", "///");
+        match ty {
+            Type::Record(r) => match r.kind {
+                RecordKind::Bitflags(repr) => {
+                    if repr != canonical_flags(r.members.len()) {
+                        self.note(format!("the {} repr is lost", repr_name(repr)));
+                    }
+                    writeln!(out, "    flags {} {{", wit).unwrap();
+                    for m in r.members.iter() {
+                        out.push_str(&docs(&m.docs, "        ", "///"));
+                        writeln!(out, "        {},", wit_name(m.name.as_str())).unwrap();
+                    }
+                    out.push_str("    }\n");
+                }
+                RecordKind::Tuple => {
+                    let ty = self.expr(&TypeRef::Value(std::rc::Rc::new(ty.clone())), name);
+                    writeln!(out, "    type {} = {};", wit, ty).unwrap();
+                }
+                RecordKind::Other => {
+                    writeln!(out, "    record {} {{", wit).unwrap();
+                    for m in r.members.iter() {
+                        if m.size.is_some() {
+                            self.note(format!("the size of `{}` is lost", m.name.as_str()));
+                        }
+                        let ty = self.expr(&m.tref, &format!("{}_{}", name, m.name.as_str()));
+                        out.push_str(&docs(&m.docs, "        ", "///"));
+                        writeln!(out, "        {}: {},", wit_name(m.name.as_str()), ty).unwrap();
+                    }
+                    out.push_str("    }\n");
+                }
+            },
+            Type::Variant(v)
+                if v.is_bool() || v.as_expected().is_some() || as_option(v).is_some() =>
+            {
+                let ty = self.expr(&TypeRef::Value(std::rc::Rc::new(ty.clone())), name);
+                writeln!(out, "    type {} = {};", wit, ty).unwrap();
+            }
+            Type::Variant(v) => {
+                if v.tag_repr != canonical_tag(v.cases.len()) {
+                    self.note(format!("the {} tag is lost", repr_name(v.tag_repr)));
+                }
+                let kind = if v.is_enum() { "enum" } else { "variant" };
+                writeln!(out, "    {} {} {{", kind, wit).unwrap();
+                for c in v.cases.iter() {
+                    out.push_str(&docs(&c.docs, "        ", "///"));
+                    match &c.tref {
+                        Some(tref) => {
+                            let ty = self.expr(tref, &format!("{}_{}", name, c.name.as_str()));
+                            writeln!(out, "        {}({}),", wit_name(c.name.as_str()), ty)
+                                .unwrap();
+                        }
+                        None => writeln!(out, "        {},", wit_name(c.name.as_str())).unwrap(),
+                    }
+                }
+                out.push_str("    }\n");
+            }
+            Type::Handle(_) => writeln!(out, "    resource {};", wit).unwrap(),
+            Type::Builtin(_) | Type::List(_) | Type::Pointer(_) | Type::ConstPointer(_) => {
+                let ty = self.expr(&TypeRef::Value(std::rc::Rc::new(ty.clone())), name);
+                writeln!(out, "    type {} = {};", wit, ty).unwrap();
+            }
+        }
+        self.define(out);
+    }
+
+    /// The WIT type of `tref`. Anonymous records, flags, enums and variants
+    /// are defined named `name`.
+    fn expr(&mut self, tref: &TypeRef, name: &str) -> String {
+        let ty = match tref {
+            TypeRef::Name(nt) => {
+                let wit = wit_name(nt.name.as_str());
+                if !self.used.contains(&wit) {
+                    self.used.push(wit.clone());
+                }
+                return wit;
+            }
+            TypeRef::Value(ty) => ty,
+        };
+        let opt = |tref: Option<&TypeRef>, case: &str, this: &mut Self| match tref {
+            Some(tref) => this.expr(tref, &format!("{}_{}", name, case)),
+            None => "_".to_string(),
+        };
+        match &**ty {
+            Type::Builtin(b) => self.builtin(*b).to_string(),
+            Type::List(elem) => match &**elem.type_() {
+                Type::Builtin(BuiltinType::Char) => "string".to_string(),
+                _ => format!("list<{}>", self.expr(elem, &format!("{}_item", name))),
+            },
+            Type::Pointer(_) | Type::ConstPointer(_) => {
+                self.note("pointers are `u32` addresses");
+                "u32".to_string()
+            }
+            Type::Handle(_) => {
+                self.note("anonymous handles are `u32`s");
+                "u32".to_string()
+            }
+            Type::Record(r) if r.is_tuple() => {
+                let members = r
+                    .members
+                    .iter()
+                    .enumerate()
+                    .map(|(i, m)| self.expr(&m.tref, &format!("{}_{}", name, i)))
+                    .collect::<Vec<_>>();
+                format!("tuple<{}>", members.join(", "))
+            }
+            Type::Variant(v) if v.is_bool() => "bool".to_string(),
+            Type::Variant(v) if v.as_expected().is_some() => {
+                let (ok, err) = v.as_expected().unwrap();
+                match (opt(ok, "ok", self), opt(err, "err", self)) {
+                    (ok, err) if ok == "_" && err == "_" => "result".to_string(),
+                    (ok, err) if err == "_" => format!("result<{}>", ok),
+                    (ok, err) => format!("result<{}, {}>", ok, err),
+                }
+            }
+            Type::Variant(v) if as_option(v).is_some() => {
+                let some = self.expr(as_option(v).unwrap(), &format!("{}_some", name));
+                format!("option<{}>", some)
+            }
+            Type::Record(_) | Type::Variant(_) => {
+                self.definition(name, ty, "");
+                let wit = wit_name(name);
+                if !self.used.contains(&wit) {
+                    self.used.push(wit.clone());
+                }
+                wit
+            }
+        }
+    }
+
+    fn builtin(&mut self, b: BuiltinType) -> &'static str {
+        match b {
+            BuiltinType::U8 { lang_c_char } => {
+                if lang_c_char {
+                    self.note("`char8` is `u8`");
+                }
+                "u8"
+            }
+            BuiltinType::U16 => "u16",
+            BuiltinType::U32 { lang_ptr_size } => {
+                if lang_ptr_size {
+                    self.note("`usize` is `u32`");
+                }
+                "u32"
+            }
+            BuiltinType::U64 => "u64",
+            BuiltinType::S8 => "s8",
+            BuiltinType::S16 => "s16",
+            BuiltinType::S32 => "s32",
+            BuiltinType::S64 => "s64",
+            BuiltinType::F32 => "f32",
+            BuiltinType::F64 => "f64",
+            BuiltinType::Char => "char",
+        }
+    }
+
+    /// The interface of `module`.
+    fn module(&mut self, module: &Module) -> String {
+        self.used.clear();
+        let mut funcs = String::new();
+        for func in module.funcs() {
+            funcs.push('\n');
+            funcs.push_str(&self.func(module, &func));
+        }
+        let mut out = docs(&module.docs, "", "///");
+        writeln!(out, "interface {} {{", wit_name(module.name.as_str())).unwrap();
+        if !self.used.is_empty() {
+            writeln!(out, "    use types.{{{}}};", self.used.join(", ")).unwrap();
+        }
+        out.push_str(&funcs);
+        out.push_str("}\n");
+        out
+    }
+
+    fn func(&mut self, module: &Module, func: &InterfaceFunc) -> String {
+        self.item = format!("func {}::{}", module.name.as_str(), func.name.as_str());
+        let context =
+            |p: &crate::InterfaceFuncParam| format!("{}_{}", func.name.as_str(), p.name.as_str());
+        let params = func
+            .params
+            .iter()
+            .map(|p| {
+                if p.size.is_some() {
+                    self.note(format!("the size of `{}` is lost", p.name.as_str()));
+                }
+                format!(
+                    "{}: {}",
+                    wit_name(p.name.as_str()),
+                    self.expr(&p.tref, &context(p))
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut results = func
+            .results
+            .iter()
+            .map(|r| self.expr(&r.tref, &context(r)))
+            .collect::<Vec<_>>();
+        if results.len() > 1 {
+            self.note("the results are a tuple");
+            results = vec![format!("tuple<{}>", results.join(", "))];
+        }
+        if func.noreturn {
+            self.note("WIT can't say the function doesn't return");
+        }
+        let mut out = docs(&func.docs, "    ", "///");
+        write!(
+            out,
+            "    {}: func({})",
+            wit_name(func.name.as_str()),
+            params.join(", ")
+        )
+        .unwrap();
+        if let Some(result) = results.first() {
+            write!(out, " -> {}", result).unwrap();
+        }
+        out.push_str(";\n");
+        out
+    }
+}
+
+/// Read the WIT package `source` into a witx document.
+pub fn from_wit(source: &str) -> Result<WitImport, WitError> {
+    from_wit_with(source, &Limits::new())
+}
+
+/// Like `from_wit`, failing on input exceeding `limits`. The source is
+/// located at the path `-`, and on the first column of its lines.
+pub fn from_wit_with(source: &str, limits: &Limits) -> Result<WitImport, WitError> {
+    let path = Path::new("-");
+    limits.check_file_size(path, source.len())?;
+    let tokens = lex(source)?;
+    let location = |line| Location {
+        path: path.to_path_buf(),
+        line,
+        column: 1,
+    };
+    let mut depth = 0usize;
+    for (token, line) in tokens.iter() {
+        match token {
+            Token::Punct('{') | Token::Punct('(') | Token::Punct('<') => {
+                depth += 1;
+                limits.check_nesting(depth, || location(*line))?;
+                // The parser recurses into nested types.
+                if depth > MAX_NESTING {
+                    return Err(WitError::Syntax {
+                        line: *line,
+                        message: format!("nesting deeper than {} levels", MAX_NESTING),
+                    });
+                }
+            }
+            Token::Punct('}') | Token::Punct(')') | Token::Punct('>') => {
+                depth = depth.saturating_sub(1)
+            }
+            Token::Id(name, _) => limits.check_ident(name, || location(*line))?,
+            _ => {}
+        }
+    }
+    let mut parser = WitParser {
+        tokens: &tokens,
+        pos: 0,
+        package: Package::default(),
+        notes: Vec::new(),
+    };
+    parser.file()?;
+    let WitParser {
+        mut package,
+        mut notes,
+        ..
+    } = parser;
+    package.lower_results(&mut notes);
+    let source = package.to_witx(&mut notes);
+    Ok(WitImport { source, notes })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A name, and whether it was escaped with `%`.
+    Id(String, bool),
+    Doc(String),
+    Arrow,
+    Punct(char),
+}
+
+fn lex(source: &str) -> Result<Vec<(Token, usize)>, WitError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            let start = i;
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            let comment = chars[start..i].iter().collect::<String>();
+            if let Some(doc) = comment.strip_prefix("///") {
+                let doc = doc.strip_prefix(' ').unwrap_or(doc);
+                tokens.push((Token::Doc(doc.trim_end().to_string()), line));
+            }
+        } else if c == '/' && next == Some('*') {
+            let start = line;
+            let mut depth = 0;
+            loop {
+                match (chars.get(i), chars.get(i + 1)) {
+                    (Some('/'), Some('*')) => {
+                        depth += 1;
+                        i += 2;
+                    }
+                    (Some('*'), Some('/')) => {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    (Some(c), _) => {
+                        if *c == '\n' {
+                            line += 1;
+                        }
+                        i += 1;
+                    }
+                    (None, _) => return Err(WitError::UnterminatedComment { line: start }),
+                }
+            }
+        } else if c == '-' && next == Some('>') {
+            tokens.push((Token::Arrow, line));
+            i += 2;
+        } else if c == '%' || is_name(c) {
+            let escaped = c == '%';
+            let start = if escaped { i + 1 } else { i };
+            i = start;
+            while i < chars.len() && is_name(chars[i]) {
+                i += 1;
+            }
+            let name = chars[start..i].iter().collect::<String>();
+            if name.is_empty() {
+                return Err(WitError::Syntax {
+                    line,
+                    message: "expected a name after `%`".to_string(),
+                });
+            }
+            tokens.push((Token::Id(name, escaped), line));
+        } else if "{}()<>,;:=./@*".contains(c) {
+            tokens.push((Token::Punct(c), line));
+            i += 1;
+        } else {
+            return Err(WitError::Syntax {
+                line,
+                message: format!("unexpected `{}`", c),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// A WIT type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Ty {
+    /// A primitive, by its witx name.
+    Builtin(&'static str),
+    List(Box<Ty>),
+    Option(Box<Ty>),
+    Result(Option<Box<Ty>>, Option<Box<Ty>>),
+    Tuple(Vec<Ty>),
+    /// A type defined in the package, by its witx name.
+    Named(String),
+}
+
+impl Ty {
+    fn names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Ty::Builtin(_) => {}
+            Ty::List(t) | Ty::Option(t) => t.names(names),
+            Ty::Result(ok, err) => {
+                for t in ok.iter().chain(err.iter()) {
+                    t.names(names);
+                }
+            }
+            Ty::Tuple(ts) => {
+                for t in ts {
+                    t.names(names);
+                }
+            }
+            Ty::Named(n) => names.push(n),
+        }
+    }
+
+    fn witx(&self) -> String {
+        match self {
+            Ty::Builtin(b) => b.to_string(),
+            Ty::List(t) => format!("(list {})", t.witx()),
+            Ty::Option(t) => format!("(variant (case $none) (case $some {}))", t.witx()),
+            Ty::Result(ok, err) => {
+                let mut out = "(expected".to_string();
+                if let Some(ok) = ok {
+                    write!(out, " {}", ok.witx()).unwrap();
+                }
+                match err {
+                    Some(err) => write!(out, " (error {}))", err.witx()).unwrap(),
+                    None => out.push_str(" (error))"),
+                }
+                out
+            }
+            Ty::Tuple(ts) => {
+                let ts = ts.iter().map(|t| t.witx()).collect::<Vec<_>>();
+                format!("(tuple {})", ts.join(" "))
+            }
+            Ty::Named(n) => format!("${}", n),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Def {
+    Alias(Ty),
+    Record(Vec<(String, String, Ty)>),
+    Enum(Vec<(String, String)>),
+    Flags(Vec<(String, String)>),
+    Variant(Vec<(String, String, Option<Ty>)>),
+    Resource,
+}
+
+#[derive(Debug, Clone)]
+struct TypeDef {
+    name: String,
+    docs: String,
+    def: Def,
+}
+
+impl TypeDef {
+    fn names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        match &self.def {
+            Def::Alias(t) => t.names(&mut names),
+            Def::Record(fields) => {
+                for (_, _, t) in fields {
+                    t.names(&mut names);
+                }
+            }
+            Def::Variant(cases) => {
+                for t in cases.iter().filter_map(|(_, _, t)| t.as_ref()) {
+                    t.names(&mut names);
+                }
+            }
+            Def::Enum(_) | Def::Flags(_) | Def::Resource => {}
+        }
+        names
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Func {
+    name: String,
+    docs: String,
+    params: Vec<(String, Ty)>,
+    results: Vec<(String, Ty)>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Package {
+    types: Vec<TypeDef>,
+    /// The interfaces, with their docs and functions.
+    interfaces: Vec<(String, String, Vec<Func>)>,
+}
+
+impl Package {
+    /// The definition of the type `name`, after any aliases, unless it's
+    /// undefined.
+    fn resolve(&self, name: &str) -> Option<&Def> {
+        let t = self.types.iter().find(|t| t.name == name)?;
+        match &t.def {
+            Def::Alias(Ty::Named(next)) => self.resolve(next),
+            def => Some(def),
+        }
+    }
+
+    /// Whether witx can return `ty`: a scalar, handle or flags, or an
+    /// `expected` of named types.
+    fn returnable(&self, ty: &Ty) -> bool {
+        match ty {
+            Ty::Builtin(b) => *b != "bool" && *b != "string",
+            Ty::Result(ok, err) => {
+                ok.as_deref().is_none_or(|ok| match self.tuple(ok) {
+                    Some(members) => members.iter().all(|m| matches!(m, Ty::Named(_))),
+                    None => matches!(ok, Ty::Named(_)),
+                }) && err.as_deref().is_none_or(|err| matches!(err, Ty::Named(_)))
+            }
+            Ty::Named(name) => match self.resolve(name) {
+                None | Some(Def::Resource) | Some(Def::Flags(_)) => true,
+                Some(Def::Alias(ty)) => self.returnable(ty),
+                Some(_) => false,
+            },
+            Ty::List(_) | Ty::Option(_) | Ty::Tuple(_) => false,
+        }
+    }
+
+    /// The members of `ty` if it's a tuple, after any aliases.
+    fn tuple<'a>(&'a self, ty: &'a Ty) -> Option<&'a [Ty]> {
+        match ty {
+            Ty::Tuple(members) => Some(members),
+            Ty::Named(name) => match self.resolve(name) {
+                Some(Def::Alias(Ty::Tuple(members))) => Some(members),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Make the results of functions ones witx can return: the payloads of
+    /// `result`s, and the members of tuples they return, are defined as
+    /// types, and other results are the payload of an `expected`.
+    fn lower_results(&mut self, notes: &mut Vec<Note>) {
+        let mut hoisted = Vec::new();
+        let mut interfaces = std::mem::take(&mut self.interfaces);
+        for (interface, _, funcs) in interfaces.iter_mut() {
+            for f in funcs.iter_mut() {
+                if f.results.len() != 1 || self.returnable(&f.results[0].1) {
+                    continue;
+                }
+                let (mut name, ty) = f.results.pop().unwrap();
+                let func = f.name.as_str();
+                let mut hoist = |ty: Ty, suffix: &str| match ty {
+                    Ty::Named(_) => ty,
+                    ty => {
+                        let name = format!("{}_{}", func, suffix);
+                        hoisted.push(TypeDef {
+                            name: name.clone(),
+                            docs: String::new(),
+                            def: Def::Alias(ty),
+                        });
+                        Ty::Named(name)
+                    }
+                };
+                let (ok, err, suffix) = match ty {
+                    Ty::Result(ok, err) => (ok.map(|ok| *ok), err.map(|err| *err), "ok"),
+                    ty => {
+                        notes.push(Note {
+                            item: format!("func {}::{}", interface, func),
+                            message: format!(
+                                "witx can't return {}, so it returns an `expected` of it",
+                                ty.witx()
+                            ),
+                        });
+                        name = "error".to_string();
+                        (Some(ty), None, "result")
+                    }
+                };
+                let ok = ok.map(|ok| match self.tuple(&ok) {
+                    Some(members) => Ty::Tuple(
+                        members
+                            .iter()
+                            .enumerate()
+                            .map(|(i, m)| hoist(m.clone(), &format!("{}_{}", suffix, i)))
+                            .collect(),
+                    ),
+                    None => hoist(ok, suffix),
+                });
+                let err = err.map(|err| hoist(err, "err"));
+                f.results
+                    .push((name, Ty::Result(ok.map(Box::new), err.map(Box::new))));
+            }
+        }
+        self.interfaces = interfaces;
+        self.types.extend(hoisted);
+    }
+
+    fn to_witx(&self, notes: &mut Vec<Note>) -> String {
+        let defined = self
+            .types
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.name.as_str(), i))
+            .collect::<HashMap<_, _>>();
+        let mut out = String::new();
+        let mut done = HashSet::new();
+        let mut undefined = Vec::new();
+        let mut used = Vec::new();
+        for t in self.types.iter() {
+            used.extend(t.names());
+        }
+        for (_, _, funcs) in self.interfaces.iter() {
+            for f in funcs {
+                for (_, t) in f.params.iter().chain(f.results.iter()) {
+                    t.names(&mut used);
+                }
+            }
+        }
+        for name in used {
+            if !defined.contains_key(name) && !undefined.contains(&name) {
+                undefined.push(name);
+                notes.push(Note {
+                    item: format!("type {}", name),
+                    message: "not defined in the package, so it's a handle".to_string(),
+                });
+                writeln!(out, "(typename ${} (handle))", name).unwrap();
+            }
+        }
+        for i in 0..self.types.len() {
+            self.emit(i, &defined, &mut done, &mut out);
+        }
+        for (name, doc, funcs) in self.interfaces.iter() {
+            if funcs.is_empty() {
+                continue;
+            }
+            out.push('\n');
+            out.push_str(&docs(doc, "", ";;;"));
+            writeln!(out, "(module ${}", name).unwrap();
+            out.push_str("  (import \"memory\" (memory))\n");
+            for f in funcs {
+                out.push_str(&docs(&f.docs, "  ", ";;;"));
+                write!(out, "  (@interface func (export \"{}\")", f.name).unwrap();
+                for (name, ty) in f.params.iter() {
+                    write!(out, "\n    (param ${} {})", name, ty.witx()).unwrap();
+                }
+                for (name, ty) in f.results.iter() {
+                    write!(out, "\n    (result ${} {})", name, ty.witx()).unwrap();
+                }
+                out.push_str(")\n");
+            }
+            out.push_str(")\n");
+        }
+        out
+    }
+
+    /// Write the type `i`, after the types it uses.
+    fn emit(
+        &self,
+        i: usize,
+        defined: &HashMap<&str, usize>,
+        done: &mut HashSet<usize>,
+        out: &mut String,
+    ) {
+        if !done.insert(i) {
+            return;
+        }
+        let t = &self.types[i];
+        for name in t.names() {
+            if let Some(dep) = defined.get(name) {
+                self.emit(*dep, defined, done, out);
+            }
+        }
+        out.push_str(&docs(&t.docs, "", ";;;"));
+        write!(out, "(typename ${}", t.name).unwrap();
+        match &t.def {
+            Def::Alias(ty) => write!(out, " {}", ty.witx()).unwrap(),
+            Def::Record(fields) => {
+                out.push_str("\n  (record");
+                for (doc, name, ty) in fields {
+                    out.push('\n');
+                    out.push_str(&docs(doc, "    ", ";;;"));
+                    write!(out, "    (field ${} {})", name, ty.witx()).unwrap();
+                }
+                out.push(')');
+            }
+            Def::Enum(cases) | Def::Flags(cases) => {
+                let (kind, annotation, repr) = match &t.def {
+                    Def::Enum(_) => ("enum", "tag", canonical_tag(cases.len())),
+                    _ => ("flags", "repr", canonical_flags(cases.len())),
+                };
+                write!(
+                    out,
+                    "\n  ({} (@witx {} {})",
+                    kind,
+                    annotation,
+                    repr_name(repr)
+                )
+                .unwrap();
+                for (doc, name) in cases {
+                    out.push('\n');
+                    out.push_str(&docs(doc, "    ", ";;;"));
+                    write!(out, "    ${}", name).unwrap();
+                }
+                out.push(')');
+            }
+            Def::Variant(cases) => {
+                write!(
+                    out,
+                    "\n  (variant (@witx tag {})",
+                    repr_name(canonical_tag(cases.len()))
+                )
+                .unwrap();
+                for (doc, name, ty) in cases {
+                    out.push('\n');
+                    out.push_str(&docs(doc, "    ", ";;;"));
+                    match ty {
+                        Some(ty) => write!(out, "    (case ${} {})", name, ty.witx()).unwrap(),
+                        None => write!(out, "    (case ${})", name).unwrap(),
+                    }
+                }
+                out.push(')');
+            }
+            Def::Resource => out.push_str(" (handle)"),
+        }
+        out.push_str(")\n");
+    }
+}
+
+struct WitParser<'a> {
+    tokens: &'a [(Token, usize)],
+    pos: usize,
+    package: Package,
+    notes: Vec<Note>,
+}
+
+/// The witx name of a WIT name.
+fn witx_name(name: &str) -> String {
+    name.to_snake_case()
+}
+
+impl<'a> WitParser<'a> {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn syntax<T>(&self, message: impl Into<String>) -> Result<T, WitError> {
+        Err(WitError::Syntax {
+            line: self.line(),
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    /// Whether the next token is the keyword `kw`.
+    fn peek_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id(name, false)) if name == kw)
+    }
+
+    fn eat_keyword(&mut self, kw: &str) -> bool {
+        let found = self.peek_keyword(kw);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(c));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), WitError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.syntax(format!("expected `{}`", c))
+        }
+    }
+
+    fn keyword(&mut self, kw: &str) -> Result<(), WitError> {
+        if self.eat_keyword(kw) {
+            Ok(())
+        } else {
+            self.syntax(format!("expected `{}`", kw))
+        }
+    }
+
+    /// A name, in witx form.
+    fn name(&mut self) -> Result<String, WitError> {
+        match self.peek() {
+            Some(Token::Id(name, _)) => {
+                self.pos += 1;
+                Ok(witx_name(name))
+            }
+            _ => self.syntax("expected a name"),
+        }
+    }
+
+    fn docs(&mut self) -> String {
+        let mut docs = Vec::new();
+        while let Some(Token::Doc(doc)) = self.peek() {
+            docs.push(doc.as_str());
+            self.pos += 1;
+        }
+        docs.join("\n")
+    }
+
+    /// Skip to after the next `;` or balanced `{ ... }`.
+    fn skip_item(&mut self) -> Result<(), WitError> {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            self.pos += 1;
+            match token {
+                Token::Punct('{') => depth += 1,
+                Token::Punct('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                Token::Punct(';') if depth == 0 => return Ok(()),
+                _ => {}
+            }
+        }
+        self.syntax("unexpected end of file")
+    }
+
+    fn file(&mut self) -> Result<(), WitError> {
+        loop {
+            let docs = self.docs();
+            if self.peek().is_none() {
+                return Ok(());
+            }
+            if self.eat_keyword("package") {
+                self.skip_item()?;
+            } else if self.eat_keyword("interface") {
+                self.interface(docs)?;
+            } else if self.peek_keyword("world") {
+                self.pos += 1;
+                let name = self.name()?;
+                self.notes.push(Note {
+                    item: format!("world {}", name),
+                    message: "skipped, since only interfaces are modules".to_string(),
+                });
+                self.skip_item()?;
+            } else if self.peek_keyword("use") {
+                self.skip_item()?;
+            } else {
+                return self.syntax("expected `interface` or `world`");
+            }
+        }
+    }
+
+    fn interface(&mut self, docs: String) -> Result<(), WitError> {
+        let name = self.name()?;
+        self.expect('{')?;
+        let mut funcs = Vec::new();
+        loop {
+            let docs = self.docs();
+            if self.eat('}') {
+                break;
+            }
+            let line = self.line();
+            if self.eat_keyword("use") {
+                self.use_()?;
+            } else if self.eat_keyword("type") {
+                let name = self.name()?;
+                self.expect('=')?;
+                let ty = self.ty()?;
+                self.expect(';')?;
+                self.define(line, name, docs, Def::Alias(ty))?;
+            } else if self.eat_keyword("record") {
+                let name = self.name()?;
+                let fields = self.list(|p, docs| {
+                    let name = p.name()?;
+                    p.expect(':')?;
+                    Ok((docs, name, p.ty()?))
+                })?;
+                self.define(line, name, docs, Def::Record(fields))?;
+            } else if self.eat_keyword("enum") {
+                let name = self.name()?;
+                let cases = self.list(|p, docs| Ok((docs, p.name()?)))?;
+                self.define(line, name, docs, Def::Enum(cases))?;
+            } else if self.eat_keyword("flags") {
+                let name = self.name()?;
+                let flags = self.list(|p, docs| Ok((docs, p.name()?)))?;
+                self.define(line, name, docs, Def::Flags(flags))?;
+            } else if self.eat_keyword("variant") {
+                let name = self.name()?;
+                let cases = self.list(|p, docs| {
+                    let name = p.name()?;
+                    let ty = if p.eat('(') {
+                        let ty = p.ty()?;
+                        p.expect(')')?;
+                        Some(ty)
+                    } else {
+                        None
+                    };
+                    Ok((docs, name, ty))
+                })?;
+                self.define(line, name, docs, Def::Variant(cases))?;
+            } else if self.eat_keyword("resource") {
+                let name = self.name()?;
+                self.define(line, name.clone(), docs, Def::Resource)?;
+                if !self.eat(';') {
+                    self.resource(&name, &mut funcs)?;
+                }
+            } else {
+                let name = self.name()?;
+                self.expect(':')?;
+                funcs.push(self.func(name, docs, None)?);
+            }
+        }
+        self.package.interfaces.push((name, docs, funcs));
+        Ok(())
+    }
+
+    fn define(
+        &mut self,
+        line: usize,
+        name: String,
+        docs: String,
+        def: Def,
+    ) -> Result<(), WitError> {
+        if self.package.types.iter().any(|t| t.name == name) {
+            return Err(WitError::Unsupported {
+                line,
+                message: format!(
+                    "`{}` is defined more than once, and witx has one namespace of types",
+                    name
+                ),
+            });
+        }
+        self.package.types.push(TypeDef { name, docs, def });
+        Ok(())
+    }
+
+    /// The items of a `{ ... }` list, separated by commas.
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self, String) -> Result<T, WitError>,
+    ) -> Result<Vec<T>, WitError> {
+        self.expect('{')?;
+        let mut items = Vec::new();
+        loop {
+            let docs = self.docs();
+            if self.eat('}') {
+                return Ok(items);
+            }
+            items.push(item(self, docs)?);
+            if !self.eat(',') {
+                self.docs();
+                self.expect('}')?;
+                return Ok(items);
+            }
+        }
+    }
+
+    /// A `use path.{a, b as c};`, after `use`. Names used as themselves
+    /// are the types of the same name, and renamed ones are aliases.
+    fn use_(&mut self) -> Result<(), WitError> {
+        let line = self.line();
+        while !self.eat('.') {
+            if self.peek().is_none() {
+                return self.syntax("expected `.`");
+            }
+            self.pos += 1;
+        }
+        for (name, alias) in self.list(|p, _| {
+            let name = p.name()?;
+            let alias = if p.eat_keyword("as") {
+                Some(p.name()?)
+            } else {
+                None
+            };
+            Ok((name, alias))
+        })? {
+            if let Some(alias) = alias {
+                self.define(line, alias, String::new(), Def::Alias(Ty::Named(name)))?;
+            }
+        }
+        self.expect(';')
+    }
+
+    /// The functions of a resource's `{ ... }`, as functions of the
+    /// interface prefixed with its name.
+    fn resource(&mut self, resource: &str, funcs: &mut Vec<Func>) -> Result<(), WitError> {
+        self.expect('{')?;
+        loop {
+            let docs = self.docs();
+            if self.eat('}') {
+                return Ok(());
+            }
+            if self.eat_keyword("constructor") {
+                let params = self.params()?;
+                self.expect(';')?;
+                funcs.push(Func {
+                    name: format!("{}_new", resource),
+                    docs,
+                    params,
+                    results: vec![("result".to_string(), Ty::Named(resource.to_string()))],
+                });
+                continue;
+            }
+            let name = format!("{}_{}", resource, self.name()?);
+            self.expect(':')?;
+            let receiver = if self.eat_keyword("static") {
+                None
+            } else {
+                Some(resource)
+            };
+            funcs.push(self.func(name, docs, receiver)?);
+        }
+    }
+
+    /// A `func(...) -> ...;`, taking `receiver` as `$self` first if it's a
+    /// method.
+    fn func(
+        &mut self,
+        name: String,
+        docs: String,
+        receiver: Option<&str>,
+    ) -> Result<Func, WitError> {
+        self.keyword("func")?;
+        let mut params = self.params()?;
+        if let Some(receiver) = receiver {
+            params.insert(0, ("self".to_string(), Ty::Named(receiver.to_string())));
+        }
+        let results = if self.peek() == Some(&Token::Arrow) {
+            self.pos += 1;
+            if self.peek() == Some(&Token::Punct('(')) {
+                self.params()?
+            } else {
+                let ty = self.ty()?;
+                let name = if matches!(ty, Ty::Result(..)) {
+                    "error"
+                } else {
+                    "result"
+                };
+                vec![(name.to_string(), ty)]
+            }
+        } else {
+            Vec::new()
+        };
+        self.expect(';')?;
+        Ok(Func {
+            name,
+            docs,
+            params,
+            results,
+        })
+    }
+
+    fn params(&mut self) -> Result<Vec<(String, Ty)>, WitError> {
+        self.expect('(')?;
+        let mut params = Vec::new();
+        while !self.eat(')') {
+            let name = self.name()?;
+            self.expect(':')?;
+            params.push((name, self.ty()?));
+            if !self.eat(',') {
+                self.expect(')')?;
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    fn ty(&mut self) -> Result<Ty, WitError> {
+        let (name, escaped) = match self.peek() {
+            Some(Token::Id(name, escaped)) => (name.as_str(), *escaped),
+            _ => return self.syntax("expected a type"),
+        };
+        self.pos += 1;
+        if escaped {
+            return Ok(Ty::Named(witx_name(name)));
+        }
+        let builtin = match name {
+            "u8" => Some("u8"),
+            "u16" => Some("u16"),
+            "u32" => Some("u32"),
+            "u64" => Some("u64"),
+            "s8" => Some("s8"),
+            "s16" => Some("s16"),
+            "s32" => Some("s32"),
+            "s64" => Some("s64"),
+            "f32" | "float32" => Some("f32"),
+            "f64" | "float64" => Some("f64"),
+            "char" => Some("char"),
+            "bool" => Some("bool"),
+            "string" => Some("string"),
+            _ => None,
+        };
+        if let Some(builtin) = builtin {
+            return Ok(Ty::Builtin(builtin));
+        }
+        match name {
+            "list" | "option" => {
+                self.expect('<')?;
+                let elem = Box::new(self.ty()?);
+                self.expect('>')?;
+                Ok(if name == "list" {
+                    Ty::List(elem)
+                } else {
+                    Ty::Option(elem)
+                })
+            }
+            "borrow" | "own" => {
+                self.expect('<')?;
+                let ty = self.ty()?;
+                self.expect('>')?;
+                Ok(ty)
+            }
+            "result" => {
+                if !self.eat('<') {
+                    return Ok(Ty::Result(None, None));
+                }
+                let ok = if self.eat_keyword("_") {
+                    None
+                } else {
+                    Some(Box::new(self.ty()?))
+                };
+                let err = if self.eat(',') {
+                    Some(Box::new(self.ty()?))
+                } else {
+                    None
+                };
+                self.expect('>')?;
+                Ok(Ty::Result(ok, err))
+            }
+            "tuple" => {
+                self.expect('<')?;
+                let mut members = Vec::new();
+                while !self.eat('>') {
+                    members.push(self.ty()?);
+                    if !self.eat(',') {
+                        self.expect('>')?;
+                        break;
+                    }
+                }
+                Ok(Ty::Tuple(members))
+            }
+            "future" | "stream" => Err(WitError::Unsupported {
+                line: self.line(),
+                message: format!("witx has no `{}` types", name),
+            }),
+            _ => Ok(Ty::Named(witx_name(name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn roundtrip() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../phases/ephemeral/witx/wasi_ephemeral_nn.witx");
+        let doc = crate::load(&[path]).unwrap();
+        let wit = to_wit(&doc, "wasi:nn");
+        assert!(wit.source.starts_with("package wasi:nn;\n"));
+        assert!(wit.source.contains("    resource graph;\n"));
+        assert!(wit.source.contains("        %type: tensor-type,\n"));
+        assert!(wit
+            .source
+            .contains("interface wasi-ephemeral-nn {\n    use types.{"));
+        assert!(wit
+            .notes
+            .iter()
+            .any(|n| n.to_string() == "type nn_errno: the u16 tag is lost"));
+
+        let roundtrip = from_wit(&wit.source).unwrap().document().unwrap();
+        let names = |doc: &Document| {
+            doc.typenames()
+                .map(|t| t.name.clone())
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(names(&roundtrip), names(&doc));
+        let module = Id::new("wasi_ephemeral_nn");
+        let funcs = |doc: &Document| {
+            doc.module(&module)
+                .unwrap()
+                .funcs()
+                .map(|f| {
+                    let params = f.params.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+                    (f.name.clone(), params, f.results.len())
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(funcs(&roundtrip), funcs(&doc));
+        let tensor_type = Id::new("tensor_type");
+        assert_eq!(
+            roundtrip.typename(&tensor_type).unwrap().shape(),
+            doc.typename(&tensor_type).unwrap().shape()
+        );
+        assert!(wit
+            .notes
+            .iter()
+            .any(|n| n.to_string() == "type tensor: the size of `data` is lost"));
+    }
+
+    #[test]
+    fn world() {
+        let doc = crate::parse(
+            "(typename $graph (handle))
+             (typename $unused u8)
+             (typename $errno (enum (@witx tag u8) $ok $busy))
+             (module $loader
+                (@interface func (export \"load\") (result $error (expected $graph (error $errno)))))
+             (module $runner
+                (@interface func (export \"run\") (param $graph $graph)))
+             (module $other)",
+        )
+        .unwrap();
+        let world = World {
+            name: "nn_host".to_string(),
+            imports: vec![Id::new("loader")],
+            exports: vec![Id::new("runner")],
+        };
+        let wit = to_wit_world(&doc, "wasi:nn", &world).unwrap();
+        assert!(wit.source.contains("    resource graph;\n"));
+        assert!(wit.source.contains("    enum errno {\n"));
+        assert!(!wit.source.contains("unused"));
+        assert!(!wit.source.contains("interface other"));
+        assert!(wit
+            .source
+            .contains("interface runner {\n    use types.{graph};\n"));
+        assert!(wit
+            .source
+            .ends_with("\nworld nn-host {\n    import loader;\n    export runner;\n}\n"));
+
+        let imported = from_wit(&wit.source).unwrap();
+        assert_eq!(imported.notes[0].item, "world nn_host");
+        let imported = imported.document().unwrap();
+        assert!(imported.module(&Id::new("loader")).is_some());
+        assert!(imported.typename(&Id::new("unused")).is_none());
+
+        let missing = World {
+            imports: vec![Id::new("loader"), Id::new("trainer")],
+            ..world.clone()
+        };
+        assert_eq!(
+            to_wit_world(&doc, "wasi:nn", &missing),
+            Err(WorldError::ModuleNotPresent(Id::new("trainer")))
+        );
+        let both = World {
+            exports: vec![Id::new("loader")],
+            ..world
+        };
+        assert_eq!(
+            to_wit_world(&doc, "wasi:nn", &both),
+            Err(WorldError::ImportedAndExported(Id::new("loader")))
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let source = "package a:b;\ninterface i {\n    type t = list<list<u8>>;\n}";
+        assert!(from_wit_with(source, &Limits::untrusted()).is_ok());
+        match from_wit_with(source, &Limits::new().nesting(2)) {
+            Err(WitError::Limit(LimitError::Nesting { limit, location })) => {
+                assert_eq!((limit, location.line), (2, 3));
+            }
+            other => panic!("{:?}", other.map(|i| i.source)),
+        }
+        assert!(matches!(
+            from_wit_with(source, &Limits::new().ident_len(8)),
+            Err(WitError::Limit(LimitError::IdentLength { len: 9, .. }))
+        ));
+        assert!(matches!(
+            from_wit_with(source, &Limits::new().file_size(8)),
+            Err(WitError::Limit(LimitError::FileSize { limit: 8, .. }))
+        ));
+        let deep = format!(
+            "package a:b; interface i {{ type t = {}u8; }}",
+            "list<".repeat(200)
+        );
+        assert!(matches!(from_wit(&deep), Err(WitError::Syntax { .. })));
+    }
+
+    #[test]
+    fn wit() {
+        let import = from_wit(
+            "package example:nn@0.1.0;
+
+             /// Inference.
+             interface inference {
+                 use types.{tensor, errno as error};
+
+                 /// An execution context.
+                 resource context {
+                     constructor(graph: u32);
+                     compute: func() -> result<_, error>;
+                     output: func(index: u32) -> result<tensor, error>;
+                     count: static func() -> u32;
+                 }
+
+                 run: func(inputs: list<option<tensor>>) -> tuple<bool, %string>;
+             }
+
+             interface types {
+                 record tensor { dims: list<u32>, data: list<u8> }
+                 /* Errors. */
+                 enum errno { busy, invalid-argument }
+                 flags access { read, write }
+                 variant value { none, num(f64), text(string), }
+                 type %string = string;
+                 type maybe = missing;
+             }
+
+             world runner { import inference; }",
+        )
+        .unwrap();
+        let doc = import.document().unwrap();
+        assert_eq!(
+            import
+                .notes
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "world runner: skipped, since only interfaces are modules",
+                "func inference::run: witx can't return (tuple bool $string), \
+                 so it returns an `expected` of it",
+                "type missing: not defined in the package, so it's a handle",
+            ]
+        );
+        let module = doc.module(&Id::new("inference")).unwrap();
+        assert_eq!(module.docs, "Inference.\n");
+        assert_eq!(
+            module
+                .funcs()
+                .map(|f| f.name.as_str().to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "context_new",
+                "context_compute",
+                "context_output",
+                "context_count",
+                "run"
+            ]
+        );
+        let compute = module.func(&Id::new("context_compute")).unwrap();
+        assert_eq!(compute.params[0].name, Id::new("self"));
+        assert_eq!(compute.results[0].name, Id::new("error"));
+        assert!(import
+            .source
+            .contains("(param $inputs (list (variant (case $none) (case $some $tensor))))"));
+        assert!(import.source.contains("(enum (@witx tag u8)"));
+        assert!(import
+            .source
+            .contains("(result $error (expected (tuple $run_result_0 $string) (error)))"));
+
+        assert_eq!(
+            from_wit("interface a {\n  f: func(x: stream<u8>);\n}"),
+            Err(WitError::Unsupported {
+                line: 2,
+                message: "witx has no `stream` types".to_string()
+            })
+        );
+        assert_eq!(
+            from_wit("interface a { type x = u8; }\ninterface b { type x = u8; }"),
+            Err(WitError::Unsupported {
+                line: 2,
+                message: "`x` is defined more than once, and witx has one namespace of types"
+                    .to_string()
+            })
+        );
+        assert!(from_wit("interface a { f: func(x: u8) }").is_err());
+        assert_eq!(
+            from_wit("/* a"),
+            Err(WitError::UnterminatedComment { line: 1 })
+        );
+    }
+}
@@ -0,0 +1,467 @@
+//! A set of named documents, such as the phases of a proposal or branches of
+//! it under review, for queries and comparisons across them.
+
+use crate::diff::DocumentDiff;
+use crate::graph::{self, TypeCycle};
+use crate::polyfill::{infer_module_mapping, ModulePolyfill, Polyfill, PolyfillError};
+use crate::{Document, Id, InterfaceFunc, Module, NamedType, RepContext, WitxError};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("No document named {name:?} in the workspace")]
+    NotPresent { name: String },
+    #[error("Failed to load document {name:?}")]
+    Load {
+        name: String,
+        #[source]
+        error: Box<WitxError>,
+    },
+    #[error("No module `{}` in document {name:?}", module.as_str())]
+    ModuleNotPresent { name: String, module: Id },
+    #[error("No implemented version can serve module `{}` of {version:?}", module.as_str())]
+    NoCompatibleVersion { module: Id, version: String },
+    #[error("Failed to calculate polyfill")]
+    Polyfill(#[from] PolyfillError),
+}
+
+/// The version of a spec chosen to serve a module a guest imports.
+#[derive(Debug, Clone)]
+pub struct Negotiated<'a> {
+    pub version: &'a str,
+    /// The module serving the import in that version.
+    pub module: Rc<Module>,
+    /// The polyfill from the imported module to the serving one, unless the
+    /// guest's own version is implemented.
+    pub polyfill: Option<ModulePolyfill>,
+}
+
+/// A module of one of a workspace's documents, with the named types to
+/// emit along with it.
+#[derive(Debug, Clone)]
+pub struct ModuleTypes<'a> {
+    pub document: &'a str,
+    pub module: Rc<Module>,
+    /// The types the module uses, directly or through other types, which no
+    /// module before it does, each after the types it's built from.
+    pub types: Vec<Rc<NamedType>>,
+}
+
+/// Documents by name, in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    documents: Vec<(String, Document)>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `doc` as `name`, returning the document it replaces, if any.
+    pub fn insert(&mut self, name: &str, doc: Document) -> Option<Document> {
+        match self.documents.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => Some(std::mem::replace(existing, doc)),
+            None => {
+                self.documents.push((name.to_string(), doc));
+                None
+            }
+        }
+    }
+
+    /// Load the document rooted at `paths` as `name`. A single directory is
+    /// loaded from every witx file in it.
+    pub fn load<P: AsRef<Path>>(&mut self, name: &str, paths: &[P]) -> Result<(), WorkspaceError> {
+        let load = || match paths {
+            [dir] if dir.as_ref().is_dir() => crate::load(&witx_files(dir.as_ref())?),
+            _ => crate::load(paths),
+        };
+        let doc = load().map_err(|error| WorkspaceError::Load {
+            name: name.to_string(),
+            error: Box::new(error),
+        })?;
+        self.insert(name, doc);
+        Ok(())
+    }
+
+    pub fn document(&self, name: &str) -> Option<&Document> {
+        self.documents
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, d)| d)
+    }
+
+    /// The document named `name`, or an error saying it isn't present.
+    pub fn get(&self, name: &str) -> Result<&Document, WorkspaceError> {
+        self.document(name)
+            .ok_or_else(|| WorkspaceError::NotPresent {
+                name: name.to_string(),
+            })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.documents.iter().map(|(n, _)| n.as_str())
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = (&str, &Document)> {
+        self.documents.iter().map(|(n, d)| (n.as_str(), d))
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// The type named `name` in each document defining it.
+    pub fn typename(&self, name: &Id) -> Vec<(&str, Rc<NamedType>)> {
+        self.documents()
+            .filter_map(|(n, d)| Some((n, d.typename(name)?)))
+            .collect()
+    }
+
+    /// The module named `name` in each document defining it.
+    pub fn module(&self, name: &Id) -> Vec<(&str, Rc<Module>)> {
+        self.documents()
+            .filter_map(|(n, d)| Some((n, d.module(name)?)))
+            .collect()
+    }
+
+    /// The function `name` of the module `module` in each document
+    /// defining it.
+    pub fn func(&self, module: &Id, name: &Id) -> Vec<(&str, Rc<InterfaceFunc>)> {
+        self.documents()
+            .filter_map(|(n, d)| Some((n, d.module(module)?.func(name)?)))
+            .collect()
+    }
+
+    /// The modules of every document, in order, with the types they use
+    /// shared between them, so that code generated for all of them defines
+    /// each type once, before the first module using it.
+    ///
+    /// Types are shared by name, as when documents `use` the same file, and
+    /// emitted as the first document defining them does. A type built from
+    /// different types in different documents can make the types depend on
+    /// each other in a cycle, which leaves no order to emit them in.
+    pub fn modules_topo_order(&self) -> Result<Vec<ModuleTypes<'_>>, TypeCycle> {
+        let mut definitions: HashMap<Id, Vec<Rc<NamedType>>> = HashMap::new();
+        for (_, doc) in self.documents() {
+            for t in doc.typenames() {
+                definitions.entry(t.name.clone()).or_default().push(t);
+            }
+        }
+        let deps = |t: &NamedType| {
+            let mut deps: Vec<Rc<NamedType>> = Vec::new();
+            for def in definitions[&t.name].iter() {
+                for dep in graph::type_names(def) {
+                    let dep = &definitions[&dep.name][0];
+                    if !deps.iter().any(|d| d.name == dep.name) {
+                        deps.push(dep.clone());
+                    }
+                }
+            }
+            deps
+        };
+        let mut done = HashMap::new();
+        let mut modules = Vec::new();
+        for (name, doc) in self.documents() {
+            for module in doc.modules() {
+                let mut types = Vec::new();
+                for t in graph::module_types(&module) {
+                    let first = &definitions[&t.name][0];
+                    graph::visit(first, &deps, &mut done, &mut Vec::new(), &mut types)?;
+                }
+                modules.push(ModuleTypes {
+                    document: name,
+                    module,
+                    types,
+                });
+            }
+        }
+        Ok(modules)
+    }
+
+    /// The best of the `implemented` versions for a host to serve the module
+    /// `module` of the version `version` a guest imports with. The guest's
+    /// own version is served directly. Otherwise each implemented version
+    /// with every function of the module, in the module of the same name or
+    /// else the one `infer_module_mapping` pairs it with, is a candidate, and
+    /// the one needing the fewest functions polyfilled is chosen, the first
+    /// in the workspace on ties.
+    pub fn negotiate(
+        &self,
+        implemented: &[&str],
+        module: &Id,
+        version: &str,
+        cx: &RepContext,
+    ) -> Result<Negotiated<'_>, WorkspaceError> {
+        let guest = self.get(version)?;
+        let imported = guest
+            .module(module)
+            .ok_or_else(|| WorkspaceError::ModuleNotPresent {
+                name: version.to_string(),
+                module: module.clone(),
+            })?;
+        for name in implemented {
+            self.get(name)?;
+        }
+        let mut best: Option<(usize, Negotiated)> = None;
+        for (name, doc) in self.documents() {
+            if !implemented.contains(&name) {
+                continue;
+            }
+            if name == version {
+                return Ok(Negotiated {
+                    version: name,
+                    module: imported,
+                    polyfill: None,
+                });
+            }
+            let served = doc.module(module).or_else(|| {
+                let pair = infer_module_mapping(doc, guest)
+                    .into_iter()
+                    .find(|p| p.old == *module)?;
+                doc.module(&pair.new)
+            });
+            let polyfill = match served {
+                Some(served) => match ModulePolyfill::new(served.clone(), imported.clone(), cx) {
+                    Ok(polyfill) => (served, polyfill),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+            let cost = polyfill.1.funcs.iter().filter(|f| !f.full_compat()).count();
+            if best.as_ref().is_none_or(|(best, _)| cost < *best) {
+                let negotiated = Negotiated {
+                    version: name,
+                    module: polyfill.0,
+                    polyfill: Some(polyfill.1),
+                };
+                best = Some((cost, negotiated));
+            }
+        }
+        best.map(|(_, negotiated)| negotiated)
+            .ok_or_else(|| WorkspaceError::NoCompatibleVersion {
+                module: module.clone(),
+                version: version.to_string(),
+            })
+    }
+
+    /// The differences from the document `old` to the document `new`, with
+    /// modules matched as by `DocumentDiff::with_context`.
+    pub fn diff(
+        &self,
+        old: &str,
+        new: &str,
+        module_mapping: &HashMap<String, String>,
+        cx: &RepContext,
+    ) -> Result<DocumentDiff, WorkspaceError> {
+        let (old, new) = (self.get(old)?, self.get(new)?);
+        Ok(DocumentDiff::with_context(old, new, module_mapping, cx))
+    }
+
+    /// The polyfill of the document `old` in terms of the document `new`.
+    pub fn polyfill(
+        &self,
+        new: &str,
+        old: &str,
+        module_mapping: &HashMap<String, String>,
+        cx: &RepContext,
+    ) -> Result<Polyfill, WorkspaceError> {
+        let (new, old) = (self.get(new)?, self.get(old)?);
+        Ok(Polyfill::with_context(new, old, module_mapping, cx)?)
+    }
+}
+
+/// The witx files in the directory `root`, in name order.
+pub(crate) fn witx_files(root: &Path) -> Result<Vec<PathBuf>, WitxError> {
+    let io = |e| WitxError::Io(root.to_path_buf(), e);
+    let mut files = std::fs::read_dir(root)
+        .map_err(io)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()
+        .map_err(io)?;
+    files.retain(|p| p.extension().map(|e| e == "witx").unwrap_or(false));
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn workspace() -> Workspace {
+        let mut ws = Workspace::new();
+        ws.insert(
+            "old",
+            crate::parse(
+                "(typename $e (enum $a))
+                 (module $m (@interface func (export \"f\") (param $e $e)))",
+            )
+            .unwrap(),
+        );
+        ws.insert(
+            "new",
+            crate::parse(
+                "(typename $e (enum $a $b))
+                 (module $m
+                    (@interface func (export \"f\") (param $e $e))
+                    (@interface func (export \"g\")))",
+            )
+            .unwrap(),
+        );
+        ws
+    }
+
+    #[test]
+    fn queries() {
+        let ws = workspace();
+        assert_eq!(ws.names().collect::<Vec<_>>(), vec!["old", "new"]);
+        fn names<T>(found: Vec<(&str, T)>) -> Vec<&str> {
+            found.into_iter().map(|(n, _)| n).collect()
+        }
+        assert_eq!(names(ws.typename(&Id::new("e"))), vec!["old", "new"]);
+        assert_eq!(names(ws.module(&Id::new("m"))), vec!["old", "new"]);
+        assert_eq!(names(ws.func(&Id::new("m"), &Id::new("g"))), vec!["new"]);
+        assert!(ws.typename(&Id::new("missing")).is_empty());
+    }
+
+    #[test]
+    fn comparisons() {
+        let ws = workspace();
+        let cx = RepContext::new();
+        let diff = ws.diff("old", "new", &HashMap::new(), &cx).unwrap();
+        assert_eq!(diff.types.len(), 1);
+        let mut mapping = HashMap::new();
+        mapping.insert("m".to_string(), "m".to_string());
+        let polyfill = ws.polyfill("new", "old", &mapping, &cx).unwrap();
+        assert_eq!(polyfill.modules[0].added_funcs.len(), 1);
+        assert!(matches!(
+            ws.diff("old", "newer", &HashMap::new(), &cx),
+            Err(WorkspaceError::NotPresent { name }) if name == "newer"
+        ));
+    }
+
+    #[test]
+    fn shared_types() {
+        let mut ws = Workspace::new();
+        let types = "(typename $dims (list u32))
+                     (typename $tensor (record (field $dims $dims)))";
+        ws.insert(
+            "nn",
+            crate::parse(&format!(
+                "{}
+                 (typename $other u8)
+                 (module $load (@interface func (export \"f\") (param $t $tensor)))
+                 (module $run
+                   (@interface func (export \"g\")
+                     (param $o $other) (param $d $dims) (param $t $tensor)))",
+                types
+            ))
+            .unwrap(),
+        );
+        ws.insert(
+            "ext",
+            crate::parse(&format!(
+                "{}
+                 (typename $batch (list $tensor))
+                 (module $ext (@interface func (export \"h\") (param $b $batch)))",
+                types
+            ))
+            .unwrap(),
+        );
+        let modules = ws.modules_topo_order().unwrap();
+        let order = modules
+            .iter()
+            .map(|m| {
+                let types = m.types.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+                (m.document, m.module.name.as_str(), types)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            vec![
+                ("nn", "load", vec!["dims", "tensor"]),
+                ("nn", "run", vec!["other"]),
+                ("ext", "ext", vec!["batch"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_types_cycle() {
+        let mut ws = Workspace::new();
+        ws.insert(
+            "a",
+            crate::parse(
+                "(typename $y u8)
+                 (typename $x (list $y))
+                 (module $a (@interface func (export \"f\") (param $x $x)))",
+            )
+            .unwrap(),
+        );
+        ws.insert(
+            "b",
+            crate::parse("(typename $x u8) (typename $y (list $x))").unwrap(),
+        );
+        let cycle = ws.modules_topo_order().unwrap_err();
+        assert_eq!(cycle.types, vec![Id::new("x"), Id::new("y"), Id::new("x")]);
+    }
+
+    #[test]
+    fn negotiation() {
+        let mut ws = workspace();
+        ws.insert(
+            "renamed",
+            crate::parse(
+                "(typename $e (enum $a $b))
+                 (module $n
+                    (@interface func (export \"f\") (param $e $e) (param $x u32))
+                    (@interface func (export \"g\")))",
+            )
+            .unwrap(),
+        );
+        let cx = RepContext::new();
+        let m = Id::new("m");
+
+        let direct = ws.negotiate(&["new", "old"], &m, "old", &cx).unwrap();
+        assert_eq!(direct.version, "old");
+        assert!(direct.polyfill.is_none());
+
+        let bridged = ws.negotiate(&["renamed", "new"], &m, "old", &cx).unwrap();
+        assert_eq!(bridged.version, "new");
+        let polyfill = bridged.polyfill.unwrap();
+        assert_eq!((&polyfill.old.name, polyfill.funcs.len()), (&m, 1));
+
+        let renamed = ws.negotiate(&["renamed"], &m, "old", &cx).unwrap();
+        assert_eq!(renamed.module.name, Id::new("n"));
+        assert!(!renamed.polyfill.unwrap().funcs[0].full_compat());
+
+        assert!(matches!(
+            ws.negotiate(&["old"], &m, "new", &cx),
+            Err(WorkspaceError::NoCompatibleVersion { version, .. }) if version == "new"
+        ));
+        assert!(matches!(
+            ws.negotiate(&["new"], &Id::new("n"), "old", &cx),
+            Err(WorkspaceError::ModuleNotPresent { name, .. }) if name == "old"
+        ));
+        assert!(matches!(
+            ws.negotiate(&["newer"], &m, "old", &cx),
+            Err(WorkspaceError::NotPresent { name }) if name == "newer"
+        ));
+    }
+
+    #[test]
+    fn insert_replaces() {
+        let mut ws = workspace();
+        let replaced = ws.insert("old", crate::parse("").unwrap());
+        assert!(replaced.unwrap().typename(&Id::new("e")).is_some());
+        assert_eq!(ws.len(), 2);
+        assert!(ws.get("old").unwrap().typename(&Id::new("e")).is_none());
+    }
+}
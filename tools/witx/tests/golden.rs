@@ -0,0 +1,73 @@
+//! Compares the bindings every backend generates for wasi-nn with the golden
+//! files in `tests/golden`:
+//!
+//!     cargo test --test golden
+//!
+//! After an intended change to the generated bindings, regenerate the golden
+//! files, and review the changes to them, with:
+//!
+//!     cargo test --test golden -- --bless
+
+use std::path::{Path, PathBuf};
+use witx::codegen::{self, GoldenDiff};
+
+fn main() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let spec = root.join("../../phases/ephemeral/witx/wasi_ephemeral_nn.witx");
+    let dir = root.join("tests/golden");
+    let doc = witx::load(&[spec]).unwrap_or_else(|e| panic!("failed to load: {:?}", e));
+    let options = codegen::Options::default();
+    if std::env::args().any(|a| a == "--bless") {
+        codegen::bless_golden(&doc, &options, &dir).unwrap();
+        println!("blessed the golden files in {}", dir.display());
+        return;
+    }
+
+    let diffs = codegen::compare_golden(&doc, &options, &dir).unwrap();
+    if diffs.is_empty() {
+        println!("golden files match");
+        return;
+    }
+    for d in diffs.iter() {
+        eprintln!("{}", d);
+        if let GoldenDiff::Changed {
+            expected, actual, ..
+        } = d
+        {
+            print_diff(expected, actual);
+        }
+    }
+    eprintln!();
+    eprintln!(
+        "To regenerate the golden files in {}, run `cargo test --test golden -- --bless`.",
+        Path::display(&dir)
+    );
+    panic!("{} golden files differ", diffs.len());
+}
+
+fn print_diff(expected: &str, actual: &str) {
+    let (mut expected_line, mut actual_line) = (1, 1);
+    let mut separated = false;
+    for diff in diff::lines(expected, actual) {
+        match diff {
+            diff::Result::Left(l) => {
+                eprintln!("line {}: -{}", expected_line, l);
+                expected_line += 1;
+                separated = false;
+            }
+            diff::Result::Both(_, _) => {
+                expected_line += 1;
+                actual_line += 1;
+                if !separated {
+                    eprintln!("...");
+                    separated = true;
+                }
+            }
+            diff::Result::Right(r) => {
+                eprintln!("line {}: +{}", actual_line, r);
+                actual_line += 1;
+                separated = false;
+            }
+        }
+    }
+}
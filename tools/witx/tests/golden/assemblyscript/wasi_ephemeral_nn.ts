@@ -0,0 +1,143 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+/**
+ * The size of a graph buffer. This is equivalent to `$size` in `typenames.witx` but renamed since `typenames.witx` is
+ * not included here but is included in the overall ephemeral phase.
+ */
+export type buffer_size = u32;
+
+/**
+ * Error codes returned by functions in this API. This is prefixed to avoid conflicts with the `$errno` in
+ * `typenames.witx`.
+ */
+export type nn_errno = u16;
+export namespace nn_errno {
+  /**
+   * No error occurred.
+   */
+  export const SUCCESS: nn_errno = 0;
+  /**
+   * Caller module passed an invalid argument.
+   */
+  export const INVALID_ARGUMENT: nn_errno = 1;
+  /**
+   * Caller module is missing a memory export.
+   */
+  export const MISSING_MEMORY: nn_errno = 2;
+  /**
+   * Device or resource busy.
+   */
+  export const BUSY: nn_errno = 3;
+}
+
+
+/**
+ * The type of the elements in a tensor.
+ */
+export type tensor_type = u8;
+export namespace tensor_type {
+  export const F16: tensor_type = 0;
+  export const F32: tensor_type = 1;
+  export const U8: tensor_type = 2;
+  export const I32: tensor_type = 3;
+}
+
+
+/**
+ * A tensor.
+ */
+@unmanaged
+export class tensor {
+  /**
+   * Describe the size of the tensor (e.g. 2x2x2x2 -> [2, 2, 2, 2]). To represent a tensor containing a single value,
+   * use `[1]` for the tensor dimensions.
+   */
+  dimensions: usize;
+  dimensions_len: usize;
+  type: tensor_type;
+  /**
+   * Contains the tensor data.
+   */
+  data: usize;
+  data_len: usize;
+}
+
+
+
+/**
+ * An execution graph for performing inference (i.e. a model).
+ */
+export type graph = u32;
+
+/**
+ * Describes the encoding of the graph. This allows the API to be implemented by various backends that encode (i.e.
+ * serialize) their graph IR differently.
+ */
+export type graph_encoding = u8;
+export namespace graph_encoding {
+  /**
+   * TODO document buffer order
+   */
+  export const OPENVINO: graph_encoding = 0;
+  export const TENSORFLOW: graph_encoding = 1;
+}
+
+/**
+ * Define where the graph should be executed.
+ */
+export type execution_target = u8;
+export namespace execution_target {
+  export const CPU: execution_target = 0;
+  export const GPU: execution_target = 1;
+  export const TPU: execution_target = 2;
+}
+
+/**
+ * A $graph_execution_context allows for attaching inputs prior to calling `compute` on a graph and retrieving outputs after
+ * the computation has completed. TODO a handle may not be the right type but we want it to be opaque to users.
+ */
+export type graph_execution_context = u32;
+
+export namespace wasi_ephemeral_nn {
+  /**
+   * Load an opaque sequence of bytes to use for inference.
+   *
+   * This allows runtime implementations to support multiple graph encoding formats. For unsupported graph encodings,
+   * return `errno::inval`.
+   */
+  @external("wasi_ephemeral_nn", "load")
+  export declare function load(builder: usize, builder_len: usize, encoding: graph_encoding, target: execution_target, retptr0: usize): nn_errno;
+
+  /**
+   * TODO Functions like `describe_graph_inputs` and `describe_graph_outputs` (returning
+   * an array of `$tensor_description`s) might be useful for introspecting the graph but are not yet included here.
+   * Create an execution instance of a loaded graph.
+   * TODO this may need to accept flags that might affect the compilation or execution of the graph.
+   */
+  @external("wasi_ephemeral_nn", "init_execution_context")
+  export declare function init_execution_context(graph: graph, retptr0: usize): nn_errno;
+
+  /**
+   * Define the inputs to use for inference.
+   *
+   * This should return an $nn_errno (TODO define) if the input tensor does not match the expected dimensions and type.
+   */
+  @external("wasi_ephemeral_nn", "set_input")
+  export declare function set_input(context: graph_execution_context, index: u32, tensor: usize): nn_errno;
+
+  /**
+   * Extract the outputs after inference.
+   *
+   * This should return an $nn_errno (TODO define) if the inference has not yet run.
+   */
+  @external("wasi_ephemeral_nn", "get_output")
+  export declare function get_output(context: graph_execution_context, index: u32, out_buffer: usize, out_buffer_max_size: buffer_size, retptr0: usize): nn_errno;
+
+  /**
+   * Compute the inference on the given inputs (see `set_input`).
+   *
+   * This should return an $nn_errno (TODO define) if the inputs are not all defined.
+   */
+  @external("wasi_ephemeral_nn", "compute")
+  export declare function compute(context: graph_execution_context): nn_errno;
+}
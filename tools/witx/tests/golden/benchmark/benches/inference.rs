@@ -0,0 +1,25 @@
+//! Benchmarks of a host implementation, with the configs described by
+//! `Config::from_env`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasmtime::{Linker, Store};
+
+/// The data of the host implementation's stores.
+type Host = ();
+
+/// A linker with the host implementation's functions, and a store for it.
+fn host() -> (Linker<Host>, Store<Host>) {
+    // TODO: add the implementation of `benchmark::MODULE` to a linker, such as
+    // with the `add_to_linker` function of its wasmtime-host bindings.
+    todo!()
+}
+
+fn inference(c: &mut Criterion) {
+    for config in benchmark::Config::from_env().unwrap() {
+        let (linker, store) = host();
+        benchmark::bench(c, &linker, store, &config).unwrap();
+    }
+}
+
+criterion_group!(benches, inference);
+criterion_main!(benches);
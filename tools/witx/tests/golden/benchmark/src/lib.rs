@@ -0,0 +1,387 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+use criterion::{Criterion, Throughput};
+use std::fmt;
+use wasmtime::{Instance, Linker, Memory, Module, Store, TypedFunc, WasmParams, WasmResults};
+
+/// The module the host implements.
+pub const MODULE: &str = "wasi_ephemeral_nn";
+/// A guest forwarding calls to the host's functions.
+const GUEST: &str = include_str!("../guests/wasi_ephemeral_nn.wat");
+
+/// The cases of `graph_encoding`, by value.
+pub const ENCODINGS: &[&str] = &["openvino", "tensorflow"];
+/// The cases of `execution_target`, by value.
+pub const TARGETS: &[&str] = &["cpu", "gpu", "tpu"];
+/// The errors functions return, by value.
+pub const ERRORS: &[&str] = &["success", "invalid_argument", "missing_memory", "busy"];
+/// The cases of `tensor_type`, by value, with the bits of their elements.
+pub const TENSOR_TYPES: &[(&str, usize)] = &[("f16", 16), ("f32", 32), ("u8", 8), ("i32", 32)];
+
+// The layout of a tensor in guest memory.
+const TENSOR_SIZE: usize = 20;
+const TENSOR_ALIGN: usize = 4;
+const TENSOR_DIMENSIONS: usize = 0;
+const TENSOR_TYPE: usize = 8;
+const TENSOR_TYPE_SIZE: usize = 1;
+const TENSOR_DATA: usize = 12;
+
+/// Where functions return their results in guest memory.
+const RET: usize = 8;
+/// Where the data written by the driver starts.
+const START: usize = 16;
+
+/// The type and dimensions of a tensor of zeroes, written as
+/// `<type>:<dimensions>`, such as `f32:1x3x224x224`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tensor {
+    pub ty: &'static str,
+    pub dimensions: Vec<u32>,
+}
+
+impl Tensor {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (ty, dimensions) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected `<type>:<dimensions>`, got `{}`", s))?;
+        let ty = TENSOR_TYPES
+            .iter()
+            .map(|(name, _)| *name)
+            .find(|name| *name == ty)
+            .ok_or_else(|| format!("unknown tensor type `{}`", ty))?;
+        let dimensions = dimensions
+            .split('x')
+            .map(|d| d.parse().map_err(|_| format!("invalid dimension `{}`", d)))
+            .collect::<Result<_, _>>()?;
+        Ok(Tensor { ty, dimensions })
+    }
+
+    /// The value of the tensor's type.
+    pub fn type_value(&self) -> usize {
+        TENSOR_TYPES.iter().position(|(name, _)| *name == self.ty).unwrap()
+    }
+
+    /// The size of the tensor's data in bytes.
+    pub fn data_size(&self) -> usize {
+        let bits = TENSOR_TYPES[self.type_value()].1;
+        let elements = self.dimensions.iter().map(|d| *d as usize).product::<usize>();
+        (elements * bits + 7) / 8
+    }
+}
+
+impl fmt::Display for Tensor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dimensions = self.dimensions.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        write!(f, "{}:{}", self.ty, dimensions.join("x"))
+    }
+}
+
+/// What to benchmark: a graph, and the inputs to compute it with.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Name of the benchmark group.
+    pub name: String,
+    /// The graph builders passed to `load`.
+    pub model: Vec<Vec<u8>>,
+    pub encoding: &'static str,
+    pub target: &'static str,
+    /// The inputs, by index.
+    pub inputs: Vec<Tensor>,
+    /// The number of outputs to retrieve, and the space for each in bytes.
+    pub outputs: u32,
+    pub output_size: usize,
+}
+
+impl Config {
+    /// The configs described by environment variables, one for each set of
+    /// inputs:
+    ///
+    /// * `WASI_NN_BENCH_MODEL`: the files of the graph builders, separated
+    ///   as in `PATH`.
+    /// * `WASI_NN_BENCH_ENCODING` and `WASI_NN_BENCH_TARGET`: cases of the
+    ///   encoding and target, the first ones by default.
+    /// * `WASI_NN_BENCH_INPUTS`: sets of inputs separated by spaces, each a
+    ///   list of tensors separated by commas, such as `f32:1x3x224x224`.
+    /// * `WASI_NN_BENCH_OUTPUTS` and `WASI_NN_BENCH_OUTPUT_SIZE`: the number
+    ///   of outputs, 1 by default, and the space for each, 1 MiB by default.
+    pub fn from_env() -> Result<Vec<Config>, String> {
+        let var = |name: &str| std::env::var(name).ok();
+        let required = |name: &str| var(name).ok_or_else(|| format!("`{}` isn't set", name));
+        let model = std::env::split_paths(&required("WASI_NN_BENCH_MODEL")?)
+            .map(|path| {
+                std::fs::read(&path).map_err(|e| format!("failed to read {:?}: {}", path, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let case = |name: &str, cases: &'static [&'static str]| match var(name) {
+            Some(value) => cases
+                .iter()
+                .find(|c| **c == value)
+                .copied()
+                .ok_or_else(|| format!("`{}` isn't one of {}", value, cases.join(", "))),
+            None => Ok(cases[0]),
+        };
+        let encoding = case("WASI_NN_BENCH_ENCODING", ENCODINGS)?;
+        let target = case("WASI_NN_BENCH_TARGET", TARGETS)?;
+        let number = |name: &str, default: usize| match var(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("`{}` isn't a number: {}", name, value)),
+            None => Ok(default),
+        };
+        let outputs = number("WASI_NN_BENCH_OUTPUTS", 1)? as u32;
+        let output_size = number("WASI_NN_BENCH_OUTPUT_SIZE", 1 << 20)?;
+        required("WASI_NN_BENCH_INPUTS")?
+            .split_whitespace()
+            .map(|inputs| {
+                Ok(Config {
+                    name: format!("{}/{}", target, inputs),
+                    model: model.clone(),
+                    encoding,
+                    target,
+                    inputs: inputs.split(',').map(Tensor::parse).collect::<Result<_, _>>()?,
+                    outputs,
+                    output_size,
+                })
+            })
+            .collect()
+    }
+}
+
+/// An instance of the guest, calling the host's functions with values it
+/// has written to guest memory.
+pub struct Session<T: 'static> {
+    store: Store<T>,
+    memory: Memory,
+    /// The end of the data written so far.
+    top: usize,
+    load: TypedFunc<(i32, i32, i32, i32, i32), i32>,
+    init_execution_context: TypedFunc<(i32, i32), i32>,
+    set_input: TypedFunc<(i32, i32, i32), i32>,
+    compute: TypedFunc<i32, i32>,
+    get_output: TypedFunc<(i32, i32, i32, i32, i32), i32>,
+}
+
+fn typed<T, P: WasmParams, R: WasmResults>(
+    instance: &Instance,
+    store: &mut Store<T>,
+    name: &str,
+) -> Result<TypedFunc<P, R>, String> {
+    instance
+        .get_typed_func(store, name)
+        .map_err(|e| format!("`{}`: {}", name, e))
+}
+
+fn trapped(e: wasmtime::Error) -> String {
+    format!("trapped: {}", e)
+}
+
+/// The index of `name` in `cases`.
+fn case(cases: &[&str], name: &str) -> Result<i32, String> {
+    cases
+        .iter()
+        .position(|c| *c == name)
+        .map(|i| i as i32)
+        .ok_or_else(|| format!("unknown case `{}`", name))
+}
+
+impl<T: 'static> Session<T> {
+    pub fn new(linker: &Linker<T>, mut store: Store<T>) -> Result<Self, String> {
+        let module = Module::new(linker.engine(), GUEST).map_err(|e| e.to_string())?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| format!("instantiation failed: {}", e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .expect("the guest exports `memory`");
+        Ok(Session {
+            load: typed(&instance, &mut store, "load")?,
+            init_execution_context: typed(&instance, &mut store, "init_execution_context")?,
+            set_input: typed(&instance, &mut store, "set_input")?,
+            compute: typed(&instance, &mut store, "compute")?,
+            get_output: typed(&instance, &mut store, "get_output")?,
+            store,
+            memory,
+            top: START,
+        })
+    }
+
+    /// Copies `bytes` to guest memory aligned to `align`, growing it if need
+    /// be, and returns their address.
+    fn write(&mut self, bytes: &[u8], align: usize) -> Result<i32, String> {
+        let at = (self.top + align - 1) / align * align;
+        let end = at + bytes.len();
+        let size = self.memory.data_size(&self.store);
+        if end > size {
+            let pages = (end - size + 0xffff) / 0x10000;
+            self.memory
+                .grow(&mut self.store, pages as u64)
+                .map_err(|e| format!("failed to grow memory: {}", e))?;
+        }
+        self.memory.data_mut(&mut self.store)[at..end].copy_from_slice(bytes);
+        self.top = end;
+        Ok(at as i32)
+    }
+
+    /// The `u32` returned at `RET` by the last call.
+    fn ret(&self) -> u32 {
+        let bytes = &self.memory.data(&self.store)[RET..RET + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn check(func: &str, code: i32) -> Result<(), String> {
+        match code {
+            0 => Ok(()),
+            code => Err(format!(
+                "`{}` failed with `{}`",
+                func,
+                ERRORS.get(code as usize).copied().unwrap_or("an unknown error")
+            )),
+        }
+    }
+
+    /// Copies graph builders to guest memory, returning the list of them to
+    /// pass to `load`.
+    pub fn write_model(&mut self, model: &[Vec<u8>]) -> Result<(i32, i32), String> {
+        let mut list = Vec::new();
+        for builder in model {
+            let ptr = self.write(builder, 1)?;
+            list.extend_from_slice(&ptr.to_le_bytes());
+            list.extend_from_slice(&(builder.len() as u32).to_le_bytes());
+        }
+        Ok((self.write(&list, 4)?, model.len() as i32))
+    }
+
+    /// Copies a tensor of zeroes to guest memory, returning its address.
+    pub fn write_tensor(&mut self, tensor: &Tensor) -> Result<i32, String> {
+        let dimensions = tensor
+            .dimensions
+            .iter()
+            .flat_map(|d| d.to_le_bytes())
+            .collect::<Vec<_>>();
+        let dimensions = self.write(&dimensions, 4)?;
+        let data = self.write(&vec![0; tensor.data_size()], 8)?;
+        let mut record = vec![0; TENSOR_SIZE];
+        let mut field = |offset: usize, bytes: &[u8]| {
+            record[offset..offset + bytes.len()].copy_from_slice(bytes)
+        };
+        field(TENSOR_DIMENSIONS, &dimensions.to_le_bytes());
+        field(TENSOR_DIMENSIONS + 4, &(tensor.dimensions.len() as u32).to_le_bytes());
+        field(TENSOR_TYPE, &tensor.type_value().to_le_bytes()[..TENSOR_TYPE_SIZE]);
+        field(TENSOR_DATA, &data.to_le_bytes());
+        field(TENSOR_DATA + 4, &(tensor.data_size() as u32).to_le_bytes());
+        self.write(&record, TENSOR_ALIGN)
+    }
+
+    /// Reserves `len` bytes of guest memory for an output, returning their
+    /// address.
+    pub fn alloc(&mut self, len: usize) -> Result<i32, String> {
+        self.write(&vec![0; len], 8)
+    }
+
+    pub fn load(&mut self, model: (i32, i32), encoding: &str, target: &str) -> Result<i32, String> {
+        let args = (model.0, model.1, case(ENCODINGS, encoding)?, case(TARGETS, target)?, RET as i32);
+        let code = self.load.call(&mut self.store, args).map_err(trapped)?;
+        Self::check("load", code)?;
+        Ok(self.ret() as i32)
+    }
+
+    pub fn init_execution_context(&mut self, graph: i32) -> Result<i32, String> {
+        let code = self
+            .init_execution_context
+            .call(&mut self.store, (graph, RET as i32))
+            .map_err(trapped)?;
+        Self::check("init_execution_context", code)?;
+        Ok(self.ret() as i32)
+    }
+
+    /// Sets the input `index` to the tensor written at `tensor`.
+    pub fn set_input(&mut self, context: i32, index: u32, tensor: i32) -> Result<(), String> {
+        let code = self
+            .set_input
+            .call(&mut self.store, (context, index as i32, tensor))
+            .map_err(trapped)?;
+        Self::check("set_input", code)
+    }
+
+    pub fn compute(&mut self, context: i32) -> Result<(), String> {
+        let code = self.compute.call(&mut self.store, context).map_err(trapped)?;
+        Self::check("compute", code)
+    }
+
+    /// Copies the output `index` to the `len` bytes at `buffer`, returning
+    /// the size of the output.
+    pub fn get_output(&mut self, context: i32, index: u32, buffer: i32, len: usize) -> Result<usize, String> {
+        let args = (context, index as i32, buffer, len as i32, RET as i32);
+        let code = self.get_output.call(&mut self.store, args).map_err(trapped)?;
+        Self::check("get_output", code)?;
+        Ok(self.ret() as usize)
+    }
+}
+
+/// Benchmarks each function of the host in `linker`, and a whole inference,
+/// with `config`. Each benchmark reuses the graph and execution context
+/// created before it, so `load` and `init_execution_context` create as many
+/// as they're called.
+pub fn bench<T: 'static>(
+    c: &mut Criterion,
+    linker: &Linker<T>,
+    store: Store<T>,
+    config: &Config,
+) -> Result<(), String> {
+    let mut s = Session::new(linker, store)?;
+    let model = s.write_model(&config.model)?;
+    let inputs = config
+        .inputs
+        .iter()
+        .map(|t| s.write_tensor(t))
+        .collect::<Result<Vec<_>, _>>()?;
+    let output = s.alloc(config.output_size)?;
+    let input_bytes = config.inputs.iter().map(|t| t.data_size() as u64).sum();
+
+    let graph = s.load(model, config.encoding, config.target)?;
+    let context = s.init_execution_context(graph)?;
+    for (i, tensor) in inputs.iter().enumerate() {
+        s.set_input(context, i as u32, *tensor)?;
+    }
+    s.compute(context)?;
+    for i in 0..config.outputs {
+        s.get_output(context, i, output, config.output_size)?;
+    }
+
+    let mut group = c.benchmark_group(config.name.clone());
+    group.bench_function("load", |b| {
+        b.iter(|| s.load(model, config.encoding, config.target).unwrap())
+    });
+    group.bench_function("init_execution_context", |b| {
+        b.iter(|| s.init_execution_context(graph).unwrap())
+    });
+    group.bench_function("compute", |b| b.iter(|| s.compute(context).unwrap()));
+    group.bench_function("get_output", |b| {
+        b.iter(|| {
+            for i in 0..config.outputs {
+                s.get_output(context, i, output, config.output_size).unwrap();
+            }
+        })
+    });
+    group.throughput(Throughput::Bytes(input_bytes));
+    group.bench_function("set_input", |b| {
+        b.iter(|| {
+            for (i, tensor) in inputs.iter().enumerate() {
+                s.set_input(context, i as u32, *tensor).unwrap();
+            }
+        })
+    });
+    group.bench_function("inference", |b| {
+        b.iter(|| {
+            for (i, tensor) in inputs.iter().enumerate() {
+                s.set_input(context, i as u32, *tensor).unwrap();
+            }
+            s.compute(context).unwrap();
+            for i in 0..config.outputs {
+                s.get_output(context, i, output, config.output_size).unwrap();
+            }
+        })
+    });
+    group.finish();
+    Ok(())
+}
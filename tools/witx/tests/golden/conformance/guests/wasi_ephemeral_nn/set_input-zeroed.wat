@@ -0,0 +1,6 @@
+;; wasi_ephemeral_nn::set_input: zeroed
+(module
+  (import "wasi_ephemeral_nn" "set_input" (func $f (param i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "run") (result i32)
+    (call $f (i32.const 0) (i32.const 0) (i32.const 1024))))
@@ -0,0 +1,183 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+use std::fmt::{self, Write};
+use wasmtime::{Linker, Module, Store, Val};
+
+/// What a host must do for a test to pass.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Expect {
+    /// Return, with any result.
+    NoTrap,
+    /// Return a non-zero error code.
+    Error,
+    /// Return a non-zero error code, or trap.
+    Rejected,
+}
+
+/// A guest program calling `module::func` once.
+#[derive(Debug)]
+pub struct Test {
+    pub module: &'static str,
+    pub func: &'static str,
+    pub case: &'static str,
+    pub expect: Expect,
+    pub wat: &'static str,
+}
+
+pub const TESTS: &[Test] = &[
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "load",
+        case: "zeroed",
+        expect: Expect::NoTrap,
+        wat: include_str!("../guests/wasi_ephemeral_nn/load-zeroed.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "load",
+        case: "unsupported-encoding",
+        expect: Expect::Rejected,
+        wat: include_str!("../guests/wasi_ephemeral_nn/load-unsupported-encoding.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "load",
+        case: "unsupported-target",
+        expect: Expect::Rejected,
+        wat: include_str!("../guests/wasi_ephemeral_nn/load-unsupported-target.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "init_execution_context",
+        case: "zeroed",
+        expect: Expect::NoTrap,
+        wat: include_str!("../guests/wasi_ephemeral_nn/init_execution_context-zeroed.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "init_execution_context",
+        case: "invalid-graph",
+        expect: Expect::Error,
+        wat: include_str!("../guests/wasi_ephemeral_nn/init_execution_context-invalid-graph.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "set_input",
+        case: "zeroed",
+        expect: Expect::Rejected,
+        wat: include_str!("../guests/wasi_ephemeral_nn/set_input-zeroed.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "set_input",
+        case: "invalid-context",
+        expect: Expect::Rejected,
+        wat: include_str!("../guests/wasi_ephemeral_nn/set_input-invalid-context.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "get_output",
+        case: "zeroed",
+        expect: Expect::NoTrap,
+        wat: include_str!("../guests/wasi_ephemeral_nn/get_output-zeroed.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "get_output",
+        case: "invalid-context",
+        expect: Expect::Error,
+        wat: include_str!("../guests/wasi_ephemeral_nn/get_output-invalid-context.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "compute",
+        case: "zeroed",
+        expect: Expect::NoTrap,
+        wat: include_str!("../guests/wasi_ephemeral_nn/compute-zeroed.wat"),
+    },
+    Test {
+        module: "wasi_ephemeral_nn",
+        func: "compute",
+        case: "invalid-context",
+        expect: Expect::Error,
+        wat: include_str!("../guests/wasi_ephemeral_nn/compute-invalid-context.wat"),
+    },
+];
+
+/// The result of running a test, with a description of the failure.
+#[derive(Debug)]
+pub struct Outcome {
+    pub test: &'static Test,
+    pub result: Result<(), String>,
+}
+
+/// Run every test against the functions in `linker`, with a new store from
+/// `new_store` for each test.
+pub fn run<T: 'static>(linker: &Linker<T>, mut new_store: impl FnMut() -> Store<T>) -> Vec<Outcome> {
+    TESTS
+        .iter()
+        .map(|test| Outcome {
+            test,
+            result: run_one(linker, new_store(), test),
+        })
+        .collect()
+}
+
+fn run_one<T: 'static>(linker: &Linker<T>, mut store: Store<T>, test: &Test) -> Result<(), String> {
+    let module = Module::new(linker.engine(), test.wat).map_err(|e| e.to_string())?;
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .map_err(|e| format!("instantiation failed: {}", e))?;
+    let run = instance
+        .get_func(&mut store, "run")
+        .expect("tests export `run`");
+    let mut results = vec![Val::I32(0); run.ty(&store).results().len()];
+    let code = match run.call(&mut store, &[], &mut results) {
+        Ok(()) => results.first().and_then(|v| v.i32()).unwrap_or(0),
+        Err(trap) => {
+            return match test.expect {
+                Expect::Rejected => Ok(()),
+                _ => Err(format!("trapped: {}", trap)),
+            }
+        }
+    };
+    match test.expect {
+        Expect::Error | Expect::Rejected if code == 0 => Err("returned success".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Pass or fail of each function, with the failing cases.
+pub fn report(outcomes: &[Outcome]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < outcomes.len() {
+        let test = outcomes[i].test;
+        let n = outcomes[i..]
+            .iter()
+            .take_while(|o| o.test.module == test.module && o.test.func == test.func)
+            .count();
+        let failures = outcomes[i..i + n]
+            .iter()
+            .filter_map(|o| o.result.as_ref().err().map(|e| (o.test.case, e)))
+            .collect::<Vec<_>>();
+        let status = if failures.is_empty() { "PASS" } else { "FAIL" };
+        writeln!(out, "{} {}::{}", status, test.module, test.func).unwrap();
+        for (case, error) in failures {
+            writeln!(out, "    {}: {}", case, error).unwrap();
+        }
+        i += n;
+    }
+    out
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = if self.result.is_ok() { "PASS" } else { "FAIL" };
+        write!(
+            f,
+            "{} {}::{} {}",
+            status, self.test.module, self.test.func, self.test.case
+        )
+    }
+}
@@ -0,0 +1,376 @@
+/**
+ * This file is automatically generated by witx, DO NOT EDIT!
+ */
+
+#ifndef WASI_EPHEMERAL_NN_H
+#define WASI_EPHEMERAL_NN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/**
+ * The size of a graph buffer. This is equivalent to `$size` in `typenames.witx` but renamed since `typenames.witx` is
+ * not included here but is included in the overall ephemeral phase.
+ */
+typedef uint32_t wasi_ephemeral_nn_buffer_size_t;
+
+/**
+ * Error codes returned by functions in this API. This is prefixed to avoid conflicts with the `$errno` in
+ * `typenames.witx`.
+ */
+typedef uint16_t wasi_ephemeral_nn_nn_errno_t;
+/**
+ * No error occurred.
+ */
+#define WASI_EPHEMERAL_NN_NN_ERRNO_SUCCESS ((wasi_ephemeral_nn_nn_errno_t)0)
+/**
+ * Caller module passed an invalid argument.
+ */
+#define WASI_EPHEMERAL_NN_NN_ERRNO_INVALID_ARGUMENT ((wasi_ephemeral_nn_nn_errno_t)1)
+/**
+ * Caller module is missing a memory export.
+ */
+#define WASI_EPHEMERAL_NN_NN_ERRNO_MISSING_MEMORY ((wasi_ephemeral_nn_nn_errno_t)2)
+/**
+ * Device or resource busy.
+ */
+#define WASI_EPHEMERAL_NN_NN_ERRNO_BUSY ((wasi_ephemeral_nn_nn_errno_t)3)
+
+/**
+ * The number of values of `wasi_ephemeral_nn_nn_errno_t`.
+ */
+#define WASI_EPHEMERAL_NN_NN_ERRNO_COUNT 4
+
+/**
+ * The witx name of `value`, or NULL if it isn't known.
+ */
+static inline const char *wasi_ephemeral_nn_nn_errno_name(wasi_ephemeral_nn_nn_errno_t value) {
+    switch (value) {
+    case WASI_EPHEMERAL_NN_NN_ERRNO_SUCCESS: return "success";
+    case WASI_EPHEMERAL_NN_NN_ERRNO_INVALID_ARGUMENT: return "invalid_argument";
+    case WASI_EPHEMERAL_NN_NN_ERRNO_MISSING_MEMORY: return "missing_memory";
+    case WASI_EPHEMERAL_NN_NN_ERRNO_BUSY: return "busy";
+    default: return NULL;
+    }
+}
+
+/**
+ * Set `*value` to the value named `name` in witx, returning whether
+ * there is one.
+ */
+static inline bool wasi_ephemeral_nn_nn_errno_from_name(const char *name, wasi_ephemeral_nn_nn_errno_t *value) {
+    for (size_t i = 0; i < WASI_EPHEMERAL_NN_NN_ERRNO_COUNT; i++) {
+        if (strcmp(name, wasi_ephemeral_nn_nn_errno_name((wasi_ephemeral_nn_nn_errno_t)i)) == 0) {
+            *value = (wasi_ephemeral_nn_nn_errno_t)i;
+            return true;
+        }
+    }
+    return false;
+}
+
+/**
+ * The dimensions of a tensor.
+ *
+ * The array length matches the tensor rank and each element in the array
+ * describes the size of each dimension.
+ */
+typedef struct wasi_ephemeral_nn_tensor_dimensions_t {
+    uint32_t *buf;
+    size_t buf_len;
+} wasi_ephemeral_nn_tensor_dimensions_t;
+
+/**
+ * The type of the elements in a tensor.
+ */
+typedef uint8_t wasi_ephemeral_nn_tensor_type_t;
+#define WASI_EPHEMERAL_NN_TENSOR_TYPE_F16 ((wasi_ephemeral_nn_tensor_type_t)0)
+#define WASI_EPHEMERAL_NN_TENSOR_TYPE_F32 ((wasi_ephemeral_nn_tensor_type_t)1)
+#define WASI_EPHEMERAL_NN_TENSOR_TYPE_U8 ((wasi_ephemeral_nn_tensor_type_t)2)
+#define WASI_EPHEMERAL_NN_TENSOR_TYPE_I32 ((wasi_ephemeral_nn_tensor_type_t)3)
+
+/**
+ * The number of values of `wasi_ephemeral_nn_tensor_type_t`.
+ */
+#define WASI_EPHEMERAL_NN_TENSOR_TYPE_COUNT 4
+
+/**
+ * The witx name of `value`, or NULL if it isn't known.
+ */
+static inline const char *wasi_ephemeral_nn_tensor_type_name(wasi_ephemeral_nn_tensor_type_t value) {
+    switch (value) {
+    case WASI_EPHEMERAL_NN_TENSOR_TYPE_F16: return "f16";
+    case WASI_EPHEMERAL_NN_TENSOR_TYPE_F32: return "f32";
+    case WASI_EPHEMERAL_NN_TENSOR_TYPE_U8: return "u8";
+    case WASI_EPHEMERAL_NN_TENSOR_TYPE_I32: return "i32";
+    default: return NULL;
+    }
+}
+
+/**
+ * Set `*value` to the value named `name` in witx, returning whether
+ * there is one.
+ */
+static inline bool wasi_ephemeral_nn_tensor_type_from_name(const char *name, wasi_ephemeral_nn_tensor_type_t *value) {
+    for (size_t i = 0; i < WASI_EPHEMERAL_NN_TENSOR_TYPE_COUNT; i++) {
+        if (strcmp(name, wasi_ephemeral_nn_tensor_type_name((wasi_ephemeral_nn_tensor_type_t)i)) == 0) {
+            *value = (wasi_ephemeral_nn_tensor_type_t)i;
+            return true;
+        }
+    }
+    return false;
+}
+
+/**
+ * Size of an element of `ty` in bits, or 0 if the type isn't known.
+ */
+static inline uint32_t wasi_ephemeral_nn_tensor_type_bits(wasi_ephemeral_nn_tensor_type_t ty) {
+    switch (ty) {
+    case WASI_EPHEMERAL_NN_TENSOR_TYPE_F16: return 16;
+    case WASI_EPHEMERAL_NN_TENSOR_TYPE_F32: return 32;
+    case WASI_EPHEMERAL_NN_TENSOR_TYPE_U8: return 8;
+    case WASI_EPHEMERAL_NN_TENSOR_TYPE_I32: return 32;
+    default: return 0;
+    }
+}
+
+/**
+ * Bytes of data in a tensor of type `ty` with `dimensions`, or 0 if the
+ * type isn't known or the size overflows. Packed elements are padded to a
+ * whole byte.
+ */
+static inline size_t wasi_ephemeral_nn_tensor_type_buffer_size(wasi_ephemeral_nn_tensor_type_t ty, const uint32_t *dimensions, size_t dimensions_len) {
+    size_t bits = wasi_ephemeral_nn_tensor_type_bits(ty);
+    for (size_t i = 0; i < dimensions_len; i++) {
+        if (dimensions[i] != 0 && bits > SIZE_MAX / dimensions[i]) {
+            return 0;
+        }
+        bits *= dimensions[i];
+    }
+    return bits / 8 + (bits % 8 != 0);
+}
+
+/**
+ * The tensor data
+ *
+ * Initially conceived as a sparse representation, each empty cell would be filled with zeroes and
+ * the array length must match the product of all of the dimensions and the number of bytes in the type (e.g. a 2x2
+ * tensor with 4-byte f32 elements would have a data array of length 16). Naturally, this representation requires
+ * some knowledge of how to lay out data in memory--e.g. using row-major ordering--and could perhaps be improved
+ * by future witx features (TODO).
+ */
+typedef struct wasi_ephemeral_nn_tensor_data_t {
+    uint8_t *buf;
+    size_t buf_len;
+} wasi_ephemeral_nn_tensor_data_t;
+
+/**
+ * A tensor.
+ */
+typedef struct wasi_ephemeral_nn_tensor_t {
+    /**
+     * Describe the size of the tensor (e.g. 2x2x2x2 -> [2, 2, 2, 2]). To represent a tensor containing a single value,
+     * use `[1]` for the tensor dimensions.
+     */
+    wasi_ephemeral_nn_tensor_dimensions_t dimensions;
+    wasi_ephemeral_nn_tensor_type_t type;
+    /**
+     * Contains the tensor data.
+     */
+    wasi_ephemeral_nn_tensor_data_t data;
+} wasi_ephemeral_nn_tensor_t;
+
+/**
+ * The graph initialization data. This consists of an array of buffers because implementing backends may encode their
+ * graph IR in parts (e.g. OpenVINO stores its IR and weights separately).
+ */
+typedef struct wasi_ephemeral_nn_graph_builder_t {
+    uint8_t *buf;
+    size_t buf_len;
+} wasi_ephemeral_nn_graph_builder_t;
+
+typedef struct wasi_ephemeral_nn_graph_builder_array_t {
+    wasi_ephemeral_nn_graph_builder_t *buf;
+    size_t buf_len;
+} wasi_ephemeral_nn_graph_builder_array_t;
+
+/**
+ * An execution graph for performing inference (i.e. a model).
+ */
+typedef int32_t wasi_ephemeral_nn_graph_t;
+
+/**
+ * Describes the encoding of the graph. This allows the API to be implemented by various backends that encode (i.e.
+ * serialize) their graph IR differently.
+ */
+typedef uint8_t wasi_ephemeral_nn_graph_encoding_t;
+/**
+ * TODO document buffer order
+ */
+#define WASI_EPHEMERAL_NN_GRAPH_ENCODING_OPENVINO ((wasi_ephemeral_nn_graph_encoding_t)0)
+#define WASI_EPHEMERAL_NN_GRAPH_ENCODING_TENSORFLOW ((wasi_ephemeral_nn_graph_encoding_t)1)
+
+/**
+ * The number of values of `wasi_ephemeral_nn_graph_encoding_t`.
+ */
+#define WASI_EPHEMERAL_NN_GRAPH_ENCODING_COUNT 2
+
+/**
+ * The witx name of `value`, or NULL if it isn't known.
+ */
+static inline const char *wasi_ephemeral_nn_graph_encoding_name(wasi_ephemeral_nn_graph_encoding_t value) {
+    switch (value) {
+    case WASI_EPHEMERAL_NN_GRAPH_ENCODING_OPENVINO: return "openvino";
+    case WASI_EPHEMERAL_NN_GRAPH_ENCODING_TENSORFLOW: return "tensorflow";
+    default: return NULL;
+    }
+}
+
+/**
+ * Set `*value` to the value named `name` in witx, returning whether
+ * there is one.
+ */
+static inline bool wasi_ephemeral_nn_graph_encoding_from_name(const char *name, wasi_ephemeral_nn_graph_encoding_t *value) {
+    for (size_t i = 0; i < WASI_EPHEMERAL_NN_GRAPH_ENCODING_COUNT; i++) {
+        if (strcmp(name, wasi_ephemeral_nn_graph_encoding_name((wasi_ephemeral_nn_graph_encoding_t)i)) == 0) {
+            *value = (wasi_ephemeral_nn_graph_encoding_t)i;
+            return true;
+        }
+    }
+    return false;
+}
+
+/**
+ * Define where the graph should be executed.
+ */
+typedef uint8_t wasi_ephemeral_nn_execution_target_t;
+#define WASI_EPHEMERAL_NN_EXECUTION_TARGET_CPU ((wasi_ephemeral_nn_execution_target_t)0)
+#define WASI_EPHEMERAL_NN_EXECUTION_TARGET_GPU ((wasi_ephemeral_nn_execution_target_t)1)
+#define WASI_EPHEMERAL_NN_EXECUTION_TARGET_TPU ((wasi_ephemeral_nn_execution_target_t)2)
+
+/**
+ * The number of values of `wasi_ephemeral_nn_execution_target_t`.
+ */
+#define WASI_EPHEMERAL_NN_EXECUTION_TARGET_COUNT 3
+
+/**
+ * The witx name of `value`, or NULL if it isn't known.
+ */
+static inline const char *wasi_ephemeral_nn_execution_target_name(wasi_ephemeral_nn_execution_target_t value) {
+    switch (value) {
+    case WASI_EPHEMERAL_NN_EXECUTION_TARGET_CPU: return "cpu";
+    case WASI_EPHEMERAL_NN_EXECUTION_TARGET_GPU: return "gpu";
+    case WASI_EPHEMERAL_NN_EXECUTION_TARGET_TPU: return "tpu";
+    default: return NULL;
+    }
+}
+
+/**
+ * Set `*value` to the value named `name` in witx, returning whether
+ * there is one.
+ */
+static inline bool wasi_ephemeral_nn_execution_target_from_name(const char *name, wasi_ephemeral_nn_execution_target_t *value) {
+    for (size_t i = 0; i < WASI_EPHEMERAL_NN_EXECUTION_TARGET_COUNT; i++) {
+        if (strcmp(name, wasi_ephemeral_nn_execution_target_name((wasi_ephemeral_nn_execution_target_t)i)) == 0) {
+            *value = (wasi_ephemeral_nn_execution_target_t)i;
+            return true;
+        }
+    }
+    return false;
+}
+
+/**
+ * A $graph_execution_context allows for attaching inputs prior to calling `compute` on a graph and retrieving outputs after
+ * the computation has completed. TODO a handle may not be the right type but we want it to be opaque to users.
+ */
+typedef int32_t wasi_ephemeral_nn_graph_execution_context_t;
+
+#if defined(__wasm32__)
+#ifdef __cplusplus
+#define WASI_EPHEMERAL_NN_ASSERT_LAYOUT(t, size, align) static_assert(sizeof(t) == size && alignof(t) == align, "layout of " #t)
+#else
+#define WASI_EPHEMERAL_NN_ASSERT_LAYOUT(t, size, align) _Static_assert(sizeof(t) == size && _Alignof(t) == align, "layout of " #t)
+#endif
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_buffer_size_t, 4, 4);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_nn_errno_t, 2, 2);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_tensor_dimensions_t, 8, 4);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_tensor_type_t, 1, 1);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_tensor_data_t, 8, 4);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_tensor_t, 20, 4);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_graph_builder_t, 8, 4);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_graph_builder_array_t, 8, 4);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_graph_t, 4, 4);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_graph_encoding_t, 1, 1);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_execution_target_t, 1, 1);
+WASI_EPHEMERAL_NN_ASSERT_LAYOUT(wasi_ephemeral_nn_graph_execution_context_t, 4, 4);
+#undef WASI_EPHEMERAL_NN_ASSERT_LAYOUT
+#endif
+
+/**
+ * Load an opaque sequence of bytes to use for inference.
+ *
+ * This allows runtime implementations to support multiple graph encoding formats. For unsupported graph encodings,
+ * return `errno::inval`.
+ */
+wasi_ephemeral_nn_nn_errno_t wasi_ephemeral_nn_load(
+    const wasi_ephemeral_nn_graph_builder_t *builder,
+    size_t builder_len,
+    wasi_ephemeral_nn_graph_encoding_t encoding,
+    wasi_ephemeral_nn_execution_target_t target,
+    wasi_ephemeral_nn_graph_t *retptr0
+) __attribute__((__import_module__("wasi_ephemeral_nn"), __import_name__("load")));
+
+/**
+ * TODO Functions like `describe_graph_inputs` and `describe_graph_outputs` (returning
+ * an array of `$tensor_description`s) might be useful for introspecting the graph but are not yet included here.
+ * Create an execution instance of a loaded graph.
+ * TODO this may need to accept flags that might affect the compilation or execution of the graph.
+ */
+wasi_ephemeral_nn_nn_errno_t wasi_ephemeral_nn_init_execution_context(
+    wasi_ephemeral_nn_graph_t graph,
+    wasi_ephemeral_nn_graph_execution_context_t *retptr0
+) __attribute__((__import_module__("wasi_ephemeral_nn"), __import_name__("init_execution_context")));
+
+/**
+ * Define the inputs to use for inference.
+ *
+ * This should return an $nn_errno (TODO define) if the input tensor does not match the expected dimensions and type.
+ */
+wasi_ephemeral_nn_nn_errno_t wasi_ephemeral_nn_set_input(
+    wasi_ephemeral_nn_graph_execution_context_t context,
+    uint32_t index,
+    const wasi_ephemeral_nn_tensor_t *tensor
+) __attribute__((__import_module__("wasi_ephemeral_nn"), __import_name__("set_input")));
+
+/**
+ * Extract the outputs after inference.
+ *
+ * This should return an $nn_errno (TODO define) if the inference has not yet run.
+ */
+wasi_ephemeral_nn_nn_errno_t wasi_ephemeral_nn_get_output(
+    wasi_ephemeral_nn_graph_execution_context_t context,
+    uint32_t index,
+    uint8_t *out_buffer,
+    wasi_ephemeral_nn_buffer_size_t out_buffer_max_size,
+    wasi_ephemeral_nn_buffer_size_t *retptr0
+) __attribute__((__import_module__("wasi_ephemeral_nn"), __import_name__("get_output")));
+
+/**
+ * Compute the inference on the given inputs (see `set_input`).
+ *
+ * This should return an $nn_errno (TODO define) if the inputs are not all defined.
+ */
+wasi_ephemeral_nn_nn_errno_t wasi_ephemeral_nn_compute(
+    wasi_ephemeral_nn_graph_execution_context_t context
+) __attribute__((__import_module__("wasi_ephemeral_nn"), __import_name__("compute")));
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* WASI_EPHEMERAL_NN_H */
@@ -0,0 +1,203 @@
+/**
+ * This file is automatically generated by witx, DO NOT EDIT!
+ */
+
+#ifndef WASI_EPHEMERAL_NN_HPP
+#define WASI_EPHEMERAL_NN_HPP
+
+#include "wasi_ephemeral_nn.h"
+
+#include <cstddef>
+#include <cstdint>
+#include <exception>
+#include <stdexcept>
+#include <string_view>
+#include <tuple>
+#include <type_traits>
+#include <utility>
+#if __cplusplus >= 202002L
+#include <span>
+#endif
+
+namespace wasi_ephemeral_nn {
+
+#if __cplusplus >= 202002L
+template <typename T>
+using span = std::span<T>;
+#else
+/**
+ * A view of contiguous values, standing in for `std::span` before C++20.
+ */
+template <typename T>
+class span {
+public:
+    constexpr span() noexcept = default;
+    constexpr span(T *data, std::size_t size) noexcept : data_(data), size_(size) {}
+    template <std::size_t N>
+    constexpr span(T (&array)[N]) noexcept : data_(array), size_(N) {}
+    template <typename C,
+              typename = std::enable_if_t<!std::is_same_v<std::decay_t<C>, span>>,
+              typename = decltype(std::declval<C &>().data())>
+    constexpr span(C &&container) noexcept
+        : data_(container.data()), size_(container.size()) {}
+
+    constexpr T *data() const noexcept { return data_; }
+    constexpr std::size_t size() const noexcept { return size_; }
+    constexpr bool empty() const noexcept { return size_ == 0; }
+    constexpr T *begin() const noexcept { return data_; }
+    constexpr T *end() const noexcept { return data_ + size_; }
+
+private:
+    T *data_ = nullptr;
+    std::size_t size_ = 0;
+};
+#endif
+
+using buffer_size_t = ::wasi_ephemeral_nn_buffer_size_t;
+using nn_errno_t = ::wasi_ephemeral_nn_nn_errno_t;
+using tensor_dimensions_t = ::wasi_ephemeral_nn_tensor_dimensions_t;
+using tensor_type_t = ::wasi_ephemeral_nn_tensor_type_t;
+using tensor_data_t = ::wasi_ephemeral_nn_tensor_data_t;
+using tensor_t = ::wasi_ephemeral_nn_tensor_t;
+using graph_builder_t = ::wasi_ephemeral_nn_graph_builder_t;
+using graph_builder_array_t = ::wasi_ephemeral_nn_graph_builder_array_t;
+using graph_t = ::wasi_ephemeral_nn_graph_t;
+using graph_encoding_t = ::wasi_ephemeral_nn_graph_encoding_t;
+using execution_target_t = ::wasi_ephemeral_nn_execution_target_t;
+using graph_execution_context_t = ::wasi_ephemeral_nn_graph_execution_context_t;
+
+/**
+ * An error returned as a `nn_errno`.
+ */
+class nn_errno_error : public std::exception {
+public:
+    explicit nn_errno_error(nn_errno_t code) noexcept : code_(code) {}
+    nn_errno_t code() const noexcept { return code_; }
+    const char *what() const noexcept override {
+        switch (code_) {
+        case WASI_EPHEMERAL_NN_NN_ERRNO_SUCCESS: return "No error occurred";
+        case WASI_EPHEMERAL_NN_NN_ERRNO_INVALID_ARGUMENT: return "Caller module passed an invalid argument";
+        case WASI_EPHEMERAL_NN_NN_ERRNO_MISSING_MEMORY: return "Caller module is missing a memory export";
+        case WASI_EPHEMERAL_NN_NN_ERRNO_BUSY: return "Device or resource busy";
+        default: return "unknown error";
+        }
+    }
+
+private:
+    nn_errno_t code_;
+};
+
+/**
+ * An execution graph for performing inference (i.e. a model).
+ */
+class graph {
+public:
+    /** Take ownership of a raw handle. */
+    explicit graph(graph_t raw) noexcept : raw_(raw) {}
+    graph(const graph &) = delete;
+    graph &operator=(const graph &) = delete;
+    graph(graph &&) noexcept = default;
+    graph &operator=(graph &&) noexcept = default;
+
+    /** The raw handle, which stays owned by this object. */
+    graph_t get() const noexcept { return raw_; }
+    /** Give up ownership of the handle. */
+    graph_t release() noexcept { return raw_; }
+
+private:
+    graph_t raw_;
+};
+
+/**
+ * A $graph_execution_context allows for attaching inputs prior to calling `compute` on a graph and retrieving outputs after
+ * the computation has completed. TODO a handle may not be the right type but we want it to be opaque to users.
+ */
+class graph_execution_context {
+public:
+    /** Take ownership of a raw handle. */
+    explicit graph_execution_context(graph_execution_context_t raw) noexcept : raw_(raw) {}
+    graph_execution_context(const graph_execution_context &) = delete;
+    graph_execution_context &operator=(const graph_execution_context &) = delete;
+    graph_execution_context(graph_execution_context &&) noexcept = default;
+    graph_execution_context &operator=(graph_execution_context &&) noexcept = default;
+
+    /** The raw handle, which stays owned by this object. */
+    graph_execution_context_t get() const noexcept { return raw_; }
+    /** Give up ownership of the handle. */
+    graph_execution_context_t release() noexcept { return raw_; }
+
+private:
+    graph_execution_context_t raw_;
+};
+
+/**
+ * Load an opaque sequence of bytes to use for inference.
+ *
+ * This allows runtime implementations to support multiple graph encoding formats. For unsupported graph encodings,
+ * return `errno::inval`.
+ */
+inline graph load(span<const graph_builder_t> builder, graph_encoding_t encoding, execution_target_t target) {
+    graph_t retptr0;
+    auto ret = ::wasi_ephemeral_nn_load(builder.data(), builder.size(), encoding, target, &retptr0);
+    if (ret != 0) {
+        throw nn_errno_error(ret);
+    }
+    return graph(retptr0);
+}
+
+/**
+ * TODO Functions like `describe_graph_inputs` and `describe_graph_outputs` (returning
+ * an array of `$tensor_description`s) might be useful for introspecting the graph but are not yet included here.
+ * Create an execution instance of a loaded graph.
+ * TODO this may need to accept flags that might affect the compilation or execution of the graph.
+ */
+inline graph_execution_context init_execution_context(const graph &graph_) {
+    graph_execution_context_t retptr0;
+    auto ret = ::wasi_ephemeral_nn_init_execution_context(graph_.get(), &retptr0);
+    if (ret != 0) {
+        throw nn_errno_error(ret);
+    }
+    return graph_execution_context(retptr0);
+}
+
+/**
+ * Define the inputs to use for inference.
+ *
+ * This should return an $nn_errno (TODO define) if the input tensor does not match the expected dimensions and type.
+ */
+inline void set_input(const graph_execution_context &context, uint32_t index, const tensor_t &tensor) {
+    auto ret = ::wasi_ephemeral_nn_set_input(context.get(), index, &tensor);
+    if (ret != 0) {
+        throw nn_errno_error(ret);
+    }
+}
+
+/**
+ * Extract the outputs after inference.
+ *
+ * This should return an $nn_errno (TODO define) if the inference has not yet run.
+ */
+inline buffer_size_t get_output(const graph_execution_context &context, uint32_t index, uint8_t *out_buffer, buffer_size_t out_buffer_max_size) {
+    buffer_size_t retptr0;
+    auto ret = ::wasi_ephemeral_nn_get_output(context.get(), index, out_buffer, out_buffer_max_size, &retptr0);
+    if (ret != 0) {
+        throw nn_errno_error(ret);
+    }
+    return retptr0;
+}
+
+/**
+ * Compute the inference on the given inputs (see `set_input`).
+ *
+ * This should return an $nn_errno (TODO define) if the inputs are not all defined.
+ */
+inline void compute(const graph_execution_context &context) {
+    auto ret = ::wasi_ephemeral_nn_compute(context.get());
+    if (ret != 0) {
+        throw nn_errno_error(ret);
+    }
+}
+
+} // namespace wasi_ephemeral_nn
+
+#endif /* WASI_EPHEMERAL_NN_HPP */
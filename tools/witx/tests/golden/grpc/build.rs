@@ -0,0 +1,6 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::compile_protos("proto/wasi_ephemeral_nn.proto")?;
+    Ok(())
+}
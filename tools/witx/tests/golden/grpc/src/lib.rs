@@ -0,0 +1,24 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+//! gRPC services built from `proto/wasi_ephemeral_nn.proto`.
+//!
+//! Implement a module's service trait to serve it, or use its client to
+//! forward calls to a remote host.
+
+pub mod proto {
+    tonic::include_proto!("wasi_ephemeral_nn");
+}
+
+pub use proto::wasi_ephemeral_nn_client::WasiEphemeralNnClient;
+pub use proto::wasi_ephemeral_nn_server::{WasiEphemeralNn, WasiEphemeralNnServer};
+
+/// Serve every module at `addr`, until an error occurs.
+pub async fn serve(
+    addr: std::net::SocketAddr,
+    wasi_ephemeral_nn: impl WasiEphemeralNn,
+) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(WasiEphemeralNnServer::new(wasi_ephemeral_nn))
+        .serve(addr)
+        .await
+}
@@ -0,0 +1,465 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+@file:Suppress("NAME_SHADOWING", "UNREACHABLE_CODE", "unused")
+
+package wasi_ephemeral_nn
+
+import com.dylibso.chicory.runtime.HostFunction
+import com.dylibso.chicory.runtime.Memory
+import com.dylibso.chicory.wasm.types.FunctionType
+import com.dylibso.chicory.wasm.types.ValType
+import java.nio.ByteBuffer
+import java.nio.ByteOrder
+import java.nio.charset.CodingErrorAction
+
+/** An error returned by a function, such as a case of an errno enum. */
+class WitxException(val error: Any) : Exception("witx error: $error")
+
+/** The linear memory of a guest, in which values are little-endian. */
+class GuestMemory(val memory: Memory) {
+    /** A copy of `len` bytes at `offset`. */
+    fun readBytes(offset: Int, len: Int): ByteArray = memory.readBytes(offset, len)
+
+    fun writeBytes(offset: Int, bytes: ByteArray) = memory.write(offset, bytes)
+
+    private fun buffer(offset: Int, len: Int): ByteBuffer =
+        ByteBuffer.wrap(readBytes(offset, len)).order(ByteOrder.LITTLE_ENDIAN)
+
+    private fun put(offset: Int, len: Int, f: (ByteBuffer) -> Unit) {
+        val buffer = ByteBuffer.allocate(len).order(ByteOrder.LITTLE_ENDIAN)
+        f(buffer)
+        writeBytes(offset, buffer.array())
+    }
+
+    fun getS8(offset: Int): Byte = readBytes(offset, 1)[0]
+    fun getU8(offset: Int): UByte = getS8(offset).toUByte()
+    fun getS16(offset: Int): Short = buffer(offset, 2).short
+    fun getU16(offset: Int): UShort = getS16(offset).toUShort()
+    fun getS32(offset: Int): Int = buffer(offset, 4).int
+    fun getU32(offset: Int): UInt = getS32(offset).toUInt()
+    fun getS64(offset: Int): Long = buffer(offset, 8).long
+    fun getU64(offset: Int): ULong = getS64(offset).toULong()
+    fun getF32(offset: Int): Float = buffer(offset, 4).float
+    fun getF64(offset: Int): Double = buffer(offset, 8).double
+
+    fun setS8(offset: Int, value: Byte) = writeBytes(offset, byteArrayOf(value))
+    fun setU8(offset: Int, value: UByte) = setS8(offset, value.toByte())
+    fun setS16(offset: Int, value: Short) = put(offset, 2) { it.putShort(value) }
+    fun setU16(offset: Int, value: UShort) = setS16(offset, value.toShort())
+    fun setS32(offset: Int, value: Int) = put(offset, 4) { it.putInt(value) }
+    fun setU32(offset: Int, value: UInt) = setS32(offset, value.toInt())
+    fun setS64(offset: Int, value: Long) = put(offset, 8) { it.putLong(value) }
+    fun setU64(offset: Int, value: ULong) = setS64(offset, value.toLong())
+    fun setF32(offset: Int, value: Float) = put(offset, 4) { it.putFloat(value) }
+    fun setF64(offset: Int, value: Double) = put(offset, 8) { it.putDouble(value) }
+
+    fun readString(offset: Int, len: Int): String =
+        Charsets.UTF_8.newDecoder()
+            .onMalformedInput(CodingErrorAction.REPORT)
+            .decode(ByteBuffer.wrap(readBytes(offset, len)))
+            .toString()
+
+    /** `len` elements of `size` bytes at `offset`, each read by `read`. */
+    fun <T> readList(size: Int, offset: Int, len: Int, read: (Int) -> T): List<T> =
+        List(len) { read(offset + it * size) }
+}
+
+private fun invalid(value: Any, ty: String): Nothing =
+    throw IllegalArgumentException("$value is not a valid `$ty`")
+
+private fun listWrite(): Nothing =
+    throw IllegalArgumentException("lists can't be written to guest memory")
+
+/**
+ * The size of a graph buffer. This is equivalent to `$size` in `typenames.witx` but renamed since `typenames.witx` is
+ * not included here but is included in the overall ephemeral phase.
+ */
+typealias BufferSize = UInt
+
+internal fun readBufferSize(mem: GuestMemory, offset: Int): BufferSize =
+    mem.getU32(offset)
+
+internal fun writeBufferSize(mem: GuestMemory, offset: Int, value: BufferSize) {
+    mem.setU32(offset, value)
+}
+
+/**
+ * Error codes returned by functions in this API. This is prefixed to avoid conflicts with the `$errno` in
+ * `typenames.witx`.
+ */
+enum class NnErrno(val value: Int, val witxName: String) {
+    /**
+     * No error occurred.
+     */
+    SUCCESS(0, "success"),
+    /**
+     * Caller module passed an invalid argument.
+     */
+    INVALID_ARGUMENT(1, "invalid_argument"),
+    /**
+     * Caller module is missing a memory export.
+     */
+    MISSING_MEMORY(2, "missing_memory"),
+    /**
+     * Device or resource busy.
+     */
+    BUSY(3, "busy"),
+    ;
+
+    companion object {
+        fun fromValue(value: Int): NnErrno =
+            values().firstOrNull { it.value == value } ?: invalid(value, "nn_errno")
+    }
+}
+
+internal fun readNnErrno(mem: GuestMemory, offset: Int): NnErrno =
+    NnErrno.fromValue(mem.getU16(offset).toInt())
+
+internal fun writeNnErrno(mem: GuestMemory, offset: Int, value: NnErrno) {
+    mem.setU16(offset, value.value.toLong().toUShort())
+}
+
+/**
+ * The dimensions of a tensor.
+ *
+ * The array length matches the tensor rank and each element in the array
+ * describes the size of each dimension.
+ */
+typealias TensorDimensions = List<UInt>
+
+internal fun readTensorDimensions(mem: GuestMemory, offset: Int): TensorDimensions =
+    mem.readList(4, mem.getU32(offset).toInt(), mem.getU32(offset + 4).toInt()) { offset -> mem.getU32(offset) }
+
+internal fun writeTensorDimensions(mem: GuestMemory, offset: Int, value: TensorDimensions) {
+    listWrite()
+}
+
+/**
+ * The type of the elements in a tensor.
+ */
+enum class TensorType(val value: Int, val witxName: String) {
+    F16(0, "f16"),
+    F32(1, "f32"),
+    U8(2, "u8"),
+    I32(3, "i32"),
+    ;
+
+    companion object {
+        fun fromValue(value: Int): TensorType =
+            values().firstOrNull { it.value == value } ?: invalid(value, "tensor_type")
+    }
+}
+
+internal fun readTensorType(mem: GuestMemory, offset: Int): TensorType =
+    TensorType.fromValue(mem.getU8(offset).toInt())
+
+internal fun writeTensorType(mem: GuestMemory, offset: Int, value: TensorType) {
+    mem.setU8(offset, value.value.toLong().toUByte())
+}
+
+/**
+ * The tensor data
+ *
+ * Initially conceived as a sparse representation, each empty cell would be filled with zeroes and
+ * the array length must match the product of all of the dimensions and the number of bytes in the type (e.g. a 2x2
+ * tensor with 4-byte f32 elements would have a data array of length 16). Naturally, this representation requires
+ * some knowledge of how to lay out data in memory--e.g. using row-major ordering--and could perhaps be improved
+ * by future witx features (TODO).
+ */
+typealias TensorData = ByteArray
+
+internal fun readTensorData(mem: GuestMemory, offset: Int): TensorData =
+    mem.readBytes(mem.getU32(offset).toInt(), mem.getU32(offset + 4).toInt())
+
+internal fun writeTensorData(mem: GuestMemory, offset: Int, value: TensorData) {
+    listWrite()
+}
+
+/**
+ * A tensor.
+ */
+data class Tensor(
+    /**
+     * Describe the size of the tensor (e.g. 2x2x2x2 -> [2, 2, 2, 2]). To represent a tensor containing a single value,
+     * use `[1]` for the tensor dimensions.
+     */
+    val dimensions: TensorDimensions,
+    val type: TensorType,
+    /**
+     * Contains the tensor data.
+     */
+    val data: TensorData,
+)
+
+internal fun readTensor(mem: GuestMemory, offset: Int): Tensor =
+    Tensor(readTensorDimensions(mem, offset), readTensorType(mem, offset + 8), readTensorData(mem, offset + 12))
+
+internal fun writeTensor(mem: GuestMemory, offset: Int, value: Tensor) {
+    writeTensorDimensions(mem, offset, value.dimensions)
+    writeTensorType(mem, offset + 8, value.type)
+    writeTensorData(mem, offset + 12, value.data)
+}
+
+/**
+ * The graph initialization data. This consists of an array of buffers because implementing backends may encode their
+ * graph IR in parts (e.g. OpenVINO stores its IR and weights separately).
+ */
+typealias GraphBuilder = ByteArray
+
+internal fun readGraphBuilder(mem: GuestMemory, offset: Int): GraphBuilder =
+    mem.readBytes(mem.getU32(offset).toInt(), mem.getU32(offset + 4).toInt())
+
+internal fun writeGraphBuilder(mem: GuestMemory, offset: Int, value: GraphBuilder) {
+    listWrite()
+}
+
+typealias GraphBuilderArray = List<GraphBuilder>
+
+internal fun readGraphBuilderArray(mem: GuestMemory, offset: Int): GraphBuilderArray =
+    mem.readList(8, mem.getU32(offset).toInt(), mem.getU32(offset + 4).toInt()) { offset -> readGraphBuilder(mem, offset) }
+
+internal fun writeGraphBuilderArray(mem: GuestMemory, offset: Int, value: GraphBuilderArray) {
+    listWrite()
+}
+
+/**
+ * An execution graph for performing inference (i.e. a model).
+ */
+@JvmInline
+value class Graph(val handle: UInt)
+
+internal fun readGraph(mem: GuestMemory, offset: Int): Graph =
+    Graph(mem.getU32(offset))
+
+internal fun writeGraph(mem: GuestMemory, offset: Int, value: Graph) {
+    mem.setU32(offset, value.handle)
+}
+
+/**
+ * Describes the encoding of the graph. This allows the API to be implemented by various backends that encode (i.e.
+ * serialize) their graph IR differently.
+ */
+enum class GraphEncoding(val value: Int, val witxName: String) {
+    /**
+     * TODO document buffer order
+     */
+    OPENVINO(0, "openvino"),
+    TENSORFLOW(1, "tensorflow"),
+    ;
+
+    companion object {
+        fun fromValue(value: Int): GraphEncoding =
+            values().firstOrNull { it.value == value } ?: invalid(value, "graph_encoding")
+    }
+}
+
+internal fun readGraphEncoding(mem: GuestMemory, offset: Int): GraphEncoding =
+    GraphEncoding.fromValue(mem.getU8(offset).toInt())
+
+internal fun writeGraphEncoding(mem: GuestMemory, offset: Int, value: GraphEncoding) {
+    mem.setU8(offset, value.value.toLong().toUByte())
+}
+
+/**
+ * Define where the graph should be executed.
+ */
+enum class ExecutionTarget(val value: Int, val witxName: String) {
+    CPU(0, "cpu"),
+    GPU(1, "gpu"),
+    TPU(2, "tpu"),
+    ;
+
+    companion object {
+        fun fromValue(value: Int): ExecutionTarget =
+            values().firstOrNull { it.value == value } ?: invalid(value, "execution_target")
+    }
+}
+
+internal fun readExecutionTarget(mem: GuestMemory, offset: Int): ExecutionTarget =
+    ExecutionTarget.fromValue(mem.getU8(offset).toInt())
+
+internal fun writeExecutionTarget(mem: GuestMemory, offset: Int, value: ExecutionTarget) {
+    mem.setU8(offset, value.value.toLong().toUByte())
+}
+
+/**
+ * A $graph_execution_context allows for attaching inputs prior to calling `compute` on a graph and retrieving outputs after
+ * the computation has completed. TODO a handle may not be the right type but we want it to be opaque to users.
+ */
+@JvmInline
+value class GraphExecutionContext(val handle: UInt)
+
+internal fun readGraphExecutionContext(mem: GuestMemory, offset: Int): GraphExecutionContext =
+    GraphExecutionContext(mem.getU32(offset))
+
+internal fun writeGraphExecutionContext(mem: GuestMemory, offset: Int, value: GraphExecutionContext) {
+    mem.setU32(offset, value.handle)
+}
+
+interface WasiEphemeralNn {
+    /**
+     * Load an opaque sequence of bytes to use for inference.
+     *
+     * This allows runtime implementations to support multiple graph encoding formats. For unsupported graph encodings,
+     * return `errno::inval`.
+     */
+    fun load(mem: GuestMemory, builder: GraphBuilderArray, encoding: GraphEncoding, target: ExecutionTarget): Graph
+
+    /**
+     * TODO Functions like `describe_graph_inputs` and `describe_graph_outputs` (returning
+     * an array of `$tensor_description`s) might be useful for introspecting the graph but are not yet included here.
+     * Create an execution instance of a loaded graph.
+     * TODO this may need to accept flags that might affect the compilation or execution of the graph.
+     */
+    fun initExecutionContext(mem: GuestMemory, graph: Graph): GraphExecutionContext
+
+    /**
+     * Define the inputs to use for inference.
+     *
+     * This should return an $nn_errno (TODO define) if the input tensor does not match the expected dimensions and type.
+     */
+    fun setInput(mem: GuestMemory, context: GraphExecutionContext, index: UInt, tensor: Tensor)
+
+    /**
+     * Extract the outputs after inference.
+     *
+     * This should return an $nn_errno (TODO define) if the inference has not yet run.
+     */
+    fun getOutput(mem: GuestMemory, context: GraphExecutionContext, index: UInt, outBuffer: UInt, outBufferMaxSize: BufferSize): BufferSize
+
+    /**
+     * Compute the inference on the given inputs (see `set_input`).
+     *
+     * This should return an $nn_errno (TODO define) if the inputs are not all defined.
+     */
+    fun compute(mem: GuestMemory, context: GraphExecutionContext)
+}
+
+/** Host functions of the `wasi_ephemeral_nn` module, calling `impl`. */
+fun wasiEphemeralNnFunctions(impl: WasiEphemeralNn): List<HostFunction> = listOf(
+    HostFunction(
+        "wasi_ephemeral_nn",
+        "load",
+        FunctionType.of(listOf(ValType.I32, ValType.I32, ValType.I32, ValType.I32, ValType.I32), listOf(ValType.I32)),
+    ) { instance, args ->
+        val mem = GuestMemory(instance.memory())
+        val param0 = mem.readList(8, args[0].toInt(), args[1].toInt()) { offset -> readGraphBuilder(mem, offset) }
+        val param1 = GraphEncoding.fromValue(args[2].toInt())
+        val param2 = ExecutionTarget.fromValue(args[3].toInt())
+        var ret3: Graph? = null
+        var ret3Err: NnErrno? = null
+        try {
+            ret3 = impl.load(mem, param0, param1, param2)
+        } catch (e: WitxException) {
+            ret3Err = e.error as NnErrno
+        }
+        val result6: Long
+        if (ret3Err == null) {
+            val payload4 = ret3!!
+            writeGraph(mem, args[4].toInt(), payload4)
+            result6 = 0L
+        } else {
+            val payload5 = ret3Err
+            result6 = payload5.value.toLong()
+        }
+        longArrayOf(result6)
+    },
+    HostFunction(
+        "wasi_ephemeral_nn",
+        "init_execution_context",
+        FunctionType.of(listOf(ValType.I32, ValType.I32), listOf(ValType.I32)),
+    ) { instance, args ->
+        val mem = GuestMemory(instance.memory())
+        val param0 = Graph(args[0].toUInt())
+        var ret1: GraphExecutionContext? = null
+        var ret1Err: NnErrno? = null
+        try {
+            ret1 = impl.initExecutionContext(mem, param0)
+        } catch (e: WitxException) {
+            ret1Err = e.error as NnErrno
+        }
+        val result4: Long
+        if (ret1Err == null) {
+            val payload2 = ret1!!
+            writeGraphExecutionContext(mem, args[1].toInt(), payload2)
+            result4 = 0L
+        } else {
+            val payload3 = ret1Err
+            result4 = payload3.value.toLong()
+        }
+        longArrayOf(result4)
+    },
+    HostFunction(
+        "wasi_ephemeral_nn",
+        "set_input",
+        FunctionType.of(listOf(ValType.I32, ValType.I32, ValType.I32), listOf(ValType.I32)),
+    ) { instance, args ->
+        val mem = GuestMemory(instance.memory())
+        val param0 = GraphExecutionContext(args[0].toUInt())
+        val param1 = args[1].toUInt()
+        val param2 = readTensor(mem, args[2].toInt())
+        var ret3Err: NnErrno? = null
+        try {
+            impl.setInput(mem, param0, param1, param2)
+        } catch (e: WitxException) {
+            ret3Err = e.error as NnErrno
+        }
+        val result5: Long
+        if (ret3Err == null) {
+            result5 = 0L
+        } else {
+            val payload4 = ret3Err
+            result5 = payload4.value.toLong()
+        }
+        longArrayOf(result5)
+    },
+    HostFunction(
+        "wasi_ephemeral_nn",
+        "get_output",
+        FunctionType.of(listOf(ValType.I32, ValType.I32, ValType.I32, ValType.I32, ValType.I32), listOf(ValType.I32)),
+    ) { instance, args ->
+        val mem = GuestMemory(instance.memory())
+        val param0 = GraphExecutionContext(args[0].toUInt())
+        val param1 = args[1].toUInt()
+        val param2 = args[2].toUInt()
+        val param3 = args[3].toUInt()
+        var ret4: BufferSize? = null
+        var ret4Err: NnErrno? = null
+        try {
+            ret4 = impl.getOutput(mem, param0, param1, param2, param3)
+        } catch (e: WitxException) {
+            ret4Err = e.error as NnErrno
+        }
+        val result7: Long
+        if (ret4Err == null) {
+            val payload5 = ret4!!
+            writeBufferSize(mem, args[4].toInt(), payload5)
+            result7 = 0L
+        } else {
+            val payload6 = ret4Err
+            result7 = payload6.value.toLong()
+        }
+        longArrayOf(result7)
+    },
+    HostFunction(
+        "wasi_ephemeral_nn",
+        "compute",
+        FunctionType.of(listOf(ValType.I32), listOf(ValType.I32)),
+    ) { instance, args ->
+        val mem = GuestMemory(instance.memory())
+        val param0 = GraphExecutionContext(args[0].toUInt())
+        var ret1Err: NnErrno? = null
+        try {
+            impl.compute(mem, param0)
+        } catch (e: WitxException) {
+            ret1Err = e.error as NnErrno
+        }
+        val result3: Long
+        if (ret1Err == null) {
+            result3 = 0L
+        } else {
+            val payload2 = ret1Err
+            result3 = payload2.value.toLong()
+        }
+        longArrayOf(result3)
+    },
+)
@@ -0,0 +1,129 @@
+//! An ONNX Runtime backend, generated by witx as a starting point.
+//!
+//! `bindings.rs` is generated from the spec and shouldn't be edited. Search
+//! this file for `TODO(onnx)` for what's left to implement.
+
+#![allow(unused_variables)]
+
+mod bindings;
+
+pub use bindings::*;
+use bindings::wasi_ephemeral_nn::WasiEphemeralNn;
+
+/// A graph loaded by [`OnnxRuntime`].
+#[derive(Debug)]
+pub struct LoadedGraph {
+    /// The model, in the ONNX format.
+    pub model: Vec<u8>,
+    /// The ONNX Runtime device to run the graph on.
+    pub device: &'static str,
+    // TODO(onnx): the `OrtSession`, released with `ReleaseSession` when dropped.
+}
+
+/// An execution context created by [`OnnxRuntime`].
+#[derive(Debug)]
+pub struct ExecutionContext {
+    pub graph: Graph,
+    /// Inputs set so far, by index.
+    pub inputs: Vec<(u32, Tensor)>,
+    /// The data of each output of the last computation.
+    pub outputs: Vec<Vec<u8>>,
+    // TODO(onnx): the `OrtRunOptions`, released with `ReleaseRunOptions` when
+    // dropped.
+}
+
+/// Runs graphs with ONNX Runtime.
+#[derive(Debug, Default)]
+pub struct OnnxRuntime {
+    pub graphs: Vec<LoadedGraph>,
+    pub contexts: Vec<ExecutionContext>,
+}
+
+/// The ONNX Runtime device for `target`, if it has one.
+pub fn device(target: ExecutionTarget) -> Option<&'static str> {
+    match target {
+        ExecutionTarget::Cpu => Some("CPUExecutionProvider"),
+        ExecutionTarget::Gpu => Some("CUDAExecutionProvider"),
+        ExecutionTarget::Tpu => None,
+    }
+}
+
+/// The element types of ONNX Runtime's tensors.
+pub type ONNXTensorElementDataType = i32;
+pub const ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT: ONNXTensorElementDataType = 1;
+pub const ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT8: ONNXTensorElementDataType = 2;
+pub const ONNX_TENSOR_ELEMENT_DATA_TYPE_INT32: ONNXTensorElementDataType = 6;
+pub const ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT16: ONNXTensorElementDataType = 10;
+pub const ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT4: ONNXTensorElementDataType = 21;
+
+/// The ONNX Runtime element type of `ty`, if it has one.
+pub fn element_type(ty: TensorType) -> Option<ONNXTensorElementDataType> {
+    match ty {
+        TensorType::F16 => Some(ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT16),
+        TensorType::F32 => Some(ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT),
+        TensorType::U8 => Some(ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT8),
+        TensorType::I32 => Some(ONNX_TENSOR_ELEMENT_DATA_TYPE_INT32),
+    }
+}
+
+/// The `TensorType` of the ONNX Runtime element type `element_type`, if it has one.
+pub fn tensor_type(element_type: ONNXTensorElementDataType) -> Option<TensorType> {
+    match element_type {
+        ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT16 => Some(TensorType::F16),
+        ONNX_TENSOR_ELEMENT_DATA_TYPE_FLOAT => Some(TensorType::F32),
+        ONNX_TENSOR_ELEMENT_DATA_TYPE_UINT8 => Some(TensorType::U8),
+        ONNX_TENSOR_ELEMENT_DATA_TYPE_INT32 => Some(TensorType::I32),
+        _ => None,
+    }
+}
+
+impl WasiEphemeralNn for OnnxRuntime {
+    fn load(&mut self, mem: &mut GuestMemory<'_>, builder: GraphBuilderArray, encoding: GraphEncoding, target: ExecutionTarget) -> Result<Graph, NnErrno> {
+        todo!("implement `load` with ONNX Runtime")
+    }
+
+    fn init_execution_context(&mut self, mem: &mut GuestMemory<'_>, graph: Graph) -> Result<GraphExecutionContext, NnErrno> {
+        let loaded = self.graphs.get(graph as usize).ok_or(NnErrno::InvalidArgument)?;
+        // TODO(onnx): create `OrtRunOptions` for running the session with
+        // `CreateRunOptions`.
+        self.contexts.push(ExecutionContext {
+            graph,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        });
+        Ok((self.contexts.len() - 1) as GraphExecutionContext)
+    }
+
+    fn set_input(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext, index: u32, tensor: Tensor) -> Result<(), NnErrno> {
+        let context = self.contexts.get_mut(context as usize).ok_or(NnErrno::InvalidArgument)?;
+        let valid = tensor.r#type.is_valid_buffer(&tensor.dimensions, tensor.data.len());
+        if !valid || element_type(tensor.r#type).is_none() {
+            return Err(NnErrno::InvalidArgument);
+        }
+        context.inputs.retain(|(i, _)| *i != index);
+        context.inputs.push((index, tensor));
+        Ok(())
+    }
+
+    fn get_output(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext, index: u32, out_buffer: GuestPtr<u8>, out_buffer_max_size: BufferSize) -> Result<BufferSize, NnErrno> {
+        let context = self.contexts.get(context as usize).ok_or(NnErrno::InvalidArgument)?;
+        let data = context.outputs.get(index as usize).ok_or(NnErrno::InvalidArgument)?;
+        if data.len() > out_buffer_max_size as usize {
+            return Err(NnErrno::InvalidArgument);
+        }
+        mem.bytes_mut(out_buffer.offset(), data.len() as u32)
+            .map_err(|_| NnErrno::InvalidArgument)?
+            .copy_from_slice(data);
+        Ok(data.len() as BufferSize)
+    }
+
+    fn compute(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext) -> Result<(), NnErrno> {
+        let context = self.contexts.get_mut(context as usize).ok_or(NnErrno::InvalidArgument)?;
+        let loaded = &self.graphs[context.graph as usize];
+        // TODO(onnx): create an `OrtValue` for each input with
+        // `CreateTensorWithDataAsOrtValue` and its element type, run the
+        // session with `Run`, and copy the data of each output, from
+        // `GetTensorMutableData`, to `context.outputs`.
+        todo!("compute with ONNX Runtime")
+    }
+}
@@ -0,0 +1,133 @@
+//! An OpenVINO backend, generated by witx as a starting point.
+//!
+//! `bindings.rs` is generated from the spec and shouldn't be edited. Search
+//! this file for `TODO(openvino)` for what's left to implement.
+
+#![allow(unused_variables)]
+
+mod bindings;
+
+pub use bindings::*;
+use bindings::wasi_ephemeral_nn::WasiEphemeralNn;
+
+/// A graph loaded by [`OpenVino`].
+#[derive(Debug)]
+pub struct LoadedGraph {
+    /// The model's IR, in OpenVINO's XML format.
+    pub xml: Vec<u8>,
+    /// The model's weights, as the IR's binary data.
+    pub weights: Vec<u8>,
+    /// The OpenVINO device to run the graph on.
+    pub device: &'static str,
+    // TODO(openvino): the compiled model.
+}
+
+/// An execution context created by [`OpenVino`].
+#[derive(Debug)]
+pub struct ExecutionContext {
+    pub graph: Graph,
+    /// Inputs set so far, by index.
+    pub inputs: Vec<(u32, Tensor)>,
+    /// The data of each output of the last computation.
+    pub outputs: Vec<Vec<u8>>,
+    // TODO(openvino): the infer request.
+}
+
+/// Runs graphs with OpenVINO.
+#[derive(Debug, Default)]
+pub struct OpenVino {
+    pub graphs: Vec<LoadedGraph>,
+    pub contexts: Vec<ExecutionContext>,
+}
+
+/// The OpenVINO device for `target`, if it has one.
+pub fn device(target: ExecutionTarget) -> Option<&'static str> {
+    match target {
+        ExecutionTarget::Cpu => Some("CPU"),
+        ExecutionTarget::Gpu => Some("GPU"),
+        ExecutionTarget::Tpu => Some("MYRIAD"),
+    }
+}
+
+/// The OpenVINO element type of `ty`, if it has one.
+pub fn element_type(ty: TensorType) -> Option<&'static str> {
+    match ty {
+        TensorType::F16 => Some("f16"),
+        TensorType::F32 => Some("f32"),
+        TensorType::U8 => Some("u8"),
+        TensorType::I32 => Some("i32"),
+    }
+}
+
+/// The `TensorType` of the OpenVINO element type `element_type`, if it has one.
+pub fn tensor_type(element_type: &str) -> Option<TensorType> {
+    match element_type {
+        "f16" => Some(TensorType::F16),
+        "f32" => Some(TensorType::F32),
+        "u8" => Some(TensorType::U8),
+        "i32" => Some(TensorType::I32),
+        _ => None,
+    }
+}
+
+impl WasiEphemeralNn for OpenVino {
+    fn load(&mut self, mem: &mut GuestMemory<'_>, builder: GraphBuilderArray, encoding: GraphEncoding, target: ExecutionTarget) -> Result<Graph, NnErrno> {
+        if encoding != GraphEncoding::Openvino {
+            return Err(NnErrno::InvalidArgument);
+        }
+        let (xml, weights) = match builder.as_slice() {
+            [xml, weights] => (xml.clone(), weights.clone()),
+            _ => return Err(NnErrno::InvalidArgument),
+        };
+        let device = device(target).ok_or(NnErrno::InvalidArgument)?;
+        // TODO(openvino): read the model with `ov_core_read_model_from_memory_buffer`
+        // and compile it for `device` with `ov_core_compile_model`.
+        self.graphs.push(LoadedGraph { xml, weights, device });
+        Ok((self.graphs.len() - 1) as Graph)
+    }
+
+    fn init_execution_context(&mut self, mem: &mut GuestMemory<'_>, graph: Graph) -> Result<GraphExecutionContext, NnErrno> {
+        let loaded = self.graphs.get(graph as usize).ok_or(NnErrno::InvalidArgument)?;
+        // TODO(openvino): create an infer request for the compiled model with
+        // `ov_compiled_model_create_infer_request`.
+        self.contexts.push(ExecutionContext {
+            graph,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        });
+        Ok((self.contexts.len() - 1) as GraphExecutionContext)
+    }
+
+    fn set_input(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext, index: u32, tensor: Tensor) -> Result<(), NnErrno> {
+        let context = self.contexts.get_mut(context as usize).ok_or(NnErrno::InvalidArgument)?;
+        let valid = tensor.r#type.is_valid_buffer(&tensor.dimensions, tensor.data.len());
+        if !valid || element_type(tensor.r#type).is_none() {
+            return Err(NnErrno::InvalidArgument);
+        }
+        context.inputs.retain(|(i, _)| *i != index);
+        context.inputs.push((index, tensor));
+        Ok(())
+    }
+
+    fn get_output(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext, index: u32, out_buffer: GuestPtr<u8>, out_buffer_max_size: BufferSize) -> Result<BufferSize, NnErrno> {
+        let context = self.contexts.get(context as usize).ok_or(NnErrno::InvalidArgument)?;
+        let data = context.outputs.get(index as usize).ok_or(NnErrno::InvalidArgument)?;
+        if data.len() > out_buffer_max_size as usize {
+            return Err(NnErrno::InvalidArgument);
+        }
+        mem.bytes_mut(out_buffer.offset(), data.len() as u32)
+            .map_err(|_| NnErrno::InvalidArgument)?
+            .copy_from_slice(data);
+        Ok(data.len() as BufferSize)
+    }
+
+    fn compute(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext) -> Result<(), NnErrno> {
+        let context = self.contexts.get_mut(context as usize).ok_or(NnErrno::InvalidArgument)?;
+        let loaded = &self.graphs[context.graph as usize];
+        // TODO(openvino): set each input as an `ov_tensor_t` of its element type
+        // with `ov_infer_request_set_input_tensor_by_index`, run
+        // `ov_infer_request_infer`, and copy the data of each output
+        // tensor to `context.outputs`.
+        todo!("compute with OpenVINO")
+    }
+}
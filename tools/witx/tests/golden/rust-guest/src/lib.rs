@@ -0,0 +1,634 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+/// The exact ABI of the imports: types with the memory layout of their
+/// witx definitions, and `unsafe` extern functions taking core wasm values.
+pub mod raw {
+    /// The size of a graph buffer. This is equivalent to `$size` in `typenames.witx` but renamed since `typenames.witx` is
+    /// not included here but is included in the overall ephemeral phase.
+    pub type BufferSize = u32;
+
+    /// Error codes returned by functions in this API. This is prefixed to avoid conflicts with the `$errno` in
+    /// `typenames.witx`.
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct NnErrno(pub(crate) u16);
+    /// No error occurred.
+    pub const NN_ERRNO_SUCCESS: NnErrno = NnErrno(0);
+    /// Caller module passed an invalid argument.
+    pub const NN_ERRNO_INVALID_ARGUMENT: NnErrno = NnErrno(1);
+    /// Caller module is missing a memory export.
+    pub const NN_ERRNO_MISSING_MEMORY: NnErrno = NnErrno(2);
+    /// Device or resource busy.
+    pub const NN_ERRNO_BUSY: NnErrno = NnErrno(3);
+    impl NnErrno {
+        /// Every value of the enum, in order.
+        pub const ALL: [NnErrno; 4] = [
+            NN_ERRNO_SUCCESS,
+            NN_ERRNO_INVALID_ARGUMENT,
+            NN_ERRNO_MISSING_MEMORY,
+            NN_ERRNO_BUSY,
+        ];
+        pub const fn raw(&self) -> u16 {
+            self.0
+        }
+        /// The witx name of the value, or `unknown` if the bindings don't know it.
+        pub const fn as_str(&self) -> &'static str {
+            match self.0 {
+                0 => "success",
+                1 => "invalid_argument",
+                2 => "missing_memory",
+                3 => "busy",
+                _ => "unknown",
+            }
+        }
+        /// The value named `name` in witx.
+        #[allow(clippy::should_implement_trait)]
+        pub fn from_str(name: &str) -> Option<Self> {
+            match name {
+                "success" => Some(NN_ERRNO_SUCCESS),
+                "invalid_argument" => Some(NN_ERRNO_INVALID_ARGUMENT),
+                "missing_memory" => Some(NN_ERRNO_MISSING_MEMORY),
+                "busy" => Some(NN_ERRNO_BUSY),
+                _ => None,
+            }
+        }
+    }
+    impl core::fmt::Debug for NnErrno {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            let name = match self.0 {
+                0 => "SUCCESS",
+                1 => "INVALID_ARGUMENT",
+                2 => "MISSING_MEMORY",
+                3 => "BUSY",
+                _ => "unknown",
+            };
+            f.debug_struct("NnErrno")
+                .field("code", &self.0)
+                .field("name", &name)
+                .finish()
+        }
+    }
+
+    /// The dimensions of a tensor.
+    ///
+    /// The array length matches the tensor rank and each element in the array
+    /// describes the size of each dimension.
+    pub type TensorDimensions<'a> = &'a [u32];
+
+    /// The type of the elements in a tensor.
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct TensorType(pub(crate) u8);
+    pub const TENSOR_TYPE_F16: TensorType = TensorType(0);
+    pub const TENSOR_TYPE_F32: TensorType = TensorType(1);
+    pub const TENSOR_TYPE_U8: TensorType = TensorType(2);
+    pub const TENSOR_TYPE_I32: TensorType = TensorType(3);
+    impl TensorType {
+        /// Every value of the enum, in order.
+        pub const ALL: [TensorType; 4] = [
+            TENSOR_TYPE_F16,
+            TENSOR_TYPE_F32,
+            TENSOR_TYPE_U8,
+            TENSOR_TYPE_I32,
+        ];
+        pub const fn raw(&self) -> u8 {
+            self.0
+        }
+        /// The witx name of the value, or `unknown` if the bindings don't know it.
+        pub const fn as_str(&self) -> &'static str {
+            match self.0 {
+                0 => "f16",
+                1 => "f32",
+                2 => "u8",
+                3 => "i32",
+                _ => "unknown",
+            }
+        }
+        /// The value named `name` in witx.
+        #[allow(clippy::should_implement_trait)]
+        pub fn from_str(name: &str) -> Option<Self> {
+            match name {
+                "f16" => Some(TENSOR_TYPE_F16),
+                "f32" => Some(TENSOR_TYPE_F32),
+                "u8" => Some(TENSOR_TYPE_U8),
+                "i32" => Some(TENSOR_TYPE_I32),
+                _ => None,
+            }
+        }
+    }
+    impl core::fmt::Debug for TensorType {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            let name = match self.0 {
+                0 => "F16",
+                1 => "F32",
+                2 => "U8",
+                3 => "I32",
+                _ => "unknown",
+            };
+            f.debug_struct("TensorType")
+                .field("code", &self.0)
+                .field("name", &name)
+                .finish()
+        }
+    }
+    impl TensorType {
+        /// Size of an element in bits, or `None` if the type isn't known.
+        pub fn element_bits(&self) -> Option<u32> {
+            match self.raw() {
+                0 => Some(16),
+                1 => Some(32),
+                2 => Some(8),
+                3 => Some(32),
+                _ => None,
+            }
+        }
+        /// Bytes of data in a tensor of this type with `dimensions`, or `None`
+        /// if the type isn't known or the size overflows. Packed elements are
+        /// padded to a whole byte.
+        pub fn buffer_size(&self, dimensions: &[u32]) -> Option<usize> {
+            let mut bits = self.element_bits()? as usize;
+            for d in dimensions {
+                bits = bits.checked_mul(*d as usize)?;
+            }
+            Some(bits / 8 + (bits % 8 != 0) as usize)
+        }
+        /// Whether `len` bytes is the size of the data of a tensor of this type
+        /// with `dimensions`.
+        pub fn is_valid_buffer(&self, dimensions: &[u32], len: usize) -> bool {
+            self.buffer_size(dimensions) == Some(len)
+        }
+    }
+    /// Conversions of tensor data between the element types of `TensorType`.
+    /// Data is little-endian, as in tensors. Functions converting data write it
+    /// to `out`, and return the number of bytes written, or `None` if `data`
+    /// isn't a whole number of elements or `out` is too small for them.
+    pub mod tensor_convert {
+        fn map<const N: usize, const M: usize>(
+            data: &[u8],
+            out: &mut [u8],
+            f: impl Fn([u8; N]) -> [u8; M],
+        ) -> Option<usize> {
+            let len = data.len() / N * M;
+            if data.len() % N != 0 || out.len() < len {
+                return None;
+            }
+            for (from, to) in data.chunks_exact(N).zip(out.chunks_exact_mut(M)) {
+                let mut bytes = [0; N];
+                bytes.copy_from_slice(from);
+                to.copy_from_slice(&f(bytes));
+            }
+            Some(len)
+        }
+
+        /// The bits of the `f16` nearest to `value`. Ties round to even, values
+        /// too large for an `f16` become infinities, and NaNs stay NaNs.
+        pub fn f32_to_f16(value: f32) -> u16 {
+            let bits = value.to_bits();
+            let sign = ((bits >> 16) & 0x8000) as u16;
+            let exp = ((bits >> 23) & 0xff) as i32;
+            let man = bits & 0x7f_ffff;
+            if exp == 0xff {
+                let nan = if man != 0 { 0x200 | (man >> 13) as u16 } else { 0 };
+                return sign | 0x7c00 | nan;
+            }
+            let exp = exp - 127 + 15;
+            if exp >= 0x1f {
+                return sign | 0x7c00;
+            }
+            // Subnormals keep fewer bits of the mantissa, with its implicit one.
+            let (man, shift, exp) = if exp <= 0 {
+                if exp < -10 {
+                    return sign;
+                }
+                (man | 0x80_0000, (14 - exp) as u32, 0)
+            } else {
+                (man, 13, exp as u32)
+            };
+            let kept = (exp << 10) | (man >> shift);
+            let dropped = man & ((1 << shift) - 1);
+            let half = 1 << (shift - 1);
+            // Rounding up may carry into the exponent, up to infinity.
+            let up = dropped > half || (dropped == half && kept & 1 == 1);
+            sign | (kept + up as u32) as u16
+        }
+
+        /// The value of the `f16` with `bits`, which is exact.
+        pub fn f16_to_f32(bits: u16) -> f32 {
+            let sign = u32::from(bits >> 15) << 31;
+            let exp = u32::from((bits >> 10) & 0x1f);
+            let man = u32::from(bits & 0x3ff);
+            let bits = match (exp, man) {
+                (0, 0) => sign,
+                // Subnormal: normalize the mantissa.
+                (0, _) => {
+                    let shift = man.leading_zeros() - 21;
+                    sign | ((113 - shift) << 23) | (((man << shift) & 0x3ff) << 13)
+                }
+                (0x1f, _) => sign | 0x7f80_0000 | (man << 13),
+                _ => sign | ((exp + 112) << 23) | (man << 13),
+            };
+            f32::from_bits(bits)
+        }
+
+        /// `f32` data as `f16` data.
+        pub fn f32_to_f16_data(data: &[u8], out: &mut [u8]) -> Option<usize> {
+            map(data, out, |b: [u8; 4]| f32_to_f16(f32::from_le_bytes(b)).to_le_bytes())
+        }
+
+        /// `f16` data as `f32` data.
+        pub fn f16_to_f32_data(data: &[u8], out: &mut [u8]) -> Option<usize> {
+            map(data, out, |b: [u8; 2]| f16_to_f32(u16::from_le_bytes(b)).to_le_bytes())
+        }
+
+        /// `value` quantized to a `u8` with `scale` and `zero_point`: the nearest
+        /// integer to `value / scale`, plus `zero_point`, saturated. Ties round
+        /// away from zero, and NaNs become `zero_point`.
+        pub fn quantize_u8(value: f32, scale: f32, zero_point: u8) -> u8 {
+            let x = value / scale;
+            // `as` truncates and saturates, and the fraction left is exact.
+            let trunc = x as i32;
+            let frac = x - trunc as f32;
+            let rounded = if frac >= 0.5 {
+                trunc.saturating_add(1)
+            } else if frac <= -0.5 {
+                trunc.saturating_sub(1)
+            } else {
+                trunc
+            };
+            rounded.saturating_add(i32::from(zero_point)).clamp(0, 255) as u8
+        }
+
+        /// The value `q` was quantized from with `scale` and `zero_point`.
+        pub fn dequantize_u8(q: u8, scale: f32, zero_point: u8) -> f32 {
+            (i32::from(q) - i32::from(zero_point)) as f32 * scale
+        }
+
+        /// `f32` data quantized to `u8` data, as by `quantize_u8`.
+        pub fn quantize_u8_data(data: &[u8], scale: f32, zero_point: u8, out: &mut [u8]) -> Option<usize> {
+            map(data, out, |b: [u8; 4]| [quantize_u8(f32::from_le_bytes(b), scale, zero_point)])
+        }
+
+        /// `u8` data dequantized to `f32` data, as by `dequantize_u8`.
+        pub fn dequantize_u8_data(data: &[u8], scale: f32, zero_point: u8, out: &mut [u8]) -> Option<usize> {
+            map(data, out, |b: [u8; 1]| dequantize_u8(b[0], scale, zero_point).to_le_bytes())
+        }
+    }
+
+    /// The tensor data
+    ///
+    /// Initially conceived as a sparse representation, each empty cell would be filled with zeroes and
+    /// the array length must match the product of all of the dimensions and the number of bytes in the type (e.g. a 2x2
+    /// tensor with 4-byte f32 elements would have a data array of length 16). Naturally, this representation requires
+    /// some knowledge of how to lay out data in memory--e.g. using row-major ordering--and could perhaps be improved
+    /// by future witx features (TODO).
+    pub type TensorData<'a> = &'a [u8];
+
+    /// A tensor.
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug)]
+    pub struct Tensor<'a> {
+        /// Describe the size of the tensor (e.g. 2x2x2x2 -> [2, 2, 2, 2]). To represent a tensor containing a single value,
+        /// use `[1]` for the tensor dimensions.
+        pub dimensions: TensorDimensions<'a>,
+        pub r#type: TensorType,
+        /// Contains the tensor data.
+        pub data: TensorData<'a>,
+    }
+
+    /// The graph initialization data. This consists of an array of buffers because implementing backends may encode their
+    /// graph IR in parts (e.g. OpenVINO stores its IR and weights separately).
+    pub type GraphBuilder<'a> = &'a [u8];
+
+    pub type GraphBuilderArray<'a> = &'a [GraphBuilder<'a>];
+
+    /// An execution graph for performing inference (i.e. a model).
+    pub type Graph = u32;
+
+    /// Describes the encoding of the graph. This allows the API to be implemented by various backends that encode (i.e.
+    /// serialize) their graph IR differently.
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct GraphEncoding(pub(crate) u8);
+    /// TODO document buffer order
+    pub const GRAPH_ENCODING_OPENVINO: GraphEncoding = GraphEncoding(0);
+    pub const GRAPH_ENCODING_TENSORFLOW: GraphEncoding = GraphEncoding(1);
+    impl GraphEncoding {
+        /// Every value of the enum, in order.
+        pub const ALL: [GraphEncoding; 2] = [
+            GRAPH_ENCODING_OPENVINO,
+            GRAPH_ENCODING_TENSORFLOW,
+        ];
+        pub const fn raw(&self) -> u8 {
+            self.0
+        }
+        /// The witx name of the value, or `unknown` if the bindings don't know it.
+        pub const fn as_str(&self) -> &'static str {
+            match self.0 {
+                0 => "openvino",
+                1 => "tensorflow",
+                _ => "unknown",
+            }
+        }
+        /// The value named `name` in witx.
+        #[allow(clippy::should_implement_trait)]
+        pub fn from_str(name: &str) -> Option<Self> {
+            match name {
+                "openvino" => Some(GRAPH_ENCODING_OPENVINO),
+                "tensorflow" => Some(GRAPH_ENCODING_TENSORFLOW),
+                _ => None,
+            }
+        }
+    }
+    impl core::fmt::Debug for GraphEncoding {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            let name = match self.0 {
+                0 => "OPENVINO",
+                1 => "TENSORFLOW",
+                _ => "unknown",
+            };
+            f.debug_struct("GraphEncoding")
+                .field("code", &self.0)
+                .field("name", &name)
+                .finish()
+        }
+    }
+
+    /// Define where the graph should be executed.
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+    pub struct ExecutionTarget(pub(crate) u8);
+    pub const EXECUTION_TARGET_CPU: ExecutionTarget = ExecutionTarget(0);
+    pub const EXECUTION_TARGET_GPU: ExecutionTarget = ExecutionTarget(1);
+    pub const EXECUTION_TARGET_TPU: ExecutionTarget = ExecutionTarget(2);
+    impl ExecutionTarget {
+        /// Every value of the enum, in order.
+        pub const ALL: [ExecutionTarget; 3] = [
+            EXECUTION_TARGET_CPU,
+            EXECUTION_TARGET_GPU,
+            EXECUTION_TARGET_TPU,
+        ];
+        pub const fn raw(&self) -> u8 {
+            self.0
+        }
+        /// The witx name of the value, or `unknown` if the bindings don't know it.
+        pub const fn as_str(&self) -> &'static str {
+            match self.0 {
+                0 => "cpu",
+                1 => "gpu",
+                2 => "tpu",
+                _ => "unknown",
+            }
+        }
+        /// The value named `name` in witx.
+        #[allow(clippy::should_implement_trait)]
+        pub fn from_str(name: &str) -> Option<Self> {
+            match name {
+                "cpu" => Some(EXECUTION_TARGET_CPU),
+                "gpu" => Some(EXECUTION_TARGET_GPU),
+                "tpu" => Some(EXECUTION_TARGET_TPU),
+                _ => None,
+            }
+        }
+    }
+    impl core::fmt::Debug for ExecutionTarget {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            let name = match self.0 {
+                0 => "CPU",
+                1 => "GPU",
+                2 => "TPU",
+                _ => "unknown",
+            };
+            f.debug_struct("ExecutionTarget")
+                .field("code", &self.0)
+                .field("name", &name)
+                .finish()
+        }
+    }
+
+    /// A $graph_execution_context allows for attaching inputs prior to calling `compute` on a graph and retrieving outputs after
+    /// the computation has completed. TODO a handle may not be the right type but we want it to be opaque to users.
+    pub type GraphExecutionContext = u32;
+
+    #[cfg(target_arch = "wasm32")]
+    const _: () = {
+        use core::mem::{align_of, size_of};
+        assert!(size_of::<BufferSize>() == 4 && align_of::<BufferSize>() == 4);
+        assert!(size_of::<NnErrno>() == 2 && align_of::<NnErrno>() == 2);
+        assert!(size_of::<TensorDimensions<'static>>() == 8 && align_of::<TensorDimensions<'static>>() == 4);
+        assert!(size_of::<TensorType>() == 1 && align_of::<TensorType>() == 1);
+        assert!(size_of::<TensorData<'static>>() == 8 && align_of::<TensorData<'static>>() == 4);
+        assert!(size_of::<Tensor<'static>>() == 20 && align_of::<Tensor<'static>>() == 4);
+        assert!(size_of::<GraphBuilder<'static>>() == 8 && align_of::<GraphBuilder<'static>>() == 4);
+        assert!(size_of::<GraphBuilderArray<'static>>() == 8 && align_of::<GraphBuilderArray<'static>>() == 4);
+        assert!(size_of::<Graph>() == 4 && align_of::<Graph>() == 4);
+        assert!(size_of::<GraphEncoding>() == 1 && align_of::<GraphEncoding>() == 1);
+        assert!(size_of::<ExecutionTarget>() == 1 && align_of::<ExecutionTarget>() == 1);
+        assert!(size_of::<GraphExecutionContext>() == 4 && align_of::<GraphExecutionContext>() == 4);
+    };
+
+    pub mod wasi_ephemeral_nn {
+        #[allow(unused_imports)]
+        use super::*;
+        #[link(wasm_import_module = "wasi_ephemeral_nn")]
+        extern "C" {
+            /// Load an opaque sequence of bytes to use for inference.
+            ///
+            /// This allows runtime implementations to support multiple graph encoding formats. For unsupported graph encodings,
+            /// return `errno::inval`.
+            pub fn load(builder: i32, builder_len: i32, encoding: i32, target: i32, retptr0: i32) -> i32;
+            /// TODO Functions like `describe_graph_inputs` and `describe_graph_outputs` (returning
+            /// an array of `$tensor_description`s) might be useful for introspecting the graph but are not yet included here.
+            /// Create an execution instance of a loaded graph.
+            /// TODO this may need to accept flags that might affect the compilation or execution of the graph.
+            pub fn init_execution_context(graph: i32, retptr0: i32) -> i32;
+            /// Define the inputs to use for inference.
+            ///
+            /// This should return an $nn_errno (TODO define) if the input tensor does not match the expected dimensions and type.
+            pub fn set_input(context: i32, index: i32, tensor: i32) -> i32;
+            /// Extract the outputs after inference.
+            ///
+            /// This should return an $nn_errno (TODO define) if the inference has not yet run.
+            pub fn get_output(context: i32, index: i32, out_buffer: i32, out_buffer_max_size: i32, retptr0: i32) -> i32;
+            /// Compute the inference on the given inputs (see `set_input`).
+            ///
+            /// This should return an $nn_errno (TODO define) if the inputs are not all defined.
+            pub fn compute(context: i32) -> i32;
+        }
+    }
+}
+
+pub use raw::*;
+
+/// An execution graph for performing inference (i.e. a model).
+#[repr(transparent)]
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Graph(raw::Graph);
+impl Graph {
+    /// Take ownership of a raw handle.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be valid and not owned by anything else.
+    pub unsafe fn from_raw(raw: raw::Graph) -> Self {
+        Graph(raw)
+    }
+    /// The raw handle, which stays owned by `self`.
+    pub fn as_raw(&self) -> raw::Graph {
+        self.0
+    }
+    /// Give up ownership of the handle without releasing it.
+    pub fn into_raw(self) -> raw::Graph {
+        self.0
+    }
+}
+
+/// A $graph_execution_context allows for attaching inputs prior to calling `compute` on a graph and retrieving outputs after
+/// the computation has completed. TODO a handle may not be the right type but we want it to be opaque to users.
+#[repr(transparent)]
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct GraphExecutionContext(raw::GraphExecutionContext);
+impl GraphExecutionContext {
+    /// Take ownership of a raw handle.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be valid and not owned by anything else.
+    pub unsafe fn from_raw(raw: raw::GraphExecutionContext) -> Self {
+        GraphExecutionContext(raw)
+    }
+    /// The raw handle, which stays owned by `self`.
+    pub fn as_raw(&self) -> raw::GraphExecutionContext {
+        self.0
+    }
+    /// Give up ownership of the handle without releasing it.
+    pub fn into_raw(self) -> raw::GraphExecutionContext {
+        self.0
+    }
+}
+
+/// Error codes returned by functions in this API. This is prefixed to avoid conflicts with the `$errno` in
+/// `typenames.witx`.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum NnErrno {
+    /// No error occurred.
+    Success,
+    /// Caller module passed an invalid argument.
+    InvalidArgument,
+    /// Caller module is missing a memory export.
+    MissingMemory,
+    /// Device or resource busy.
+    Busy,
+    /// An error code these bindings don't know.
+    Unknown(u16),
+}
+impl From<raw::NnErrno> for NnErrno {
+    fn from(e: raw::NnErrno) -> Self {
+        match e.raw() {
+            0 => NnErrno::Success,
+            1 => NnErrno::InvalidArgument,
+            2 => NnErrno::MissingMemory,
+            3 => NnErrno::Busy,
+            other => NnErrno::Unknown(other),
+        }
+    }
+}
+impl From<NnErrno> for raw::NnErrno {
+    fn from(e: NnErrno) -> Self {
+        match e {
+            NnErrno::Success => raw::NnErrno(0),
+            NnErrno::InvalidArgument => raw::NnErrno(1),
+            NnErrno::MissingMemory => raw::NnErrno(2),
+            NnErrno::Busy => raw::NnErrno(3),
+            NnErrno::Unknown(other) => raw::NnErrno(other),
+        }
+    }
+}
+impl core::fmt::Display for NnErrno {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            NnErrno::Success => f.write_str("No error occurred"),
+            NnErrno::InvalidArgument => f.write_str("Caller module passed an invalid argument"),
+            NnErrno::MissingMemory => f.write_str("Caller module is missing a memory export"),
+            NnErrno::Busy => f.write_str("Device or resource busy"),
+            NnErrno::Unknown(code) => write!(f, "unknown error {}", code),
+        }
+    }
+}
+impl std::error::Error for NnErrno {}
+
+pub mod wasi_ephemeral_nn {
+    #[allow(unused_imports)]
+    use super::*;
+
+    /// Load an opaque sequence of bytes to use for inference.
+    ///
+    /// This allows runtime implementations to support multiple graph encoding formats. For unsupported graph encodings,
+    /// return `errno::inval`.
+    pub fn load(builder: GraphBuilderArray<'_>, encoding: GraphEncoding, target: ExecutionTarget) -> Result<Graph, NnErrno> {
+        unsafe {
+            let mut rp0 = core::mem::MaybeUninit::<raw::Graph>::uninit();
+            let ret = raw::wasi_ephemeral_nn::load(builder.as_ptr() as i32, builder.len() as i32, encoding.0 as i32, target.0 as i32, rp0.as_mut_ptr() as i32);
+            match ret {
+                0 => Ok(Graph::from_raw(rp0.assume_init())),
+                _ => Err(NnErrno::from(raw::NnErrno(ret as u16))),
+            }
+        }
+    }
+
+    /// TODO Functions like `describe_graph_inputs` and `describe_graph_outputs` (returning
+    /// an array of `$tensor_description`s) might be useful for introspecting the graph but are not yet included here.
+    /// Create an execution instance of a loaded graph.
+    /// TODO this may need to accept flags that might affect the compilation or execution of the graph.
+    pub fn init_execution_context(graph: &Graph) -> Result<GraphExecutionContext, NnErrno> {
+        unsafe {
+            let mut rp0 = core::mem::MaybeUninit::<raw::GraphExecutionContext>::uninit();
+            let ret = raw::wasi_ephemeral_nn::init_execution_context(graph.as_raw() as i32, rp0.as_mut_ptr() as i32);
+            match ret {
+                0 => Ok(GraphExecutionContext::from_raw(rp0.assume_init())),
+                _ => Err(NnErrno::from(raw::NnErrno(ret as u16))),
+            }
+        }
+    }
+
+    /// Define the inputs to use for inference.
+    ///
+    /// This should return an $nn_errno (TODO define) if the input tensor does not match the expected dimensions and type.
+    pub fn set_input(context: &GraphExecutionContext, index: u32, tensor: Tensor<'_>) -> Result<(), NnErrno> {
+        unsafe {
+            let ret = raw::wasi_ephemeral_nn::set_input(context.as_raw() as i32, index as i32, &tensor as *const _ as i32);
+            match ret {
+                0 => Ok(()),
+                _ => Err(NnErrno::from(raw::NnErrno(ret as u16))),
+            }
+        }
+    }
+
+    /// Extract the outputs after inference.
+    ///
+    /// This should return an $nn_errno (TODO define) if the inference has not yet run.
+    /// # Safety
+    ///
+    /// The host reads and writes memory through the pointers passed, so
+    /// they must be valid for it.
+    pub unsafe fn get_output(context: &GraphExecutionContext, index: u32, out_buffer: *mut u8, out_buffer_max_size: BufferSize) -> Result<BufferSize, NnErrno> {
+        let mut rp0 = core::mem::MaybeUninit::<raw::BufferSize>::uninit();
+        let ret = raw::wasi_ephemeral_nn::get_output(context.as_raw() as i32, index as i32, out_buffer as i32, out_buffer_max_size as i32, rp0.as_mut_ptr() as i32);
+        match ret {
+            0 => Ok(rp0.assume_init()),
+            _ => Err(NnErrno::from(raw::NnErrno(ret as u16))),
+        }
+    }
+
+    /// Compute the inference on the given inputs (see `set_input`).
+    ///
+    /// This should return an $nn_errno (TODO define) if the inputs are not all defined.
+    pub fn compute(context: &GraphExecutionContext) -> Result<(), NnErrno> {
+        unsafe {
+            let ret = raw::wasi_ephemeral_nn::compute(context.as_raw() as i32);
+            match ret {
+                0 => Ok(()),
+                _ => Err(NnErrno::from(raw::NnErrno(ret as u16))),
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+/// A type laid out in guest memory as witx describes it, which needn't be
+/// how Swift lays it out.
+protocol WitxLayout {
+    static var witxSize: Int { get }
+    static var witxAlignment: Int { get }
+    /// The value at `pointer`, which is aligned to `witxAlignment`.
+    static func load(from pointer: UnsafeRawPointer) -> Self
+    /// Write the value to the `witxSize` bytes at `pointer`.
+    func store(to pointer: UnsafeMutableRawPointer)
+}
+
+/// The size of a graph buffer. This is equivalent to `$size` in `typenames.witx` but renamed since `typenames.witx` is
+/// not included here but is included in the overall ephemeral phase.
+typealias BufferSize = UInt32
+
+/// Error codes returned by functions in this API. This is prefixed to avoid conflicts with the `$errno` in
+/// `typenames.witx`.
+struct NnErrno: RawRepresentable, Hashable {
+    var rawValue: UInt16
+
+    init(rawValue: UInt16) {
+        self.rawValue = rawValue
+    }
+
+    /// No error occurred.
+    static let success = NnErrno(rawValue: 0)
+    /// Caller module passed an invalid argument.
+    static let invalidArgument = NnErrno(rawValue: 1)
+    /// Caller module is missing a memory export.
+    static let missingMemory = NnErrno(rawValue: 2)
+    /// Device or resource busy.
+    static let busy = NnErrno(rawValue: 3)
+}
+
+extension NnErrno: WitxLayout {
+    static let witxSize = 2
+    static let witxAlignment = 2
+
+    static func load(from pointer: UnsafeRawPointer) -> NnErrno {
+        NnErrno(rawValue: pointer.load(as: UInt16.self))
+    }
+
+    func store(to pointer: UnsafeMutableRawPointer) {
+        pointer.storeBytes(of: rawValue, as: UInt16.self)
+    }
+}
+
+/// The dimensions of a tensor.
+///
+/// The array length matches the tensor rank and each element in the array
+/// describes the size of each dimension.
+typealias TensorDimensions = (UnsafeRawPointer?, UInt)
+
+/// The type of the elements in a tensor.
+struct TensorType: RawRepresentable, Hashable {
+    var rawValue: UInt8
+
+    init(rawValue: UInt8) {
+        self.rawValue = rawValue
+    }
+
+    static let f16 = TensorType(rawValue: 0)
+    static let f32 = TensorType(rawValue: 1)
+    static let u8 = TensorType(rawValue: 2)
+    static let i32 = TensorType(rawValue: 3)
+}
+
+extension TensorType: WitxLayout {
+    static let witxSize = 1
+    static let witxAlignment = 1
+
+    static func load(from pointer: UnsafeRawPointer) -> TensorType {
+        TensorType(rawValue: pointer.load(as: UInt8.self))
+    }
+
+    func store(to pointer: UnsafeMutableRawPointer) {
+        pointer.storeBytes(of: rawValue, as: UInt8.self)
+    }
+}
+
+/// The tensor data
+///
+/// Initially conceived as a sparse representation, each empty cell would be filled with zeroes and
+/// the array length must match the product of all of the dimensions and the number of bytes in the type (e.g. a 2x2
+/// tensor with 4-byte f32 elements would have a data array of length 16). Naturally, this representation requires
+/// some knowledge of how to lay out data in memory--e.g. using row-major ordering--and could perhaps be improved
+/// by future witx features (TODO).
+typealias TensorData = (UnsafeRawPointer?, UInt)
+
+/// A tensor.
+struct Tensor {
+    /// Describe the size of the tensor (e.g. 2x2x2x2 -> [2, 2, 2, 2]). To represent a tensor containing a single value,
+    /// use `[1]` for the tensor dimensions.
+    var dimensions: TensorDimensions
+    var type: TensorType
+    /// Contains the tensor data.
+    var data: TensorData
+}
+
+extension Tensor: WitxLayout {
+    static let witxSize = 20
+    static let witxAlignment = 4
+
+    static func load(from pointer: UnsafeRawPointer) -> Tensor {
+        Tensor(dimensions: (pointer.load(as: UnsafeRawPointer?.self), pointer.load(fromByteOffset: 4, as: UInt.self)), type: TensorType.load(from: pointer + 8), data: (pointer.load(fromByteOffset: 12, as: UnsafeRawPointer?.self), pointer.load(fromByteOffset: 16, as: UInt.self)))
+    }
+
+    func store(to pointer: UnsafeMutableRawPointer) {
+        pointer.storeBytes(of: self.dimensions.0, as: UnsafeRawPointer?.self)
+        pointer.storeBytes(of: self.dimensions.1, toByteOffset: 4, as: UInt.self)
+        self.type.store(to: pointer + 8)
+        pointer.storeBytes(of: self.data.0, toByteOffset: 12, as: UnsafeRawPointer?.self)
+        pointer.storeBytes(of: self.data.1, toByteOffset: 16, as: UInt.self)
+    }
+}
+
+/// The graph initialization data. This consists of an array of buffers because implementing backends may encode their
+/// graph IR in parts (e.g. OpenVINO stores its IR and weights separately).
+typealias GraphBuilder = (UnsafeRawPointer?, UInt)
+
+typealias GraphBuilderArray = (UnsafeRawPointer?, UInt)
+
+/// An execution graph for performing inference (i.e. a model).
+struct Graph: RawRepresentable, Hashable {
+    var rawValue: UInt32
+
+    init(rawValue: UInt32) {
+        self.rawValue = rawValue
+    }
+}
+
+extension Graph: WitxLayout {
+    static let witxSize = 4
+    static let witxAlignment = 4
+
+    static func load(from pointer: UnsafeRawPointer) -> Graph {
+        Graph(rawValue: pointer.load(as: UInt32.self))
+    }
+
+    func store(to pointer: UnsafeMutableRawPointer) {
+        pointer.storeBytes(of: rawValue, as: UInt32.self)
+    }
+}
+
+/// Describes the encoding of the graph. This allows the API to be implemented by various backends that encode (i.e.
+/// serialize) their graph IR differently.
+struct GraphEncoding: RawRepresentable, Hashable {
+    var rawValue: UInt8
+
+    init(rawValue: UInt8) {
+        self.rawValue = rawValue
+    }
+
+    /// TODO document buffer order
+    static let openvino = GraphEncoding(rawValue: 0)
+    static let tensorflow = GraphEncoding(rawValue: 1)
+}
+
+extension GraphEncoding: WitxLayout {
+    static let witxSize = 1
+    static let witxAlignment = 1
+
+    static func load(from pointer: UnsafeRawPointer) -> GraphEncoding {
+        GraphEncoding(rawValue: pointer.load(as: UInt8.self))
+    }
+
+    func store(to pointer: UnsafeMutableRawPointer) {
+        pointer.storeBytes(of: rawValue, as: UInt8.self)
+    }
+}
+
+/// Define where the graph should be executed.
+struct ExecutionTarget: RawRepresentable, Hashable {
+    var rawValue: UInt8
+
+    init(rawValue: UInt8) {
+        self.rawValue = rawValue
+    }
+
+    static let cpu = ExecutionTarget(rawValue: 0)
+    static let gpu = ExecutionTarget(rawValue: 1)
+    static let tpu = ExecutionTarget(rawValue: 2)
+}
+
+extension ExecutionTarget: WitxLayout {
+    static let witxSize = 1
+    static let witxAlignment = 1
+
+    static func load(from pointer: UnsafeRawPointer) -> ExecutionTarget {
+        ExecutionTarget(rawValue: pointer.load(as: UInt8.self))
+    }
+
+    func store(to pointer: UnsafeMutableRawPointer) {
+        pointer.storeBytes(of: rawValue, as: UInt8.self)
+    }
+}
+
+/// A $graph_execution_context allows for attaching inputs prior to calling `compute` on a graph and retrieving outputs after
+/// the computation has completed. TODO a handle may not be the right type but we want it to be opaque to users.
+struct GraphExecutionContext: RawRepresentable, Hashable {
+    var rawValue: UInt32
+
+    init(rawValue: UInt32) {
+        self.rawValue = rawValue
+    }
+}
+
+extension GraphExecutionContext: WitxLayout {
+    static let witxSize = 4
+    static let witxAlignment = 4
+
+    static func load(from pointer: UnsafeRawPointer) -> GraphExecutionContext {
+        GraphExecutionContext(rawValue: pointer.load(as: UInt32.self))
+    }
+
+    func store(to pointer: UnsafeMutableRawPointer) {
+        pointer.storeBytes(of: rawValue, as: UInt32.self)
+    }
+}
+
+/// Load an opaque sequence of bytes to use for inference.
+///
+/// This allows runtime implementations to support multiple graph encoding formats. For unsupported graph encodings,
+/// return `errno::inval`.
+@_extern(wasm, module: "wasi_ephemeral_nn", name: "load")
+func wasiEphemeralNnLoad(_ builder: UnsafeRawPointer?, _ builderLen: UInt, _ encoding: UInt8, _ target: UInt8, _ retptr0: UnsafeMutableRawPointer) -> UInt16
+
+/// TODO Functions like `describe_graph_inputs` and `describe_graph_outputs` (returning
+/// an array of `$tensor_description`s) might be useful for introspecting the graph but are not yet included here.
+/// Create an execution instance of a loaded graph.
+/// TODO this may need to accept flags that might affect the compilation or execution of the graph.
+@_extern(wasm, module: "wasi_ephemeral_nn", name: "init_execution_context")
+func wasiEphemeralNnInitExecutionContext(_ graph: UInt32, _ retptr0: UnsafeMutableRawPointer) -> UInt16
+
+/// Define the inputs to use for inference.
+///
+/// This should return an $nn_errno (TODO define) if the input tensor does not match the expected dimensions and type.
+@_extern(wasm, module: "wasi_ephemeral_nn", name: "set_input")
+func wasiEphemeralNnSetInput(_ context: UInt32, _ index: UInt32, _ tensor: UnsafeRawPointer) -> UInt16
+
+/// Extract the outputs after inference.
+///
+/// This should return an $nn_errno (TODO define) if the inference has not yet run.
+@_extern(wasm, module: "wasi_ephemeral_nn", name: "get_output")
+func wasiEphemeralNnGetOutput(_ context: UInt32, _ index: UInt32, _ outBuffer: UnsafeMutableRawPointer?, _ outBufferMaxSize: UInt32, _ retptr0: UnsafeMutableRawPointer) -> UInt16
+
+/// Compute the inference on the given inputs (see `set_input`).
+///
+/// This should return an $nn_errno (TODO define) if the inputs are not all defined.
+@_extern(wasm, module: "wasi_ephemeral_nn", name: "compute")
+func wasiEphemeralNnCompute(_ context: UInt32) -> UInt16
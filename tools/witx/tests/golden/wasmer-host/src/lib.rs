@@ -0,0 +1,1033 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+use std::fmt;
+use std::marker::PhantomData;
+use wasmer::{
+    AsStoreMut, Exports, Function, FunctionEnv, FunctionEnvMut, Imports, Memory,
+    RuntimeError,
+};
+
+/// An invalid access to guest memory, which traps the calling instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuestError {
+    /// `len` bytes at `offset` are out of bounds of the guest memory.
+    OutOfBounds { offset: u32, len: u32 },
+    /// `value` is not a case of the enum `ty`.
+    InvalidEnumValue { ty: &'static str, value: u64 },
+    /// `value` is not a unicode scalar value.
+    InvalidChar { value: u32 },
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// Lists can't be written to guest memory, since that requires
+    /// allocating in the guest.
+    ListWrite,
+    /// The list `name` has `len` elements, but its declared size is
+    /// `expected`, or overflows.
+    SizeMismatch { name: &'static str, len: u64, expected: Option<u64> },
+    /// The pointer `name` is null.
+    NullPointer { name: &'static str },
+}
+
+impl fmt::Display for GuestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuestError::OutOfBounds { offset, len } => {
+                write!(f, "{} bytes at offset {} are out of bounds", len, offset)
+            }
+            GuestError::InvalidEnumValue { ty, value } => {
+                write!(f, "{} is not a valid `{}`", value, ty)
+            }
+            GuestError::InvalidChar { value } => write!(f, "{:#x} is not a valid char", value),
+            GuestError::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            GuestError::ListWrite => write!(f, "lists can't be written to guest memory"),
+            GuestError::SizeMismatch { name, len, expected: Some(expected) } => {
+                write!(f, "`{}` has {} elements, expected {}", name, len, expected)
+            }
+            GuestError::SizeMismatch { name, expected: None, .. } => {
+                write!(f, "the size of `{}` overflows", name)
+            }
+            GuestError::NullPointer { name } => write!(f, "`{}` is a null pointer", name),
+        }
+    }
+}
+
+impl std::error::Error for GuestError {}
+
+impl GuestError {
+    /// Whether this is an invalid argument, rather than an access to guest
+    /// memory which can't be made.
+    pub fn is_invalid_argument(&self) -> bool {
+        !matches!(self, GuestError::OutOfBounds { .. } | GuestError::ListWrite)
+    }
+}
+
+/// What functions do with arguments which aren't valid values of their
+/// declared types. Accesses out of bounds of guest memory always trap.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum InvalidArguments {
+    /// Trap the calling instance.
+    #[default]
+    Trap,
+    /// Return the `invalid_argument` or `inval` error of functions whose
+    /// error enum has one, and trap in others.
+    Error,
+}
+
+/// Check that the pointer `name` isn't null.
+#[allow(dead_code)]
+fn check_pointer<T>(name: &'static str, ptr: &GuestPtr<T>) -> Result<(), GuestError> {
+    if ptr.offset() == 0 {
+        Err(GuestError::NullPointer { name })
+    } else {
+        Ok(())
+    }
+}
+
+/// A pointer to a `T` in guest memory.
+pub struct GuestPtr<T> {
+    offset: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> GuestPtr<T> {
+    pub fn new(offset: u32) -> Self {
+        GuestPtr {
+            offset,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+}
+
+impl<T: GuestType> GuestPtr<T> {
+    /// A pointer to the `n`th `T` after this one.
+    pub fn add(&self, n: u32) -> Result<Self, GuestError> {
+        n.checked_mul(T::SIZE)
+            .and_then(|bytes| self.offset.checked_add(bytes))
+            .map(GuestPtr::new)
+            .ok_or(GuestError::OutOfBounds {
+                offset: self.offset,
+                len: n.saturating_mul(T::SIZE),
+            })
+    }
+}
+
+impl<T> Clone for GuestPtr<T> {
+    fn clone(&self) -> Self {
+        GuestPtr::new(self.offset)
+    }
+}
+
+impl<T> Copy for GuestPtr<T> {}
+
+impl<T> PartialEq for GuestPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+
+impl<T> fmt::Debug for GuestPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GuestPtr({:#x})", self.offset)
+    }
+}
+
+/// The linear memory of the calling instance.
+pub struct GuestMemory<'a> {
+    bytes: &'a mut [u8],
+}
+
+impl<'a> GuestMemory<'a> {
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        GuestMemory { bytes }
+    }
+
+    pub fn bytes(&self, offset: u32, len: u32) -> Result<&[u8], GuestError> {
+        let start = offset as usize;
+        start
+            .checked_add(len as usize)
+            .and_then(|end| self.bytes.get(start..end))
+            .ok_or(GuestError::OutOfBounds { offset, len })
+    }
+
+    pub fn bytes_mut(&mut self, offset: u32, len: u32) -> Result<&mut [u8], GuestError> {
+        let start = offset as usize;
+        start
+            .checked_add(len as usize)
+            .and_then(move |end| self.bytes.get_mut(start..end))
+            .ok_or(GuestError::OutOfBounds { offset, len })
+    }
+
+    pub fn read<T: GuestType>(&self, ptr: GuestPtr<T>) -> Result<T, GuestError> {
+        T::read(self, ptr.offset())
+    }
+
+    pub fn write<T: GuestType>(&mut self, ptr: GuestPtr<T>, value: &T) -> Result<(), GuestError> {
+        value.write(self, ptr.offset())
+    }
+
+    /// Read the `len` elements of a list starting at `offset`.
+    pub fn read_list<T: GuestType>(&self, offset: u32, len: u32) -> Result<Vec<T>, GuestError> {
+        let base = GuestPtr::<T>::new(offset);
+        (0..len).map(|i| self.read(base.add(i)?)).collect()
+    }
+
+    /// Read the `len` bytes of a string starting at `offset`.
+    pub fn read_string(&self, offset: u32, len: u32) -> Result<String, GuestError> {
+        let bytes = self.bytes(offset, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| GuestError::InvalidUtf8)
+    }
+}
+
+/// Types which can be read from and written to guest memory.
+pub trait GuestType: Sized {
+    const SIZE: u32;
+    const ALIGN: u32;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError>;
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError>;
+}
+
+macro_rules! primitive {
+    ($($ty:ident)*) => {$(
+        impl GuestType for $ty {
+            const SIZE: u32 = std::mem::size_of::<$ty>() as u32;
+            const ALIGN: u32 = std::mem::size_of::<$ty>() as u32;
+            fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+                let mut buf = [0; std::mem::size_of::<$ty>()];
+                buf.copy_from_slice(mem.bytes(offset, Self::SIZE)?);
+                Ok($ty::from_le_bytes(buf))
+            }
+            fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+                mem.bytes_mut(offset, Self::SIZE)?.copy_from_slice(&self.to_le_bytes());
+                Ok(())
+            }
+        }
+    )*};
+}
+
+primitive!(u8 u16 u32 u64 i8 i16 i32 i64 f32 f64);
+
+impl GuestType for char {
+    const SIZE: u32 = 4;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        let value = u32::read(mem, offset)?;
+        std::char::from_u32(value).ok_or(GuestError::InvalidChar { value })
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        (*self as u32).write(mem, offset)
+    }
+}
+
+impl<T> GuestType for GuestPtr<T> {
+    const SIZE: u32 = 4;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        Ok(GuestPtr::new(u32::read(mem, offset)?))
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        self.offset.write(mem, offset)
+    }
+}
+
+impl<T: GuestType> GuestType for Vec<T> {
+    const SIZE: u32 = 8;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        let ptr = u32::read(mem, offset)?;
+        let len = u32::read(mem, field(offset, 4)?)?;
+        mem.read_list(ptr, len)
+    }
+    fn write(&self, _mem: &mut GuestMemory<'_>, _offset: u32) -> Result<(), GuestError> {
+        Err(GuestError::ListWrite)
+    }
+}
+
+impl GuestType for String {
+    const SIZE: u32 = 8;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        let ptr = u32::read(mem, offset)?;
+        let len = u32::read(mem, field(offset, 4)?)?;
+        mem.read_string(ptr, len)
+    }
+    fn write(&self, _mem: &mut GuestMemory<'_>, _offset: u32) -> Result<(), GuestError> {
+        Err(GuestError::ListWrite)
+    }
+}
+
+/// The offset of a field `field_offset` bytes into a value at `offset`.
+fn field(offset: u32, field_offset: u32) -> Result<u32, GuestError> {
+    offset.checked_add(field_offset).ok_or(GuestError::OutOfBounds {
+        offset,
+        len: field_offset,
+    })
+}
+
+/// The environment of the host functions of an instance: the value
+/// implementing them, and the memory of the instance, set once it's
+/// instantiated.
+pub struct HostEnv<T> {
+    pub data: T,
+    memory: Option<Memory>,
+}
+
+impl<T> HostEnv<T> {
+    pub fn new(data: T) -> Self {
+        HostEnv { data, memory: None }
+    }
+
+    /// Set the memory guests pass values in, the `memory` export of the
+    /// instance, before it calls any host function.
+    pub fn set_memory(&mut self, memory: Memory) {
+        self.memory = Some(memory);
+    }
+}
+
+impl From<GuestError> for RuntimeError {
+    fn from(e: GuestError) -> Self {
+        RuntimeError::user(Box::new(e))
+    }
+}
+
+fn env_memory<T>(env: &HostEnv<T>) -> Result<Memory, RuntimeError> {
+    env.memory
+        .clone()
+        .ok_or_else(|| RuntimeError::new("missing required memory export"))
+}
+
+/// Check that the list `name` has `len` elements, its size `expected`.
+fn check_size(name: &'static str, len: usize, expected: Option<u64>) -> Result<(), GuestError> {
+    let len = len as u64;
+    if expected == Some(len) {
+        Ok(())
+    } else {
+        Err(GuestError::SizeMismatch { name, len, expected })
+    }
+}
+
+// Only the operations used by the document's sizes are called.
+#[allow(dead_code)]
+fn size_of_value<T>(value: T) -> Option<u64>
+where
+    u64: std::convert::TryFrom<T>,
+{
+    std::convert::TryFrom::try_from(value).ok()
+}
+
+#[allow(dead_code)]
+fn size_product<T: Copy>(values: &[T]) -> Option<u64>
+where
+    u64: std::convert::TryFrom<T>,
+{
+    size_mul(&values.iter().map(|v| size_of_value(*v)).collect::<Vec<_>>())
+}
+
+#[allow(dead_code)]
+fn size_mul(sizes: &[Option<u64>]) -> Option<u64> {
+    sizes.iter().try_fold(1u64, |n, s| n.checked_mul((*s)?))
+}
+
+#[allow(dead_code)]
+fn size_add(sizes: &[Option<u64>]) -> Option<u64> {
+    sizes.iter().try_fold(0u64, |n, s| n.checked_add((*s)?))
+}
+
+/// The size of a graph buffer. This is equivalent to `$size` in `typenames.witx` but renamed since `typenames.witx` is
+/// not included here but is included in the overall ephemeral phase.
+pub type BufferSize = u32;
+
+/// Error codes returned by functions in this API. This is prefixed to avoid conflicts with the `$errno` in
+/// `typenames.witx`.
+#[repr(u16)]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum NnErrno {
+    /// No error occurred.
+    Success,
+    /// Caller module passed an invalid argument.
+    InvalidArgument,
+    /// Caller module is missing a memory export.
+    MissingMemory,
+    /// Device or resource busy.
+    Busy,
+}
+impl NnErrno {
+    /// Every value of the enum, in order.
+    pub const ALL: [NnErrno; 4] = [
+        NnErrno::Success,
+        NnErrno::InvalidArgument,
+        NnErrno::MissingMemory,
+        NnErrno::Busy,
+    ];
+    pub fn raw(&self) -> u16 {
+        *self as u16
+    }
+    /// The witx name of the value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NnErrno::Success => "success",
+            NnErrno::InvalidArgument => "invalid_argument",
+            NnErrno::MissingMemory => "missing_memory",
+            NnErrno::Busy => "busy",
+        }
+    }
+    /// The value named `name` in witx.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "success" => Some(NnErrno::Success),
+            "invalid_argument" => Some(NnErrno::InvalidArgument),
+            "missing_memory" => Some(NnErrno::MissingMemory),
+            "busy" => Some(NnErrno::Busy),
+            _ => None,
+        }
+    }
+    pub fn from_raw(raw: u16) -> Result<Self, GuestError> {
+        match raw {
+            0 => Ok(NnErrno::Success),
+            1 => Ok(NnErrno::InvalidArgument),
+            2 => Ok(NnErrno::MissingMemory),
+            3 => Ok(NnErrno::Busy),
+            _ => Err(GuestError::InvalidEnumValue { ty: "nn_errno", value: raw as u64 }),
+        }
+    }
+}
+impl GuestType for NnErrno {
+    const SIZE: u32 = 2;
+    const ALIGN: u32 = 2;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        Self::from_raw(u16::read(mem, offset)?)
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        self.raw().write(mem, offset)
+    }
+}
+impl std::fmt::Display for NnErrno {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            NnErrno::Success => "No error occurred",
+            NnErrno::InvalidArgument => "Caller module passed an invalid argument",
+            NnErrno::MissingMemory => "Caller module is missing a memory export",
+            NnErrno::Busy => "Device or resource busy",
+        })
+    }
+}
+impl std::error::Error for NnErrno {}
+
+/// The dimensions of a tensor.
+///
+/// The array length matches the tensor rank and each element in the array
+/// describes the size of each dimension.
+pub type TensorDimensions = Vec<u32>;
+
+/// The type of the elements in a tensor.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum TensorType {
+    F16,
+    F32,
+    U8,
+    I32,
+}
+impl TensorType {
+    /// Every value of the enum, in order.
+    pub const ALL: [TensorType; 4] = [
+        TensorType::F16,
+        TensorType::F32,
+        TensorType::U8,
+        TensorType::I32,
+    ];
+    pub fn raw(&self) -> u8 {
+        *self as u8
+    }
+    /// The witx name of the value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TensorType::F16 => "f16",
+            TensorType::F32 => "f32",
+            TensorType::U8 => "u8",
+            TensorType::I32 => "i32",
+        }
+    }
+    /// The value named `name` in witx.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "f16" => Some(TensorType::F16),
+            "f32" => Some(TensorType::F32),
+            "u8" => Some(TensorType::U8),
+            "i32" => Some(TensorType::I32),
+            _ => None,
+        }
+    }
+    pub fn from_raw(raw: u8) -> Result<Self, GuestError> {
+        match raw {
+            0 => Ok(TensorType::F16),
+            1 => Ok(TensorType::F32),
+            2 => Ok(TensorType::U8),
+            3 => Ok(TensorType::I32),
+            _ => Err(GuestError::InvalidEnumValue { ty: "tensor_type", value: raw as u64 }),
+        }
+    }
+}
+impl GuestType for TensorType {
+    const SIZE: u32 = 1;
+    const ALIGN: u32 = 1;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        Self::from_raw(u8::read(mem, offset)?)
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        self.raw().write(mem, offset)
+    }
+}
+impl TensorType {
+    /// Size of an element in bits, or `None` if the type isn't known.
+    pub fn element_bits(&self) -> Option<u32> {
+        match self.raw() {
+            0 => Some(16),
+            1 => Some(32),
+            2 => Some(8),
+            3 => Some(32),
+            _ => None,
+        }
+    }
+    /// Bytes of data in a tensor of this type with `dimensions`, or `None`
+    /// if the type isn't known or the size overflows. Packed elements are
+    /// padded to a whole byte.
+    pub fn buffer_size(&self, dimensions: &[u32]) -> Option<usize> {
+        let mut bits = self.element_bits()? as usize;
+        for d in dimensions {
+            bits = bits.checked_mul(*d as usize)?;
+        }
+        Some(bits / 8 + (bits % 8 != 0) as usize)
+    }
+    /// Whether `len` bytes is the size of the data of a tensor of this type
+    /// with `dimensions`.
+    pub fn is_valid_buffer(&self, dimensions: &[u32], len: usize) -> bool {
+        self.buffer_size(dimensions) == Some(len)
+    }
+}
+/// Conversions of tensor data between the element types of `TensorType`.
+/// Data is little-endian, as in tensors. Functions converting data write it
+/// to `out`, and return the number of bytes written, or `None` if `data`
+/// isn't a whole number of elements or `out` is too small for them.
+pub mod tensor_convert {
+    fn map<const N: usize, const M: usize>(
+        data: &[u8],
+        out: &mut [u8],
+        f: impl Fn([u8; N]) -> [u8; M],
+    ) -> Option<usize> {
+        let len = data.len() / N * M;
+        if data.len() % N != 0 || out.len() < len {
+            return None;
+        }
+        for (from, to) in data.chunks_exact(N).zip(out.chunks_exact_mut(M)) {
+            let mut bytes = [0; N];
+            bytes.copy_from_slice(from);
+            to.copy_from_slice(&f(bytes));
+        }
+        Some(len)
+    }
+
+    /// The bits of the `f16` nearest to `value`. Ties round to even, values
+    /// too large for an `f16` become infinities, and NaNs stay NaNs.
+    pub fn f32_to_f16(value: f32) -> u16 {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exp = ((bits >> 23) & 0xff) as i32;
+        let man = bits & 0x7f_ffff;
+        if exp == 0xff {
+            let nan = if man != 0 { 0x200 | (man >> 13) as u16 } else { 0 };
+            return sign | 0x7c00 | nan;
+        }
+        let exp = exp - 127 + 15;
+        if exp >= 0x1f {
+            return sign | 0x7c00;
+        }
+        // Subnormals keep fewer bits of the mantissa, with its implicit one.
+        let (man, shift, exp) = if exp <= 0 {
+            if exp < -10 {
+                return sign;
+            }
+            (man | 0x80_0000, (14 - exp) as u32, 0)
+        } else {
+            (man, 13, exp as u32)
+        };
+        let kept = (exp << 10) | (man >> shift);
+        let dropped = man & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        // Rounding up may carry into the exponent, up to infinity.
+        let up = dropped > half || (dropped == half && kept & 1 == 1);
+        sign | (kept + up as u32) as u16
+    }
+
+    /// The value of the `f16` with `bits`, which is exact.
+    pub fn f16_to_f32(bits: u16) -> f32 {
+        let sign = u32::from(bits >> 15) << 31;
+        let exp = u32::from((bits >> 10) & 0x1f);
+        let man = u32::from(bits & 0x3ff);
+        let bits = match (exp, man) {
+            (0, 0) => sign,
+            // Subnormal: normalize the mantissa.
+            (0, _) => {
+                let shift = man.leading_zeros() - 21;
+                sign | ((113 - shift) << 23) | (((man << shift) & 0x3ff) << 13)
+            }
+            (0x1f, _) => sign | 0x7f80_0000 | (man << 13),
+            _ => sign | ((exp + 112) << 23) | (man << 13),
+        };
+        f32::from_bits(bits)
+    }
+
+    /// `f32` data as `f16` data.
+    pub fn f32_to_f16_data(data: &[u8], out: &mut [u8]) -> Option<usize> {
+        map(data, out, |b: [u8; 4]| f32_to_f16(f32::from_le_bytes(b)).to_le_bytes())
+    }
+
+    /// `f16` data as `f32` data.
+    pub fn f16_to_f32_data(data: &[u8], out: &mut [u8]) -> Option<usize> {
+        map(data, out, |b: [u8; 2]| f16_to_f32(u16::from_le_bytes(b)).to_le_bytes())
+    }
+
+    /// `value` quantized to a `u8` with `scale` and `zero_point`: the nearest
+    /// integer to `value / scale`, plus `zero_point`, saturated. Ties round
+    /// away from zero, and NaNs become `zero_point`.
+    pub fn quantize_u8(value: f32, scale: f32, zero_point: u8) -> u8 {
+        let x = value / scale;
+        // `as` truncates and saturates, and the fraction left is exact.
+        let trunc = x as i32;
+        let frac = x - trunc as f32;
+        let rounded = if frac >= 0.5 {
+            trunc.saturating_add(1)
+        } else if frac <= -0.5 {
+            trunc.saturating_sub(1)
+        } else {
+            trunc
+        };
+        rounded.saturating_add(i32::from(zero_point)).clamp(0, 255) as u8
+    }
+
+    /// The value `q` was quantized from with `scale` and `zero_point`.
+    pub fn dequantize_u8(q: u8, scale: f32, zero_point: u8) -> f32 {
+        (i32::from(q) - i32::from(zero_point)) as f32 * scale
+    }
+
+    /// `f32` data quantized to `u8` data, as by `quantize_u8`.
+    pub fn quantize_u8_data(data: &[u8], scale: f32, zero_point: u8, out: &mut [u8]) -> Option<usize> {
+        map(data, out, |b: [u8; 4]| [quantize_u8(f32::from_le_bytes(b), scale, zero_point)])
+    }
+
+    /// `u8` data dequantized to `f32` data, as by `dequantize_u8`.
+    pub fn dequantize_u8_data(data: &[u8], scale: f32, zero_point: u8, out: &mut [u8]) -> Option<usize> {
+        map(data, out, |b: [u8; 1]| dequantize_u8(b[0], scale, zero_point).to_le_bytes())
+    }
+}
+
+/// The tensor data
+///
+/// Initially conceived as a sparse representation, each empty cell would be filled with zeroes and
+/// the array length must match the product of all of the dimensions and the number of bytes in the type (e.g. a 2x2
+/// tensor with 4-byte f32 elements would have a data array of length 16). Naturally, this representation requires
+/// some knowledge of how to lay out data in memory--e.g. using row-major ordering--and could perhaps be improved
+/// by future witx features (TODO).
+pub type TensorData = Vec<u8>;
+
+/// A tensor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tensor {
+    /// Describe the size of the tensor (e.g. 2x2x2x2 -> [2, 2, 2, 2]). To represent a tensor containing a single value,
+    /// use `[1]` for the tensor dimensions.
+    pub dimensions: TensorDimensions,
+    pub r#type: TensorType,
+    /// Contains the tensor data.
+    pub data: TensorData,
+}
+impl GuestType for Tensor {
+    const SIZE: u32 = 20;
+    const ALIGN: u32 = 4;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        let value = Self {
+            dimensions: <TensorDimensions>::read(mem, field(offset, 0)?)?,
+            r#type: <TensorType>::read(mem, field(offset, 8)?)?,
+            data: <TensorData>::read(mem, field(offset, 12)?)?,
+        };
+        check_size("tensor.data", value.data.len(), value.r#type.buffer_size(&value.dimensions).map(|n| n as u64))?;
+        Ok(value)
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        self.dimensions.write(mem, field(offset, 0)?)?;
+        self.r#type.write(mem, field(offset, 8)?)?;
+        self.data.write(mem, field(offset, 12)?)?;
+        Ok(())
+    }
+}
+
+/// The graph initialization data. This consists of an array of buffers because implementing backends may encode their
+/// graph IR in parts (e.g. OpenVINO stores its IR and weights separately).
+pub type GraphBuilder = Vec<u8>;
+
+pub type GraphBuilderArray = Vec<GraphBuilder>;
+
+/// An execution graph for performing inference (i.e. a model).
+pub type Graph = u32;
+
+/// Describes the encoding of the graph. This allows the API to be implemented by various backends that encode (i.e.
+/// serialize) their graph IR differently.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum GraphEncoding {
+    /// TODO document buffer order
+    Openvino,
+    Tensorflow,
+}
+impl GraphEncoding {
+    /// Every value of the enum, in order.
+    pub const ALL: [GraphEncoding; 2] = [
+        GraphEncoding::Openvino,
+        GraphEncoding::Tensorflow,
+    ];
+    pub fn raw(&self) -> u8 {
+        *self as u8
+    }
+    /// The witx name of the value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GraphEncoding::Openvino => "openvino",
+            GraphEncoding::Tensorflow => "tensorflow",
+        }
+    }
+    /// The value named `name` in witx.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "openvino" => Some(GraphEncoding::Openvino),
+            "tensorflow" => Some(GraphEncoding::Tensorflow),
+            _ => None,
+        }
+    }
+    pub fn from_raw(raw: u8) -> Result<Self, GuestError> {
+        match raw {
+            0 => Ok(GraphEncoding::Openvino),
+            1 => Ok(GraphEncoding::Tensorflow),
+            _ => Err(GuestError::InvalidEnumValue { ty: "graph_encoding", value: raw as u64 }),
+        }
+    }
+}
+impl GuestType for GraphEncoding {
+    const SIZE: u32 = 1;
+    const ALIGN: u32 = 1;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        Self::from_raw(u8::read(mem, offset)?)
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        self.raw().write(mem, offset)
+    }
+}
+
+/// Define where the graph should be executed.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ExecutionTarget {
+    Cpu,
+    Gpu,
+    Tpu,
+}
+impl ExecutionTarget {
+    /// Every value of the enum, in order.
+    pub const ALL: [ExecutionTarget; 3] = [
+        ExecutionTarget::Cpu,
+        ExecutionTarget::Gpu,
+        ExecutionTarget::Tpu,
+    ];
+    pub fn raw(&self) -> u8 {
+        *self as u8
+    }
+    /// The witx name of the value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionTarget::Cpu => "cpu",
+            ExecutionTarget::Gpu => "gpu",
+            ExecutionTarget::Tpu => "tpu",
+        }
+    }
+    /// The value named `name` in witx.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "cpu" => Some(ExecutionTarget::Cpu),
+            "gpu" => Some(ExecutionTarget::Gpu),
+            "tpu" => Some(ExecutionTarget::Tpu),
+            _ => None,
+        }
+    }
+    pub fn from_raw(raw: u8) -> Result<Self, GuestError> {
+        match raw {
+            0 => Ok(ExecutionTarget::Cpu),
+            1 => Ok(ExecutionTarget::Gpu),
+            2 => Ok(ExecutionTarget::Tpu),
+            _ => Err(GuestError::InvalidEnumValue { ty: "execution_target", value: raw as u64 }),
+        }
+    }
+}
+impl GuestType for ExecutionTarget {
+    const SIZE: u32 = 1;
+    const ALIGN: u32 = 1;
+    fn read(mem: &GuestMemory<'_>, offset: u32) -> Result<Self, GuestError> {
+        Self::from_raw(u8::read(mem, offset)?)
+    }
+    fn write(&self, mem: &mut GuestMemory<'_>, offset: u32) -> Result<(), GuestError> {
+        self.raw().write(mem, offset)
+    }
+}
+
+/// A $graph_execution_context allows for attaching inputs prior to calling `compute` on a graph and retrieving outputs after
+/// the computation has completed. TODO a handle may not be the right type but we want it to be opaque to users.
+pub type GraphExecutionContext = u32;
+
+pub mod wasi_ephemeral_nn {
+    #[allow(unused_imports)]
+    use super::*;
+
+    pub trait WasiEphemeralNn {
+        /// Load an opaque sequence of bytes to use for inference.
+        ///
+        /// This allows runtime implementations to support multiple graph encoding formats. For unsupported graph encodings,
+        /// return `errno::inval`.
+        fn load(&mut self, mem: &mut GuestMemory<'_>, builder: GraphBuilderArray, encoding: GraphEncoding, target: ExecutionTarget) -> Result<Graph, NnErrno>;
+        /// TODO Functions like `describe_graph_inputs` and `describe_graph_outputs` (returning
+        /// an array of `$tensor_description`s) might be useful for introspecting the graph but are not yet included here.
+        /// Create an execution instance of a loaded graph.
+        /// TODO this may need to accept flags that might affect the compilation or execution of the graph.
+        fn init_execution_context(&mut self, mem: &mut GuestMemory<'_>, graph: Graph) -> Result<GraphExecutionContext, NnErrno>;
+        /// Define the inputs to use for inference.
+        ///
+        /// This should return an $nn_errno (TODO define) if the input tensor does not match the expected dimensions and type.
+        fn set_input(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext, index: u32, tensor: Tensor) -> Result<(), NnErrno>;
+        /// Extract the outputs after inference.
+        ///
+        /// This should return an $nn_errno (TODO define) if the inference has not yet run.
+        fn get_output(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext, index: u32, out_buffer: GuestPtr<u8>, out_buffer_max_size: BufferSize) -> Result<BufferSize, NnErrno>;
+        /// Compute the inference on the given inputs (see `set_input`).
+        ///
+        /// This should return an $nn_errno (TODO define) if the inputs are not all defined.
+        fn compute(&mut self, mem: &mut GuestMemory<'_>, context: GraphExecutionContext) -> Result<(), NnErrno>;
+    }
+
+    /// Add the functions of `wasi_ephemeral_nn` to `imports`, implemented by the
+    /// value that `get` returns from the data of `env`. Invalid arguments
+    /// trap.
+    pub fn add_to_imports<T: Send + 'static, U>(
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<HostEnv<T>>,
+        imports: &mut Imports,
+        get: impl Fn(&mut T) -> &mut U + Send + Sync + Copy + 'static,
+    ) where
+        U: WasiEphemeralNn,
+    {
+        add_to_imports_with(store, env, imports, get, InvalidArguments::Trap)
+    }
+
+    /// [`add_to_imports`], handling invalid arguments as `invalid` says.
+    pub fn add_to_imports_with<T: Send + 'static, U>(
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<HostEnv<T>>,
+        imports: &mut Imports,
+        get: impl Fn(&mut T) -> &mut U + Send + Sync + Copy + 'static,
+        invalid: InvalidArguments,
+    ) where
+        U: WasiEphemeralNn,
+    {
+        let mut exports = Exports::new();
+        exports.insert(
+            "load",
+            Function::new_typed_with_env(
+                store,
+                env,
+                move |mut ctx: FunctionEnvMut<HostEnv<T>>, arg0: i32, arg1: i32, arg2: i32, arg3: i32, arg4: i32| -> Result<i32, RuntimeError> {
+                    let (env, store) = ctx.data_and_store_mut();
+                    let memory = env_memory(env)?;
+                    let view = memory.view(&store);
+                    // SAFETY: the guest is suspended in this call, and nothing else
+                    // accesses its memory until the call returns.
+                    let mut mem = GuestMemory::new(unsafe { view.data_unchecked_mut() });
+                    let imp = get(&mut env.data);
+                    #[allow(clippy::redundant_closure_call)]
+                    let checked = (|| -> Result<_, GuestError> {
+                        let param0 = mem.read_list::<GraphBuilder>(arg0 as u32, arg1 as u32)?;
+                        let param1 = GraphEncoding::from_raw(<u8 as std::convert::TryFrom<_>>::try_from(arg2).map_err(|_| GuestError::InvalidEnumValue { ty: "graph_encoding", value: arg2 as u32 as u64 })?)?;
+                        let param2 = ExecutionTarget::from_raw(<u8 as std::convert::TryFrom<_>>::try_from(arg3).map_err(|_| GuestError::InvalidEnumValue { ty: "execution_target", value: arg3 as u32 as u64 })?)?;
+                        Ok((param0, param1, param2))
+                    })();
+                    let (param0, param1, param2) = match checked {
+                        Ok(params) => params,
+                        Err(e) if invalid == InvalidArguments::Error && e.is_invalid_argument() => {
+                            return Ok(NnErrno::InvalidArgument.raw() as i32)
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let ret3 = imp.load(&mut mem, param0, param1, param2);
+                    let result6 = match ret3 {
+                        Ok(payload4) => {
+                            payload4.write(&mut mem, arg4 as u32)?;
+                            0
+                        }
+                        Err(payload5) => payload5.raw() as i32,
+                    };
+                    Ok(result6)
+                },
+            ),
+        );
+        exports.insert(
+            "init_execution_context",
+            Function::new_typed_with_env(
+                store,
+                env,
+                move |mut ctx: FunctionEnvMut<HostEnv<T>>, arg0: i32, arg1: i32| -> Result<i32, RuntimeError> {
+                    let (env, store) = ctx.data_and_store_mut();
+                    let memory = env_memory(env)?;
+                    let view = memory.view(&store);
+                    // SAFETY: the guest is suspended in this call, and nothing else
+                    // accesses its memory until the call returns.
+                    let mut mem = GuestMemory::new(unsafe { view.data_unchecked_mut() });
+                    let imp = get(&mut env.data);
+                    #[allow(clippy::redundant_closure_call)]
+                    let checked = (|| -> Result<_, GuestError> {
+                        let param0 = arg0 as u32;
+                        Ok((param0,))
+                    })();
+                    let (param0,) = match checked {
+                        Ok(params) => params,
+                        Err(e) if invalid == InvalidArguments::Error && e.is_invalid_argument() => {
+                            return Ok(NnErrno::InvalidArgument.raw() as i32)
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let ret1 = imp.init_execution_context(&mut mem, param0);
+                    let result4 = match ret1 {
+                        Ok(payload2) => {
+                            payload2.write(&mut mem, arg1 as u32)?;
+                            0
+                        }
+                        Err(payload3) => payload3.raw() as i32,
+                    };
+                    Ok(result4)
+                },
+            ),
+        );
+        exports.insert(
+            "set_input",
+            Function::new_typed_with_env(
+                store,
+                env,
+                move |mut ctx: FunctionEnvMut<HostEnv<T>>, arg0: i32, arg1: i32, arg2: i32| -> Result<i32, RuntimeError> {
+                    let (env, store) = ctx.data_and_store_mut();
+                    let memory = env_memory(env)?;
+                    let view = memory.view(&store);
+                    // SAFETY: the guest is suspended in this call, and nothing else
+                    // accesses its memory until the call returns.
+                    let mut mem = GuestMemory::new(unsafe { view.data_unchecked_mut() });
+                    let imp = get(&mut env.data);
+                    #[allow(clippy::redundant_closure_call)]
+                    let checked = (|| -> Result<_, GuestError> {
+                        let param0 = arg0 as u32;
+                        let param1 = arg1 as u32;
+                        let param2 = <Tensor>::read(&mem, arg2 as u32)?;
+                        Ok((param0, param1, param2))
+                    })();
+                    let (param0, param1, param2) = match checked {
+                        Ok(params) => params,
+                        Err(e) if invalid == InvalidArguments::Error && e.is_invalid_argument() => {
+                            return Ok(NnErrno::InvalidArgument.raw() as i32)
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let ret3 = imp.set_input(&mut mem, param0, param1, param2);
+                    let result5 = match ret3 {
+                        Ok(()) => 0,
+                        Err(payload4) => payload4.raw() as i32,
+                    };
+                    Ok(result5)
+                },
+            ),
+        );
+        exports.insert(
+            "get_output",
+            Function::new_typed_with_env(
+                store,
+                env,
+                move |mut ctx: FunctionEnvMut<HostEnv<T>>, arg0: i32, arg1: i32, arg2: i32, arg3: i32, arg4: i32| -> Result<i32, RuntimeError> {
+                    let (env, store) = ctx.data_and_store_mut();
+                    let memory = env_memory(env)?;
+                    let view = memory.view(&store);
+                    // SAFETY: the guest is suspended in this call, and nothing else
+                    // accesses its memory until the call returns.
+                    let mut mem = GuestMemory::new(unsafe { view.data_unchecked_mut() });
+                    let imp = get(&mut env.data);
+                    #[allow(clippy::redundant_closure_call)]
+                    let checked = (|| -> Result<_, GuestError> {
+                        let param0 = arg0 as u32;
+                        let param1 = arg1 as u32;
+                        let param2 = GuestPtr::new(arg2 as u32);
+                        let param3 = arg3 as u32;
+                        check_pointer("get_output.out_buffer", &param2)?;
+                        Ok((param0, param1, param2, param3))
+                    })();
+                    let (param0, param1, param2, param3) = match checked {
+                        Ok(params) => params,
+                        Err(e) if invalid == InvalidArguments::Error && e.is_invalid_argument() => {
+                            return Ok(NnErrno::InvalidArgument.raw() as i32)
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let ret4 = imp.get_output(&mut mem, param0, param1, param2, param3);
+                    let result7 = match ret4 {
+                        Ok(payload5) => {
+                            payload5.write(&mut mem, arg4 as u32)?;
+                            0
+                        }
+                        Err(payload6) => payload6.raw() as i32,
+                    };
+                    Ok(result7)
+                },
+            ),
+        );
+        exports.insert(
+            "compute",
+            Function::new_typed_with_env(
+                store,
+                env,
+                move |mut ctx: FunctionEnvMut<HostEnv<T>>, arg0: i32| -> Result<i32, RuntimeError> {
+                    let (env, store) = ctx.data_and_store_mut();
+                    let memory = env_memory(env)?;
+                    let view = memory.view(&store);
+                    // SAFETY: the guest is suspended in this call, and nothing else
+                    // accesses its memory until the call returns.
+                    let mut mem = GuestMemory::new(unsafe { view.data_unchecked_mut() });
+                    let imp = get(&mut env.data);
+                    #[allow(clippy::redundant_closure_call)]
+                    let checked = (|| -> Result<_, GuestError> {
+                        let param0 = arg0 as u32;
+                        Ok((param0,))
+                    })();
+                    let (param0,) = match checked {
+                        Ok(params) => params,
+                        Err(e) if invalid == InvalidArguments::Error && e.is_invalid_argument() => {
+                            return Ok(NnErrno::InvalidArgument.raw() as i32)
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let ret1 = imp.compute(&mut mem, param0);
+                    let result3 = match ret1 {
+                        Ok(()) => 0,
+                        Err(payload2) => payload2.raw() as i32,
+                    };
+                    Ok(result3)
+                },
+            ),
+        );
+        imports.register_namespace("wasi_ephemeral_nn", exports);
+    }
+}
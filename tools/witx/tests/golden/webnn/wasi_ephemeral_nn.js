@@ -0,0 +1,436 @@
+// This file is automatically generated by witx, DO NOT EDIT!
+
+/** An error returned by a function, such as a case of an errno enum. */
+export class WitxError extends Error {
+    constructor(error) {
+        super(`witx error: ${error}`);
+        this.error = error;
+    }
+}
+
+/** The linear memory of a guest. Out of bounds accesses throw a `RangeError`. */
+export class GuestMemory {
+    constructor(buffer) {
+        this.view = new DataView(buffer);
+        this.bytes = new Uint8Array(buffer);
+    }
+
+    check(offset, len) {
+        if (offset + len > this.bytes.length) {
+            throw new RangeError(`${len} bytes at offset ${offset} are out of bounds`);
+        }
+    }
+
+    /** A copy of `len` bytes at `offset`. */
+    readBytes(offset, len) {
+        this.check(offset, len);
+        return this.bytes.slice(offset, offset + len);
+    }
+
+    readString(offset, len) {
+        return new TextDecoder("utf-8", { fatal: true }).decode(this.readBytes(offset, len));
+    }
+
+    /** `len` elements of `size` bytes at `offset`, each read by `read`. */
+    readList(read, size, offset, len) {
+        this.check(offset, size * len);
+        const list = [];
+        for (let i = 0; i < len; i++) {
+            list.push(read(this, offset + i * size));
+        }
+        return list;
+    }
+
+    writeBytes(offset, bytes) {
+        this.check(offset, bytes.length);
+        this.bytes.set(bytes, offset);
+    }
+}
+
+function liftCase(cases, ty, value) {
+    if (value >= cases.length) {
+        throw new RangeError(`${value} is not a valid \`${ty}\``);
+    }
+    return cases[value];
+}
+
+function lowerCase(cases, ty, value) {
+    const raw = cases.indexOf(value);
+    if (raw < 0) {
+        throw new RangeError(`${value} is not a valid \`${ty}\``);
+    }
+    return raw;
+}
+
+function listWrite() {
+    throw new RangeError("lists can't be written to guest memory");
+}
+
+const suspending = (f) =>
+    typeof WebAssembly.Suspending === "function" ? new WebAssembly.Suspending(f) : f;
+
+function read_buffer_size(mem, offset) {
+    return mem.view.getUint32(offset, true);
+}
+function write_buffer_size(mem, offset, value) {
+    mem.view.setUint32(offset, value, true);
+}
+
+const cases_nn_errno = ["success", "invalid_argument", "missing_memory", "busy"];
+function lift_nn_errno(value) {
+    return liftCase(cases_nn_errno, "nn_errno", value);
+}
+function lower_nn_errno(value) {
+    return lowerCase(cases_nn_errno, "nn_errno", value);
+}
+function read_nn_errno(mem, offset) {
+    return lift_nn_errno(mem.view.getUint16(offset, true));
+}
+function write_nn_errno(mem, offset, value) {
+    mem.view.setUint16(offset, lower_nn_errno(value), true);
+}
+
+function read_tensor_dimensions(mem, offset) {
+    return mem.readList((mem, offset) => mem.view.getUint32(offset, true), 4, mem.view.getUint32(offset, true), mem.view.getUint32(offset + 4, true));
+}
+function write_tensor_dimensions(mem, offset, value) {
+    listWrite();
+}
+
+const cases_tensor_type = ["f16", "f32", "u8", "i32"];
+function lift_tensor_type(value) {
+    return liftCase(cases_tensor_type, "tensor_type", value);
+}
+function lower_tensor_type(value) {
+    return lowerCase(cases_tensor_type, "tensor_type", value);
+}
+function read_tensor_type(mem, offset) {
+    return lift_tensor_type(mem.view.getUint8(offset));
+}
+function write_tensor_type(mem, offset, value) {
+    mem.view.setUint8(offset, lower_tensor_type(value));
+}
+
+function read_tensor_data(mem, offset) {
+    return mem.readBytes(mem.view.getUint32(offset, true), mem.view.getUint32(offset + 4, true));
+}
+function write_tensor_data(mem, offset, value) {
+    listWrite();
+}
+
+function read_tensor(mem, offset) {
+    return { dimensions: read_tensor_dimensions(mem, offset), type: read_tensor_type(mem, offset + 8), data: read_tensor_data(mem, offset + 12) };
+}
+function write_tensor(mem, offset, value) {
+    write_tensor_dimensions(mem, offset, value.dimensions);
+    write_tensor_type(mem, offset + 8, value.type);
+    write_tensor_data(mem, offset + 12, value.data);
+}
+
+function read_graph_builder(mem, offset) {
+    return mem.readBytes(mem.view.getUint32(offset, true), mem.view.getUint32(offset + 4, true));
+}
+function write_graph_builder(mem, offset, value) {
+    listWrite();
+}
+
+function read_graph_builder_array(mem, offset) {
+    return mem.readList((mem, offset) => read_graph_builder(mem, offset), 8, mem.view.getUint32(offset, true), mem.view.getUint32(offset + 4, true));
+}
+function write_graph_builder_array(mem, offset, value) {
+    listWrite();
+}
+
+function read_graph(mem, offset) {
+    return mem.view.getUint32(offset, true);
+}
+function write_graph(mem, offset, value) {
+    mem.view.setUint32(offset, value, true);
+}
+
+const cases_graph_encoding = ["openvino", "tensorflow"];
+function lift_graph_encoding(value) {
+    return liftCase(cases_graph_encoding, "graph_encoding", value);
+}
+function lower_graph_encoding(value) {
+    return lowerCase(cases_graph_encoding, "graph_encoding", value);
+}
+function read_graph_encoding(mem, offset) {
+    return lift_graph_encoding(mem.view.getUint8(offset));
+}
+function write_graph_encoding(mem, offset, value) {
+    mem.view.setUint8(offset, lower_graph_encoding(value));
+}
+
+const cases_execution_target = ["cpu", "gpu", "tpu"];
+function lift_execution_target(value) {
+    return liftCase(cases_execution_target, "execution_target", value);
+}
+function lower_execution_target(value) {
+    return lowerCase(cases_execution_target, "execution_target", value);
+}
+function read_execution_target(mem, offset) {
+    return lift_execution_target(mem.view.getUint8(offset));
+}
+function write_execution_target(mem, offset, value) {
+    mem.view.setUint8(offset, lower_execution_target(value));
+}
+
+function read_graph_execution_context(mem, offset) {
+    return mem.view.getUint32(offset, true);
+}
+function write_graph_execution_context(mem, offset, value) {
+    mem.view.setUint32(offset, value, true);
+}
+
+/**
+ * The import object for a guest, implemented by `backend`. `memory` is the
+ * guest's exported memory, or a function returning it, since it's only known
+ * once the guest is instantiated.
+ */
+export function imports(backend, memory) {
+    const buffer = () => (typeof memory === "function" ? memory() : memory).buffer;
+    return {
+        "wasi_ephemeral_nn": {
+            "load": suspending(async (arg0, arg1, arg2, arg3, arg4) => {
+                const mem = new GuestMemory(buffer());
+                const param0 = mem.readList((mem, offset) => read_graph_builder(mem, offset), 8, arg0 >>> 0, arg1 >>> 0);
+                const param1 = lift_graph_encoding(arg2);
+                const param2 = lift_execution_target(arg3);
+                let ret3;
+                try {
+                    ret3 = { tag: "ok", value: await backend.load(mem, param0, param1, param2) };
+                } catch (e) {
+                    if (!(e instanceof WitxError)) {
+                        throw e;
+                    }
+                    ret3 = { tag: "err", value: e.error };
+                }
+                let result6;
+                if (ret3.tag === "ok") {
+                    const payload4 = ret3.value;
+                    write_graph(mem, arg4 >>> 0, payload4);
+                    result6 = 0;
+                } else {
+                    const payload5 = ret3.value;
+                    result6 = lower_nn_errno(payload5);
+                }
+                return result6;
+            }),
+            "init_execution_context": suspending(async (arg0, arg1) => {
+                const mem = new GuestMemory(buffer());
+                const param0 = arg0 >>> 0;
+                let ret1;
+                try {
+                    ret1 = { tag: "ok", value: await backend.initExecutionContext(mem, param0) };
+                } catch (e) {
+                    if (!(e instanceof WitxError)) {
+                        throw e;
+                    }
+                    ret1 = { tag: "err", value: e.error };
+                }
+                let result4;
+                if (ret1.tag === "ok") {
+                    const payload2 = ret1.value;
+                    write_graph_execution_context(mem, arg1 >>> 0, payload2);
+                    result4 = 0;
+                } else {
+                    const payload3 = ret1.value;
+                    result4 = lower_nn_errno(payload3);
+                }
+                return result4;
+            }),
+            "set_input": suspending(async (arg0, arg1, arg2) => {
+                const mem = new GuestMemory(buffer());
+                const param0 = arg0 >>> 0;
+                const param1 = arg1 >>> 0;
+                const param2 = read_tensor(mem, arg2 >>> 0);
+                let ret3;
+                try {
+                    ret3 = { tag: "ok", value: await backend.setInput(mem, param0, param1, param2) };
+                } catch (e) {
+                    if (!(e instanceof WitxError)) {
+                        throw e;
+                    }
+                    ret3 = { tag: "err", value: e.error };
+                }
+                let result5;
+                if (ret3.tag === "ok") {
+                    result5 = 0;
+                } else {
+                    const payload4 = ret3.value;
+                    result5 = lower_nn_errno(payload4);
+                }
+                return result5;
+            }),
+            "get_output": suspending(async (arg0, arg1, arg2, arg3, arg4) => {
+                const mem = new GuestMemory(buffer());
+                const param0 = arg0 >>> 0;
+                const param1 = arg1 >>> 0;
+                const param2 = arg2 >>> 0;
+                const param3 = arg3 >>> 0;
+                let ret4;
+                try {
+                    ret4 = { tag: "ok", value: await backend.getOutput(mem, param0, param1, param2, param3) };
+                } catch (e) {
+                    if (!(e instanceof WitxError)) {
+                        throw e;
+                    }
+                    ret4 = { tag: "err", value: e.error };
+                }
+                let result7;
+                if (ret4.tag === "ok") {
+                    const payload5 = ret4.value;
+                    write_buffer_size(mem, arg4 >>> 0, payload5);
+                    result7 = 0;
+                } else {
+                    const payload6 = ret4.value;
+                    result7 = lower_nn_errno(payload6);
+                }
+                return result7;
+            }),
+            "compute": suspending(async (arg0) => {
+                const mem = new GuestMemory(buffer());
+                const param0 = arg0 >>> 0;
+                let ret1;
+                try {
+                    ret1 = { tag: "ok", value: await backend.compute(mem, param0) };
+                } catch (e) {
+                    if (!(e instanceof WitxError)) {
+                        throw e;
+                    }
+                    ret1 = { tag: "err", value: e.error };
+                }
+                let result3;
+                if (ret1.tag === "ok") {
+                    result3 = 0;
+                } else {
+                    const payload2 = ret1.value;
+                    result3 = lower_nn_errno(payload2);
+                }
+                return result3;
+            }),
+        },
+    };
+}
+
+const DEVICE_TYPES = { cpu: "cpu", gpu: "gpu", tpu: "npu" };
+const DATA_TYPES = { f16: "float16", f32: "float32", u8: "uint8", i32: "int32" };
+const ARRAY_TYPES = {
+    float16: typeof Float16Array === "function" ? Float16Array : Uint16Array,
+    float32: Float32Array,
+    uint8: Uint8Array,
+    int32: Int32Array,
+};
+
+/**
+ * Implements wasi-nn with the WebNN API.
+ *
+ * `loaders` maps graph encodings, such as `openvino`, to async functions
+ * `(builders, context)` building a graph from the graph builders passed to
+ * `load`, with an `MLGraphBuilder` for `context`. They return
+ * `{ graph, inputs, outputs }`, where `inputs` and `outputs` describe the
+ * graph's operands in the order wasi-nn indexes them, as
+ * `{ name, descriptor }` with an `MLOperandDescriptor`.
+ */
+export class WebNNBackend {
+    constructor(loaders, ml = globalThis.navigator && globalThis.navigator.ml) {
+        this.loaders = loaders;
+        this.ml = ml;
+        this.graphs = [];
+        this.contexts = [];
+    }
+
+    async load(mem, builders, encoding, target) {
+        const loader = this.loaders[encoding];
+        if (!loader || !this.ml) {
+            throw new WitxError("invalid_argument");
+        }
+        const context = await this.ml.createContext({ deviceType: DEVICE_TYPES[target] });
+        let model;
+        try {
+            model = await loader(builders, context);
+        } catch (e) {
+            throw new WitxError("invalid_argument");
+        }
+        this.graphs.push({ context, ...model });
+        return this.graphs.length - 1;
+    }
+
+    initExecutionContext(mem, graph) {
+        const model = this.graphs[graph];
+        if (!model) {
+            throw new WitxError("invalid_argument");
+        }
+        this.contexts.push({ model, inputs: new Array(model.inputs.length).fill(undefined), outputs: null });
+        return this.contexts.length - 1;
+    }
+
+    executionContext(context) {
+        const c = this.contexts[context];
+        if (!c) {
+            throw new WitxError("invalid_argument");
+        }
+        return c;
+    }
+
+    setInput(mem, context, index, tensor) {
+        const c = this.executionContext(context);
+        const input = c.model.inputs[index];
+        if (!input || input.descriptor.dataType !== DATA_TYPES[tensor.type]) {
+            throw new WitxError("invalid_argument");
+        }
+        c.inputs[index] = tensor.data;
+        c.outputs = null;
+    }
+
+    async compute(mem, context) {
+        const c = this.executionContext(context);
+        const { model, inputs } = c;
+        if (inputs.some((data) => data === undefined)) {
+            throw new WitxError("invalid_argument");
+        }
+        const ml = model.context;
+        if (typeof ml.dispatch === "function") {
+            const ins = {};
+            const outs = {};
+            for (const [i, input] of model.inputs.entries()) {
+                const tensor = await ml.createTensor({ ...input.descriptor, writable: true });
+                ml.writeTensor(tensor, inputs[i]);
+                ins[input.name] = tensor;
+            }
+            for (const output of model.outputs) {
+                outs[output.name] = await ml.createTensor({ ...output.descriptor, readable: true });
+            }
+            ml.dispatch(model.graph, ins, outs);
+            c.outputs = await Promise.all(model.outputs.map((o) => ml.readTensor(outs[o.name])));
+        } else {
+            const array = (descriptor, buffer) => {
+                const ArrayType = ARRAY_TYPES[descriptor.dataType];
+                if (buffer) {
+                    return new ArrayType(buffer.buffer, buffer.byteOffset, buffer.byteLength / ArrayType.BYTES_PER_ELEMENT);
+                }
+                return new ArrayType(descriptor.shape.reduce((n, d) => n * d, 1));
+            };
+            const ins = {};
+            const outs = {};
+            model.inputs.forEach((input, i) => (ins[input.name] = array(input.descriptor, inputs[i])));
+            model.outputs.forEach((output) => (outs[output.name] = array(output.descriptor)));
+            const result = await ml.compute(model.graph, ins, outs);
+            c.outputs = model.outputs.map((o) => result.outputs[o.name].buffer);
+        }
+    }
+
+    getOutput(mem, context, index, outBuffer, outBufferMaxSize) {
+        const c = this.executionContext(context);
+        if (!c.outputs || index >= c.outputs.length) {
+            throw new WitxError("invalid_argument");
+        }
+        const bytes = new Uint8Array(c.outputs[index]);
+        if (bytes.length > outBufferMaxSize) {
+            throw new WitxError("invalid_argument");
+        }
+        mem.writeBytes(outBuffer, bytes);
+        return bytes.length;
+    }
+}
@@ -9,8 +9,8 @@ fn validate_docs() {
         witx::phases::ephemeral().unwrap(),
         witx::phases::old::snapshot_0().unwrap(),
     ] {
-        let doc = witx::load(&phase).unwrap_or_else(|e| panic!("failed to parse: {:?}", e));
-        diff_against_filesystem(&doc.to_md(), &witx::phases::docs_path(&phase));
+        let doc = witx::load(phase).unwrap_or_else(|e| panic!("failed to parse: {:?}", e));
+        diff_against_filesystem(&doc.to_md(), &witx::phases::docs_path(phase));
     }
 }
 
@@ -30,7 +30,7 @@ fn diff_against_filesystem(expected: &str, path: &Path) {
         .unwrap_or_else(|e| panic!("couldn't read {}: {:?}", Path::display(path), e));
     // Git may checkout the file with dos line endings on windows. Strip all \r:
     let actual = dos2unix(&actual);
-    if &actual == expected {
+    if actual == expected {
         return;
     }
 
@@ -42,7 +42,7 @@ fn diff_against_filesystem(expected: &str, path: &Path) {
     let mut actual_line = 1;
     let mut separated = false;
     let mut any_lines = false;
-    for diff in diff::lines(&expected, &actual) {
+    for diff in diff::lines(expected, &actual) {
         match diff {
             diff::Result::Left(l) => {
                 eprintln!("line {}: -{}", expected_line, l);
@@ -160,7 +160,7 @@ impl WitxtRunner<'_> {
             WitxtDirective::AssertInvalid { witx, message, .. } => {
                 let err = match witx.document(contents, test) {
                     Ok(_) => bail!("witx was valid when it shouldn't be"),
-                    Err(e) => format!("{:?}", anyhow::Error::from(e)),
+                    Err(e) => format!("{:?}", e),
                 };
                 if !err.contains(message) {
                     bail!("expected error {:?}\nfound error {}", message, err);
@@ -183,7 +183,7 @@ impl WitxtRunner<'_> {
                 let t2 = t2d
                     .typename(&witx::Id::new(t2t))
                     .ok_or_else(|| anyhow!("no type named {:?}", t2t))?;
-                match (repr, t1.type_().representable(&t2.type_())) {
+                match (repr, t1.representable(&t2)) {
                     (RepEquality::Eq, witx::RepEquality::Eq)
                     | (RepEquality::Superset, witx::RepEquality::Superset)
                     | (RepEquality::NotEq, witx::RepEquality::NotEq) => {}
@@ -228,44 +228,8 @@ impl WitxtRunner<'_> {
 
     fn assert_roundtrip(&self, doc: &witx::Document) -> Result<()> {
         self.bump_ntests();
-        let back_to_sexprs = format!("{}", doc);
-        let doc2 = witx::parse(&back_to_sexprs)?;
-        if *doc == doc2 {
-            return Ok(());
-        }
-
-        // Try to get a more specific error message that isn't thousands of
-        // lines long of debug representations.
-        for type_ in doc.typenames() {
-            let type2 = match doc2.typename(&type_.name) {
-                Some(t) => t,
-                None => bail!("doc2 missing datatype"),
-            };
-            if type_ != type2 {
-                bail!("types are not equal\n{:?}\n   !=\n{:?}", type_, type2);
-            }
-        }
-        for mod_ in doc.modules() {
-            let mod2 = match doc2.module(&mod_.name) {
-                Some(m) => m,
-                None => bail!("doc2 missing module"),
-            };
-            for import in mod_.imports() {
-                let import2 = match mod2.import(&import.name) {
-                    Some(i) => i,
-                    None => bail!("mod2 missing import"),
-                };
-                assert_eq!(import, import2);
-            }
-            for func in mod_.funcs() {
-                let func2 = match mod2.func(&func.name) {
-                    Some(f) => f,
-                    None => bail!("mod2 missing func"),
-                };
-                assert_eq!(func, func2);
-            }
-        }
-        bail!("{:?} != {:?}", doc, doc2)
+        witx::roundtrip::check("the document", doc)?;
+        Ok(())
     }
 
     fn assert_md(&self, doc: &witx::Document) -> Result<()> {
@@ -559,7 +523,8 @@ struct Witx<'a> {
 }
 
 enum WitxDef<'a> {
-    Fs(Vec<&'a str>),
+    /// The files to load, and the modules to load them for, if not all.
+    Fs(Vec<&'a str>, Vec<&'a str>),
     Inline(Vec<witx::parser::Documented<'a, witx::parser::DeclSyntax<'a>>>),
 }
 
@@ -577,10 +542,13 @@ impl Witx<'_> {
                 }
                 Ok(validator.into_document(definitions))
             }
-            WitxDef::Fs(paths) => {
+            WitxDef::Fs(paths, modules) => {
                 let parent = file.parent().unwrap();
                 let paths = paths.iter().map(|p| parent.join(p)).collect::<Vec<_>>();
-                Ok(witx::load(&paths)?)
+                let options = modules
+                    .iter()
+                    .fold(witx::LoadOptions::new(), |o, m| o.module(m));
+                Ok(options.load(&paths)?)
             }
         }
     }
@@ -595,10 +563,17 @@ impl<'a> Parse<'a> for Witx<'a> {
             parser.parens(|p| {
                 p.parse::<kw::load>()?;
                 let mut paths = Vec::new();
-                while !p.is_empty() {
+                while !p.is_empty() && !p.peek::<wast::LParen>() {
                     paths.push(p.parse()?);
                 }
-                Ok(WitxDef::Fs(paths))
+                let mut modules = Vec::new();
+                while !p.is_empty() {
+                    modules.push(p.parens(|p| {
+                        p.parse::<wast::kw::module>()?;
+                        Ok(p.parse::<wast::Id>()?.name())
+                    })?);
+                }
+                Ok(WitxDef::Fs(paths, modules))
             })?
         } else {
             let mut decls = Vec::new();